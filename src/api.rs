@@ -6,7 +6,91 @@ pub mod characters;
 /// See sec 7.5
 pub mod structure;
 
+/// The basic object types of the PDF standard: booleans, numbers, strings,
+/// names, arrays, dictionaries, streams and the null object.
+/// See sec. 7.3
+pub mod objects;
+
+/// Stream filters, used to decode (decompress) the data of stream objects.
+/// See sec. 7.4
+pub mod filters;
+
+/// Decryption of strings and streams when a file is read, and encryption
+/// when it is saved, with the standard (password) security handler or the
+/// public-key security handler. See sec. 7.6
+pub mod encryption;
+
 /// Functionality to read a PDF file.
 pub mod read;
 
+/// Functionality to write a PDF file.
+pub mod write;
+
+/// Removing unreachable and duplicate objects, and renumbering the rest.
+pub mod optimize;
+
+/// Name trees and number trees. See sec. 7.9.6 and 7.9.7
+pub mod tree;
+
+/// A PDF file read into memory, with access to its catalog and page tree.
+/// See sec. 7.7
+pub mod document;
+
+/// The document information dictionary and XMP metadata. See sec. 14.3
+pub mod metadata;
+
+/// The document outline (bookmarks) and destinations. See sec. 12.3
+pub mod outline;
+
+/// The name dictionary of the catalog: named destinations, embedded files
+/// and document-level JavaScript. See sec. 7.7.4
+pub mod names;
+
+/// Page labels. See sec. 12.4.2
+pub mod labels;
+
+/// Page objects. See sec. 7.7.3.3
+pub mod page;
+
+/// Annotations: reading, creating, changing and flattening them into the
+/// page content, and the targets of links. See sec. 12.5 and 12.6
+pub mod annotation;
+
+/// Editing the pages of documents: extracting, merging, reordering, rotating
+/// and deleting pages. See sec. 7.7.3
+pub mod edit;
+
+/// Transformation matrices and paths. See sec. 8.3 and 8.5
+pub mod geometry;
+
+/// Color spaces and color values. See sec. 8.6
+pub mod color;
+
+/// Functions, mapping input values to output values. See sec. 7.10
+pub mod function;
+
+/// Tiling and shading patterns, and shadings. See sec. 8.7
+pub mod pattern;
+
+/// Fonts, their encodings and glyph widths. See sec. 9
+pub mod font;
+
+/// Content streams: the operators that describe the appearance of a page.
+/// See sec. 7.8 and 8.2
+pub mod content;
+
+/// Image XObjects and inline images, and their extraction to image files.
+/// See sec. 8.9
+pub mod image;
+
+/// Rendering of pages to RGBA bitmaps. See sec. 8 and 10
+pub mod render;
+
+/// Creating new documents: pages with text, lines, rectangles and images.
+pub mod builder;
+
+/// Preflight checks of documents meant for print production, such as RGB
+/// content in CMYK files.
+pub mod preflight;
+
 pub mod utils;
@@ -0,0 +1,1673 @@
+use std::{collections::HashSet, io};
+
+use crate::api::{
+    content::builder::ContentBuilder,
+    document::Document,
+    edit::out_of_range,
+    filters::flate_encode,
+    font::{encoding::BaseEncoding, metrics::standard_width},
+    geometry::{Matrix, Point},
+    metadata::PdfDate,
+    names::EmbeddedFile,
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    outline::Destination,
+    page::Page,
+};
+
+/// The font of generated text appearances, named in their resources.
+const FONT: &str = "Helv";
+
+/// The distance of the control points of a Bézier curve approximating a
+/// quarter of a circle from its end points, as a fraction of the radius.
+const KAPPA: f64 = 0.552_284_75;
+
+///
+/// The type of an annotation, with the entries specific to it.
+/// See sec. 12.5.6, Table 171
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationKind {
+    /// A hypertext link, see `Page::links` for its target.
+    Link,
+    /// A sticky note, shown as an icon such as "Comment" or "Note".
+    Text {
+        icon: Option<String>,
+        open: bool,
+    },
+    /// Text shown directly on the page in Helvetica of the given size.
+    FreeText {
+        font_size: f64,
+    },
+    /// Text markup: each quadrilateral, as in /QuadPoints, covers a run of
+    /// the text that is marked up. See sec. 12.5.6.10
+    Highlight {
+        quad_points: Vec<[f64; 8]>,
+    },
+    Underline {
+        quad_points: Vec<[f64; 8]>,
+    },
+    StrikeOut {
+        quad_points: Vec<[f64; 8]>,
+    },
+    /// A rectangle or ellipse inscribed in the annotation rectangle, filled
+    /// with the interior color if there is one.
+    Square {
+        interior_color: Option<Vec<f64>>,
+    },
+    Circle {
+        interior_color: Option<Vec<f64>>,
+    },
+    /// Freehand lines, each a list of points.
+    Ink {
+        paths: Vec<Vec<[f64; 2]>>,
+    },
+    /// A rubber stamp, such as "Approved" or "Draft".
+    Stamp {
+        icon: Option<String>,
+    },
+    /// The pop-up window showing the text of its parent annotation.
+    Popup {
+        parent: Option<ObjectId>,
+        open: bool,
+    },
+    /// A widget of an interactive form field, with the field's name.
+    Widget {
+        field_name: Option<String>,
+    },
+    /// A file attached to the page.
+    FileAttachment {
+        file: Option<EmbeddedFile>,
+        icon: Option<String>,
+    },
+    /// Any other type of annotation, by its /Subtype.
+    Other(String),
+}
+
+impl AnnotationKind {
+    pub fn name(&self) -> &str {
+        match self {
+            AnnotationKind::Link => "Link",
+            AnnotationKind::Text { .. } => "Text",
+            AnnotationKind::FreeText { .. } => "FreeText",
+            AnnotationKind::Highlight { .. } => "Highlight",
+            AnnotationKind::Underline { .. } => "Underline",
+            AnnotationKind::StrikeOut { .. } => "StrikeOut",
+            AnnotationKind::Square { .. } => "Square",
+            AnnotationKind::Circle { .. } => "Circle",
+            AnnotationKind::Ink { .. } => "Ink",
+            AnnotationKind::Stamp { .. } => "Stamp",
+            AnnotationKind::Popup { .. } => "Popup",
+            AnnotationKind::Widget { .. } => "Widget",
+            AnnotationKind::FileAttachment { .. } => "FileAttachment",
+            AnnotationKind::Other(subtype) => subtype,
+        }
+    }
+
+    /// Markup annotations are the ones that a reviewer adds as comments,
+    /// with an author and a subject. See sec. 12.5.6.2
+    pub fn is_markup(&self) -> bool {
+        !matches!(
+            self,
+            AnnotationKind::Link
+                | AnnotationKind::Popup { .. }
+                | AnnotationKind::Widget { .. }
+                | AnnotationKind::Other(_)
+        )
+    }
+}
+
+///
+/// "An annotation associates an object such as a note, link or rich media
+/// with a location on a page of a PDF document."
+/// See sec. 12.5 (pg. 465)
+///
+/// The dictionary holds the entries of the annotation as read, including
+/// those without a field of their own, and is written back with the fields
+/// replacing the entries they were read from.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub id: Option<ObjectId>,
+    pub kind: AnnotationKind,
+    /// The location of the annotation on the page, as [llx lly urx ury].
+    pub rect: [f64; 4],
+    pub contents: Option<String>,
+    /// The author of a markup annotation, from its /T entry.
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub modified: Option<PdfDate>,
+    /// The color of the annotation's border, icon or markup, as 1 (gray), 3
+    /// (RGB) or 4 (CMYK) components.
+    pub color: Option<Vec<f64>>,
+    pub border_width: Option<f64>,
+    /// The annotation flags, such as `Annotation::PRINT`.
+    /// See sec. 12.5.3, Table 167
+    pub flags: u32,
+    /// The annotation that this one is a reply to.
+    pub in_reply_to: Option<ObjectId>,
+    /// The normal appearance: a form XObject, or a dictionary of them for
+    /// each appearance state. See sec. 12.5.5
+    pub appearance: Option<PdfObject>,
+    pub dict: Dictionary,
+}
+
+impl Annotation {
+    pub const INVISIBLE: u32 = 1 << 0;
+    pub const HIDDEN: u32 = 1 << 1;
+    pub const PRINT: u32 = 1 << 2;
+    pub const NO_VIEW: u32 = 1 << 5;
+
+    /// A new annotation to be added with `Document::add_annotation`, which
+    /// is printed with the page and gets a generated appearance.
+    pub fn new(kind: AnnotationKind, rect: [f64; 4]) -> Annotation {
+        Annotation {
+            id: None,
+            kind,
+            rect,
+            contents: None,
+            author: None,
+            subject: None,
+            modified: None,
+            color: None,
+            border_width: None,
+            flags: Annotation::PRINT,
+            in_reply_to: None,
+            appearance: None,
+            dict: Dictionary::new(),
+        }
+    }
+
+    pub fn from_dict(doc: &Document, id: Option<ObjectId>, dict: &Dictionary) -> Annotation {
+        let get = |key: &str| doc.resolve_key(dict, key);
+        let text = |key: &str| get(key).and_then(PdfObject::as_text);
+        let numbers = |key: &str| get(key).and_then(PdfObject::as_numbers);
+        let name = |key: &str| get(key).and_then(PdfObject::as_name).map(str::to_string);
+        let open = get("Open").and_then(PdfObject::as_bool).unwrap_or(false);
+        let quad_points = || {
+            numbers("QuadPoints")
+                .unwrap_or_default()
+                .chunks_exact(8)
+                .filter_map(|quad| quad.try_into().ok())
+                .collect()
+        };
+        let interior_color = numbers("IC");
+
+        let subtype = get("Subtype").and_then(PdfObject::as_name).unwrap_or("");
+        let kind = match subtype {
+            "Link" => AnnotationKind::Link,
+            "Text" => AnnotationKind::Text {
+                icon: name("Name"),
+                open,
+            },
+            "FreeText" => AnnotationKind::FreeText {
+                font_size: text("DA").and_then(|da| font_size(&da)).unwrap_or(12.0),
+            },
+            "Highlight" => AnnotationKind::Highlight {
+                quad_points: quad_points(),
+            },
+            "Underline" => AnnotationKind::Underline {
+                quad_points: quad_points(),
+            },
+            "StrikeOut" => AnnotationKind::StrikeOut {
+                quad_points: quad_points(),
+            },
+            "Square" => AnnotationKind::Square { interior_color },
+            "Circle" => AnnotationKind::Circle { interior_color },
+            "Ink" => AnnotationKind::Ink {
+                paths: get("InkList")
+                    .and_then(PdfObject::as_array)
+                    .map(|paths| {
+                        paths
+                            .iter()
+                            .filter_map(|path| doc.resolve(path).as_numbers())
+                            .map(|path| path.chunks_exact(2).map(|p| [p[0], p[1]]).collect())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            },
+            "Stamp" => AnnotationKind::Stamp { icon: name("Name") },
+            "Popup" => AnnotationKind::Popup {
+                parent: dict.get("Parent").and_then(PdfObject::as_reference),
+                open,
+            },
+            "Widget" => AnnotationKind::Widget {
+                field_name: text("T"),
+            },
+            "FileAttachment" => AnnotationKind::FileAttachment {
+                file: get("FS")
+                    .and_then(PdfObject::as_dict)
+                    .and_then(|spec| doc.file_spec(spec, String::new()))
+                    .map(attached_file),
+                icon: name("Name"),
+            },
+            other => AnnotationKind::Other(other.to_string()),
+        };
+
+        let rect = match numbers("Rect").as_deref() {
+            Some(&[x1, y1, x2, y2]) => [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)],
+            _ => [0.0; 4],
+        };
+        let border_width = doc
+            .resolve_dict(dict, "BS")
+            .and_then(|style| style.get_f64("W"))
+            .or_else(|| numbers("Border").and_then(|border| border.get(2).copied()));
+        let markup = kind.is_markup();
+        Annotation {
+            id,
+            rect,
+            contents: text("Contents"),
+            author: if markup { text("T") } else { None },
+            subject: if markup { text("Subj") } else { None },
+            modified: get("M")
+                .and_then(PdfObject::as_bytes)
+                .and_then(PdfDate::parse),
+            color: numbers("C"),
+            border_width,
+            flags: get("F").and_then(PdfObject::as_i64).unwrap_or(0) as u32,
+            in_reply_to: dict.get("IRT").and_then(PdfObject::as_reference),
+            appearance: doc
+                .resolve_dict(dict, "AP")
+                .and_then(|ap| ap.get("N"))
+                .cloned(),
+            dict: dict.clone(),
+            kind,
+        }
+    }
+
+    /// The form XObject of the normal appearance, in the annotation's
+    /// appearance state (/AS) if it has several.
+    pub fn appearance_stream<'a>(&'a self, doc: &'a Document) -> Option<&'a Stream> {
+        match doc.resolve(self.appearance.as_ref()?) {
+            PdfObject::Stream(stream) => Some(stream),
+            PdfObject::Dictionary(states) => {
+                let state = self.dict.get_name("AS")?;
+                doc.resolve_key(states, state)?.as_stream()
+            }
+            _ => None,
+        }
+    }
+
+    fn is_visible(&self) -> bool {
+        self.flags & (Annotation::HIDDEN | Annotation::NO_VIEW) == 0
+    }
+}
+
+///
+/// Where a link leads: a view of a page of the document, or the target of
+/// one of the actions that links commonly perform. See sec. 12.6.4
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    /// A page of this document, with named destinations resolved.
+    Page(Destination),
+    /// A URI, made absolute with the document's base URI if it has one.
+    Uri(String),
+    /// A page of another PDF file, given by its index if the link names
+    /// one, or by the name of a destination in that file.
+    Remote {
+        file: String,
+        page: Option<usize>,
+        destination: Option<String>,
+    },
+    /// An application or document to open.
+    Launch(String),
+    /// A named action such as "NextPage" or "Print".
+    Named(String),
+    /// Any other action, by its type, such as "JavaScript".
+    Other(String),
+}
+
+///
+/// A link annotation: an area of a page and its target, which is None if
+/// the link has no target or points to a destination or page that does
+/// not exist. See sec. 12.5.6.5
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub id: Option<ObjectId>,
+    pub rect: [f64; 4],
+    pub target: Option<LinkTarget>,
+}
+
+impl Page {
+    /// The annotations of the page, in the order of its /Annots array.
+    pub fn annotations(&self, doc: &Document) -> Vec<Annotation> {
+        let annots = doc
+            .resolve_key(&self.dict, "Annots")
+            .and_then(PdfObject::as_array);
+        annots
+            .into_iter()
+            .flatten()
+            .filter_map(|annot| {
+                let dict = doc.resolve(annot).as_dict()?;
+                Some(Annotation::from_dict(doc, annot.as_reference(), dict))
+            })
+            .collect()
+    }
+
+    /// The link annotations of the page, with their targets resolved.
+    pub fn links(&self, doc: &Document) -> Vec<Link> {
+        let pages: Vec<Option<ObjectId>> = doc.pages().iter().map(|page| page.id).collect();
+        self.annotations(doc)
+            .into_iter()
+            .filter(|annotation| annotation.kind == AnnotationKind::Link)
+            .map(|annotation| Link {
+                id: annotation.id,
+                rect: annotation.rect,
+                target: doc.link_target(&annotation.dict, &pages),
+            })
+            .collect()
+    }
+}
+
+impl Document {
+    /// Adds an annotation to a page, generating its appearance if it has
+    /// none, and returns the new annotation object.
+    pub fn add_annotation(
+        &mut self,
+        page: usize,
+        annotation: &Annotation,
+    ) -> Result<ObjectId, io::Error> {
+        let page_id = self.page_id(page)?;
+        let mut dict = self.annotation_dict(annotation);
+        dict.set("P", PdfObject::Reference(page_id));
+        let id = self.add_object(PdfObject::Dictionary(dict));
+        let mut annots = self.page_annots(page_id);
+        annots.push(PdfObject::Reference(id));
+        self.set_page_annots(page_id, annots);
+        Ok(id)
+    }
+
+    /// Writes the fields of an annotation read from the document back to its
+    /// object. An annotation without an appearance gets a new one generated,
+    /// so setting `appearance` to None after changing the annotation keeps
+    /// the appearance up to date.
+    pub fn update_annotation(&mut self, annotation: &Annotation) -> Result<(), io::Error> {
+        let id = annotation.id.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "annotation is not an object of the document",
+            )
+        })?;
+        let dict = self.annotation_dict(annotation);
+        self.pdf
+            .body
+            .objects
+            .insert(id, PdfObject::Dictionary(dict));
+        Ok(())
+    }
+
+    /// Removes an annotation from a page, together with its pop-up.
+    pub fn remove_annotation(&mut self, page: usize, id: ObjectId) -> Result<(), io::Error> {
+        let page_id = self.page_id(page)?;
+        let annots = self
+            .page_annots(page_id)
+            .into_iter()
+            .filter(|annot| match annot.as_reference() {
+                Some(annot) => annot != id && self.popup_parent(annot) != Some(id),
+                None => true,
+            })
+            .collect();
+        self.set_page_annots(page_id, annots);
+        Ok(())
+    }
+
+    /// Draws the visible annotations of a page that have an appearance into
+    /// its content, and removes them and their pop-ups from the page. Widgets
+    /// that are flattened are also removed from the fields of the interactive
+    /// form. Returns the number of annotations flattened. See sec. 12.5.5
+    pub fn flatten_annotations(&mut self, page: usize) -> Result<usize, io::Error> {
+        let page_id = self.page_id(page)?;
+        let page = self.pages().swap_remove(page);
+        let mut resources = page.resources(self);
+        let mut xobjects = self
+            .resolve_dict(&resources, "XObject")
+            .cloned()
+            .unwrap_or_default();
+
+        let mut content = ContentBuilder::new();
+        let mut flattened = vec![];
+        let mut widgets = vec![];
+        for annotation in page.annotations(self) {
+            let Some(id) = annotation.id.filter(|_| annotation.is_visible()) else {
+                continue;
+            };
+            let Some(stream) = annotation.appearance_stream(self) else {
+                continue;
+            };
+            let Some(matrix) = appearance_matrix(stream, annotation.rect) else {
+                continue;
+            };
+            let stream = stream.clone();
+            let name = (xobjects.len()..)
+                .map(|n| format!("Annot{}", n))
+                .find(|name| !xobjects.contains_key(name))
+                .unwrap_or_default();
+            let mut form = stream.dict.clone();
+            form.set("Type", PdfObject::Name("XObject".to_string()));
+            form.set("Subtype", PdfObject::Name("Form".to_string()));
+            let form = self.add_object(PdfObject::Stream(Stream::new(form, stream.data)));
+            xobjects.set(&name, PdfObject::Reference(form));
+            content
+                .save_state()
+                .transform(matrix)
+                .draw_xobject(&name)
+                .restore_state();
+            flattened.push(id);
+            if matches!(annotation.kind, AnnotationKind::Widget { .. }) {
+                widgets.push(id);
+            }
+        }
+        if flattened.is_empty() {
+            return Ok(0);
+        }
+
+        // The page's own content is wrapped in q and Q so that any changes it
+        // leaves to the graphics state do not apply to the annotations.
+        let stream = |doc: &mut Document, data: &[u8]| {
+            let mut dict = Dictionary::new();
+            dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+            let data = flate_encode(data);
+            PdfObject::Reference(doc.add_object(PdfObject::Stream(Stream::new(dict, data))))
+        };
+        let mut contents = vec![stream(self, b"q")];
+        match page.dict.get("Contents").map(|c| (c, self.resolve(c))) {
+            Some((_, PdfObject::Array(items))) => contents.extend(items.iter().cloned()),
+            Some((item, _)) => contents.push(item.clone()),
+            None => {}
+        }
+        let mut end = b"Q\n".to_vec();
+        end.extend(content.to_bytes());
+        contents.push(stream(self, &end));
+        resources.set("XObject", PdfObject::Dictionary(xobjects));
+
+        let annots = self
+            .page_annots(page_id)
+            .into_iter()
+            .filter(|annot| match annot.as_reference() {
+                Some(annot) => {
+                    !flattened.contains(&annot)
+                        && !self
+                            .popup_parent(annot)
+                            .is_some_and(|parent| flattened.contains(&parent))
+                }
+                None => true,
+            })
+            .collect();
+        self.set_page_annots(page_id, annots);
+        if let Some(PdfObject::Dictionary(dict)) = self.get_mut(page_id) {
+            dict.set("Contents", PdfObject::Array(contents));
+            dict.set("Resources", PdfObject::Dictionary(resources));
+        }
+        if !widgets.is_empty() {
+            self.remove_form_widgets(&widgets);
+        }
+        Ok(flattened.len())
+    }
+
+    /// Removes widgets from the /Fields of the interactive form and from the
+    /// /Kids of their fields, along with the fields left without widgets.
+    /// See sec. 12.7.3
+    fn remove_form_widgets(&mut self, widgets: &[ObjectId]) {
+        let Some(form) = self
+            .catalog()
+            .and_then(|catalog| catalog.get("AcroForm"))
+            .cloned()
+        else {
+            return;
+        };
+        let Some(fields) = self
+            .resolve(&form)
+            .as_dict()
+            .and_then(|form| self.resolve_key(form, "Fields"))
+            .and_then(PdfObject::as_array)
+            .cloned()
+        else {
+            return;
+        };
+        let fields = self.remove_widget_fields(fields, widgets, &mut HashSet::new());
+        let form = match form.as_reference() {
+            Some(id) => self.get_mut(id),
+            None => self
+                .catalog_id()
+                .and_then(|id| self.get_mut(id))
+                .and_then(|catalog| match catalog {
+                    PdfObject::Dictionary(catalog) => catalog.get_mut("AcroForm"),
+                    _ => None,
+                }),
+        };
+        if let Some(PdfObject::Dictionary(form)) = form {
+            form.set("Fields", PdfObject::Array(fields));
+        }
+    }
+
+    /// The fields of `fields` that are not among the widgets and that still
+    /// have widgets once the widgets are removed from their descendants.
+    fn remove_widget_fields(
+        &mut self,
+        fields: Vec<PdfObject>,
+        widgets: &[ObjectId],
+        visited: &mut HashSet<ObjectId>,
+    ) -> Vec<PdfObject> {
+        let mut kept = vec![];
+        for field in fields {
+            let Some(id) = field.as_reference() else {
+                kept.push(field);
+                continue;
+            };
+            if widgets.contains(&id) {
+                continue;
+            }
+            let kids = self
+                .get(id)
+                .and_then(PdfObject::as_dict)
+                .and_then(|field| self.resolve_key(field, "Kids"))
+                .and_then(PdfObject::as_array)
+                .filter(|kids| !kids.is_empty())
+                .cloned();
+            if let Some(kids) = kids.filter(|_| visited.insert(id)) {
+                let count = kids.len();
+                let kids = self.remove_widget_fields(kids, widgets, visited);
+                if kids.is_empty() {
+                    continue;
+                }
+                if kids.len() != count {
+                    if let Some(PdfObject::Dictionary(field)) = self.get_mut(id) {
+                        field.set("Kids", PdfObject::Array(kids));
+                    }
+                }
+            }
+            kept.push(field);
+        }
+        kept
+    }
+
+    /// The annotation dictionary holding the fields of an annotation, with
+    /// a generated appearance if it has none.
+    fn annotation_dict(&mut self, annotation: &Annotation) -> Dictionary {
+        let mut dict = annotation.dict.clone();
+        let set = |dict: &mut Dictionary, key: &str, value: Option<PdfObject>| match value {
+            Some(value) => dict.set(key, value),
+            None => {
+                dict.remove(key);
+            }
+        };
+        let text = |text: &Option<String>| text.as_deref().map(PdfObject::text_string);
+        let name = |name: &Option<String>| name.clone().map(PdfObject::Name);
+        let numbers =
+            |values: &[f64]| PdfObject::Array(values.iter().map(|&v| number(v)).collect());
+        let kind = &annotation.kind;
+
+        dict.set("Type", PdfObject::Name("Annot".to_string()));
+        dict.set("Subtype", PdfObject::Name(kind.name().to_string()));
+        dict.set("Rect", numbers(&annotation.rect));
+        set(&mut dict, "Contents", text(&annotation.contents));
+        if kind.is_markup() {
+            set(&mut dict, "T", text(&annotation.author));
+            set(&mut dict, "Subj", text(&annotation.subject));
+        }
+        set(
+            &mut dict,
+            "M",
+            annotation
+                .modified
+                .map(|date| PdfObject::String(date.format().into_bytes())),
+        );
+        set(&mut dict, "C", annotation.color.as_deref().map(numbers));
+        if let Some(width) = annotation.border_width {
+            let mut style = self.resolve_dict(&dict, "BS").cloned().unwrap_or_default();
+            style.set("W", number(width));
+            dict.set("BS", PdfObject::Dictionary(style));
+            dict.remove("Border");
+        }
+        let flags = (annotation.flags != 0).then_some(PdfObject::Integer(annotation.flags as i64));
+        set(&mut dict, "F", flags);
+        set(
+            &mut dict,
+            "IRT",
+            annotation.in_reply_to.map(PdfObject::Reference),
+        );
+
+        match kind {
+            AnnotationKind::Text { icon, open } => {
+                set(&mut dict, "Name", name(icon));
+                set(&mut dict, "Open", open.then_some(PdfObject::Boolean(true)));
+            }
+            AnnotationKind::Popup { parent, open } => {
+                set(&mut dict, "Parent", parent.map(PdfObject::Reference));
+                set(&mut dict, "Open", open.then_some(PdfObject::Boolean(true)));
+            }
+            AnnotationKind::FreeText { font_size: size } => {
+                let da = dict.get("DA").and_then(PdfObject::as_text);
+                if da.and_then(|da| font_size(&da)) != Some(*size) {
+                    let da = format!("/{} {} Tf 0 g", FONT, size);
+                    dict.set("DA", PdfObject::String(da.into_bytes()));
+                }
+            }
+            AnnotationKind::Highlight { quad_points }
+            | AnnotationKind::Underline { quad_points }
+            | AnnotationKind::StrikeOut { quad_points } => {
+                dict.set("QuadPoints", numbers(&quad_points.concat()));
+            }
+            AnnotationKind::Square { interior_color }
+            | AnnotationKind::Circle { interior_color } => {
+                set(&mut dict, "IC", interior_color.as_deref().map(numbers));
+            }
+            AnnotationKind::Ink { paths } => {
+                let paths = paths.iter().map(|path| numbers(&path.concat())).collect();
+                dict.set("InkList", PdfObject::Array(paths));
+            }
+            AnnotationKind::Stamp { icon } => set(&mut dict, "Name", name(icon)),
+            AnnotationKind::Widget { field_name } => set(&mut dict, "T", text(field_name)),
+            AnnotationKind::FileAttachment { file, icon } => {
+                set(&mut dict, "Name", name(icon));
+                // The file is embedded again only if it changed.
+                let unchanged = self
+                    .resolve_dict(&dict, "FS")
+                    .and_then(|spec| self.file_spec(spec, String::new()))
+                    .is_some_and(|old| file.as_ref() == Some(&attached_file(old)));
+                if let Some(file) = file.as_ref().filter(|_| !unchanged) {
+                    let spec = self.add_file_spec(file);
+                    dict.set("FS", PdfObject::Reference(spec));
+                }
+            }
+            AnnotationKind::Link | AnnotationKind::Other(_) => {}
+        }
+
+        match &annotation.appearance {
+            Some(appearance) => {
+                let mut ap = self.resolve_dict(&dict, "AP").cloned().unwrap_or_default();
+                ap.set("N", appearance.clone());
+                dict.set("AP", PdfObject::Dictionary(ap));
+            }
+            None => {
+                dict.remove("AS");
+                match appearance(annotation) {
+                    Some(stream) => {
+                        let stream = self.add_object(PdfObject::Stream(stream));
+                        let mut ap = Dictionary::new();
+                        ap.set("N", PdfObject::Reference(stream));
+                        dict.set("AP", PdfObject::Dictionary(ap));
+                    }
+                    None => {
+                        dict.remove("AP");
+                    }
+                }
+            }
+        }
+        dict
+    }
+
+    /// The target of a link: its /Dest, or else the target of its action.
+    /// See sec. 12.6.4, Table 201
+    fn link_target(&self, dict: &Dictionary, pages: &[Option<ObjectId>]) -> Option<LinkTarget> {
+        if let Some(dest) = dict.get("Dest") {
+            return self.destination(dest, pages).map(LinkTarget::Page);
+        }
+        let action = self.resolve_dict(dict, "A")?;
+        let file = || {
+            let spec = self.resolve_key(action, "F").or_else(|| {
+                self.resolve_dict(action, "Win")
+                    .and_then(|win| self.resolve_key(win, "F"))
+            })?;
+            match spec {
+                PdfObject::Dictionary(spec) => ["UF", "F"]
+                    .iter()
+                    .find_map(|key| self.resolve_key(spec, key)?.as_text()),
+                spec => spec.as_text(),
+            }
+        };
+        let target = match action.get_name("S")? {
+            "GoTo" => LinkTarget::Page(self.destination(action.get("D")?, pages)?),
+            "URI" => {
+                let uri = self.resolve_key(action, "URI")?.as_bytes()?;
+                let uri = String::from_utf8_lossy(uri).into_owned();
+                let base = self
+                    .catalog()
+                    .and_then(|catalog| self.resolve_dict(catalog, "URI"))
+                    .and_then(|dict| self.resolve_key(dict, "Base"))
+                    .and_then(PdfObject::as_bytes)
+                    .map(|base| String::from_utf8_lossy(base).into_owned());
+                LinkTarget::Uri(match base {
+                    Some(base) if !uri.contains(':') => format!("{}{}", base, uri),
+                    _ => uri,
+                })
+            }
+            "GoToR" => {
+                // The destination cannot be looked up in this document, so a
+                // named one is kept by its name.
+                let dest = self.resolve_key(action, "D");
+                LinkTarget::Remote {
+                    file: file()?,
+                    page: dest
+                        .and_then(PdfObject::as_array)
+                        .and_then(|dest| dest.first()?.as_i64())
+                        .and_then(|page| usize::try_from(page).ok()),
+                    destination: match dest {
+                        Some(PdfObject::Name(name)) => Some(name.clone()),
+                        Some(name @ PdfObject::String(_)) => name.as_text(),
+                        _ => None,
+                    },
+                }
+            }
+            "Launch" => LinkTarget::Launch(file()?),
+            "Named" => LinkTarget::Named(action.get_name("N")?.to_string()),
+            other => LinkTarget::Other(other.to_string()),
+        };
+        Some(target)
+    }
+
+    fn page_id(&self, index: usize) -> Result<ObjectId, io::Error> {
+        let pages = self.pages();
+        let page = pages
+            .get(index)
+            .ok_or_else(|| out_of_range(index..index + 1, pages.len()))?;
+        page.id.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "page is not an indirect object")
+        })
+    }
+
+    pub(crate) fn page_annots(&self, page_id: ObjectId) -> Vec<PdfObject> {
+        self.get(page_id)
+            .and_then(PdfObject::as_dict)
+            .and_then(|dict| self.resolve_key(dict, "Annots"))
+            .and_then(PdfObject::as_array)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Sets the /Annots of a page, in place if the page refers to an array
+    /// object, or removes them if there are none.
+    pub(crate) fn set_page_annots(&mut self, page_id: ObjectId, annots: Vec<PdfObject>) {
+        let dict = self.get(page_id).and_then(PdfObject::as_dict);
+        if let Some(id) = dict
+            .and_then(|dict| dict.get("Annots"))
+            .and_then(PdfObject::as_reference)
+        {
+            if !annots.is_empty() {
+                self.pdf.body.objects.insert(id, PdfObject::Array(annots));
+                return;
+            }
+        }
+        if let Some(PdfObject::Dictionary(dict)) = self.get_mut(page_id) {
+            if annots.is_empty() {
+                dict.remove("Annots");
+            } else {
+                dict.set("Annots", PdfObject::Array(annots));
+            }
+        }
+    }
+
+    /// The parent of a pop-up annotation.
+    fn popup_parent(&self, id: ObjectId) -> Option<ObjectId> {
+        let dict = self.get(id)?.as_dict()?;
+        if dict.get_name("Subtype") != Some("Popup") {
+            return None;
+        }
+        dict.get("Parent")?.as_reference()
+    }
+}
+
+/// A file attached to an annotation, which is named by its file name.
+fn attached_file(file: EmbeddedFile) -> EmbeddedFile {
+    EmbeddedFile {
+        name: file.file_name.clone(),
+        ..file
+    }
+}
+
+/// The font size of a default appearance string such as "/Helv 12 Tf 0 g".
+/// See sec. 12.7.4.3
+fn font_size(da: &str) -> Option<f64> {
+    let tokens: Vec<&str> = da.split_whitespace().collect();
+    let tf = tokens.iter().position(|&token| token == "Tf")?;
+    tokens.get(tf.checked_sub(1)?)?.parse().ok()
+}
+
+/// Whole numbers are written as integers, others as reals.
+fn number(value: f64) -> PdfObject {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        PdfObject::Integer(value as i64)
+    } else {
+        PdfObject::Real(value)
+    }
+}
+
+/// The matrix that maps an appearance stream onto the annotation rectangle:
+/// its /Matrix followed by the mapping of its transformed bounding box onto
+/// the rectangle. See sec. 12.5.5, Algorithm: Appearance streams
+fn appearance_matrix(stream: &Stream, rect: [f64; 4]) -> Option<Matrix> {
+    let bbox = stream.dict.get("BBox")?.as_numbers()?;
+    let [x1, y1, x2, y2] = <[f64; 4]>::try_from(bbox).ok()?;
+    let matrix = stream
+        .dict
+        .get("Matrix")
+        .and_then(PdfObject::as_numbers)
+        .and_then(|values| Matrix::from_slice(&values))
+        .unwrap_or_default();
+    let corners =
+        [(x1, y1), (x2, y1), (x1, y2), (x2, y2)].map(|(x, y)| matrix.apply(Point::new(x, y)));
+    let left = corners.iter().map(|p| p.x).fold(f64::INFINITY, f64::min);
+    let bottom = corners.iter().map(|p| p.y).fold(f64::INFINITY, f64::min);
+    let right = corners
+        .iter()
+        .map(|p| p.x)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let top = corners
+        .iter()
+        .map(|p| p.y)
+        .fold(f64::NEG_INFINITY, f64::max);
+    if right - left <= 0.0 || top - bottom <= 0.0 {
+        return None;
+    }
+    let [llx, lly, urx, ury] = rect;
+    let fit = Matrix::translate(-left, -bottom)
+        .multiply(&Matrix::scale(
+            (urx - llx) / (right - left),
+            (ury - lly) / (top - bottom),
+        ))
+        .multiply(&Matrix::translate(llx, lly));
+    Some(matrix.multiply(&fit))
+}
+
+/// Generates the appearance of the annotations that are drawn from their
+/// own entries, as a form XObject whose bounding box is the annotation
+/// rectangle.
+fn appearance(annotation: &Annotation) -> Option<Stream> {
+    let [llx, lly, urx, ury] = annotation.rect;
+    let (width, height) = (urx - llx, ury - lly);
+    let color = annotation.color.as_deref();
+    let line_width = annotation.border_width.unwrap_or(1.0);
+    let mut content = ContentBuilder::new();
+    let mut resources = Dictionary::new();
+
+    match &annotation.kind {
+        AnnotationKind::Square { interior_color } | AnnotationKind::Circle { interior_color } => {
+            let fill = interior_color
+                .as_deref()
+                .is_some_and(|c| set_color(&mut content, c, false));
+            let stroke = line_width > 0.0 && set_color(&mut content, color.unwrap_or(&[0.0]), true);
+            content.set_line_width(line_width);
+            let inset = if stroke { line_width / 2.0 } else { 0.0 };
+            let (x, y) = (llx + inset, lly + inset);
+            let (w, h) = (width - 2.0 * inset, height - 2.0 * inset);
+            if let AnnotationKind::Square { .. } = annotation.kind {
+                content.rect(x, y, w, h);
+            } else {
+                ellipse(&mut content, x, y, w, h);
+            }
+            match (fill, stroke) {
+                (true, true) => content.fill_and_stroke(),
+                (true, false) => content.fill(),
+                (false, true) => content.stroke(),
+                (false, false) => return None,
+            };
+        }
+        AnnotationKind::Highlight { quad_points } => {
+            let mut state = Dictionary::new();
+            state.set("BM", PdfObject::Name("Multiply".to_string()));
+            let mut states = Dictionary::new();
+            states.set("GS0", PdfObject::Dictionary(state));
+            resources.set("ExtGState", PdfObject::Dictionary(states));
+            content.set_graphics_state("GS0");
+            set_color(&mut content, color.unwrap_or(&[1.0, 1.0, 0.0]), false);
+            for quad in quad_points {
+                // The points are in the order upper left, upper right, lower
+                // left, lower right.
+                content
+                    .move_to(quad[0], quad[1])
+                    .line_to(quad[2], quad[3])
+                    .line_to(quad[6], quad[7])
+                    .line_to(quad[4], quad[5])
+                    .close_path();
+            }
+            content.fill();
+        }
+        AnnotationKind::Underline { quad_points } | AnnotationKind::StrikeOut { quad_points } => {
+            set_color(&mut content, color.unwrap_or(&[0.0]), true);
+            for quad in quad_points {
+                let xs = [quad[0], quad[2], quad[4], quad[6]];
+                let ys = [quad[1], quad[3], quad[5], quad[7]];
+                let left = xs.iter().copied().fold(f64::INFINITY, f64::min);
+                let right = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let bottom = ys.iter().copied().fold(f64::INFINITY, f64::min);
+                let top = ys.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let width = ((top - bottom) / 16.0).max(0.5);
+                let y = match annotation.kind {
+                    AnnotationKind::Underline { .. } => bottom + width,
+                    _ => (bottom + top) / 2.0,
+                };
+                content
+                    .set_line_width(width)
+                    .move_to(left, y)
+                    .line_to(right, y)
+                    .stroke();
+            }
+        }
+        AnnotationKind::Ink { paths } => {
+            set_color(&mut content, color.unwrap_or(&[0.0]), true);
+            content
+                .set_line_width(line_width)
+                .set_line_cap(1)
+                .set_line_join(1);
+            for path in paths.iter().filter(|path| !path.is_empty()) {
+                content.move_to(path[0][0], path[0][1]);
+                for point in &path[1..] {
+                    content.line_to(point[0], point[1]);
+                }
+            }
+            content.stroke();
+        }
+        AnnotationKind::Text { .. } => {
+            // A note: a sheet of paper with lines of text on it.
+            set_color(&mut content, color.unwrap_or(&[1.0, 1.0, 0.0]), false);
+            content.set_stroke_gray(0.0).set_line_width(1.0);
+            content
+                .rect(llx + 0.5, lly + 0.5, width - 1.0, height - 1.0)
+                .fill_and_stroke();
+            for line in 1..4 {
+                let y = lly + height * line as f64 / 4.0;
+                content
+                    .move_to(llx + width * 0.2, y)
+                    .line_to(urx - width * 0.2, y);
+            }
+            content.stroke();
+        }
+        AnnotationKind::FreeText { font_size } => {
+            if let Some(width) = annotation.border_width.filter(|&width| width > 0.0) {
+                set_color(&mut content, color.unwrap_or(&[0.0]), true);
+                content
+                    .set_line_width(width)
+                    .rect(
+                        llx + width / 2.0,
+                        lly + width / 2.0,
+                        urx - llx - width,
+                        ury - lly - width,
+                    )
+                    .stroke();
+            }
+            let text = annotation.contents.as_deref().unwrap_or("");
+            let lines = wrap(text, *font_size, width - 4.0);
+            resources.set("Font", font_resources());
+            content
+                .set_fill_gray(0.0)
+                .begin_text()
+                .set_font(FONT, *font_size);
+            content
+                .set_leading(font_size * 1.2)
+                .move_text(llx + 2.0, ury - 2.0 - font_size);
+            for line in lines {
+                content.show_text(&encode(&line)).next_line();
+            }
+            content.end_text();
+        }
+        AnnotationKind::Stamp { icon } => {
+            let text = icon.as_deref().unwrap_or("Draft");
+            let stamp_color = color.unwrap_or(&[0.8, 0.0, 0.0]);
+            set_color(&mut content, stamp_color, true);
+            set_color(&mut content, stamp_color, false);
+            content
+                .set_line_width(2.0)
+                .rect(llx + 1.0, lly + 1.0, width - 2.0, height - 2.0)
+                .stroke();
+            let size = (height * 0.6).min((width - 8.0) / text_width(text, 1.0).max(1.0));
+            let x = llx + (width - text_width(text, size)) / 2.0;
+            resources.set("Font", font_resources());
+            content
+                .begin_text()
+                .set_font(FONT, size)
+                .move_text(x, lly + (height - size * 0.7) / 2.0)
+                .show_text(&encode(text))
+                .end_text();
+        }
+        _ => return None,
+    }
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", PdfObject::Name("XObject".to_string()));
+    dict.set("Subtype", PdfObject::Name("Form".to_string()));
+    dict.set(
+        "BBox",
+        PdfObject::Array(annotation.rect.iter().map(|&v| number(v)).collect()),
+    );
+    dict.set("Resources", PdfObject::Dictionary(resources));
+    dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+    Some(Stream::new(dict, flate_encode(&content.to_bytes())))
+}
+
+/// Sets the fill or stroke color from 1, 3 or 4 components. An empty color
+/// is transparent, and nothing is set.
+fn set_color(content: &mut ContentBuilder, color: &[f64], stroke: bool) -> bool {
+    match (color, stroke) {
+        (&[gray], false) => content.set_fill_gray(gray),
+        (&[gray], true) => content.set_stroke_gray(gray),
+        (&[r, g, b], false) => content.set_fill_rgb(r, g, b),
+        (&[r, g, b], true) => content.set_stroke_rgb(r, g, b),
+        (&[c, m, y, k], false) => content.set_fill_cmyk(c, m, y, k),
+        (&[c, m, y, k], true) => content.set_stroke_cmyk(c, m, y, k),
+        _ => return false,
+    };
+    true
+}
+
+/// An ellipse inscribed in a rectangle, as four Bézier curves.
+fn ellipse(content: &mut ContentBuilder, x: f64, y: f64, width: f64, height: f64) {
+    let (rx, ry) = (width / 2.0, height / 2.0);
+    let (cx, cy) = (x + rx, y + ry);
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    content
+        .move_to(cx + rx, cy)
+        .curve_to(cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry)
+        .curve_to(cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy)
+        .curve_to(cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry)
+        .curve_to(cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy)
+        .close_path();
+}
+
+fn font_resources() -> PdfObject {
+    let mut font = Dictionary::new();
+    font.set("Type", PdfObject::Name("Font".to_string()));
+    font.set("Subtype", PdfObject::Name("Type1".to_string()));
+    font.set("BaseFont", PdfObject::Name("Helvetica".to_string()));
+    font.set("Encoding", PdfObject::Name("WinAnsiEncoding".to_string()));
+    let mut fonts = Dictionary::new();
+    fonts.set(FONT, PdfObject::Dictionary(font));
+    PdfObject::Dictionary(fonts)
+}
+
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| BaseEncoding::WinAnsi.from_unicode(c).unwrap_or(b'?'))
+        .collect()
+}
+
+fn text_width(text: &str, size: f64) -> f64 {
+    let width: f64 = text
+        .chars()
+        .map(|c| standard_width("Helvetica", c).unwrap_or(556.0))
+        .sum();
+    width * size / 1000.0
+}
+
+/// Breaks text into lines at spaces so that each fits the width where it
+/// can, keeping line breaks of the text.
+fn wrap(text: &str, size: f64, width: f64) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if !line.is_empty() && text_width(&candidate, size) > width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        builder::DocumentBuilder, document::tests::reload, outline::Fit, render::render_page,
+    };
+
+    #[test]
+    fn test_annotations() {
+        let mut builder = DocumentBuilder::new();
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |_| {});
+        let mut doc = builder.build();
+
+        let quad = [20.0, 120.0, 100.0, 120.0, 20.0, 100.0, 100.0, 100.0];
+        let mut highlight = Annotation::new(
+            AnnotationKind::Highlight {
+                quad_points: vec![quad],
+            },
+            [20.0, 100.0, 100.0, 120.0],
+        );
+        highlight.contents = Some("Check this figure".to_string());
+        highlight.author = Some("Reviewer".to_string());
+        highlight.modified = PdfDate::parse(b"D:20240102030405Z");
+        let highlight = doc.add_annotation(0, &highlight).unwrap();
+        let mut square = Annotation::new(
+            AnnotationKind::Square {
+                interior_color: None,
+            },
+            [120.0, 20.0, 180.0, 80.0],
+        );
+        square.color = Some(vec![1.0, 0.0, 0.0]);
+        square.border_width = Some(4.0);
+        doc.add_annotation(0, &square).unwrap();
+        let mut popup = Annotation::new(
+            AnnotationKind::Popup {
+                parent: Some(highlight),
+                open: false,
+            },
+            [100.0, 100.0, 200.0, 150.0],
+        );
+        popup.flags = 0;
+        doc.add_annotation(0, &popup).unwrap();
+
+        let mut doc = reload(&doc);
+        let page = doc.page(0).unwrap();
+        let annotations = page.annotations(&doc);
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(annotations[0].kind.name(), "Highlight");
+        assert_eq!(annotations[0].author.as_deref(), Some("Reviewer"));
+        assert_eq!(
+            annotations[0].contents.as_deref(),
+            Some("Check this figure")
+        );
+        assert_eq!(annotations[0].modified.unwrap().year, 2024);
+        assert_eq!(
+            annotations[0].kind,
+            AnnotationKind::Highlight {
+                quad_points: vec![quad]
+            }
+        );
+        assert_eq!(annotations[1].border_width, Some(4.0));
+        assert!(annotations[1].appearance_stream(&doc).is_some());
+
+        let mut changed = annotations[0].clone();
+        changed.contents = Some("Fixed".to_string());
+        doc.update_annotation(&changed).unwrap();
+        let annotations = doc.page(0).unwrap().annotations(&doc);
+        assert_eq!(annotations[0].contents.as_deref(), Some("Fixed"));
+        assert!(annotations[0].appearance.is_some());
+
+        // The highlight and the square are drawn into the page, and the
+        // highlight's pop-up goes with it.
+        assert_eq!(doc.flatten_annotations(0).unwrap(), 2);
+        let doc = reload(&doc);
+        let page = doc.page(0).unwrap();
+        assert!(page.annotations(&doc).is_empty());
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        assert_eq!(bitmap.pixel(150, 200 - 21), [255, 0, 0, 255]);
+        assert_eq!(bitmap.pixel(50, 200 - 110), [255, 255, 0, 255]);
+        assert_eq!(bitmap.pixel(150, 200 - 50), [255, 255, 255, 255]);
+    }
+
+    /// An annotation without its dictionary and appearance, leaving the
+    /// fields that are written to the dictionary.
+    fn fields(annotation: &Annotation) -> Annotation {
+        Annotation {
+            appearance: None,
+            dict: Dictionary::new(),
+            ..annotation.clone()
+        }
+    }
+
+    #[test]
+    fn test_annotation_kinds() {
+        let mut builder = DocumentBuilder::new();
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |_| {});
+        let mut doc = builder.build();
+
+        let quad = [10.0, 30.0, 90.0, 30.0, 10.0, 10.0, 90.0, 10.0];
+        let mut file = EmbeddedFile::new("notes.txt", b"Some notes".to_vec());
+        file.mime_type = Some("text/plain".to_string());
+        let kinds = vec![
+            AnnotationKind::Text {
+                icon: Some("Comment".to_string()),
+                open: true,
+            },
+            AnnotationKind::FreeText { font_size: 9.0 },
+            AnnotationKind::Highlight {
+                quad_points: vec![quad],
+            },
+            AnnotationKind::Underline {
+                quad_points: vec![quad, quad],
+            },
+            AnnotationKind::StrikeOut {
+                quad_points: vec![quad],
+            },
+            AnnotationKind::Square {
+                interior_color: Some(vec![0.0, 0.0, 1.0]),
+            },
+            AnnotationKind::Circle {
+                interior_color: None,
+            },
+            AnnotationKind::Ink {
+                paths: vec![vec![[10.0, 10.0], [20.0, 25.5]], vec![[30.0, 30.0]]],
+            },
+            AnnotationKind::Stamp {
+                icon: Some("Approved".to_string()),
+            },
+            AnnotationKind::Widget {
+                field_name: Some("signature".to_string()),
+            },
+            AnnotationKind::FileAttachment {
+                file: Some(file),
+                icon: Some("Paperclip".to_string()),
+            },
+            AnnotationKind::Link,
+            AnnotationKind::Other("Caret".to_string()),
+        ];
+        let mut annotations = vec![];
+        for kind in kinds {
+            let markup = kind.is_markup();
+            let mut annotation = Annotation::new(kind, [10.0, 10.0, 90.0, 40.5]);
+            annotation.contents = Some("Remarque".to_string());
+            annotation.color = Some(vec![0.25]);
+            annotation.border_width = Some(2.0);
+            if markup {
+                annotation.author = Some("Reviewer".to_string());
+                annotation.subject = Some("Typo".to_string());
+            }
+            annotation.id = Some(doc.add_annotation(0, &annotation).unwrap());
+            annotations.push(annotation);
+        }
+        let mut popup = Annotation::new(
+            AnnotationKind::Popup {
+                parent: annotations[0].id,
+                open: true,
+            },
+            [100.0, 100.0, 200.0, 150.0],
+        );
+        popup.flags = Annotation::HIDDEN;
+        popup.id = Some(doc.add_annotation(0, &popup).unwrap());
+        let mut reply = Annotation::new(
+            AnnotationKind::Text {
+                icon: None,
+                open: false,
+            },
+            [0.0, 0.0, 20.0, 20.0],
+        );
+        reply.in_reply_to = annotations[0].id;
+        reply.id = Some(doc.add_annotation(0, &reply).unwrap());
+        annotations.extend([popup, reply]);
+
+        let doc = reload(&doc);
+        let read = doc.page(0).unwrap().annotations(&doc);
+        assert_eq!(read.len(), annotations.len());
+        for (read, written) in read.iter().zip(&annotations) {
+            assert_eq!(fields(read), fields(written));
+            assert_eq!(
+                read.dict.get("P"),
+                doc.pages()[0].id.map(PdfObject::Reference).as_ref()
+            );
+        }
+
+        // The kinds drawn from their own entries get an appearance.
+        let drawn: Vec<&str> = read
+            .iter()
+            .filter(|annotation| annotation.appearance_stream(&doc).is_some())
+            .map(|annotation| annotation.kind.name())
+            .collect();
+        assert_eq!(
+            drawn,
+            [
+                "Text",
+                "FreeText",
+                "Highlight",
+                "Underline",
+                "StrikeOut",
+                "Square",
+                "Circle",
+                "Ink",
+                "Stamp",
+                "Text"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_file_attachment() {
+        let mut builder = DocumentBuilder::new();
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |_| {});
+        let mut doc = builder.build();
+        let file = EmbeddedFile::new("data.csv", b"a,b\n1,2\n".to_vec());
+        let attachment = Annotation::new(
+            AnnotationKind::FileAttachment {
+                file: Some(file.clone()),
+                icon: None,
+            },
+            [10.0, 10.0, 30.0, 30.0],
+        );
+        doc.add_annotation(0, &attachment).unwrap();
+        let embedded = |doc: &Document| {
+            doc.pdf
+                .body
+                .objects
+                .values()
+                .filter(|object| object.as_dict().is_some_and(|d| d.has_type("Filespec")))
+                .count()
+        };
+        assert_eq!(embedded(&doc), 1);
+
+        // Updating the annotation keeps its file specification.
+        let mut annotation = doc.page(0).unwrap().annotations(&doc).remove(0);
+        let spec = annotation.dict.get("FS").cloned();
+        annotation.contents = Some("The data".to_string());
+        doc.update_annotation(&annotation).unwrap();
+        doc.update_annotation(&annotation).unwrap();
+        assert_eq!(embedded(&doc), 1);
+        let annotation = doc.page(0).unwrap().annotations(&doc).remove(0);
+        assert_eq!(annotation.dict.get("FS").cloned(), spec);
+
+        // A changed file is embedded anew.
+        let mut changed = annotation.clone();
+        let changed_file = EmbeddedFile::new("data.csv", b"a,b\n3,4\n".to_vec());
+        changed.kind = AnnotationKind::FileAttachment {
+            file: Some(changed_file.clone()),
+            icon: None,
+        };
+        doc.update_annotation(&changed).unwrap();
+        assert_eq!(embedded(&doc), 2);
+        let annotation = doc.page(0).unwrap().annotations(&doc).remove(0);
+        assert_eq!(
+            annotation.kind,
+            AnnotationKind::FileAttachment {
+                file: Some(changed_file),
+                icon: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_flatten_annotations() {
+        let mut builder = DocumentBuilder::new();
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |_| {});
+        let mut doc = builder.build();
+
+        // The page's content is an array of two streams, and its resources
+        // are inherited from the root of the page tree.
+        let page_id = doc.pages()[0].id.unwrap();
+        let tree = doc
+            .catalog()
+            .and_then(|catalog| catalog.get("Pages"))
+            .and_then(PdfObject::as_reference)
+            .unwrap();
+        let stream = |doc: &mut Document, data: &[u8]| {
+            let stream = Stream::new(Dictionary::new(), data.to_vec());
+            PdfObject::Reference(doc.add_object(PdfObject::Stream(stream)))
+        };
+        let contents = vec![
+            stream(&mut doc, b"/GS0 gs 0 0 1 rg 0 0 50 50 re f"),
+            stream(&mut doc, b"0 1 0 rg 50 0 50 50 re f"),
+        ];
+        let mut state = Dictionary::new();
+        state.set("ca", PdfObject::Real(1.0));
+        let mut states = Dictionary::new();
+        states.set("GS0", PdfObject::Dictionary(state));
+        let mut resources = Dictionary::new();
+        resources.set("ExtGState", PdfObject::Dictionary(states));
+        if let Some(PdfObject::Dictionary(page)) = doc.get_mut(page_id) {
+            page.set("Contents", PdfObject::Array(contents));
+            page.remove("Resources");
+        }
+        if let Some(PdfObject::Dictionary(tree)) = doc.get_mut(tree) {
+            tree.set("Resources", PdfObject::Dictionary(resources));
+        }
+
+        let square = |color: f64, rect: [f64; 4]| {
+            let mut square = Annotation::new(
+                AnnotationKind::Square {
+                    interior_color: Some(vec![color, 0.0, 0.0]),
+                },
+                rect,
+            );
+            square.border_width = Some(0.0);
+            square
+        };
+        let shown = doc
+            .add_annotation(0, &square(1.0, [100.0, 100.0, 150.0, 150.0]))
+            .unwrap();
+        let mut hidden = square(0.5, [150.0, 150.0, 200.0, 200.0]);
+        hidden.flags |= Annotation::HIDDEN;
+        let hidden = doc.add_annotation(0, &hidden).unwrap();
+        let mut popups = vec![];
+        for parent in [shown, hidden] {
+            let popup = Annotation::new(
+                AnnotationKind::Popup {
+                    parent: Some(parent),
+                    open: false,
+                },
+                [0.0, 150.0, 50.0, 200.0],
+            );
+            popups.push(doc.add_annotation(0, &popup).unwrap());
+        }
+
+        // Only the visible square is drawn, and its pop-up goes with it.
+        assert_eq!(doc.flatten_annotations(0).unwrap(), 1);
+        let doc = reload(&doc);
+        let page = doc.page(0).unwrap();
+        let left: Vec<Option<ObjectId>> = page
+            .annotations(&doc)
+            .iter()
+            .map(|annotation| annotation.id)
+            .collect();
+        assert_eq!(left, [Some(hidden), Some(popups[1])]);
+
+        let resources = doc.resolve_dict(&page.dict, "Resources").unwrap();
+        assert!(resources.contains_key("ExtGState"));
+        assert!(resources.contains_key("XObject"));
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        assert_eq!(bitmap.pixel(25, 200 - 25), [0, 0, 255, 255]);
+        assert_eq!(bitmap.pixel(75, 200 - 25), [0, 255, 0, 255]);
+        assert_eq!(bitmap.pixel(125, 200 - 125), [255, 0, 0, 255]);
+        assert_eq!(bitmap.pixel(175, 200 - 175), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_flatten_form_widgets() {
+        let mut builder = DocumentBuilder::new();
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |_| {});
+        let mut doc = builder.build();
+        let box_stream = Stream::new(
+            [(
+                "BBox".to_string(),
+                PdfObject::Array([0, 0, 10, 10].map(PdfObject::Integer).to_vec()),
+            )]
+            .into_iter()
+            .collect(),
+            b"0 0 10 10 re f".to_vec(),
+        );
+        let box_stream = doc.add_object(PdfObject::Stream(box_stream));
+        let mut widget = |name: Option<&str>, flags: u32| {
+            let mut widget = Annotation::new(
+                AnnotationKind::Widget {
+                    field_name: name.map(str::to_string),
+                },
+                [0.0, 0.0, 10.0, 10.0],
+            );
+            widget.flags = flags;
+            widget.appearance = Some(PdfObject::Reference(box_stream));
+            doc.add_annotation(0, &widget).unwrap()
+        };
+        // A field with a widget that is shown and one that is hidden, a field
+        // that is its own widget, and a field with a single widget.
+        let shown = widget(None, Annotation::PRINT);
+        let hidden = widget(None, Annotation::HIDDEN);
+        let merged = widget(Some("merged"), Annotation::PRINT);
+        let only = widget(None, Annotation::PRINT);
+        let field = |doc: &mut Document, name: &str, kids: &[ObjectId]| {
+            let kids = kids.iter().map(|&kid| PdfObject::Reference(kid)).collect();
+            let field: Dictionary = [
+                ("T".to_string(), PdfObject::text_string(name)),
+                ("Kids".to_string(), PdfObject::Array(kids)),
+            ]
+            .into_iter()
+            .collect();
+            doc.add_object(PdfObject::Dictionary(field))
+        };
+        let parent = field(&mut doc, "parent", &[shown, hidden]);
+        let single = field(&mut doc, "single", &[only]);
+        let fields = [parent, merged, single]
+            .iter()
+            .map(|&id| PdfObject::Reference(id))
+            .collect();
+        let form: Dictionary = [("Fields".to_string(), PdfObject::Array(fields))]
+            .into_iter()
+            .collect();
+        let form = doc.add_object(PdfObject::Dictionary(form));
+        let root = doc.catalog_id().unwrap();
+        if let Some(PdfObject::Dictionary(catalog)) = doc.get_mut(root) {
+            catalog.set("AcroForm", PdfObject::Reference(form));
+        }
+
+        assert_eq!(doc.flatten_annotations(0).unwrap(), 3);
+        let doc = reload(&doc);
+        let form = doc
+            .resolve_dict(doc.catalog().unwrap(), "AcroForm")
+            .unwrap();
+        assert_eq!(
+            form.get("Fields"),
+            Some(&PdfObject::Array(vec![PdfObject::Reference(parent)]))
+        );
+        let parent = doc.get(parent).and_then(PdfObject::as_dict).unwrap();
+        assert_eq!(
+            parent.get("Kids"),
+            Some(&PdfObject::Array(vec![PdfObject::Reference(hidden)]))
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let mut builder = DocumentBuilder::new();
+        for _ in 0..3 {
+            builder.add_page([0.0, 0.0, 200.0, 200.0], |_| {});
+        }
+        let mut doc = builder.build();
+        let pages: Vec<ObjectId> = doc.pages().iter().filter_map(|page| page.id).collect();
+        doc.set_named_destinations(&[("end".to_string(), Destination::new(2))]);
+        let root = doc.catalog_id().unwrap();
+        if let Some(PdfObject::Dictionary(catalog)) = doc.get_mut(root) {
+            let mut uri = Dictionary::new();
+            uri.set("Base", PdfObject::String(b"https://example.com/".to_vec()));
+            catalog.set("URI", PdfObject::Dictionary(uri));
+        }
+
+        let name = |name: &str| PdfObject::Name(name.to_string());
+        let string = |text: &str| PdfObject::String(text.as_bytes().to_vec());
+        let action = |entries: Vec<(&str, PdfObject)>| {
+            let dict: Dictionary = entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect();
+            vec![("A", PdfObject::Dictionary(dict))]
+        };
+        let dest = PdfObject::Array(vec![
+            PdfObject::Reference(pages[1]),
+            name("XYZ"),
+            PdfObject::Integer(0),
+            PdfObject::Integer(100),
+            PdfObject::Null,
+        ]);
+        let spec: Dictionary = [
+            ("Type".to_string(), name("Filespec")),
+            ("UF".to_string(), PdfObject::text_string("Rapport été.pdf")),
+        ]
+        .into_iter()
+        .collect();
+        let win: Dictionary = [("F".to_string(), string("notepad.exe"))]
+            .into_iter()
+            .collect();
+        let links = [
+            vec![("Dest", dest)],
+            vec![("Dest", string("end"))],
+            vec![("Dest", string("nowhere"))],
+            action(vec![("S", name("URI")), ("URI", string("help.html"))]),
+            action(vec![("S", name("Named")), ("N", name("NextPage"))]),
+            action(vec![
+                ("S", name("GoToR")),
+                ("F", string("other.pdf")),
+                (
+                    "D",
+                    PdfObject::Array(vec![PdfObject::Integer(4), name("Fit")]),
+                ),
+            ]),
+            action(vec![("S", name("JavaScript")), ("JS", string("1"))]),
+            // Named destinations in GoTo actions, and targets that do not
+            // exist.
+            action(vec![("S", name("GoTo")), ("D", string("end"))]),
+            action(vec![("S", name("GoTo")), ("D", name("nowhere"))]),
+            vec![(
+                "Dest",
+                PdfObject::Array(vec![PdfObject::Reference((999, 0)), name("Fit")]),
+            )],
+            vec![],
+            // An absolute URI is not resolved against the base URI.
+            action(vec![("S", name("URI")), ("URI", string("mailto:a@b.org"))]),
+            // Named destinations in another file.
+            action(vec![
+                ("S", name("GoToR")),
+                ("F", PdfObject::Dictionary(spec)),
+                ("D", name("chapter-2")),
+            ]),
+            action(vec![
+                ("S", name("GoToR")),
+                ("F", string("other.pdf")),
+                ("D", string("intro")),
+            ]),
+            // Applications and documents, also given for Windows only.
+            action(vec![("S", name("Launch")), ("F", string("readme.txt"))]),
+            action(vec![
+                ("S", name("Launch")),
+                ("Win", PdfObject::Dictionary(win)),
+            ]),
+            action(vec![("S", name("Launch"))]),
+        ];
+        for (i, entries) in links.into_iter().enumerate() {
+            let y = 10.0 + 20.0 * i as f64;
+            let mut link = Annotation::new(AnnotationKind::Link, [10.0, y, 100.0, y + 15.0]);
+            for (key, value) in entries {
+                link.dict.set(key, value);
+            }
+            doc.add_annotation(0, &link).unwrap();
+        }
+
+        let doc = reload(&doc);
+        let links = doc.page(0).unwrap().links(&doc);
+        let targets: Vec<Option<LinkTarget>> = links.into_iter().map(|link| link.target).collect();
+        let view = Destination {
+            page: 1,
+            fit: Fit::Xyz {
+                left: Some(0.0),
+                top: Some(100.0),
+                zoom: None,
+            },
+        };
+        assert_eq!(
+            targets,
+            vec![
+                Some(LinkTarget::Page(view)),
+                Some(LinkTarget::Page(Destination::new(2))),
+                None,
+                Some(LinkTarget::Uri("https://example.com/help.html".to_string())),
+                Some(LinkTarget::Named("NextPage".to_string())),
+                Some(LinkTarget::Remote {
+                    file: "other.pdf".to_string(),
+                    page: Some(4),
+                    destination: None,
+                }),
+                Some(LinkTarget::Other("JavaScript".to_string())),
+                Some(LinkTarget::Page(Destination::new(2))),
+                None,
+                None,
+                None,
+                Some(LinkTarget::Uri("mailto:a@b.org".to_string())),
+                Some(LinkTarget::Remote {
+                    file: "Rapport été.pdf".to_string(),
+                    page: None,
+                    destination: Some("chapter-2".to_string()),
+                }),
+                Some(LinkTarget::Remote {
+                    file: "other.pdf".to_string(),
+                    page: None,
+                    destination: Some("intro".to_string()),
+                }),
+                Some(LinkTarget::Launch("readme.txt".to_string())),
+                Some(LinkTarget::Launch("notepad.exe".to_string())),
+                None,
+            ]
+        );
+    }
+}
@@ -0,0 +1,870 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::api::{
+    content::builder::ContentBuilder,
+    document::Document,
+    filters::flate_encode,
+    font::{
+        encoding::BaseEncoding,
+        metrics::{standard_width, STANDARD_FONTS},
+        truetype::TrueTypeFont,
+    },
+    geometry::Matrix,
+    image::Bitmap,
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    structure::{Body, CrossRefTable, Header, Trailer, PDF},
+};
+
+/// The character shown in place of characters a font's encoding lacks.
+const REPLACEMENT: u8 = b'?';
+
+/// A font added to a `DocumentBuilder`, to draw text with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FontId(usize);
+
+/// An image added to a `DocumentBuilder`, to be painted on pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ImageId(usize);
+
+#[derive(Debug)]
+enum BuilderFont {
+    /// One of the standard 14 fonts, which readers provide themselves.
+    Standard { id: ObjectId, base_font: String },
+    /// A TrueType or OpenType font, embedded as a subset of the glyphs shown
+    /// when the document is built.
+    TrueType {
+        id: ObjectId,
+        font: Box<TrueTypeFont>,
+        /// The glyphs shown so far, with the character each one shows.
+        used: BTreeMap<u16, char>,
+    },
+}
+
+impl BuilderFont {
+    fn id(&self) -> ObjectId {
+        match self {
+            BuilderFont::Standard { id, .. } | BuilderFont::TrueType { id, .. } => *id,
+        }
+    }
+
+    /// The symbolic standard fonts have their own built-in encoding, in which
+    /// the codes of the characters are used as is.
+    fn encoding(&self) -> Option<BaseEncoding> {
+        match self {
+            BuilderFont::Standard { base_font, .. }
+                if base_font == "Symbol" || base_font == "ZapfDingbats" =>
+            {
+                None
+            }
+            _ => Some(BaseEncoding::WinAnsi),
+        }
+    }
+
+    /// Encodes text as a string to show. Standard fonts use one byte per
+    /// character; embedded fonts use two-byte CIDs with the Identity-H CMap.
+    fn encode(&self, text: &str) -> Vec<u8> {
+        let encoding = self.encoding();
+        match self {
+            BuilderFont::Standard { .. } => text
+                .chars()
+                .map(|c| match encoding {
+                    Some(encoding) => encoding.from_unicode(c),
+                    None => u8::try_from(c).ok(),
+                })
+                .map(|code| code.unwrap_or(REPLACEMENT))
+                .collect(),
+            BuilderFont::TrueType { font, .. } => text
+                .chars()
+                .flat_map(|c| cid(font, font.glyph_index(c).unwrap_or(0)).to_be_bytes())
+                .collect(),
+        }
+    }
+
+    /// Notes the glyphs of text shown with an embedded font, to be kept in
+    /// its subset.
+    fn record(&mut self, text: &str) {
+        if let BuilderFont::TrueType { font, used, .. } = self {
+            for c in text.chars() {
+                if let Some(gid) = font.glyph_index(c) {
+                    used.entry(gid).or_insert(c);
+                }
+            }
+        }
+    }
+
+    /// The width of text in thousandths of text space units.
+    fn width(&self, text: &str) -> f64 {
+        match self {
+            BuilderFont::Standard { base_font, .. } => {
+                let encoding = self.encoding();
+                let unicode = |code: u8| match encoding {
+                    Some(encoding) => encoding.to_unicode(code),
+                    None => Some(code as char),
+                };
+                self.encode(text)
+                    .into_iter()
+                    .filter_map(|code| standard_width(base_font, unicode(code)?))
+                    .sum()
+            }
+            BuilderFont::TrueType { font, .. } => text
+                .chars()
+                .filter_map(|c| font.advance_width(font.glyph_index(c).unwrap_or(0)))
+                .sum(),
+        }
+    }
+}
+
+/// The CID of a glyph of an embedded font: the glyph index, except for
+/// CID-keyed CFF fonts, which have their own CIDs.
+fn cid(font: &TrueTypeFont, gid: u16) -> u16 {
+    font.cff().and_then(|cff| cff.glyph_cid(gid)).unwrap_or(gid)
+}
+
+///
+/// Creates a new document from scratch: pages drawn with text in the standard
+/// 14 fonts or embedded TrueType fonts, lines, rectangles and images, and the
+/// document information dictionary. `build` produces a `Document` that can be
+/// saved like one that was read from a file.
+///
+/// Fonts and images are added to the builder once and can then be used on any
+/// number of pages. See sec. 7.7 (pg. 98) for the document structure created.
+///
+#[derive(Debug)]
+pub struct DocumentBuilder {
+    objects: BTreeMap<ObjectId, PdfObject>,
+    next_number: i32,
+    pages_id: ObjectId,
+    pages: Vec<ObjectId>,
+    fonts: Vec<BuilderFont>,
+    images: Vec<ObjectId>,
+    info: Dictionary,
+}
+
+impl Default for DocumentBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DocumentBuilder {
+    pub fn new() -> DocumentBuilder {
+        let mut builder = DocumentBuilder {
+            objects: BTreeMap::new(),
+            next_number: 1,
+            pages_id: (0, 0),
+            pages: vec![],
+            fonts: vec![],
+            images: vec![],
+            info: Dictionary::new(),
+        };
+        builder.pages_id = builder.add(PdfObject::Null);
+        builder
+    }
+
+    /// Adds an indirect object to the document.
+    pub fn add(&mut self, object: PdfObject) -> ObjectId {
+        let id = (self.next_number, 0);
+        self.next_number += 1;
+        self.objects.insert(id, object);
+        id
+    }
+
+    /// Adds one of the standard 14 fonts, by its name, e.g. "Helvetica-Bold".
+    /// Text is encoded with WinAnsiEncoding. See sec. 9.6.2.2 (pg. 300)
+    pub fn add_standard_font(&mut self, name: &str) -> Option<FontId> {
+        let base_font = STANDARD_FONTS.iter().find(|&&font| font == name)?;
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("Font".to_string()));
+        dict.set("Subtype", PdfObject::Name("Type1".to_string()));
+        dict.set("BaseFont", PdfObject::Name(base_font.to_string()));
+        let id = self.add(PdfObject::Null);
+        let font = BuilderFont::Standard {
+            id,
+            base_font: base_font.to_string(),
+        };
+        if font.encoding().is_some() {
+            dict.set("Encoding", PdfObject::Name("WinAnsiEncoding".to_string()));
+        }
+        self.objects.insert(id, PdfObject::Dictionary(dict));
+        self.fonts.push(font);
+        Some(FontId(self.fonts.len() - 1))
+    }
+
+    /// Adds a TrueType font, or an OpenType font with TrueType or PostScript
+    /// outlines. Text is shown with the font's Unicode 'cmap' table, and only
+    /// the glyphs shown are embedded. Returns None if the font file cannot be
+    /// read or has no Unicode 'cmap' table.
+    pub fn add_truetype_font(&mut self, data: Vec<u8>) -> Option<FontId> {
+        let font = TrueTypeFont::parse(data).filter(TrueTypeFont::has_unicode_cmap)?;
+        let id = self.add(PdfObject::Null);
+        self.fonts.push(BuilderFont::TrueType {
+            id,
+            font: Box::new(font),
+            used: BTreeMap::new(),
+        });
+        Some(FontId(self.fonts.len() - 1))
+    }
+
+    /// Embeds the subset of a font with the glyphs shown, as a Type0 font with
+    /// a CIDFont descendant, /W widths and a /ToUnicode CMap so that the text
+    /// can be extracted. A font that cannot be subset is embedded whole, and
+    /// its name has no subset tag. See sec. 9.7 (pg. 313) and 9.10.3 (pg. 348)
+    fn embed_font(&mut self, id: ObjectId, font: &TrueTypeFont, used: &BTreeMap<u16, char>) {
+        let glyphs: BTreeSet<u16> = used.keys().copied().collect();
+        let name = font
+            .postscript_name()
+            .map(|name| name.replace(|c: char| !c.is_ascii_graphic() || c == '/', ""))
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("Font{}", id.0));
+        let (name, data) = match font.subset(&glyphs) {
+            Some(data) => (format!("{}+{}", subset_tag(&glyphs), name), data),
+            None => (name, font.data().to_vec()),
+        };
+
+        let mut file = Dictionary::new();
+        let (file_key, subtype) = match font.cff() {
+            Some(_) => {
+                file.set("Subtype", PdfObject::Name("OpenType".to_string()));
+                ("FontFile3", "CIDFontType0")
+            }
+            None => {
+                file.set("Length1", PdfObject::Integer(data.len() as i64));
+                ("FontFile2", "CIDFontType2")
+            }
+        };
+        file.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+        let file = self.add(PdfObject::Stream(Stream::new(file, flate_encode(&data))));
+
+        let integers = |values: &[f64]| {
+            values
+                .iter()
+                .map(|v| PdfObject::Integer(v.round() as i64))
+                .collect()
+        };
+        let bbox = font.bbox().unwrap_or([0.0, -200.0, 1000.0, 800.0]);
+        let (ascent, descent) = font.vertical_metrics().unwrap_or((bbox[3], bbox[1]));
+        let mut descriptor = Dictionary::new();
+        descriptor.set("Type", PdfObject::Name("FontDescriptor".to_string()));
+        descriptor.set("FontName", PdfObject::Name(name.clone()));
+        // Symbolic: the glyphs are outside the standard Latin character set.
+        descriptor.set("Flags", PdfObject::Integer(4));
+        descriptor.set("FontBBox", PdfObject::Array(integers(&bbox)));
+        descriptor.set("ItalicAngle", PdfObject::Integer(0));
+        descriptor.set("Ascent", PdfObject::Integer(ascent.round() as i64));
+        descriptor.set("Descent", PdfObject::Integer(descent.round() as i64));
+        descriptor.set("CapHeight", PdfObject::Integer(ascent.round() as i64));
+        descriptor.set("StemV", PdfObject::Integer(80));
+        descriptor.set(file_key, PdfObject::Reference(file));
+        let descriptor = self.add(PdfObject::Dictionary(descriptor));
+
+        let widths: BTreeMap<u16, f64> = glyphs
+            .iter()
+            .map(|&gid| (cid(font, gid), font.advance_width(gid).unwrap_or(0.0)))
+            .collect();
+        let mut system_info = Dictionary::new();
+        system_info.set("Registry", PdfObject::String(b"Adobe".to_vec()));
+        system_info.set("Ordering", PdfObject::String(b"Identity".to_vec()));
+        system_info.set("Supplement", PdfObject::Integer(0));
+        let mut descendant = Dictionary::new();
+        descendant.set("Type", PdfObject::Name("Font".to_string()));
+        descendant.set("Subtype", PdfObject::Name(subtype.to_string()));
+        descendant.set("BaseFont", PdfObject::Name(name.clone()));
+        descendant.set("CIDSystemInfo", PdfObject::Dictionary(system_info));
+        descendant.set("FontDescriptor", PdfObject::Reference(descriptor));
+        descendant.set("W", width_array(&widths));
+        if font.cff().is_none() {
+            descendant.set("CIDToGIDMap", PdfObject::Name("Identity".to_string()));
+        }
+        let descendant = self.add(PdfObject::Dictionary(descendant));
+
+        let unicode: BTreeMap<u16, char> =
+            used.iter().map(|(&gid, &c)| (cid(font, gid), c)).collect();
+        let to_unicode = self.add(PdfObject::Stream(Stream::new(
+            Dictionary::new(),
+            to_unicode_cmap(&unicode),
+        )));
+
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("Font".to_string()));
+        dict.set("Subtype", PdfObject::Name("Type0".to_string()));
+        dict.set("BaseFont", PdfObject::Name(name));
+        dict.set("Encoding", PdfObject::Name("Identity-H".to_string()));
+        let descendants = vec![PdfObject::Reference(descendant)];
+        dict.set("DescendantFonts", PdfObject::Array(descendants));
+        dict.set("ToUnicode", PdfObject::Reference(to_unicode));
+        self.objects.insert(id, PdfObject::Dictionary(dict));
+    }
+
+    /// Adds an image from decoded pixels. The color channels are stored with
+    /// the Flate filter; an alpha channel becomes a soft mask.
+    /// See sec. 8.9.5 (pg. 222) and 11.6.5.3 (pg. 425)
+    pub fn add_image(&mut self, bitmap: &Bitmap) -> ImageId {
+        let channels = bitmap.format.channels();
+        let colors = if bitmap.format.has_alpha() {
+            channels - 1
+        } else {
+            channels
+        };
+        let mut color = Vec::with_capacity(bitmap.width * bitmap.height * colors);
+        let mut alpha = Vec::with_capacity(bitmap.width * bitmap.height);
+        for pixel in bitmap.pixels.chunks_exact(channels) {
+            color.extend(&pixel[..colors]);
+            alpha.extend(&pixel[colors..]);
+        }
+        let space = if colors == 1 {
+            "DeviceGray"
+        } else {
+            "DeviceRGB"
+        };
+        let mut dict = image_dict(bitmap.width, bitmap.height, space);
+        dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+        if bitmap.format.has_alpha() {
+            let mut mask = image_dict(bitmap.width, bitmap.height, "DeviceGray");
+            mask.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+            let mask = self.add(PdfObject::Stream(Stream::new(mask, flate_encode(&alpha))));
+            dict.set("SMask", PdfObject::Reference(mask));
+        }
+        let id = self.add(PdfObject::Stream(Stream::new(dict, flate_encode(&color))));
+        self.images.push(id);
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Adds a JPEG file as an image, unchanged, to be decoded with the DCTDecode
+    /// filter. Returns None if the frame header with the size and number of
+    /// components cannot be found. See sec. 7.4.8 (pg. 49)
+    pub fn add_jpeg(&mut self, data: Vec<u8>) -> Option<ImageId> {
+        let (width, height, components) = jpeg_frame(&data)?;
+        let space = match components {
+            1 => "DeviceGray",
+            3 => "DeviceRGB",
+            4 => "DeviceCMYK",
+            _ => return None,
+        };
+        let mut dict = image_dict(width, height, space);
+        dict.set("Filter", PdfObject::Name("DCTDecode".to_string()));
+        // Adobe applications write CMYK JPEG files with inverted values.
+        if components == 4 && data.windows(6).any(|w| w == b"Adobe\0") {
+            let decode = [1, 0, 1, 0, 1, 0, 1, 0].map(PdfObject::Integer);
+            dict.set("Decode", PdfObject::Array(decode.to_vec()));
+        }
+        let id = self.add(PdfObject::Stream(Stream::new(dict, data)));
+        self.images.push(id);
+        Some(ImageId(self.images.len() - 1))
+    }
+
+    /// Sets an entry of the document information dictionary, such as "Title",
+    /// "Author" or "Producer". See sec. 14.3.3 (pg. 700)
+    pub fn set_info(&mut self, key: &str, value: &str) {
+        self.info.set(key, PdfObject::text_string(value));
+    }
+
+    /// The width of text in a font at a size, in text space units, or None if
+    /// the font was not added to this builder.
+    pub fn text_width(&self, font: FontId, size: f64, text: &str) -> Option<f64> {
+        Some(self.fonts.get(font.0)?.width(text) * size / 1000.0)
+    }
+
+    /// Adds a page of the size of `media_box` ([llx lly urx ury], in units of
+    /// 1/72 inch) and draws its content. Returns the page object.
+    pub fn add_page(
+        &mut self,
+        media_box: [f64; 4],
+        draw: impl FnOnce(&mut PageBuilder),
+    ) -> ObjectId {
+        let mut page = PageBuilder {
+            builder: self,
+            content: ContentBuilder::new(),
+            fonts: BTreeSet::new(),
+            images: BTreeSet::new(),
+        };
+        draw(&mut page);
+        let PageBuilder {
+            content,
+            fonts,
+            images,
+            ..
+        } = page;
+
+        let mut resources = Dictionary::new();
+        if !fonts.is_empty() {
+            let fonts = fonts
+                .iter()
+                .filter_map(|&font| {
+                    let id = self.fonts.get(font.0)?.id();
+                    Some((font_name(font), PdfObject::Reference(id)))
+                })
+                .collect();
+            resources.set("Font", PdfObject::Dictionary(fonts));
+        }
+        if !images.is_empty() {
+            let images = images
+                .iter()
+                .filter_map(|&image| {
+                    let id = *self.images.get(image.0)?;
+                    Some((image_name(image), PdfObject::Reference(id)))
+                })
+                .collect();
+            resources.set("XObject", PdfObject::Dictionary(images));
+        }
+
+        let mut stream = Dictionary::new();
+        stream.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+        let contents = self.add(PdfObject::Stream(Stream::new(
+            stream,
+            flate_encode(&content.to_bytes()),
+        )));
+
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("Page".to_string()));
+        dict.set("Parent", PdfObject::Reference(self.pages_id));
+        let media_box = media_box.iter().map(|&v| number_object(v)).collect();
+        dict.set("MediaBox", PdfObject::Array(media_box));
+        dict.set("Resources", PdfObject::Dictionary(resources));
+        dict.set("Contents", PdfObject::Reference(contents));
+        let id = self.add(PdfObject::Dictionary(dict));
+        self.pages.push(id);
+        id
+    }
+
+    /// Embeds the fonts, and finishes the page tree and the catalog.
+    pub fn build(mut self) -> Document {
+        for font in std::mem::take(&mut self.fonts) {
+            if let BuilderFont::TrueType { id, font, used } = font {
+                self.embed_font(id, &font, &used);
+            }
+        }
+
+        let mut pages = Dictionary::new();
+        pages.set("Type", PdfObject::Name("Pages".to_string()));
+        let kids = self
+            .pages
+            .iter()
+            .map(|&id| PdfObject::Reference(id))
+            .collect();
+        pages.set("Kids", PdfObject::Array(kids));
+        pages.set("Count", PdfObject::Integer(self.pages.len() as i64));
+        self.objects
+            .insert(self.pages_id, PdfObject::Dictionary(pages));
+
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", PdfObject::Name("Catalog".to_string()));
+        catalog.set("Pages", PdfObject::Reference(self.pages_id));
+        let catalog = self.add(PdfObject::Dictionary(catalog));
+
+        let mut trailer = Dictionary::new();
+        trailer.set("Root", PdfObject::Reference(catalog));
+        if !self.info.is_empty() {
+            let info = std::mem::take(&mut self.info);
+            let info = self.add(PdfObject::Dictionary(info));
+            trailer.set("Info", PdfObject::Reference(info));
+        }
+        trailer.set("Size", PdfObject::Integer(self.next_number as i64));
+
+        let pdf = PDF {
+            header: Header {
+                version: "1.7".to_string(),
+                comment: true,
+            },
+            body: Body {
+                objects: self.objects,
+            },
+            cross_ref_table: CrossRefTable::default(),
+            trailer: Trailer {
+                entries: trailer,
+                start_xref: 0,
+            },
+        };
+        Document { pdf, data: vec![] }
+    }
+}
+
+///
+/// Draws the content of one page, see `DocumentBuilder::add_page`. Coordinates
+/// are in default user space: units of 1/72 inch, with the origin in the lower
+/// left corner of the page. See sec. 8.3.2.3 (pg. 128)
+///
+pub struct PageBuilder<'a> {
+    builder: &'a mut DocumentBuilder,
+    content: ContentBuilder,
+    fonts: BTreeSet<FontId>,
+    images: BTreeSet<ImageId>,
+}
+
+impl PageBuilder<'_> {
+    /// Sets the color that shapes and text are filled with, as RGB components
+    /// from 0 to 1.
+    pub fn set_fill_color(&mut self, r: f64, g: f64, b: f64) {
+        self.content.set_fill_rgb(r, g, b);
+    }
+
+    /// Sets the color that lines and outlines are stroked with.
+    pub fn set_stroke_color(&mut self, r: f64, g: f64, b: f64) {
+        self.content.set_stroke_rgb(r, g, b);
+    }
+
+    pub fn set_line_width(&mut self, width: f64) {
+        self.content.set_line_width(width);
+    }
+
+    /// Saves the graphics state (colors, line width, ...), to be restored with
+    /// `restore_state`.
+    pub fn save_state(&mut self) {
+        self.content.save_state();
+    }
+
+    pub fn restore_state(&mut self) {
+        self.content.restore_state();
+    }
+
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        self.content.move_to(x1, y1).line_to(x2, y2).stroke();
+    }
+
+    /// Strokes the outline of a rectangle with its lower left corner at (x, y).
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.content.rect(x, y, width, height).stroke();
+    }
+
+    pub fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.content.rect(x, y, width, height).fill();
+    }
+
+    /// Shows a line of text starting at (x, y) on the baseline. Characters a
+    /// standard font's encoding lacks are shown as "?", and characters missing
+    /// from an embedded font as its .notdef glyph. Text in a font that was
+    /// not added to this builder is left out.
+    pub fn text(&mut self, font: FontId, size: f64, x: f64, y: f64, text: &str) {
+        let Some(builder_font) = self.builder.fonts.get_mut(font.0) else {
+            return;
+        };
+        builder_font.record(text);
+        let encoded = builder_font.encode(text);
+        self.fonts.insert(font);
+        self.content
+            .begin_text()
+            .set_font(&font_name(font), size)
+            .move_text(x, y)
+            .show_text(&encoded)
+            .end_text();
+    }
+
+    /// The width of text in a font at a size, see `DocumentBuilder::text_width`.
+    pub fn text_width(&self, font: FontId, size: f64, text: &str) -> Option<f64> {
+        self.builder.text_width(font, size, text)
+    }
+
+    /// Paints an image scaled to the rectangle with its lower left corner at
+    /// (x, y). An image that was not added to this builder is left out.
+    pub fn image(&mut self, image: ImageId, x: f64, y: f64, width: f64, height: f64) {
+        if image.0 >= self.builder.images.len() {
+            return;
+        }
+        self.images.insert(image);
+        self.content
+            .save_state()
+            .transform(Matrix::new(width, 0.0, 0.0, height, x, y))
+            .draw_xobject(&image_name(image))
+            .restore_state();
+    }
+}
+
+fn font_name(font: FontId) -> String {
+    format!("F{}", font.0)
+}
+
+fn image_name(image: ImageId) -> String {
+    format!("Im{}", image.0)
+}
+
+/// A subset tag of six uppercase letters, derived from the glyphs of the
+/// subset. See sec. 9.9.2 (pg. 344)
+fn subset_tag(glyphs: &BTreeSet<u16>) -> String {
+    // FNV-1a
+    let mut hash = glyphs.iter().fold(0xCBF29CE484222325u64, |hash, gid| {
+        gid.to_be_bytes().iter().fold(hash, |hash, &b| {
+            (hash ^ b as u64).wrapping_mul(0x100000001B3)
+        })
+    });
+    (0..6)
+        .map(|_| {
+            let letter = (b'A' + (hash % 26) as u8) as char;
+            hash /= 26;
+            letter
+        })
+        .collect()
+}
+
+/// The /W array of a CIDFont: runs of consecutive CIDs with their widths.
+/// See sec. 9.7.4.3 (pg. 319)
+fn width_array(widths: &BTreeMap<u16, f64>) -> PdfObject {
+    let mut array = vec![];
+    let mut run: Option<(u16, Vec<PdfObject>)> = None;
+    for (&cid, &width) in widths {
+        let width = PdfObject::Integer(width.round() as i64);
+        match &mut run {
+            Some((first, run)) if *first as usize + run.len() == cid as usize => run.push(width),
+            _ => {
+                if let Some((first, run)) = run.replace((cid, vec![width])) {
+                    array.extend([PdfObject::Integer(first as i64), PdfObject::Array(run)]);
+                }
+            }
+        }
+    }
+    if let Some((first, run)) = run {
+        array.extend([PdfObject::Integer(first as i64), PdfObject::Array(run)]);
+    }
+    PdfObject::Array(array)
+}
+
+/// A /ToUnicode CMap for two-byte codes. See sec. 9.10.3 (pg. 348)
+fn to_unicode_cmap(unicode: &BTreeMap<u16, char>) -> Vec<u8> {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let entries: Vec<_> = unicode.iter().collect();
+    // At most 100 entries are allowed per block.
+    for chunk in entries.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+        for (code, c) in chunk {
+            let utf16: String = c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .map(|unit| format!("{:04X}", unit))
+                .collect();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", code, utf16));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end\n",
+    );
+    cmap.into_bytes()
+}
+
+fn image_dict(width: usize, height: usize, color_space: &str) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("Type", PdfObject::Name("XObject".to_string()));
+    dict.set("Subtype", PdfObject::Name("Image".to_string()));
+    dict.set("Width", PdfObject::Integer(width as i64));
+    dict.set("Height", PdfObject::Integer(height as i64));
+    dict.set("ColorSpace", PdfObject::Name(color_space.to_string()));
+    dict.set("BitsPerComponent", PdfObject::Integer(8));
+    dict
+}
+
+/// Whole numbers are written as integers, others as reals.
+fn number_object(value: f64) -> PdfObject {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        PdfObject::Integer(value as i64)
+    } else {
+        PdfObject::Real(value)
+    }
+}
+
+/// Reads the width, height and number of components from the start of frame
+/// segment of a JPEG file. See ITU T.81, sec. B.2.2
+fn jpeg_frame(data: &[u8]) -> Option<(usize, usize, u8)> {
+    if data.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut position = 2;
+    loop {
+        if *data.get(position)? != 0xFF {
+            return None;
+        }
+        let marker = *data.get(position + 1)?;
+        if marker == 0xFF {
+            position += 1;
+            continue;
+        }
+        let length = u16::from_be_bytes([*data.get(position + 2)?, *data.get(position + 3)?]);
+        // SOF0 to SOF15, except DHT, JPG and DAC.
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let frame = data.get(position + 4..position + 10)?;
+            let height = u16::from_be_bytes([frame[1], frame[2]]) as usize;
+            let width = u16::from_be_bytes([frame[3], frame[4]]) as usize;
+            return Some((width, height, frame[5]));
+        }
+        position += 2 + length as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        content::{
+            interpreter::{ContentVisitor, Interpreter, TextGlyph, XObject},
+            state::GraphicsState,
+        },
+        document::tests::reload,
+        font::truetype::tests::{sample_font, sample_otf},
+        geometry::Matrix,
+        image::PixelFormat,
+    };
+
+    #[derive(Default)]
+    struct Collector {
+        text: String,
+        images: usize,
+    }
+
+    impl ContentVisitor for Collector {
+        fn glyph(&mut self, glyph: &TextGlyph, _state: &GraphicsState) {
+            self.text
+                .push_str(glyph.unicode.as_deref().unwrap_or("\u{FFFD}"));
+        }
+
+        fn image(&mut self, _image: &XObject, _state: &GraphicsState) {
+            self.images += 1;
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        let mut builder = DocumentBuilder::new();
+        let helvetica = builder.add_standard_font("Helvetica").unwrap();
+        assert!(builder.add_standard_font("Comic Sans").is_none());
+        let custom = builder.add_truetype_font(sample_font()).unwrap();
+        let mut bitmap = Bitmap::new(2, 2, PixelFormat::Rgba);
+        bitmap.pixels.fill(255);
+        let image = builder.add_image(&bitmap);
+        builder.set_info("Title", "Rechnung Nr. 1 – März");
+        assert_eq!(builder.text_width(helvetica, 10.0, "Hi"), Some(9.44));
+        assert_eq!(builder.text_width(custom, 10.0, "AB"), Some(12.0));
+
+        builder.add_page([0.0, 0.0, 595.0, 842.0], |page| {
+            page.set_fill_color(0.2, 0.2, 0.2);
+            page.text(helvetica, 12.0, 72.0, 770.0, "Invoice (€ 10)");
+            page.text(custom, 12.0, 72.0, 750.0, "BA");
+            page.set_line_width(0.5);
+            page.line(72.0, 740.0, 523.0, 740.0);
+            page.rect(72.0, 600.0, 451.0, 100.0);
+            page.image(image, 72.0, 72.0, 100.0, 100.0);
+        });
+        builder.add_page([0.0, 0.0, 612.0, 792.0], |_| {});
+        let doc = reload(&builder.build());
+        let pages = doc.pages();
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].media_box(), [0.0, 0.0, 595.0, 842.0]);
+        assert_eq!(pages[1].media_box(), [0.0, 0.0, 612.0, 792.0]);
+        let info = doc.resolve_dict(doc.trailer(), "Info").unwrap();
+        assert_eq!(
+            info.get("Title"),
+            Some(&PdfObject::text_string("Rechnung Nr. 1 – März"))
+        );
+
+        let mut collector = Collector::default();
+        let content = pages[0].contents(&doc).unwrap();
+        Interpreter::for_page(&doc, &pages[0], Matrix::identity()).run(&content, &mut collector);
+        assert_eq!(collector.text, "Invoice (€ 10)BA");
+        assert_eq!(collector.images, 1);
+    }
+
+    #[test]
+    fn test_ids_of_another_builder() {
+        let mut other = DocumentBuilder::new();
+        let font = other.add_standard_font("Helvetica").unwrap();
+        let image = other.add_image(&Bitmap::new(1, 1, PixelFormat::Rgba));
+
+        let mut builder = DocumentBuilder::new();
+        assert_eq!(builder.text_width(font, 10.0, "Hi"), None);
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |page| {
+            page.text(font, 12.0, 10.0, 100.0, "Hi");
+            page.image(image, 10.0, 10.0, 50.0, 50.0);
+        });
+        let doc = builder.build();
+        let page = &doc.pages()[0];
+        assert!(page.resources(&doc).is_empty());
+        assert!(page.contents(&doc).unwrap().trim_ascii().is_empty());
+    }
+
+    #[test]
+    fn test_embedded_fonts() {
+        let mut builder = DocumentBuilder::new();
+        let truetype = builder.add_truetype_font(sample_font()).unwrap();
+        let opentype = builder.add_truetype_font(sample_otf()).unwrap();
+        builder.add_page([0.0, 0.0, 200.0, 200.0], |page| {
+            page.text(truetype, 12.0, 10.0, 100.0, "A");
+            page.text(opentype, 12.0, 10.0, 50.0, "B");
+        });
+        let doc = reload(&builder.build());
+        let page = &doc.pages()[0];
+
+        let fonts = page.resources(&doc);
+        let fonts = doc.resolve_dict(&fonts, "Font").unwrap();
+        let descendant = |name: &str| {
+            let font = doc.resolve_dict(fonts, name).unwrap();
+            assert_eq!(font.get_name("Encoding"), Some("Identity-H"));
+            let descendants = doc.resolve_key(font, "DescendantFonts").unwrap();
+            let descendant = doc.resolve(&descendants.as_array().unwrap()[0]);
+            descendant.as_dict().unwrap().clone()
+        };
+        let widths = |cid: i64| {
+            let run = vec![PdfObject::Integer(600)];
+            Some(PdfObject::Array(vec![
+                PdfObject::Integer(cid),
+                PdfObject::Array(run),
+            ]))
+        };
+
+        let cid_font = descendant("F0");
+        assert_eq!(cid_font.get_name("Subtype"), Some("CIDFontType2"));
+        assert_eq!(cid_font.get("W").cloned(), widths(1));
+        let descriptor = doc.resolve_dict(&cid_font, "FontDescriptor").unwrap();
+        assert_eq!(descriptor.get_name("FontName").unwrap().len(), 15);
+        let file = doc.resolve_key(descriptor, "FontFile2").unwrap();
+        let file = doc.decode_stream(file.as_stream().unwrap()).unwrap();
+        let subset = TrueTypeFont::parse(file).unwrap();
+        assert!(subset.glyph_outline(2).unwrap().segments.is_empty());
+
+        let cid_font = descendant("F1");
+        assert_eq!(cid_font.get_name("Subtype"), Some("CIDFontType0"));
+        assert_eq!(cid_font.get("W").cloned(), widths(2));
+        let descriptor = doc.resolve_dict(&cid_font, "FontDescriptor").unwrap();
+        let file = doc.resolve_key(descriptor, "FontFile3").unwrap();
+        assert_eq!(
+            file.as_stream().unwrap().dict.get_name("Subtype"),
+            Some("OpenType")
+        );
+
+        #[derive(Default)]
+        struct Outlines(Vec<Option<[f64; 4]>>);
+        impl ContentVisitor for Outlines {
+            fn glyph(&mut self, glyph: &TextGlyph, _state: &GraphicsState) {
+                let outline = glyph.font.glyph_outline(glyph.code);
+                self.0
+                    .push(outline.and_then(|outline| outline.bounding_box()));
+            }
+        }
+        let mut outlines = Outlines::default();
+        let content = page.contents(&doc).unwrap();
+        Interpreter::for_page(&doc, page, Matrix::identity()).run(&content, &mut outlines);
+        assert_eq!(
+            outlines.0,
+            vec![
+                Some([100.0, 0.0, 500.0, 700.0]),
+                Some([100.0, 100.0, 400.0, 400.0])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jpeg_frame() {
+        let data = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00,
+            0x20, 0x00, 0x40, 0x03,
+        ];
+        assert_eq!(jpeg_frame(&data), Some((64, 32, 3)));
+        assert_eq!(jpeg_frame(b"\x89PNG"), None);
+    }
+}
@@ -11,7 +11,7 @@
 ///   - Delimited
 ///   - White-Space
 ///
-trait Character {
+pub trait Character {
     fn name(&self) -> &'static str;
     fn decimal(&self) -> i32;
     fn character_class(&self) -> CharacterClass;
@@ -23,7 +23,7 @@ trait Character {
 /// such as names and numbers from each other."
 /// See ISO 32000-2:2020 pp. 21-22
 #[derive(Debug, PartialEq, Eq)]
-enum CharacterClass {
+pub enum CharacterClass {
     Regular,
     Delimiter,
     WhiteSpace,
@@ -32,7 +32,7 @@ enum CharacterClass {
 /// White Space Characters in the PDF standard as defined in sec. 7.2, Table 1
 /// (pg. 22) of the PDF standard.
 #[derive(Debug)]
-enum WhiteSpaceCharacter {
+pub enum WhiteSpaceCharacter {
     Null,
     HorizontalTab,
     LineFeed,
@@ -69,9 +69,75 @@ impl Character for WhiteSpaceCharacter {
     }
 }
 
+/// The characters of PDFDocEncoding that differ from ISO Latin-1: codes 0x18
+/// to 0x1F and 0x80 to 0xA0. 0x9F is undefined. See Annex D.2, Table D.2
+const PDF_DOC_ENCODING: [(u8, char); 41] = [
+    (0x18, '\u{02D8}'),
+    (0x19, '\u{02C7}'),
+    (0x1A, '\u{02C6}'),
+    (0x1B, '\u{02D9}'),
+    (0x1C, '\u{02DD}'),
+    (0x1D, '\u{02DB}'),
+    (0x1E, '\u{02DA}'),
+    (0x1F, '\u{02DC}'),
+    (0x80, '\u{2022}'),
+    (0x81, '\u{2020}'),
+    (0x82, '\u{2021}'),
+    (0x83, '\u{2026}'),
+    (0x84, '\u{2014}'),
+    (0x85, '\u{2013}'),
+    (0x86, '\u{0192}'),
+    (0x87, '\u{2044}'),
+    (0x88, '\u{2039}'),
+    (0x89, '\u{203A}'),
+    (0x8A, '\u{2212}'),
+    (0x8B, '\u{2030}'),
+    (0x8C, '\u{201E}'),
+    (0x8D, '\u{201C}'),
+    (0x8E, '\u{201D}'),
+    (0x8F, '\u{2018}'),
+    (0x90, '\u{2019}'),
+    (0x91, '\u{201A}'),
+    (0x92, '\u{2122}'),
+    (0x93, '\u{FB01}'),
+    (0x94, '\u{FB02}'),
+    (0x95, '\u{0141}'),
+    (0x96, '\u{0152}'),
+    (0x97, '\u{0160}'),
+    (0x98, '\u{0178}'),
+    (0x99, '\u{017D}'),
+    (0x9A, '\u{0131}'),
+    (0x9B, '\u{0142}'),
+    (0x9C, '\u{0153}'),
+    (0x9D, '\u{0161}'),
+    (0x9E, '\u{017E}'),
+    (0x9F, '\u{FFFD}'),
+    (0xA0, '\u{20AC}'),
+];
+
+/// The character a byte of a PDFDocEncoding text string stands for.
+pub fn pdf_doc_char(byte: u8) -> char {
+    PDF_DOC_ENCODING
+        .iter()
+        .find(|(code, _)| *code == byte)
+        .map_or(byte as char, |(_, c)| *c)
+}
+
+/// The PDFDocEncoding byte of a character, if it has one.
+pub fn pdf_doc_byte(c: char) -> Option<u8> {
+    if let Some((code, _)) = PDF_DOC_ENCODING.iter().find(|(_, d)| *d == c) {
+        return (*code != 0x9F).then_some(*code);
+    }
+    match c as u32 {
+        0x18..=0x1F | 0x7F | 0x80..=0xA0 | 0xAD => None,
+        code @ 0..=0xFF => Some(code as u8),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::WhiteSpaceCharacter;
+    use super::{pdf_doc_byte, pdf_doc_char, WhiteSpaceCharacter};
     use crate::api::characters::{Character, CharacterClass};
 
     #[test]
@@ -79,4 +145,16 @@ mod tests {
         let null = WhiteSpaceCharacter::Null;
         assert_eq!(null.character_class(), CharacterClass::WhiteSpace)
     }
+
+    #[test]
+    fn test_pdf_doc_encoding() {
+        assert_eq!(pdf_doc_char(b'A'), 'A');
+        assert_eq!(pdf_doc_char(0x84), '—');
+        assert_eq!(pdf_doc_char(0xE4), 'ä');
+        assert_eq!(pdf_doc_byte('€'), Some(0xA0));
+        assert_eq!(pdf_doc_byte('ä'), Some(0xE4));
+        assert_eq!(pdf_doc_byte('\u{80}'), None);
+        assert_eq!(pdf_doc_byte('\u{FFFD}'), None);
+        assert_eq!(pdf_doc_byte('中'), None);
+    }
 }
@@ -0,0 +1,601 @@
+/// ICC profiles, converting colors of ICCBased color spaces to CIE XYZ.
+pub mod icc;
+
+use std::rc::Rc;
+
+use crate::api::{
+    document::Document,
+    function::Function,
+    objects::{Dictionary, PdfObject, Stream},
+};
+
+use icc::{lab_to_xyz, IccProfile, D50};
+
+///
+/// Color spaces as defined in sec. 8.6 (pg. 164). They are divided into three
+/// families:
+///     - Device color spaces: DeviceGray, DeviceRGB and DeviceCMYK
+///     - CIE-based color spaces: CalGray, CalRGB, Lab and ICCBased
+///     - Special color spaces: Indexed, Pattern, Separation and DeviceN
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    DeviceGray,
+    DeviceRGB,
+    DeviceCMYK,
+    CalGray(Dictionary),
+    CalRGB(Dictionary),
+    Lab(Dictionary),
+    ICCBased {
+        components: usize,
+        alternate: Option<Box<ColorSpace>>,
+        profile: Rc<Stream>,
+        /// The profile read from the stream, if it can be used to convert
+        /// colors; otherwise they are converted through the alternate space.
+        icc: Option<Rc<IccProfile>>,
+    },
+    Indexed {
+        base: Box<ColorSpace>,
+        hival: usize,
+        lookup: Rc<Vec<u8>>,
+    },
+    Separation {
+        name: String,
+        alternate: Box<ColorSpace>,
+        tint_transform: Rc<PdfObject>,
+        /// The tint transform loaded as a function, if it is valid.
+        function: Option<Rc<Function>>,
+    },
+    DeviceN {
+        names: Vec<String>,
+        alternate: Box<ColorSpace>,
+        tint_transform: Rc<PdfObject>,
+        function: Option<Rc<Function>>,
+    },
+    Pattern(Option<Box<ColorSpace>>),
+}
+
+impl ColorSpace {
+    /// Reads a color space from a name or an array. Names other than the device
+    /// color space names are looked up in the /ColorSpace resource dictionary.
+    /// See sec. 8.6.3 (pg. 167)
+    pub fn from_object(
+        doc: &Document,
+        object: &PdfObject,
+        resources: Option<&Dictionary>,
+    ) -> Option<ColorSpace> {
+        match doc.resolve(object) {
+            PdfObject::Name(name) => match name.as_str() {
+                "DeviceGray" | "G" | "CalGray" => Some(ColorSpace::DeviceGray),
+                "DeviceRGB" | "RGB" | "CalRGB" => Some(ColorSpace::DeviceRGB),
+                "DeviceCMYK" | "CMYK" => Some(ColorSpace::DeviceCMYK),
+                "Pattern" => Some(ColorSpace::Pattern(None)),
+                other => {
+                    let named = resources
+                        .and_then(|r| doc.resolve_dict(r, "ColorSpace"))
+                        .and_then(|spaces| spaces.get(other))?;
+                    ColorSpace::from_object(doc, named, None)
+                }
+            },
+            PdfObject::Array(items) => ColorSpace::from_array(doc, items, resources),
+            _ => None,
+        }
+    }
+
+    fn from_array(
+        doc: &Document,
+        items: &[PdfObject],
+        resources: Option<&Dictionary>,
+    ) -> Option<ColorSpace> {
+        let family = doc.resolve(items.first()?).as_name()?;
+        let param = |i: usize| items.get(i).map(|item| doc.resolve(item));
+        let dict_param = |i: usize| {
+            param(i)
+                .and_then(PdfObject::as_dict)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let space_param = |i: usize| {
+            param(i)
+                .and_then(|space| ColorSpace::from_object(doc, space, resources))
+                .map(Box::new)
+        };
+
+        match family {
+            "DeviceGray" | "G" => Some(ColorSpace::DeviceGray),
+            "DeviceRGB" | "RGB" => Some(ColorSpace::DeviceRGB),
+            "DeviceCMYK" | "CMYK" => Some(ColorSpace::DeviceCMYK),
+            "CalGray" => Some(ColorSpace::CalGray(dict_param(1))),
+            "CalRGB" => Some(ColorSpace::CalRGB(dict_param(1))),
+            "Lab" => Some(ColorSpace::Lab(dict_param(1))),
+            "ICCBased" => {
+                let profile = param(1)?.as_stream()?.clone();
+                let components = profile.dict.get_i64("N").unwrap_or(3) as usize;
+                let alternate = doc
+                    .resolve_key(&profile.dict, "Alternate")
+                    .and_then(|alt| ColorSpace::from_object(doc, alt, resources))
+                    .map(Box::new);
+                let icc = doc
+                    .decode_stream(&profile)
+                    .ok()
+                    .and_then(|data| IccProfile::parse(&data))
+                    .filter(|icc| icc.components == components)
+                    .map(Rc::new);
+                Some(ColorSpace::ICCBased {
+                    components,
+                    alternate,
+                    profile: Rc::new(profile),
+                    icc,
+                })
+            }
+            "Indexed" | "I" => {
+                let base = space_param(1)?;
+                let hival = param(2)?.as_i64()?.clamp(0, 255) as usize;
+                let lookup = match param(3)? {
+                    PdfObject::String(bytes) => bytes.clone(),
+                    PdfObject::Stream(stream) => stream.decode().ok()?,
+                    _ => return None,
+                };
+                Some(ColorSpace::Indexed {
+                    base,
+                    hival,
+                    lookup: Rc::new(lookup),
+                })
+            }
+            "Separation" => Some(ColorSpace::Separation {
+                name: param(1)?.as_name()?.to_string(),
+                alternate: space_param(2)?,
+                tint_transform: Rc::new(param(3)?.clone()),
+                function: Function::load(doc, param(3)?).map(Rc::new),
+            }),
+            "DeviceN" => Some(ColorSpace::DeviceN {
+                names: param(1)?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|n| n.as_name().map(String::from))
+                    .collect(),
+                alternate: space_param(2)?,
+                tint_transform: Rc::new(param(3)?.clone()),
+                function: Function::load(doc, param(3)?).map(Rc::new),
+            }),
+            "Pattern" => Some(ColorSpace::Pattern(space_param(1))),
+            _ => None,
+        }
+    }
+
+    /// The number of color components used to specify a color in this space.
+    pub fn components(&self) -> usize {
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => 1,
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) | ColorSpace::Lab(_) => 3,
+            ColorSpace::DeviceCMYK => 4,
+            ColorSpace::ICCBased { components, .. } => *components,
+            ColorSpace::Indexed { .. } | ColorSpace::Separation { .. } => 1,
+            ColorSpace::DeviceN { names, .. } => names.len(),
+            ColorSpace::Pattern(base) => base.as_ref().map(|b| b.components()).unwrap_or(0),
+        }
+    }
+
+    /// The color that is selected when this color space is set with "CS" or "cs".
+    /// See sec. 8.6.8, Table 73 (pg. 205)
+    pub fn initial_color(&self) -> Color {
+        let components = match self {
+            ColorSpace::DeviceCMYK => vec![0.0, 0.0, 0.0, 1.0],
+            ColorSpace::Lab(dict) => {
+                // 0.0 for L*, clamped to the /Range for a* and b*.
+                let range = dict
+                    .get("Range")
+                    .and_then(|r| r.as_numbers())
+                    .unwrap_or(vec![-100.0, 100.0, -100.0, 100.0]);
+                vec![
+                    0.0,
+                    0.0f64.clamp(range[0], range[1]),
+                    0.0f64.clamp(range[2], range[3]),
+                ]
+            }
+            ColorSpace::Separation { .. } | ColorSpace::DeviceN { .. } => {
+                vec![1.0; self.components()]
+            }
+            ColorSpace::Pattern(_) => vec![],
+            _ => vec![0.0; self.components()],
+        };
+        Color::new(components)
+    }
+
+    /// Converts a color in this space to DeviceRGB, with each component in the
+    /// range 0.0 to 1.0. CIE-based color spaces, including ICCBased spaces with
+    /// a usable profile, are converted to sRGB through CIE XYZ. Device color
+    /// spaces are converted as described in sec. 10.4 (pg. 452), and special
+    /// color spaces through their base or alternate color space.
+    pub fn to_rgb(&self, components: &[f64]) -> [f64; 3] {
+        let c = |i: usize| components.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        match self {
+            ColorSpace::DeviceGray => [c(0); 3],
+            ColorSpace::DeviceRGB => [c(0), c(1), c(2)],
+            ColorSpace::DeviceCMYK => [
+                1.0 - (c(0) + c(3)).min(1.0),
+                1.0 - (c(1) + c(3)).min(1.0),
+                1.0 - (c(2) + c(3)).min(1.0),
+            ],
+            ColorSpace::CalGray(dict) | ColorSpace::CalRGB(dict) | ColorSpace::Lab(dict) => {
+                let xyz = self.to_xyz(components).unwrap_or_default();
+                xyz_to_rgb(xyz, white_point(dict))
+            }
+            ColorSpace::ICCBased {
+                components: n,
+                alternate,
+                icc,
+                ..
+            } => match (icc, alternate) {
+                (Some(icc), _) => xyz_to_rgb(icc.to_xyz(components), D50),
+                (None, Some(alternate)) => alternate.to_rgb(components),
+                (None, None) => ColorSpace::device(*n).to_rgb(components),
+            },
+            ColorSpace::Indexed { base, .. } => base.to_rgb(&self.base_components(components)),
+            ColorSpace::Separation { alternate, .. } | ColorSpace::DeviceN { alternate, .. } => {
+                match self.tint_transform(components) {
+                    Some(values) => alternate.to_rgb(&values),
+                    None => [1.0 - self.max_tint(components); 3],
+                }
+            }
+            ColorSpace::Pattern(_) => [0.0; 3],
+        }
+    }
+
+    /// Converts a color in this space to DeviceGray. See sec. 10.4.2.2 and
+    /// 10.4.2.3 (pg. 453)
+    pub fn to_gray(&self, components: &[f64]) -> f64 {
+        let c = |i: usize| components.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        match self {
+            ColorSpace::DeviceGray => c(0),
+            ColorSpace::DeviceCMYK => {
+                1.0 - (0.3 * c(0) + 0.59 * c(1) + 0.11 * c(2) + c(3)).min(1.0)
+            }
+            ColorSpace::Indexed { base, .. } => base.to_gray(&self.base_components(components)),
+            ColorSpace::Separation { alternate, .. } | ColorSpace::DeviceN { alternate, .. } => {
+                match self.tint_transform(components) {
+                    Some(values) => alternate.to_gray(&values),
+                    None => 1.0 - self.max_tint(components),
+                }
+            }
+            _ => {
+                let [r, g, b] = self.to_rgb(components);
+                0.3 * r + 0.59 * g + 0.11 * b
+            }
+        }
+    }
+
+    /// Converts a color in this space to DeviceCMYK. Colors that are not
+    /// already CMYK are converted from gray or RGB, with black generation and
+    /// undercolor removal taking all of the gray component. See sec. 10.4.2.4
+    /// (pg. 454)
+    pub fn to_cmyk(&self, components: &[f64]) -> [f64; 4] {
+        let c = |i: usize| components.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => {
+                [0.0, 0.0, 0.0, 1.0 - self.to_gray(components)]
+            }
+            ColorSpace::DeviceCMYK => [c(0), c(1), c(2), c(3)],
+            ColorSpace::ICCBased {
+                components: 4,
+                icc: None,
+                alternate: None,
+                ..
+            } => [c(0), c(1), c(2), c(3)],
+            ColorSpace::ICCBased {
+                alternate: Some(alternate),
+                icc: None,
+                ..
+            } => alternate.to_cmyk(components),
+            ColorSpace::Indexed { base, .. } => base.to_cmyk(&self.base_components(components)),
+            ColorSpace::Separation { alternate, .. } | ColorSpace::DeviceN { alternate, .. } => {
+                match self.tint_transform(components) {
+                    Some(values) => alternate.to_cmyk(&values),
+                    None => [0.0, 0.0, 0.0, self.max_tint(components)],
+                }
+            }
+            _ => {
+                let [r, g, b] = self.to_rgb(components);
+                let [c, m, y] = [1.0 - r, 1.0 - g, 1.0 - b];
+                let k = c.min(m).min(y);
+                [c - k, m - k, y - k, k]
+            }
+        }
+    }
+
+    /// Converts a color in a CIE-based color space to CIE XYZ, relative to the
+    /// white point of the space (D50 for ICCBased spaces), or None for other
+    /// color spaces. See sec. 8.6.5 (pg. 169)
+    pub fn to_xyz(&self, components: &[f64]) -> Option<[f64; 3]> {
+        let c = |i: usize| components.get(i).copied().unwrap_or(0.0);
+        let numbers = |dict: &Dictionary, key: &str| dict.get(key).and_then(|n| n.as_numbers());
+        match self {
+            ColorSpace::CalGray(dict) => {
+                let gamma = dict.get("Gamma").and_then(|g| g.as_f64()).unwrap_or(1.0);
+                let y = c(0).clamp(0.0, 1.0).powf(gamma);
+                Some(white_point(dict).map(|w| w * y))
+            }
+            ColorSpace::CalRGB(dict) => {
+                let gamma = numbers(dict, "Gamma")
+                    .filter(|g| g.len() == 3)
+                    .unwrap_or(vec![1.0; 3]);
+                let matrix = numbers(dict, "Matrix")
+                    .filter(|m| m.len() == 9)
+                    .unwrap_or(vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+                let abc = [0, 1, 2].map(|i| c(i).clamp(0.0, 1.0).powf(gamma[i]));
+                // The matrix lists the XYZ of each of A, B and C in turn.
+                Some([0, 1, 2].map(|j| (0..3).map(|i| matrix[3 * i + j] * abc[i]).sum()))
+            }
+            ColorSpace::Lab(dict) => {
+                let range = numbers(dict, "Range")
+                    .filter(|r| r.len() == 4)
+                    .unwrap_or(vec![-100.0, 100.0, -100.0, 100.0]);
+                let lab = [
+                    c(0).clamp(0.0, 100.0),
+                    c(1).clamp(range[0], range[1]),
+                    c(2).clamp(range[2], range[3]),
+                ];
+                // Relative to the white point of the space rather than D50.
+                let white = white_point(dict);
+                Some([0, 1, 2].map(|i| lab_to_xyz(lab)[i] / D50[i] * white[i]))
+            }
+            ColorSpace::ICCBased { icc: Some(icc), .. } => Some(icc.to_xyz(components)),
+            _ => None,
+        }
+    }
+
+    /// Whether colors in this space are specified as RGB: DeviceRGB, CalRGB,
+    /// an RGB ICC profile, or an indexed or pattern space based on one.
+    pub fn is_rgb(&self) -> bool {
+        match self {
+            ColorSpace::DeviceRGB | ColorSpace::CalRGB(_) => true,
+            ColorSpace::ICCBased {
+                components,
+                icc,
+                alternate,
+                ..
+            } => match (icc, alternate) {
+                (Some(icc), _) => &icc.color_space == b"RGB ",
+                (None, Some(alternate)) => alternate.is_rgb(),
+                (None, None) => *components == 3,
+            },
+            ColorSpace::Indexed { base, .. } => base.is_rgb(),
+            ColorSpace::Pattern(Some(base)) => base.is_rgb(),
+            _ => false,
+        }
+    }
+
+    /// The device color space with the given number of components.
+    fn device(components: usize) -> ColorSpace {
+        match components {
+            1 => ColorSpace::DeviceGray,
+            4 => ColorSpace::DeviceCMYK,
+            _ => ColorSpace::DeviceRGB,
+        }
+    }
+
+    /// The base color space components of the lookup table entry that an
+    /// Indexed color selects. See sec. 8.6.6.3 (pg. 182)
+    fn base_components(&self, components: &[f64]) -> Vec<f64> {
+        let ColorSpace::Indexed {
+            base,
+            hival,
+            lookup,
+        } = self
+        else {
+            return components.to_vec();
+        };
+        let index =
+            (components.first().copied().unwrap_or(0.0).round().max(0.0) as usize).min(*hival);
+        let n = base.components();
+        // Lookup bytes span the range of each component of the base space.
+        let ranges = match base.as_ref() {
+            ColorSpace::Lab(dict) => {
+                let range = dict
+                    .get("Range")
+                    .and_then(|r| r.as_numbers())
+                    .filter(|r| r.len() == 4)
+                    .unwrap_or(vec![-100.0, 100.0, -100.0, 100.0]);
+                vec![(0.0, 100.0), (range[0], range[1]), (range[2], range[3])]
+            }
+            _ => vec![(0.0, 1.0); n],
+        };
+        (0..n)
+            .map(|i| {
+                let (low, high) = ranges.get(i).copied().unwrap_or((0.0, 1.0));
+                let byte = lookup.get(index * n + i).copied().unwrap_or(0) as f64;
+                low + byte / 255.0 * (high - low)
+            })
+            .collect()
+    }
+
+    /// The alternate color space components of a Separation or DeviceN color,
+    /// given by the tint transform. See sec. 8.6.6.4 (pg. 184)
+    fn tint_transform(&self, components: &[f64]) -> Option<Vec<f64>> {
+        match self {
+            ColorSpace::Separation { function, .. } | ColorSpace::DeviceN { function, .. } => {
+                Some(function.as_ref()?.eval(components))
+            }
+            _ => None,
+        }
+    }
+
+    /// Without a tint transform, a tint is shown as a shade of gray, where a
+    /// tint of 1.0 is the full colorant.
+    fn max_tint(&self, components: &[f64]) -> f64 {
+        components
+            .iter()
+            .fold(0.0f64, |a, &b| a.max(b))
+            .clamp(0.0, 1.0)
+    }
+
+    /// Whether colors in this space are shades of gray, so that they can be
+    /// stored with a single component.
+    pub fn is_gray(&self) -> bool {
+        match self {
+            ColorSpace::DeviceGray | ColorSpace::CalGray(_) => true,
+            ColorSpace::ICCBased {
+                components,
+                alternate,
+                ..
+            } => match alternate {
+                Some(alternate) => alternate.is_gray(),
+                None => *components == 1,
+            },
+            // Tints without a tint transform are shown as shades of gray.
+            ColorSpace::Separation {
+                alternate,
+                function,
+                ..
+            } => function.is_none() || alternate.is_gray(),
+            _ => false,
+        }
+    }
+}
+
+/// The /WhitePoint of a CIE-based color space; D65 when it is missing.
+fn white_point(dict: &Dictionary) -> [f64; 3] {
+    dict.get("WhitePoint")
+        .and_then(|w| w.as_numbers())
+        .and_then(|w| <[f64; 3]>::try_from(w).ok())
+        .filter(|w| w[1] > 0.0)
+        .unwrap_or(D65)
+}
+
+/// The white point of sRGB, in CIE XYZ.
+const D65: [f64; 3] = [0.9505, 1.0, 1.089];
+
+/// Converts CIE XYZ relative to a white point to sRGB, adapting the white point
+/// to D65 with the Bradford transform.
+pub fn xyz_to_rgb(xyz: [f64; 3], white: [f64; 3]) -> [f64; 3] {
+    const BRADFORD: [[f64; 3]; 3] = [
+        [0.8951, 0.2664, -0.1614],
+        [-0.7502, 1.7135, 0.0367],
+        [0.0389, -0.0685, 1.0296],
+    ];
+    const BRADFORD_INVERSE: [[f64; 3]; 3] = [
+        [0.9870, -0.1471, 0.1600],
+        [0.4323, 0.5184, 0.0493],
+        [-0.0085, 0.0400, 0.9685],
+    ];
+    let apply =
+        |m: &[[f64; 3]; 3], v: [f64; 3]| m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2]);
+    let (source, target) = (apply(&BRADFORD, white), apply(&BRADFORD, D65));
+    let cone = apply(&BRADFORD, xyz);
+    let adapted = [0, 1, 2].map(|i| cone[i] * target[i] / source[i]);
+    let [x, y, z] = apply(&BRADFORD_INVERSE, adapted);
+
+    let gamma = |v: f64| {
+        let v = v.clamp(0.0, 1.0);
+        if v <= 0.0031308 {
+            12.92 * v
+        } else {
+            1.055 * v.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    [
+        gamma(3.2406 * x - 1.5372 * y - 0.4986 * z),
+        gamma(-0.9689 * x + 1.8758 * y + 0.0415 * z),
+        gamma(0.0557 * x - 0.2040 * y + 1.0570 * z),
+    ]
+}
+
+///
+/// A color value: the components in the current color space, and for the
+/// Pattern color space, the name of the pattern resource.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Color {
+    pub components: Vec<f64>,
+    pub pattern: Option<String>,
+    /// The pattern the name refers to, looked up in the resources in effect
+    /// where the color was set.
+    pub pattern_object: Option<Rc<PdfObject>>,
+}
+
+impl Color {
+    pub fn new(components: Vec<f64>) -> Color {
+        Color {
+            components,
+            pattern: None,
+            pattern_object: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::read::objects::parse_object;
+
+    #[test]
+    fn test_initial_color() {
+        assert_eq!(
+            ColorSpace::DeviceCMYK.initial_color().components,
+            vec![0.0, 0.0, 0.0, 1.0]
+        );
+        assert_eq!(ColorSpace::DeviceRGB.components(), 3);
+        assert_eq!(
+            ColorSpace::Pattern(None).initial_color().components.len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_to_rgb() {
+        assert_eq!(
+            ColorSpace::DeviceCMYK.to_rgb(&[0.0, 1.0, 1.0, 0.0]),
+            [1.0, 0.0, 0.0]
+        );
+        let indexed = ColorSpace::Indexed {
+            base: Box::new(ColorSpace::DeviceRGB),
+            hival: 1,
+            lookup: Rc::new(vec![0, 0, 0, 255, 0, 255]),
+        };
+        assert_eq!(indexed.to_rgb(&[1.0]), [1.0, 0.0, 1.0]);
+        let white = ColorSpace::Lab(Dictionary::new()).to_rgb(&[100.0, 0.0, 0.0]);
+        assert!(white.iter().all(|c| (c - 1.0).abs() < 0.01));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let doc = Document::from_bytes(b"%PDF-1.7\ntrailer << >>\n".to_vec()).unwrap();
+        let space = |source: &[u8]| {
+            let (_, object) = parse_object(source).unwrap();
+            ColorSpace::from_object(&doc, &object, None).unwrap()
+        };
+
+        // A spot color whose tint transform gives magenta in CMYK.
+        let spot = space(
+            b"[/Separation /Spot /DeviceCMYK \
+              << /FunctionType 2 /Domain [0 1] /C0 [0 0 0 0] /C1 [0 1 0 0] /N 1 >>]",
+        );
+        assert!(!spot.is_gray());
+        assert_eq!(spot.to_cmyk(&[0.5]), [0.0, 0.5, 0.0, 0.0]);
+        assert_eq!(spot.to_rgb(&[1.0]), [1.0, 0.0, 1.0]);
+
+        // A CalGray white point of D50 is adapted to the white of sRGB.
+        let cal_gray = space(b"[/CalGray << /WhitePoint [0.9642 1 0.8249] /Gamma 2.2 >>]");
+        let white = cal_gray.to_rgb(&[1.0]);
+        assert!(white.iter().all(|c| (c - 1.0).abs() < 0.01));
+        assert!((cal_gray.to_rgb(&[0.5])[0] - 0.5).abs() < 0.05);
+        let cal_rgb = space(
+            b"[/CalRGB << /WhitePoint [0.9505 1 1.089] \
+              /Matrix [0.4124 0.2126 0.0193 0.3576 0.7152 0.1192 0.1805 0.0722 0.9505] >>]",
+        );
+        assert!(cal_rgb.is_rgb());
+        let red = cal_rgb.to_rgb(&[1.0, 0.0, 0.0]);
+        assert!(red[0] > 0.99 && red[1] < 0.01 && red[2] < 0.01);
+
+        assert_eq!(
+            ColorSpace::DeviceRGB.to_cmyk(&[1.0, 0.0, 0.0]),
+            [0.0, 1.0, 1.0, 0.0]
+        );
+        assert_eq!(
+            ColorSpace::DeviceGray.to_cmyk(&[0.25]),
+            [0.0, 0.0, 0.0, 0.75]
+        );
+        assert_eq!(ColorSpace::DeviceCMYK.to_gray(&[0.0, 0.0, 0.0, 0.5]), 0.5);
+        assert!(!ColorSpace::DeviceCMYK.is_rgb());
+    }
+}
@@ -0,0 +1,582 @@
+/// The white point of the profile connection space, D50, in CIE XYZ.
+pub const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// A CLUT with more grid points than this, in total, is not loaded.
+const MAX_CLUT_SIZE: usize = 1 << 24;
+
+///
+/// An ICC profile, read far enough to convert colors from the profile's color
+/// space to the profile connection space (PCS): CIE XYZ or L*a*b*, relative to
+/// D50. See sec. 8.6.5.5 (pg. 177) and ISO 15076-1.
+///
+/// Conversions use the A2B0 (perceptual) transform when the profile has one,
+/// and otherwise the matrix and tone curves of gray and RGB profiles.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccProfile {
+    /// The data color space signature, such as "RGB " or "CMYK".
+    pub color_space: [u8; 4],
+    /// The number of color components of the data color space.
+    pub components: usize,
+    transform: Transform,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Transform {
+    /// A gray profile: the gray tone curve gives the luminance of the PCS
+    /// white.
+    Gray(Curve),
+    /// An RGB profile: tone curves, then a matrix whose columns are the XYZ
+    /// values of the primaries.
+    Matrix {
+        curves: [Curve; 3],
+        matrix: [[f64; 3]; 3],
+    },
+    Lut(Lut),
+}
+
+///
+/// A lut8, lut16 or lutAToB transform: curves, a color lookup table and more
+/// curves, ending in an encoded PCS value.
+///
+#[derive(Debug, Clone, PartialEq)]
+struct Lut {
+    input: Vec<Curve>,
+    clut: Option<Clut>,
+    /// For lutAToB, the curves and matrix applied after the CLUT.
+    middle: Vec<Curve>,
+    matrix: Option<[f64; 12]>,
+    output: Vec<Curve>,
+    encoding: PcsEncoding,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PcsEncoding {
+    Xyz,
+    /// The L*a*b* encoding of lut8 and lutAToB transforms.
+    Lab,
+    /// The legacy 16-bit L*a*b* encoding of lut16 transforms.
+    Lab16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Curve {
+    Identity,
+    Gamma(f64),
+    /// Values between 0 and 1, sampled evenly over the input range.
+    Table(Vec<f64>),
+    Parametric(u16, Vec<f64>),
+}
+
+///
+/// A color lookup table: output values on a grid over the inputs, with the
+/// first input varying slowest.
+///
+#[derive(Debug, Clone, PartialEq)]
+struct Clut {
+    grid: Vec<usize>,
+    outputs: usize,
+    values: Vec<f64>,
+}
+
+impl IccProfile {
+    pub fn parse(data: &[u8]) -> Option<IccProfile> {
+        let color_space: [u8; 4] = data.get(16..20)?.try_into().ok()?;
+        let pcs_lab = data.get(20..24)? == b"Lab ";
+        let components = match &color_space {
+            b"GRAY" => 1,
+            b"RGB " | b"Lab " | b"XYZ " | b"YCbr" | b"HSV " | b"HLS " | b"CMY " | b"Yxy "
+            | b"Luv " => 3,
+            b"CMYK" => 4,
+            [b'0'..=b'9' | b'A'..=b'F', b'C', b'L', b'R'] => {
+                (color_space[0] as char).to_digit(16)? as usize
+            }
+            _ => return None,
+        };
+
+        let count = read_u32(data, 128)? as usize;
+        let tag = |signature: &[u8; 4]| {
+            (0..count.min(1024)).find_map(|i| {
+                let entry = 132 + 12 * i;
+                if data.get(entry..entry + 4)? != signature {
+                    return None;
+                }
+                let offset = read_u32(data, entry + 4)? as usize;
+                let size = read_u32(data, entry + 8)? as usize;
+                data.get(offset..offset.checked_add(size)?)
+            })
+        };
+
+        let lut = tag(b"A2B0")
+            .or_else(|| tag(b"A2B1"))
+            .and_then(|t| read_lut(t, components, pcs_lab));
+        let transform = match lut {
+            Some(lut) => Transform::Lut(lut),
+            None if components == 1 => Transform::Gray(read_curve(tag(b"kTRC")?)?.0),
+            None if components == 3 && !pcs_lab => {
+                let column = |signature: &[u8; 4]| read_xyz(tag(signature)?);
+                let [r, g, b] = [column(b"rXYZ")?, column(b"gXYZ")?, column(b"bXYZ")?];
+                let curve = |signature: &[u8; 4]| Some(read_curve(tag(signature)?)?.0);
+                Transform::Matrix {
+                    curves: [curve(b"rTRC")?, curve(b"gTRC")?, curve(b"bTRC")?],
+                    matrix: [0, 1, 2].map(|i| [r[i], g[i], b[i]]),
+                }
+            }
+            None => return None,
+        };
+        Some(IccProfile {
+            color_space,
+            components,
+            transform,
+        })
+    }
+
+    /// Converts a color, with components between 0 and 1, to CIE XYZ relative
+    /// to D50.
+    pub fn to_xyz(&self, components: &[f64]) -> [f64; 3] {
+        let input: Vec<f64> = (0..self.components)
+            .map(|i| components.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0))
+            .collect();
+        match &self.transform {
+            Transform::Gray(curve) => D50.map(|w| w * curve.eval(input[0])),
+            Transform::Matrix { curves, matrix } => {
+                let linear = [0, 1, 2].map(|i| curves[i].eval(input[i]));
+                matrix.map(|row| row.iter().zip(linear).map(|(m, v)| m * v).sum())
+            }
+            Transform::Lut(lut) => lut.eval(&input),
+        }
+    }
+}
+
+impl Lut {
+    fn eval(&self, input: &[f64]) -> [f64; 3] {
+        let mut values: Vec<f64> = input
+            .iter()
+            .zip(&self.input)
+            .map(|(&v, curve)| curve.eval(v))
+            .collect();
+        if let Some(clut) = &self.clut {
+            values = clut.eval(&values);
+        }
+        if !self.middle.is_empty() {
+            for (v, curve) in values.iter_mut().zip(&self.middle) {
+                *v = curve.eval(*v);
+            }
+        }
+        if let (Some(m), [a, b, c, ..]) = (self.matrix, values.as_slice()) {
+            let (a, b, c) = (*a, *b, *c);
+            values = vec![
+                (m[0] * a + m[1] * b + m[2] * c + m[9]).clamp(0.0, 1.0),
+                (m[3] * a + m[4] * b + m[5] * c + m[10]).clamp(0.0, 1.0),
+                (m[6] * a + m[7] * b + m[8] * c + m[11]).clamp(0.0, 1.0),
+            ];
+        }
+        for (v, curve) in values.iter_mut().zip(&self.output) {
+            *v = curve.eval(*v);
+        }
+        let v = |i: usize| values.get(i).copied().unwrap_or(0.0);
+        match self.encoding {
+            // u1Fixed15 numbers: 1.0 is encoded as 0x8000.
+            PcsEncoding::Xyz => [0, 1, 2].map(|i| v(i) * 65535.0 / 32768.0),
+            PcsEncoding::Lab => {
+                lab_to_xyz([v(0) * 100.0, v(1) * 255.0 - 128.0, v(2) * 255.0 - 128.0])
+            }
+            PcsEncoding::Lab16 => {
+                let scale = 65535.0 / 65280.0;
+                lab_to_xyz([
+                    v(0) * scale * 100.0,
+                    v(1) * scale * 255.0 - 128.0,
+                    v(2) * scale * 255.0 - 128.0,
+                ])
+            }
+        }
+    }
+}
+
+impl Curve {
+    fn eval(&self, x: f64) -> f64 {
+        let y = match self {
+            Curve::Identity => x,
+            Curve::Gamma(gamma) => x.powf(*gamma),
+            Curve::Table(table) => {
+                let position = x * (table.len() - 1) as f64;
+                let i = (position.floor() as usize).min(table.len() - 1);
+                let next = (i + 1).min(table.len() - 1);
+                table[i] + (table[next] - table[i]) * (position - i as f64)
+            }
+            Curve::Parametric(kind, p) => {
+                let p = |i: usize| p.get(i).copied().unwrap_or(0.0);
+                let power = |base: f64| base.max(0.0).powf(p(0));
+                match kind {
+                    0 => power(x),
+                    1 if x >= -p(2) / p(1) => power(p(1) * x + p(2)),
+                    1 => 0.0,
+                    2 if x >= -p(2) / p(1) => power(p(1) * x + p(2)) + p(3),
+                    2 => p(3),
+                    3 if x >= p(4) => power(p(1) * x + p(2)),
+                    3 => p(3) * x,
+                    4 if x >= p(4) => power(p(1) * x + p(2)) + p(5),
+                    4 => p(3) * x + p(6),
+                    _ => x,
+                }
+            }
+        };
+        y.clamp(0.0, 1.0)
+    }
+}
+
+impl Clut {
+    /// Interpolates the table multilinearly between the grid points around
+    /// the input.
+    fn eval(&self, input: &[f64]) -> Vec<f64> {
+        let n = self.grid.len();
+        let mut base = 0;
+        let mut stride = self.outputs;
+        let mut offsets = vec![(0, 0.0); n];
+        for i in (0..n).rev() {
+            let size = self.grid[i];
+            let position = input.get(i).copied().unwrap_or(0.0) * (size - 1) as f64;
+            let low = (position.floor() as usize).min(size.saturating_sub(2));
+            let fraction = (position - low as f64).clamp(0.0, 1.0);
+            base += low * stride;
+            // Grids of one point do not vary along their input.
+            offsets[i] = (if size > 1 { stride } else { 0 }, fraction);
+            stride *= size;
+        }
+        let mut output = vec![0.0; self.outputs];
+        for corner in 0..1usize << n {
+            let mut weight = 1.0;
+            let mut index = base;
+            for (i, &(offset, fraction)) in offsets.iter().enumerate() {
+                if corner & (1 << i) != 0 {
+                    weight *= fraction;
+                    index += offset;
+                } else {
+                    weight *= 1.0 - fraction;
+                }
+            }
+            if weight == 0.0 {
+                continue;
+            }
+            for (k, value) in output.iter_mut().enumerate() {
+                *value += weight * self.values.get(index + k).copied().unwrap_or(0.0);
+            }
+        }
+        output
+    }
+}
+
+/// Converts CIE L*a*b* to XYZ relative to the D50 white point.
+pub fn lab_to_xyz([l, a, b]: [f64; 3]) -> [f64; 3] {
+    let f = |t: f64| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            108.0 / 841.0 * (t - 4.0 / 29.0)
+        }
+    };
+    let m = (l + 16.0) / 116.0;
+    [
+        D50[0] * f(m + a / 500.0),
+        D50[1] * f(m),
+        D50[2] * f(m - b / 200.0),
+    ]
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads an s15Fixed16Number.
+fn read_fixed(data: &[u8], offset: usize) -> Option<f64> {
+    Some(read_u32(data, offset)? as i32 as f64 / 65536.0)
+}
+
+/// Reads an XYZType tag.
+fn read_xyz(data: &[u8]) -> Option<[f64; 3]> {
+    if data.get(..4)? != b"XYZ " {
+        return None;
+    }
+    Some([
+        read_fixed(data, 8)?,
+        read_fixed(data, 12)?,
+        read_fixed(data, 16)?,
+    ])
+}
+
+/// Reads a curveType or parametricCurveType, returning the curve and its size
+/// in bytes.
+fn read_curve(data: &[u8]) -> Option<(Curve, usize)> {
+    match data.get(..4)? {
+        b"curv" => {
+            let count = read_u32(data, 8)? as usize;
+            let curve = match count {
+                0 => Curve::Identity,
+                1 => Curve::Gamma(read_u16(data, 12)? as f64 / 256.0),
+                _ => Curve::Table(
+                    (0..count)
+                        .map(|i| Some(read_u16(data, 12 + 2 * i)? as f64 / 65535.0))
+                        .collect::<Option<_>>()?,
+                ),
+            };
+            Some((curve, 12 + 2 * count))
+        }
+        b"para" => {
+            let kind = read_u16(data, 8)?;
+            let count = match kind {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return None,
+            };
+            let params = (0..count)
+                .map(|i| read_fixed(data, 12 + 4 * i))
+                .collect::<Option<_>>()?;
+            Some((Curve::Parametric(kind, params), 12 + 4 * count))
+        }
+        _ => None,
+    }
+}
+
+/// Reads consecutive curves, each starting on a 4-byte boundary.
+fn read_curves(data: &[u8], offset: usize, count: usize) -> Option<Vec<Curve>> {
+    let mut offset = offset;
+    let mut curves = vec![];
+    for _ in 0..count {
+        let (curve, size) = read_curve(data.get(offset..)?)?;
+        curves.push(curve);
+        offset += size.div_ceil(4) * 4;
+    }
+    Some(curves)
+}
+
+/// Reads a CLUT of values with the given number of bytes each.
+fn read_clut(
+    data: &[u8],
+    offset: usize,
+    grid: Vec<usize>,
+    outputs: usize,
+    bytes: usize,
+) -> Option<Clut> {
+    let points = grid
+        .iter()
+        .try_fold(1usize, |total, &size| total.checked_mul(size))
+        .filter(|&points| points <= MAX_CLUT_SIZE)?;
+    let max = if bytes == 1 { 255.0 } else { 65535.0 };
+    let values = (0..points * outputs)
+        .map(|i| {
+            let at = offset + i * bytes;
+            Some(
+                match bytes {
+                    1 => *data.get(at)? as f64,
+                    _ => read_u16(data, at)? as f64,
+                } / max,
+            )
+        })
+        .collect::<Option<_>>()?;
+    Some(Clut {
+        grid,
+        outputs,
+        values,
+    })
+}
+
+/// Reads a lut8Type, lut16Type or lutAToBType tag.
+fn read_lut(data: &[u8], components: usize, pcs_lab: bool) -> Option<Lut> {
+    let inputs = *data.get(8)? as usize;
+    let outputs = *data.get(9)? as usize;
+    if inputs != components || outputs != 3 {
+        return None;
+    }
+    match data.get(..4)? {
+        b"mft1" | b"mft2" => {
+            let lut16 = data.get(..4)? == b"mft2";
+            let grid = *data.get(10)? as usize;
+            let (bytes, input_entries, output_entries, start) = if lut16 {
+                (
+                    2,
+                    read_u16(data, 48)? as usize,
+                    read_u16(data, 50)? as usize,
+                    52,
+                )
+            } else {
+                (1, 256, 256, 48)
+            };
+            let tables = |offset: usize, count: usize, entries: usize| {
+                (0..count)
+                    .map(|i| {
+                        let table =
+                            read_clut(data, offset + i * entries * bytes, vec![entries], 1, bytes)?;
+                        Some(Curve::Table(table.values))
+                    })
+                    .collect::<Option<Vec<_>>>()
+            };
+            let input = tables(start, inputs, input_entries)?;
+            let clut_offset = start + inputs * input_entries * bytes;
+            let clut = read_clut(data, clut_offset, vec![grid; inputs], outputs, bytes)?;
+            let output_offset = clut_offset + clut.values.len() * bytes;
+            let output = tables(output_offset, outputs, output_entries)?;
+            Some(Lut {
+                input,
+                clut: Some(clut),
+                middle: vec![],
+                matrix: None,
+                output,
+                encoding: match (pcs_lab, lut16) {
+                    (false, _) => PcsEncoding::Xyz,
+                    (true, false) => PcsEncoding::Lab,
+                    (true, true) => PcsEncoding::Lab16,
+                },
+            })
+        }
+        b"mAB " => {
+            let offset = |at: usize| read_u32(data, at).map(|o| o as usize);
+            let (b, matrix, m, clut, a) = (
+                offset(12)?,
+                offset(16)?,
+                offset(20)?,
+                offset(24)?,
+                offset(28)?,
+            );
+            let output = read_curves(data, b, outputs)?;
+            let (input, clut) = match (a, clut) {
+                (0, _) | (_, 0) => (vec![], None),
+                (a, clut) => {
+                    let grid = (0..inputs)
+                        .map(|i| data.get(clut + i).map(|&g| g as usize))
+                        .collect::<Option<Vec<_>>>()?;
+                    let bytes = *data.get(clut + 16)? as usize;
+                    (
+                        read_curves(data, a, inputs)?,
+                        Some(read_clut(data, clut + 20, grid, outputs, bytes)?),
+                    )
+                }
+            };
+            // Without a CLUT, the input must already have three components.
+            if clut.is_none() && inputs != outputs {
+                return None;
+            }
+            let middle = match m {
+                0 => vec![],
+                m => read_curves(data, m, outputs)?,
+            };
+            let matrix = match matrix {
+                0 => None,
+                at => Some(
+                    (0..12)
+                        .map(|i| read_fixed(data, at + 4 * i))
+                        .collect::<Option<Vec<_>>>()?
+                        .try_into()
+                        .ok()?,
+                ),
+            };
+            Some(Lut {
+                input,
+                clut,
+                middle,
+                matrix,
+                output,
+                encoding: if pcs_lab {
+                    PcsEncoding::Lab
+                } else {
+                    PcsEncoding::Xyz
+                },
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A profile with the given color space and PCS, and tags.
+    fn profile(color_space: &[u8; 4], pcs: &[u8; 4], tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0; 128];
+        data[16..20].copy_from_slice(color_space);
+        data[20..24].copy_from_slice(pcs);
+        data.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + 12 * tags.len();
+        for (signature, tag) in tags {
+            data.extend(signature.iter());
+            data.extend((offset as u32).to_be_bytes());
+            data.extend((tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+        }
+        for (_, tag) in tags {
+            data.extend(tag);
+        }
+        data
+    }
+
+    fn fixed(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    #[test]
+    fn test_matrix_profile() {
+        let xyz = |x: f64, y: f64, z: f64| {
+            [
+                b"XYZ \0\0\0\0".to_vec(),
+                [fixed(x), fixed(y), fixed(z)].concat(),
+            ]
+            .concat()
+        };
+        // A gamma 2.0 curve.
+        let curve = b"curv\0\0\0\0\0\0\0\x01\x02\x00".to_vec();
+        let data = profile(
+            b"RGB ",
+            b"XYZ ",
+            &[
+                (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+                (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+                (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+                (b"rTRC", curve.clone()),
+                (b"gTRC", curve.clone()),
+                (b"bTRC", curve),
+            ],
+        );
+        let profile = IccProfile::parse(&data).unwrap();
+        assert_eq!(profile.components, 3);
+        let white = profile.to_xyz(&[1.0, 1.0, 1.0]);
+        assert!((white[1] - 1.0).abs() < 1e-3);
+        let gray = profile.to_xyz(&[0.5, 0.5, 0.5]);
+        assert!((gray[1] - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_lut_profile() {
+        // A lut8 CMYK profile with a 2-point grid, giving L* = 100 for no ink
+        // and 0 otherwise, with a* = b* = 0.
+        let mut lut = b"mft1\0\0\0\0\x04\x03\x02\0".to_vec();
+        for i in 0..9 {
+            lut.extend(fixed(if i % 4 == 0 { 1.0 } else { 0.0 }));
+        }
+        let identity: Vec<u8> = (0..=255).collect();
+        lut.extend(identity.repeat(4));
+        for corner in 0..16 {
+            let l = if corner == 0 { 255 } else { 0 };
+            lut.extend([l, 128, 128]);
+        }
+        lut.extend(identity.repeat(3));
+        let data = profile(b"CMYK", b"Lab ", &[(b"A2B0", lut)]);
+        let profile = IccProfile::parse(&data).unwrap();
+        assert_eq!(profile.components, 4);
+
+        let paper = profile.to_xyz(&[0.0; 4]);
+        assert!(paper.iter().zip(D50).all(|(a, b)| (a - b).abs() < 0.01));
+        let black = profile.to_xyz(&[0.0, 0.0, 0.0, 1.0]);
+        assert!(black[1] < 0.01);
+    }
+}
@@ -0,0 +1,118 @@
+/// The graphics state maintained while content streams are processed.
+/// See sec. 8.4
+pub mod state;
+
+/// Processing of content streams, reporting what is drawn to a visitor.
+/// See sec. 8.2
+pub mod interpreter;
+
+/// Building content streams from typed operators. See sec. 8.2
+pub mod builder;
+
+use crate::api::objects::{Dictionary, PdfObject};
+
+///
+/// A single content stream operation: an operator keyword together with the
+/// operands that precede it. For example, "56.8 724.1 Td" is the operator "Td"
+/// with the operands 56.8 and 724.1. See sec. 7.8.2 (pg. 112)
+///
+/// Inline images ("BI . . . ID . . . EI") are represented as a single "BI"
+/// operation whose only operand is a stream holding the image parameters and
+/// data.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub operator: String,
+    pub operands: Vec<PdfObject>,
+}
+
+impl Operation {
+    pub fn new(operator: &str, operands: Vec<PdfObject>) -> Operation {
+        Operation {
+            operator: operator.to_string(),
+            operands,
+        }
+    }
+
+    /// The operands as numbers. Non-numeric operands are read as 0.
+    pub fn numbers(&self) -> Vec<f64> {
+        self.operands
+            .iter()
+            .map(|o| o.as_f64().unwrap_or(0.0))
+            .collect()
+    }
+
+    pub fn number(&self, index: usize) -> f64 {
+        self.operands
+            .get(index)
+            .and_then(PdfObject::as_f64)
+            .unwrap_or(0.0)
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.operands.get(index).and_then(PdfObject::as_name)
+    }
+}
+
+/// Expands the abbreviated keys and values used in inline image dictionaries to
+/// their full names, so that inline images can be handled like image XObjects.
+/// See sec. 8.9.7, Tables 91 and 92 (pg. 242)
+pub fn expand_inline_image_dict(dict: &Dictionary) -> Dictionary {
+    let expand_key = |key: &str| -> String {
+        match key {
+            "BPC" => "BitsPerComponent",
+            "CS" => "ColorSpace",
+            "D" => "Decode",
+            "DP" => "DecodeParms",
+            "F" => "Filter",
+            "H" => "Height",
+            "IM" => "ImageMask",
+            "I" => "Interpolate",
+            "W" => "Width",
+            "L" => "Length",
+            other => other,
+        }
+        .to_string()
+    };
+    let expand_name = |name: &str| -> String {
+        match name {
+            "G" => "DeviceGray",
+            "RGB" => "DeviceRGB",
+            "CMYK" => "DeviceCMYK",
+            "I" => "Indexed",
+            "AHx" => "ASCIIHexDecode",
+            "A85" => "ASCII85Decode",
+            "LZW" => "LZWDecode",
+            "Fl" => "FlateDecode",
+            "RL" => "RunLengthDecode",
+            "CCF" => "CCITTFaxDecode",
+            "DCT" => "DCTDecode",
+            other => other,
+        }
+        .to_string()
+    };
+    let expand_value = |key: &str, value: &PdfObject| -> PdfObject {
+        match (key, value) {
+            ("ColorSpace" | "Filter", PdfObject::Name(name)) => PdfObject::Name(expand_name(name)),
+            ("ColorSpace" | "Filter", PdfObject::Array(items)) => PdfObject::Array(
+                items
+                    .iter()
+                    .map(|item| match item {
+                        PdfObject::Name(name) => PdfObject::Name(expand_name(name)),
+                        other => other.clone(),
+                    })
+                    .collect(),
+            ),
+            _ => value.clone(),
+        }
+    };
+
+    let mut expanded = Dictionary::new();
+    for (key, value) in dict.iter() {
+        let key = expand_key(key);
+        let value = expand_value(&key, value);
+        expanded.set(key, value);
+    }
+    expanded.set("Subtype", PdfObject::Name("Image".to_string()));
+    expanded
+}
@@ -0,0 +1,378 @@
+use crate::api::{
+    content::Operation,
+    geometry::Matrix,
+    objects::{Dictionary, PdfObject},
+    write::write_content,
+};
+
+/// Operands are rounded to this many decimal places, more precise than any
+/// output device, so that arithmetic noise such as 0.30000000000000004 is not
+/// written out.
+const DECIMALS: i32 = 6;
+
+///
+/// Builds a content stream operator by operator. Each method appends one
+/// operation and returns the builder, so that calls can be chained, as in
+/// `content.rect(0.0, 0.0, 10.0, 10.0).fill()`.
+///
+/// The operations can be inspected, combined with parsed operations, and
+/// written out with `to_bytes`. See sec. 8.2, Table 50 (pg. 136) for the
+/// operators.
+///
+#[derive(Debug, Clone, Default)]
+pub struct ContentBuilder {
+    operations: Vec<Operation>,
+}
+
+/// Whole numbers are written as integers, others as reals.
+fn number(value: f64) -> PdfObject {
+    let scale = 10f64.powi(DECIMALS);
+    let value = (value * scale).round() / scale;
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        PdfObject::Integer(value as i64)
+    } else {
+        PdfObject::Real(value)
+    }
+}
+
+fn numbers(values: &[f64]) -> Vec<PdfObject> {
+    values.iter().map(|&value| number(value)).collect()
+}
+
+fn name(name: &str) -> PdfObject {
+    PdfObject::Name(name.to_string())
+}
+
+impl ContentBuilder {
+    pub fn new() -> ContentBuilder {
+        ContentBuilder::default()
+    }
+
+    /// Appends any operation, for operators without a method of their own.
+    pub fn push(&mut self, operator: &str, operands: Vec<PdfObject>) -> &mut Self {
+        self.operations.push(Operation::new(operator, operands));
+        self
+    }
+
+    fn push_numbers(&mut self, operator: &str, operands: &[f64]) -> &mut Self {
+        self.push(operator, numbers(operands))
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    pub fn into_operations(self) -> Vec<Operation> {
+        self.operations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// The content stream data, one operation per line.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![];
+        // Writing to a Vec cannot fail.
+        let _ = write_content(&mut data, &self.operations);
+        data
+    }
+
+    // Graphics state. See sec. 8.4.4, Table 56 (pg. 149)
+
+    pub fn save_state(&mut self) -> &mut Self {
+        self.push("q", vec![])
+    }
+
+    pub fn restore_state(&mut self) -> &mut Self {
+        self.push("Q", vec![])
+    }
+
+    /// Concatenates a matrix with the current transformation matrix.
+    pub fn transform(&mut self, matrix: Matrix) -> &mut Self {
+        let Matrix { a, b, c, d, e, f } = matrix;
+        self.push_numbers("cm", &[a, b, c, d, e, f])
+    }
+
+    pub fn set_line_width(&mut self, width: f64) -> &mut Self {
+        self.push_numbers("w", &[width])
+    }
+
+    /// 0 for butt caps, 1 for round caps and 2 for projecting square caps.
+    pub fn set_line_cap(&mut self, cap: i64) -> &mut Self {
+        self.push("J", vec![PdfObject::Integer(cap)])
+    }
+
+    /// 0 for miter joins, 1 for round joins and 2 for bevel joins.
+    pub fn set_line_join(&mut self, join: i64) -> &mut Self {
+        self.push("j", vec![PdfObject::Integer(join)])
+    }
+
+    /// Sets the lengths of alternating dashes and gaps; an empty pattern draws
+    /// solid lines.
+    pub fn set_dash(&mut self, pattern: &[f64], phase: f64) -> &mut Self {
+        let pattern = PdfObject::Array(numbers(pattern));
+        self.push("d", vec![pattern, number(phase)])
+    }
+
+    /// Applies the parameters of an ExtGState resource.
+    pub fn set_graphics_state(&mut self, resource: &str) -> &mut Self {
+        self.push("gs", vec![name(resource)])
+    }
+
+    // Path construction and painting. See sec. 8.5.2 (pg. 155) and 8.5.3 (pg. 158)
+
+    pub fn move_to(&mut self, x: f64, y: f64) -> &mut Self {
+        self.push_numbers("m", &[x, y])
+    }
+
+    pub fn line_to(&mut self, x: f64, y: f64) -> &mut Self {
+        self.push_numbers("l", &[x, y])
+    }
+
+    /// Appends a cubic Bézier curve to (x3, y3) with the control points
+    /// (x1, y1) and (x2, y2).
+    pub fn curve_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) -> &mut Self {
+        self.push_numbers("c", &[x1, y1, x2, y2, x3, y3])
+    }
+
+    pub fn close_path(&mut self) -> &mut Self {
+        self.push("h", vec![])
+    }
+
+    /// Appends a rectangle with its lower left corner at (x, y).
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) -> &mut Self {
+        self.push_numbers("re", &[x, y, width, height])
+    }
+
+    pub fn stroke(&mut self) -> &mut Self {
+        self.push("S", vec![])
+    }
+
+    /// Fills the path using the nonzero winding number rule.
+    pub fn fill(&mut self) -> &mut Self {
+        self.push("f", vec![])
+    }
+
+    pub fn fill_even_odd(&mut self) -> &mut Self {
+        self.push("f*", vec![])
+    }
+
+    pub fn fill_and_stroke(&mut self) -> &mut Self {
+        self.push("B", vec![])
+    }
+
+    /// Ends the path without painting it, e.g. after a clip.
+    pub fn end_path(&mut self) -> &mut Self {
+        self.push("n", vec![])
+    }
+
+    /// Intersects the clipping path with the current path, using the nonzero
+    /// winding number rule. The path still has to be painted or ended.
+    pub fn clip(&mut self) -> &mut Self {
+        self.push("W", vec![])
+    }
+
+    pub fn clip_even_odd(&mut self) -> &mut Self {
+        self.push("W*", vec![])
+    }
+
+    // Color. See sec. 8.6.8, Table 73 (pg. 194)
+
+    pub fn set_fill_gray(&mut self, gray: f64) -> &mut Self {
+        self.push_numbers("g", &[gray])
+    }
+
+    pub fn set_stroke_gray(&mut self, gray: f64) -> &mut Self {
+        self.push_numbers("G", &[gray])
+    }
+
+    pub fn set_fill_rgb(&mut self, r: f64, g: f64, b: f64) -> &mut Self {
+        self.push_numbers("rg", &[r, g, b])
+    }
+
+    pub fn set_stroke_rgb(&mut self, r: f64, g: f64, b: f64) -> &mut Self {
+        self.push_numbers("RG", &[r, g, b])
+    }
+
+    pub fn set_fill_cmyk(&mut self, c: f64, m: f64, y: f64, k: f64) -> &mut Self {
+        self.push_numbers("k", &[c, m, y, k])
+    }
+
+    pub fn set_stroke_cmyk(&mut self, c: f64, m: f64, y: f64, k: f64) -> &mut Self {
+        self.push_numbers("K", &[c, m, y, k])
+    }
+
+    // Text. See sec. 9.3 (pg. 291) and 9.4 (pg. 297)
+
+    pub fn begin_text(&mut self) -> &mut Self {
+        self.push("BT", vec![])
+    }
+
+    pub fn end_text(&mut self) -> &mut Self {
+        self.push("ET", vec![])
+    }
+
+    /// Selects a Font resource and the font size.
+    pub fn set_font(&mut self, resource: &str, size: f64) -> &mut Self {
+        self.push("Tf", vec![name(resource), number(size)])
+    }
+
+    /// Moves to the start of the next line, offset from the start of the
+    /// current line.
+    pub fn move_text(&mut self, x: f64, y: f64) -> &mut Self {
+        self.push_numbers("Td", &[x, y])
+    }
+
+    /// Moves to the start of the next line, the text leading below the current.
+    pub fn next_line(&mut self) -> &mut Self {
+        self.push("T*", vec![])
+    }
+
+    pub fn set_text_matrix(&mut self, matrix: Matrix) -> &mut Self {
+        let Matrix { a, b, c, d, e, f } = matrix;
+        self.push_numbers("Tm", &[a, b, c, d, e, f])
+    }
+
+    pub fn set_character_spacing(&mut self, spacing: f64) -> &mut Self {
+        self.push_numbers("Tc", &[spacing])
+    }
+
+    pub fn set_word_spacing(&mut self, spacing: f64) -> &mut Self {
+        self.push_numbers("Tw", &[spacing])
+    }
+
+    pub fn set_leading(&mut self, leading: f64) -> &mut Self {
+        self.push_numbers("TL", &[leading])
+    }
+
+    pub fn set_text_rise(&mut self, rise: f64) -> &mut Self {
+        self.push_numbers("Ts", &[rise])
+    }
+
+    /// 0 fills glyphs, 1 strokes them, 3 makes them invisible. See sec. 9.3.6,
+    /// Table 104 (pg. 295) for the other modes.
+    pub fn set_text_rendering_mode(&mut self, mode: i64) -> &mut Self {
+        self.push("Tr", vec![PdfObject::Integer(mode)])
+    }
+
+    /// Shows a string, encoded as the current font expects.
+    pub fn show_text(&mut self, text: &[u8]) -> &mut Self {
+        self.push("Tj", vec![PdfObject::String(text.to_vec())])
+    }
+
+    /// Shows strings with adjustments between them, in thousandths of text
+    /// space units that move the next glyph to the left.
+    pub fn show_text_adjusted(&mut self, items: &[TextItem]) -> &mut Self {
+        let items = items
+            .iter()
+            .map(|item| match item {
+                TextItem::Text(text) => PdfObject::String(text.clone()),
+                TextItem::Adjustment(adjustment) => number(*adjustment),
+            })
+            .collect();
+        self.push("TJ", vec![PdfObject::Array(items)])
+    }
+
+    // XObjects, shadings and marked content. See sec. 8.8 (pg. 216), 8.7.4.2
+    // (pg. 196) and 14.6 (pg. 730)
+
+    /// Paints an image or form XObject resource.
+    pub fn draw_xobject(&mut self, resource: &str) -> &mut Self {
+        self.push("Do", vec![name(resource)])
+    }
+
+    /// Paints a Shading resource over the clipping region.
+    pub fn paint_shading(&mut self, resource: &str) -> &mut Self {
+        self.push("sh", vec![name(resource)])
+    }
+
+    pub fn begin_marked_content(&mut self, tag: &str) -> &mut Self {
+        self.push("BMC", vec![name(tag)])
+    }
+
+    /// Begins marked content with a property list, given inline or as the
+    /// name of a Properties resource.
+    pub fn begin_marked_content_with(&mut self, tag: &str, properties: PdfObject) -> &mut Self {
+        self.push("BDC", vec![name(tag), properties])
+    }
+
+    pub fn end_marked_content(&mut self) -> &mut Self {
+        self.push("EMC", vec![])
+    }
+
+    /// Marks content as an artifact, such as a watermark or page number, that
+    /// is not part of the document's text. See sec. 14.8.2.2 (pg. 760)
+    pub fn begin_artifact(&mut self) -> &mut Self {
+        self.begin_marked_content_with("Artifact", PdfObject::Dictionary(Dictionary::new()))
+    }
+}
+
+/// An element of the array shown with "TJ".
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextItem {
+    Text(Vec<u8>),
+    Adjustment(f64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::read::content::parse_content;
+
+    #[test]
+    fn test_content_builder() {
+        let mut content = ContentBuilder::new();
+        content
+            .save_state()
+            .transform(Matrix::new(1.0, 0.0, 0.0, 1.0, 0.1 + 0.2, -0.0))
+            .set_fill_rgb(1.0, 0.5, 1.0 / 3.0)
+            .rect(0.0, 0.0, 100.0, 50.0)
+            .fill()
+            .begin_text()
+            .set_font("F1", 12.0)
+            .move_text(10.0, 20.0)
+            .show_text(b"(Hi)")
+            .show_text_adjusted(&[TextItem::Text(b"A".to_vec()), TextItem::Adjustment(-120.0)])
+            .end_text()
+            .restore_state();
+        let data = content.to_bytes();
+        assert_eq!(
+            String::from_utf8_lossy(&data),
+            "q\n1 0 0 1 0.3 0 cm\n1 0.5 0.333333 rg\n0 0 100 50 re\nf\nBT\n/F1 12 Tf\n\
+             10 20 Td\n(\\(Hi\\)) Tj\n[(A) -120] TJ\nET\nQ\n"
+        );
+        assert_eq!(parse_content(&data), content.into_operations());
+    }
+
+    /// Wraps existing content in "q" and "Q", so that it cannot affect what is
+    /// added after it, and adds a watermark.
+    #[test]
+    fn test_watermark() {
+        let page = b"1 0 0 RG 2 w 0 0 m 100 100 l S BT /F1 10 Tf (Total) Tj ET";
+        let mut content = ContentBuilder::new();
+        content.save_state();
+        let mut operations = content.into_operations();
+        operations.extend(parse_content(page));
+        let mut content = ContentBuilder::new();
+        content
+            .restore_state()
+            .begin_artifact()
+            .set_fill_gray(0.8)
+            .begin_text()
+            .set_font("F1", 48.0)
+            .set_text_matrix(Matrix::new(0.8, 0.6, -0.6, 0.8, 100.0, 100.0))
+            .show_text(b"DRAFT")
+            .end_text()
+            .end_marked_content();
+        operations.extend(content.into_operations());
+
+        let mut data = vec![];
+        write_content(&mut data, &operations).unwrap();
+        let read_back = parse_content(&data);
+        assert_eq!(read_back, operations);
+        let operators: Vec<&str> = read_back.iter().map(|o| o.operator.as_str()).collect();
+        assert_eq!(operators[..2], ["q", "RG"]);
+        assert_eq!(operators[10..13], ["Q", "BDC", "g"]);
+    }
+}
@@ -0,0 +1,875 @@
+use std::{collections::HashMap, io, rc::Rc};
+
+use crate::api::{
+    color::{Color, ColorSpace},
+    content::{
+        expand_inline_image_dict,
+        state::{
+            ClipPath, DashPattern, GraphicsState, LineCap, LineJoin, SoftMask, TextRenderingMode,
+        },
+        Operation,
+    },
+    document::Document,
+    font::Font,
+    geometry::{FillRule, Matrix, Path},
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    page::Page,
+    read::content::parse_content,
+};
+
+///
+/// How a path is painted by a path-painting operator.
+/// See sec. 8.5.3, Table 59 (pg. 156)
+///
+/// Operators that close the path first ("s", "b" and "b*") have already added
+/// the closing segment to the path that is reported.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathPaint {
+    pub stroke: bool,
+    pub fill: Option<FillRule>,
+}
+
+///
+/// A single glyph shown by a text-showing operator.
+///
+/// The matrix maps text space to device space (the text rendering matrix of
+/// sec. 9.4.4, multiplied by the CTM), already scaled by the font size; the
+/// glyph outline in glyph space must first be transformed by the font matrix.
+///
+#[derive(Debug, Clone)]
+pub struct TextGlyph {
+    pub code: u32,
+    pub unicode: Option<String>,
+    pub matrix: Matrix,
+    /// The horizontal displacement of the glyph in unscaled text space units,
+    /// i.e. the glyph width multiplied by the font size and horizontal scaling,
+    /// not including character or word spacing.
+    pub advance: f64,
+    pub font: Rc<Font>,
+}
+
+///
+/// An external object painted with "Do", or an inline image. Inline images have
+/// neither a name nor an object identifier, and their dictionary has had its
+/// abbreviations expanded. See sec. 8.8 (pg. 216)
+///
+#[derive(Debug, Clone, Copy)]
+pub struct XObject<'a> {
+    pub name: Option<&'a str>,
+    pub id: Option<ObjectId>,
+    pub stream: &'a Stream,
+    /// The resources in effect where the XObject is painted, which named color
+    /// spaces in an inline image dictionary refer to.
+    pub resources: &'a Dictionary,
+}
+
+///
+/// Receives what a content stream draws. Every method has an empty default so
+/// that visitors only implement what they are interested in: text extraction
+/// only needs glyphs, while a renderer needs paths, glyphs and images.
+///
+pub trait ContentVisitor {
+    /// Called for every operation before it is applied to the graphics state.
+    fn operation(&mut self, _operation: &Operation, _state: &GraphicsState) {}
+
+    /// A path was painted. Paths are given in user space; the CTM in the state
+    /// maps them to device space.
+    fn path(&mut self, _path: &Path, _paint: &PathPaint, _state: &GraphicsState) {}
+
+    /// A clipping path was added to the graphics state.
+    fn clip(&mut self, _clip: &ClipPath, _state: &GraphicsState) {}
+
+    fn begin_text(&mut self, _state: &GraphicsState) {}
+
+    fn end_text(&mut self, _state: &GraphicsState) {}
+
+    /// A glyph was shown. The state holds the text state it was shown with.
+    fn glyph(&mut self, _glyph: &TextGlyph, _state: &GraphicsState) {}
+
+    /// An image XObject or inline image was painted. Images occupy the unit
+    /// square of user space.
+    fn image(&mut self, _image: &XObject, _state: &GraphicsState) {}
+
+    /// A form XObject is about to be painted. The state already has the form
+    /// matrix concatenated with the CTM and the form's bounding box added to the
+    /// clipping path. Returning false skips the content of the form, which is
+    /// otherwise run through this visitor between `begin_form` and `end_form`.
+    fn begin_form(&mut self, _form: &XObject, _state: &GraphicsState) -> bool {
+        true
+    }
+
+    fn end_form(&mut self, _form: &XObject, _state: &GraphicsState) {}
+
+    /// The "sh" operator painted a shading over the current clipping region.
+    fn shading(&mut self, _shading: &PdfObject, _state: &GraphicsState) {}
+
+    fn begin_marked_content(&mut self, _tag: &str, _properties: Option<&Dictionary>) {}
+
+    fn end_marked_content(&mut self) {}
+}
+
+/// Form XObjects may paint other forms; nesting deeper than this is ignored.
+const MAX_FORM_DEPTH: usize = 32;
+
+/// Content runs at most this many operators, counting those of the forms it
+/// paints, so that forms painting each other many times over cannot hang the
+/// reader.
+const MAX_OPERATIONS: usize = 1 << 22;
+
+///
+/// Processes content streams: applies each operator to the graphics state, and
+/// reports paths, text, images and XObjects to a `ContentVisitor`.
+/// See sec. 8.2, Table 50 (pg. 136) for the operators.
+///
+/// The interpreter holds the resource dictionary the content stream refers to by
+/// name (fonts, XObjects, ExtGState parameters, color spaces, ...) along with the
+/// graphics state stack. Form XObjects painted with "Do" are run in place, each
+/// with its own resources and graphics state stack.
+///
+pub struct Interpreter<'a> {
+    pub doc: &'a Document,
+    pub resources: Rc<Dictionary>,
+    state: GraphicsState,
+    stack: Vec<GraphicsState>,
+    path: Path,
+    pending_clip: Option<FillRule>,
+    text_matrix: Matrix,
+    text_line_matrix: Matrix,
+    fonts: HashMap<String, Rc<Font>>,
+    /// The forms currently being run, innermost last, to detect forms that
+    /// (directly or indirectly) paint themselves.
+    forms: Vec<Option<ObjectId>>,
+    /// The operators run so far, counted against `MAX_OPERATIONS`.
+    operations: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(doc: &'a Document, resources: Dictionary, ctm: Matrix) -> Interpreter<'a> {
+        Interpreter {
+            doc,
+            resources: Rc::new(resources),
+            state: GraphicsState::with_ctm(ctm),
+            stack: vec![],
+            path: Path::new(),
+            pending_clip: None,
+            text_matrix: Matrix::identity(),
+            text_line_matrix: Matrix::identity(),
+            fonts: HashMap::new(),
+            forms: vec![],
+            operations: 0,
+        }
+    }
+
+    /// Sets up an interpreter for a page's content, with the page's resources and
+    /// the CTM mapping default user space to the output.
+    pub fn for_page(doc: &'a Document, page: &Page, ctm: Matrix) -> Interpreter<'a> {
+        Interpreter::new(doc, page.resources(doc), ctm)
+    }
+
+    /// Runs the content of a page through the visitor, using the page's default
+    /// user space as device space.
+    pub fn run_page(
+        doc: &Document,
+        page: &Page,
+        visitor: &mut dyn ContentVisitor,
+    ) -> Result<(), io::Error> {
+        let content = page.contents(doc)?;
+        let mut interpreter = Interpreter::for_page(doc, page, Matrix::identity());
+        interpreter.run(&content, visitor);
+        Ok(())
+    }
+
+    pub fn state(&self) -> &GraphicsState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut GraphicsState {
+        &mut self.state
+    }
+
+    /// Parses and runs a decoded content stream.
+    pub fn run(&mut self, content: &[u8], visitor: &mut dyn ContentVisitor) {
+        let operations = parse_content(content);
+        self.run_operations(&operations, visitor);
+    }
+
+    pub fn run_operations(&mut self, operations: &[Operation], visitor: &mut dyn ContentVisitor) {
+        for operation in operations {
+            if self.operations >= MAX_OPERATIONS {
+                return;
+            }
+            self.operations += 1;
+            visitor.operation(operation, &self.state);
+            self.execute(operation, visitor);
+        }
+    }
+
+    fn execute(&mut self, op: &Operation, visitor: &mut dyn ContentVisitor) {
+        let n = |i: usize| op.number(i);
+        match op.operator.as_str() {
+            // General graphics state
+            "w" => self.state.line_width = n(0),
+            "J" => self.state.line_cap = LineCap::from_number(n(0) as i64),
+            "j" => self.state.line_join = LineJoin::from_number(n(0) as i64),
+            "M" => self.state.miter_limit = n(0),
+            "d" => self.state.dash = dash_pattern(op.operands.first(), op.operands.get(1)),
+            "ri" => {
+                if let Some(intent) = op.name(0) {
+                    self.state.rendering_intent = intent.to_string();
+                }
+            }
+            "i" => self.state.flatness = n(0),
+            "gs" => {
+                if let Some(name) = op.name(0) {
+                    self.apply_ext_g_state(name);
+                }
+            }
+
+            // Special graphics state
+            "q" => self.stack.push(self.state.clone()),
+            "Q" => {
+                if let Some(state) = self.stack.pop() {
+                    self.state = state;
+                }
+            }
+            "cm" => {
+                if let Some(matrix) = Matrix::from_slice(&op.numbers()) {
+                    self.state.ctm = matrix.multiply(&self.state.ctm);
+                }
+            }
+
+            // Path construction
+            "m" => self.path.move_to(n(0), n(1)),
+            "l" => self.path.line_to(n(0), n(1)),
+            "c" => self.path.curve_to(n(0), n(1), n(2), n(3), n(4), n(5)),
+            "v" => {
+                let current = self.path.current_point().unwrap_or_default();
+                self.path
+                    .curve_to(current.x, current.y, n(0), n(1), n(2), n(3));
+            }
+            "y" => self.path.curve_to(n(0), n(1), n(2), n(3), n(2), n(3)),
+            "h" => self.path.close(),
+            "re" => self.path.rect(n(0), n(1), n(2), n(3)),
+
+            // Path painting
+            "S" => self.paint(visitor, false, true, None),
+            "s" => self.paint(visitor, true, true, None),
+            "f" | "F" => self.paint(visitor, false, false, Some(FillRule::NonZeroWinding)),
+            "f*" => self.paint(visitor, false, false, Some(FillRule::EvenOdd)),
+            "B" => self.paint(visitor, false, true, Some(FillRule::NonZeroWinding)),
+            "B*" => self.paint(visitor, false, true, Some(FillRule::EvenOdd)),
+            "b" => self.paint(visitor, true, true, Some(FillRule::NonZeroWinding)),
+            "b*" => self.paint(visitor, true, true, Some(FillRule::EvenOdd)),
+            "n" => self.paint(visitor, false, false, None),
+
+            // Clipping paths
+            "W" => self.pending_clip = Some(FillRule::NonZeroWinding),
+            "W*" => self.pending_clip = Some(FillRule::EvenOdd),
+
+            // Text objects
+            "BT" => {
+                self.text_matrix = Matrix::identity();
+                self.text_line_matrix = Matrix::identity();
+                visitor.begin_text(&self.state);
+            }
+            "ET" => visitor.end_text(&self.state),
+
+            // Text state
+            "Tc" => self.state.text.character_spacing = n(0),
+            "Tw" => self.state.text.word_spacing = n(0),
+            "Tz" => self.state.text.horizontal_scaling = n(0) / 100.0,
+            "TL" => self.state.text.leading = n(0),
+            "Tf" => {
+                if let Some(name) = op.name(0) {
+                    self.state.text.font = self.load_font(name);
+                    self.state.text.font_name = Some(name.to_string());
+                }
+                self.state.text.font_size = n(1);
+            }
+            "Tr" => self.state.text.rendering_mode = TextRenderingMode::from_number(n(0) as i64),
+            "Ts" => self.state.text.rise = n(0),
+
+            // Text positioning
+            "Td" => self.move_text_line(n(0), n(1)),
+            "TD" => {
+                self.state.text.leading = -n(1);
+                self.move_text_line(n(0), n(1));
+            }
+            "Tm" => {
+                if let Some(matrix) = Matrix::from_slice(&op.numbers()) {
+                    self.text_matrix = matrix;
+                    self.text_line_matrix = matrix;
+                }
+            }
+            "T*" => self.next_line(),
+
+            // Text showing
+            "Tj" => {
+                if let Some(text) = op.operands.first().and_then(PdfObject::as_bytes) {
+                    self.show_text(text, visitor);
+                }
+            }
+            "TJ" => {
+                if let Some(items) = op.operands.first().and_then(PdfObject::as_array) {
+                    for item in items {
+                        match item {
+                            PdfObject::String(text) => self.show_text(text, visitor),
+                            number => {
+                                let adjustment = number.as_f64().unwrap_or(0.0);
+                                let tx = -adjustment / 1000.0
+                                    * self.state.text.font_size
+                                    * self.state.text.horizontal_scaling;
+                                self.text_matrix =
+                                    Matrix::translate(tx, 0.0).multiply(&self.text_matrix);
+                            }
+                        }
+                    }
+                }
+            }
+            "'" => {
+                self.next_line();
+                if let Some(text) = op.operands.first().and_then(PdfObject::as_bytes) {
+                    self.show_text(text, visitor);
+                }
+            }
+            "\"" => {
+                self.state.text.word_spacing = n(0);
+                self.state.text.character_spacing = n(1);
+                self.next_line();
+                if let Some(text) = op.operands.get(2).and_then(PdfObject::as_bytes) {
+                    self.show_text(text, visitor);
+                }
+            }
+
+            // Color
+            "CS" => {
+                if let Some(space) = op.operands.first().and_then(|s| self.color_space(s)) {
+                    self.state.stroke_color = space.initial_color();
+                    self.state.stroke_color_space = space;
+                }
+            }
+            "cs" => {
+                if let Some(space) = op.operands.first().and_then(|s| self.color_space(s)) {
+                    self.state.fill_color = space.initial_color();
+                    self.state.fill_color_space = space;
+                }
+            }
+            "SC" | "SCN" => self.state.stroke_color = self.color_operands(op),
+            "sc" | "scn" => self.state.fill_color = self.color_operands(op),
+            "G" => self.set_device_color(true, ColorSpace::DeviceGray, op),
+            "g" => self.set_device_color(false, ColorSpace::DeviceGray, op),
+            "RG" => self.set_device_color(true, ColorSpace::DeviceRGB, op),
+            "rg" => self.set_device_color(false, ColorSpace::DeviceRGB, op),
+            "K" => self.set_device_color(true, ColorSpace::DeviceCMYK, op),
+            "k" => self.set_device_color(false, ColorSpace::DeviceCMYK, op),
+
+            // Shading
+            "sh" => {
+                if let Some(shading) = op.name(0).and_then(|name| self.resource("Shading", name)) {
+                    visitor.shading(&shading, &self.state);
+                }
+            }
+
+            // Inline images
+            "BI" => {
+                if let Some(image) = op.operands.first().and_then(PdfObject::as_stream) {
+                    let stream =
+                        Stream::new(expand_inline_image_dict(&image.dict), image.data.clone());
+                    let xobject = XObject {
+                        name: None,
+                        id: None,
+                        stream: &stream,
+                        resources: &self.resources,
+                    };
+                    visitor.image(&xobject, &self.state);
+                }
+            }
+
+            // XObjects
+            "Do" => {
+                if let Some(name) = op.name(0) {
+                    self.paint_xobject(name, visitor);
+                }
+            }
+
+            // Marked content
+            "BMC" | "BDC" => {
+                let tag = op.name(0).unwrap_or_default();
+                let properties = match op.operands.get(1) {
+                    Some(PdfObject::Dictionary(dict)) => Some(dict.clone()),
+                    Some(PdfObject::Name(name)) => self.resource_dict("Properties", name),
+                    _ => None,
+                };
+                visitor.begin_marked_content(tag, properties.as_ref());
+            }
+            "EMC" => visitor.end_marked_content(),
+
+            // Type 3 glyph metrics (d0, d1), marked-content points (MP, DP) and
+            // compatibility sections (BX, EX) do not change the graphics state.
+            _ => {}
+        }
+    }
+
+    /// Paints the current path and then ends it. A pending clip from "W" or "W*"
+    /// takes effect after painting. See sec. 8.5.4 (pg. 162)
+    fn paint(
+        &mut self,
+        visitor: &mut dyn ContentVisitor,
+        close: bool,
+        stroke: bool,
+        fill: Option<FillRule>,
+    ) {
+        let mut path = std::mem::take(&mut self.path);
+        if close {
+            path.close();
+        }
+        if stroke || fill.is_some() {
+            visitor.path(&path, &PathPaint { stroke, fill }, &self.state);
+        }
+        if let Some(rule) = self.pending_clip.take() {
+            let clip = ClipPath {
+                path: path.transform(&self.state.ctm),
+                rule,
+            };
+            visitor.clip(&clip, &self.state);
+            self.state.clip.push(clip);
+        }
+    }
+
+    /// Looks up a named resource, such as a font or an XObject.
+    /// See sec. 7.8.3, Table 34 (pg. 114)
+    pub fn resource(&self, category: &str, name: &str) -> Option<PdfObject> {
+        let category = self.doc.resolve_dict(&self.resources, category)?;
+        self.doc.resolve_key(category, name).cloned()
+    }
+
+    fn load_font(&mut self, name: &str) -> Option<Rc<Font>> {
+        if let Some(font) = self.fonts.get(name) {
+            return Some(font.clone());
+        }
+        let dict = self.resource_dict("Font", name)?;
+        let font = Rc::new(Font::load(self.doc, &dict));
+        self.fonts.insert(name.to_string(), font.clone());
+        Some(font)
+    }
+
+    fn resource_dict(&self, category: &str, name: &str) -> Option<Dictionary> {
+        match self.resource(category, name)? {
+            PdfObject::Dictionary(dict) => Some(dict),
+            PdfObject::Stream(stream) => Some(stream.dict),
+            _ => None,
+        }
+    }
+
+    fn color_space(&self, object: &PdfObject) -> Option<ColorSpace> {
+        ColorSpace::from_object(self.doc, object, Some(&self.resources))
+    }
+
+    /// The operands of SC, SCN, sc and scn: color components, optionally
+    /// followed by the name of a pattern, which is looked up in the current
+    /// resources. See sec. 8.6.8 (pg. 204)
+    fn color_operands(&self, op: &Operation) -> Color {
+        let components = op.operands.iter().filter_map(PdfObject::as_f64).collect();
+        let pattern = op
+            .operands
+            .last()
+            .and_then(PdfObject::as_name)
+            .map(String::from);
+        let pattern_object = pattern
+            .as_ref()
+            .and_then(|name| self.resource("Pattern", name))
+            .map(Rc::new);
+        Color {
+            components,
+            pattern,
+            pattern_object,
+        }
+    }
+
+    fn set_device_color(&mut self, stroke: bool, space: ColorSpace, op: &Operation) {
+        let color = Color::new(op.numbers());
+        if stroke {
+            self.state.stroke_color_space = space;
+            self.state.stroke_color = color;
+        } else {
+            self.state.fill_color_space = space;
+            self.state.fill_color = color;
+        }
+    }
+
+    /// Applies the parameters of a named graphics state parameter dictionary.
+    /// See sec. 8.4.5, Table 57 (pg. 151)
+    fn apply_ext_g_state(&mut self, name: &str) {
+        let Some(params) = self.resource_dict("ExtGState", name) else {
+            return;
+        };
+        let doc = self.doc;
+        for (key, value) in params.iter() {
+            let value = doc.resolve(value);
+            match key.as_str() {
+                "LW" => self.state.line_width = value.as_f64().unwrap_or(self.state.line_width),
+                "LC" => self.state.line_cap = LineCap::from_number(value.as_i64().unwrap_or(0)),
+                "LJ" => self.state.line_join = LineJoin::from_number(value.as_i64().unwrap_or(0)),
+                "ML" => self.state.miter_limit = value.as_f64().unwrap_or(self.state.miter_limit),
+                "D" => {
+                    if let Some(dash) = value.as_array() {
+                        self.state.dash = dash_pattern(dash.first(), dash.get(1));
+                    }
+                }
+                "RI" => {
+                    if let Some(intent) = value.as_name() {
+                        self.state.rendering_intent = intent.to_string();
+                    }
+                }
+                "FL" => self.state.flatness = value.as_f64().unwrap_or(self.state.flatness),
+                "SA" => self.state.stroke_adjustment = value.as_bool().unwrap_or(false),
+                "OP" => {
+                    self.state.stroke_overprint = value.as_bool().unwrap_or(false);
+                    // /op defaults to the value of /OP when it is not given.
+                    if !params.contains_key("op") {
+                        self.state.fill_overprint = self.state.stroke_overprint;
+                    }
+                }
+                "op" => self.state.fill_overprint = value.as_bool().unwrap_or(false),
+                "OPM" => self.state.overprint_mode = value.as_i64().unwrap_or(0),
+                "Font" => {
+                    if let Some([font, size]) = value.as_array().map(|a| a.as_slice()) {
+                        if let Some(dict) = doc.resolve(font).as_dict() {
+                            self.state.text.font = Some(Rc::new(Font::load(doc, dict)));
+                            self.state.text.font_name = None;
+                        }
+                        self.state.text.font_size = size.as_f64().unwrap_or(0.0);
+                    }
+                }
+                "BM" => {
+                    let mode = match value {
+                        PdfObject::Array(modes) => modes.first().and_then(|m| m.as_name()),
+                        other => other.as_name(),
+                    };
+                    self.state.blend_mode = mode.unwrap_or("Normal").to_string();
+                }
+                "SMask" => {
+                    self.state.soft_mask = value.as_dict().map(|dict| SoftMask {
+                        dict: dict.clone(),
+                        ctm: self.state.ctm,
+                    })
+                }
+                "CA" => self.state.stroke_alpha = value.as_f64().unwrap_or(1.0),
+                "ca" => self.state.fill_alpha = value.as_f64().unwrap_or(1.0),
+                "AIS" => self.state.alpha_is_shape = value.as_bool().unwrap_or(false),
+                "TK" => self.state.text.knockout = value.as_bool().unwrap_or(true),
+                _ => {}
+            }
+        }
+    }
+
+    /// Td: move to the start of the next line, offset from the start of the
+    /// current line. See sec. 9.4.2, Table 106 (pg. 293)
+    fn move_text_line(&mut self, tx: f64, ty: f64) {
+        self.text_line_matrix = Matrix::translate(tx, ty).multiply(&self.text_line_matrix);
+        self.text_matrix = self.text_line_matrix;
+    }
+
+    fn next_line(&mut self) {
+        let leading = self.state.text.leading;
+        self.move_text_line(0.0, -leading);
+    }
+
+    /// Shows each glyph of a string and advances the text matrix past it.
+    /// See sec. 9.4.4 (pg. 295)
+    fn show_text(&mut self, text: &[u8], visitor: &mut dyn ContentVisitor) {
+        let Some(font) = self.state.text.font.clone() else {
+            return;
+        };
+        let text_state = &self.state.text;
+        let size = text_state.font_size;
+        let scaling = text_state.horizontal_scaling;
+        let parameters = Matrix::new(size * scaling, 0.0, 0.0, size, 0.0, text_state.rise);
+
+        for (code, length) in font.codes(text) {
+            let width = font.width(code);
+            let matrix = parameters
+                .multiply(&self.text_matrix)
+                .multiply(&self.state.ctm);
+            let glyph = TextGlyph {
+                code,
+                unicode: font.to_unicode(code),
+                matrix,
+                advance: width * size * scaling,
+                font: font.clone(),
+            };
+            visitor.glyph(&glyph, &self.state);
+
+            let text_state = &self.state.text;
+            let word_spacing = if length == 1 && code == 32 {
+                text_state.word_spacing
+            } else {
+                0.0
+            };
+            let tx = (width * size + text_state.character_spacing + word_spacing) * scaling;
+            self.text_matrix = Matrix::translate(tx, 0.0).multiply(&self.text_matrix);
+        }
+    }
+
+    /// Do: images are reported to the visitor, and forms are run in place.
+    /// PostScript XObjects are ignored. See sec. 8.8 (pg. 216)
+    fn paint_xobject(&mut self, name: &str, visitor: &mut dyn ContentVisitor) {
+        let doc = self.doc;
+        // XObjects are indirect objects, so this only copies a reference.
+        let Some(entry) = doc
+            .resolve_dict(&self.resources, "XObject")
+            .and_then(|category| category.get(name))
+            .cloned()
+        else {
+            return;
+        };
+        let id = entry.as_reference();
+        let Some(stream) = doc.resolve(&entry).as_stream() else {
+            return;
+        };
+        let resources = self.resources.clone();
+        let xobject = XObject {
+            name: Some(name),
+            id,
+            stream,
+            resources: &resources,
+        };
+        match stream.dict.get_name("Subtype") {
+            Some("Image") => visitor.image(&xobject, &self.state),
+            Some("Form") => self.run_form(&xobject, visitor),
+            _ => {}
+        }
+    }
+
+    /// Runs the content of a form XObject as if it appeared in place of "Do".
+    /// See sec. 8.10.1 (pg. 250)
+    ///
+    /// The graphics state is saved and restored around the form, /Matrix is
+    /// concatenated with the CTM and /BBox clips the form. The form's content
+    /// refers to its own /Resources; forms without them use the resources of the
+    /// content that paints them, as some older files rely on.
+    pub fn run_form(&mut self, form: &XObject, visitor: &mut dyn ContentVisitor) {
+        if self.forms.len() >= MAX_FORM_DEPTH
+            || (form.id.is_some() && self.forms.contains(&form.id))
+        {
+            return;
+        }
+        let doc = self.doc;
+        let dict = &form.stream.dict;
+        let Ok(content) = doc.decode_stream(form.stream) else {
+            return;
+        };
+
+        let saved_state = self.state.clone();
+        let saved_stack = std::mem::take(&mut self.stack);
+        let saved_path = std::mem::take(&mut self.path);
+        let saved_text = (self.text_matrix, self.text_line_matrix);
+
+        if let Some(matrix) = dict
+            .get("Matrix")
+            .and_then(|m| doc.resolve(m).as_numbers())
+            .and_then(|m| Matrix::from_slice(&m))
+        {
+            self.state.ctm = matrix.multiply(&self.state.ctm);
+        }
+        if let Some([x1, y1, x2, y2]) = doc
+            .resolve_key(dict, "BBox")
+            .and_then(PdfObject::as_numbers)
+            .and_then(|b| <[f64; 4]>::try_from(b).ok())
+        {
+            let mut bbox = Path::new();
+            bbox.rect(x1, y1, x2 - x1, y2 - y1);
+            let clip = ClipPath {
+                path: bbox.transform(&self.state.ctm),
+                rule: FillRule::NonZeroWinding,
+            };
+            visitor.clip(&clip, &self.state);
+            self.state.clip.push(clip);
+        }
+
+        if visitor.begin_form(form, &self.state) {
+            let resources = doc.resolve_dict(dict, "Resources").cloned();
+            let saved_resources =
+                resources.map(|r| std::mem::replace(&mut self.resources, Rc::new(r)));
+            // Font names are only meaningful within one resource dictionary.
+            let saved_fonts = saved_resources
+                .is_some()
+                .then(|| std::mem::take(&mut self.fonts));
+
+            // A transparency group is composited as a whole with the soft mask,
+            // alpha and blend mode of the state; its content starts without
+            // them. See sec. 11.6.6 (pg. 401)
+            if transparency_group(doc, dict).is_some() {
+                self.state.soft_mask = None;
+                self.state.fill_alpha = 1.0;
+                self.state.stroke_alpha = 1.0;
+                self.state.blend_mode = "Normal".to_string();
+            }
+            self.forms.push(form.id);
+            self.run(&content, visitor);
+            self.forms.pop();
+
+            if let Some(resources) = saved_resources {
+                self.resources = resources;
+            }
+            if let Some(fonts) = saved_fonts {
+                self.fonts = fonts;
+            }
+            visitor.end_form(form, &self.state);
+        }
+
+        self.state = saved_state;
+        self.stack = saved_stack;
+        self.path = saved_path;
+        (self.text_matrix, self.text_line_matrix) = saved_text;
+    }
+}
+
+/// The group attributes dictionary of a form XObject that is a transparency
+/// group. See sec. 11.6.6, Table 145 (pg. 402)
+pub fn transparency_group<'d>(doc: &'d Document, form: &'d Dictionary) -> Option<&'d Dictionary> {
+    doc.resolve_dict(form, "Group")
+        .filter(|group| group.get_name("S") == Some("Transparency"))
+}
+
+fn dash_pattern(array: Option<&PdfObject>, phase: Option<&PdfObject>) -> DashPattern {
+    DashPattern {
+        array: array.and_then(|a| a.as_numbers()).unwrap_or_default(),
+        phase: phase.and_then(|p| p.as_f64()).unwrap_or(0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::api::read::objects::parse_dictionary;
+
+    #[derive(Default)]
+    struct Recorder {
+        paths: Vec<(Path, PathPaint)>,
+        text: String,
+        origins: Vec<(f64, f64)>,
+        forms: Vec<ObjectId>,
+    }
+
+    impl ContentVisitor for Recorder {
+        fn path(&mut self, path: &Path, paint: &PathPaint, _state: &GraphicsState) {
+            self.paths.push((path.clone(), *paint));
+        }
+
+        fn glyph(&mut self, glyph: &TextGlyph, _state: &GraphicsState) {
+            self.text.push_str(glyph.unicode.as_deref().unwrap_or("?"));
+            self.origins.push((glyph.matrix.e, glyph.matrix.f));
+        }
+
+        fn begin_form(&mut self, form: &XObject, _state: &GraphicsState) -> bool {
+            self.forms.extend(form.id);
+            true
+        }
+    }
+
+    fn dictionary(source: &str) -> Dictionary {
+        parse_dictionary(source.as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn test_run_page() {
+        let doc = Document::load("HelloWorld.pdf").unwrap();
+        let page = doc.page(0).unwrap();
+        let mut recorder = Recorder::default();
+        Interpreter::run_page(&doc, &page, &mut recorder).unwrap();
+        assert_eq!(recorder.text, "Hello World");
+        assert_eq!(recorder.origins[0], (56.8, 724.1));
+        // Each glyph starts further right than the previous one.
+        assert!(recorder.origins.windows(2).all(|w| w[1].0 > w[0].0));
+    }
+
+    #[test]
+    fn test_graphics_state_stack() {
+        let doc = Document::load("HelloWorld.pdf").unwrap();
+        let mut interpreter = Interpreter::new(&doc, Dictionary::new(), Matrix::identity());
+        let mut recorder = Recorder::default();
+        interpreter.run(
+            b"q 2 0 0 2 10 10 cm 1 0 0 rg 5 w 0 0 10 10 re W n Q 0 0 m 5 5 l S",
+            &mut recorder,
+        );
+        let state = interpreter.state();
+        assert_eq!(state.ctm, Matrix::identity());
+        assert_eq!(state.line_width, 1.0);
+        assert!(state.clip.is_empty());
+        assert_eq!(recorder.paths.len(), 1);
+        assert!(recorder.paths[0].1.stroke);
+    }
+
+    #[test]
+    fn test_form_xobjects() {
+        let mut doc = Document::load("HelloWorld.pdf").unwrap();
+        let outer = Stream::new(
+            dictionary(
+                "<< /Subtype /Form /BBox [0 0 100 100] /Matrix [2 0 0 2 0 0] \
+                 /Resources << /Font << /X 9 0 R >> /XObject << /Inner 101 0 R >> >> >>",
+            ),
+            b"BT /X 10 Tf 5 5 Td <01> Tj ET /Inner Do".to_vec(),
+        );
+        // Without resources of its own, the inner form uses those of the outer
+        // form, so it paints itself again; the cycle is cut off.
+        let inner = Stream::new(
+            dictionary("<< /Subtype /Form /BBox [0 0 100 100] /Matrix [1 0 0 1 100 0] >>"),
+            b"q 3 w BT /X 10 Tf <01> Tj ET /Inner Do".to_vec(),
+        );
+        doc.pdf
+            .body
+            .objects
+            .insert((100, 0), PdfObject::Stream(outer));
+        doc.pdf
+            .body
+            .objects
+            .insert((101, 0), PdfObject::Stream(inner));
+
+        let resources = dictionary("<< /XObject << /Fm0 100 0 R >> >>");
+        let mut interpreter = Interpreter::new(&doc, resources, Matrix::identity());
+        let mut recorder = Recorder::default();
+        interpreter.run(b"/Fm0 Do", &mut recorder);
+
+        assert_eq!(recorder.forms, vec![(100, 0), (101, 0)]);
+        assert_eq!(recorder.text, "HH");
+        assert_eq!(recorder.origins, vec![(10.0, 10.0), (200.0, 0.0)]);
+        let state = interpreter.state();
+        assert_eq!(state.ctm, Matrix::identity());
+        assert_eq!(state.line_width, 1.0);
+        assert!(state.clip.is_empty());
+        assert!(interpreter.resource("Font", "X").is_none());
+    }
+
+    #[test]
+    fn test_operation_budget() {
+        let mut doc = Document::load("HelloWorld.pdf").unwrap();
+        // Each form paints the next one 16 times, which would paint the last
+        // form 16^8 times.
+        for number in 200..208 {
+            let form = Stream::new(
+                dictionary(&format!(
+                    "<< /Subtype /Form /BBox [0 0 1 1] \
+                     /Resources << /XObject << /N {} 0 R >> >> >>",
+                    number + 1
+                )),
+                b"/N Do ".repeat(16),
+            );
+            doc.pdf
+                .body
+                .objects
+                .insert((number, 0), PdfObject::Stream(form));
+        }
+
+        let resources = dictionary("<< /XObject << /Fm0 200 0 R >> >>");
+        let mut interpreter = Interpreter::new(&doc, resources, Matrix::identity());
+        // Start close to the end of the budget, to keep the test quick.
+        interpreter.operations = MAX_OPERATIONS - 1000;
+        let mut recorder = Recorder::default();
+        interpreter.run(b"/Fm0 Do", &mut recorder);
+        assert!(recorder.forms.len() < 1000);
+        assert_eq!(interpreter.operations, MAX_OPERATIONS);
+    }
+}
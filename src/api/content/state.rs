@@ -0,0 +1,262 @@
+use std::rc::Rc;
+
+use crate::api::{
+    color::{Color, ColorSpace},
+    font::Font,
+    geometry::{FillRule, Matrix, Path},
+    objects::Dictionary,
+};
+
+///
+/// The shape at the ends of open subpaths when they are stroked.
+/// See sec. 8.4.3.3, Table 53 (pg. 144)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    ProjectingSquare,
+}
+
+impl LineCap {
+    pub fn from_number(number: i64) -> LineCap {
+        match number {
+            1 => LineCap::Round,
+            2 => LineCap::ProjectingSquare,
+            _ => LineCap::Butt,
+        }
+    }
+}
+
+///
+/// The shape at the corners of paths when they are stroked.
+/// See sec. 8.4.3.4, Table 54 (pg. 145)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl LineJoin {
+    pub fn from_number(number: i64) -> LineJoin {
+        match number {
+            1 => LineJoin::Round,
+            2 => LineJoin::Bevel,
+            _ => LineJoin::Miter,
+        }
+    }
+}
+
+///
+/// The pattern of dashes and gaps used to stroke paths: an array of lengths and
+/// the phase at which the pattern starts. An empty array is a solid line.
+/// See sec. 8.4.3.6 (pg. 146)
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DashPattern {
+    pub array: Vec<f64>,
+    pub phase: f64,
+}
+
+///
+/// A clipping path, stored in device space since the CTM may change after the
+/// clip is set.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipPath {
+    pub path: Path,
+    pub rule: FillRule,
+}
+
+///
+/// A soft mask dictionary set by the "gs" operator, with the CTM at that time,
+/// which maps the mask's group to device space. See sec. 11.6.5.2 (pg. 397)
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoftMask {
+    pub dict: Dictionary,
+    pub ctm: Matrix,
+}
+
+///
+/// How glyphs are painted. See sec. 9.3.6, Table 104 (pg. 292)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextRenderingMode {
+    Fill,
+    Stroke,
+    FillStroke,
+    Invisible,
+    FillClip,
+    StrokeClip,
+    FillStrokeClip,
+    Clip,
+}
+
+impl TextRenderingMode {
+    pub fn from_number(number: i64) -> TextRenderingMode {
+        match number {
+            1 => TextRenderingMode::Stroke,
+            2 => TextRenderingMode::FillStroke,
+            3 => TextRenderingMode::Invisible,
+            4 => TextRenderingMode::FillClip,
+            5 => TextRenderingMode::StrokeClip,
+            6 => TextRenderingMode::FillStrokeClip,
+            7 => TextRenderingMode::Clip,
+            _ => TextRenderingMode::Fill,
+        }
+    }
+
+    pub fn fills(&self) -> bool {
+        matches!(
+            self,
+            TextRenderingMode::Fill
+                | TextRenderingMode::FillStroke
+                | TextRenderingMode::FillClip
+                | TextRenderingMode::FillStrokeClip
+        )
+    }
+
+    pub fn strokes(&self) -> bool {
+        matches!(
+            self,
+            TextRenderingMode::Stroke
+                | TextRenderingMode::FillStroke
+                | TextRenderingMode::StrokeClip
+                | TextRenderingMode::FillStrokeClip
+        )
+    }
+
+    pub fn clips(&self) -> bool {
+        matches!(
+            self,
+            TextRenderingMode::FillClip
+                | TextRenderingMode::StrokeClip
+                | TextRenderingMode::FillStrokeClip
+                | TextRenderingMode::Clip
+        )
+    }
+}
+
+///
+/// The text state parameters. See sec. 9.3, Table 102 (pg. 287)
+///
+#[derive(Debug, Clone)]
+pub struct TextState {
+    /// Tc
+    pub character_spacing: f64,
+    /// Tw
+    pub word_spacing: f64,
+    /// Tz, stored as a fraction rather than a percentage.
+    pub horizontal_scaling: f64,
+    /// TL
+    pub leading: f64,
+    /// Tf: the font and the resource name it was selected by.
+    pub font: Option<Rc<Font>>,
+    pub font_name: Option<String>,
+    pub font_size: f64,
+    /// Tr
+    pub rendering_mode: TextRenderingMode,
+    /// Ts
+    pub rise: f64,
+    /// TK, set through an ExtGState dictionary.
+    pub knockout: bool,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        TextState {
+            character_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scaling: 1.0,
+            leading: 0.0,
+            font: None,
+            font_name: None,
+            font_size: 0.0,
+            rendering_mode: TextRenderingMode::Fill,
+            rise: 0.0,
+            knockout: true,
+        }
+    }
+}
+
+///
+/// The graphics state: the parameters that control how graphics are drawn, saved
+/// and restored with the "q" and "Q" operators.
+/// See sec. 8.4.1, Tables 51 and 52 (pg. 138)
+///
+#[derive(Debug, Clone)]
+pub struct GraphicsState {
+    /// The current transformation matrix, mapping user space to device space.
+    pub ctm: Matrix,
+    /// The clipping paths in effect. The visible region is their intersection.
+    pub clip: Vec<ClipPath>,
+    pub stroke_color_space: ColorSpace,
+    pub fill_color_space: ColorSpace,
+    pub stroke_color: Color,
+    pub fill_color: Color,
+    pub text: TextState,
+    pub line_width: f64,
+    pub line_cap: LineCap,
+    pub line_join: LineJoin,
+    pub miter_limit: f64,
+    pub dash: DashPattern,
+    pub rendering_intent: String,
+    pub flatness: f64,
+    pub stroke_adjustment: bool,
+    /// BM: the blend mode name, or the first supported name of an array.
+    pub blend_mode: String,
+    /// SMask: the soft mask, or None for the /None soft mask.
+    pub soft_mask: Option<SoftMask>,
+    /// CA
+    pub stroke_alpha: f64,
+    /// ca
+    pub fill_alpha: f64,
+    /// AIS
+    pub alpha_is_shape: bool,
+    pub stroke_overprint: bool,
+    pub fill_overprint: bool,
+    pub overprint_mode: i64,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        GraphicsState {
+            ctm: Matrix::identity(),
+            clip: vec![],
+            stroke_color_space: ColorSpace::DeviceGray,
+            fill_color_space: ColorSpace::DeviceGray,
+            stroke_color: Color::new(vec![0.0]),
+            fill_color: Color::new(vec![0.0]),
+            text: TextState::default(),
+            line_width: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            miter_limit: 10.0,
+            dash: DashPattern::default(),
+            rendering_intent: "RelativeColorimetric".to_string(),
+            flatness: 1.0,
+            stroke_adjustment: false,
+            blend_mode: "Normal".to_string(),
+            soft_mask: None,
+            stroke_alpha: 1.0,
+            fill_alpha: 1.0,
+            alpha_is_shape: false,
+            stroke_overprint: false,
+            fill_overprint: false,
+            overprint_mode: 0,
+        }
+    }
+}
+
+impl GraphicsState {
+    /// The initial graphics state for a page or form, with the given CTM.
+    pub fn with_ctm(ctm: Matrix) -> GraphicsState {
+        GraphicsState {
+            ctm,
+            ..Default::default()
+        }
+    }
+}
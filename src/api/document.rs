@@ -0,0 +1,364 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use crate::api::{
+    encryption::{Credentials, RsaPrivateKey},
+    filters::{self, FilterError},
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    optimize,
+    page::Page,
+    read,
+    structure::PDF,
+    write,
+};
+
+/// References are followed at most this many times when resolving an object, so
+/// that a reference cycle cannot hang the reader.
+const MAX_REFERENCE_DEPTH: usize = 32;
+
+///
+/// A PDF document that has been read into memory.
+///
+/// The Document wraps the four-part `PDF` structure together with the bytes it was
+/// read from, and provides access to the document-level structures built on top
+/// of the objects in the body: the catalog and the page tree.
+/// See sec. 7.7 (pg. 98)
+///
+#[derive(Debug)]
+pub struct Document {
+    pub pdf: PDF,
+    pub data: Vec<u8>,
+}
+
+impl Document {
+    pub fn load(path: &str) -> Result<Document, io::Error> {
+        let data = read::read_bytes(path)?;
+        Document::from_bytes(data)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Document, io::Error> {
+        let pdf = read::read_pdf(&data)?;
+        Ok(Document { pdf, data })
+    }
+
+    /// Loads an encrypted document with its user or owner password. The
+    /// objects are decrypted as they are read. See `read::read_pdf_with_password`.
+    pub fn load_with_password(path: &str, password: &str) -> Result<Document, io::Error> {
+        let data = read::read_bytes(path)?;
+        Document::from_bytes_with_password(data, password)
+    }
+
+    pub fn from_bytes_with_password(data: Vec<u8>, password: &str) -> Result<Document, io::Error> {
+        let pdf = read::read_pdf_with_password(&data, password)?;
+        Ok(Document { pdf, data })
+    }
+
+    /// Loads a document encrypted for recipients' certificates with the
+    /// private key of one of them. See `encryption::SecurityHandler`.
+    pub fn load_with_private_key(path: &str, key: &RsaPrivateKey) -> Result<Document, io::Error> {
+        let data = read::read_bytes(path)?;
+        Document::from_bytes_with_private_key(data, key)
+    }
+
+    pub fn from_bytes_with_private_key(
+        data: Vec<u8>,
+        key: &RsaPrivateKey,
+    ) -> Result<Document, io::Error> {
+        let pdf = read::read_pdf_with(&data, Credentials::PrivateKey(key))?;
+        Ok(Document { pdf, data })
+    }
+
+    /// Writes the document as a complete PDF file, with a freshly computed
+    /// cross-reference table. See `write::write_pdf`.
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), io::Error> {
+        write::write_pdf(&self.pdf, writer)
+    }
+
+    /// Like `save`, e.g. with object streams and a cross-reference stream to
+    /// make the file smaller. See `write::WriteOptions`.
+    pub fn save_with<W: Write>(
+        &self,
+        writer: W,
+        options: &write::WriteOptions,
+    ) -> Result<(), io::Error> {
+        write::write_pdf_with(&self.pdf, writer, options)
+    }
+
+    /// Writes the bytes the document was read from, unchanged, followed by an
+    /// incremental update holding the changes made since. See
+    /// `write::write_incremental`.
+    pub fn save_incremental<W: Write>(&self, writer: W) -> Result<(), io::Error> {
+        write::write_incremental(&self.pdf, &self.data, writer)
+    }
+
+    /// Drops unreachable objects, merges identical ones and renumbers the rest.
+    /// See `optimize::optimize`.
+    pub fn optimize(&mut self) -> optimize::OptimizeStats {
+        optimize::optimize(&mut self.pdf)
+    }
+
+    /// Looks up an indirect object by its object and generation number.
+    pub fn get(&self, id: ObjectId) -> Option<&PdfObject> {
+        self.pdf.body.objects.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut PdfObject> {
+        self.pdf.body.objects.get_mut(&id)
+    }
+
+    /// Follows indirect references until a direct object is reached. A reference
+    /// to an object that does not exist is treated as the null object.
+    /// See sec. 7.3.10 (pg. 33)
+    pub fn resolve<'a>(&'a self, object: &'a PdfObject) -> &'a PdfObject {
+        let mut current = object;
+        for _ in 0..MAX_REFERENCE_DEPTH {
+            match current {
+                PdfObject::Reference(id) => match self.get(*id) {
+                    Some(next) => current = next,
+                    None => return &PdfObject::Null,
+                },
+                _ => return current,
+            }
+        }
+        &PdfObject::Null
+    }
+
+    /// Resolves the value of a dictionary entry.
+    pub fn resolve_key<'a>(&'a self, dict: &'a Dictionary, key: &str) -> Option<&'a PdfObject> {
+        dict.get(key)
+            .map(|value| self.resolve(value))
+            .filter(|value| !value.is_null())
+    }
+
+    /// Resolves a dictionary entry that is expected to hold a dictionary (or a
+    /// stream, whose dictionary is returned).
+    pub fn resolve_dict<'a>(&'a self, dict: &'a Dictionary, key: &str) -> Option<&'a Dictionary> {
+        self.resolve_key(dict, key).and_then(PdfObject::as_dict)
+    }
+
+    /// Decodes the data of a stream, like `Stream::decode`, after resolving the
+    /// indirect references in its decode parameters. These are needed by
+    /// JBIG2Decode, whose /JBIG2Globals entry refers to a stream of shared
+    /// segments.
+    pub fn decode_stream(&self, stream: &Stream) -> Result<Vec<u8>, FilterError> {
+        self.decode_stream_until(stream, |_| false)
+            .map(|(data, _)| data)
+    }
+
+    /// Like `filters::decode_stream_until`, with the decode parameters resolved.
+    pub fn decode_stream_until<'a>(
+        &self,
+        stream: &'a Stream,
+        stop: impl Fn(&str) -> bool,
+    ) -> Result<(Vec<u8>, Option<&'a str>), FilterError> {
+        let mut dict = Dictionary::new();
+        for key in ["DecodeParms", "DP"] {
+            if let Some(params) = self.resolve_key(&stream.dict, key) {
+                let params = match params {
+                    PdfObject::Array(array) => {
+                        PdfObject::Array(array.iter().map(|p| self.resolve_entries(p)).collect())
+                    }
+                    params => self.resolve_entries(params),
+                };
+                dict.set(key, params);
+            }
+        }
+        let params = filters::decode_params(&dict, stream.filters().len());
+        filters::decode_stream_with(stream, &params, stop)
+    }
+
+    /// Replaces each reference held by a dictionary with the object it refers to.
+    fn resolve_entries(&self, object: &PdfObject) -> PdfObject {
+        match self.resolve(object) {
+            PdfObject::Dictionary(dict) => {
+                let mut resolved = Dictionary::new();
+                for (key, value) in dict.iter() {
+                    resolved.set(key.clone(), self.resolve(value).clone());
+                }
+                PdfObject::Dictionary(resolved)
+            }
+            other => other.clone(),
+        }
+    }
+
+    pub fn trailer(&self) -> &Dictionary {
+        &self.pdf.trailer.entries
+    }
+
+    /// The document catalog is the root of the document's object hierarchy and is
+    /// located through the /Root entry of the trailer. See sec. 7.7.2 (pg. 99)
+    pub fn catalog(&self) -> Option<&Dictionary> {
+        self.resolve_dict(self.trailer(), "Root")
+    }
+
+    /// The object number of the catalog, for changing it in place.
+    pub(crate) fn catalog_id(&self) -> Option<ObjectId> {
+        self.trailer().get("Root").and_then(PdfObject::as_reference)
+    }
+
+    /// The pages of the document, in order, found by walking the page tree.
+    /// See sec. 7.7.3 (pg. 102)
+    pub fn pages(&self) -> Vec<Page> {
+        let mut pages = vec![];
+        if let Some(root) = self.catalog().and_then(|c| c.get("Pages")) {
+            let mut visited = HashSet::new();
+            self.collect_pages(root, &Dictionary::new(), &mut pages, &mut visited);
+        }
+        pages
+    }
+
+    pub fn page(&self, index: usize) -> Option<Page> {
+        self.pages().into_iter().nth(index)
+    }
+
+    /// Walks one node of the page tree. Attributes that can be inherited from
+    /// parent nodes (/Resources, /MediaBox, /CropBox and /Rotate) are passed down
+    /// and applied to each page that does not define them itself.
+    /// See sec. 7.7.3.4, Table 31 (pg. 106)
+    fn collect_pages(
+        &self,
+        node: &PdfObject,
+        inherited: &Dictionary,
+        pages: &mut Vec<Page>,
+        visited: &mut HashSet<ObjectId>,
+    ) {
+        let id = node.as_reference();
+        if id.is_some_and(|id| !visited.insert(id)) {
+            return;
+        }
+        let Some(dict) = self.resolve(node).as_dict() else {
+            return;
+        };
+
+        let mut attributes = inherited.clone();
+        for key in Page::INHERITABLE {
+            if let Some(value) = dict.get(key) {
+                attributes.set(key, value.clone());
+            }
+        }
+
+        match dict.get("Kids").map(|kids| self.resolve(kids)) {
+            Some(PdfObject::Array(kids)) if !dict.has_type("Page") => {
+                for kid in kids {
+                    self.collect_pages(kid, &attributes, pages, visited);
+                }
+            }
+            _ => {
+                let mut page_dict = dict.clone();
+                for key in Page::INHERITABLE {
+                    if !page_dict.contains_key(key) {
+                        if let Some(value) = attributes.get(key) {
+                            page_dict.set(key, value.clone());
+                        }
+                    }
+                }
+                pages.push(Page::new(id, page_dict));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::encryption::read_private_key;
+
+    /// Saves a document and reads it back, to check what is written.
+    pub(crate) fn reload(doc: &Document) -> Document {
+        let mut data = vec![];
+        doc.save(&mut data).unwrap();
+        Document::from_bytes(data).unwrap()
+    }
+
+    #[test]
+    fn test_document_pages() -> Result<(), io::Error> {
+        let doc = Document::load("HelloWorld.pdf")?;
+        assert!(doc.catalog().unwrap().has_type("Catalog"));
+        let pages = doc.pages();
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].id, Some((1, 0)));
+        assert_eq!(pages[0].media_box(), [0.0, 0.0, 612.0, 792.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted() -> Result<(), io::Error> {
+        let files = [
+            ("samples/rc4-40.pdf", "", "owner"),
+            ("samples/rc4-128.pdf", "", "owner"),
+            ("samples/aes-128.pdf", "user", "owner"),
+            ("samples/aes-256.pdf", "user", "owner"),
+        ];
+        for (path, user, owner) in files {
+            for password in [user, owner] {
+                let doc = Document::load_with_password(path, password)?;
+                assert!(doc.pdf.trailer.entries.get("Encrypt").is_none());
+                let info = doc.resolve_dict(&doc.pdf.trailer.entries, "Info").unwrap();
+                assert_eq!(
+                    info.get("Title").and_then(PdfObject::as_bytes),
+                    Some(&b"Secret (report)"[..])
+                );
+                let page = &doc.pages()[0];
+                let contents = doc.resolve_key(&page.dict, "Contents").unwrap();
+                let data = doc.decode_stream(contents.as_stream().unwrap()).unwrap();
+                assert!(data.ends_with(b"(Hello, encrypted world) Tj ET"));
+
+                let mut saved = vec![];
+                doc.save(&mut saved)?;
+                let reloaded = Document::from_bytes(saved)?;
+                assert_eq!(reloaded.pages().len(), 1);
+                let err = doc.save_incremental(&mut vec![]).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+            }
+        }
+
+        let err = Document::load("samples/aes-256.pdf").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        let err = Document::load_with_password("samples/aes-128.pdf", "wrong").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_key() -> Result<(), io::Error> {
+        let key_a = read_private_key(&read::read_bytes("samples/recipient-a.pem")?).unwrap();
+        let key_b = read_private_key(&read::read_bytes("samples/recipient-b.der")?).unwrap();
+        let files = [
+            ("samples/pubsec-rc4.pdf", &key_a),
+            ("samples/pubsec-aes-256.pdf", &key_a),
+            ("samples/pubsec-aes-256.pdf", &key_b),
+        ];
+        for (path, key) in files {
+            let doc = Document::load_with_private_key(path, key)?;
+            let info = doc.resolve_dict(doc.trailer(), "Info").unwrap();
+            assert_eq!(
+                info.get("Title").and_then(PdfObject::as_bytes),
+                Some(&b"Partner report"[..])
+            );
+            let contents = doc.pages()[0].contents(&doc)?;
+            assert!(contents.ends_with(b"(Hello, recipient) Tj ET\n"));
+        }
+
+        // The seed of this sample was enveloped for both recipients by
+        // OpenSSL's `cms -encrypt`, and its strings and streams were encrypted
+        // with Python's cryptography package.
+        for key in [&key_a, &key_b] {
+            let doc = Document::load_with_private_key("samples/pubsec-openssl.pdf", key)?;
+            let info = doc.resolve_dict(doc.trailer(), "Info").unwrap();
+            assert_eq!(
+                info.get("Title").and_then(PdfObject::as_bytes),
+                Some(&b"Made with OpenSSL"[..])
+            );
+            let contents = doc.pages()[0].contents(&doc)?;
+            assert!(contents.starts_with(b"BT /F1 24 Tf 72 700 Td (Hello from OpenSSL) Tj ET"));
+        }
+
+        let err = Document::load_with_private_key("samples/pubsec-rc4.pdf", &key_b).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        let err = Document::load("samples/pubsec-rc4.pdf").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        Ok(())
+    }
+}
@@ -0,0 +1,813 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    ops::Range,
+};
+
+use crate::api::{
+    builder::DocumentBuilder,
+    document::Document,
+    objects::{Dictionary, ObjectId, PdfObject},
+    page::Page,
+    write,
+};
+
+/// Dictionaries of these types only describe how something is drawn, so
+/// identical copies of them can be shared.
+const SHAREABLE_TYPES: [&str; 6] = [
+    "Font",
+    "FontDescriptor",
+    "Encoding",
+    "ExtGState",
+    "Pattern",
+    "Halftone",
+];
+
+/// The keys of a resource dictionary. See sec. 7.8.3, Table 33
+const RESOURCE_KEYS: [&str; 8] = [
+    "ExtGState",
+    "ColorSpace",
+    "Pattern",
+    "Shading",
+    "XObject",
+    "Font",
+    "ProcSet",
+    "Properties",
+];
+
+impl Document {
+    /// Adds an indirect object with the next free object number.
+    pub fn add_object(&mut self, object: PdfObject) -> ObjectId {
+        let objects = &mut self.pdf.body.objects;
+        let number = objects.keys().last().map_or(1, |(number, _)| number + 1);
+        let id = (number.max(1), 0);
+        objects.insert(id, object);
+        id
+    }
+
+    /// Keeps the pages at the given indices, in the order given. Pages that are
+    /// not listed are removed as by `delete_pages`. Each index may be listed
+    /// once.
+    pub fn reorder_pages(&mut self, order: &[usize]) -> Result<(), io::Error> {
+        let pages = self.pages();
+        let mut listed = HashSet::new();
+        let mut reordered = vec![];
+        for &index in order {
+            let page = pages
+                .get(index)
+                .ok_or_else(|| out_of_range(index..index + 1, pages.len()))?;
+            if !listed.insert(index) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("page {} is listed more than once", index),
+                ));
+            }
+            reordered.push(page.clone());
+        }
+        let deleted: HashSet<ObjectId> = pages
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !listed.contains(index))
+            .filter_map(|(_, page)| page.id)
+            .collect();
+        self.set_pages(reordered)?;
+        self.prune_destinations(&deleted);
+        Ok(())
+    }
+
+    /// Removes the pages at the given indices from the page tree, along with
+    /// what leads to them: link annotations of the other pages that go to
+    /// them, their named destinations, and the destinations of outline items,
+    /// which stay as headings. The objects the pages use stay in the body
+    /// until the document is optimized, see `optimize::optimize`.
+    pub fn delete_pages(&mut self, indices: &[usize]) -> Result<(), io::Error> {
+        let count = self.pages().len();
+        if let Some(&index) = indices.iter().find(|&&index| index >= count) {
+            return Err(out_of_range(index..index + 1, count));
+        }
+        let indices: HashSet<usize> = indices.iter().copied().collect();
+        let order: Vec<usize> = (0..count).filter(|i| !indices.contains(i)).collect();
+        self.reorder_pages(&order)
+    }
+
+    /// Sets the /Rotate entry of a page: the number of degrees by which it is
+    /// rotated clockwise when displayed, a multiple of 90.
+    /// See sec. 7.7.3.3, Table 31 (pg. 104)
+    pub fn set_rotation(&mut self, index: usize, degrees: i64) -> Result<(), io::Error> {
+        if degrees % 90 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("rotation of {} degrees is not a multiple of 90", degrees),
+            ));
+        }
+        let mut pages = self.pages();
+        let count = pages.len();
+        let page = pages
+            .get_mut(index)
+            .ok_or_else(|| out_of_range(index..index + 1, count))?;
+        let rotate = PdfObject::Integer(degrees.rem_euclid(360));
+        match page.id.and_then(|id| self.get_mut(id)) {
+            Some(PdfObject::Dictionary(dict)) => {
+                dict.set("Rotate", rotate);
+                Ok(())
+            }
+            _ => {
+                page.dict.set("Rotate", rotate);
+                self.set_pages(pages)
+            }
+        }
+    }
+
+    /// Rotates a page clockwise by the given number of degrees, a multiple of
+    /// 90, in addition to its current rotation.
+    pub fn rotate_page(&mut self, index: usize, degrees: i64) -> Result<(), io::Error> {
+        let count = self.pages().len();
+        let page = self
+            .page(index)
+            .ok_or_else(|| out_of_range(index..index + 1, count))?;
+        self.set_rotation(index, page.rotation() + degrees)
+    }
+
+    /// Copies the pages in the range into a new document, with the objects they
+    /// use renumbered from 1.
+    pub fn extract_pages(&self, range: Range<usize>) -> Result<Document, io::Error> {
+        let mut doc = DocumentBuilder::new().build();
+        doc.pdf.header.version = self.pdf.header.version.clone();
+        doc.insert_pages(0, self, range)?;
+        Ok(doc)
+    }
+
+    /// Copies every page of another document to the end of this one.
+    pub fn append(&mut self, source: &Document) -> Result<(), io::Error> {
+        let at = self.pages().len();
+        self.insert_pages(at, source, 0..source.pages().len())
+    }
+
+    /// Copies the pages in the range of another document into this one, before
+    /// the page at index `at`.
+    ///
+    /// The objects the pages use, such as fonts, images and content streams,
+    /// are copied along with them and given new object numbers. A copied
+    /// object that is identical to one this document already has is replaced
+    /// by a reference to it, so that merging documents that use the same
+    /// fonts or images does not duplicate them. References to pages that are
+    /// not copied, e.g. in link annotations, become null.
+    pub fn insert_pages(
+        &mut self,
+        at: usize,
+        source: &Document,
+        range: Range<usize>,
+    ) -> Result<(), io::Error> {
+        let mut pages = self.pages();
+        let source_pages = source.pages();
+        if range.start > range.end || range.end > source_pages.len() {
+            return Err(out_of_range(range, source_pages.len()));
+        }
+        if at > pages.len() {
+            return Err(out_of_range(at..at + 1, pages.len()));
+        }
+
+        let mut copier = Copier::new(self, source);
+        let mut copied = vec![];
+        for page in &source_pages[range.clone()] {
+            let id = self.add_object(PdfObject::Null);
+            if let Some(source_id) = page.id {
+                copier.pages.insert(source_id, Some(id));
+            }
+            copied.push(id);
+        }
+        let mut inserted = vec![];
+        for (page, id) in source_pages[range].iter().zip(copied) {
+            let dict = page
+                .dict
+                .iter()
+                .filter(|(key, _)| *key != "Parent")
+                .map(|(key, value)| (key.clone(), copier.copy(self, value)))
+                .collect();
+            inserted.push(Page::new(Some(id), dict));
+        }
+        pages.splice(at..at, inserted);
+        raise_version(self, &source.pdf.header.version);
+        self.set_pages(pages)
+    }
+
+    /// Rebuilds the page tree as a single node holding the pages in order, and
+    /// removes the intermediate nodes. The attributes each page inherited are
+    /// set on the page itself, since the nodes it inherited them from may no
+    /// longer be its ancestors. See sec. 7.7.3.2 (pg. 102)
+    fn set_pages(&mut self, pages: Vec<Page>) -> Result<(), io::Error> {
+        let root = self.page_tree_root()?;
+        for id in self.page_tree_nodes(root) {
+            if id != root {
+                self.pdf.body.objects.remove(&id);
+            }
+        }
+        let mut kids = vec![];
+        for page in pages {
+            let mut dict = page.dict;
+            dict.set("Parent", PdfObject::Reference(root));
+            let id = match page.id {
+                Some(id) => {
+                    self.pdf
+                        .body
+                        .objects
+                        .insert(id, PdfObject::Dictionary(dict));
+                    id
+                }
+                None => self.add_object(PdfObject::Dictionary(dict)),
+            };
+            kids.push(PdfObject::Reference(id));
+        }
+
+        let mut node = self
+            .get(root)
+            .and_then(PdfObject::as_dict)
+            .cloned()
+            .unwrap_or_default();
+        for key in Page::INHERITABLE.iter().chain(&["Parent"]) {
+            node.remove(key);
+        }
+        node.set("Type", PdfObject::Name("Pages".to_string()));
+        node.set("Count", PdfObject::Integer(kids.len() as i64));
+        node.set("Kids", PdfObject::Array(kids));
+        self.pdf
+            .body
+            .objects
+            .insert(root, PdfObject::Dictionary(node));
+        Ok(())
+    }
+
+    /// The nodes of the page tree below and including `root`, which are not
+    /// pages.
+    fn page_tree_nodes(&self, root: ObjectId) -> Vec<ObjectId> {
+        let mut nodes = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let Some(node) = self.get(id).and_then(PdfObject::as_dict) else {
+                continue;
+            };
+            if node.has_type("Page") {
+                continue;
+            }
+            nodes.push(id);
+            if let Some(kids) = self.resolve_key(node, "Kids").and_then(PdfObject::as_array) {
+                stack.extend(kids.iter().filter_map(PdfObject::as_reference));
+            }
+        }
+        nodes
+    }
+
+    /// Removes the link annotations, named destinations and outline item
+    /// destinations that go to the given pages. Everything is looked up
+    /// before anything is removed, since links and outline items may go to a
+    /// page through a named destination.
+    fn prune_destinations(&mut self, deleted: &HashSet<ObjectId>) {
+        if deleted.is_empty() {
+            return;
+        }
+        let goes_to_deleted = |dest: &PdfObject| {
+            self.explicit_destination(dest)
+                .and_then(|array| array.first())
+                .and_then(PdfObject::as_reference)
+                .is_some_and(|id| deleted.contains(&id))
+        };
+        let target_deleted = |dict: &Dictionary| {
+            let dest = match self.resolve_key(dict, "Dest") {
+                Some(dest) => Some(dest),
+                None => self
+                    .resolve_dict(dict, "A")
+                    .filter(|action| action.get_name("S") == Some("GoTo"))
+                    .and_then(|action| self.resolve_key(action, "D")),
+            };
+            dest.is_some_and(goes_to_deleted)
+        };
+
+        let mut annots = vec![];
+        for page_id in self.pages().iter().filter_map(|page| page.id) {
+            let all = self.page_annots(page_id);
+            let kept: Vec<PdfObject> = all
+                .iter()
+                .filter(|annot| {
+                    !self.resolve(annot).as_dict().is_some_and(|dict| {
+                        dict.get_name("Subtype") == Some("Link") && target_deleted(dict)
+                    })
+                })
+                .cloned()
+                .collect();
+            if kept.len() != all.len() {
+                annots.push((page_id, kept));
+            }
+        }
+
+        let mut items = vec![];
+        let mut visited = HashSet::new();
+        let root = self
+            .catalog()
+            .and_then(|catalog| self.resolve_dict(catalog, "Outlines"));
+        let mut stack: Vec<&PdfObject> = root
+            .and_then(|root| root.get("First"))
+            .into_iter()
+            .collect();
+        while let Some(next) = stack.pop() {
+            let Some(id) = next.as_reference().filter(|&id| visited.insert(id)) else {
+                continue;
+            };
+            let Some(item) = self.get(id).and_then(PdfObject::as_dict) else {
+                continue;
+            };
+            if target_deleted(item) {
+                items.push(id);
+            }
+            stack.extend(["First", "Next"].iter().filter_map(|key| item.get(key)));
+        }
+
+        // The catalog's /Dests dictionary, and the /Dests name tree.
+        let dests_object = self.catalog().and_then(|catalog| catalog.get("Dests"));
+        let dests_id = dests_object.and_then(PdfObject::as_reference);
+        let dests = dests_object
+            .and_then(|dests| self.resolve(dests).as_dict())
+            .filter(|dests| dests.iter().any(|(_, dest)| goes_to_deleted(dest)))
+            .map(|dests| {
+                dests
+                    .iter()
+                    .filter(|(_, dest)| !goes_to_deleted(dest))
+                    .map(|(name, dest)| (name.clone(), dest.clone()))
+                    .collect::<Dictionary>()
+            });
+        let tree = self
+            .catalog()
+            .and_then(|catalog| self.resolve_dict(catalog, "Names"))
+            .and_then(|names| self.resolve_dict(names, "Dests"))
+            .map(|tree| self.name_tree(tree))
+            .unwrap_or_default();
+        let tree_count = tree.len();
+        let tree: Vec<(Vec<u8>, PdfObject)> = tree
+            .into_iter()
+            .filter(|(_, dest)| !goes_to_deleted(dest))
+            .map(|(name, dest)| (name, dest.clone()))
+            .collect();
+
+        for (page_id, annots) in annots {
+            self.set_page_annots(page_id, annots);
+        }
+        for id in items {
+            if let Some(PdfObject::Dictionary(item)) = self.get_mut(id) {
+                item.remove("Dest");
+                item.remove("A");
+            }
+        }
+        match (dests, dests_id) {
+            (Some(dests), Some(id)) => {
+                self.pdf
+                    .body
+                    .objects
+                    .insert(id, PdfObject::Dictionary(dests));
+            }
+            (Some(dests), None) => {
+                if let Some(PdfObject::Dictionary(catalog)) =
+                    self.catalog_id().and_then(|id| self.get_mut(id))
+                {
+                    catalog.set("Dests", PdfObject::Dictionary(dests));
+                }
+            }
+            _ => {}
+        }
+        if tree.len() != tree_count {
+            self.set_name_tree("Dests", tree);
+        }
+    }
+
+    /// The object holding the root of the page tree, created if the catalog
+    /// does not refer to one.
+    fn page_tree_root(&mut self) -> Result<ObjectId, io::Error> {
+        let no_catalog = || io::Error::new(io::ErrorKind::InvalidData, "missing document catalog");
+        let catalog_id = self.catalog_id().ok_or_else(no_catalog)?;
+        let catalog = self.catalog().ok_or_else(no_catalog)?;
+        if let Some(id) = catalog.get("Pages").and_then(PdfObject::as_reference) {
+            return Ok(id);
+        }
+        let id = self.add_object(PdfObject::Null);
+        match self.get_mut(catalog_id) {
+            Some(PdfObject::Dictionary(catalog)) => {
+                catalog.set("Pages", PdfObject::Reference(id));
+                Ok(id)
+            }
+            _ => Err(no_catalog()),
+        }
+    }
+}
+
+pub(crate) fn out_of_range(range: Range<usize>, count: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "pages {:?} are out of range for a document of {} pages",
+            range, count
+        ),
+    )
+}
+
+/// Raises the header version of the document to `version` if it is lower.
+fn raise_version(doc: &mut Document, version: &str) {
+    let parse = |version: &str| version.parse::<f64>().unwrap_or(0.0);
+    if parse(version) > parse(&doc.pdf.header.version) {
+        doc.pdf.header.version = version.to_string();
+    }
+}
+
+///
+/// Copies objects from one document into another, following references
+/// depth-first. Each object is copied once, however many references lead to
+/// it, and objects the target already has an identical copy of are shared.
+///
+struct Copier<'a> {
+    source: &'a Document,
+    /// The copies of the objects of the source, by the source's object id.
+    copies: HashMap<ObjectId, ObjectId>,
+    /// The pages of the source, mapped to their copies for the pages that are
+    /// copied. References to the other pages become null.
+    pages: HashMap<ObjectId, Option<ObjectId>>,
+    /// The objects of the target that can be shared, by their serialization.
+    shared: HashMap<Vec<u8>, ObjectId>,
+    /// Objects being copied, and those among them that refer back to
+    /// themselves, whose copy is already referred to and cannot be replaced.
+    in_progress: HashSet<ObjectId>,
+    cyclic: HashSet<ObjectId>,
+}
+
+impl<'a> Copier<'a> {
+    fn new(target: &Document, source: &'a Document) -> Copier<'a> {
+        let pages = source
+            .pages()
+            .iter()
+            .filter_map(|page| page.id)
+            .map(|id| (id, None))
+            .collect();
+        let shared = target
+            .pdf
+            .body
+            .objects
+            .iter()
+            .filter(|(_, object)| is_shareable(object))
+            .map(|(&id, object)| (serialize(object), id))
+            .collect();
+        Copier {
+            source,
+            copies: HashMap::new(),
+            pages,
+            shared,
+            in_progress: HashSet::new(),
+            cyclic: HashSet::new(),
+        }
+    }
+
+    /// Copies a direct object, copying the indirect objects it refers to into
+    /// the target and renumbering the references.
+    fn copy(&mut self, target: &mut Document, object: &PdfObject) -> PdfObject {
+        match object {
+            PdfObject::Reference(id) => self.copy_indirect(target, *id),
+            PdfObject::Array(items) => {
+                PdfObject::Array(items.iter().map(|i| self.copy(target, i)).collect())
+            }
+            PdfObject::Dictionary(dict) => PdfObject::Dictionary(self.copy_dict(target, dict)),
+            PdfObject::Stream(stream) => {
+                let mut stream = stream.clone();
+                stream.dict = self.copy_dict(target, &stream.dict);
+                PdfObject::Stream(stream)
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn copy_dict(&mut self, target: &mut Document, dict: &Dictionary) -> Dictionary {
+        dict.iter()
+            .map(|(key, value)| (key.clone(), self.copy(target, value)))
+            .collect()
+    }
+
+    fn copy_indirect(&mut self, target: &mut Document, id: ObjectId) -> PdfObject {
+        if let Some(page) = self.pages.get(&id) {
+            return page.map_or(PdfObject::Null, PdfObject::Reference);
+        }
+        if self.in_progress.contains(&id) {
+            self.cyclic.insert(id);
+        }
+        if let Some(&copy) = self.copies.get(&id) {
+            return PdfObject::Reference(copy);
+        }
+        let object = match self.source.get(id) {
+            Some(object) if !has_unshared_type(object, &["Catalog", "Pages"]) => object,
+            _ => return PdfObject::Null,
+        };
+
+        let copy_id = target.add_object(PdfObject::Null);
+        self.copies.insert(id, copy_id);
+        self.in_progress.insert(id);
+        let copy = self.copy(target, object);
+        self.in_progress.remove(&id);
+
+        if is_shareable(&copy) && !self.cyclic.contains(&id) {
+            let key = serialize(&copy);
+            if let Some(&existing) = self.shared.get(&key) {
+                target.pdf.body.objects.remove(&copy_id);
+                self.copies.insert(id, existing);
+                return PdfObject::Reference(existing);
+            }
+            self.shared.insert(key, copy_id);
+        }
+        target.pdf.body.objects.insert(copy_id, copy);
+        PdfObject::Reference(copy_id)
+    }
+}
+
+fn has_unshared_type(object: &PdfObject, types: &[&str]) -> bool {
+    object
+        .as_dict()
+        .is_some_and(|dict| types.iter().any(|t| dict.has_type(t)))
+}
+
+/// Whether identical copies of the object can be replaced by one: streams,
+/// arrays such as color spaces, and the dictionaries of fonts, graphics
+/// states, patterns, shadings, functions and resources. Other dictionaries,
+/// such as pages, annotations, outline items, form fields, structure
+/// elements, optional content groups and actions, each have an identity of
+/// their own, even when they are identical.
+pub(crate) fn is_shareable(object: &PdfObject) -> bool {
+    match object {
+        PdfObject::Array(_) | PdfObject::Stream(_) => true,
+        PdfObject::Dictionary(dict) => match dict.get_name("Type") {
+            Some(name) => SHAREABLE_TYPES.contains(&name),
+            None => {
+                ["ShadingType", "FunctionType", "PatternType"]
+                    .iter()
+                    .any(|key| dict.contains_key(key))
+                    || (!dict.is_empty()
+                        && dict
+                            .iter()
+                            .all(|(key, _)| RESOURCE_KEYS.contains(&key.as_str())))
+            }
+        },
+        _ => false,
+    }
+}
+
+pub(crate) fn serialize(object: &PdfObject) -> Vec<u8> {
+    let mut data = vec![];
+    // Writing to a Vec cannot fail.
+    let _ = write::write_object(&mut data, object);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        annotation::LinkTarget,
+        document::tests::reload,
+        outline::{Destination, OutlineItem},
+    };
+
+    /// A document whose pages each show their label in Helvetica.
+    fn labeled(labels: &[&str]) -> Document {
+        let mut builder = DocumentBuilder::new();
+        let font = builder.add_standard_font("Helvetica").unwrap();
+        for label in labels {
+            builder.add_page([0.0, 0.0, 612.0, 792.0], |page| {
+                page.text(font, 12.0, 72.0, 720.0, label);
+            });
+        }
+        builder.build()
+    }
+
+    fn labels(doc: &Document) -> Vec<String> {
+        doc.pages()
+            .iter()
+            .map(|page| {
+                let content = page.contents(doc).unwrap();
+                let start = content.iter().position(|&b| b == b'(').unwrap();
+                let end = content.iter().position(|&b| b == b')').unwrap();
+                String::from_utf8_lossy(&content[start + 1..end]).into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reorder_pages() -> Result<(), io::Error> {
+        let mut doc = labeled(&["A", "B", "C", "D"]);
+        // The media box is inherited from the root of the page tree.
+        let root = doc.catalog().unwrap().get("Pages").unwrap().as_reference();
+        for page in doc.pages() {
+            if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(page.id.unwrap()) {
+                dict.remove("MediaBox");
+            }
+        }
+        if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(root.unwrap()) {
+            let media_box = [0, 0, 200, 300].map(PdfObject::Integer);
+            dict.set("MediaBox", PdfObject::Array(media_box.to_vec()));
+        }
+
+        doc.reorder_pages(&[3, 0, 2, 1])?;
+        assert_eq!(labels(&doc), ["D", "A", "C", "B"]);
+        doc.delete_pages(&[1, 3])?;
+        assert_eq!(labels(&doc), ["D", "C"]);
+        doc.rotate_page(0, 90)?;
+        doc.rotate_page(0, 180)?;
+        doc.set_rotation(1, -90)?;
+
+        let doc = reload(&doc);
+        assert_eq!(labels(&doc), ["D", "C"]);
+        let pages = doc.pages();
+        assert_eq!(pages[0].rotation(), 270);
+        assert_eq!(pages[1].rotation(), 270);
+        assert_eq!(pages[1].media_box(), [0.0, 0.0, 200.0, 300.0]);
+        let root = doc.resolve_dict(doc.catalog().unwrap(), "Pages").unwrap();
+        assert_eq!(root.get_i64("Count"), Some(2));
+        assert!(root.get("MediaBox").is_none());
+
+        let mut doc = labeled(&["A", "B"]);
+        let err = doc.reorder_pages(&[0, 0]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(doc.delete_pages(&[2]).is_err());
+        assert!(doc.set_rotation(0, 45).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_intermediate_nodes() -> Result<(), io::Error> {
+        // Pages A and B inherit their resources, crop box and rotation from an
+        // intermediate node; C and D hang from another one.
+        let mut doc = labeled(&["A", "B", "C", "D"]);
+        let root = doc.catalog().unwrap().get("Pages").unwrap().as_reference();
+        let root = root.unwrap();
+        let pages = doc.pages();
+        let resources = pages[0].dict.get("Resources").unwrap().clone();
+        let mut nodes = vec![];
+        for (pair, attributes) in pages.chunks(2).zip([true, false]) {
+            let mut node = Dictionary::new();
+            node.set("Type", PdfObject::Name("Pages".to_string()));
+            node.set("Parent", PdfObject::Reference(root));
+            node.set("Count", PdfObject::Integer(2));
+            let kids = pair
+                .iter()
+                .map(|page| PdfObject::Reference(page.id.unwrap()));
+            node.set("Kids", PdfObject::Array(kids.collect()));
+            if attributes {
+                node.set("Resources", resources.clone());
+                let crop_box = [10, 10, 500, 700].map(PdfObject::Integer);
+                node.set("CropBox", PdfObject::Array(crop_box.to_vec()));
+                node.set("Rotate", PdfObject::Integer(90));
+            }
+            let node = doc.add_object(PdfObject::Dictionary(node));
+            for page in pair {
+                if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(page.id.unwrap()) {
+                    dict.set("Parent", PdfObject::Reference(node));
+                    if attributes {
+                        dict.remove("Resources");
+                    }
+                }
+            }
+            nodes.push(node);
+        }
+        if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(root) {
+            let kids = nodes.iter().map(|&node| PdfObject::Reference(node));
+            dict.set("Kids", PdfObject::Array(kids.collect()));
+        }
+        assert_eq!(doc.pages()[1].rotation(), 90);
+
+        doc.delete_pages(&[0, 2])?;
+        assert!(nodes.iter().all(|&node| doc.get(node).is_none()));
+        let doc = reload(&doc);
+        assert_eq!(labels(&doc), ["B", "D"]);
+        let pages = doc.pages();
+        let page = doc.get(pages[0].id.unwrap()).unwrap().as_dict().unwrap();
+        assert!(page.get("Resources").is_some());
+        assert_eq!(pages[0].crop_box(), [10.0, 10.0, 500.0, 700.0]);
+        assert_eq!(pages[0].rotation(), 90);
+        assert!(pages[0].resources(&doc).contains_key("Font"));
+        assert_eq!(pages[1].rotation(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_pages_prunes_destinations() -> Result<(), io::Error> {
+        let mut doc = labeled(&["A", "B", "C"]);
+        let ids: Vec<ObjectId> = doc.pages().iter().map(|page| page.id.unwrap()).collect();
+        doc.set_named_destinations(&[
+            ("b".to_string(), Destination::new(1)),
+            ("c".to_string(), Destination::new(2)),
+        ]);
+        let mut section = OutlineItem::new("B", Some(Destination::new(1)));
+        section.children = vec![OutlineItem::new("C", Some(Destination::new(2)))];
+        doc.set_outlines(&[section]);
+
+        // Links on page A to B, to B by name, and to C.
+        let fit = |id| {
+            PdfObject::Array(vec![
+                PdfObject::Reference(id),
+                PdfObject::Name("Fit".to_string()),
+            ])
+        };
+        // PDF 1.1 named destinations, in the catalog.
+        let dests: Dictionary = [("old-b", fit(ids[1])), ("old-c", fit(ids[2]))]
+            .into_iter()
+            .map(|(name, dest)| (name.to_string(), dest))
+            .collect();
+        let dests = doc.add_object(PdfObject::Dictionary(dests));
+        if let Some(PdfObject::Dictionary(catalog)) =
+            doc.catalog_id().and_then(|id| doc.get_mut(id))
+        {
+            catalog.set("Dests", PdfObject::Reference(dests));
+        }
+
+        let mut annots = vec![];
+        for dest in [fit(ids[1]), PdfObject::String(b"b".to_vec()), fit(ids[2])] {
+            let mut link = Dictionary::new();
+            link.set("Type", PdfObject::Name("Annot".to_string()));
+            link.set("Subtype", PdfObject::Name("Link".to_string()));
+            link.set("Dest", dest);
+            annots.push(PdfObject::Reference(
+                doc.add_object(PdfObject::Dictionary(link)),
+            ));
+        }
+        if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(ids[0]) {
+            dict.set("Annots", PdfObject::Array(annots));
+        }
+
+        doc.delete_pages(&[1])?;
+        let doc = reload(&doc);
+        assert_eq!(labels(&doc), ["A", "C"]);
+        assert_eq!(
+            doc.named_destinations(),
+            [
+                ("c".to_string(), Destination::new(1)),
+                ("old-c".to_string(), Destination::new(1))
+            ]
+        );
+        let mut section = OutlineItem::new("B", None);
+        section.children = vec![OutlineItem::new("C", Some(Destination::new(1)))];
+        assert_eq!(doc.outlines(), [section]);
+        let links = doc.pages()[0].links(&doc);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, Some(LinkTarget::Page(Destination::new(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_pages() -> Result<(), io::Error> {
+        let mut doc = labeled(&["A", "B"]);
+        let other = labeled(&["C", "D", "E"]);
+        doc.append(&other)?;
+        doc.insert_pages(1, &other, 2..3)?;
+        assert_eq!(labels(&doc), ["A", "E", "B", "C", "D", "E"]);
+
+        // Helvetica is shared rather than copied with each page.
+        let fonts = doc
+            .pdf
+            .body
+            .objects
+            .values()
+            .filter(|object| object.as_dict().is_some_and(|d| d.has_type("Font")))
+            .count();
+        assert_eq!(fonts, 1);
+
+        let mut doc = reload(&doc);
+        assert_eq!(labels(&doc), ["A", "E", "B", "C", "D", "E"]);
+        let root = doc.catalog().unwrap().get("Pages").cloned();
+        assert!(doc
+            .pages()
+            .iter()
+            .all(|page| page.dict.get("Parent") == root.as_ref()));
+        assert!(doc.insert_pages(0, &other, 2..4).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_pages() -> Result<(), io::Error> {
+        let mut doc = labeled(&["A", "B", "C"]);
+        // A link on page B to page C, which is not extracted with it.
+        let target = doc.pages()[2].id.unwrap();
+        let mut link = Dictionary::new();
+        link.set("Type", PdfObject::Name("Annot".to_string()));
+        link.set("Subtype", PdfObject::Name("Link".to_string()));
+        link.set("Dest", PdfObject::Array(vec![PdfObject::Reference(target)]));
+        let link = doc.add_object(PdfObject::Dictionary(link));
+        let page = doc.pages()[1].id.unwrap();
+        if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(page) {
+            dict.set("Annots", PdfObject::Array(vec![PdfObject::Reference(link)]));
+        }
+
+        let extracted = reload(&doc.extract_pages(0..2)?);
+        assert_eq!(labels(&extracted), ["A", "B"]);
+        let page = &extracted.pages()[1];
+        let annots = extracted.resolve_key(&page.dict, "Annots").unwrap();
+        let link = extracted.resolve(&annots.as_array().unwrap()[0]);
+        let dest = link.as_dict().unwrap().get("Dest").unwrap();
+        assert_eq!(dest, &PdfObject::Array(vec![PdfObject::Null]));
+        assert!(extracted.pdf.body.objects.len() < doc.pdf.body.objects.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,1191 @@
+/// PKCS#7 enveloped data, which holds the seed of the public-key security
+/// handler for each recipient.
+pub mod pkcs7;
+
+use std::{collections::BTreeMap, fmt, io};
+
+use aes::{
+    cipher::{
+        block_padding::NoPadding, generic_array::GenericArray, BlockDecrypt, BlockDecryptMut,
+        BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher,
+    },
+    Aes128, Aes192, Aes256,
+};
+use md5::{Digest, Md5};
+use rc4::{consts, Rc4};
+pub use rsa::RsaPrivateKey;
+use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey};
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+
+use crate::api::{
+    encryption::pkcs7::decrypt_enveloped_data,
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+};
+
+/// Passwords shorter than 32 bytes are padded with these bytes, and an empty
+/// password is made of them entirely. See sec. 7.6.4.3.2, Algorithm 2 (pg. 82)
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+///
+/// Errors raised while opening an encrypted document.
+///
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// The document uses a security handler, revision or crypt filter method
+    /// that is not implemented.
+    Unsupported(String),
+    /// The password is neither the user nor the owner password.
+    InvalidPassword,
+    /// The private key is not one of the recipients the document was
+    /// encrypted for.
+    NotARecipient,
+    /// The encryption dictionary is missing required entries.
+    Malformed(String),
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::Unsupported(what) => write!(f, "unsupported encryption: {}", what),
+            EncryptionError::InvalidPassword => write!(f, "incorrect password"),
+            EncryptionError::NotARecipient => write!(f, "the key is not a recipient's"),
+            EncryptionError::Malformed(msg) => {
+                write!(f, "malformed encryption dictionary: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+impl From<EncryptionError> for io::Error {
+    fn from(err: EncryptionError) -> Self {
+        let kind = match err {
+            EncryptionError::Unsupported(_) => io::ErrorKind::Unsupported,
+            EncryptionError::InvalidPassword | EncryptionError::NotARecipient => {
+                io::ErrorKind::PermissionDenied
+            }
+            EncryptionError::Malformed(_) => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+///
+/// The methods a crypt filter may use to encrypt strings and streams.
+/// See sec. 7.6.6, Table 25 (pg. 92)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptMethod {
+    /// The data is not encrypted (the Identity crypt filter).
+    None,
+    /// RC4 with a key derived from the object number (/V2).
+    Rc4,
+    /// AES-128 in CBC mode with a key derived from the object number (/AESV2).
+    AesV2,
+    /// AES-256 in CBC mode with the file encryption key (/AESV3).
+    AesV3,
+}
+
+///
+/// The operations a user who opened the document with the user password may
+/// perform. The owner password grants all of them. See sec. 7.6.4.2, Table 22
+/// (pg. 84)
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Permissions {
+    /// Printing, at full quality.
+    pub print: bool,
+    /// Changing the document other than by the operations below.
+    pub modify: bool,
+    /// Copying or otherwise extracting text and graphics.
+    pub copy: bool,
+    /// Adding or changing annotations, and filling in form fields.
+    pub annotate: bool,
+    /// Filling in form fields, even when `annotate` is not allowed.
+    pub fill_forms: bool,
+    /// Inserting, rotating and deleting pages, and creating bookmarks.
+    pub assemble: bool,
+}
+
+impl Permissions {
+    pub fn all() -> Self {
+        Permissions {
+            print: true,
+            modify: true,
+            copy: true,
+            annotate: true,
+            fill_forms: true,
+            assemble: true,
+        }
+    }
+
+    /// The value of /P. Bits 1 and 2 are 0 and the reserved bits are 1; bit 10,
+    /// extraction for accessibility, is always set as PDF 2.0 asks, and bit 12,
+    /// high-quality printing, follows `print`.
+    pub fn bits(&self) -> i32 {
+        let flags = [
+            (3, self.print),
+            (4, self.modify),
+            (5, self.copy),
+            (6, self.annotate),
+            (9, self.fill_forms),
+            (10, true),
+            (11, self.assemble),
+            (12, self.print),
+        ];
+        let reserved = !0b1111_1111_1111u32 | 0b1100_0000;
+        let bits = flags
+            .iter()
+            .filter(|(_, allowed)| *allowed)
+            .fold(reserved, |bits, (bit, _)| bits | 1 << (bit - 1));
+        bits as i32
+    }
+
+    pub fn from_bits(bits: i32) -> Self {
+        let bit = |n: u32| bits & (1 << (n - 1)) != 0;
+        Permissions {
+            print: bit(3),
+            modify: bit(4),
+            copy: bit(5),
+            annotate: bit(6),
+            fill_forms: bit(9),
+            assemble: bit(11),
+        }
+    }
+}
+
+/// The encryption written by `Encryption`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EncryptionAlgorithm {
+    /// AES-128 with revision 4 of the standard security handler, from PDF 1.6.
+    Aes128,
+    /// AES-256 with revision 6, from PDF 2.0.
+    #[default]
+    Aes256,
+}
+
+impl EncryptionAlgorithm {
+    /// The lowest PDF version that supports the algorithm.
+    pub fn version(&self) -> &'static str {
+        match self {
+            EncryptionAlgorithm::Aes128 => "1.6",
+            EncryptionAlgorithm::Aes256 => "2.0",
+        }
+    }
+}
+
+///
+/// How to encrypt a document when writing it. See `write::WriteOptions`.
+///
+/// Opening the document needs the user password, which may be empty to let
+/// anyone open it with the given permissions. The owner password grants
+/// every permission.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encryption {
+    pub user_password: String,
+    pub owner_password: String,
+    pub permissions: Permissions,
+    pub algorithm: EncryptionAlgorithm,
+}
+
+impl Encryption {
+    /// AES-256 encryption with every permission granted.
+    pub fn new(user_password: &str, owner_password: &str) -> Self {
+        Encryption {
+            user_password: user_password.to_string(),
+            owner_password: owner_password.to_string(),
+            permissions: Permissions::all(),
+            algorithm: EncryptionAlgorithm::Aes256,
+        }
+    }
+}
+
+///
+/// What opens an encrypted document: a password for the standard security
+/// handler, or the private key of a recipient for the public-key one.
+///
+#[derive(Debug, Clone, Copy)]
+pub enum Credentials<'a> {
+    Password(&'a str),
+    PrivateKey(&'a RsaPrivateKey),
+}
+
+///
+/// A security handler holding the file encryption key. The standard security
+/// handler derives the key from a user or owner password (see sec. 7.6.4,
+/// pg. 81), and the public-key security handler from a seed that is encrypted
+/// for each recipient's certificate (see sec. 7.6.5, pg. 89).
+///
+/// Once authenticated, the handler decrypts the strings and streams of each
+/// object with a key computed from the file key and the object's number
+/// (RC4 and AES-128), or with the file key itself (AES-256).
+///
+#[derive(Debug, Clone)]
+pub struct SecurityHandler {
+    pub version: i64,
+    pub revision: i64,
+    /// The access permissions of /P, as bits. See Table 22 (pg. 84)
+    pub permissions: i32,
+    pub encrypt_metadata: bool,
+    /// Whether the password given was the owner password, which grants all
+    /// permissions.
+    pub owner: bool,
+    key: Vec<u8>,
+    string_method: CryptMethod,
+    stream_method: CryptMethod,
+    crypt_filters: BTreeMap<String, CryptMethod>,
+}
+
+impl SecurityHandler {
+    /// Sets up the handler that /Filter names in the encryption dictionary.
+    pub fn open(
+        encrypt: &Dictionary,
+        file_id: &[u8],
+        credentials: Credentials,
+    ) -> Result<SecurityHandler, EncryptionError> {
+        match (encrypt.get_name("Filter"), credentials) {
+            (Some("Adobe.PubSec"), Credentials::PrivateKey(key)) => {
+                SecurityHandler::new_public_key(encrypt, key)
+            }
+            (Some("Adobe.PubSec"), Credentials::Password(_)) => Err(EncryptionError::NotARecipient),
+            (_, Credentials::Password(password)) => {
+                SecurityHandler::new(encrypt, file_id, password)
+            }
+            (_, Credentials::PrivateKey(_)) => Err(EncryptionError::InvalidPassword),
+        }
+    }
+
+    /// Sets up the public-key security handler. Each recipient string holds
+    /// PKCS#7 enveloped data with a 20-byte seed and the recipient's
+    /// permissions. The file key is the SHA-1 hash (SHA-256 for AES-256) of the
+    /// seed followed by every recipient string. With /SubFilter
+    /// adbe.pkcs7.s5, the recipients are listed in the crypt filter used for
+    /// streams. See sec. 7.6.5.3 (pg. 92)
+    pub fn new_public_key(
+        encrypt: &Dictionary,
+        key: &RsaPrivateKey,
+    ) -> Result<SecurityHandler, EncryptionError> {
+        let version = encrypt.get_i64("V").unwrap_or(0);
+        let (string_method, stream_method, crypt_filters) = crypt_methods(encrypt, version)?;
+        let holder = match encrypt.get_name("SubFilter") {
+            Some("adbe.pkcs7.s5") => {
+                let name = encrypt.get_name("StmF").unwrap_or("DefaultCryptFilter");
+                encrypt
+                    .get("CF")
+                    .and_then(PdfObject::as_dict)
+                    .and_then(|cf| cf.get(name))
+                    .and_then(PdfObject::as_dict)
+                    .ok_or_else(|| {
+                        EncryptionError::Malformed(format!("undefined crypt filter {}", name))
+                    })?
+            }
+            Some("adbe.pkcs7.s3" | "adbe.pkcs7.s4") => encrypt,
+            other => {
+                return Err(EncryptionError::Unsupported(format!(
+                    "public-key subfilter {}",
+                    other.unwrap_or("(none)")
+                )))
+            }
+        };
+        let recipients: Vec<&[u8]> = match holder.get("Recipients") {
+            Some(PdfObject::Array(array)) => array.iter().filter_map(PdfObject::as_bytes).collect(),
+            Some(PdfObject::String(recipient)) => vec![recipient],
+            _ => {
+                return Err(EncryptionError::Malformed(
+                    "missing /Recipients".to_string(),
+                ))
+            }
+        };
+        let encrypt_metadata = holder
+            .get("EncryptMetadata")
+            .and_then(PdfObject::as_bool)
+            .unwrap_or(true);
+
+        let mut envelope = None;
+        for recipient in &recipients {
+            if let Some(content) = decrypt_enveloped_data(recipient, key)? {
+                envelope = Some(content);
+                break;
+            }
+        }
+        let envelope = envelope.ok_or(EncryptionError::NotARecipient)?;
+        let (Some(seed), Some(permissions)) = (envelope.get(..20), envelope.get(20..24)) else {
+            return Err(EncryptionError::Malformed(
+                "short recipient seed".to_string(),
+            ));
+        };
+        let mut input = [seed, &recipients.concat()].concat();
+        if !encrypt_metadata {
+            input.extend_from_slice(&[0xFF; 4]);
+        }
+        let key = match stream_method {
+            CryptMethod::AesV3 => Sha256::digest(&input).to_vec(),
+            _ => {
+                let length =
+                    encrypt
+                        .get_i64("Length")
+                        .unwrap_or(if version >= 4 { 128 } else { 40 });
+                Sha1::digest(&input)[..(length as usize / 8).clamp(5, 16)].to_vec()
+            }
+        };
+
+        Ok(SecurityHandler {
+            version,
+            revision: encrypt.get_i64("R").unwrap_or(0),
+            permissions: i32::from_be_bytes(permissions.try_into().unwrap()),
+            encrypt_metadata,
+            owner: false,
+            key,
+            string_method,
+            stream_method,
+            crypt_filters,
+        })
+    }
+
+    /// Authenticates the password against the encryption dictionary, trying it
+    /// as the owner password first and then as the user password. The first
+    /// string of the trailer's /ID enters the key derivation of revisions 2 to 4.
+    pub fn new(
+        encrypt: &Dictionary,
+        file_id: &[u8],
+        password: &str,
+    ) -> Result<SecurityHandler, EncryptionError> {
+        match encrypt.get_name("Filter") {
+            Some("Standard") => {}
+            other => {
+                return Err(EncryptionError::Unsupported(format!(
+                    "security handler {}",
+                    other.unwrap_or("(none)")
+                )))
+            }
+        }
+        let version = encrypt.get_i64("V").unwrap_or(0);
+        let revision = encrypt
+            .get_i64("R")
+            .ok_or_else(|| EncryptionError::Malformed("missing /R".to_string()))?;
+        if !(2..=6).contains(&revision) {
+            return Err(EncryptionError::Unsupported(format!(
+                "revision {}",
+                revision
+            )));
+        }
+        let bytes = |key: &str| {
+            encrypt
+                .get(key)
+                .and_then(PdfObject::as_bytes)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| EncryptionError::Malformed(format!("missing /{}", key)))
+        };
+        let (owner_hash, user_hash) = (bytes("O")?, bytes("U")?);
+        let permissions = encrypt.get_i64("P").unwrap_or(-1) as i32;
+        let encrypt_metadata = encrypt
+            .get("EncryptMetadata")
+            .and_then(PdfObject::as_bool)
+            .unwrap_or(true);
+
+        let (string_method, stream_method, crypt_filters) = crypt_methods(encrypt, version)?;
+
+        let mut handler = SecurityHandler {
+            version,
+            revision,
+            permissions,
+            encrypt_metadata,
+            owner: false,
+            key: vec![],
+            string_method,
+            stream_method,
+            crypt_filters,
+        };
+
+        if revision >= 5 {
+            let (key, owner) = handler.authenticate_aes256(
+                password,
+                &owner_hash,
+                &user_hash,
+                &bytes("OE")?,
+                &bytes("UE")?,
+            )?;
+            handler.key = key;
+            handler.owner = owner;
+            if let Ok(perms) = bytes("Perms") {
+                handler.check_perms(&perms)?;
+            }
+            return Ok(handler);
+        }
+
+        let length = match version {
+            1 => 40,
+            _ => encrypt
+                .get_i64("Length")
+                .unwrap_or(if version >= 4 { 128 } else { 40 }),
+        };
+        let key_length = if revision == 2 {
+            5
+        } else {
+            (length as usize / 8).clamp(5, 16)
+        };
+        let password = latin1_password(password);
+        if let Some(user_password) =
+            handler.owner_to_user_password(&password, &owner_hash, key_length)
+        {
+            let key = handler.file_key(&user_password, &owner_hash, file_id, key_length);
+            if handler.user_hash(&key, file_id) == user_hash_prefix(&user_hash, revision) {
+                handler.key = key;
+                handler.owner = true;
+                return Ok(handler);
+            }
+        }
+        let key = handler.file_key(&password, &owner_hash, file_id, key_length);
+        if handler.user_hash(&key, file_id) == user_hash_prefix(&user_hash, revision) {
+            handler.key = key;
+            return Ok(handler);
+        }
+        Err(EncryptionError::InvalidPassword)
+    }
+
+    /// Sets up the handler for writing a document encrypted as asked, along with
+    /// the encryption dictionary that lets readers authenticate. Without an
+    /// owner password, the user password serves as both. The first string of
+    /// the file's /ID enters the key derivation of AES-128.
+    /// Fails only if no random bytes can be had for the keys.
+    /// See Algorithms 3, 5, 8, 9 and 10 (pg. 83 to 88)
+    pub fn create(
+        encryption: &Encryption,
+        file_id: &[u8],
+    ) -> Result<(SecurityHandler, Dictionary), io::Error> {
+        let owner_password = match encryption.owner_password.is_empty() {
+            true => &encryption.user_password,
+            false => &encryption.owner_password,
+        };
+        let (version, revision, method, key_length) = match encryption.algorithm {
+            EncryptionAlgorithm::Aes128 => (4, 4, CryptMethod::AesV2, 16),
+            EncryptionAlgorithm::Aes256 => (5, 6, CryptMethod::AesV3, 32),
+        };
+        let mut handler = SecurityHandler {
+            version,
+            revision,
+            permissions: encryption.permissions.bits(),
+            encrypt_metadata: true,
+            owner: true,
+            key: vec![],
+            string_method: method,
+            stream_method: method,
+            crypt_filters: BTreeMap::from([("StdCF".to_string(), method)]),
+        };
+
+        let mut dict = Dictionary::new();
+        dict.set("Filter", PdfObject::Name("Standard".to_string()));
+        dict.set("V", PdfObject::Integer(version));
+        dict.set("R", PdfObject::Integer(revision));
+        dict.set("Length", PdfObject::Integer(key_length as i64 * 8));
+        let crypt_filter: Dictionary = [
+            (
+                "CFM",
+                PdfObject::Name(if revision == 6 { "AESV3" } else { "AESV2" }.to_string()),
+            ),
+            ("AuthEvent", PdfObject::Name("DocOpen".to_string())),
+            ("Length", PdfObject::Integer(key_length as i64)),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+        let filters =
+            Dictionary::from_iter([("StdCF".to_string(), PdfObject::Dictionary(crypt_filter))]);
+        dict.set("CF", PdfObject::Dictionary(filters));
+        dict.set("StmF", PdfObject::Name("StdCF".to_string()));
+        dict.set("StrF", PdfObject::Name("StdCF".to_string()));
+
+        if revision == 4 {
+            let user = latin1_password(&encryption.user_password);
+            let owner = latin1_password(owner_password);
+            let owner_hash = handler.owner_hash(&owner, &user, key_length);
+            handler.key = handler.file_key(&user, &owner_hash, file_id, key_length);
+            let mut user_hash = handler.user_hash(&handler.key, file_id);
+            user_hash.resize(32, 0);
+            dict.set("O", PdfObject::String(owner_hash));
+            dict.set("U", PdfObject::String(user_hash));
+        } else {
+            handler.key = random_bytes(32)?;
+            let truncate = |password: &str| password.as_bytes()[..password.len().min(127)].to_vec();
+            let (user, owner) = (
+                truncate(&encryption.user_password),
+                truncate(owner_password),
+            );
+
+            // Each hash is followed by a validation salt and a key salt, and the
+            // file key is encrypted with a key hashed from the password and the
+            // key salt. The owner's hashes include /U.
+            let salts = random_bytes(16)?;
+            let mut user_hash = handler.hash_aes256(&user, &salts[..8], &[]);
+            user_hash.extend_from_slice(&salts);
+            let intermediate = handler.hash_aes256(&user, &salts[8..], &[]);
+            let user_key = cbc_encrypt(&intermediate, &[0; 16], &handler.key).unwrap();
+
+            let salts = random_bytes(16)?;
+            let mut owner_hash = handler.hash_aes256(&owner, &salts[..8], &user_hash);
+            owner_hash.extend_from_slice(&salts);
+            let intermediate = handler.hash_aes256(&owner, &salts[8..], &user_hash);
+            let owner_key = cbc_encrypt(&intermediate, &[0; 16], &handler.key).unwrap();
+
+            // /Perms repeats /P, followed by "T" for encrypted metadata and "adb".
+            let mut perms = [0u8; 16];
+            perms[..4].copy_from_slice(&handler.permissions.to_le_bytes());
+            perms[4..8].copy_from_slice(&[0xFF; 4]);
+            perms[8..12].copy_from_slice(b"Tadb");
+            perms[12..].copy_from_slice(&random_bytes(4)?);
+            Aes256::new_from_slice(&handler.key)
+                .expect("32-byte key")
+                .encrypt_block(GenericArray::from_mut_slice(&mut perms));
+
+            dict.set("O", PdfObject::String(owner_hash));
+            dict.set("U", PdfObject::String(user_hash));
+            dict.set("OE", PdfObject::String(owner_key));
+            dict.set("UE", PdfObject::String(user_key));
+            dict.set("Perms", PdfObject::String(perms.to_vec()));
+        }
+        dict.set("P", PdfObject::Integer(handler.permissions as i64));
+        Ok((handler, dict))
+    }
+
+    /// Computes the file encryption key from a padded user password.
+    /// See Algorithm 2 (pg. 82)
+    fn file_key(
+        &self,
+        password: &[u8],
+        owner_hash: &[u8],
+        file_id: &[u8],
+        length: usize,
+    ) -> Vec<u8> {
+        let mut digest = Md5::new()
+            .chain_update(pad_password(password))
+            .chain_update(&owner_hash[..owner_hash.len().min(32)])
+            .chain_update(self.permissions.to_le_bytes())
+            .chain_update(file_id);
+        if self.revision >= 4 && !self.encrypt_metadata {
+            digest.update([0xFF; 4]);
+        }
+        let mut hash = digest.finalize();
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                hash = Md5::digest(&hash[..length]);
+            }
+        }
+        hash[..length].to_vec()
+    }
+
+    /// Computes the value of /U for a file key: the encrypted padding (revision
+    /// 2), or the first 16 bytes of the encrypted MD5 hash of the padding and the
+    /// file identifier (revision 3 and 4). See Algorithms 4 and 5 (pg. 84)
+    fn user_hash(&self, key: &[u8], file_id: &[u8]) -> Vec<u8> {
+        if self.revision == 2 {
+            return rc4(key, &PASSWORD_PADDING);
+        }
+        let hash = Md5::new()
+            .chain_update(PASSWORD_PADDING)
+            .chain_update(file_id)
+            .finalize();
+        let mut value = rc4(key, &hash);
+        for i in 1..20u8 {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+            value = rc4(&round_key, &value);
+        }
+        value
+    }
+
+    /// The RC4 key that encrypts the user password into /O is derived from the
+    /// owner password. See Algorithm 3 (pg. 83)
+    fn owner_key(&self, password: &[u8], length: usize) -> Vec<u8> {
+        let mut hash = Md5::digest(pad_password(password));
+        if self.revision >= 3 {
+            for _ in 0..50 {
+                hash = Md5::digest(&hash[..length]);
+            }
+        }
+        hash[..length].to_vec()
+    }
+
+    /// Computes /O by encrypting the padded user password with the owner key.
+    /// See Algorithm 3 (pg. 83)
+    fn owner_hash(&self, owner_password: &[u8], user_password: &[u8], length: usize) -> Vec<u8> {
+        let key = self.owner_key(owner_password, length);
+        let mut value = rc4(&key, &pad_password(user_password));
+        if self.revision >= 3 {
+            for i in 1..20u8 {
+                let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+                value = rc4(&round_key, &value);
+            }
+        }
+        value
+    }
+
+    /// Decrypting /O with the owner key gives back the user password.
+    /// See Algorithm 7 (pg. 86)
+    fn owner_to_user_password(
+        &self,
+        password: &[u8],
+        owner_hash: &[u8],
+        length: usize,
+    ) -> Option<Vec<u8>> {
+        let key = self.owner_key(password, length);
+        let encrypted = owner_hash.get(..32)?;
+        if self.revision == 2 {
+            return Some(rc4(&key, encrypted));
+        }
+        let mut value = encrypted.to_vec();
+        for i in (0..20u8).rev() {
+            let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+            value = rc4(&round_key, &value);
+        }
+        Some(value)
+    }
+
+    /// Checks the password against the hashes of /O and /U, and decrypts the
+    /// file key from /OE or /UE with a key hashed from the password.
+    /// See Algorithm 2.A (pg. 83)
+    fn authenticate_aes256(
+        &self,
+        password: &str,
+        owner_hash: &[u8],
+        user_hash: &[u8],
+        owner_key: &[u8],
+        user_key: &[u8],
+    ) -> Result<(Vec<u8>, bool), EncryptionError> {
+        if owner_hash.len() < 48
+            || user_hash.len() < 48
+            || owner_key.len() < 32
+            || user_key.len() < 32
+        {
+            return Err(EncryptionError::Malformed(
+                "short /O, /U, /OE or /UE".to_string(),
+            ));
+        }
+        // Passwords are UTF-8 encoded and truncated to 127 bytes.
+        let mut password = password.as_bytes();
+        password = &password[..password.len().min(127)];
+        let user = &user_hash[..48];
+
+        let candidates = [
+            (
+                &owner_hash[..32],
+                &owner_hash[32..40],
+                &owner_hash[40..48],
+                user,
+                owner_key,
+                true,
+            ),
+            (
+                &user_hash[..32],
+                &user_hash[32..40],
+                &user_hash[40..48],
+                &[][..],
+                user_key,
+                false,
+            ),
+        ];
+        for (hash, validation_salt, key_salt, extra, encrypted_key, owner) in candidates {
+            if self.hash_aes256(password, validation_salt, extra) == hash {
+                let intermediate = self.hash_aes256(password, key_salt, extra);
+                let key = cbc_decrypt(&intermediate, &[0; 16], &encrypted_key[..32])
+                    .ok_or_else(|| EncryptionError::Malformed("intermediate key".to_string()))?;
+                return Ok((key, owner));
+            }
+        }
+        Err(EncryptionError::InvalidPassword)
+    }
+
+    /// Revision 5 hashes the password, salt and user data with SHA-256. Revision
+    /// 6 repeats rounds of AES-128 encryption and SHA-2 hashing, where the hash
+    /// function of each round depends on the previous output.
+    /// See Algorithm 2.B (pg. 83)
+    fn hash_aes256(&self, password: &[u8], salt: &[u8], user: &[u8]) -> Vec<u8> {
+        let mut k = Sha256::digest([password, salt, user].concat()).to_vec();
+        if self.revision == 5 {
+            return k;
+        }
+        let mut round = 0;
+        loop {
+            let block = [password, &k, user].concat();
+            let k1 = block.repeat(64);
+            let iv: [u8; 16] = k[16..32].try_into().unwrap();
+            let e = cbc_encrypt(&k[..16], &iv, &k1).expect("16-byte key");
+            let sum: u32 = e[..16].iter().map(|&b| b as u32).sum();
+            k = match sum % 3 {
+                0 => Sha256::digest(&e).to_vec(),
+                1 => Sha384::digest(&e).to_vec(),
+                _ => Sha512::digest(&e).to_vec(),
+            };
+            round += 1;
+            if round >= 64 && *e.last().unwrap() as u32 <= round - 32 {
+                break;
+            }
+        }
+        k.truncate(32);
+        k
+    }
+
+    /// /Perms holds the permissions encrypted with the file key, to detect
+    /// tampering with /P. See Algorithm 13 (pg. 88)
+    fn check_perms(&self, perms: &[u8]) -> Result<(), EncryptionError> {
+        let (Ok(aes), Ok(mut block)) = (
+            Aes256::new_from_slice(&self.key),
+            <[u8; 16]>::try_from(&perms[..perms.len().min(16)]),
+        ) else {
+            return Err(EncryptionError::Malformed("/Perms".to_string()));
+        };
+        aes.decrypt_block(GenericArray::from_mut_slice(&mut block));
+        if &block[9..12] != b"adb" || block[..4] != self.permissions.to_le_bytes() {
+            return Err(EncryptionError::Malformed(
+                "/Perms does not match /P".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The key for an object's strings and streams. Revisions 2 to 4 hash the
+    /// file key with the low bytes of the object and generation number (and
+    /// "sAlT" for AES). See Algorithm 1 (pg. 80)
+    fn object_key(&self, id: ObjectId, method: CryptMethod) -> Vec<u8> {
+        if method == CryptMethod::AesV3 {
+            return self.key.clone();
+        }
+        let mut digest = Md5::new()
+            .chain_update(&self.key)
+            .chain_update(&id.0.to_le_bytes()[..3])
+            .chain_update(&id.1.to_le_bytes()[..2]);
+        if method == CryptMethod::AesV2 {
+            digest.update(b"sAlT");
+        }
+        let hash = digest.finalize();
+        hash[..(self.key.len() + 5).min(16)].to_vec()
+    }
+
+    /// Decrypts the bytes of a string or stream of the given object. AES data
+    /// starts with the 16-byte initialization vector and is padded as in
+    /// PKCS#5. See sec. 7.6.3 (pg. 79)
+    pub fn decrypt_bytes(&self, id: ObjectId, data: &[u8], method: CryptMethod) -> Vec<u8> {
+        match method {
+            CryptMethod::None => data.to_vec(),
+            CryptMethod::Rc4 => rc4(&self.object_key(id, method), data),
+            CryptMethod::AesV2 | CryptMethod::AesV3 => {
+                let Some((iv, rest)) = data.split_first_chunk::<16>() else {
+                    return vec![];
+                };
+                let Some(mut plain) = cbc_decrypt(&self.object_key(id, method), iv, rest) else {
+                    return data.to_vec();
+                };
+                let padding = plain.last().copied().unwrap_or(0) as usize;
+                if (1..=16).contains(&padding) && padding <= plain.len() {
+                    plain.truncate(plain.len() - padding);
+                }
+                plain
+            }
+        }
+    }
+
+    /// Encrypts the bytes of a string or stream of the given object. AES data
+    /// gets a random initialization vector.
+    pub fn encrypt_bytes(
+        &self,
+        id: ObjectId,
+        data: &[u8],
+        method: CryptMethod,
+    ) -> Result<Vec<u8>, io::Error> {
+        Ok(match method {
+            CryptMethod::None => data.to_vec(),
+            CryptMethod::Rc4 => rc4(&self.object_key(id, method), data),
+            CryptMethod::AesV2 | CryptMethod::AesV3 => {
+                let iv: [u8; 16] = random_bytes(16)?.try_into().unwrap();
+                let padding = 16 - data.len() % 16;
+                let mut padded = data.to_vec();
+                padded.resize(data.len() + padding, padding as u8);
+                let Some(encrypted) = cbc_encrypt(&self.object_key(id, method), &iv, &padded)
+                else {
+                    return Ok(data.to_vec());
+                };
+                [&iv[..], &encrypted].concat()
+            }
+        })
+    }
+
+    /// Decrypts every string in the object, and the data of a stream. The
+    /// strings of a cross-reference stream's dictionary are not encrypted, and
+    /// neither are metadata streams when /EncryptMetadata is false. A stream
+    /// that names a crypt filter in /Filter is decrypted with that filter,
+    /// which is then removed. See sec. 7.6.2 (pg. 78)
+    pub fn decrypt_object(&self, id: ObjectId, object: &mut PdfObject) {
+        // Only encryption needs random bytes, so decryption cannot fail.
+        self.crypt_object(id, object, false)
+            .expect("decryption does not fail");
+    }
+
+    /// Encrypts every string in the object, and the data of a stream, leaving
+    /// out the same objects as `decrypt_object`.
+    pub fn encrypt_object(&self, id: ObjectId, object: &mut PdfObject) -> Result<(), io::Error> {
+        self.crypt_object(id, object, true)
+    }
+
+    fn crypt_object(
+        &self,
+        id: ObjectId,
+        object: &mut PdfObject,
+        encrypt: bool,
+    ) -> Result<(), io::Error> {
+        let crypt = |data: &[u8], method| match encrypt {
+            true => self.encrypt_bytes(id, data, method),
+            false => Ok(self.decrypt_bytes(id, data, method)),
+        };
+        match object {
+            PdfObject::String(bytes) => *bytes = crypt(bytes, self.string_method)?,
+            PdfObject::Array(array) => {
+                for item in array {
+                    self.crypt_object(id, item, encrypt)?;
+                }
+            }
+            PdfObject::Dictionary(dict) => {
+                for (_, value) in dict.iter_mut() {
+                    self.crypt_object(id, value, encrypt)?;
+                }
+            }
+            PdfObject::Stream(stream) => {
+                if stream.dict.has_type("XRef") {
+                    return Ok(());
+                }
+                for (_, value) in stream.dict.iter_mut() {
+                    self.crypt_object(id, value, encrypt)?;
+                }
+                let crypt_filter = match encrypt {
+                    true => None,
+                    false => self.take_crypt_filter(stream),
+                };
+                let method = match crypt_filter {
+                    Some(method) => method,
+                    None if stream.dict.has_type("Metadata") && !self.encrypt_metadata => {
+                        CryptMethod::None
+                    }
+                    None => self.stream_method,
+                };
+                stream.data = crypt(&stream.data, method)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// A crypt filter, if any, comes first in /Filter; its /Name decode
+    /// parameter selects one of the filters in /CF, or Identity by default.
+    /// See sec. 7.4.10 (pg. 50)
+    fn take_crypt_filter(&self, stream: &mut Stream) -> Option<CryptMethod> {
+        if stream.filters().first() != Some(&"Crypt") {
+            return None;
+        }
+        let params = crate::api::filters::decode_params(&stream.dict, stream.filters().len());
+        let name = params
+            .first()
+            .and_then(|p| p.as_ref())
+            .and_then(|p| p.get_name("Name"))
+            .unwrap_or("Identity")
+            .to_string();
+        for key in ["Filter", "DecodeParms"] {
+            match stream.dict.get_mut(key) {
+                Some(PdfObject::Array(array)) if array.len() > 1 => {
+                    array.remove(0);
+                }
+                _ => {
+                    stream.dict.remove(key);
+                }
+            }
+        }
+        Some(match name.as_str() {
+            "Identity" => CryptMethod::None,
+            name => self
+                .crypt_filters
+                .get(name)
+                .copied()
+                .unwrap_or(self.stream_method),
+        })
+    }
+}
+
+/// Revisions 2 and 3 used RC4 for everything. From version 4 on, /CF defines
+/// named crypt filters, with /StmF and /StrF naming the ones used for streams
+/// and strings. See sec. 7.6.5 (pg. 90)
+#[allow(clippy::type_complexity)]
+fn crypt_methods(
+    encrypt: &Dictionary,
+    version: i64,
+) -> Result<(CryptMethod, CryptMethod, BTreeMap<String, CryptMethod>), EncryptionError> {
+    if version < 4 {
+        return Ok((CryptMethod::Rc4, CryptMethod::Rc4, BTreeMap::new()));
+    }
+    let mut filters = BTreeMap::new();
+    if let Some(PdfObject::Dictionary(cf)) = encrypt.get("CF") {
+        for (name, filter) in cf.iter() {
+            let method = match filter.as_dict().and_then(|f| f.get_name("CFM")) {
+                None | Some("None") => CryptMethod::None,
+                Some("V2") => CryptMethod::Rc4,
+                Some("AESV2") => CryptMethod::AesV2,
+                Some("AESV3") => CryptMethod::AesV3,
+                Some(other) => {
+                    return Err(EncryptionError::Unsupported(format!(
+                        "crypt filter method {}",
+                        other
+                    )))
+                }
+            };
+            filters.insert(name.clone(), method);
+        }
+    }
+    let method = |key: &str| match encrypt.get_name(key).unwrap_or("Identity") {
+        "Identity" => Ok(CryptMethod::None),
+        name => filters
+            .get(name)
+            .copied()
+            .ok_or_else(|| EncryptionError::Malformed(format!("undefined crypt filter {}", name))),
+    };
+    Ok((method("StrF")?, method("StmF")?, filters))
+}
+
+/// Passwords of revisions 2 to 4 are in PDFDocEncoding, which agrees with
+/// Latin-1 for the characters a password is likely to contain.
+fn latin1_password(password: &str) -> Vec<u8> {
+    password
+        .chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .collect()
+}
+
+fn pad_password(password: &[u8]) -> Vec<u8> {
+    password
+        .iter()
+        .chain(PASSWORD_PADDING.iter())
+        .take(32)
+        .copied()
+        .collect()
+}
+
+/// Encrypts or decrypts data with RC4. The keys of revisions 2 to 4 are 5 to
+/// 16 bytes long, and so are the object keys derived from them.
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    macro_rules! apply_keystream {
+        ($($length:literal => $size:ty),*) => {
+            match key.len() {
+                $($length => Rc4::<$size>::new(GenericArray::from_slice(key))
+                    .apply_keystream(&mut data),)*
+                length => panic!("RC4 key of {} bytes", length),
+            }
+        };
+    }
+    apply_keystream!(
+        5 => consts::U5, 6 => consts::U6, 7 => consts::U7, 8 => consts::U8,
+        9 => consts::U9, 10 => consts::U10, 11 => consts::U11, 12 => consts::U12,
+        13 => consts::U13, 14 => consts::U14, 15 => consts::U15, 16 => consts::U16
+    );
+    data
+}
+
+/// Encrypts data with AES-128 or AES-256, depending on the length of the key,
+/// in cipher block chaining mode. The data must be a whole number of blocks;
+/// padding is up to the caller. Returns `None` for keys of other lengths.
+fn cbc_encrypt(key: &[u8], iv: &[u8; 16], data: &[u8]) -> Option<Vec<u8>> {
+    let data = &data[..data.len() - data.len() % 16];
+    match key.len() {
+        16 => cbc::Encryptor::<Aes128>::new_from_slices(key, iv)
+            .ok()
+            .map(|cipher| cipher.encrypt_padded_vec_mut::<NoPadding>(data)),
+        32 => cbc::Encryptor::<Aes256>::new_from_slices(key, iv)
+            .ok()
+            .map(|cipher| cipher.encrypt_padded_vec_mut::<NoPadding>(data)),
+        _ => None,
+    }
+}
+
+/// Decrypts data with AES-128, AES-192 or AES-256 in cipher block chaining
+/// mode, leaving the padding in place. A trailing partial block is ignored.
+fn cbc_decrypt(key: &[u8], iv: &[u8; 16], data: &[u8]) -> Option<Vec<u8>> {
+    let data = &data[..data.len() - data.len() % 16];
+    match key.len() {
+        16 => cbc::Decryptor::<Aes128>::new_from_slices(key, iv)
+            .ok()?
+            .decrypt_padded_vec_mut::<NoPadding>(data)
+            .ok(),
+        24 => cbc::Decryptor::<Aes192>::new_from_slices(key, iv)
+            .ok()?
+            .decrypt_padded_vec_mut::<NoPadding>(data)
+            .ok(),
+        32 => cbc::Decryptor::<Aes256>::new_from_slices(key, iv)
+            .ok()?
+            .decrypt_padded_vec_mut::<NoPadding>(data)
+            .ok(),
+        _ => None,
+    }
+}
+
+/// Random bytes for keys, salts and initialization vectors, from the
+/// operating system's cryptographically secure random number generator.
+pub fn random_bytes(length: usize) -> Result<Vec<u8>, io::Error> {
+    let mut bytes = vec![0; length];
+    getrandom::fill(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Reads an RSA private key in PKCS#8 or PKCS#1 form, PEM encoded if the data
+/// starts with "-----BEGIN" and DER encoded otherwise.
+pub fn read_private_key(data: &[u8]) -> Option<RsaPrivateKey> {
+    let pem = std::str::from_utf8(data)
+        .ok()
+        .map(str::trim_start)
+        .filter(|text| text.starts_with("-----BEGIN"));
+    match pem {
+        Some(pem) => RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .ok(),
+        None => RsaPrivateKey::from_pkcs8_der(data)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_der(data))
+            .ok(),
+    }
+}
+
+/// Revision 3 and 4 only define the first 16 bytes of /U.
+fn user_hash_prefix(user_hash: &[u8], revision: i64) -> &[u8] {
+    let length = if revision == 2 { 32 } else { 16 };
+    &user_hash[..user_hash.len().min(length)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::read::{read_bytes, scan_objects};
+
+    fn handler(path: &str, password: &str) -> Result<SecurityHandler, EncryptionError> {
+        let (_, trailer, body) = scan_objects(&read_bytes(path).unwrap());
+        let encrypt = match trailer.get("Encrypt") {
+            Some(PdfObject::Reference(id)) => body.objects[id].as_dict().unwrap().clone(),
+            _ => panic!("not encrypted"),
+        };
+        let id = trailer.get("ID").unwrap().as_array().unwrap()[0]
+            .as_bytes()
+            .unwrap()
+            .to_vec();
+        SecurityHandler::new(&encrypt, &id, password)
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let rc4 = handler("samples/rc4-128.pdf", "").unwrap();
+        assert_eq!((rc4.revision, rc4.owner), (3, false));
+        assert!(handler("samples/rc4-128.pdf", "owner").unwrap().owner);
+        assert!(handler("samples/rc4-40.pdf", "owner").unwrap().owner);
+
+        let aes = handler("samples/aes-256.pdf", "user").unwrap();
+        assert_eq!((aes.revision, aes.owner), (6, false));
+        assert!(handler("samples/aes-256.pdf", "owner").unwrap().owner);
+        assert_eq!(
+            handler("samples/aes-256.pdf", "").unwrap_err(),
+            EncryptionError::InvalidPassword
+        );
+        assert_eq!(
+            handler("samples/aes-128.pdf", "wrong").unwrap_err(),
+            EncryptionError::InvalidPassword
+        );
+    }
+
+    #[test]
+    fn test_permissions() {
+        assert_eq!(Permissions::all().bits(), -4);
+        let print_only = Permissions {
+            print: true,
+            ..Permissions::default()
+        };
+        assert_eq!(print_only.bits(), -1340);
+        assert_eq!(Permissions::from_bits(print_only.bits()), print_only);
+        assert!(Permissions::from_bits(-1028).copy);
+    }
+
+    #[test]
+    fn test_ciphers() {
+        // RFC 6229, 40-bit key, keystream at offset 0.
+        assert_eq!(
+            rc4(&[1, 2, 3, 4, 5], &[0; 16]),
+            [
+                0xB2, 0x39, 0x63, 0x05, 0xF0, 0x3D, 0xC0, 0x27, 0xCC, 0xC3, 0x52, 0x4A, 0x0A, 0x11,
+                0x18, 0xA8
+            ]
+        );
+
+        // NIST SP 800-38A, F.2.1 and F.2.5, first block.
+        let iv: [u8; 16] = std::array::from_fn(|i| i as u8);
+        let plain = [
+            0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93,
+            0x17, 0x2A,
+        ];
+        let aes128 = [
+            0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF,
+            0x4F, 0x3C,
+        ];
+        let aes256 = [
+            0x60, 0x3D, 0xEB, 0x10, 0x15, 0xCA, 0x71, 0xBE, 0x2B, 0x73, 0xAE, 0xF0, 0x85, 0x7D,
+            0x77, 0x81, 0x1F, 0x35, 0x2C, 0x07, 0x3B, 0x61, 0x08, 0xD7, 0x2D, 0x98, 0x10, 0xA3,
+            0x09, 0x14, 0xDF, 0xF4,
+        ];
+        for (key, cipher) in [
+            (
+                &aes128[..],
+                [
+                    0x76, 0x49, 0xAB, 0xAC, 0x81, 0x19, 0xB2, 0x46, 0xCE, 0xE9, 0x8E, 0x9B, 0x12,
+                    0xE9, 0x19, 0x7D,
+                ],
+            ),
+            (
+                &aes256[..],
+                [
+                    0xF5, 0x8C, 0x4C, 0x04, 0xD6, 0xE5, 0xF1, 0xBA, 0x77, 0x9E, 0xAB, 0xFB, 0x5F,
+                    0x7B, 0xFB, 0xD6,
+                ],
+            ),
+        ] {
+            assert_eq!(cbc_encrypt(key, &iv, &plain).unwrap(), cipher);
+            assert_eq!(cbc_decrypt(key, &iv, &cipher).unwrap(), plain);
+        }
+        assert_eq!(cbc_encrypt(&aes128[..10], &iv, &plain), None);
+    }
+
+    #[test]
+    fn test_create() {
+        let file_id = random_bytes(16).unwrap();
+        for algorithm in [EncryptionAlgorithm::Aes128, EncryptionAlgorithm::Aes256] {
+            let encryption = Encryption {
+                permissions: Permissions {
+                    copy: true,
+                    ..Permissions::default()
+                },
+                algorithm,
+                ..Encryption::new("", "secret")
+            };
+            let (writer, dict) = SecurityHandler::create(&encryption, &file_id).unwrap();
+            let reader = SecurityHandler::new(&dict, &file_id, "").unwrap();
+            assert!(!reader.owner);
+            assert!(
+                SecurityHandler::new(&dict, &file_id, "secret")
+                    .unwrap()
+                    .owner
+            );
+            assert_eq!(
+                Permissions::from_bits(reader.permissions),
+                encryption.permissions
+            );
+
+            let mut object = PdfObject::Array(vec![PdfObject::String(b"text".to_vec())]);
+            writer.encrypt_object((3, 0), &mut object).unwrap();
+            assert_ne!(object.as_array().unwrap()[0].as_bytes().unwrap(), b"text");
+            reader.decrypt_object((3, 0), &mut object);
+            assert_eq!(object.as_array().unwrap()[0].as_bytes().unwrap(), b"text");
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use cms::{
+    content_info::ContentInfo,
+    enveloped_data::{EncryptedContentInfo, EnvelopedData, RecipientInfo},
+};
+use const_oid::{
+    db::{
+        rfc5911::{ID_AES_128_CBC, ID_AES_192_CBC, ID_AES_256_CBC, ID_ENVELOPED_DATA},
+        rfc5912::RSA_ENCRYPTION,
+    },
+    ObjectIdentifier,
+};
+use der::{asn1::OctetString, Decode, SliceReader};
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey};
+
+use crate::api::encryption::{cbc_decrypt, EncryptionError};
+
+/// aes128-CBC, aes192-CBC and aes256-CBC, with their key lengths.
+const AES_CBC: [(ObjectIdentifier, usize); 3] = [
+    (ID_AES_128_CBC, 16),
+    (ID_AES_192_CBC, 24),
+    (ID_AES_256_CBC, 32),
+];
+
+/// Decrypts the content of PKCS#7 enveloped data for the recipient holding the
+/// private key. The content is encrypted with a random key, which is in turn
+/// encrypted with the public key of each recipient. Returns None if none of
+/// the recipients' keys decrypts with this private key. Bytes after the
+/// enveloped data are ignored. See RFC 5652, sec. 6
+pub fn decrypt_enveloped_data(
+    data: &[u8],
+    key: &RsaPrivateKey,
+) -> Result<Option<Vec<u8>>, EncryptionError> {
+    let malformed = || EncryptionError::Malformed("PKCS#7 enveloped data".to_string());
+    let content_info = SliceReader::new(data)
+        .and_then(|mut reader| ContentInfo::decode(&mut reader))
+        .map_err(|_| malformed())?;
+    if content_info.content_type != ID_ENVELOPED_DATA {
+        return Err(malformed());
+    }
+    let enveloped: EnvelopedData = content_info.content.decode_as().map_err(|_| malformed())?;
+
+    for recipient in enveloped.recip_infos.0.iter() {
+        // Only key transport recipients can hold an RSA key.
+        let RecipientInfo::Ktri(recipient) = recipient else {
+            continue;
+        };
+        if recipient.key_enc_alg.oid != RSA_ENCRYPTION {
+            continue;
+        }
+        let Ok(content_key) = key.decrypt(Pkcs1v15Encrypt, recipient.enc_key.as_bytes()) else {
+            continue;
+        };
+        if let Some(content) = decrypt_content(&enveloped.encrypted_content, &content_key)? {
+            return Ok(Some(content));
+        }
+    }
+    Ok(None)
+}
+
+/// Decrypts the EncryptedContentInfo, whose algorithm parameters are the
+/// initialization vector and whose content is padded as in PKCS#5. Returns
+/// None if the key does not fit or the padding is wrong.
+fn decrypt_content(
+    encrypted_content: &EncryptedContentInfo,
+    key: &[u8],
+) -> Result<Option<Vec<u8>>, EncryptionError> {
+    let malformed = || EncryptionError::Malformed("PKCS#7 encrypted content".to_string());
+    let Some(content) = &encrypted_content.encrypted_content else {
+        return Ok(Some(vec![]));
+    };
+    let algorithm = &encrypted_content.content_enc_alg;
+    let Some(&(_, key_length)) = AES_CBC.iter().find(|(oid, _)| *oid == algorithm.oid) else {
+        return Err(EncryptionError::Unsupported(
+            "PKCS#7 content encryption other than AES".to_string(),
+        ));
+    };
+    let iv: [u8; 16] = algorithm
+        .parameters
+        .as_ref()
+        .and_then(|parameters| parameters.decode_as::<OctetString>().ok())
+        .and_then(|iv| iv.as_bytes().try_into().ok())
+        .ok_or_else(malformed)?;
+    if key.len() != key_length {
+        return Ok(None);
+    }
+
+    let mut plain = cbc_decrypt(key, &iv, content.as_bytes()).ok_or_else(malformed)?;
+    let padding = plain.last().copied().unwrap_or(0) as usize;
+    if !(1..=16).contains(&padding)
+        || padding > plain.len()
+        || plain[plain.len() - padding..]
+            .iter()
+            .any(|&b| b as usize != padding)
+    {
+        return Ok(None);
+    }
+    plain.truncate(plain.len() - padding);
+    Ok(Some(plain))
+}
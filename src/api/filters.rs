@@ -0,0 +1,461 @@
+/// CCITT Group 3 and Group 4 facsimile decoding. See sec. 7.4.6
+pub mod ccitt;
+
+/// JBIG2 decoding of bilevel images. See sec. 7.4.7
+pub mod jbig2;
+
+use std::{
+    fmt, io,
+    io::{Read, Write},
+};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::api::objects::{Dictionary, PdfObject, Stream};
+
+///
+/// Errors raised while decoding stream data.
+///
+#[derive(Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// The stream names a filter that is not implemented.
+    Unsupported(String),
+    /// The stream data could not be decoded with the named filter.
+    Malformed(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::Unsupported(name) => write!(f, "unsupported filter: {}", name),
+            FilterError::Malformed(msg) => write!(f, "malformed stream data: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl From<FilterError> for io::Error {
+    fn from(err: FilterError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Decodes the stream data by applying each filter in the /Filter entry in turn,
+/// using the matching entry of /DecodeParms (if present).
+pub fn decode_stream(stream: &Stream) -> Result<Vec<u8>, FilterError> {
+    decode_stream_until(stream, |_| false).map(|(data, _)| data)
+}
+
+/// Filters whose output is a complete image file format (JPEG or JPEG 2000).
+pub const IMAGE_FORMAT_FILTERS: [&str; 3] = ["DCTDecode", "DCT", "JPXDecode"];
+
+/// Applies the filters of a stream in turn, stopping before the first filter for
+/// which `stop` returns true. Returns the data decoded so far along with the
+/// name of that filter, if any. Image extraction uses this to keep JPEG data
+/// as it is while still removing e.g. an ASCII85 encoding wrapped around it.
+pub fn decode_stream_until(
+    stream: &Stream,
+    stop: impl Fn(&str) -> bool,
+) -> Result<(Vec<u8>, Option<&str>), FilterError> {
+    let params = decode_params(&stream.dict, stream.filters().len());
+    decode_stream_with(stream, &params, stop)
+}
+
+/// Like `decode_stream_until`, with the decode parameters of each filter given
+/// by the caller rather than taken from the stream dictionary as they are.
+pub fn decode_stream_with<'a>(
+    stream: &'a Stream,
+    params: &[Option<Dictionary>],
+    stop: impl Fn(&str) -> bool,
+) -> Result<(Vec<u8>, Option<&'a str>), FilterError> {
+    let filters = stream.filters();
+    let params = params.iter().chain(std::iter::repeat(&None));
+
+    let mut data = stream.data.clone();
+    for (filter, params) in filters.iter().zip(params) {
+        if stop(filter) {
+            return Ok((data, Some(filter)));
+        }
+        data = decode(filter, &data, params.as_ref())?;
+    }
+    Ok((data, None))
+}
+
+/// /DecodeParms is either a single dictionary or an array with one entry per filter.
+pub fn decode_params(dict: &Dictionary, num_filters: usize) -> Vec<Option<Dictionary>> {
+    let params = dict.get("DecodeParms").or_else(|| dict.get("DP"));
+    match params {
+        Some(PdfObject::Dictionary(d)) => {
+            let mut params = vec![None; num_filters];
+            if let Some(first) = params.first_mut() {
+                *first = Some(d.clone());
+            }
+            params
+        }
+        Some(PdfObject::Array(array)) => (0..num_filters)
+            .map(|i| array.get(i).and_then(|p| p.as_dict()).cloned())
+            .collect(),
+        _ => vec![None; num_filters],
+    }
+}
+
+/// Applies a single named filter. Abbreviated names used by inline images are
+/// accepted as well. See sec. 7.4, Table 6 (pg. 35).
+pub fn decode(
+    filter: &str,
+    data: &[u8],
+    params: Option<&Dictionary>,
+) -> Result<Vec<u8>, FilterError> {
+    match filter {
+        "FlateDecode" | "Fl" => {
+            let data = flate_decode(data)?;
+            apply_predictor(data, params)
+        }
+        "LZWDecode" | "LZW" => {
+            let early_change = params.and_then(|p| p.get_i64("EarlyChange")).unwrap_or(1);
+            let data = lzw_decode(data, early_change != 0)?;
+            apply_predictor(data, params)
+        }
+        "ASCIIHexDecode" | "AHx" => ascii_hex_decode(data),
+        "ASCII85Decode" | "A85" => ascii85_decode(data),
+        "RunLengthDecode" | "RL" => run_length_decode(data),
+        "CCITTFaxDecode" | "CCF" => ccitt::ccitt_fax_decode(data, params),
+        // /JBIG2Globals is an indirect reference to a stream, which the caller
+        // must have resolved (see `Document::decode_stream`).
+        "JBIG2Decode" => {
+            let globals = match params.and_then(|p| p.get("JBIG2Globals")) {
+                Some(PdfObject::Stream(globals)) => Some(globals.decode()?),
+                _ => None,
+            };
+            jbig2::jbig2_decode(data, globals.as_deref())
+        }
+        other => Err(FilterError::Unsupported(other.to_string())),
+    }
+}
+
+/// Flate (zlib/deflate) decompression. See sec. 7.4.4 (pg. 38)
+///
+/// Some producers truncate the final checksum, so whatever could be inflated
+/// before an error is returned as long as something was decoded.
+pub fn flate_decode(data: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let mut decoder = ZlibDecoder::new(data);
+    let mut output = vec![];
+    match decoder.read_to_end(&mut output) {
+        Ok(_) => Ok(output),
+        Err(_) if !output.is_empty() => Ok(output),
+        Err(e) => Err(FilterError::Malformed(format!("FlateDecode: {}", e))),
+    }
+}
+
+/// Flate (zlib/deflate) compression, the inverse of `flate_decode`.
+pub fn flate_encode(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    // Writing to a vector cannot fail.
+    let _ = encoder.write_all(data);
+    encoder.finish().unwrap_or_default()
+}
+
+/// Undoes the TIFF or PNG predictor named in the decode parameters.
+/// See sec. 7.4.4.4, Table 10 (pg. 41)
+pub fn apply_predictor(data: Vec<u8>, params: Option<&Dictionary>) -> Result<Vec<u8>, FilterError> {
+    let Some(params) = params else {
+        return Ok(data);
+    };
+    let predictor = params.get_i64("Predictor").unwrap_or(1);
+    if predictor == 1 {
+        return Ok(data);
+    }
+    let colors = params.get_i64("Colors").unwrap_or(1).max(1) as usize;
+    let bits = params.get_i64("BitsPerComponent").unwrap_or(8).max(1) as usize;
+    let columns = params.get_i64("Columns").unwrap_or(1).max(1) as usize;
+    let bytes_per_pixel = (colors * bits).div_ceil(8);
+    let row_length = (colors * bits * columns).div_ceil(8);
+
+    match predictor {
+        2 => Ok(tiff_predictor(data, row_length, colors, bits)),
+        10..=15 => png_predictor(&data, row_length, bytes_per_pixel),
+        other => Err(FilterError::Malformed(format!(
+            "unknown predictor {}",
+            other
+        ))),
+    }
+}
+
+/// Applies the PNG "Up" predictor to rows of the given length, the inverse of
+/// `apply_predictor` with /Predictor 12 and /Columns set to the row length.
+/// Each row is written as its difference from the row above, prefixed with the
+/// filter type byte 2. See sec. 7.4.4.4 (pg. 41)
+pub fn png_up_encode(data: &[u8], row_length: usize) -> Vec<u8> {
+    let row_length = row_length.max(1);
+    let mut output = Vec::with_capacity(data.len() + data.len() / row_length + 1);
+    let mut previous = vec![0u8; row_length];
+    for row in data.chunks(row_length) {
+        output.push(2);
+        output.extend(row.iter().zip(&previous).map(|(&b, &a)| b.wrapping_sub(a)));
+        previous[..row.len()].copy_from_slice(row);
+    }
+    output
+}
+
+fn tiff_predictor(mut data: Vec<u8>, row_length: usize, colors: usize, bits: usize) -> Vec<u8> {
+    if bits != 8 {
+        // Only byte-aligned components are supported for TIFF prediction.
+        return data;
+    }
+    for row in data.chunks_mut(row_length) {
+        for i in colors..row.len() {
+            row[i] = row[i].wrapping_add(row[i - colors]);
+        }
+    }
+    data
+}
+
+fn png_predictor(data: &[u8], row_length: usize, bpp: usize) -> Result<Vec<u8>, FilterError> {
+    let mut output = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_length];
+
+    for chunk in data.chunks(row_length + 1) {
+        let (filter_type, row) = chunk.split_first().unwrap_or((&0, &[]));
+        let mut current = row.to_vec();
+        current.resize(row_length, 0);
+        for i in 0..row_length {
+            let left = if i >= bpp { current[i - bpp] } else { 0 };
+            let up = previous[i];
+            let up_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            current[i] = match filter_type {
+                0 => current[i],
+                1 => current[i].wrapping_add(left),
+                2 => current[i].wrapping_add(up),
+                3 => current[i].wrapping_add(((left as u16 + up as u16) / 2) as u8),
+                4 => current[i].wrapping_add(paeth(left, up, up_left)),
+                other => return Err(FilterError::Malformed(format!("PNG filter type {}", other))),
+            };
+        }
+        output.extend_from_slice(&current[..row.len().min(row_length)]);
+        previous = current;
+    }
+    Ok(output)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Each pair of hexadecimal digits is one byte; white-space is ignored and ">"
+/// marks the end of the data. A missing final digit is treated as 0.
+/// See sec. 7.4.2 (pg. 37)
+pub fn ascii_hex_decode(data: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let mut output = vec![];
+    let mut high: Option<u8> = None;
+    for &byte in data {
+        if byte == b'>' {
+            break;
+        }
+        if byte.is_ascii_whitespace() || byte == 0 {
+            continue;
+        }
+        let digit = (byte as char)
+            .to_digit(16)
+            .ok_or_else(|| FilterError::Malformed(format!("ASCIIHexDecode: {:?}", byte as char)))?
+            as u8;
+        match high.take() {
+            Some(h) => output.push(h << 4 | digit),
+            None => high = Some(digit),
+        }
+    }
+    if let Some(h) = high {
+        output.push(h << 4);
+    }
+    Ok(output)
+}
+
+/// Groups of five characters from "!" to "u" encode four bytes in base-85, "z"
+/// stands for four zero bytes, and "~>" marks the end of the data.
+/// See sec. 7.4.3 (pg. 37)
+pub fn ascii85_decode(data: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let mut output = vec![];
+    let mut group: Vec<u32> = Vec::with_capacity(5);
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+
+    for &byte in data {
+        match byte {
+            b'~' => break,
+            b'z' if group.is_empty() => output.extend_from_slice(&[0, 0, 0, 0]),
+            b'!'..=b'u' => {
+                group.push((byte - b'!') as u32);
+                if group.len() == 5 {
+                    let value = group
+                        .iter()
+                        .fold(0u32, |acc, &d| acc.wrapping_mul(85).wrapping_add(d));
+                    output.extend_from_slice(&value.to_be_bytes());
+                    group.clear();
+                }
+            }
+            b if b.is_ascii_whitespace() || b == 0 => continue,
+            b => {
+                return Err(FilterError::Malformed(format!(
+                    "ASCII85Decode: {:?}",
+                    b as char
+                )))
+            }
+        }
+    }
+    if group.len() > 1 {
+        let n = group.len();
+        group.resize(5, 84);
+        let value = group
+            .iter()
+            .fold(0u32, |acc, &d| acc.wrapping_mul(85).wrapping_add(d));
+        output.extend_from_slice(&value.to_be_bytes()[..n - 1]);
+    }
+    Ok(output)
+}
+
+/// LZW decompression with variable code lengths from 9 to 12 bits, a clear-table
+/// code of 256 and an end-of-data code of 257. See sec. 7.4.4.2 (pg. 39)
+pub fn lzw_decode(data: &[u8], early_change: bool) -> Result<Vec<u8>, FilterError> {
+    let mut output = vec![];
+    let mut table: Vec<Vec<u8>> = (0..=255u8).map(|b| vec![b]).collect();
+    table.push(vec![]);
+    table.push(vec![]);
+    let mut code_length = 9;
+    let mut previous: Option<Vec<u8>> = None;
+
+    let mut bit_buffer: u32 = 0;
+    let mut bit_count = 0;
+    for &byte in data {
+        bit_buffer = (bit_buffer << 8) | byte as u32;
+        bit_count += 8;
+        while bit_count >= code_length {
+            let code =
+                ((bit_buffer >> (bit_count - code_length)) & ((1 << code_length) - 1)) as usize;
+            bit_count -= code_length;
+
+            match code {
+                256 => {
+                    table.truncate(258);
+                    code_length = 9;
+                    previous = None;
+                    continue;
+                }
+                257 => return Ok(output),
+                _ => {}
+            }
+
+            let entry = if code < table.len() {
+                table[code].clone()
+            } else if let (Some(prev), true) = (&previous, code == table.len()) {
+                let mut entry = prev.clone();
+                entry.push(prev[0]);
+                entry
+            } else {
+                return Err(FilterError::Malformed(format!("LZWDecode: code {}", code)));
+            };
+            output.extend_from_slice(&entry);
+
+            if let Some(prev) = previous {
+                let mut new_entry = prev;
+                new_entry.push(entry[0]);
+                table.push(new_entry);
+            }
+            previous = Some(entry);
+
+            let threshold = table.len() + usize::from(early_change);
+            code_length = match threshold {
+                t if t >= 2048 => 12,
+                t if t >= 1024 => 11,
+                t if t >= 512 => 10,
+                _ => 9,
+            };
+        }
+    }
+    Ok(output)
+}
+
+/// A length byte from 0 to 127 is followed by that many plus one literal bytes;
+/// a length byte from 129 to 255 is followed by a single byte to repeat 257 minus
+/// the length times; 128 marks the end of the data. See sec. 7.4.5 (pg. 42)
+pub fn run_length_decode(data: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let mut output = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let length = data[i] as usize;
+        i += 1;
+        match length {
+            0..=127 => {
+                let end = (i + length + 1).min(data.len());
+                output.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            128 => break,
+            _ => {
+                let byte = *data
+                    .get(i)
+                    .ok_or_else(|| FilterError::Malformed("RunLengthDecode: truncated".into()))?;
+                output.extend(std::iter::repeat_n(byte, 257 - length));
+                i += 1;
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_hex_decode() {
+        assert_eq!(ascii_hex_decode(b"48 65 6c6C6f>").unwrap(), b"Hello");
+        assert_eq!(ascii_hex_decode(b"7").unwrap(), vec![0x70]);
+    }
+
+    #[test]
+    fn test_ascii85_decode() {
+        assert_eq!(
+            ascii85_decode(b"87cURD]i,\"Ebo7~>").unwrap(),
+            b"Hello World"
+        );
+        assert_eq!(ascii85_decode(b"z~>").unwrap(), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_run_length_decode() {
+        let data = [2, b'a', b'b', b'c', 254, b'z', 128];
+        assert_eq!(run_length_decode(&data).unwrap(), b"abczzz");
+    }
+
+    #[test]
+    fn test_lzw_decode() {
+        // Example from sec. 7.4.4.2: "-----A---B" encoded with early change.
+        let data = [0x80, 0x0B, 0x60, 0x50, 0x22, 0x0C, 0x0C, 0x85, 0x01];
+        assert_eq!(lzw_decode(&data, true).unwrap(), b"-----A---B");
+    }
+
+    #[test]
+    fn test_png_predictor() {
+        let mut params = Dictionary::new();
+        params.set("Predictor", PdfObject::Integer(12));
+        params.set("Columns", PdfObject::Integer(3));
+        // Two rows using the "Up" filter.
+        let data = vec![2, 1, 2, 3, 2, 1, 1, 1];
+        let output = apply_predictor(data, Some(&params)).unwrap();
+        assert_eq!(output, vec![1, 2, 3, 2, 3, 4]);
+
+        let encoded = png_up_encode(&output, 3);
+        assert_eq!(encoded, vec![2, 1, 2, 3, 2, 1, 1, 1]);
+        let compressed = flate_encode(&encoded);
+        assert_eq!(flate_decode(&compressed).unwrap(), encoded);
+    }
+}
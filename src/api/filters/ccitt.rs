@@ -0,0 +1,679 @@
+use std::collections::HashMap;
+
+use crate::api::{filters::FilterError, image::MAX_PIXELS, objects::Dictionary};
+
+///
+/// The parameters of the CCITTFaxDecode filter. See sec. 7.4.6, Table 12 (pg. 44)
+///
+/// K selects the encoding: negative values for pure two-dimensional (Group 4)
+/// encoding, 0 for one-dimensional (Group 3, 1-D) encoding, and positive values
+/// for mixed one- and two-dimensional (Group 3, 2-D) encoding.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CcittParams {
+    pub k: i64,
+    pub end_of_line: bool,
+    pub encoded_byte_align: bool,
+    pub columns: usize,
+    /// The number of rows, or 0 if it is not known.
+    pub rows: usize,
+    pub end_of_block: bool,
+    pub black_is_1: bool,
+}
+
+impl Default for CcittParams {
+    fn default() -> Self {
+        CcittParams {
+            k: 0,
+            end_of_line: false,
+            encoded_byte_align: false,
+            columns: 1728,
+            rows: 0,
+            end_of_block: true,
+            black_is_1: false,
+        }
+    }
+}
+
+impl CcittParams {
+    pub fn from_dict(params: Option<&Dictionary>) -> CcittParams {
+        let default = CcittParams::default();
+        let Some(params) = params else {
+            return default;
+        };
+        let flag =
+            |key: &str, default: bool| params.get(key).and_then(|v| v.as_bool()).unwrap_or(default);
+        CcittParams {
+            k: params.get_i64("K").unwrap_or(default.k),
+            end_of_line: flag("EndOfLine", default.end_of_line),
+            encoded_byte_align: flag("EncodedByteAlign", default.encoded_byte_align),
+            columns: params
+                .get_i64("Columns")
+                .filter(|&c| c > 0)
+                .map_or(default.columns, |c| c as usize),
+            rows: params
+                .get_i64("Rows")
+                .filter(|&r| r > 0)
+                .map_or(default.rows, |r| r as usize),
+            end_of_block: flag("EndOfBlock", default.end_of_block),
+            black_is_1: flag("BlackIs1", default.black_is_1),
+        }
+    }
+}
+
+/// Decodes data encoded with the CCITT facsimile standards (ITU-T T.4 and T.6)
+/// into rows of 1 bit per pixel. Unless /BlackIs1 is true, 0 bits are black, so
+/// that the result can be used directly as a DeviceGray image.
+/// See sec. 7.4.6 (pg. 43)
+pub fn ccitt_fax_decode(data: &[u8], params: Option<&Dictionary>) -> Result<Vec<u8>, FilterError> {
+    let params = CcittParams::from_dict(params);
+    let lines = decode_lines(data, &params)?;
+
+    let row_length = params.columns.div_ceil(8);
+    let (white, black) = if params.black_is_1 { (0, 1) } else { (1, 0) };
+    let mut output = Vec::with_capacity(row_length * lines.len());
+    for changes in &lines {
+        let mut row = vec![if white == 1 { 0xFF } else { 0x00 }; row_length];
+        for run in changes.chunks(2) {
+            let end = run.get(1).copied().unwrap_or(params.columns);
+            for x in run[0]..end {
+                let mask = 0x80 >> (x % 8);
+                if black == 1 {
+                    row[x / 8] |= mask;
+                } else {
+                    row[x / 8] &= !mask;
+                }
+            }
+        }
+        output.extend(row);
+    }
+    Ok(output)
+}
+
+/// Decodes each coded line into its changing elements: the positions of the
+/// pixels whose color differs from the pixel before them, starting from an
+/// imaginary white pixel before the line. Even entries therefore start black
+/// runs and odd entries start white runs.
+///
+/// Decoding stops at the end of the data, at an end-of-block pattern or after
+/// /Rows lines. Whatever could be decoded before an error is kept; when /Rows
+/// is known, missing lines are left white. Images of more than `MAX_PIXELS`
+/// pixels are an error, and without /Rows, decoding stops at that many.
+pub fn decode_lines(data: &[u8], params: &CcittParams) -> Result<Vec<Vec<usize>>, FilterError> {
+    if params
+        .columns
+        .checked_mul(params.rows.max(1))
+        .is_none_or(|pixels| pixels > MAX_PIXELS)
+    {
+        return Err(FilterError::Malformed(format!(
+            "CCITTFaxDecode: image of {} by {} pixels is too large",
+            params.columns, params.rows
+        )));
+    }
+    let max_rows = MAX_PIXELS / params.columns.max(1);
+    let codes = Codes::new();
+    let mut reader = BitReader { data, position: 0 };
+    let columns = params.columns;
+    let mut lines: Vec<Vec<usize>> = vec![];
+    let mut reference: Vec<usize> = vec![];
+
+    while (params.rows == 0 && lines.len() < max_rows) || lines.len() < params.rows {
+        if params.encoded_byte_align && !(params.k >= 0 && params.end_of_line) {
+            reader.align();
+        }
+        let mut end_of_lines = 0;
+        while reader.skip_end_of_line() {
+            end_of_lines += 1;
+            if params.k > 0 {
+                // Each end-of-line is followed by the tag bit of the next line,
+                // which is only skipped here if another end-of-line follows.
+                let mut ahead = reader;
+                ahead.consume(1);
+                let mut next = ahead;
+                if !next.skip_end_of_line() {
+                    break;
+                }
+                reader = ahead;
+            }
+        }
+        // Two end-of-line codes in a row end the data: EOFB in Group 4 and RTC
+        // (six of them) in Group 3. A Group 4 end-of-line can only be EOFB.
+        let end_of_block = end_of_lines >= 2 || (params.k < 0 && end_of_lines > 0);
+        if reader.at_end() || (params.end_of_block && end_of_block) {
+            break;
+        }
+
+        let two_dimensional = match params.k {
+            k if k < 0 => true,
+            0 => false,
+            _ => {
+                let tag = reader.peek(1);
+                reader.consume(1);
+                tag == 0
+            }
+        };
+        let line = if two_dimensional {
+            decode_2d(&mut reader, &codes, &reference, columns)
+        } else {
+            decode_1d(&mut reader, &codes, columns)
+        };
+        match line {
+            Some(line) => {
+                reference = line.clone();
+                lines.push(line);
+            }
+            None if lines.is_empty() => {
+                return Err(FilterError::Malformed(
+                    "CCITTFaxDecode: invalid code".to_string(),
+                ))
+            }
+            None => break,
+        }
+    }
+
+    if params.rows > lines.len() {
+        lines.resize(params.rows, vec![]);
+    }
+    Ok(lines)
+}
+
+/// Records a changing element. A run of length zero adds the same position
+/// twice, in which case neither is a changing element.
+fn push_change(line: &mut Vec<usize>, position: usize, columns: usize) {
+    if position >= columns {
+        return;
+    }
+    if line.last() == Some(&position) {
+        line.pop();
+    } else {
+        line.push(position);
+    }
+}
+
+/// A one-dimensional line is a sequence of alternating white and black runs,
+/// starting with white. See ITU-T T.4, sec. 4.1
+fn decode_1d(reader: &mut BitReader, codes: &Codes, columns: usize) -> Option<Vec<usize>> {
+    let mut line = vec![];
+    let mut position = 0;
+    let mut white = true;
+    while position < columns {
+        let run = codes.read_run(reader, white)?;
+        position = (position + run).min(columns);
+        push_change(&mut line, position, columns);
+        white = !white;
+    }
+    Some(line)
+}
+
+/// A two-dimensional line is coded relative to the line above it (the
+/// reference line). See ITU-T T.4, sec. 4.2 and ITU-T T.6, sec. 2.2
+///     a0: the reference element on the coding line, initially before the line
+///     a1, a2: the next two changing elements on the coding line
+///     b1: the first changing element on the reference line to the right of
+///         a0 and of opposite color to a0
+///     b2: the next changing element on the reference line after b1
+fn decode_2d(
+    reader: &mut BitReader,
+    codes: &Codes,
+    reference: &[usize],
+    columns: usize,
+) -> Option<Vec<usize>> {
+    let mut line = vec![];
+    let mut a0: isize = -1;
+    let mut white = true;
+    while a0 < columns as isize {
+        let (b1, b2) = find_b1_b2(reference, a0, white, columns);
+        match codes.read_mode(reader)? {
+            Mode::Pass => a0 = b2 as isize,
+            Mode::Horizontal => {
+                let start = a0.max(0) as usize;
+                let first = codes.read_run(reader, white)?;
+                let second = codes.read_run(reader, !white)?;
+                let a1 = (start + first).min(columns);
+                let a2 = (a1 + second).min(columns);
+                push_change(&mut line, a1, columns);
+                push_change(&mut line, a2, columns);
+                a0 = a2 as isize;
+            }
+            Mode::Vertical(delta) => {
+                let a1 = (b1 as isize + delta).clamp(a0.max(0), columns as isize);
+                push_change(&mut line, a1 as usize, columns);
+                a0 = a1;
+                white = !white;
+            }
+            Mode::Extension => return None,
+        }
+    }
+    Some(line)
+}
+
+fn find_b1_b2(reference: &[usize], a0: isize, white: bool, columns: usize) -> (usize, usize) {
+    // Changing elements at even indexes start black runs.
+    let found = reference
+        .iter()
+        .enumerate()
+        .find(|&(i, &change)| change as isize > a0 && (i % 2 == 0) == white);
+    match found {
+        Some((i, &b1)) => (b1, reference.get(i + 1).copied().unwrap_or(columns)),
+        None => (columns, columns),
+    }
+}
+
+/// Reads bits from the most significant bit of each byte. Bits past the end of
+/// the data read as 0.
+#[derive(Clone, Copy)]
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn peek(&self, length: usize) -> u32 {
+        (0..length).fold(0, |value, i| {
+            let bit = self.position + i;
+            let byte = self.data.get(bit / 8).copied().unwrap_or(0);
+            (value << 1) | ((byte >> (7 - bit % 8)) & 1) as u32
+        })
+    }
+
+    fn consume(&mut self, length: usize) {
+        self.position += length;
+    }
+
+    fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.data.len() * 8
+    }
+
+    /// Skips an end-of-line code (000000000001), along with any fill bits (0)
+    /// before it.
+    fn skip_end_of_line(&mut self) -> bool {
+        let mut position = self.position;
+        while position + 12 <= self.data.len() * 8 {
+            let reader = BitReader {
+                data: self.data,
+                position,
+            };
+            match reader.peek(12) {
+                1 => {
+                    self.position = position + 12;
+                    return true;
+                }
+                0 => position += 1,
+                _ => return false,
+            }
+        }
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Pass,
+    Horizontal,
+    Vertical(isize),
+    Extension,
+}
+
+/// Lookup tables for the variable-length codes, keyed by code length and value.
+struct Codes {
+    white: HashMap<(usize, u32), usize>,
+    black: HashMap<(usize, u32), usize>,
+    modes: HashMap<(usize, u32), Mode>,
+}
+
+impl Codes {
+    fn new() -> Codes {
+        let table = |codes: &[(&str, u16)]| {
+            codes
+                .iter()
+                .chain(EXTENDED_MAKEUP_CODES.iter())
+                .map(|&(code, run)| (code_key(code), run as usize))
+                .collect()
+        };
+        Codes {
+            white: table(WHITE_CODES),
+            black: table(BLACK_CODES),
+            modes: MODE_CODES
+                .iter()
+                .map(|&(code, mode)| (code_key(code), mode))
+                .collect(),
+        }
+    }
+
+    fn read_mode(&self, reader: &mut BitReader) -> Option<Mode> {
+        (1..=7).find_map(|length| {
+            let mode = *self.modes.get(&(length, reader.peek(length)))?;
+            reader.consume(length);
+            Some(mode)
+        })
+    }
+
+    /// A run length is coded as any number of makeup codes (multiples of 64)
+    /// followed by a terminating code (0 to 63).
+    fn read_run(&self, reader: &mut BitReader, white: bool) -> Option<usize> {
+        let table = if white { &self.white } else { &self.black };
+        let mut total = 0;
+        loop {
+            let run = (2..=13).find_map(|length| {
+                let run = *table.get(&(length, reader.peek(length)))?;
+                reader.consume(length);
+                Some(run)
+            })?;
+            total += run;
+            if run < 64 {
+                return Some(total);
+            }
+        }
+    }
+}
+
+fn code_key(code: &str) -> (usize, u32) {
+    let value = code
+        .bytes()
+        .fold(0, |value, bit| (value << 1) | (bit == b'1') as u32);
+    (code.len(), value)
+}
+
+/// See ITU-T T.4, Table 4
+const MODE_CODES: &[(&str, Mode)] = &[
+    ("0001", Mode::Pass),
+    ("001", Mode::Horizontal),
+    ("1", Mode::Vertical(0)),
+    ("011", Mode::Vertical(1)),
+    ("000011", Mode::Vertical(2)),
+    ("0000011", Mode::Vertical(3)),
+    ("010", Mode::Vertical(-1)),
+    ("000010", Mode::Vertical(-2)),
+    ("0000010", Mode::Vertical(-3)),
+    ("0000001", Mode::Extension),
+];
+
+/// Terminating and makeup codes for white runs. See ITU-T T.4, Tables 2 and 3
+const WHITE_CODES: &[(&str, u16)] = &[
+    ("00110101", 0),
+    ("000111", 1),
+    ("0111", 2),
+    ("1000", 3),
+    ("1011", 4),
+    ("1100", 5),
+    ("1110", 6),
+    ("1111", 7),
+    ("10011", 8),
+    ("10100", 9),
+    ("00111", 10),
+    ("01000", 11),
+    ("001000", 12),
+    ("000011", 13),
+    ("110100", 14),
+    ("110101", 15),
+    ("101010", 16),
+    ("101011", 17),
+    ("0100111", 18),
+    ("0001100", 19),
+    ("0001000", 20),
+    ("0010111", 21),
+    ("0000011", 22),
+    ("0000100", 23),
+    ("0101000", 24),
+    ("0101011", 25),
+    ("0010011", 26),
+    ("0100100", 27),
+    ("0011000", 28),
+    ("00000010", 29),
+    ("00000011", 30),
+    ("00011010", 31),
+    ("00011011", 32),
+    ("00010010", 33),
+    ("00010011", 34),
+    ("00010100", 35),
+    ("00010101", 36),
+    ("00010110", 37),
+    ("00010111", 38),
+    ("00101000", 39),
+    ("00101001", 40),
+    ("00101010", 41),
+    ("00101011", 42),
+    ("00101100", 43),
+    ("00101101", 44),
+    ("00000100", 45),
+    ("00000101", 46),
+    ("00001010", 47),
+    ("00001011", 48),
+    ("01010010", 49),
+    ("01010011", 50),
+    ("01010100", 51),
+    ("01010101", 52),
+    ("00100100", 53),
+    ("00100101", 54),
+    ("01011000", 55),
+    ("01011001", 56),
+    ("01011010", 57),
+    ("01011011", 58),
+    ("01001010", 59),
+    ("01001011", 60),
+    ("00110010", 61),
+    ("00110011", 62),
+    ("00110100", 63),
+    ("11011", 64),
+    ("10010", 128),
+    ("010111", 192),
+    ("0110111", 256),
+    ("00110110", 320),
+    ("00110111", 384),
+    ("01100100", 448),
+    ("01100101", 512),
+    ("01101000", 576),
+    ("01100111", 640),
+    ("011001100", 704),
+    ("011001101", 768),
+    ("011010010", 832),
+    ("011010011", 896),
+    ("011010100", 960),
+    ("011010101", 1024),
+    ("011010110", 1088),
+    ("011010111", 1152),
+    ("011011000", 1216),
+    ("011011001", 1280),
+    ("011011010", 1344),
+    ("011011011", 1408),
+    ("010011000", 1472),
+    ("010011001", 1536),
+    ("010011010", 1600),
+    ("011000", 1664),
+    ("010011011", 1728),
+];
+
+/// Terminating and makeup codes for black runs. See ITU-T T.4, Tables 2 and 3
+const BLACK_CODES: &[(&str, u16)] = &[
+    ("0000110111", 0),
+    ("010", 1),
+    ("11", 2),
+    ("10", 3),
+    ("011", 4),
+    ("0011", 5),
+    ("0010", 6),
+    ("00011", 7),
+    ("000101", 8),
+    ("000100", 9),
+    ("0000100", 10),
+    ("0000101", 11),
+    ("0000111", 12),
+    ("00000100", 13),
+    ("00000111", 14),
+    ("000011000", 15),
+    ("0000010111", 16),
+    ("0000011000", 17),
+    ("0000001000", 18),
+    ("00001100111", 19),
+    ("00001101000", 20),
+    ("00001101100", 21),
+    ("00000110111", 22),
+    ("00000101000", 23),
+    ("00000010111", 24),
+    ("00000011000", 25),
+    ("000011001010", 26),
+    ("000011001011", 27),
+    ("000011001100", 28),
+    ("000011001101", 29),
+    ("000001101000", 30),
+    ("000001101001", 31),
+    ("000001101010", 32),
+    ("000001101011", 33),
+    ("000011010010", 34),
+    ("000011010011", 35),
+    ("000011010100", 36),
+    ("000011010101", 37),
+    ("000011010110", 38),
+    ("000011010111", 39),
+    ("000001101100", 40),
+    ("000001101101", 41),
+    ("000011011010", 42),
+    ("000011011011", 43),
+    ("000001010100", 44),
+    ("000001010101", 45),
+    ("000001010110", 46),
+    ("000001010111", 47),
+    ("000001100100", 48),
+    ("000001100101", 49),
+    ("000001010010", 50),
+    ("000001010011", 51),
+    ("000000100100", 52),
+    ("000000110111", 53),
+    ("000000111000", 54),
+    ("000000100111", 55),
+    ("000000101000", 56),
+    ("000001011000", 57),
+    ("000001011001", 58),
+    ("000000101011", 59),
+    ("000000101100", 60),
+    ("000001011010", 61),
+    ("000001100110", 62),
+    ("000001100111", 63),
+    ("0000001111", 64),
+    ("000011001000", 128),
+    ("000011001001", 192),
+    ("000001011011", 256),
+    ("000000110011", 320),
+    ("000000110100", 384),
+    ("000000110101", 448),
+    ("0000001101100", 512),
+    ("0000001101101", 576),
+    ("0000001001010", 640),
+    ("0000001001011", 704),
+    ("0000001001100", 768),
+    ("0000001001101", 832),
+    ("0000001110010", 896),
+    ("0000001110011", 960),
+    ("0000001110100", 1024),
+    ("0000001110101", 1088),
+    ("0000001110110", 1152),
+    ("0000001110111", 1216),
+    ("0000001010010", 1280),
+    ("0000001010011", 1344),
+    ("0000001010100", 1408),
+    ("0000001010101", 1472),
+    ("0000001011010", 1536),
+    ("0000001011011", 1600),
+    ("0000001100100", 1664),
+    ("0000001100101", 1728),
+];
+
+/// Makeup codes shared by white and black runs. See ITU-T T.4, Table 3
+const EXTENDED_MAKEUP_CODES: &[(&str, u16)] = &[
+    ("00000001000", 1792),
+    ("00000001100", 1856),
+    ("00000001101", 1920),
+    ("000000010010", 1984),
+    ("000000010011", 2048),
+    ("000000010100", 2112),
+    ("000000010101", 2176),
+    ("000000010110", 2240),
+    ("000000010111", 2304),
+    ("000000011100", 2368),
+    ("000000011101", 2432),
+    ("000000011110", 2496),
+    ("000000011111", 2560),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::objects::PdfObject;
+
+    /// Packs a string of '0' and '1' characters into bytes.
+    fn bits(source: &str) -> Vec<u8> {
+        let bits: Vec<u8> = source.bytes().filter(|b| b"01".contains(b)).collect();
+        bits.chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, &bit)| byte | ((bit - b'0') << (7 - i)))
+            })
+            .collect()
+    }
+
+    fn params(k: i64, columns: i64) -> Dictionary {
+        let mut params = Dictionary::new();
+        params.set("K", PdfObject::Integer(k));
+        params.set("Columns", PdfObject::Integer(columns));
+        params
+    }
+
+    #[test]
+    fn test_group_4() {
+        // Horizontal mode with 2 white and 3 black pixels, then V0 to the end.
+        // The second line repeats the first with three V0 codes, followed by
+        // EOFB.
+        let data = bits("001 0111 10 1  1 1 1  000000000001 000000000001");
+        let decoded = ccitt_fax_decode(&data, Some(&params(-1, 8))).unwrap();
+        assert_eq!(decoded, vec![0b1100_0111, 0b1100_0111]);
+    }
+
+    #[test]
+    fn test_group_3() {
+        // White 2, black 3, white 3 on each line, preceded by end-of-line codes,
+        // and terminated by RTC.
+        let line = "000000000001 0111 10 1000";
+        let data = bits(&format!("{} {} {}", line, line, "000000000001".repeat(6)));
+        let mut params = params(0, 8);
+        params.set("BlackIs1", PdfObject::Boolean(true));
+        let decoded = ccitt_fax_decode(&data, Some(&params)).unwrap();
+        assert_eq!(decoded, vec![0b0011_1000, 0b0011_1000]);
+    }
+
+    #[test]
+    fn test_long_runs() {
+        // A white run of 1800 (1792 + 8) and a black run of 200 (192 + 8).
+        let data = bits("00000001000 10011 000011001001 000101");
+        let lines = decode_lines(
+            &data,
+            &CcittParams {
+                columns: 2000,
+                rows: 1,
+                ..CcittParams::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(lines, vec![vec![1800]]);
+    }
+
+    #[test]
+    fn test_too_large() {
+        // EOFB alone, for an image whose rows would take terabytes.
+        let data = bits("000000000001 000000000001");
+        let mut params = params(-1, 8);
+        params.set("Rows", PdfObject::Integer(1 << 36));
+        assert!(matches!(
+            ccitt_fax_decode(&data, Some(&params)),
+            Err(FilterError::Malformed(_))
+        ));
+        params.set("Rows", PdfObject::Integer(1));
+        params.set("Columns", PdfObject::Integer(1 << 40));
+        assert!(ccitt_fax_decode(&data, Some(&params)).is_err());
+    }
+}
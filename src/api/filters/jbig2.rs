@@ -0,0 +1,1489 @@
+use std::collections::HashMap;
+
+use crate::api::{
+    filters::{
+        ccitt::{self, CcittParams},
+        FilterError,
+    },
+    image::MAX_PIXELS,
+};
+
+/// Decodes a JBIG2 image in the embedded stream organization used by PDF: a
+/// sequence of segments without the JBIG2 file header. Segments shared between
+/// images (typically symbol dictionaries) are given separately as the
+/// /JBIG2Globals stream. See sec. 7.4.7 (pg. 46) and ITU-T T.88
+///
+/// The result has 1 bit per pixel, with 0 bits for black pixels: JBIG2 uses 1
+/// for black, which is the opposite of the DeviceGray convention.
+///
+/// Generic regions (arithmetic or MMR coded), generic refinement regions,
+/// symbol dictionaries and text regions are supported when they use arithmetic
+/// coding. Huffman coded symbol dictionaries and text regions, and halftone
+/// regions, are not.
+pub fn jbig2_decode(data: &[u8], globals: Option<&[u8]>) -> Result<Vec<u8>, FilterError> {
+    let mut decoder = Decoder::default();
+    if let Some(globals) = globals {
+        decoder.run(globals)?;
+    }
+    decoder.run(data)?;
+    let page = decoder
+        .page
+        .ok_or_else(|| malformed("no page information"))?;
+
+    let row_length = page.width.div_ceil(8);
+    let mut output = vec![0xFF; row_length * page.height];
+    for y in 0..page.height {
+        for x in 0..page.width {
+            if page.pixels[y * page.width + x] == 1 {
+                output[y * row_length + x / 8] &= !(0x80 >> (x % 8));
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// The number of pixels of a raster, if it is at most `MAX_PIXELS`.
+fn pixel_count(width: usize, height: usize) -> Result<usize, FilterError> {
+    width
+        .checked_mul(height)
+        .filter(|&count| count <= MAX_PIXELS)
+        .ok_or_else(|| malformed(&format!("image of {width} by {height} pixels is too large")))
+}
+
+fn malformed(message: &str) -> FilterError {
+    FilterError::Malformed(format!("JBIG2Decode: {}", message))
+}
+
+fn unsupported(feature: &str) -> FilterError {
+    FilterError::Unsupported(format!("JBIG2Decode ({})", feature))
+}
+
+///
+/// A bilevel image with one byte per pixel, 1 for black. Pixels outside of the
+/// image read as 0.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Raster {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// A raster filled with a value. The size comes from the data, and is
+    /// checked against `MAX_PIXELS` before anything is allocated.
+    fn new(width: usize, height: usize, value: u8) -> Result<Raster, FilterError> {
+        Ok(Raster {
+            width,
+            height,
+            pixels: vec![value; pixel_count(width, height)?],
+        })
+    }
+
+    fn get(&self, x: i64, y: i64) -> u8 {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return 0;
+        }
+        self.pixels[y as usize * self.width + x as usize]
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: u8) {
+        self.pixels[y * self.width + x] = value;
+    }
+
+    /// Adds rows at the bottom, for pages whose height is not known in advance.
+    fn grow(&mut self, height: usize, value: u8) -> Result<(), FilterError> {
+        if height > self.height {
+            self.pixels.resize(pixel_count(self.width, height)?, value);
+            self.height = height;
+        }
+        Ok(())
+    }
+
+    fn sub_raster(
+        &self,
+        x: i64,
+        y: i64,
+        width: usize,
+        height: usize,
+    ) -> Result<Raster, FilterError> {
+        let mut raster = Raster::new(width, height, 0)?;
+        for row in 0..height {
+            for column in 0..width {
+                let value = self.get(x + column as i64, y + row as i64);
+                raster.set(column, row, value);
+            }
+        }
+        Ok(raster)
+    }
+
+    /// Draws another raster onto this one, with its top-left corner at (x, y).
+    /// The operators are OR, AND, XOR, XNOR and REPLACE. See T.88, sec. 6.4.10
+    /// and 7.4.1.5
+    fn combine(&mut self, other: &Raster, x: i64, y: i64, operator: u8) {
+        for row in 0..other.height {
+            let target_y = y + row as i64;
+            if target_y < 0 || target_y >= self.height as i64 {
+                continue;
+            }
+            for column in 0..other.width {
+                let target_x = x + column as i64;
+                if target_x < 0 || target_x >= self.width as i64 {
+                    continue;
+                }
+                let index = target_y as usize * self.width + target_x as usize;
+                let (old, new) = (self.pixels[index], other.pixels[row * other.width + column]);
+                self.pixels[index] = match operator {
+                    0 => old | new,
+                    1 => old & new,
+                    2 => old ^ new,
+                    3 => 1 - (old ^ new),
+                    _ => new,
+                };
+            }
+        }
+    }
+}
+
+/// The state kept while the segments of an image are decoded: the page, the
+/// symbols exported by symbol dictionary segments and the intermediate regions
+/// that refinement segments refer to, keyed by segment number.
+#[derive(Default)]
+struct Decoder {
+    page: Option<Raster>,
+    page_default_pixel: u8,
+    page_height_known: bool,
+    symbols: HashMap<u32, Vec<Raster>>,
+    regions: HashMap<u32, (RegionInfo, Raster)>,
+}
+
+/// The header of a segment. See T.88, sec. 7.2
+struct SegmentHeader {
+    number: u32,
+    kind: u8,
+    referred: Vec<u32>,
+    /// Only immediate generic regions may leave their length unspecified.
+    length: Option<usize>,
+}
+
+/// The region segment information field. See T.88, sec. 7.4.1
+#[derive(Debug, Clone, Copy)]
+struct RegionInfo {
+    width: usize,
+    height: usize,
+    x: i64,
+    y: i64,
+    combination: u8,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, position: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], FilterError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(|| malformed("unexpected end of data"))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, FilterError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, FilterError> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn u16(&mut self) -> Result<u16, FilterError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, FilterError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position.min(self.data.len())..];
+        self.position = self.data.len();
+        rest
+    }
+
+    fn at_end(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    /// Adaptive template pixels are pairs of signed byte offsets.
+    fn at_pixels(&mut self, count: usize) -> Result<Vec<(i64, i64)>, FilterError> {
+        (0..count)
+            .map(|_| Ok((self.i8()? as i64, self.i8()? as i64)))
+            .collect()
+    }
+
+    fn region_info(&mut self) -> Result<RegionInfo, FilterError> {
+        Ok(RegionInfo {
+            width: self.u32()? as usize,
+            height: self.u32()? as usize,
+            x: self.u32()? as i64,
+            y: self.u32()? as i64,
+            combination: self.u8()? & 0x07,
+        })
+    }
+
+    fn segment_header(&mut self) -> Result<SegmentHeader, FilterError> {
+        let number = self.u32()?;
+        let flags = self.u8()?;
+        let kind = flags & 0x3F;
+        let long_page_association = flags & 0x40 != 0;
+
+        let first = self.u8()?;
+        let mut count = (first >> 5) as usize;
+        if count == 7 {
+            self.position -= 1;
+            count = (self.u32()? & 0x1FFF_FFFF) as usize;
+            // Retention flags: one bit for this segment and each referred one.
+            self.bytes((count + 8) / 8)?;
+        }
+        let referred = (0..count)
+            .map(|_| match number {
+                0..=256 => self.u8().map(u32::from),
+                257..=65536 => self.u16().map(u32::from),
+                _ => self.u32(),
+            })
+            .collect::<Result<_, _>>()?;
+        if long_page_association {
+            self.u32()?;
+        } else {
+            self.u8()?;
+        }
+        let length = match self.u32()? {
+            0xFFFF_FFFF => None,
+            length => Some(length as usize),
+        };
+        Ok(SegmentHeader {
+            number,
+            kind,
+            referred,
+            length,
+        })
+    }
+
+    /// Finds the end of an immediate generic region whose length is unknown:
+    /// its coded data is followed by an end marker (0xFFAC for arithmetic
+    /// coding, 0x0000 for MMR) and a 4-byte row count. See T.88, sec. 7.2.7
+    fn unknown_length(&self) -> Result<usize, FilterError> {
+        let rest = &self.data[self.position..];
+        // Region information (17 bytes) and generic region flags.
+        let mmr = rest.get(17).ok_or_else(|| malformed("truncated region"))? & 1 == 1;
+        let marker: &[u8] = if mmr { &[0x00, 0x00] } else { &[0xFF, 0xAC] };
+        (18..rest.len().saturating_sub(5))
+            .find(|&i| &rest[i..i + 2] == marker)
+            .map(|i| i + 6)
+            .ok_or_else(|| malformed("missing end of region marker"))
+    }
+}
+
+impl Decoder {
+    /// Decodes every segment of the given data in order.
+    fn run(&mut self, data: &[u8]) -> Result<(), FilterError> {
+        let mut reader = Reader::new(data);
+        while !reader.at_end() {
+            let header = reader.segment_header()?;
+            let length = match header.length {
+                Some(length) => length,
+                None if matches!(header.kind, 36 | 38 | 39) => reader.unknown_length()?,
+                None => return Err(malformed("segment of unknown length")),
+            };
+            // Some encoders write a final segment whose data is cut short.
+            let length = length.min(data.len() - reader.position);
+            let body = reader.bytes(length)?;
+            self.segment(&header, body)?;
+            if header.kind == 51 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn segment(&mut self, header: &SegmentHeader, data: &[u8]) -> Result<(), FilterError> {
+        let mut reader = Reader::new(data);
+        match header.kind {
+            0 => {
+                let input = header
+                    .referred
+                    .iter()
+                    .filter_map(|number| self.symbols.get(number))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                let symbols = symbol_dictionary(&mut reader, input)?;
+                self.symbols.insert(header.number, symbols);
+            }
+            4 | 6 | 7 => {
+                let symbols: Vec<&Raster> = header
+                    .referred
+                    .iter()
+                    .filter_map(|number| self.symbols.get(number))
+                    .flatten()
+                    .collect();
+                let (info, region) = text_region(&mut reader, &symbols)?;
+                self.region(header, info, region)?;
+            }
+            16 => return Err(unsupported("pattern dictionaries")),
+            20 | 22 | 23 => return Err(unsupported("halftone regions")),
+            36 | 38 | 39 => {
+                let (info, region) = generic_region(&mut reader, header.length.is_none())?;
+                self.region(header, info, region)?;
+            }
+            40 | 42 | 43 => {
+                let info = reader.region_info()?;
+                let reference = match header.referred.first() {
+                    Some(number) => match self.regions.remove(number) {
+                        Some((_, region)) => region,
+                        None => return Err(malformed("missing region to refine")),
+                    },
+                    None => {
+                        let page = self.page.as_ref().ok_or_else(|| malformed("no page"))?;
+                        page.sub_raster(info.x, info.y, info.width, info.height)?
+                    }
+                };
+                let region = refinement_region(&mut reader, &info, &reference)?;
+                self.region(header, info, region)?;
+            }
+            48 => {
+                let width = reader.u32()? as usize;
+                let height = reader.u32()?;
+                reader.bytes(8)?;
+                let flags = reader.u8()?;
+                self.page_default_pixel = (flags >> 2) & 1;
+                self.page_height_known = height != 0xFFFF_FFFF;
+                let height = if self.page_height_known {
+                    height as usize
+                } else {
+                    0
+                };
+                self.page = Some(Raster::new(width, height, self.page_default_pixel)?);
+            }
+            50 => {
+                let row = reader.u32()? as usize;
+                if let (Some(page), false) = (self.page.as_mut(), self.page_height_known) {
+                    page.grow(row.saturating_add(1), self.page_default_pixel)?;
+                }
+            }
+            // End of page, end of file, profiles, tables and extensions.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Keeps intermediate regions for later refinement and draws immediate ones
+    /// onto the page.
+    fn region(
+        &mut self,
+        header: &SegmentHeader,
+        info: RegionInfo,
+        region: Raster,
+    ) -> Result<(), FilterError> {
+        if matches!(header.kind, 4 | 36 | 40) {
+            self.regions.insert(header.number, (info, region));
+            return Ok(());
+        }
+        if let Some(page) = self.page.as_mut() {
+            if !self.page_height_known {
+                let bottom = (info.y.max(0) as usize).saturating_add(region.height);
+                page.grow(bottom, self.page_default_pixel)?;
+            }
+            page.combine(&region, info.x, info.y, info.combination);
+        }
+        Ok(())
+    }
+}
+
+/// The smallest number of bits that can hold every index below the count.
+fn code_length(count: usize) -> usize {
+    let mut length = 0;
+    while (1usize << length) < count {
+        length += 1;
+    }
+    length
+}
+
+/// A generic region segment. See T.88, sec. 7.4.6
+fn generic_region(
+    reader: &mut Reader,
+    unknown_length: bool,
+) -> Result<(RegionInfo, Raster), FilterError> {
+    let mut info = reader.region_info()?;
+    let flags = reader.u8()?;
+    let mmr = flags & 1 == 1;
+    let template = (flags >> 1) & 3;
+    let tpgdon = (flags >> 3) & 1 == 1;
+    let at = match (mmr, template) {
+        (true, _) => vec![],
+        (false, 0) => reader.at_pixels(4)?,
+        (false, _) => reader.at_pixels(1)?,
+    };
+    let mut data = reader.rest();
+    if unknown_length {
+        let (coded, rows) = data.split_at(data.len() - 4);
+        info.height = u32::from_be_bytes([rows[0], rows[1], rows[2], rows[3]]) as usize;
+        data = coded;
+    }
+
+    let region = if mmr {
+        mmr_region(data, info.width, info.height)?
+    } else {
+        let mut coder = Coder::new(data);
+        coder.generic(info.width, info.height, template, tpgdon, &at)?
+    };
+    Ok((info, region))
+}
+
+/// MMR coded regions use the CCITT Group 4 coding, with 1 bits for black.
+fn mmr_region(data: &[u8], width: usize, height: usize) -> Result<Raster, FilterError> {
+    let params = CcittParams {
+        k: -1,
+        columns: width,
+        rows: height,
+        ..CcittParams::default()
+    };
+    let lines = ccitt::decode_lines(data, &params)?;
+    let mut raster = Raster::new(width, height, 0)?;
+    for (y, changes) in lines.iter().take(height).enumerate() {
+        for run in changes.chunks(2) {
+            let end = run.get(1).copied().unwrap_or(width).min(width);
+            for x in run[0]..end {
+                raster.set(x, y, 1);
+            }
+        }
+    }
+    Ok(raster)
+}
+
+/// A generic refinement region segment. See T.88, sec. 7.4.7
+fn refinement_region(
+    reader: &mut Reader,
+    info: &RegionInfo,
+    reference: &Raster,
+) -> Result<Raster, FilterError> {
+    let flags = reader.u8()?;
+    let template = flags & 1;
+    let tpgron = (flags >> 1) & 1 == 1;
+    let at = if template == 0 {
+        reader.at_pixels(2)?
+    } else {
+        vec![]
+    };
+    let mut coder = Coder::new(reader.rest());
+    let params = Refinement {
+        template,
+        reference,
+        dx: 0,
+        dy: 0,
+        tpgron,
+        at: &at,
+    };
+    coder.refinement(info.width, info.height, &params)
+}
+
+/// A symbol dictionary segment, returning the exported symbols. See T.88, sec.
+/// 6.5 and 7.4.2
+fn symbol_dictionary(reader: &mut Reader, input: Vec<Raster>) -> Result<Vec<Raster>, FilterError> {
+    let flags = reader.u16()?;
+    if flags & 1 == 1 {
+        return Err(unsupported("Huffman coded symbol dictionaries"));
+    }
+    let refagg = (flags >> 1) & 1 == 1;
+    let template = ((flags >> 10) & 3) as u8;
+    let refinement_template = ((flags >> 12) & 1) as u8;
+    let at = reader.at_pixels(if template == 0 { 4 } else { 1 })?;
+    let refinement_at = if refagg && refinement_template == 0 {
+        reader.at_pixels(2)?
+    } else {
+        vec![]
+    };
+    let exported_count = reader.u32()? as usize;
+    let new_count = reader.u32()? as usize;
+
+    let mut coder = Coder::new(reader.rest());
+    let mut symbols = input;
+    let input_count = symbols.len();
+    let id_length = code_length(input_count + new_count);
+    let mut height = 0i64;
+    while symbols.len() < input_count + new_count {
+        height += coder
+            .integer(Integer::Dh)
+            .ok_or_else(|| malformed("invalid height"))? as i64;
+        let mut width = 0i64;
+        while let Some(delta) = coder.integer(Integer::Dw) {
+            width += delta as i64;
+            if width < 0 || height < 0 || symbols.len() >= input_count + new_count {
+                return Err(malformed("invalid symbol size"));
+            }
+            let (width, height) = (width as usize, height as usize);
+            let symbol = if !refagg {
+                coder.generic(width, height, template, false, &at)?
+            } else {
+                let instances = coder.integer(Integer::Ai).unwrap_or(0);
+                if instances == 1 {
+                    let id = coder.id(id_length);
+                    let dx = coder.integer(Integer::Rdx).unwrap_or(0) as i64;
+                    let dy = coder.integer(Integer::Rdy).unwrap_or(0) as i64;
+                    let reference = symbols.get(id).ok_or_else(|| malformed("invalid symbol"))?;
+                    let params = Refinement {
+                        template: refinement_template,
+                        reference,
+                        dx,
+                        dy,
+                        tpgron: false,
+                        at: &refinement_at,
+                    };
+                    coder.refinement(width, height, &params)?
+                } else {
+                    let references: Vec<&Raster> = symbols.iter().collect();
+                    let params = TextRegion {
+                        width,
+                        height,
+                        instances: instances.max(0) as usize,
+                        strips: 1,
+                        symbols: &references,
+                        id_length,
+                        default_pixel: 0,
+                        combination: 0,
+                        transposed: false,
+                        reference_corner: 1,
+                        ds_offset: 0,
+                        refine: true,
+                        refinement_template,
+                        refinement_at: &refinement_at,
+                    };
+                    coder.text(&params)?
+                }
+            };
+            symbols.push(symbol);
+        }
+    }
+
+    // Runs of alternately not exported and exported symbols.
+    let mut exported = Vec::with_capacity(exported_count);
+    let mut export = false;
+    let mut index = 0;
+    while index < symbols.len() {
+        let run = coder
+            .integer(Integer::Ex)
+            .ok_or_else(|| malformed("invalid export"))?;
+        if run < 0 {
+            return Err(malformed("invalid export"));
+        }
+        let end = (index + run as usize).min(symbols.len());
+        if export {
+            exported.extend_from_slice(&symbols[index..end]);
+        }
+        index = end;
+        export = !export;
+    }
+    Ok(exported)
+}
+
+/// A text region segment. See T.88, sec. 6.4 and 7.4.3
+fn text_region(
+    reader: &mut Reader,
+    symbols: &[&Raster],
+) -> Result<(RegionInfo, Raster), FilterError> {
+    let info = reader.region_info()?;
+    let flags = reader.u16()?;
+    if flags & 1 == 1 {
+        return Err(unsupported("Huffman coded text regions"));
+    }
+    let refine = (flags >> 1) & 1 == 1;
+    let ds_offset = ((flags >> 10) & 0x1F) as i64;
+    let refinement_template = ((flags >> 15) & 1) as u8;
+    let refinement_at = if refine && refinement_template == 0 {
+        reader.at_pixels(2)?
+    } else {
+        vec![]
+    };
+    let instances = reader.u32()? as usize;
+
+    let params = TextRegion {
+        width: info.width,
+        height: info.height,
+        instances,
+        strips: 1 << ((flags >> 2) & 3),
+        symbols,
+        id_length: code_length(symbols.len()),
+        default_pixel: ((flags >> 9) & 1) as u8,
+        combination: ((flags >> 7) & 3) as u8,
+        transposed: (flags >> 6) & 1 == 1,
+        reference_corner: ((flags >> 4) & 3) as u8,
+        // A signed 5-bit value.
+        ds_offset: if ds_offset >= 16 {
+            ds_offset - 32
+        } else {
+            ds_offset
+        },
+        refine,
+        refinement_template,
+        refinement_at: &refinement_at,
+    };
+    let mut coder = Coder::new(reader.rest());
+    Ok((info, coder.text(&params)?))
+}
+
+/// The parameters of generic refinement decoding. See T.88, sec. 6.3.2
+struct Refinement<'a> {
+    template: u8,
+    reference: &'a Raster,
+    dx: i64,
+    dy: i64,
+    tpgron: bool,
+    at: &'a [(i64, i64)],
+}
+
+/// The parameters of text region decoding. See T.88, sec. 6.4.2
+struct TextRegion<'a> {
+    width: usize,
+    height: usize,
+    instances: usize,
+    strips: i64,
+    symbols: &'a [&'a Raster],
+    id_length: usize,
+    default_pixel: u8,
+    combination: u8,
+    transposed: bool,
+    /// 0 for bottom left, 1 for top left, 2 for bottom right and 3 for top right.
+    reference_corner: u8,
+    ds_offset: i64,
+    refine: bool,
+    refinement_template: u8,
+    refinement_at: &'a [(i64, i64)],
+}
+
+/// The integer arithmetic decoding procedures, each with its own contexts. See
+/// T.88, Annex A.2
+#[derive(Clone, Copy)]
+enum Integer {
+    Dh,
+    Dw,
+    Ex,
+    Ai,
+    Dt,
+    Fs,
+    Ds,
+    It,
+    Ri,
+    Rdw,
+    Rdh,
+    Rdx,
+    Rdy,
+}
+
+/// An arithmetic decoder with the contexts of every decoding procedure used
+/// by a segment.
+struct Coder<'a> {
+    decoder: ArithmeticDecoder<'a>,
+    generic: Vec<u8>,
+    refinement: Vec<u8>,
+    integers: Vec<Vec<u8>>,
+    ids: Vec<u8>,
+}
+
+impl<'a> Coder<'a> {
+    fn new(data: &'a [u8]) -> Coder<'a> {
+        Coder {
+            decoder: ArithmeticDecoder::new(data),
+            generic: vec![0; 1 << 16],
+            refinement: vec![0; 1 << 13],
+            integers: vec![vec![0; 512]; 13],
+            ids: vec![],
+        }
+    }
+
+    /// Returns None for the out-of-band value.
+    fn integer(&mut self, kind: Integer) -> Option<i32> {
+        let contexts = &mut self.integers[kind as usize];
+        let mut previous = 1;
+        let mut bits = |decoder: &mut ArithmeticDecoder, length: usize| {
+            let mut value = 0u32;
+            for _ in 0..length {
+                let bit = decoder.read_bit(contexts, previous);
+                previous = if previous < 256 {
+                    (previous << 1) | bit as usize
+                } else {
+                    (((previous << 1) | bit as usize) & 511) | 256
+                };
+                value = (value << 1) | bit as u32;
+            }
+            value
+        };
+        let decoder = &mut self.decoder;
+        let sign = bits(decoder, 1);
+        let value = if bits(decoder, 1) == 0 {
+            bits(decoder, 2)
+        } else if bits(decoder, 1) == 0 {
+            bits(decoder, 4) + 4
+        } else if bits(decoder, 1) == 0 {
+            bits(decoder, 6) + 20
+        } else if bits(decoder, 1) == 0 {
+            bits(decoder, 8) + 84
+        } else if bits(decoder, 1) == 0 {
+            bits(decoder, 12) + 340
+        } else {
+            bits(decoder, 32).wrapping_add(4436)
+        };
+        match (sign, value) {
+            (0, value) => Some(value as i32),
+            (_, 0) => None,
+            (_, value) => Some((value as i32).wrapping_neg()),
+        }
+    }
+
+    /// Symbol IDs have a fixed length. See T.88, Annex A.3
+    fn id(&mut self, length: usize) -> usize {
+        if self.ids.len() != 1 << (length + 1) {
+            self.ids = vec![0; 1 << (length + 1)];
+        }
+        let mut previous = 1;
+        for _ in 0..length {
+            let bit = self.decoder.read_bit(&mut self.ids, previous);
+            previous = (previous << 1) | bit as usize;
+        }
+        previous - (1 << length)
+    }
+
+    /// Generic region decoding with arithmetic coding. See T.88, sec. 6.2.5
+    fn generic(
+        &mut self,
+        width: usize,
+        height: usize,
+        template: u8,
+        tpgdon: bool,
+        at: &[(i64, i64)],
+    ) -> Result<Raster, FilterError> {
+        let template = generic_template(template, at);
+        let mut raster = Raster::new(width, height, 0)?;
+        let mut typical = false;
+        for y in 0..height {
+            if tpgdon {
+                let bit = self
+                    .decoder
+                    .read_bit(&mut self.generic, template.typical_context);
+                typical ^= bit == 1;
+                if typical {
+                    if y > 0 {
+                        raster
+                            .pixels
+                            .copy_within((y - 1) * width..y * width, y * width);
+                    }
+                    continue;
+                }
+            }
+            for x in 0..width {
+                let context = template.context(&raster, x as i64, y as i64);
+                let pixel = self.decoder.read_bit(&mut self.generic, context);
+                raster.set(x, y, pixel);
+            }
+        }
+        Ok(raster)
+    }
+
+    /// Generic refinement region decoding. See T.88, sec. 6.3.5
+    fn refinement(
+        &mut self,
+        width: usize,
+        height: usize,
+        params: &Refinement,
+    ) -> Result<Raster, FilterError> {
+        let template = refinement_template(params.template, params.at);
+        let reference = params.reference;
+        let mut raster = Raster::new(width, height, 0)?;
+        let mut typical = false;
+        for y in 0..height {
+            if params.tpgron {
+                let bit = self
+                    .decoder
+                    .read_bit(&mut self.refinement, template.typical_context);
+                typical ^= bit == 1;
+            }
+            for x in 0..width {
+                let (rx, ry) = (x as i64 - params.dx, y as i64 - params.dy);
+                if typical {
+                    // Pixels whose reference neighbourhood is uniform are copied.
+                    let value = reference.get(rx, ry);
+                    let uniform =
+                        (-1..=1).all(|j| (-1..=1).all(|i| reference.get(rx + i, ry + j) == value));
+                    if uniform {
+                        raster.set(x, y, value);
+                        continue;
+                    }
+                }
+                let mut context = 0;
+                for (bit, &(i, j)) in template.coding.iter().enumerate() {
+                    context |= (raster.get(x as i64 + i, y as i64 + j) as usize) << bit;
+                }
+                let shift = template.coding.len();
+                for (bit, &(i, j)) in template.reference.iter().enumerate() {
+                    context |= (reference.get(rx + i, ry + j) as usize) << (shift + bit);
+                }
+                let pixel = self.decoder.read_bit(&mut self.refinement, context);
+                raster.set(x, y, pixel);
+            }
+        }
+        Ok(raster)
+    }
+
+    /// Text region decoding, placing symbol instances strip by strip. See
+    /// T.88, sec. 6.4.5
+    fn text(&mut self, params: &TextRegion) -> Result<Raster, FilterError> {
+        let mut raster = Raster::new(params.width, params.height, params.default_pixel)?;
+        let invalid = || malformed("invalid text region");
+
+        let mut strip_t = -(self.integer(Integer::Dt).ok_or_else(invalid)? as i64) * params.strips;
+        let mut first_s = 0i64;
+        let mut instances = 0;
+        while instances < params.instances {
+            strip_t += self.integer(Integer::Dt).ok_or_else(invalid)? as i64 * params.strips;
+            first_s += self.integer(Integer::Fs).ok_or_else(invalid)? as i64;
+            let mut current_s = first_s;
+            let mut first = true;
+            loop {
+                if !first {
+                    match self.integer(Integer::Ds) {
+                        Some(delta) => current_s += delta as i64 + params.ds_offset,
+                        None => break,
+                    }
+                }
+                first = false;
+                let current_t = if params.strips == 1 {
+                    0
+                } else {
+                    self.integer(Integer::It).ok_or_else(invalid)? as i64
+                };
+                let t = strip_t + current_t;
+                let id = self.id(params.id_length);
+                let symbol = *params.symbols.get(id).ok_or_else(invalid)?;
+
+                let refined;
+                let symbol = if params.refine && self.integer(Integer::Ri).unwrap_or(0) != 0 {
+                    let dw = self.integer(Integer::Rdw).ok_or_else(invalid)? as i64;
+                    let dh = self.integer(Integer::Rdh).ok_or_else(invalid)? as i64;
+                    let dx = self.integer(Integer::Rdx).ok_or_else(invalid)? as i64;
+                    let dy = self.integer(Integer::Rdy).ok_or_else(invalid)? as i64;
+                    let width = symbol.width as i64 + dw;
+                    let height = symbol.height as i64 + dh;
+                    if width < 0 || height < 0 {
+                        return Err(invalid());
+                    }
+                    let refinement = Refinement {
+                        template: params.refinement_template,
+                        reference: symbol,
+                        dx: dw.div_euclid(2) + dx,
+                        dy: dh.div_euclid(2) + dy,
+                        tpgron: false,
+                        at: params.refinement_at,
+                    };
+                    refined = self.refinement(width as usize, height as usize, &refinement)?;
+                    &refined
+                } else {
+                    symbol
+                };
+
+                // CURS moves past the symbol, before or after drawing it depending
+                // on which of its corners is placed at (S, T).
+                let right = params.reference_corner & 2 != 0;
+                let top = params.reference_corner & 1 != 0;
+                let (width, height) = (symbol.width as i64, symbol.height as i64);
+                let (along, before) = if params.transposed {
+                    (height, !top)
+                } else {
+                    (width, right)
+                };
+                if before {
+                    current_s += along - 1;
+                }
+                let (x, y) = if params.transposed {
+                    (t, current_s)
+                } else {
+                    (current_s, t)
+                };
+                let x = if right { x - width + 1 } else { x };
+                let y = if top { y } else { y - height + 1 };
+                raster.combine(symbol, x, y, params.combination);
+                if !before {
+                    current_s += along - 1;
+                }
+                instances += 1;
+            }
+        }
+        Ok(raster)
+    }
+}
+
+/// The pixels making up the context of generic region decoding, from the least
+/// significant bit, along with the context used for typical prediction. See
+/// T.88, sec. 6.2.5.3 and 6.2.5.7
+struct GenericTemplate {
+    pixels: Vec<(i64, i64)>,
+    typical_context: usize,
+}
+
+impl GenericTemplate {
+    fn context(&self, raster: &Raster, x: i64, y: i64) -> usize {
+        self.pixels
+            .iter()
+            .enumerate()
+            .fold(0, |context, (bit, &(i, j))| {
+                context | (raster.get(x + i, y + j) as usize) << bit
+            })
+    }
+}
+
+fn generic_template(template: u8, at: &[(i64, i64)]) -> GenericTemplate {
+    let at = |index: usize, default: (i64, i64)| at.get(index).copied().unwrap_or(default);
+    let (pixels, typical_context) = match template {
+        0 => (
+            vec![
+                (-1, 0),
+                (-2, 0),
+                (-3, 0),
+                (-4, 0),
+                at(0, (3, -1)),
+                (2, -1),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+                (-2, -1),
+                at(1, (-3, -1)),
+                at(2, (2, -2)),
+                (1, -2),
+                (0, -2),
+                (-1, -2),
+                at(3, (-2, -2)),
+            ],
+            0x9B25,
+        ),
+        1 => (
+            vec![
+                (-1, 0),
+                (-2, 0),
+                (-3, 0),
+                at(0, (3, -1)),
+                (2, -1),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+                (-2, -1),
+                (2, -2),
+                (1, -2),
+                (0, -2),
+                (-1, -2),
+            ],
+            0x0795,
+        ),
+        2 => (
+            vec![
+                (-1, 0),
+                (-2, 0),
+                at(0, (2, -1)),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+                (-2, -1),
+                (1, -2),
+                (0, -2),
+                (-1, -2),
+            ],
+            0x00E5,
+        ),
+        _ => (
+            vec![
+                (-1, 0),
+                (-2, 0),
+                (-3, 0),
+                (-4, 0),
+                at(0, (2, -1)),
+                (1, -1),
+                (0, -1),
+                (-1, -1),
+                (-2, -1),
+                (-3, -1),
+            ],
+            0x0195,
+        ),
+    };
+    GenericTemplate {
+        pixels,
+        typical_context,
+    }
+}
+
+/// The pixels of the refinement context, taken from the region being decoded
+/// and from the reference, from the least significant bit. The typical
+/// prediction context has only the reference pixel at the current position set.
+/// See T.88, sec. 6.3.5.3
+struct RefinementTemplate {
+    coding: Vec<(i64, i64)>,
+    reference: Vec<(i64, i64)>,
+    typical_context: usize,
+}
+
+fn refinement_template(template: u8, at: &[(i64, i64)]) -> RefinementTemplate {
+    let at = |index: usize, default: (i64, i64)| at.get(index).copied().unwrap_or(default);
+    if template == 0 {
+        RefinementTemplate {
+            coding: vec![(-1, 0), (1, -1), (0, -1), at(0, (-1, -1))],
+            reference: vec![
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (1, 0),
+                (0, 0),
+                (-1, 0),
+                (1, -1),
+                (0, -1),
+                at(1, (-1, -1)),
+            ],
+            typical_context: 0x0100,
+        }
+    } else {
+        RefinementTemplate {
+            coding: vec![(-1, 0), (1, -1), (0, -1), (-1, -1)],
+            reference: vec![(1, 1), (0, 1), (1, 0), (0, 0), (-1, 0), (0, -1)],
+            typical_context: 0x0080,
+        }
+    }
+}
+
+/// The MQ arithmetic decoder. Each context is stored as a byte holding the
+/// index into the probability estimation table and the more probable symbol in
+/// its lowest bit. See T.88, Annex E
+struct ArithmeticDecoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    chigh: u32,
+    clow: u32,
+    ct: i32,
+    a: u32,
+}
+
+/// Qe, the index after an MPS, the index after an LPS, and whether the MPS
+/// switches after an LPS. See T.88, Table E.1
+const QE_TABLE: [(u32, u8, u8, bool); 47] = [
+    (0x5601, 1, 1, true),
+    (0x3401, 2, 6, false),
+    (0x1801, 3, 9, false),
+    (0x0AC1, 4, 12, false),
+    (0x0521, 5, 29, false),
+    (0x0221, 38, 33, false),
+    (0x5601, 7, 6, true),
+    (0x5401, 8, 14, false),
+    (0x4801, 9, 14, false),
+    (0x3801, 10, 14, false),
+    (0x3001, 11, 17, false),
+    (0x2401, 12, 18, false),
+    (0x1C01, 13, 20, false),
+    (0x1601, 29, 21, false),
+    (0x5601, 15, 14, true),
+    (0x5401, 16, 14, false),
+    (0x5101, 17, 15, false),
+    (0x4801, 18, 16, false),
+    (0x3801, 19, 17, false),
+    (0x3401, 20, 18, false),
+    (0x3001, 21, 19, false),
+    (0x2801, 22, 19, false),
+    (0x2401, 23, 20, false),
+    (0x2201, 24, 21, false),
+    (0x1C01, 25, 22, false),
+    (0x1801, 26, 23, false),
+    (0x1601, 27, 24, false),
+    (0x1401, 28, 25, false),
+    (0x1201, 29, 26, false),
+    (0x1101, 30, 27, false),
+    (0x0AC1, 31, 28, false),
+    (0x09C1, 32, 29, false),
+    (0x08A1, 33, 30, false),
+    (0x0521, 34, 31, false),
+    (0x0441, 35, 32, false),
+    (0x02A1, 36, 33, false),
+    (0x0221, 37, 34, false),
+    (0x0141, 38, 35, false),
+    (0x0111, 39, 36, false),
+    (0x0085, 40, 37, false),
+    (0x0049, 41, 38, false),
+    (0x0025, 42, 39, false),
+    (0x0015, 43, 40, false),
+    (0x0009, 44, 41, false),
+    (0x0005, 45, 42, false),
+    (0x0001, 45, 43, false),
+    (0x5601, 46, 46, false),
+];
+
+impl<'a> ArithmeticDecoder<'a> {
+    /// INITDEC. See T.88, sec. E.3.5
+    fn new(data: &'a [u8]) -> ArithmeticDecoder<'a> {
+        let mut decoder = ArithmeticDecoder {
+            data,
+            position: 0,
+            chigh: data.first().copied().unwrap_or(0xFF) as u32,
+            clow: 0,
+            ct: 0,
+            a: 0,
+        };
+        decoder.byte_in();
+        decoder.chigh = ((decoder.chigh << 7) & 0xFFFF) | ((decoder.clow >> 9) & 0x7F);
+        decoder.clow = (decoder.clow << 7) & 0xFFFF;
+        decoder.ct -= 7;
+        decoder.a = 0x8000;
+        decoder
+    }
+
+    /// BYTEIN. Past the end of the data, the decoder is fed 0xFF bytes. See
+    /// T.88, sec. E.3.4
+    fn byte_in(&mut self) {
+        if self.data.get(self.position) == Some(&0xFF) {
+            match self.data.get(self.position + 1) {
+                Some(&next) if next <= 0x8F => {
+                    self.position += 1;
+                    self.clow += (next as u32) << 9;
+                    self.ct = 7;
+                }
+                // A marker ends the coded data.
+                _ => {
+                    self.clow += 0xFF00;
+                    self.ct = 8;
+                }
+            }
+        } else {
+            self.position += 1;
+            self.clow += match self.data.get(self.position) {
+                Some(&byte) => (byte as u32) << 8,
+                None => 0xFF00,
+            };
+            self.ct = 8;
+        }
+        if self.clow > 0xFFFF {
+            self.chigh += self.clow >> 16;
+            self.clow &= 0xFFFF;
+        }
+    }
+
+    /// DECODE. See T.88, sec. E.3.2
+    fn read_bit(&mut self, contexts: &mut [u8], index: usize) -> u8 {
+        let state = contexts[index];
+        let (qe, next_mps, next_lps, switch) = QE_TABLE[(state >> 1) as usize];
+        let mps = state & 1;
+        let bit;
+        self.a -= qe;
+        if self.chigh < qe {
+            // Conditional exchange for the LPS path.
+            if self.a < qe {
+                self.a = qe;
+                bit = mps;
+                contexts[index] = (next_mps << 1) | mps;
+            } else {
+                self.a = qe;
+                bit = 1 - mps;
+                let mps = if switch { 1 - mps } else { mps };
+                contexts[index] = (next_lps << 1) | mps;
+            }
+        } else {
+            self.chigh -= qe;
+            if self.a & 0x8000 != 0 {
+                return mps;
+            }
+            if self.a < qe {
+                bit = 1 - mps;
+                let mps = if switch { 1 - mps } else { mps };
+                contexts[index] = (next_lps << 1) | mps;
+            } else {
+                bit = mps;
+                contexts[index] = (next_mps << 1) | mps;
+            }
+        }
+        // RENORMD
+        loop {
+            if self.ct == 0 {
+                self.byte_in();
+            }
+            self.a <<= 1;
+            self.chigh = ((self.chigh << 1) & 0xFFFF) | ((self.clow >> 15) & 1);
+            self.clow = (self.clow << 1) & 0xFFFF;
+            self.ct -= 1;
+            if self.a & 0x8000 != 0 {
+                break;
+            }
+        }
+        bit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The MQ encoder of T.88, sec. E.2, to produce coded data for the tests.
+    struct ArithmeticEncoder {
+        output: Vec<u8>,
+        a: u32,
+        c: u32,
+        ct: u32,
+    }
+
+    impl ArithmeticEncoder {
+        fn new() -> ArithmeticEncoder {
+            // The first byte stands for the one before the coded data.
+            ArithmeticEncoder {
+                output: vec![0],
+                a: 0x8000,
+                c: 0,
+                ct: 12,
+            }
+        }
+
+        fn encode(&mut self, contexts: &mut [u8], index: usize, bit: u8) {
+            let state = contexts[index];
+            let (qe, next_mps, next_lps, switch) = QE_TABLE[(state >> 1) as usize];
+            let mps = state & 1;
+            self.a -= qe;
+            if bit == mps {
+                if self.a & 0x8000 != 0 {
+                    self.c += qe;
+                    return;
+                }
+                if self.a < qe {
+                    self.a = qe;
+                } else {
+                    self.c += qe;
+                }
+                contexts[index] = (next_mps << 1) | mps;
+            } else {
+                if self.a < qe {
+                    self.c += qe;
+                } else {
+                    self.a = qe;
+                }
+                let mps = if switch { 1 - mps } else { mps };
+                contexts[index] = (next_lps << 1) | mps;
+            }
+            while self.a & 0x8000 == 0 {
+                self.a <<= 1;
+                self.c <<= 1;
+                self.ct -= 1;
+                if self.ct == 0 {
+                    self.byte_out();
+                }
+            }
+        }
+
+        fn byte_out(&mut self) {
+            let last = self.output.last_mut().unwrap();
+            if *last == 0xFF {
+                self.output.push((self.c >> 20) as u8);
+                self.c &= 0xFFFFF;
+                self.ct = 7;
+            } else if self.c < 0x8000000 {
+                self.output.push((self.c >> 19) as u8);
+                self.c &= 0x7FFFF;
+                self.ct = 8;
+            } else {
+                *last += 1;
+                if *last == 0xFF {
+                    self.c &= 0x7FFFFFF;
+                    self.output.push((self.c >> 20) as u8);
+                    self.c &= 0xFFFFF;
+                    self.ct = 7;
+                } else {
+                    self.output.push((self.c >> 19) as u8);
+                    self.c &= 0x7FFFF;
+                    self.ct = 8;
+                }
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            let sum = self.c + self.a;
+            self.c |= 0xFFFF;
+            if self.c >= sum {
+                self.c -= 0x8000;
+            }
+            self.c <<= self.ct;
+            self.byte_out();
+            self.c <<= self.ct;
+            self.byte_out();
+            if self.output.last() != Some(&0xFF) {
+                self.output.push(0xFF);
+            }
+            self.output.push(0xAC);
+            self.output.remove(0);
+            self.output
+        }
+    }
+
+    fn segment(number: u32, kind: u8, referred: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut segment = number.to_be_bytes().to_vec();
+        segment.push(kind);
+        segment.push((referred.len() as u8) << 5);
+        segment.extend_from_slice(referred);
+        segment.push(1);
+        segment.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    fn page_info(width: u32, height: u32) -> Vec<u8> {
+        let mut data = width.to_be_bytes().to_vec();
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[0, 0, 0]);
+        segment(0, 48, &[], &data)
+    }
+
+    fn region_info(width: u32, height: u32, x: u32, y: u32) -> Vec<u8> {
+        [width, height, x, y]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .chain([0])
+            .collect()
+    }
+
+    #[test]
+    fn test_arithmetic_decoder() {
+        // The test sequence of T.88, sec. H.2, coded with a single context.
+        let coded = [
+            0x84, 0xC7, 0x3B, 0xFC, 0xE1, 0xA1, 0x43, 0x04, 0x02, 0x20, 0x00, 0x00, 0x41, 0x0D,
+            0xBB, 0x86, 0xF4, 0x31, 0x7F, 0xFF, 0x88, 0xFF, 0x37, 0x47, 0x1A, 0xDB, 0x6A, 0xDF,
+            0xFF, 0xAC,
+        ];
+        let expected = [
+            0x00, 0x02, 0x00, 0x51, 0x00, 0x00, 0x00, 0xC0, 0x03, 0x52, 0x87, 0x2A, 0xAA, 0xAA,
+            0xAA, 0xAA, 0x82, 0xC0, 0x20, 0x00, 0xFC, 0xD7, 0x9E, 0xF6, 0xBF, 0x7F, 0xED, 0x90,
+            0x4F, 0x46, 0xA3, 0xBF,
+        ];
+        let mut decoder = ArithmeticDecoder::new(&coded);
+        let mut contexts = [0];
+        let decoded: Vec<u8> = (0..expected.len())
+            .map(|_| {
+                (0..8).fold(0, |byte, _| {
+                    (byte << 1) | decoder.read_bit(&mut contexts, 0)
+                })
+            })
+            .collect();
+        assert_eq!(decoded, expected);
+
+        let mut encoder = ArithmeticEncoder::new();
+        let mut contexts = [0];
+        for byte in expected {
+            for i in (0..8).rev() {
+                encoder.encode(&mut contexts, 0, (byte >> i) & 1);
+            }
+        }
+        assert_eq!(encoder.finish(), coded);
+    }
+
+    #[test]
+    fn test_generic_region() {
+        // A 20x12 ring with a repeated row, coded with typical prediction.
+        let (width, height) = (20, 12);
+        let mut image = Raster::new(width, height, 0).unwrap();
+        for y in 2..10 {
+            for x in 3..17 {
+                let inside = (5..7).contains(&y) || (y < 8 && !(5..=14).contains(&x));
+                image.set(x, y, inside as u8);
+            }
+        }
+        let template = generic_template(0, &[]);
+        let mut encoder = ArithmeticEncoder::new();
+        let mut contexts = vec![0; 1 << 16];
+        for y in 0..height {
+            let typical = y > 0
+                && image.pixels[(y - 1) * width..y * width]
+                    == image.pixels[y * width..(y + 1) * width];
+            let previous = y > 1
+                && image.pixels[(y - 2) * width..(y - 1) * width]
+                    == image.pixels[(y - 1) * width..y * width];
+            encoder.encode(
+                &mut contexts,
+                template.typical_context,
+                (typical != previous) as u8,
+            );
+            if typical {
+                continue;
+            }
+            for x in 0..width {
+                let context = template.context(&image, x as i64, y as i64);
+                encoder.encode(&mut contexts, context, image.get(x as i64, y as i64));
+            }
+        }
+        let coded = encoder.finish();
+
+        let mut data = region_info(width as u32, height as u32, 2, 1);
+        // Template 0 with typical prediction, and the default AT pixels.
+        data.extend_from_slice(&[0x08, 3, 0xFF, 0xFD, 0xFF, 2, 0xFE, 0xFE, 0xFE]);
+        data.extend_from_slice(&coded);
+        let mut stream = page_info(24, 14);
+        stream.extend(segment(1, 38, &[], &data));
+
+        let output = jbig2_decode(&stream, None).unwrap();
+        assert_eq!(output.len(), 3 * 14);
+        for y in 0..14 {
+            for x in 0..24 {
+                let bit = (output[y * 3 + x / 8] >> (7 - x % 8)) & 1;
+                let black = image.get(x as i64 - 2, y as i64 - 1) == 1;
+                assert_eq!(bit == 0, black, "pixel ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mmr_region() {
+        // Two rows with black pixels 2 to 4, in Group 4 coding: H(2, 3) and V0
+        // for the first row, V0 three times for the second, then EOFB.
+        let mut data = region_info(8, 2, 0, 0);
+        data.extend_from_slice(&[0x01, 0x2F, 0x78, 0x01, 0x00, 0x10]);
+        let mut stream = page_info(8, 2);
+        stream.extend(segment(1, 39, &[], &data));
+        assert_eq!(
+            jbig2_decode(&stream, None),
+            Ok(vec![0b1100_0111, 0b1100_0111])
+        );
+        assert!(jbig2_decode(&stream[..10], None).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_segments() {
+        let mut stream = page_info(8, 2);
+        stream.extend(segment(1, 16, &[], &[0; 8]));
+        assert!(matches!(
+            jbig2_decode(&stream, None),
+            Err(FilterError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_page_too_large() {
+        // A page of a million by a million pixels is refused before anything
+        // is allocated for it, as is a page of unknown height growing past the
+        // limit through end-of-stripe segments.
+        let stream = page_info(1 << 20, 1 << 20);
+        assert!(matches!(
+            jbig2_decode(&stream, None),
+            Err(FilterError::Malformed(_))
+        ));
+        let mut stream = page_info(1 << 20, 0xFFFF_FFFF);
+        stream.extend(segment(1, 50, &[], &(1u32 << 20).to_be_bytes()));
+        assert!(matches!(
+            jbig2_decode(&stream, None),
+            Err(FilterError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,576 @@
+pub mod cff;
+pub mod encoding;
+pub mod metrics;
+pub mod truetype;
+pub mod type1;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use crate::api::{
+    document::Document,
+    font::{
+        cff::CffFont,
+        encoding::{glyph_name_to_unicode, BaseEncoding},
+        truetype::TrueTypeFont,
+        type1::Type1Font,
+    },
+    geometry::{Matrix, Path},
+    objects::{Dictionary, PdfObject},
+    read::cmap::read_cmap,
+};
+
+///
+/// A CMap maps character codes to CIDs (when used as the encoding of a Type0 font)
+/// or to Unicode values (when used as a /ToUnicode map).
+/// See sec. 9.7.5 (pg. 321) and 9.10.3 (pg. 349)
+///
+/// The codespace ranges determine how many bytes make up each character code in a
+/// string shown with the font.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CMap {
+    pub codespace_ranges: Vec<(Vec<u8>, Vec<u8>)>,
+    pub cid_ranges: Vec<CidRange>,
+    pub unicode: BTreeMap<u32, String>,
+    pub vertical: bool,
+}
+
+/// Maps the codes from low to high to consecutive CIDs starting at cid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CidRange {
+    pub low: u32,
+    pub high: u32,
+    pub cid: u32,
+}
+
+impl CMap {
+    /// Reads the next character code from a string, returning the code and the
+    /// number of bytes it occupies. A code matches a codespace range when each of
+    /// its bytes lies between the corresponding bytes of the range.
+    pub fn next_code(&self, bytes: &[u8]) -> (u32, usize) {
+        for length in 1..=4.min(bytes.len()) {
+            let candidate = &bytes[..length];
+            let matches = self.codespace_ranges.iter().any(|(low, high)| {
+                low.len() == length
+                    && candidate
+                        .iter()
+                        .zip(low.iter().zip(high))
+                        .all(|(b, (l, h))| l <= b && b <= h)
+            });
+            if matches {
+                return (code_from_bytes(candidate), length);
+            }
+        }
+        // Without a matching range, fall back to the shortest declared length.
+        let length = self
+            .codespace_ranges
+            .iter()
+            .map(|(low, _)| low.len())
+            .min()
+            .unwrap_or(1)
+            .clamp(1, bytes.len().max(1));
+        (code_from_bytes(&bytes[..length.min(bytes.len())]), length)
+    }
+
+    pub fn cid(&self, code: u32) -> Option<u32> {
+        self.cid_ranges
+            .iter()
+            .rev()
+            .find(|range| range.low <= code && code <= range.high)
+            .map(|range| range.cid + (code - range.low))
+    }
+
+    pub fn to_unicode(&self, code: u32) -> Option<&str> {
+        self.unicode.get(&code).map(String::as_str)
+    }
+}
+
+fn code_from_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |code, &b| (code << 8) | b as u32)
+}
+
+///
+/// The kinds of fonts defined in the PDF standard. See sec. 9.5, Table 108 (pg. 298)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontType {
+    Type0,
+    Type1,
+    MMType1,
+    Type3,
+    TrueType,
+    CIDFontType0,
+    CIDFontType2,
+}
+
+impl FontType {
+    pub fn from_name(name: &str) -> FontType {
+        match name {
+            "Type0" => FontType::Type0,
+            "MMType1" => FontType::MMType1,
+            "Type3" => FontType::Type3,
+            "TrueType" => FontType::TrueType,
+            "CIDFontType0" => FontType::CIDFontType0,
+            "CIDFontType2" => FontType::CIDFontType2,
+            _ => FontType::Type1,
+        }
+    }
+}
+
+///
+/// A font program embedded in the PDF file, from the /FontFile, /FontFile2 or
+/// /FontFile3 entry of the font descriptor. See sec. 9.9, Table 124 (pg. 342)
+///
+#[derive(Debug, Clone)]
+pub enum FontProgram {
+    Type1(Type1Font),
+    TrueType(TrueTypeFont),
+    Cff(CffFont),
+}
+
+impl FontProgram {
+    pub fn load(doc: &Document, descriptor: &Dictionary) -> Option<FontProgram> {
+        let decode = |key: &str| {
+            let stream = doc.resolve_key(descriptor, key)?.as_stream()?;
+            Some((
+                doc.decode_stream(stream).ok()?,
+                stream.dict.get_name("Subtype"),
+            ))
+        };
+        if let Some((data, _)) = decode("FontFile") {
+            return Type1Font::parse(&data).map(FontProgram::Type1);
+        }
+        if let Some((data, _)) = decode("FontFile2") {
+            return TrueTypeFont::parse(data).map(FontProgram::TrueType);
+        }
+        match decode("FontFile3")? {
+            (data, Some("OpenType")) => TrueTypeFont::parse(data).map(FontProgram::TrueType),
+            (data, _) => CffFont::parse(data).map(FontProgram::Cff),
+        }
+    }
+}
+
+///
+/// A font as needed to show text: how strings split into character codes, the
+/// width of each glyph, and what Unicode text each code represents.
+///
+/// Simple fonts (Type1, TrueType, Type3) use single-byte codes and an /Encoding
+/// that gives each code a glyph name. Composite (Type0) fonts use a CMap to split
+/// strings into codes of one or more bytes, which are mapped to CIDs and looked up
+/// in the descendant CIDFont. See sec. 9.6 (pg. 301) and 9.7 (pg. 313)
+///
+#[derive(Debug, Clone)]
+pub struct Font {
+    pub font_type: FontType,
+    pub base_font: String,
+    pub dict: Dictionary,
+    /// The /FontDescriptor of the font, or of the descendant font for Type0 fonts.
+    pub descriptor: Option<Dictionary>,
+    /// Maps glyph space to text space. This is a scale of 1/1000 for every font
+    /// type except Type3, which gives its own /FontMatrix.
+    pub font_matrix: Matrix,
+    /// Glyph names for each single-byte code of a simple font, after applying the
+    /// /Differences of the encoding dictionary.
+    pub glyph_names: HashMap<u32, String>,
+    base_encoding: Option<BaseEncoding>,
+    widths: HashMap<u32, f64>,
+    default_width: f64,
+    encoding_cmap: Option<CMap>,
+    to_unicode: Option<CMap>,
+    /// The embedded font program that holds the glyph outlines.
+    pub program: Option<Rc<FontProgram>>,
+    /// Maps CIDs to glyph indices for CIDFontType2 fonts; None for /Identity.
+    /// See sec. 9.7.4.2, Table 115 (pg. 318)
+    cid_to_gid: Option<Vec<u16>>,
+}
+
+impl Font {
+    pub fn load(doc: &Document, dict: &Dictionary) -> Font {
+        let font_type = FontType::from_name(dict.get_name("Subtype").unwrap_or("Type1"));
+        let base_font = dict.get_name("BaseFont").unwrap_or_default().to_string();
+        let to_unicode = doc
+            .resolve_key(dict, "ToUnicode")
+            .and_then(PdfObject::as_stream)
+            .and_then(|stream| stream.decode().ok())
+            .map(|data| read_cmap(&data));
+
+        let mut font = Font {
+            font_type,
+            base_font,
+            dict: dict.clone(),
+            descriptor: doc.resolve_dict(dict, "FontDescriptor").cloned(),
+            font_matrix: Matrix::scale(0.001, 0.001),
+            glyph_names: HashMap::new(),
+            base_encoding: None,
+            widths: HashMap::new(),
+            default_width: 0.0,
+            encoding_cmap: None,
+            to_unicode,
+            program: None,
+            cid_to_gid: None,
+        };
+
+        if font_type == FontType::Type0 {
+            font.load_composite(doc, dict);
+        } else {
+            font.load_simple(doc, dict);
+        }
+        font.program = font
+            .descriptor
+            .as_ref()
+            .and_then(|descriptor| FontProgram::load(doc, descriptor))
+            .map(Rc::new);
+        font
+    }
+
+    /// Simple fonts list their widths in /Widths, starting at /FirstChar.
+    /// See sec. 9.6.2 (pg. 301)
+    fn load_simple(&mut self, doc: &Document, dict: &Dictionary) {
+        if self.font_type == FontType::Type3 {
+            if let Some(matrix) = dict
+                .get("FontMatrix")
+                .and_then(|m| m.as_numbers())
+                .and_then(|m| Matrix::from_slice(&m))
+            {
+                self.font_matrix = matrix;
+            }
+        }
+
+        let first_char = dict.get_i64("FirstChar").unwrap_or(0).max(0) as u32;
+        if let Some(PdfObject::Array(widths)) = doc.resolve_key(dict, "Widths") {
+            for (i, width) in widths.iter().enumerate() {
+                if let Some(width) = doc.resolve(width).as_f64() {
+                    self.widths.insert(first_char + i as u32, width);
+                }
+            }
+        }
+        self.default_width = self
+            .descriptor
+            .as_ref()
+            .and_then(|d| d.get_f64("MissingWidth"))
+            .unwrap_or(0.0);
+
+        // Symbolic fonts use their built-in encoding; otherwise Standard is assumed.
+        // See sec. 9.6.5 (pg. 308)
+        let symbolic = self
+            .descriptor
+            .as_ref()
+            .and_then(|d| d.get_i64("Flags"))
+            .map(|flags| flags & 4 != 0)
+            .unwrap_or(false);
+        self.base_encoding = if symbolic {
+            None
+        } else {
+            Some(BaseEncoding::Standard)
+        };
+
+        match doc.resolve_key(dict, "Encoding") {
+            Some(PdfObject::Name(name)) => {
+                if let Some(base) = BaseEncoding::from_name(name) {
+                    self.base_encoding = Some(base);
+                }
+            }
+            Some(PdfObject::Dictionary(encoding)) => {
+                if let Some(base) = encoding
+                    .get_name("BaseEncoding")
+                    .and_then(BaseEncoding::from_name)
+                {
+                    self.base_encoding = Some(base);
+                }
+                self.read_differences(encoding);
+            }
+            _ => {}
+        }
+    }
+
+    /// /Differences is an array of codes, each followed by the glyph names for
+    /// consecutive codes starting at that code. See sec. 9.6.5.1 (pg. 309)
+    fn read_differences(&mut self, encoding: &Dictionary) {
+        let Some(differences) = encoding.get("Differences").and_then(|d| d.as_array()) else {
+            return;
+        };
+        let mut code = 0u32;
+        for item in differences {
+            match item {
+                PdfObject::Integer(start) => code = *start as u32,
+                PdfObject::Name(name) => {
+                    self.glyph_names.insert(code, name.clone());
+                    code += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Type0 fonts take their widths from the descendant CIDFont: /W gives widths
+    /// per CID and /DW the default. See sec. 9.7.4.3 (pg. 320)
+    fn load_composite(&mut self, doc: &Document, dict: &Dictionary) {
+        match doc.resolve_key(dict, "Encoding") {
+            Some(PdfObject::Stream(stream)) => {
+                self.encoding_cmap = stream.decode().ok().map(|data| read_cmap(&data));
+            }
+            // Identity-H, Identity-V and other predefined CMaps are read as
+            // two-byte codes mapping directly to CIDs.
+            _ => self.encoding_cmap = None,
+        }
+
+        let descendant = doc
+            .resolve_key(dict, "DescendantFonts")
+            .and_then(|d| d.as_array())
+            .and_then(|fonts| fonts.first())
+            .and_then(|font| doc.resolve(font).as_dict());
+        let Some(descendant) = descendant else {
+            return;
+        };
+        self.descriptor = doc.resolve_dict(descendant, "FontDescriptor").cloned();
+        self.default_width = descendant.get_f64("DW").unwrap_or(1000.0);
+        if let Some(PdfObject::Stream(map)) = doc.resolve_key(descendant, "CIDToGIDMap") {
+            self.cid_to_gid = doc.decode_stream(map).ok().map(|data| {
+                data.chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect()
+            });
+        }
+
+        let Some(PdfObject::Array(w)) = doc.resolve_key(descendant, "W") else {
+            return;
+        };
+        // Entries are either "c [w1 w2 ...]" or "c_first c_last w".
+        let mut i = 0;
+        while i < w.len() {
+            let first = doc.resolve(&w[i]).as_i64().unwrap_or(0) as u32;
+            match w.get(i + 1).map(|next| doc.resolve(next)) {
+                Some(PdfObject::Array(widths)) => {
+                    for (j, width) in widths.iter().enumerate() {
+                        if let Some(width) = doc.resolve(width).as_f64() {
+                            self.widths.insert(first + j as u32, width);
+                        }
+                    }
+                    i += 2;
+                }
+                Some(last) => {
+                    let last = last.as_i64().unwrap_or(0) as u32;
+                    let width = w
+                        .get(i + 2)
+                        .and_then(|w| doc.resolve(w).as_f64())
+                        .unwrap_or(0.0);
+                    for cid in first..=last.min(first + 0xFFFF) {
+                        self.widths.insert(cid, width);
+                    }
+                    i += 3;
+                }
+                None => break,
+            }
+        }
+    }
+
+    pub fn is_composite(&self) -> bool {
+        self.font_type == FontType::Type0
+    }
+
+    /// Splits a string shown with this font into character codes. Each code is
+    /// returned with the number of bytes it was read from, since word spacing only
+    /// applies to the single-byte code 32. See sec. 9.3.3 (pg. 289)
+    pub fn codes(&self, bytes: &[u8]) -> Vec<(u32, usize)> {
+        if !self.is_composite() {
+            return bytes.iter().map(|&b| (b as u32, 1)).collect();
+        }
+        let mut codes = vec![];
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let (code, length) = match &self.encoding_cmap {
+                Some(cmap) => cmap.next_code(rest),
+                None if rest.len() >= 2 => (code_from_bytes(&rest[..2]), 2),
+                None => (rest[0] as u32, 1),
+            };
+            codes.push((code, length));
+            rest = &rest[length.max(1).min(rest.len())..];
+        }
+        codes
+    }
+
+    /// The CID selected by a character code of a composite font. For simple fonts
+    /// the code itself is returned.
+    pub fn cid(&self, code: u32) -> u32 {
+        match &self.encoding_cmap {
+            Some(cmap) if self.is_composite() => cmap.cid(code).unwrap_or(code),
+            _ => code,
+        }
+    }
+
+    /// The horizontal displacement of a glyph in text space, before scaling by the
+    /// font size. See sec. 9.2.4 (pg. 286)
+    pub fn width(&self, code: u32) -> f64 {
+        let key = self.cid(code);
+        let width = self.widths.get(&key).copied().or_else(|| {
+            if self.widths.is_empty() && !self.is_composite() {
+                let c = self.to_unicode(code)?.chars().next()?;
+                metrics::standard_width(&self.base_font, c)
+            } else {
+                None
+            }
+        });
+        let width = width.unwrap_or(self.default_width);
+        width * self.font_matrix.a
+    }
+
+    /// The glyph name of a code in a simple font, from /Differences or the base
+    /// encoding.
+    pub fn glyph_name(&self, code: u32) -> Option<String> {
+        if let Some(name) = self.glyph_names.get(&code) {
+            return Some(name.clone());
+        }
+        let code = u8::try_from(code).ok()?;
+        self.base_encoding?.glyph_name(code)
+    }
+
+    fn is_symbolic(&self) -> bool {
+        self.descriptor
+            .as_ref()
+            .and_then(|d| d.get_i64("Flags"))
+            .is_some_and(|flags| flags & 4 != 0)
+    }
+
+    /// The glyph name a code of a simple font is given by /Encoding. Without
+    /// an /Encoding entry, the font program's built-in encoding applies.
+    fn encoded_glyph_name(&self, code: u32) -> Option<String> {
+        if let Some(name) = self.glyph_names.get(&code) {
+            return Some(name.clone());
+        }
+        self.dict.get("Encoding")?;
+        self.base_encoding?.glyph_name(u8::try_from(code).ok()?)
+    }
+
+    /// The outline of the glyph for a character code, in glyph space: the font
+    /// matrix maps it to text space. Glyphs are selected as described in
+    /// sec. 9.6.5 (pg. 308) for simple fonts and sec. 9.7.4.2 (pg. 318) for
+    /// CIDFonts. Returns None without an embedded font program; Type3 glyphs
+    /// are content streams and have no outline.
+    pub fn glyph_outline(&self, code: u32) -> Option<Path> {
+        match self.program.as_deref()? {
+            FontProgram::Type1(font) => {
+                let code = u8::try_from(code).ok()?;
+                let name = self
+                    .encoded_glyph_name(code as u32)
+                    .filter(|name| font.has_glyph(name))
+                    .or_else(|| font.glyph_name(code))
+                    .filter(|name| font.has_glyph(name))
+                    .unwrap_or_else(|| ".notdef".to_string());
+                font.glyph_outline(&name)
+            }
+            FontProgram::Cff(font) => font.glyph_outline(self.cff_glyph(font, code)?),
+            FontProgram::TrueType(font) => {
+                let gid = match font.cff() {
+                    Some(cff) if self.is_composite() => self.cff_glyph(cff, code)?,
+                    _ => self.truetype_glyph(font, code)?,
+                };
+                font.glyph_outline(gid)
+            }
+        }
+    }
+
+    fn cff_glyph(&self, font: &CffFont, code: u32) -> Option<u16> {
+        if self.is_composite() {
+            return font.glyph_for_cid(self.cid(code));
+        }
+        let code = u8::try_from(code).ok()?;
+        self.encoded_glyph_name(code as u32)
+            .and_then(|name| font.glyph_for_name(&name))
+            .or_else(|| font.glyph_for_code(code))
+    }
+
+    /// TrueType glyphs are found through the font's 'cmap' table, or for
+    /// CIDFontType2 fonts through /CIDToGIDMap. See sec. 9.6.5.4 (pg. 311)
+    fn truetype_glyph(&self, font: &TrueTypeFont, code: u32) -> Option<u16> {
+        if self.is_composite() {
+            let cid = self.cid(code);
+            return match &self.cid_to_gid {
+                Some(map) => map.get(cid as usize).copied(),
+                None => u16::try_from(cid).ok(),
+            };
+        }
+        if !self.is_symbolic() && font.has_cmap(3, 1) {
+            let unicode = self
+                .encoded_glyph_name(code)
+                .and_then(|name| glyph_name_to_unicode(&name));
+            if let Some(gid) = unicode.and_then(|c| font.lookup(3, 1, c as u32)) {
+                return Some(gid);
+            }
+        }
+        // Symbolic fonts map codes into one of the ranges starting at 0xF000,
+        // 0xF100 or 0xF200 of the (3, 0) subtable.
+        [0, 0xF000, 0xF100, 0xF200]
+            .iter()
+            .find_map(|base| font.lookup(3, 0, base + code))
+            .or_else(|| font.lookup(1, 0, code))
+            .or_else(|| font.lookup(3, 1, code))
+            .or_else(|| u16::try_from(code).ok())
+    }
+
+    /// The text a character code represents. The /ToUnicode CMap takes precedence;
+    /// simple fonts otherwise fall back to their encoding's glyph names.
+    /// See sec. 9.10.2 (pg. 347)
+    pub fn to_unicode(&self, code: u32) -> Option<String> {
+        if let Some(unicode) = self
+            .to_unicode
+            .as_ref()
+            .and_then(|cmap| cmap.to_unicode(code))
+        {
+            return Some(unicode.to_string());
+        }
+        if self.is_composite() {
+            return None;
+        }
+        if let Some(name) = self.glyph_names.get(&code) {
+            return glyph_name_to_unicode(name).map(String::from);
+        }
+        let byte = u8::try_from(code).ok()?;
+        match self.base_encoding {
+            Some(encoding) => encoding.to_unicode(byte).map(String::from),
+            None => (byte.is_ascii_graphic() || byte == b' ').then(|| (byte as char).to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_font() {
+        let doc = Document::load("HelloWorld.pdf").unwrap();
+        let dict = doc.get((9, 0)).unwrap().as_dict().unwrap();
+        let font = Font::load(&doc, dict);
+        assert_eq!(font.font_type, FontType::TrueType);
+        assert_eq!(font.to_unicode(1), Some("H".to_string()));
+        assert_eq!(font.width(1), 0.722);
+        assert_eq!(font.codes(&[1, 2]), vec![(1, 1), (2, 1)]);
+
+        // The embedded subset TrueType font has the outline of the "H".
+        assert!(matches!(
+            font.program.as_deref(),
+            Some(FontProgram::TrueType(_))
+        ));
+        let outline = font.glyph_outline(1).unwrap();
+        let [llx, lly, urx, ury] = outline.bounding_box().unwrap();
+        assert_eq!(lly, 0.0);
+        assert!(0.0 < llx && urx < 722.0);
+        assert!((600.0..750.0).contains(&ury));
+    }
+
+    #[test]
+    fn test_cmap_next_code() {
+        let cmap = CMap {
+            codespace_ranges: vec![
+                (vec![0x00], vec![0x80]),
+                (vec![0x81, 0x40], vec![0xFE, 0xFE]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(cmap.next_code(&[0x41, 0x81]), (0x41, 1));
+        assert_eq!(cmap.next_code(&[0x81, 0x41]), (0x8141, 2));
+    }
+}
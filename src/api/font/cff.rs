@@ -0,0 +1,1124 @@
+use std::{collections::BTreeSet, ops::Range};
+
+use crate::api::{
+    font::encoding::BaseEncoding,
+    geometry::{Matrix, Path, Point},
+};
+
+/// Subroutine calls nest at most this deep. See sec. 4.5 of Adobe Technical
+/// Note #5177
+const MAX_SUBR_DEPTH: usize = 10;
+
+/// The standard strings of Appendix A of Adobe Technical Note #5176, by SID.
+const STANDARD_STRINGS: &str = "
+    .notdef space exclam quotedbl numbersign dollar percent ampersand quoteright
+    parenleft parenright asterisk plus comma hyphen period slash zero one two three
+    four five six seven eight nine colon semicolon less equal greater question at A
+    B C D E F G H I J K L M N O P Q R S T U V W X Y Z bracketleft backslash
+    bracketright asciicircum underscore quoteleft a b c d e f g h i j k l m n o p q
+    r s t u v w x y z braceleft bar braceright asciitilde exclamdown cent sterling
+    fraction yen florin section currency quotesingle quotedblleft guillemotleft
+    guilsinglleft guilsinglright fi fl endash dagger daggerdbl periodcentered
+    paragraph bullet quotesinglbase quotedblbase quotedblright guillemotright
+    ellipsis perthousand questiondown grave acute circumflex tilde macron breve
+    dotaccent dieresis ring cedilla hungarumlaut ogonek caron emdash AE ordfeminine
+    Lslash Oslash OE ordmasculine ae dotlessi lslash oslash oe germandbls
+    onesuperior logicalnot mu trademark Eth onehalf plusminus Thorn onequarter
+    divide brokenbar degree thorn threequarters twosuperior registered minus eth
+    multiply threesuperior copyright Aacute Acircumflex Adieresis Agrave Aring
+    Atilde Ccedilla Eacute Ecircumflex Edieresis Egrave Iacute Icircumflex Idieresis
+    Igrave Ntilde Oacute Ocircumflex Odieresis Ograve Otilde Scaron Uacute
+    Ucircumflex Udieresis Ugrave Yacute Ydieresis Zcaron aacute acircumflex
+    adieresis agrave aring atilde ccedilla eacute ecircumflex edieresis egrave
+    iacute icircumflex idieresis igrave ntilde oacute ocircumflex odieresis ograve
+    otilde scaron uacute ucircumflex udieresis ugrave yacute ydieresis zcaron
+    exclamsmall Hungarumlautsmall dollaroldstyle dollarsuperior ampersandsmall
+    Acutesmall parenleftsuperior parenrightsuperior twodotenleader onedotenleader
+    zerooldstyle oneoldstyle twooldstyle threeoldstyle fouroldstyle fiveoldstyle
+    sixoldstyle sevenoldstyle eightoldstyle nineoldstyle commasuperior
+    threequartersemdash periodsuperior questionsmall asuperior bsuperior
+    centsuperior dsuperior esuperior isuperior lsuperior msuperior nsuperior
+    osuperior rsuperior ssuperior tsuperior ff ffi ffl parenleftinferior
+    parenrightinferior Circumflexsmall hyphensuperior Gravesmall Asmall Bsmall
+    Csmall Dsmall Esmall Fsmall Gsmall Hsmall Ismall Jsmall Ksmall Lsmall Msmall
+    Nsmall Osmall Psmall Qsmall Rsmall Ssmall Tsmall Usmall Vsmall Wsmall Xsmall
+    Ysmall Zsmall colonmonetary onefitted rupiah Tildesmall exclamdownsmall
+    centoldstyle Lslashsmall Scaronsmall Zcaronsmall Dieresissmall Brevesmall
+    Caronsmall Dotaccentsmall Macronsmall figuredash hypheninferior Ogoneksmall
+    Ringsmall Cedillasmall questiondownsmall oneeighth threeeighths fiveeighths
+    seveneighths onethird twothirds zerosuperior foursuperior fivesuperior
+    sixsuperior sevensuperior eightsuperior ninesuperior zeroinferior oneinferior
+    twoinferior threeinferior fourinferior fiveinferior sixinferior seveninferior
+    eightinferior nineinferior centinferior dollarinferior periodinferior
+    commainferior Agravesmall Aacutesmall Acircumflexsmall Atildesmall
+    Adieresissmall Aringsmall AEsmall Ccedillasmall Egravesmall Eacutesmall
+    Ecircumflexsmall Edieresissmall Igravesmall Iacutesmall Icircumflexsmall
+    Idieresissmall Ethsmall Ntildesmall Ogravesmall Oacutesmall Ocircumflexsmall
+    Otildesmall Odieresissmall OEsmall Oslashsmall Ugravesmall Uacutesmall
+    Ucircumflexsmall Udieresissmall Yacutesmall Thornsmall Ydieresissmall 001.000
+    001.001 001.002 001.003 Black Bold Book Light Medium Regular Roman Semibold";
+
+const STANDARD_STRING_COUNT: usize = 391;
+
+fn standard_string(sid: usize) -> Option<&'static str> {
+    STANDARD_STRINGS.split_whitespace().nth(sid)
+}
+
+/// Top DICT and Private DICT operators. See Table 9 and Table 23 of Adobe
+/// Technical Note #5176
+const CHARSET: u16 = 15;
+const ENCODING: u16 = 16;
+const CHAR_STRINGS: u16 = 17;
+const PRIVATE: u16 = 18;
+const SUBRS: u16 = 19;
+const FONT_MATRIX: u16 = 1207;
+const ROS: u16 = 1230;
+const FD_ARRAY: u16 = 1236;
+const FD_SELECT: u16 = 1237;
+
+///
+/// A Compact Font Format font program, as embedded with /FontFile3 /Type1C or
+/// /CIDFontType0C, or in the 'CFF ' table of an OpenType font. Glyphs are Type 2
+/// charstrings. See sec. 9.9 (pg. 341) and Adobe Technical Notes #5176 and
+/// #5177
+///
+/// Name-keyed fonts find glyphs by name through the charset; CID-keyed fonts
+/// find them by CID, with local subroutines chosen per glyph by FDSelect.
+///
+#[derive(Debug, Clone)]
+pub struct CffFont {
+    data: Vec<u8>,
+    strings: Vec<Range<usize>>,
+    global_subrs: Vec<Range<usize>>,
+    char_strings: Vec<Range<usize>>,
+    /// The local subroutines of each Font DICT; name-keyed fonts have one.
+    local_subrs: Vec<Vec<Range<usize>>>,
+    /// The Font DICT of each glyph, for CID-keyed fonts.
+    fd_select: Vec<u8>,
+    /// The SID (or CID, for CID-keyed fonts) of each glyph.
+    charset: Vec<u16>,
+    /// The glyph for each code of a custom built-in encoding; None if the font
+    /// uses the standard encoding.
+    encoding: Option<Vec<u16>>,
+    pub is_cid: bool,
+    pub font_matrix: Matrix,
+}
+
+/// Reads an INDEX, returning the range of each item and the end of the INDEX.
+fn read_index(data: &[u8], start: usize) -> Option<(Vec<Range<usize>>, usize)> {
+    let count = u16::from_be_bytes(data.get(start..start + 2)?.try_into().ok()?) as usize;
+    if count == 0 {
+        return Some((vec![], start + 2));
+    }
+    let offset_size = *data.get(start + 2)? as usize;
+    if !(1..=4).contains(&offset_size) {
+        return None;
+    }
+    let offsets_start = start + 3;
+    let offsets: Vec<usize> = (0..=count)
+        .map(|i| {
+            let at = offsets_start + i * offset_size;
+            let bytes = data.get(at..at + offset_size)?;
+            Some(bytes.iter().fold(0usize, |v, &b| (v << 8) | b as usize))
+        })
+        .collect::<Option<_>>()?;
+    let base = offsets_start + (count + 1) * offset_size - 1;
+    let items = offsets
+        .windows(2)
+        .map(|w| (base + w[0]).min(data.len())..(base + w[1]).min(data.len()))
+        .collect();
+    Some((items, base + offsets[count]))
+}
+
+/// Reads a DICT into its operators and operands. Two-byte operators are
+/// numbered 1200 + their second byte.
+fn read_dict(data: &[u8]) -> Vec<(u16, Vec<f64>)> {
+    let mut entries = vec![];
+    let mut operands = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let b0 = data[i];
+        i += 1;
+        match b0 {
+            0..=21 => {
+                let operator = if b0 == 12 {
+                    i += 1;
+                    1200 + *data.get(i - 1).unwrap_or(&0) as u16
+                } else {
+                    b0 as u16
+                };
+                entries.push((operator, std::mem::take(&mut operands)));
+            }
+            28 => {
+                let Some(bytes) = data.get(i..i + 2) else {
+                    break;
+                };
+                operands.push(i16::from_be_bytes([bytes[0], bytes[1]]) as f64);
+                i += 2;
+            }
+            29 => {
+                let Some(bytes) = data.get(i..i + 4) else {
+                    break;
+                };
+                operands.push(i32::from_be_bytes(bytes.try_into().unwrap()) as f64);
+                i += 4;
+            }
+            30 => {
+                let mut text = String::new();
+                'real: while let Some(&byte) = data.get(i) {
+                    i += 1;
+                    for nibble in [byte >> 4, byte & 0xF] {
+                        match nibble {
+                            0..=9 => text.push((b'0' + nibble) as char),
+                            0xA => text.push('.'),
+                            0xB => text.push('E'),
+                            0xC => text.push_str("E-"),
+                            0xE => text.push('-'),
+                            0xF => break 'real,
+                            _ => {}
+                        }
+                    }
+                }
+                operands.push(text.parse().unwrap_or(0.0));
+            }
+            32..=246 => operands.push(b0 as f64 - 139.0),
+            247..=250 => {
+                let Some(&b1) = data.get(i) else { break };
+                i += 1;
+                operands.push((b0 as f64 - 247.0) * 256.0 + b1 as f64 + 108.0);
+            }
+            251..=254 => {
+                let Some(&b1) = data.get(i) else { break };
+                i += 1;
+                operands.push(-(b0 as f64 - 251.0) * 256.0 - b1 as f64 - 108.0);
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Writes an INDEX with offsets of the smallest size that fits.
+fn write_index(items: &[&[u8]]) -> Vec<u8> {
+    let mut index = (items.len() as u16).to_be_bytes().to_vec();
+    if items.is_empty() {
+        return index;
+    }
+    let end = 1 + items.iter().map(|item| item.len()).sum::<usize>();
+    let offset_size = (1..4).find(|&size| end < 1 << (8 * size)).unwrap_or(4);
+    index.push(offset_size as u8);
+    let mut offset = 1;
+    for item in items.iter().map(|item| item.len()).chain([0]) {
+        index.extend(&(offset as u32).to_be_bytes()[4 - offset_size..]);
+        offset += item;
+    }
+    for item in items {
+        index.extend(*item);
+    }
+    index
+}
+
+/// Writes a DICT. The operands of the `wide` operators are written as 5-byte
+/// integers, so that their size does not depend on their value.
+fn write_dict(dict: &[(u16, Vec<f64>)], wide: &[u16]) -> Vec<u8> {
+    let mut data = vec![];
+    for (operator, operands) in dict {
+        for &value in operands {
+            let integer = value as i32;
+            if wide.contains(operator) {
+                data.push(29);
+                data.extend(integer.to_be_bytes());
+            } else if integer as f64 != value {
+                data.push(30);
+                let mut nibbles: Vec<u8> = value
+                    .to_string()
+                    .bytes()
+                    .map(|b| match b {
+                        b'.' => 0xA,
+                        b'-' => 0xE,
+                        _ => b - b'0',
+                    })
+                    .collect();
+                nibbles.push(0xF);
+                if nibbles.len() % 2 == 1 {
+                    nibbles.push(0xF);
+                }
+                data.extend(nibbles.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+            } else if (-107..=107).contains(&integer) {
+                data.push((integer + 139) as u8);
+            } else if (108..=1131).contains(&integer) {
+                let v = integer - 108;
+                data.extend([(v >> 8) as u8 + 247, v as u8]);
+            } else if (-1131..=-108).contains(&integer) {
+                let v = -integer - 108;
+                data.extend([(v >> 8) as u8 + 251, v as u8]);
+            } else if let Ok(short) = i16::try_from(integer) {
+                data.push(28);
+                data.extend(short.to_be_bytes());
+            } else {
+                data.push(29);
+                data.extend(integer.to_be_bytes());
+            }
+        }
+        match operator {
+            1200.. => data.extend([12, (operator - 1200) as u8]),
+            _ => data.push(*operator as u8),
+        }
+    }
+    data
+}
+
+fn dict_value(dict: &[(u16, Vec<f64>)], operator: u16) -> Option<&[f64]> {
+    dict.iter()
+        .find(|(op, _)| *op == operator)
+        .map(|(_, operands)| operands.as_slice())
+}
+
+fn dict_offset(dict: &[(u16, Vec<f64>)], operator: u16) -> Option<usize> {
+    dict_value(dict, operator)?
+        .first()
+        .map(|&v| v.max(0.0) as usize)
+}
+
+impl CffFont {
+    /// Reads the first font of a CFF font set.
+    pub fn parse(data: Vec<u8>) -> Option<CffFont> {
+        let header_size = *data.get(2)? as usize;
+        let (_, names_end) = read_index(&data, header_size)?;
+        let (top_dicts, top_end) = read_index(&data, names_end)?;
+        let (strings, strings_end) = read_index(&data, top_end)?;
+        let (global_subrs, _) = read_index(&data, strings_end)?;
+        let top = read_dict(data.get(top_dicts.first()?.clone())?);
+
+        let (char_strings, _) = read_index(&data, dict_offset(&top, CHAR_STRINGS)?)?;
+        let glyphs = char_strings.len();
+        let is_cid = dict_value(&top, ROS).is_some();
+        let font_matrix = dict_value(&top, FONT_MATRIX)
+            .and_then(Matrix::from_slice)
+            .unwrap_or(Matrix::scale(0.001, 0.001));
+
+        let mut font = CffFont {
+            data,
+            strings,
+            global_subrs,
+            char_strings,
+            local_subrs: vec![],
+            fd_select: vec![],
+            charset: vec![],
+            encoding: None,
+            is_cid,
+            font_matrix,
+        };
+        if is_cid {
+            if let Some((fonts, _)) =
+                dict_offset(&top, FD_ARRAY).and_then(|offset| read_index(&font.data, offset))
+            {
+                for range in fonts {
+                    let dict = read_dict(&font.data[range]);
+                    let subrs = font.private_subrs(&dict).unwrap_or_default();
+                    font.local_subrs.push(subrs);
+                }
+            }
+            font.fd_select = dict_offset(&top, FD_SELECT)
+                .and_then(|offset| font.read_fd_select(offset, glyphs))
+                .unwrap_or_default();
+        } else {
+            let subrs = font.private_subrs(&top).unwrap_or_default();
+            font.local_subrs.push(subrs);
+        }
+        font.charset = match dict_offset(&top, CHARSET).unwrap_or(0) {
+            // ISOAdobe maps glyphs to SIDs in order. The Expert charsets are not
+            // built in, and are approximated the same way.
+            0..=2 => (0..glyphs as u16).collect(),
+            offset => font.read_charset(offset, glyphs).unwrap_or_default(),
+        };
+        if !is_cid {
+            font.encoding = match dict_offset(&top, ENCODING).unwrap_or(0) {
+                0 | 1 => None,
+                offset => font.read_encoding(offset),
+            };
+        }
+        Some(font)
+    }
+
+    /// Reads the local subroutines of the Private DICT referenced by a Top or
+    /// Font DICT. The Subrs offset is relative to the Private DICT.
+    fn private_subrs(&self, dict: &[(u16, Vec<f64>)]) -> Option<Vec<Range<usize>>> {
+        let private = dict_value(dict, PRIVATE)?;
+        let (size, offset) = (*private.first()? as usize, *private.get(1)? as usize);
+        let private_dict = read_dict(self.data.get(offset..offset + size)?);
+        let subrs = dict_offset(&private_dict, SUBRS)?;
+        read_index(&self.data, offset + subrs).map(|(subrs, _)| subrs)
+    }
+
+    fn read_fd_select(&self, offset: usize, glyphs: usize) -> Option<Vec<u8>> {
+        let data = &self.data;
+        let u16_at = |at: usize| Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]));
+        match *data.get(offset)? {
+            0 => Some(data.get(offset + 1..offset + 1 + glyphs)?.to_vec()),
+            3 => {
+                let ranges = u16_at(offset + 1)? as usize;
+                let mut select = vec![0; glyphs];
+                for i in 0..ranges {
+                    let at = offset + 3 + i * 3;
+                    let first = u16_at(at)? as usize;
+                    let fd = *data.get(at + 2)?;
+                    let end = (u16_at(at + 3)? as usize).min(glyphs);
+                    for entry in select.iter_mut().take(end).skip(first) {
+                        *entry = fd;
+                    }
+                }
+                Some(select)
+            }
+            _ => None,
+        }
+    }
+
+    /// Charsets list the SID or CID of each glyph after .notdef, individually
+    /// (format 0) or in ranges (formats 1 and 2).
+    fn read_charset(&self, offset: usize, glyphs: usize) -> Option<Vec<u16>> {
+        let data = &self.data;
+        let u16_at = |at: usize| Some(u16::from_be_bytes([*data.get(at)?, *data.get(at + 1)?]));
+        let format = *data.get(offset)?;
+        let mut charset = vec![0];
+        let mut at = offset + 1;
+        while charset.len() < glyphs {
+            match format {
+                0 => {
+                    charset.push(u16_at(at)?);
+                    at += 2;
+                }
+                1 | 2 => {
+                    let first = u16_at(at)?;
+                    let left = if format == 1 {
+                        *data.get(at + 2)? as u16
+                    } else {
+                        u16_at(at + 2)?
+                    };
+                    at += if format == 1 { 3 } else { 4 };
+                    for i in 0..=left {
+                        charset.push(first.wrapping_add(i));
+                    }
+                }
+                _ => return None,
+            }
+        }
+        charset.truncate(glyphs);
+        Some(charset)
+    }
+
+    /// Custom encodings assign codes to glyphs in order (format 0) or in ranges
+    /// (format 1), with supplements mapping further codes to glyph names.
+    fn read_encoding(&self, offset: usize) -> Option<Vec<u16>> {
+        let data = &self.data;
+        let format = *data.get(offset)?;
+        let count = *data.get(offset + 1)? as usize;
+        let mut encoding = vec![0u16; 256];
+        let mut at = offset + 2;
+        match format & 0x7F {
+            0 => {
+                for gid in 1..=count {
+                    encoding[*data.get(at)? as usize] = gid as u16;
+                    at += 1;
+                }
+            }
+            1 => {
+                let mut gid = 1;
+                for _ in 0..count {
+                    let first = *data.get(at)? as usize;
+                    let left = *data.get(at + 1)? as usize;
+                    at += 2;
+                    for entry in encoding.iter_mut().skip(first).take(left + 1) {
+                        *entry = gid;
+                        gid += 1;
+                    }
+                }
+            }
+            _ => return None,
+        }
+        if format & 0x80 != 0 {
+            let supplements = *data.get(at)? as usize;
+            for i in 0..supplements {
+                let entry = at + 1 + i * 3;
+                let code = *data.get(entry)? as usize;
+                let sid = u16::from_be_bytes([*data.get(entry + 1)?, *data.get(entry + 2)?]);
+                if let Some(gid) = self.charset.iter().position(|&s| s == sid) {
+                    encoding[code] = gid as u16;
+                }
+            }
+        }
+        Some(encoding)
+    }
+
+    pub fn num_glyphs(&self) -> usize {
+        self.char_strings.len()
+    }
+
+    /// The CID of a glyph. Name-keyed fonts used as CIDFonts select glyphs by
+    /// index, so there the CID is the glyph index. See sec. 9.7.4.2 (pg. 318)
+    pub fn glyph_cid(&self, gid: u16) -> Option<u16> {
+        if gid as usize >= self.char_strings.len() {
+            return None;
+        }
+        match self.is_cid {
+            true => self.charset.get(gid as usize).copied(),
+            false => Some(gid),
+        }
+    }
+
+    /// Writes a copy of the font in which only the given glyphs (and .notdef)
+    /// keep their charstrings; the others are reduced to "endchar". Glyph
+    /// indices, the charset and the subroutines are unchanged, and the font's
+    /// built-in encoding is dropped.
+    pub fn subset(&self, glyphs: &BTreeSet<u16>) -> Option<Vec<u8>> {
+        let data = &self.data;
+        let header_size = *data.get(2)? as usize;
+        let (_, names_end) = read_index(data, header_size)?;
+        let (top_dicts, _) = read_index(data, names_end)?;
+        let mut top = read_dict(data.get(top_dicts.first()?.clone())?);
+        let fonts = match self.is_cid {
+            true => {
+                let (fonts, _) = read_index(data, dict_offset(&top, FD_ARRAY)?)?;
+                let fonts: Option<Vec<_>> = fonts
+                    .into_iter()
+                    .map(|range| Some(read_dict(data.get(range)?)))
+                    .collect();
+                fonts?
+            }
+            false => vec![top.clone()],
+        };
+
+        // The Private DICT of each font, followed by its local subroutines.
+        let mut privates = vec![];
+        for (font, subrs) in fonts.iter().zip(&self.local_subrs) {
+            let private = dict_value(font, PRIVATE)?;
+            let (size, offset) = (*private.first()? as usize, *private.get(1)? as usize);
+            let mut dict = read_dict(data.get(offset..offset + size)?);
+            dict.retain(|(operator, _)| *operator != SUBRS);
+            if !subrs.is_empty() {
+                // The Subrs operand is 5 bytes, and the operator 1.
+                let size = write_dict(&dict, &[]).len() + 6;
+                dict.push((SUBRS, vec![size as f64]));
+            }
+            let subrs = write_index(&self.items(subrs));
+            privates.push((write_dict(&dict, &[SUBRS]), subrs));
+        }
+
+        let char_strings: Vec<&[u8]> = (0..self.char_strings.len())
+            .map(|gid| match gid == 0 || glyphs.contains(&(gid as u16)) {
+                true => &data[self.char_strings[gid].clone()],
+                false => &[14],
+            })
+            .collect();
+        let char_strings = write_index(&char_strings);
+        let mut charset = vec![0];
+        for &sid in self.charset.iter().skip(1) {
+            charset.extend(sid.to_be_bytes());
+        }
+        let mut fd_select = vec![];
+        if self.is_cid {
+            fd_select.push(0);
+            fd_select.extend(&self.fd_select);
+            fd_select.resize(1 + self.char_strings.len(), 0);
+        }
+        let strings = write_index(&self.items(&self.strings));
+        let global_subrs = write_index(&self.items(&self.global_subrs));
+
+        // Offsets are written with 5 bytes each, so the sizes of the DICTs do
+        // not depend on them and the layout can be computed up front.
+        const OFFSETS: [u16; 5] = [CHARSET, CHAR_STRINGS, PRIVATE, FD_ARRAY, FD_SELECT];
+        top.retain(|(operator, _)| !OFFSETS.contains(operator) && *operator != ENCODING);
+        let layout = |offsets: &[usize]| {
+            let mut top = top.clone();
+            top.push((CHARSET, vec![offsets[0] as f64]));
+            top.push((CHAR_STRINGS, vec![offsets[1] as f64]));
+            if self.is_cid {
+                top.push((FD_SELECT, vec![offsets[2] as f64]));
+                top.push((FD_ARRAY, vec![offsets[3] as f64]));
+            } else {
+                let size = privates[0].0.len() as f64;
+                top.push((PRIVATE, vec![size, offsets[4] as f64]));
+            }
+            let mut font_dicts = vec![];
+            let mut private_offset = offsets[4];
+            for (font, (private, subrs)) in fonts.iter().zip(&privates) {
+                let mut font = font.clone();
+                font.retain(|(operator, _)| *operator != PRIVATE);
+                font.push((PRIVATE, vec![private.len() as f64, private_offset as f64]));
+                font_dicts.push(write_dict(&font, &OFFSETS));
+                private_offset += private.len() + subrs.len();
+            }
+            let font_dicts: Vec<&[u8]> = font_dicts.iter().map(Vec::as_slice).collect();
+            (
+                write_index(&[&write_dict(&top, &OFFSETS)]),
+                write_index(&font_dicts),
+            )
+        };
+        let (top_index, fd_array) = layout(&[0; 5]);
+        let charset_offset = names_end + top_index.len() + strings.len() + global_subrs.len();
+        let fd_select_offset = charset_offset + charset.len();
+        let char_strings_offset = fd_select_offset + fd_select.len();
+        let fd_array_offset = char_strings_offset + char_strings.len();
+        let private_offset = match self.is_cid {
+            true => fd_array_offset + fd_array.len(),
+            false => fd_array_offset,
+        };
+        let (top_index, fd_array) = layout(&[
+            charset_offset,
+            char_strings_offset,
+            fd_select_offset,
+            fd_array_offset,
+            private_offset,
+        ]);
+
+        let mut font = data[..names_end].to_vec();
+        font.extend(top_index);
+        font.extend(strings);
+        font.extend(global_subrs);
+        font.extend(charset);
+        font.extend(fd_select);
+        font.extend(char_strings);
+        if self.is_cid {
+            font.extend(fd_array);
+        }
+        for (private, subrs) in privates {
+            font.extend(private);
+            font.extend(subrs);
+        }
+        Some(font)
+    }
+
+    fn items(&self, ranges: &[Range<usize>]) -> Vec<&[u8]> {
+        ranges
+            .iter()
+            .map(|range| &self.data[range.clone()])
+            .collect()
+    }
+
+    fn string(&self, sid: u16) -> Option<&str> {
+        let sid = sid as usize;
+        if sid < STANDARD_STRING_COUNT {
+            return standard_string(sid);
+        }
+        let range = self.strings.get(sid - STANDARD_STRING_COUNT)?.clone();
+        std::str::from_utf8(&self.data[range]).ok()
+    }
+
+    /// The name of a glyph of a name-keyed font.
+    pub fn glyph_name(&self, gid: u16) -> Option<&str> {
+        if self.is_cid {
+            return None;
+        }
+        self.string(*self.charset.get(gid as usize)?)
+    }
+
+    pub fn glyph_for_name(&self, name: &str) -> Option<u16> {
+        if self.is_cid {
+            return None;
+        }
+        (0..self.charset.len() as u16).find(|&gid| self.glyph_name(gid) == Some(name))
+    }
+
+    pub fn glyph_for_cid(&self, cid: u32) -> Option<u16> {
+        if !self.is_cid {
+            return u16::try_from(cid).ok();
+        }
+        self.charset
+            .iter()
+            .position(|&c| c as u32 == cid)
+            .map(|gid| gid as u16)
+    }
+
+    /// The glyph for a code in the font's built-in encoding.
+    pub fn glyph_for_code(&self, code: u8) -> Option<u16> {
+        match &self.encoding {
+            Some(encoding) => Some(encoding[code as usize]).filter(|&gid| gid != 0),
+            None => self.glyph_for_name(&BaseEncoding::Standard.glyph_name(code)?),
+        }
+    }
+
+    /// The outline of a glyph, scaled so that the em square is 1000 units.
+    pub fn glyph_outline(&self, gid: u16) -> Option<Path> {
+        let range = self.char_strings.get(gid as usize)?.clone();
+        let fd = self.fd_select.get(gid as usize).copied().unwrap_or(0) as usize;
+        let mut interpreter = CharStringInterpreter {
+            font: self,
+            local_subrs: self.local_subrs.get(fd).map_or(&[], Vec::as_slice),
+            stack: vec![],
+            path: Path::new(),
+            point: Point::new(0.0, 0.0),
+            stems: 0,
+            width_parsed: false,
+            open: false,
+            transient: [0.0; 32],
+        };
+        interpreter.run(&self.data[range], 0)?;
+        interpreter.close();
+        let scale = self.font_matrix.multiply(&Matrix::scale(1000.0, 1000.0));
+        Some(interpreter.path.transform(&scale))
+    }
+}
+
+/// The bias added to subroutine numbers, which depends on the number of
+/// subroutines. See sec. 4.7 of Adobe Technical Note #5177
+fn subr_bias(count: usize) -> i32 {
+    if count < 1240 {
+        107
+    } else if count < 33900 {
+        1131
+    } else {
+        32768
+    }
+}
+
+/// Runs Type 2 charstrings, building the glyph's path. Hints are skipped.
+struct CharStringInterpreter<'a> {
+    font: &'a CffFont,
+    local_subrs: &'a [Range<usize>],
+    stack: Vec<f64>,
+    path: Path,
+    point: Point,
+    stems: usize,
+    /// The first stack-clearing operator may be preceded by the advance width.
+    width_parsed: bool,
+    open: bool,
+    transient: [f64; 32],
+}
+
+/// Why a charstring stopped running normally.
+enum Stop {
+    Return,
+    EndChar,
+}
+
+impl CharStringInterpreter<'_> {
+    fn close(&mut self) {
+        if self.open {
+            self.path.close();
+            self.open = false;
+        }
+    }
+
+    fn move_to(&mut self, dx: f64, dy: f64) {
+        self.close();
+        self.point = Point::new(self.point.x + dx, self.point.y + dy);
+        self.path.move_to(self.point.x, self.point.y);
+        self.open = true;
+    }
+
+    fn line_to(&mut self, dx: f64, dy: f64) {
+        self.point = Point::new(self.point.x + dx, self.point.y + dy);
+        self.path.line_to(self.point.x, self.point.y);
+    }
+
+    fn curve_to(&mut self, d: [f64; 6]) {
+        let c1 = Point::new(self.point.x + d[0], self.point.y + d[1]);
+        let c2 = Point::new(c1.x + d[2], c1.y + d[3]);
+        self.point = Point::new(c2.x + d[4], c2.y + d[5]);
+        self.path
+            .curve_to(c1.x, c1.y, c2.x, c2.y, self.point.x, self.point.y);
+    }
+
+    /// Drops the advance width if the stack holds one more argument than the
+    /// operator takes.
+    fn take_width(&mut self, extra: bool) {
+        if !self.width_parsed && extra && !self.stack.is_empty() {
+            self.stack.remove(0);
+        }
+        self.width_parsed = true;
+    }
+
+    fn stem_hints(&mut self) {
+        self.take_width(self.stack.len() % 2 == 1);
+        self.stems += self.stack.len() / 2;
+        self.stack.clear();
+    }
+
+    fn run(&mut self, code: &[u8], depth: usize) -> Option<Stop> {
+        if depth > MAX_SUBR_DEPTH {
+            return None;
+        }
+        let mut i = 0;
+        while i < code.len() {
+            let b0 = code[i];
+            i += 1;
+            match b0 {
+                1 | 3 | 18 | 23 => self.stem_hints(),
+                19 | 20 => {
+                    // A hintmask after stems implicitly ends a vstem list.
+                    self.stem_hints();
+                    i += self.stems.div_ceil(8);
+                }
+                21 => {
+                    self.take_width(self.stack.len() > 2);
+                    let (dx, dy) = (self.arg(0)?, self.arg(1)?);
+                    self.move_to(dx, dy);
+                    self.stack.clear();
+                }
+                22 | 4 => {
+                    self.take_width(self.stack.len() > 1);
+                    let d = self.arg(0)?;
+                    if b0 == 22 {
+                        self.move_to(d, 0.0);
+                    } else {
+                        self.move_to(0.0, d);
+                    }
+                    self.stack.clear();
+                }
+                5 => {
+                    for pair in std::mem::take(&mut self.stack).chunks_exact(2) {
+                        self.line_to(pair[0], pair[1]);
+                    }
+                }
+                6 | 7 => {
+                    let mut horizontal = b0 == 6;
+                    for d in std::mem::take(&mut self.stack) {
+                        if horizontal {
+                            self.line_to(d, 0.0);
+                        } else {
+                            self.line_to(0.0, d);
+                        }
+                        horizontal = !horizontal;
+                    }
+                }
+                8 => {
+                    for d in std::mem::take(&mut self.stack).chunks_exact(6) {
+                        self.curve_to(d.try_into().unwrap());
+                    }
+                }
+                24 => {
+                    let args = std::mem::take(&mut self.stack);
+                    let curves = args.len().saturating_sub(2) / 6;
+                    for d in args.chunks_exact(6).take(curves) {
+                        self.curve_to(d.try_into().unwrap());
+                    }
+                    if let [dx, dy] = args[curves * 6..] {
+                        self.line_to(dx, dy);
+                    }
+                }
+                25 => {
+                    let args = std::mem::take(&mut self.stack);
+                    let lines = args.len().saturating_sub(6) / 2;
+                    for pair in args.chunks_exact(2).take(lines) {
+                        self.line_to(pair[0], pair[1]);
+                    }
+                    if let Ok(d) = args[lines * 2..].try_into() {
+                        self.curve_to(d);
+                    }
+                }
+                26 | 27 => {
+                    // vvcurveto and hhcurveto, with an optional leading offset
+                    // across the direction of the curves.
+                    let mut args = std::mem::take(&mut self.stack);
+                    let mut across = 0.0;
+                    if args.len() % 4 == 1 {
+                        across = args.remove(0);
+                    }
+                    for d in args.chunks_exact(4) {
+                        if b0 == 26 {
+                            self.curve_to([across, d[0], d[1], d[2], 0.0, d[3]]);
+                        } else {
+                            self.curve_to([d[0], across, d[1], d[2], d[3], 0.0]);
+                        }
+                        across = 0.0;
+                    }
+                }
+                30 | 31 => {
+                    // vhcurveto and hvcurveto alternate between curves starting
+                    // vertically and horizontally; the last may end obliquely.
+                    let args = std::mem::take(&mut self.stack);
+                    let mut horizontal = b0 == 31;
+                    let count = args.len() / 4;
+                    for (n, d) in args.chunks_exact(4).enumerate() {
+                        let last = if n + 1 == count && args.len() % 4 == 1 {
+                            args[args.len() - 1]
+                        } else {
+                            0.0
+                        };
+                        if horizontal {
+                            self.curve_to([d[0], 0.0, d[1], d[2], last, d[3]]);
+                        } else {
+                            self.curve_to([0.0, d[0], d[1], d[2], d[3], last]);
+                        }
+                        horizontal = !horizontal;
+                    }
+                }
+                10 | 29 => {
+                    let subrs = if b0 == 10 {
+                        self.local_subrs
+                    } else {
+                        self.font.global_subrs.as_slice()
+                    };
+                    let index = self.stack.pop()? as i32 + subr_bias(subrs.len());
+                    let range = subrs.get(usize::try_from(index).ok()?)?.clone();
+                    let font = self.font;
+                    if let Stop::EndChar = self.run(&font.data[range], depth + 1)? {
+                        return Some(Stop::EndChar);
+                    }
+                }
+                11 => return Some(Stop::Return),
+                14 => {
+                    self.take_width(self.stack.len() == 1 || self.stack.len() == 5);
+                    if let [adx, ady, bchar, achar] = self.stack[..] {
+                        self.seac(adx, ady, bchar, achar, depth)?;
+                    }
+                    self.stack.clear();
+                    return Some(Stop::EndChar);
+                }
+                12 => {
+                    let b1 = *code.get(i)?;
+                    i += 1;
+                    self.escape(b1)?;
+                }
+                28 => {
+                    let bytes = code.get(i..i + 2)?;
+                    self.stack
+                        .push(i16::from_be_bytes([bytes[0], bytes[1]]) as f64);
+                    i += 2;
+                }
+                32..=246 => self.stack.push(b0 as f64 - 139.0),
+                247..=250 => {
+                    let b1 = *code.get(i)? as f64;
+                    i += 1;
+                    self.stack.push((b0 as f64 - 247.0) * 256.0 + b1 + 108.0);
+                }
+                251..=254 => {
+                    let b1 = *code.get(i)? as f64;
+                    i += 1;
+                    self.stack.push(-(b0 as f64 - 251.0) * 256.0 - b1 - 108.0);
+                }
+                255 => {
+                    let bytes = code.get(i..i + 4)?;
+                    let fixed = i32::from_be_bytes(bytes.try_into().ok()?);
+                    self.stack.push(fixed as f64 / 65536.0);
+                    i += 4;
+                }
+                _ => self.stack.clear(),
+            }
+        }
+        Some(Stop::Return)
+    }
+
+    fn arg(&self, index: usize) -> Option<f64> {
+        self.stack.get(index).copied()
+    }
+
+    /// The two-byte operators: flex curves and arithmetic.
+    fn escape(&mut self, operator: u8) -> Option<()> {
+        let args = &self.stack;
+        match operator {
+            // flex
+            35 if args.len() >= 12 => {
+                let d: Vec<f64> = args[..12].to_vec();
+                self.curve_to(d[..6].try_into().unwrap());
+                self.curve_to(d[6..12].try_into().unwrap());
+            }
+            // hflex
+            34 if args.len() >= 7 => {
+                let d: Vec<f64> = args[..7].to_vec();
+                let y = self.point.y;
+                self.curve_to([d[0], 0.0, d[1], d[2], d[3], 0.0]);
+                self.curve_to([d[4], 0.0, d[5], y - (self.point.y), d[6], 0.0]);
+            }
+            // hflex1
+            36 if args.len() >= 9 => {
+                let d: Vec<f64> = args[..9].to_vec();
+                let y = self.point.y;
+                self.curve_to([d[0], d[1], d[2], d[3], d[4], 0.0]);
+                let dy = y - (self.point.y + d[7]);
+                self.curve_to([d[5], 0.0, d[6], d[7], d[8], dy]);
+            }
+            // flex1: the last coordinate runs along the larger total delta.
+            37 if args.len() >= 11 => {
+                let d: Vec<f64> = args[..11].to_vec();
+                let dx: f64 = d[..10].iter().step_by(2).sum();
+                let dy: f64 = d[1..10].iter().step_by(2).sum();
+                self.curve_to(d[..6].try_into().unwrap());
+                let (x, y) = if dx.abs() > dy.abs() {
+                    (d[10], -dy)
+                } else {
+                    (-dx, d[10])
+                };
+                self.curve_to([d[6], d[7], d[8], d[9], x, y]);
+            }
+            _ => {
+                let result = match (operator, args.as_slice()) {
+                    (9, [.., a]) => Some(vec![a.abs()]),
+                    (10, [.., a, b]) => Some(vec![a + b]),
+                    (11, [.., a, b]) => Some(vec![a - b]),
+                    (12, [.., a, b]) => Some(vec![if *b == 0.0 { 0.0 } else { a / b }]),
+                    (14, [.., a]) => Some(vec![-a]),
+                    (18, [.., _]) => Some(vec![]),
+                    (24, [.., a, b]) => Some(vec![a * b]),
+                    (26, [.., a]) => Some(vec![a.abs().sqrt()]),
+                    (27, [.., a]) => Some(vec![*a, *a]),
+                    (28, [.., a, b]) => Some(vec![*b, *a]),
+                    (20, [.., value, index]) => {
+                        let index = *index as usize;
+                        if index < self.transient.len() {
+                            self.transient[index] = *value;
+                        }
+                        Some(vec![])
+                    }
+                    (21, [.., index]) => Some(vec![self
+                        .transient
+                        .get(*index as usize)
+                        .copied()
+                        .unwrap_or(0.0)]),
+                    _ => None,
+                };
+                let consumed = match operator {
+                    9 | 14 | 18 | 26 | 27 | 21 => 1,
+                    10 | 11 | 12 | 24 | 28 | 20 => 2,
+                    _ => 0,
+                };
+                match result {
+                    Some(values) => {
+                        self.stack.truncate(self.stack.len() - consumed);
+                        self.stack.extend(values);
+                    }
+                    None => self.stack.clear(),
+                }
+                return Some(());
+            }
+        }
+        self.stack.clear();
+        Some(())
+    }
+
+    /// An endchar with four arguments composes an accented character from two
+    /// glyphs named by their StandardEncoding codes, like the Type 1 seac.
+    fn seac(&mut self, adx: f64, ady: f64, bchar: f64, achar: f64, depth: usize) -> Option<()> {
+        let font = self.font;
+        let glyph = |code: f64| {
+            let name = BaseEncoding::Standard.glyph_name(code as u8)?;
+            let gid = font.glyph_for_name(&name)?;
+            font.char_strings.get(gid as usize).cloned()
+        };
+        let (base, accent) = (glyph(bchar)?, glyph(achar)?);
+        self.close();
+        self.point = Point::new(0.0, 0.0);
+        self.stack.clear();
+        self.run(&font.data[base], depth + 1)?;
+        self.close();
+        let base_path = std::mem::take(&mut self.path);
+        self.point = Point::new(0.0, 0.0);
+        self.stack.clear();
+        self.run(&font.data[accent], depth + 1)?;
+        self.close();
+        let accent_path = std::mem::replace(&mut self.path, base_path);
+        let offset = Matrix::translate(adx, ady);
+        self.path
+            .segments
+            .extend(accent_path.transform(&offset).segments);
+        Some(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::geometry::PathSegment;
+
+    #[test]
+    fn test_standard_strings() {
+        let strings: Vec<&str> = STANDARD_STRINGS.split_whitespace().collect();
+        assert_eq!(strings.len(), STANDARD_STRING_COUNT);
+        assert_eq!(standard_string(34), Some("A"));
+        assert_eq!(standard_string(228), Some("zcaron"));
+        assert_eq!(standard_string(390), Some("Semibold"));
+    }
+
+    #[test]
+    fn test_char_string() {
+        // 500 100 100 rmoveto 300 hlineto 300 vlineto -300 hlineto endchar, where
+        // 500 is the advance width.
+        let data = vec![
+            248, 136, 239, 239, 21, 247, 192, 6, 247, 192, 7, 251, 192, 6, 14,
+        ];
+        let font = CffFont {
+            char_strings: vec![Range {
+                start: 0,
+                end: data.len(),
+            }],
+            data,
+            strings: vec![],
+            global_subrs: vec![],
+            local_subrs: vec![],
+            fd_select: vec![],
+            charset: vec![0],
+            encoding: None,
+            is_cid: false,
+            font_matrix: Matrix::scale(0.001, 0.001),
+        };
+        let outline = font.glyph_outline(0).unwrap();
+        assert_eq!(outline.bounding_box(), Some([100.0, 100.0, 400.0, 400.0]));
+        assert_eq!(outline.segments.last(), Some(&PathSegment::ClosePath));
+    }
+
+    #[test]
+    fn test_read_dict() {
+        // 0 -100 1000 -1000 -1000 39.5 CharStrings, using each number encoding.
+        let dict = read_dict(&[
+            0x8B, 0x27, 0xFA, 0x7C, 0xFE, 0x7C, 0x1C, 0xFC, 0x18, 0x1E, 0x39, 0xA5, 0xFF, 0x11,
+        ]);
+        assert_eq!(
+            dict,
+            vec![(
+                CHAR_STRINGS,
+                vec![0.0, -100.0, 1000.0, -1000.0, -1000.0, 39.5]
+            )]
+        );
+    }
+
+    /// A name-keyed CFF font with the glyphs .notdef, a square, and the same
+    /// square drawn by a local subroutine.
+    pub(crate) fn sample_cff() -> Vec<u8> {
+        let square = [239, 239, 21, 247, 192, 6, 247, 192, 7, 251, 192, 6, 14];
+        let private = write_dict(&[(SUBRS, vec![6.0])], &[SUBRS]);
+        let subrs = write_index(&[&square]);
+        let char_strings = write_index(&[&[14], &square, &[32, 10]]);
+        let top = |char_strings: usize, private: usize| {
+            let dict = [
+                (CHAR_STRINGS, vec![char_strings as f64]),
+                (PRIVATE, vec![6.0, private as f64]),
+            ];
+            write_index(&[&write_dict(&dict, &[CHAR_STRINGS, PRIVATE])])
+        };
+        let mut font = vec![1, 0, 4, 1];
+        font.extend(write_index(&[b"Test"]));
+        let char_strings_offset = font.len() + top(0, 0).len() + 4;
+        let private_offset = char_strings_offset + char_strings.len();
+        font.extend(top(char_strings_offset, private_offset));
+        font.extend(write_index(&[]));
+        font.extend(write_index(&[]));
+        font.extend(char_strings);
+        font.extend(private);
+        font.extend(subrs);
+        font
+    }
+
+    #[test]
+    fn test_write_dict() {
+        let dict = vec![
+            (CHAR_STRINGS, vec![0.0, -100.0, 1000.0, -1131.0, 40000.0]),
+            (FONT_MATRIX, vec![0.001, 0.0, 0.0, 0.001, -0.5, 1e6]),
+        ];
+        assert_eq!(read_dict(&write_dict(&dict, &[])), dict);
+        assert_eq!(write_dict(&dict, &[CHAR_STRINGS]).len(), 47);
+    }
+
+    #[test]
+    fn test_subset() {
+        let font = CffFont::parse(sample_cff()).unwrap();
+        let square = Some([100.0, 100.0, 400.0, 400.0]);
+        assert_eq!(font.glyph_outline(1).unwrap().bounding_box(), square);
+        assert_eq!(font.glyph_outline(2).unwrap().bounding_box(), square);
+
+        let subset = CffFont::parse(font.subset(&BTreeSet::from([2])).unwrap()).unwrap();
+        assert_eq!(subset.num_glyphs(), 3);
+        assert!(subset.glyph_outline(1).unwrap().segments.is_empty());
+        assert_eq!(subset.glyph_outline(2).unwrap().bounding_box(), square);
+        assert_eq!(subset.glyph_cid(2), Some(2));
+    }
+}
@@ -0,0 +1,77 @@
+//!
+//! Glyph widths for the standard 14 fonts, which PDF processors are expected to
+//! know without the font being embedded. See sec. 9.6.2.2 (pg. 304)
+//!
+//! Only the printable ASCII range is covered. Bold and oblique variants reuse the
+//! widths of the regular face, which is close enough for positioning text.
+//!
+
+/// Widths of the characters 32 to 126 in Helvetica, in 1/1000 text space units.
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 222, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 222, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Widths of the characters 32 to 126 in Times-Roman, in 1/1000 text space units.
+const TIMES_ROMAN: [u16; 95] = [
+    250, 333, 408, 500, 500, 833, 778, 333, 333, 333, 500, 564, 250, 333, 250, 278, 500, 500, 500,
+    500, 500, 500, 500, 500, 500, 500, 278, 278, 564, 564, 564, 444, 921, 722, 667, 667, 722, 611,
+    556, 722, 722, 333, 389, 722, 611, 889, 722, 722, 556, 722, 667, 556, 611, 722, 722, 944, 722,
+    722, 611, 333, 278, 333, 469, 500, 333, 444, 500, 444, 500, 444, 333, 500, 500, 278, 278, 500,
+    278, 778, 500, 500, 500, 500, 333, 389, 278, 500, 500, 722, 500, 500, 444, 480, 200, 480, 541,
+];
+
+/// The names of the standard 14 fonts.
+pub const STANDARD_FONTS: [&str; 14] = [
+    "Times-Roman",
+    "Times-Bold",
+    "Times-Italic",
+    "Times-BoldItalic",
+    "Helvetica",
+    "Helvetica-Bold",
+    "Helvetica-Oblique",
+    "Helvetica-BoldOblique",
+    "Courier",
+    "Courier-Bold",
+    "Courier-Oblique",
+    "Courier-BoldOblique",
+    "Symbol",
+    "ZapfDingbats",
+];
+
+/// Returns the width of a character in one of the standard 14 fonts. Common
+/// aliases such as "Arial" and "TimesNewRoman" are recognised too.
+pub fn standard_width(base_font: &str, c: char) -> Option<f64> {
+    let family = standard_family(base_font)?;
+    if family == "Courier" {
+        return Some(600.0);
+    }
+    let index = (c as u32).checked_sub(32).filter(|&i| i < 95)? as usize;
+    match family {
+        "Helvetica" => Some(HELVETICA[index] as f64),
+        "Times" => Some(TIMES_ROMAN[index] as f64),
+        _ => None,
+    }
+}
+
+/// Reduces a font name to the family of the standard font it corresponds to.
+/// Subset prefixes ("ABCDEF+") are removed first.
+pub fn standard_family(base_font: &str) -> Option<&'static str> {
+    let name = base_font.split('+').next_back().unwrap_or(base_font);
+    if name.starts_with("Courier") {
+        Some("Courier")
+    } else if name.starts_with("Helvetica") || name.starts_with("Arial") {
+        Some("Helvetica")
+    } else if name.starts_with("Times") {
+        Some("Times")
+    } else if name.starts_with("Symbol") {
+        Some("Symbol")
+    } else if name.starts_with("ZapfDingbats") {
+        Some("ZapfDingbats")
+    } else {
+        None
+    }
+}
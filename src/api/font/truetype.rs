@@ -0,0 +1,778 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::api::{
+    font::cff::CffFont,
+    geometry::{Matrix, Path, Point},
+    read::cmap::utf16_be_to_string,
+};
+
+/// Composite glyphs may refer to other composite glyphs; deeper nesting than
+/// this is ignored.
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+/// The (platform ID, encoding ID) of the 'cmap' subtables that map Unicode
+/// characters, in order of preference: full Unicode before the BMP only.
+const UNICODE_CMAPS: [(u16, u16); 4] = [(3, 10), (3, 1), (0, 4), (0, 3)];
+
+/// The tables copied into subsets besides the glyph outlines. The 'cmap' table
+/// is left out since CIDFonts select glyphs by index.
+const SUBSET_TABLES: [&[u8; 4]; 8] = [
+    b"head", b"hhea", b"maxp", b"hmtx", b"OS/2", b"cvt ", b"fpgm", b"prep",
+];
+
+///
+/// A TrueType or OpenType font program, as embedded with /FontFile2 or
+/// /FontFile3 /OpenType. Glyph outlines come from the 'glyf' table, or from
+/// the 'CFF ' table of OpenType fonts with PostScript outlines.
+/// See sec. 9.9 (pg. 341) and the OpenType specification.
+///
+#[derive(Debug, Clone)]
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    tables: HashMap<[u8; 4], (usize, usize)>,
+    pub units_per_em: u16,
+    pub num_glyphs: u16,
+    /// The start of each glyph in the 'glyf' table, by glyph index.
+    loca: Vec<usize>,
+    /// The (platform ID, encoding ID) of each 'cmap' subtable with its offset.
+    cmaps: Vec<((u16, u16), usize)>,
+    cff: Option<CffFont>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    u16_at(data, offset).map(|v| v as i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+impl TrueTypeFont {
+    /// Reads the table directory and the tables needed to find glyphs. Font
+    /// collections are read from their first font.
+    pub fn parse(data: Vec<u8>) -> Option<TrueTypeFont> {
+        let mut start = 0;
+        if data.get(..4) == Some(b"ttcf") {
+            start = u32_at(&data, 12)? as usize;
+        }
+        let num_tables = u16_at(&data, start + 4)? as usize;
+        let mut tables = HashMap::new();
+        for i in 0..num_tables {
+            let record = start + 12 + i * 16;
+            let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+            let offset = u32_at(&data, record + 8)? as usize;
+            let length = u32_at(&data, record + 12)? as usize;
+            tables.insert(tag, (offset, length));
+        }
+
+        let mut font = TrueTypeFont {
+            data,
+            tables,
+            units_per_em: 1000,
+            num_glyphs: 0,
+            loca: vec![],
+            cmaps: vec![],
+            cff: None,
+        };
+        if let Some(head) = font.table(b"head") {
+            font.units_per_em = u16_at(head, 18).filter(|&u| u > 0).unwrap_or(1000);
+        }
+        font.num_glyphs = font.table(b"maxp").and_then(|t| u16_at(t, 4)).unwrap_or(0);
+        font.loca = font.read_loca().unwrap_or_default();
+        font.cmaps = font.read_cmaps().unwrap_or_default();
+        font.cff = font
+            .table(b"CFF ")
+            .and_then(|cff| CffFont::parse(cff.to_vec()));
+        if font.loca.is_empty() && font.cff.is_none() {
+            return None;
+        }
+        Some(font)
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+        let &(offset, length) = self.tables.get(tag)?;
+        let end = (offset + length).min(self.data.len());
+        self.data.get(offset..end)
+    }
+
+    fn read_loca(&self) -> Option<Vec<usize>> {
+        let long = i16_at(self.table(b"head")?, 50)? != 0;
+        let loca = self.table(b"loca")?;
+        let count = self.num_glyphs as usize + 1;
+        (0..count)
+            .map(|i| match long {
+                true => u32_at(loca, i * 4).map(|o| o as usize),
+                false => u16_at(loca, i * 2).map(|o| o as usize * 2),
+            })
+            .collect()
+    }
+
+    fn read_cmaps(&self) -> Option<Vec<((u16, u16), usize)>> {
+        let cmap = self.table(b"cmap")?;
+        let count = u16_at(cmap, 2)? as usize;
+        (0..count)
+            .map(|i| {
+                let record = 4 + i * 8;
+                let platform = u16_at(cmap, record)?;
+                let encoding = u16_at(cmap, record + 2)?;
+                Some(((platform, encoding), u32_at(cmap, record + 4)? as usize))
+            })
+            .collect()
+    }
+
+    pub fn has_cmap(&self, platform: u16, encoding: u16) -> bool {
+        self.cmaps.iter().any(|(id, _)| *id == (platform, encoding))
+    }
+
+    /// Looks a character code up in the 'cmap' subtable for the given platform
+    /// and encoding. Formats 0, 4, 6 and 12 are read.
+    pub fn lookup(&self, platform: u16, encoding: u16, code: u32) -> Option<u16> {
+        let cmap = self.table(b"cmap")?;
+        let (_, offset) = self
+            .cmaps
+            .iter()
+            .find(|(id, _)| *id == (platform, encoding))?;
+        let table = cmap.get(*offset..)?;
+        let gid = match u16_at(table, 0)? {
+            0 => *table.get(6 + code as usize)? as u16,
+            4 => {
+                let segments = u16_at(table, 6)? as usize / 2;
+                let ends = 14;
+                let starts = ends + segments * 2 + 2;
+                let deltas = starts + segments * 2;
+                let range_offsets = deltas + segments * 2;
+                let segment = (0..segments)
+                    .find(|&i| u16_at(table, ends + i * 2).is_some_and(|end| code <= end as u32))?;
+                let start = u16_at(table, starts + segment * 2)? as u32;
+                if code < start {
+                    return None;
+                }
+                let delta = u16_at(table, deltas + segment * 2)?;
+                let range_offset = u16_at(table, range_offsets + segment * 2)? as usize;
+                if range_offset == 0 {
+                    (code as u16).wrapping_add(delta)
+                } else {
+                    let position =
+                        range_offsets + segment * 2 + range_offset + (code - start) as usize * 2;
+                    match u16_at(table, position)? {
+                        0 => 0,
+                        gid => gid.wrapping_add(delta),
+                    }
+                }
+            }
+            6 => {
+                let first = u16_at(table, 6)? as u32;
+                let count = u16_at(table, 8)? as u32;
+                if code < first || code >= first + count {
+                    return None;
+                }
+                u16_at(table, 10 + (code - first) as usize * 2)?
+            }
+            12 => {
+                let groups = u32_at(table, 12)? as usize;
+                (0..groups).find_map(|i| {
+                    let group = 16 + i * 12;
+                    let start = u32_at(table, group)?;
+                    let end = u32_at(table, group + 4)?;
+                    let first = u32_at(table, group + 8)?;
+                    (start <= code && code <= end).then(|| (first + code - start) as u16)
+                })?
+            }
+            _ => return None,
+        };
+        (gid != 0).then_some(gid)
+    }
+
+    /// The outline of a glyph, scaled so that the em square is 1000 units.
+    pub fn glyph_outline(&self, gid: u16) -> Option<Path> {
+        if let Some(cff) = &self.cff {
+            return cff.glyph_outline(gid);
+        }
+        let mut path = Path::new();
+        let scale = 1000.0 / self.units_per_em as f64;
+        self.append_glyph(gid, &Matrix::scale(scale, scale), &mut path, 0)?;
+        Some(path)
+    }
+
+    fn glyph_data(&self, gid: u16) -> Option<&[u8]> {
+        let glyf = self.table(b"glyf")?;
+        let start = *self.loca.get(gid as usize)?;
+        let end = *self.loca.get(gid as usize + 1)?;
+        if end <= start {
+            return Some(&[]);
+        }
+        glyf.get(start..end)
+    }
+
+    /// Appends the contours of a glyph, transformed by the matrix. See the
+    /// 'glyf' table of the OpenType specification.
+    fn append_glyph(&self, gid: u16, matrix: &Matrix, path: &mut Path, depth: usize) -> Option<()> {
+        let data = self.glyph_data(gid)?;
+        if data.is_empty() {
+            return Some(());
+        }
+        let contours = i16_at(data, 0)?;
+        if contours >= 0 {
+            append_simple_glyph(data, contours as usize, matrix, path)
+        } else if depth < MAX_COMPONENT_DEPTH {
+            self.append_composite_glyph(data, matrix, path, depth)
+        } else {
+            Some(())
+        }
+    }
+
+    fn append_composite_glyph(
+        &self,
+        data: &[u8],
+        matrix: &Matrix,
+        path: &mut Path,
+        depth: usize,
+    ) -> Option<()> {
+        const ARGS_ARE_WORDS: u16 = 0x0001;
+        const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+        const HAVE_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const HAVE_X_AND_Y_SCALE: u16 = 0x0040;
+        const HAVE_TWO_BY_TWO: u16 = 0x0080;
+
+        let f2dot14 = |offset: usize| i16_at(data, offset).map(|v| v as f64 / 16384.0);
+        let mut offset = 10;
+        loop {
+            let flags = u16_at(data, offset)?;
+            let gid = u16_at(data, offset + 2)?;
+            offset += 4;
+            let (dx, dy) = if flags & ARGS_ARE_WORDS != 0 {
+                offset += 4;
+                (
+                    i16_at(data, offset - 4)? as f64,
+                    i16_at(data, offset - 2)? as f64,
+                )
+            } else {
+                offset += 2;
+                (
+                    *data.get(offset - 2)? as i8 as f64,
+                    *data.get(offset - 1)? as i8 as f64,
+                )
+            };
+            // Components positioned by matching points are placed unshifted.
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                (dx, dy)
+            } else {
+                (0.0, 0.0)
+            };
+            let (a, b, c, d) = if flags & HAVE_SCALE != 0 {
+                offset += 2;
+                let scale = f2dot14(offset - 2)?;
+                (scale, 0.0, 0.0, scale)
+            } else if flags & HAVE_X_AND_Y_SCALE != 0 {
+                offset += 4;
+                (f2dot14(offset - 4)?, 0.0, 0.0, f2dot14(offset - 2)?)
+            } else if flags & HAVE_TWO_BY_TWO != 0 {
+                offset += 8;
+                (
+                    f2dot14(offset - 8)?,
+                    f2dot14(offset - 6)?,
+                    f2dot14(offset - 4)?,
+                    f2dot14(offset - 2)?,
+                )
+            } else {
+                (1.0, 0.0, 0.0, 1.0)
+            };
+            let component = Matrix::new(a, b, c, d, dx, dy).multiply(matrix);
+            self.append_glyph(gid, &component, path, depth + 1)?;
+            if flags & MORE_COMPONENTS == 0 {
+                return Some(());
+            }
+        }
+    }
+
+    /// The glyphs a composite glyph is made of.
+    fn glyph_components(&self, gid: u16) -> Option<Vec<u16>> {
+        const ARGS_ARE_WORDS: u16 = 0x0001;
+        const HAVE_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const HAVE_X_AND_Y_SCALE: u16 = 0x0040;
+        const HAVE_TWO_BY_TWO: u16 = 0x0080;
+
+        let data = self.glyph_data(gid)?;
+        let mut components = vec![];
+        if data.is_empty() || i16_at(data, 0)? >= 0 {
+            return Some(components);
+        }
+        let mut offset = 10;
+        loop {
+            let flags = u16_at(data, offset)?;
+            components.push(u16_at(data, offset + 2)?);
+            offset += if flags & ARGS_ARE_WORDS != 0 { 8 } else { 6 };
+            if flags & HAVE_SCALE != 0 {
+                offset += 2;
+            } else if flags & HAVE_X_AND_Y_SCALE != 0 {
+                offset += 4;
+            } else if flags & HAVE_TWO_BY_TWO != 0 {
+                offset += 8;
+            }
+            if flags & MORE_COMPONENTS == 0 {
+                return Some(components);
+            }
+        }
+    }
+
+    /// Writes a copy of the font with the outlines of only the given glyphs,
+    /// .notdef and the components of composite glyphs. Glyph indices are
+    /// unchanged, so the subset is meant to be embedded as a CIDFont with an
+    /// identity mapping from CIDs to glyphs. See sec. 9.9 (pg. 341)
+    pub fn subset(&self, glyphs: &BTreeSet<u16>) -> Option<Vec<u8>> {
+        let mut tables: Vec<([u8; 4], Vec<u8>)> = SUBSET_TABLES
+            .iter()
+            .filter_map(|&tag| Some((*tag, self.table(tag)?.to_vec())))
+            .collect();
+        let head = tables.iter_mut().find(|(tag, _)| tag == b"head")?;
+        head.1.get_mut(8..12)?.fill(0);
+        if let Some(cff) = &self.cff {
+            tables.push((*b"CFF ", cff.subset(glyphs)?));
+            return Some(write_sfnt(tables));
+        }
+
+        let mut kept: BTreeSet<u16> = glyphs.iter().copied().chain([0]).collect();
+        let mut pending: Vec<u16> = kept.iter().copied().collect();
+        while let Some(gid) = pending.pop() {
+            for component in self.glyph_components(gid).unwrap_or_default() {
+                if kept.insert(component) {
+                    pending.push(component);
+                }
+            }
+        }
+        let mut glyf = vec![];
+        let mut loca = vec![];
+        for gid in 0..self.num_glyphs {
+            loca.extend((glyf.len() as u32).to_be_bytes());
+            if kept.contains(&gid) {
+                glyf.extend(self.glyph_data(gid).unwrap_or_default());
+                glyf.resize(glyf.len().next_multiple_of(4), 0);
+            }
+        }
+        loca.extend((glyf.len() as u32).to_be_bytes());
+        // The new 'loca' table has long offsets.
+        head.1.get_mut(50..52)?.copy_from_slice(&[0, 1]);
+        tables.push((*b"glyf", glyf));
+        tables.push((*b"loca", loca));
+        Some(write_sfnt(tables))
+    }
+
+    /// The CFF font of an OpenType font with PostScript outlines.
+    pub fn cff(&self) -> Option<&CffFont> {
+        self.cff.as_ref()
+    }
+
+    /// The font file, as it is embedded in a PDF file.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn has_unicode_cmap(&self) -> bool {
+        UNICODE_CMAPS
+            .iter()
+            .any(|&(platform, encoding)| self.has_cmap(platform, encoding))
+    }
+
+    /// The glyph of a Unicode character, looked up in a Unicode 'cmap' subtable.
+    pub fn glyph_index(&self, c: char) -> Option<u16> {
+        UNICODE_CMAPS
+            .into_iter()
+            .filter(|&(platform, encoding)| self.has_cmap(platform, encoding))
+            .find_map(|(platform, encoding)| self.lookup(platform, encoding, c as u32))
+    }
+
+    /// The advance width of a glyph from the 'hmtx' table, scaled so that the
+    /// em square is 1000 units. Glyphs after the last full metric share its
+    /// width.
+    pub fn advance_width(&self, gid: u16) -> Option<f64> {
+        let metrics = u16_at(self.table(b"hhea")?, 34)? as usize;
+        let index = (gid as usize).min(metrics.checked_sub(1)?);
+        let width = u16_at(self.table(b"hmtx")?, index * 4)?;
+        Some(width as f64 * 1000.0 / self.units_per_em as f64)
+    }
+
+    /// The bounding box of all glyphs from the 'head' table, scaled so that the
+    /// em square is 1000 units.
+    pub fn bbox(&self) -> Option<[f64; 4]> {
+        let head = self.table(b"head")?;
+        let scale = 1000.0 / self.units_per_em as f64;
+        let value = |offset: usize| i16_at(head, offset).map(|v| v as f64 * scale);
+        Some([value(36)?, value(38)?, value(40)?, value(42)?])
+    }
+
+    /// The ascent and descent from the 'hhea' table, scaled so that the em
+    /// square is 1000 units. The descent is negative.
+    pub fn vertical_metrics(&self) -> Option<(f64, f64)> {
+        let hhea = self.table(b"hhea")?;
+        let scale = 1000.0 / self.units_per_em as f64;
+        Some((
+            i16_at(hhea, 4)? as f64 * scale,
+            i16_at(hhea, 6)? as f64 * scale,
+        ))
+    }
+
+    /// The PostScript name of the font, name 6 of the 'name' table.
+    pub fn postscript_name(&self) -> Option<String> {
+        let name = self.table(b"name")?;
+        let count = u16_at(name, 2)? as usize;
+        let strings = u16_at(name, 4)? as usize;
+        (0..count).find_map(|i| {
+            let record = 6 + i * 12;
+            if u16_at(name, record + 6)? != 6 {
+                return None;
+            }
+            let platform = u16_at(name, record)?;
+            let length = u16_at(name, record + 8)? as usize;
+            let offset = strings + u16_at(name, record + 10)? as usize;
+            let bytes = name.get(offset..offset + length)?;
+            match platform {
+                0 | 3 => Some(utf16_be_to_string(bytes)),
+                _ => Some(bytes.iter().map(|&b| b as char).collect()),
+            }
+        })
+    }
+}
+
+/// Reads the points of a simple glyph and converts its quadratic contours to
+/// cubic curves.
+fn append_simple_glyph(
+    data: &[u8],
+    contours: usize,
+    matrix: &Matrix,
+    path: &mut Path,
+) -> Option<()> {
+    const ON_CURVE: u8 = 0x01;
+    const X_SHORT: u8 = 0x02;
+    const Y_SHORT: u8 = 0x04;
+    const REPEAT: u8 = 0x08;
+    const X_SAME_OR_POSITIVE: u8 = 0x10;
+    const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+    let ends: Vec<usize> = (0..contours)
+        .map(|i| u16_at(data, 10 + i * 2).map(|e| e as usize))
+        .collect::<Option<_>>()?;
+    let count = ends.last().map_or(0, |&e| e + 1);
+    let instructions = u16_at(data, 10 + contours * 2)? as usize;
+    let mut offset = 12 + contours * 2 + instructions;
+
+    let mut flags = Vec::with_capacity(count);
+    while flags.len() < count {
+        let flag = *data.get(offset)?;
+        offset += 1;
+        flags.push(flag);
+        if flag & REPEAT != 0 {
+            let repeat = *data.get(offset)?;
+            offset += 1;
+            for _ in 0..repeat {
+                flags.push(flag);
+            }
+        }
+    }
+    flags.truncate(count);
+
+    let mut read_coordinates = |short: u8, same_or_positive: u8| -> Option<Vec<f64>> {
+        let mut value = 0i32;
+        let mut values = Vec::with_capacity(count);
+        for &flag in &flags {
+            if flag & short != 0 {
+                let delta = *data.get(offset)? as i32;
+                offset += 1;
+                value += if flag & same_or_positive != 0 {
+                    delta
+                } else {
+                    -delta
+                };
+            } else if flag & same_or_positive == 0 {
+                value += i16_at(data, offset)? as i32;
+                offset += 2;
+            }
+            values.push(value as f64);
+        }
+        Some(values)
+    };
+    let xs = read_coordinates(X_SHORT, X_SAME_OR_POSITIVE)?;
+    let ys = read_coordinates(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+    let mut start = 0;
+    for &end in &ends {
+        if end < start || end >= count {
+            return None;
+        }
+        let points: Vec<(Point, bool)> = (start..=end)
+            .map(|i| {
+                (
+                    matrix.apply(Point::new(xs[i], ys[i])),
+                    flags[i] & ON_CURVE != 0,
+                )
+            })
+            .collect();
+        append_quadratic_contour(&points, path);
+        start = end + 1;
+    }
+    Some(())
+}
+
+/// Appends a closed contour of on-curve points and quadratic control points.
+/// Two control points in a row have an implied on-curve point midway.
+fn append_quadratic_contour(points: &[(Point, bool)], path: &mut Path) {
+    let Some(first_on) = points.iter().position(|(_, on)| *on) else {
+        // Only control points: start midway between the first two.
+        if let [(a, _), (b, _), ..] = points {
+            let start = midpoint(*a, *b);
+            let rotated: Vec<_> = points[1..].iter().chain(&points[..1]).copied().collect();
+            append_from(start, &rotated, path);
+        }
+        return;
+    };
+    let start = points[first_on].0;
+    let rotated: Vec<_> = points[first_on + 1..]
+        .iter()
+        .chain(&points[..=first_on])
+        .copied()
+        .collect();
+    append_from(start, &rotated, path);
+}
+
+fn append_from(start: Point, points: &[(Point, bool)], path: &mut Path) {
+    path.move_to(start.x, start.y);
+    let mut current = start;
+    let mut control: Option<Point> = None;
+    let quad_to = |path: &mut Path, from: Point, c: Point, to: Point| {
+        let c1 = Point::new(
+            from.x + 2.0 / 3.0 * (c.x - from.x),
+            from.y + 2.0 / 3.0 * (c.y - from.y),
+        );
+        let c2 = Point::new(
+            to.x + 2.0 / 3.0 * (c.x - to.x),
+            to.y + 2.0 / 3.0 * (c.y - to.y),
+        );
+        path.curve_to(c1.x, c1.y, c2.x, c2.y, to.x, to.y);
+    };
+    for &(point, on) in points {
+        match (on, control) {
+            (true, None) => {
+                path.line_to(point.x, point.y);
+                current = point;
+            }
+            (true, Some(c)) => {
+                quad_to(path, current, c, point);
+                current = point;
+                control = None;
+            }
+            (false, None) => control = Some(point),
+            (false, Some(c)) => {
+                let middle = midpoint(c, point);
+                quad_to(path, current, c, middle);
+                current = middle;
+                control = Some(point);
+            }
+        }
+    }
+    if let Some(c) = control {
+        quad_to(path, current, c, start);
+    }
+    path.close();
+}
+
+fn midpoint(a: Point, b: Point) -> Point {
+    Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0)
+}
+
+/// The checksum of a table: the sum of its 32-bit words.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+/// Writes a font file from its tables, with the table directory, checksums and
+/// the 'head' table's checksum adjustment filled in.
+fn write_sfnt(mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+    tables.sort_by_key(|(tag, _)| *tag);
+    let count = tables.len() as u16;
+    let entry_selector = count.max(1).ilog2() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+    let version: u32 = match tables.iter().any(|(tag, _)| tag == b"CFF ") {
+        true => u32::from_be_bytes(*b"OTTO"),
+        false => 0x00010000,
+    };
+    let mut font = version.to_be_bytes().to_vec();
+    for value in [
+        count,
+        search_range,
+        entry_selector,
+        count * 16 - search_range,
+    ] {
+        font.extend(value.to_be_bytes());
+    }
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in &tables {
+        font.extend(tag);
+        font.extend(checksum(table).to_be_bytes());
+        font.extend((offset as u32).to_be_bytes());
+        font.extend((table.len() as u32).to_be_bytes());
+        offset += table.len().next_multiple_of(4);
+    }
+    let mut head = None;
+    for (tag, table) in &tables {
+        if tag == b"head" {
+            head = Some(font.len());
+        }
+        font.extend(table);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    if let Some(head) = head.filter(|head| head + 12 <= font.len()) {
+        let adjustment = 0xB1B0AFBAu32.wrapping_sub(checksum(&font));
+        font[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::api::font::cff::tests::sample_cff;
+
+    /// A TrueType font with the glyphs .notdef, a square for "A" and a composite
+    /// glyph of the square for "B", 600 units wide on a 1000 unit em square.
+    pub(crate) fn sample_font() -> Vec<u8> {
+        write_sfnt(sample_tables())
+    }
+
+    /// The same font with PostScript outlines, from `cff::tests::sample_cff`.
+    pub(crate) fn sample_otf() -> Vec<u8> {
+        let mut tables = sample_tables();
+        tables.retain(|(tag, _)| tag != b"glyf" && tag != b"loca");
+        tables.push((*b"CFF ", sample_cff()));
+        write_sfnt(tables)
+    }
+
+    fn sample_tables() -> Vec<([u8; 4], Vec<u8>)> {
+        let be16 = |values: &[i32]| -> Vec<u8> {
+            values
+                .iter()
+                .flat_map(|&v| (v as u16).to_be_bytes())
+                .collect()
+        };
+        // A closed contour of on-curve points with 16-bit coordinates.
+        let glyph = |points: &[(i32, i32)]| {
+            let (xs, ys): (Vec<i32>, Vec<i32>) = points.iter().copied().unzip();
+            let bounds = [
+                *xs.iter().min().unwrap(),
+                *ys.iter().min().unwrap(),
+                *xs.iter().max().unwrap(),
+                *ys.iter().max().unwrap(),
+            ];
+            let mut data = be16(&[1]);
+            data.extend(be16(&bounds));
+            data.extend(be16(&[points.len() as i32 - 1, 0]));
+            data.extend(vec![1u8; points.len()]);
+            let deltas = |values: &[i32]| {
+                let mut previous = 0;
+                values
+                    .iter()
+                    .map(|&v| std::mem::replace(&mut previous, v))
+                    .zip(values)
+                    .map(|(p, &v)| v - p)
+                    .collect::<Vec<_>>()
+            };
+            data.extend(be16(&deltas(&xs)));
+            data.extend(be16(&deltas(&ys)));
+            data
+        };
+        let glyphs = [
+            vec![],
+            glyph(&[(100, 0), (500, 0), (500, 700), (100, 700)]),
+            // One component, glyph 1, positioned at (0, 0).
+            be16(&[-1, 100, 0, 500, 700, 0x0002, 1, 0]),
+        ];
+        let mut glyf = vec![];
+        let mut loca = vec![];
+        for data in &glyphs {
+            loca.push(glyf.len() as i32 / 2);
+            glyf.extend(data);
+            if glyf.len() % 2 == 1 {
+                glyf.push(0);
+            }
+        }
+        loca.push(glyf.len() as i32 / 2);
+
+        let mut head = be16(&[1, 0, 1, 0, 0, 0, 0x5F0F, 0x3CF5, 0, 1000]);
+        head.extend([0; 16]);
+        head.extend(be16(&[0, 0, 600, 700, 0, 8, 2, 0, 0]));
+        let mut hhea = be16(&[1, 0, 800, -200, 0, 600, 0, 0, 600, 1, 0, 0]);
+        hhea.extend([0; 8]);
+        hhea.extend(be16(&[0, 3]));
+        let maxp = be16(&[0, 0x5000, 3]);
+        let hmtx = be16(&[500, 0, 600, 100, 600, 50]);
+        let mut cmap = be16(&[0, 1, 3, 1, 0, 12]);
+        cmap.extend(be16(&[4, 32, 0, 4, 4, 1, 0]));
+        cmap.extend(be16(&[0x42, 0xFFFF, 0, 0x41, 0xFFFF, 1 - 0x41, 1, 0, 0]));
+        let name_string: Vec<u8> = "TestFont"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        let mut name = be16(&[0, 1, 18, 3, 1, 0x409, 6, name_string.len() as i32, 0]);
+        name.extend(&name_string);
+
+        vec![
+            (*b"cmap", cmap),
+            (*b"glyf", glyf),
+            (*b"head", head),
+            (*b"hhea", hhea),
+            (*b"hmtx", hmtx),
+            (*b"loca", be16(&loca)),
+            (*b"maxp", maxp),
+            (*b"name", name),
+        ]
+    }
+
+    #[test]
+    fn test_sample_font() {
+        let font = TrueTypeFont::parse(sample_font()).unwrap();
+        assert_eq!(font.num_glyphs, 3);
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('B'), Some(2));
+        assert_eq!(font.glyph_index('C'), None);
+        assert_eq!(font.advance_width(2), Some(600.0));
+        assert_eq!(font.bbox(), Some([0.0, 0.0, 600.0, 700.0]));
+        assert_eq!(font.vertical_metrics(), Some((800.0, -200.0)));
+        assert_eq!(font.postscript_name().as_deref(), Some("TestFont"));
+        let square = Some([100.0, 0.0, 500.0, 700.0]);
+        assert_eq!(font.glyph_outline(2).unwrap().bounding_box(), square);
+    }
+
+    #[test]
+    fn test_subset() {
+        let font = TrueTypeFont::parse(sample_font()).unwrap();
+        let subset = TrueTypeFont::parse(font.subset(&BTreeSet::from([2])).unwrap()).unwrap();
+        assert_eq!(subset.num_glyphs, 3);
+        assert!(!subset.has_cmap(3, 1));
+        let square = Some([100.0, 0.0, 500.0, 700.0]);
+        assert_eq!(subset.glyph_outline(2).unwrap().bounding_box(), square);
+        assert_eq!(subset.glyph_outline(1).unwrap().bounding_box(), square);
+        let subset = TrueTypeFont::parse(font.subset(&BTreeSet::new()).unwrap()).unwrap();
+        assert!(subset.glyph_outline(1).unwrap().segments.is_empty());
+        assert_eq!(
+            checksum(&font.subset(&BTreeSet::new()).unwrap()),
+            0xB1B0AFBA
+        );
+
+        let font = TrueTypeFont::parse(sample_otf()).unwrap();
+        assert!(font.cff().is_some());
+        let subset = TrueTypeFont::parse(font.subset(&BTreeSet::from([1])).unwrap()).unwrap();
+        assert_eq!(subset.data().get(..4), Some(&b"OTTO"[..]));
+        assert!(subset.glyph_outline(2).unwrap().segments.is_empty());
+        assert!(!subset.glyph_outline(1).unwrap().segments.is_empty());
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+
+use crate::api::{
+    font::encoding::BaseEncoding,
+    geometry::{Matrix, Path, Point},
+};
+
+/// Subroutine calls nest at most this deep.
+const MAX_SUBR_DEPTH: usize = 10;
+
+/// The keys of the encryption applied to the private part of the font, and to
+/// each charstring. See sec. 7 of the Adobe Type 1 Font Format.
+const EEXEC_KEY: u16 = 55665;
+const CHARSTRING_KEY: u16 = 4330;
+
+/// Decrypts eexec or charstring encrypted data. The first bytes of the result
+/// are random and left for the caller to skip.
+pub fn decrypt(data: &[u8], key: u16) -> Vec<u8> {
+    let mut r = key;
+    data.iter()
+        .map(|&c| {
+            let plain = c ^ (r >> 8) as u8;
+            r = (c as u16)
+                .wrapping_add(r)
+                .wrapping_mul(52845)
+                .wrapping_add(22719);
+            plain
+        })
+        .collect()
+}
+
+///
+/// A Type 1 font program, as embedded with /FontFile. The cleartext part gives
+/// the font matrix and built-in encoding; the eexec encrypted part holds the
+/// subroutines and charstrings. See sec. 9.9 (pg. 341) and the Adobe Type 1
+/// Font Format.
+///
+#[derive(Debug, Clone)]
+pub struct Type1Font {
+    pub font_matrix: Matrix,
+    /// The glyph name of each code of the built-in encoding; None if the font
+    /// uses StandardEncoding.
+    pub encoding: Option<HashMap<u8, String>>,
+    subrs: Vec<Vec<u8>>,
+    char_strings: HashMap<String, Vec<u8>>,
+}
+
+/// Splits PostScript source into tokens, with access to the binary data that
+/// follows RD tokens.
+struct Lexer<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn token(&mut self) -> Option<&'a [u8]> {
+        while let Some(&b) = self.data.get(self.pos) {
+            if b == b'%' {
+                while self
+                    .data
+                    .get(self.pos)
+                    .is_some_and(|&b| b != b'\n' && b != b'\r')
+                {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() || b == 0 {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let start = self.pos;
+        let first = *self.data.get(self.pos)?;
+        self.pos += 1;
+        if matches!(first, b'[' | b']' | b'{' | b'}') {
+            return Some(&self.data[start..self.pos]);
+        }
+        while let Some(&b) = self.data.get(self.pos) {
+            if b.is_ascii_whitespace() || b"[]{}/%()<>".contains(&b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        Some(&self.data[start..self.pos])
+    }
+
+    fn number(&mut self) -> Option<f64> {
+        std::str::from_utf8(self.token()?).ok()?.parse().ok()
+    }
+
+    /// Reads `length RD <binary>`, where RD (or -|) is followed by one space
+    /// and then the binary data. Restores the position if the tokens differ.
+    fn binary(&mut self) -> Option<&'a [u8]> {
+        let start = self.pos;
+        let result = (|| {
+            let length = self.number()? as usize;
+            if !matches!(self.token()?, b"RD" | b"-|") {
+                return None;
+            }
+            let data = self.data.get(self.pos + 1..self.pos + 1 + length)?;
+            self.pos += 1 + length;
+            Some(data)
+        })();
+        if result.is_none() {
+            self.pos = start;
+        }
+        result
+    }
+}
+
+/// Removes the segment headers of the PFB format, which some producers embed.
+fn strip_pfb(data: &[u8]) -> Vec<u8> {
+    let mut result = vec![];
+    let mut rest = data;
+    while let [0x80, 1 | 2, a, b, c, d, tail @ ..] = rest {
+        let length = u32::from_le_bytes([*a, *b, *c, *d]) as usize;
+        result.extend_from_slice(&tail[..length.min(tail.len())]);
+        rest = &tail[length.min(tail.len())..];
+    }
+    result
+}
+
+impl Type1Font {
+    pub fn parse(data: &[u8]) -> Option<Type1Font> {
+        let stripped;
+        let data = if data.first() == Some(&0x80) {
+            stripped = strip_pfb(data);
+            &stripped[..]
+        } else {
+            data
+        };
+        let eexec = data.windows(5).position(|w| w == b"eexec")?;
+        let mut font = Type1Font {
+            font_matrix: Matrix::scale(0.001, 0.001),
+            encoding: None,
+            subrs: vec![],
+            char_strings: HashMap::new(),
+        };
+        font.read_cleartext(&data[..eexec]);
+
+        let mut start = eexec + 5;
+        while data.get(start).is_some_and(|b| b.is_ascii_whitespace()) {
+            start += 1;
+        }
+        let encrypted = &data[start..];
+        // The encrypted part is binary unless its first bytes are hex digits.
+        let is_hex = encrypted.len() >= 4 && encrypted[..4].iter().all(u8::is_ascii_hexdigit);
+        let encrypted = if is_hex {
+            let digits: Vec<u8> = encrypted
+                .iter()
+                .filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8))
+                .collect();
+            digits
+                .chunks(2)
+                .map(|p| p[0] << 4 | p.get(1).unwrap_or(&0))
+                .collect()
+        } else {
+            encrypted.to_vec()
+        };
+        let private = decrypt(&encrypted, EEXEC_KEY);
+        font.read_private(private.get(4..)?);
+        Some(font)
+    }
+
+    fn read_cleartext(&mut self, data: &[u8]) {
+        let mut lexer = Lexer { data, pos: 0 };
+        while let Some(token) = lexer.token() {
+            match token {
+                b"/FontMatrix" => {
+                    if !matches!(lexer.token(), Some(b"[" | b"{")) {
+                        continue;
+                    }
+                    let values: Vec<f64> = (0..6).filter_map(|_| lexer.number()).collect();
+                    if let Some(matrix) = Matrix::from_slice(&values) {
+                        self.font_matrix = matrix;
+                    }
+                }
+                b"/Encoding" => {
+                    if lexer.token() == Some(b"StandardEncoding") {
+                        continue;
+                    }
+                    // A custom encoding is built by "dup code /name put" entries.
+                    let mut encoding = HashMap::new();
+                    while let Some(token) = lexer.token() {
+                        match token {
+                            b"dup" => {
+                                let Some(code) = lexer.number() else { continue };
+                                let Some(name) = lexer.token() else { break };
+                                if let (Some(name), Ok(code)) =
+                                    (name.strip_prefix(b"/"), u8::try_from(code as i64))
+                                {
+                                    encoding
+                                        .insert(code, String::from_utf8_lossy(name).into_owned());
+                                }
+                            }
+                            b"def" | b"readonly" => break,
+                            _ => {}
+                        }
+                    }
+                    self.encoding = Some(encoding);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads /lenIV, /Subrs and /CharStrings from the decrypted private part,
+    /// then decrypts the charstrings.
+    fn read_private(&mut self, data: &[u8]) {
+        let mut lexer = Lexer { data, pos: 0 };
+        let mut len_iv = 4;
+        let mut in_char_strings = false;
+        let mut subrs = vec![];
+        while let Some(token) = lexer.token() {
+            if token == b"/lenIV" {
+                len_iv = lexer.number().unwrap_or(4.0) as i64;
+            } else if token == b"/CharStrings" {
+                in_char_strings = true;
+            } else if token == b"dup" && !in_char_strings {
+                let start = lexer.pos;
+                if let Some(index) = lexer.number() {
+                    match lexer.binary() {
+                        Some(data) => subrs.push((index as usize, data)),
+                        None => lexer.pos = start,
+                    }
+                }
+            } else if in_char_strings && token.starts_with(b"/") {
+                if let Some(data) = lexer.binary() {
+                    let name = String::from_utf8_lossy(&token[1..]).into_owned();
+                    self.char_strings.insert(name, data.to_vec());
+                }
+            }
+        }
+
+        let decode = |data: &[u8]| match usize::try_from(len_iv) {
+            Ok(skip) => decrypt(data, CHARSTRING_KEY)
+                .get(skip..)
+                .unwrap_or_default()
+                .to_vec(),
+            Err(_) => data.to_vec(),
+        };
+        let count = subrs.iter().map(|(i, _)| i + 1).max().unwrap_or(0);
+        self.subrs = vec![vec![]; count.min(0x10000)];
+        for (index, data) in subrs {
+            if let Some(subr) = self.subrs.get_mut(index) {
+                *subr = decode(data);
+            }
+        }
+        for data in self.char_strings.values_mut() {
+            *data = decode(data);
+        }
+    }
+
+    /// The glyph name for a code in the font's built-in encoding.
+    pub fn glyph_name(&self, code: u8) -> Option<String> {
+        match &self.encoding {
+            Some(encoding) => encoding.get(&code).cloned(),
+            None => BaseEncoding::Standard.glyph_name(code),
+        }
+    }
+
+    pub fn has_glyph(&self, name: &str) -> bool {
+        self.char_strings.contains_key(name)
+    }
+
+    /// The outline of a named glyph, scaled so that the em square is 1000 units.
+    pub fn glyph_outline(&self, name: &str) -> Option<Path> {
+        let mut interpreter = CharStringInterpreter {
+            font: self,
+            stack: vec![],
+            ps_stack: vec![],
+            path: Path::new(),
+            point: Point::new(0.0, 0.0),
+            side_bearing: Point::new(0.0, 0.0),
+            flex: None,
+            open: false,
+        };
+        interpreter.run(self.char_strings.get(name)?, 0)?;
+        interpreter.close();
+        let scale = self.font_matrix.multiply(&Matrix::scale(1000.0, 1000.0));
+        Some(interpreter.path.transform(&scale))
+    }
+}
+
+/// Runs Type 1 charstrings, building the glyph's path. Hints are skipped.
+/// See sec. 6 of the Adobe Type 1 Font Format.
+struct CharStringInterpreter<'a> {
+    font: &'a Type1Font,
+    stack: Vec<f64>,
+    /// Values passed back from OtherSubrs, taken by pop.
+    ps_stack: Vec<f64>,
+    path: Path,
+    point: Point,
+    side_bearing: Point,
+    /// The points collected between the start and end of a flex.
+    flex: Option<Vec<Point>>,
+    open: bool,
+}
+
+enum Stop {
+    Return,
+    EndChar,
+}
+
+impl CharStringInterpreter<'_> {
+    fn close(&mut self) {
+        if self.open {
+            self.path.close();
+            self.open = false;
+        }
+    }
+
+    fn move_to(&mut self, dx: f64, dy: f64) {
+        self.point = Point::new(self.point.x + dx, self.point.y + dy);
+        // Inside a flex, moves only record the points of the two curves.
+        if self.flex.is_some() {
+            return;
+        }
+        self.close();
+        self.path.move_to(self.point.x, self.point.y);
+        self.open = true;
+    }
+
+    fn line_to(&mut self, dx: f64, dy: f64) {
+        self.point = Point::new(self.point.x + dx, self.point.y + dy);
+        self.path.line_to(self.point.x, self.point.y);
+    }
+
+    fn curve_to(&mut self, d: [f64; 6]) {
+        let c1 = Point::new(self.point.x + d[0], self.point.y + d[1]);
+        let c2 = Point::new(c1.x + d[2], c1.y + d[3]);
+        self.point = Point::new(c2.x + d[4], c2.y + d[5]);
+        self.path
+            .curve_to(c1.x, c1.y, c2.x, c2.y, self.point.x, self.point.y);
+    }
+
+    fn run(&mut self, code: &[u8], depth: usize) -> Option<Stop> {
+        if depth > MAX_SUBR_DEPTH {
+            return None;
+        }
+        let mut i = 0;
+        while i < code.len() {
+            let v = code[i];
+            i += 1;
+            match v {
+                32..=246 => self.stack.push(v as f64 - 139.0),
+                247..=250 => {
+                    let w = *code.get(i)? as f64;
+                    i += 1;
+                    self.stack.push((v as f64 - 247.0) * 256.0 + w + 108.0);
+                }
+                251..=254 => {
+                    let w = *code.get(i)? as f64;
+                    i += 1;
+                    self.stack.push(-(v as f64 - 251.0) * 256.0 - w - 108.0);
+                }
+                255 => {
+                    let bytes = code.get(i..i + 4)?;
+                    self.stack
+                        .push(i32::from_be_bytes(bytes.try_into().ok()?) as f64);
+                    i += 4;
+                }
+                12 => {
+                    let operator = *code.get(i)?;
+                    i += 1;
+                    if let Some(stop) = self.escape(operator, depth)? {
+                        return Some(stop);
+                    }
+                }
+                _ => {
+                    if let Some(stop) = self.command(v, depth)? {
+                        return Some(stop);
+                    }
+                }
+            }
+        }
+        Some(Stop::Return)
+    }
+
+    fn command(&mut self, operator: u8, depth: usize) -> Option<Option<Stop>> {
+        let s = std::mem::take(&mut self.stack);
+        let arg = |i: usize| s.get(i).copied().unwrap_or(0.0);
+        match operator {
+            // hsbw
+            13 => {
+                self.side_bearing = Point::new(arg(0), 0.0);
+                self.point = self.side_bearing;
+            }
+            9 => self.close(),
+            21 => self.move_to(arg(0), arg(1)),
+            22 => self.move_to(arg(0), 0.0),
+            4 => self.move_to(0.0, arg(0)),
+            5 => self.line_to(arg(0), arg(1)),
+            6 => self.line_to(arg(0), 0.0),
+            7 => self.line_to(0.0, arg(0)),
+            8 => self.curve_to([arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)]),
+            30 => self.curve_to([0.0, arg(0), arg(1), arg(2), arg(3), 0.0]),
+            31 => self.curve_to([arg(0), 0.0, arg(1), arg(2), 0.0, arg(3)]),
+            10 => {
+                let mut s = s;
+                let index = s.pop()? as usize;
+                self.stack = s;
+                let font = self.font;
+                if let Stop::EndChar = self.run(font.subrs.get(index)?, depth + 1)? {
+                    return Some(Some(Stop::EndChar));
+                }
+            }
+            11 => {
+                self.stack = s;
+                return Some(Some(Stop::Return));
+            }
+            14 => return Some(Some(Stop::EndChar)),
+            // hstem and vstem
+            1 | 3 => {}
+            _ => {}
+        }
+        Some(None)
+    }
+
+    fn escape(&mut self, operator: u8, depth: usize) -> Option<Option<Stop>> {
+        let mut s = std::mem::take(&mut self.stack);
+        let arg = |i: usize| s.get(i).copied().unwrap_or(0.0);
+        match operator {
+            // sbw
+            7 => {
+                self.side_bearing = Point::new(arg(0), arg(1));
+                self.point = self.side_bearing;
+            }
+            // seac
+            6 => {
+                let (asb, adx, ady) = (arg(0), arg(1), arg(2));
+                self.seac(asb, adx, ady, arg(3), arg(4), depth)?;
+                return Some(Some(Stop::EndChar));
+            }
+            // div
+            12 => {
+                let b = s.pop()?;
+                let a = s.pop()?;
+                s.push(if b == 0.0 { 0.0 } else { a / b });
+                self.stack = s;
+            }
+            // callothersubr
+            16 => {
+                let number = s.pop()? as i64;
+                let count = (s.pop()? as usize).min(s.len());
+                let args = s.split_off(s.len() - count);
+                self.stack = s;
+                self.other_subr(number, args);
+            }
+            // pop
+            17 => {
+                self.stack = s;
+                let value = self.ps_stack.pop().unwrap_or(0.0);
+                self.stack.push(value);
+            }
+            // setcurrentpoint
+            33 => self.point = Point::new(arg(0), arg(1)),
+            // dotsection, vstem3 and hstem3
+            _ => {}
+        }
+        Some(None)
+    }
+
+    /// The OtherSubrs used by flex and hint replacement. See sec. 8 of the
+    /// Adobe Type 1 Font Format.
+    fn other_subr(&mut self, number: i64, args: Vec<f64>) {
+        match number {
+            // Start of a flex.
+            1 => self.flex = Some(vec![]),
+            // A flex point, reached by the preceding rmoveto.
+            2 => {
+                if let Some(flex) = &mut self.flex {
+                    flex.push(self.point);
+                }
+            }
+            // End of a flex: the first point is a reference, then two curves.
+            0 => {
+                if let Some(flex) = self.flex.take() {
+                    if let [_, c1, c2, p1, c3, c4, p2] = flex[..] {
+                        self.path.curve_to(c1.x, c1.y, c2.x, c2.y, p1.x, p1.y);
+                        self.path.curve_to(c3.x, c3.y, c4.x, c4.y, p2.x, p2.y);
+                    }
+                }
+                // The end point is popped and passed to setcurrentpoint.
+                let (x, y) = (args.get(1).copied(), args.get(2).copied());
+                self.ps_stack = vec![y.unwrap_or(self.point.y), x.unwrap_or(self.point.x)];
+            }
+            // Hint replacement returns its subroutine number to be called.
+            _ => self.ps_stack = args.into_iter().rev().collect(),
+        }
+    }
+
+    /// Composes an accented character from two glyphs named by their
+    /// StandardEncoding codes, shifting the accent by (adx, ady) relative to
+    /// the base glyph after aligning their side bearings.
+    fn seac(
+        &mut self,
+        asb: f64,
+        adx: f64,
+        ady: f64,
+        bchar: f64,
+        achar: f64,
+        depth: usize,
+    ) -> Option<()> {
+        let font = self.font;
+        let glyph = |code: f64| {
+            let name = BaseEncoding::Standard.glyph_name(code as u8)?;
+            font.char_strings.get(&name)
+        };
+        let (base, accent) = (glyph(bchar)?, glyph(achar)?);
+        self.close();
+        self.run(base, depth + 1)?;
+        self.close();
+        let base_path = std::mem::take(&mut self.path);
+        let base_side_bearing = self.side_bearing.x;
+        self.stack.clear();
+        self.run(accent, depth + 1)?;
+        self.close();
+        let accent_path = std::mem::replace(&mut self.path, base_path);
+        let offset = Matrix::translate(adx - asb + base_side_bearing, ady);
+        self.path
+            .segments
+            .extend(accent_path.transform(&offset).segments);
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(data: &[u8], key: u16) -> Vec<u8> {
+        let mut r = key;
+        data.iter()
+            .map(|&p| {
+                let c = p ^ (r >> 8) as u8;
+                r = (c as u16)
+                    .wrapping_add(r)
+                    .wrapping_mul(52845)
+                    .wrapping_add(22719);
+                c
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decrypt() {
+        let data = b"\x00\x00\x00\x00/lenIV 4 def";
+        assert_eq!(decrypt(&encrypt(data, EEXEC_KEY), EEXEC_KEY), data);
+    }
+
+    #[test]
+    fn test_parse_font() {
+        // A square glyph: 0 500 hsbw 100 100 rmoveto 300 hlineto 300 vlineto
+        // -300 hlineto closepath endchar, with four leading random bytes.
+        let glyph = [
+            0, 0, 0, 0, 139, 248, 136, 13, 239, 239, 21, 247, 192, 6, 247, 192, 7, 251, 192, 6, 9,
+            14,
+        ];
+        let glyph = encrypt(&glyph, CHARSTRING_KEY);
+        let mut private =
+            b"\0\0\0\0/Private 2 dict dup begin /CharStrings 1 dict dup begin\n/A ".to_vec();
+        private.extend(format!("{} RD ", glyph.len()).bytes());
+        private.extend(&glyph);
+        private.extend(b" ND\nend end");
+
+        let mut data = b"%!FontType1\n/FontMatrix [0.001 0 0 0.001 0 0] readonly def\n\
+            /Encoding 256 array\ndup 65 /A put\nreadonly def\ncurrentfile eexec\n"
+            .to_vec();
+        data.extend(encrypt(&private, EEXEC_KEY));
+
+        let font = Type1Font::parse(&data).unwrap();
+        assert_eq!(font.glyph_name(65), Some("A".to_string()));
+        let outline = font.glyph_outline("A").unwrap();
+        assert_eq!(outline.bounding_box(), Some([100.0, 100.0, 400.0, 400.0]));
+    }
+}
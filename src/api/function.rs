@@ -0,0 +1,389 @@
+/// The PostScript calculator language of type 4 functions.
+pub mod postscript;
+
+use crate::api::{document::Document, objects::PdfObject};
+
+use postscript::Program;
+
+/// Stitching functions may contain other stitching functions; deeper nesting
+/// than this is not loaded.
+const MAX_DEPTH: usize = 8;
+
+/// Sampled functions with more inputs than this are not loaded: each
+/// evaluation interpolates between 2^m samples.
+const MAX_INPUTS: usize = 16;
+
+///
+/// A PDF function, mapping m input values to n output values. Functions are
+/// used for shadings, tint transforms and transfer functions.
+/// See sec. 7.10 (pg. 106)
+///
+/// Every function clips its inputs to its /Domain, and its outputs to its
+/// /Range when one is given.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub domain: Vec<f64>,
+    pub range: Option<Vec<f64>>,
+    pub kind: FunctionKind,
+}
+
+///
+/// The four types of functions. See sec. 7.10.1, Table 38 (pg. 106)
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum FunctionKind {
+    /// Type 0: a table of samples, interpolated linearly. See sec. 7.10.2
+    /// (pg. 107)
+    Sampled {
+        size: Vec<usize>,
+        encode: Vec<f64>,
+        decode: Vec<f64>,
+        /// The samples scaled to 0.0–1.0, with the outputs of each sample
+        /// adjacent and the first input varying fastest.
+        samples: Vec<f64>,
+    },
+    /// Type 2: C0 + x^N × (C1 − C0). See sec. 7.10.3 (pg. 109)
+    Exponential { c0: Vec<f64>, c1: Vec<f64>, n: f64 },
+    /// Type 3: one of several functions, chosen by which subdomain of /Bounds
+    /// the input lies in. See sec. 7.10.4 (pg. 110)
+    Stitching {
+        functions: Vec<Function>,
+        bounds: Vec<f64>,
+        encode: Vec<f64>,
+    },
+    /// Type 4: a program in a subset of PostScript. See sec. 7.10.5 (pg. 111)
+    PostScript(Program),
+}
+
+impl Function {
+    /// Reads a function from a dictionary (types 2 and 3) or a stream (types 0
+    /// and 4).
+    pub fn load(doc: &Document, object: &PdfObject) -> Option<Function> {
+        Function::load_nested(doc, object, 0)
+    }
+
+    fn load_nested(doc: &Document, object: &PdfObject, depth: usize) -> Option<Function> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let object = doc.resolve(object);
+        let dict = match object {
+            PdfObject::Dictionary(dict) => dict,
+            PdfObject::Stream(stream) => &stream.dict,
+            _ => return None,
+        };
+        let numbers = |key: &str| doc.resolve_key(dict, key).and_then(PdfObject::as_numbers);
+        let domain = numbers("Domain").filter(|d| d.len() >= 2)?;
+        let range = numbers("Range").filter(|r| r.len() >= 2);
+
+        let kind = match dict.get_i64("FunctionType")? {
+            0 => {
+                let stream = object.as_stream()?;
+                let range = range.as_ref()?;
+                let size: Vec<usize> = numbers("Size")?
+                    .iter()
+                    .map(|&s| (s.max(1.0) as usize).min(1 << 16))
+                    .collect();
+                if size.len() * 2 != domain.len() || size.len() > MAX_INPUTS {
+                    return None;
+                }
+                let encode = numbers("Encode")
+                    .filter(|e| e.len() == size.len() * 2)
+                    .unwrap_or_else(|| size.iter().flat_map(|&s| [0.0, (s - 1) as f64]).collect());
+                let decode = numbers("Decode")
+                    .filter(|d| d.len() == range.len())
+                    .unwrap_or_else(|| range.clone());
+                let bits = dict.get_i64("BitsPerSample")? as usize;
+                let data = doc.decode_stream(stream).ok()?;
+                let count = size
+                    .iter()
+                    .try_fold(range.len() / 2, |count, &s| count.checked_mul(s))?;
+                FunctionKind::Sampled {
+                    samples: read_samples(&data, bits, count)?,
+                    size,
+                    encode,
+                    decode,
+                }
+            }
+            2 => FunctionKind::Exponential {
+                c0: numbers("C0").unwrap_or(vec![0.0]),
+                c1: numbers("C1").unwrap_or(vec![1.0]),
+                n: doc.resolve_key(dict, "N")?.as_f64()?,
+            },
+            3 => {
+                let functions = doc
+                    .resolve_key(dict, "Functions")?
+                    .as_array()?
+                    .iter()
+                    .map(|f| Function::load_nested(doc, f, depth + 1))
+                    .collect::<Option<Vec<_>>>()?;
+                let bounds = numbers("Bounds").unwrap_or_default();
+                let encode = numbers("Encode").unwrap_or_default();
+                if functions.is_empty()
+                    || bounds.len() + 1 != functions.len()
+                    || encode.len() != functions.len() * 2
+                {
+                    return None;
+                }
+                FunctionKind::Stitching {
+                    functions,
+                    bounds,
+                    encode,
+                }
+            }
+            4 => {
+                let data = doc.decode_stream(object.as_stream()?).ok()?;
+                FunctionKind::PostScript(Program::parse(&data)?)
+            }
+            _ => return None,
+        };
+        Some(Function {
+            domain,
+            range,
+            kind,
+        })
+    }
+
+    /// Evaluates the function. Missing inputs are taken to be 0.
+    pub fn eval(&self, inputs: &[f64]) -> Vec<f64> {
+        let inputs: Vec<f64> = self
+            .domain
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(i, d)| clip(inputs.get(i).copied().unwrap_or(0.0), d[0], d[1]))
+            .collect();
+        let mut outputs = match &self.kind {
+            FunctionKind::Sampled {
+                size,
+                encode,
+                decode,
+                samples,
+            } => {
+                let outputs = decode.len() / 2;
+                let values =
+                    interpolate_samples(&inputs, &self.domain, size, encode, samples, outputs);
+                values
+                    .iter()
+                    .zip(decode.chunks_exact(2))
+                    .map(|(v, d)| d[0] + v * (d[1] - d[0]))
+                    .collect()
+            }
+            FunctionKind::Exponential { c0, c1, n } => {
+                let x = inputs[0];
+                c0.iter()
+                    .zip(c1)
+                    .map(|(a, b)| a + x.powf(*n) * (b - a))
+                    .collect()
+            }
+            FunctionKind::Stitching {
+                functions,
+                bounds,
+                encode,
+            } => {
+                let x = inputs[0];
+                let i = bounds.iter().take_while(|&&b| x >= b).count();
+                let low = if i == 0 {
+                    self.domain[0]
+                } else {
+                    bounds[i - 1]
+                };
+                let high = bounds.get(i).copied().unwrap_or(self.domain[1]);
+                let x = interpolate(x, low, high, encode[2 * i], encode[2 * i + 1]);
+                functions[i].eval(&[x])
+            }
+            FunctionKind::PostScript(program) => {
+                let outputs = self.range.as_ref().map_or(0, |r| r.len() / 2);
+                program.run(&inputs, outputs)
+            }
+        };
+        if let Some(range) = &self.range {
+            for (value, r) in outputs.iter_mut().zip(range.chunks_exact(2)) {
+                *value = clip(*value, r[0], r[1]);
+            }
+        }
+        outputs
+    }
+}
+
+/// Reads the functions of a shading or tint transform: either a single
+/// function, or an array of one-output functions whose results are combined.
+pub fn load_functions(doc: &Document, object: &PdfObject) -> Option<Vec<Function>> {
+    match doc.resolve(object) {
+        PdfObject::Array(functions) => functions.iter().map(|f| Function::load(doc, f)).collect(),
+        function => Function::load(doc, function).map(|f| vec![f]),
+    }
+}
+
+/// Evaluates the functions read by `load_functions`.
+pub fn eval_functions(functions: &[Function], inputs: &[f64]) -> Vec<f64> {
+    match functions {
+        [function] => function.eval(inputs),
+        _ => functions
+            .iter()
+            .filter_map(|f| f.eval(inputs).first().copied())
+            .collect(),
+    }
+}
+
+fn clip(x: f64, low: f64, high: f64) -> f64 {
+    x.max(low.min(high)).min(high.max(low))
+}
+
+/// Maps x from the interval [x_min, x_max] to [y_min, y_max].
+/// See sec. 7.10.2 (pg. 108)
+pub fn interpolate(x: f64, x_min: f64, x_max: f64, y_min: f64, y_max: f64) -> f64 {
+    if x_max == x_min {
+        return y_min;
+    }
+    y_min + (x - x_min) * (y_max - y_min) / (x_max - x_min)
+}
+
+/// Unpacks samples of the given bit width, most significant bit first, and
+/// scales them to 0.0–1.0.
+fn read_samples(data: &[u8], bits: usize, count: usize) -> Option<Vec<f64>> {
+    let length = count.checked_mul(bits)?;
+    if ![1, 2, 4, 8, 12, 16, 24, 32].contains(&bits) || data.len().saturating_mul(8) < length {
+        return None;
+    }
+    let max = ((1u64 << bits) - 1) as f64;
+    Some(
+        (0..count)
+            .map(|i| {
+                let mut value = 0u64;
+                for bit in i * bits..(i + 1) * bits {
+                    value = value << 1 | ((data[bit / 8] >> (7 - bit % 8)) & 1) as u64;
+                }
+                value as f64 / max
+            })
+            .collect(),
+    )
+}
+
+/// Multilinear interpolation between the samples surrounding the encoded
+/// inputs.
+fn interpolate_samples(
+    inputs: &[f64],
+    domain: &[f64],
+    size: &[usize],
+    encode: &[f64],
+    samples: &[f64],
+    outputs: usize,
+) -> Vec<f64> {
+    let m = size.len();
+    // The sample below each encoded input, and the fraction towards the next.
+    let positions: Vec<(usize, f64)> = (0..m)
+        .map(|i| {
+            let e = interpolate(
+                inputs[i],
+                domain[2 * i],
+                domain[2 * i + 1],
+                encode[2 * i],
+                encode[2 * i + 1],
+            );
+            let e = e.clamp(0.0, (size[i] - 1) as f64);
+            let low = (e.floor() as usize).min(size[i].saturating_sub(2));
+            (low, e - low as f64)
+        })
+        .collect();
+
+    let mut result = vec![0.0; outputs];
+    for corner in 0..1usize << m {
+        let mut weight = 1.0;
+        let mut index = 0;
+        let mut stride = 1;
+        for (i, &(low, fraction)) in positions.iter().enumerate() {
+            let upper = corner >> i & 1 == 1;
+            weight *= if upper { fraction } else { 1.0 - fraction };
+            let position = if upper {
+                (low + 1).min(size[i] - 1)
+            } else {
+                low
+            };
+            index += position * stride;
+            stride *= size[i];
+        }
+        if weight == 0.0 {
+            continue;
+        }
+        for (j, value) in result.iter_mut().enumerate() {
+            *value += weight * samples.get(index * outputs + j).copied().unwrap_or(0.0);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        objects::{Dictionary, Stream},
+        read::objects::parse_dictionary,
+    };
+
+    fn dictionary(source: &str) -> Dictionary {
+        parse_dictionary(source.as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn test_exponential_and_stitching() {
+        let doc = Document::from_bytes(b"%PDF-1.7\ntrailer << >>\n".to_vec()).unwrap();
+        let function = Function::load(
+            &doc,
+            &PdfObject::Dictionary(dictionary(
+                "<< /FunctionType 2 /Domain [0 1] /C0 [1 0 0] /C1 [0 0 1] /N 2 >>",
+            )),
+        )
+        .unwrap();
+        assert_eq!(function.eval(&[0.5]), vec![0.75, 0.0, 0.25]);
+        assert_eq!(function.eval(&[2.0]), vec![0.0, 0.0, 1.0]);
+
+        // Rises from 0 to 1 over [0 0.5], then falls back over [0.5 1].
+        let function = Function::load(
+            &doc,
+            &PdfObject::Dictionary(dictionary(
+                "<< /FunctionType 3 /Domain [0 1] /Bounds [0.5] /Encode [0 1 1 0] \
+                 /Functions [<< /FunctionType 2 /Domain [0 1] /N 1 >> \
+                             << /FunctionType 2 /Domain [0 1] /N 1 >>] >>",
+            )),
+        )
+        .unwrap();
+        assert_eq!(function.eval(&[0.25]), vec![0.5]);
+        assert_eq!(function.eval(&[0.75]), vec![0.5]);
+        assert_eq!(function.eval(&[0.5]), vec![1.0]);
+    }
+
+    #[test]
+    fn test_sampled() {
+        // Two inputs, one output, 2x2 samples: 0 and 255 on the first row and
+        // 255 and 0 on the second.
+        let samples = interpolate_samples(
+            &[0.5, 0.25],
+            &[0.0, 1.0, 0.0, 1.0],
+            &[2, 2],
+            &[0.0, 1.0, 0.0, 1.0],
+            &[0.0, 1.0, 1.0, 0.0],
+            1,
+        );
+        assert_eq!(samples, vec![0.5]);
+        assert_eq!(read_samples(&[0xF0, 0x00], 4, 3), Some(vec![1.0, 0.0, 0.0]));
+        assert_eq!(read_samples(&[0xF0], 32, usize::MAX), None);
+
+        // Sample tables too large to count, and functions with too many
+        // inputs to interpolate, are not loaded.
+        let doc = Document::from_bytes(b"%PDF-1.7\ntrailer << >>\n".to_vec()).unwrap();
+        let sampled = |inputs: usize, size: usize| {
+            let dict = dictionary(&format!(
+                "<< /FunctionType 0 /Domain [{}] /Range [0 1] /Size [{}] /BitsPerSample 8 >>",
+                "0 1 ".repeat(inputs),
+                format!("{} ", size).repeat(inputs)
+            ));
+            let stream = PdfObject::Stream(Stream::new(dict, vec![0; 16]));
+            Function::load(&doc, &stream)
+        };
+        assert!(sampled(1, 16).is_some());
+        assert!(sampled(4, 65536).is_none());
+        assert!(sampled(40, 1).is_none());
+        assert!(sampled(64, 1).is_none());
+    }
+}
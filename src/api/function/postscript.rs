@@ -0,0 +1,308 @@
+/// The operand stack of a calculator program holds at most this many values.
+/// See sec. 7.10.5.1 (pg. 111)
+const MAX_STACK: usize = 100;
+
+///
+/// A type 4 function program: PostScript operators, numbers and the procedure
+/// blocks of "if" and "ifelse". See sec. 7.10.5, Table 42 (pg. 112)
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Instruction {
+    Number(f64),
+    Bool(bool),
+    Operator(String),
+    If(Vec<Instruction>),
+    IfElse(Vec<Instruction>, Vec<Instruction>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Value {
+    Number(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn number(self) -> f64 {
+        match self {
+            Value::Number(n) => n,
+            Value::Bool(b) => b as i64 as f64,
+        }
+    }
+
+    fn int(self) -> i64 {
+        self.number() as i64
+    }
+}
+
+impl Program {
+    /// Parses the program text: a procedure in braces.
+    pub fn parse(source: &[u8]) -> Option<Program> {
+        let tokens = tokenize(source);
+        let mut tokens = tokens.iter().map(String::as_str).peekable();
+        // Skip anything before the opening brace.
+        while tokens.next_if(|&t| t != "{").is_some() {}
+        tokens.next()?;
+        let instructions = parse_block(&mut tokens)?;
+        Some(Program { instructions })
+    }
+
+    /// Runs the program with the inputs on the stack, returning the values left
+    /// on it. Errors end the program early, and missing outputs are 0.
+    pub fn run(&self, inputs: &[f64], outputs: usize) -> Vec<f64> {
+        let mut stack: Vec<Value> = inputs.iter().map(|&x| Value::Number(x)).collect();
+        let _ = execute(&self.instructions, &mut stack);
+        let mut values: Vec<f64> = stack.iter().map(|v| v.number()).collect();
+        if values.len() > outputs {
+            values.drain(..values.len() - outputs);
+        }
+        values.resize(outputs, 0.0);
+        values
+    }
+}
+
+fn tokenize(source: &[u8]) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut comment = false;
+    for &b in source {
+        if comment {
+            comment = b != b'\n' && b != b'\r';
+            continue;
+        }
+        if b.is_ascii_whitespace() || b == b'{' || b == b'}' || b == b'%' {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            match b {
+                b'{' | b'}' => tokens.push((b as char).to_string()),
+                b'%' => comment = true,
+                _ => {}
+            }
+        } else {
+            current.push(b as char);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Parses instructions up to the closing brace of a block.
+fn parse_block<'a>(
+    tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+) -> Option<Vec<Instruction>> {
+    let mut instructions = vec![];
+    // Blocks waiting for the "if" or "ifelse" that follows them.
+    let mut blocks: Vec<Vec<Instruction>> = vec![];
+    loop {
+        let token = tokens.next()?;
+        match token {
+            "}" => return Some(instructions),
+            "{" => blocks.push(parse_block(tokens)?),
+            "if" => instructions.push(Instruction::If(blocks.pop()?)),
+            "ifelse" => {
+                let otherwise = blocks.pop()?;
+                instructions.push(Instruction::IfElse(blocks.pop()?, otherwise));
+            }
+            "true" => instructions.push(Instruction::Bool(true)),
+            "false" => instructions.push(Instruction::Bool(false)),
+            _ => match token.parse::<f64>() {
+                Ok(number) => instructions.push(Instruction::Number(number)),
+                Err(_) => instructions.push(Instruction::Operator(token.to_string())),
+            },
+        }
+    }
+}
+
+fn execute(instructions: &[Instruction], stack: &mut Vec<Value>) -> Option<()> {
+    for instruction in instructions {
+        if stack.len() > MAX_STACK {
+            return None;
+        }
+        match instruction {
+            Instruction::Number(n) => stack.push(Value::Number(*n)),
+            Instruction::Bool(b) => stack.push(Value::Bool(*b)),
+            Instruction::If(block) => {
+                if let Value::Bool(true) = stack.pop()? {
+                    execute(block, stack)?;
+                }
+            }
+            Instruction::IfElse(then, otherwise) => match stack.pop()? {
+                Value::Bool(true) => execute(then, stack)?,
+                _ => execute(otherwise, stack)?,
+            },
+            Instruction::Operator(operator) => operate(operator, stack)?,
+        }
+    }
+    Some(())
+}
+
+fn operate(operator: &str, stack: &mut Vec<Value>) -> Option<()> {
+    use Value::{Bool, Number};
+    let unary = |stack: &mut Vec<Value>, f: fn(f64) -> f64| {
+        let a = stack.pop()?.number();
+        stack.push(Number(f(a)));
+        Some(())
+    };
+    let binary = |stack: &mut Vec<Value>, f: fn(f64, f64) -> f64| {
+        let b = stack.pop()?.number();
+        let a = stack.pop()?.number();
+        stack.push(Number(f(a, b)));
+        Some(())
+    };
+    let compare = |stack: &mut Vec<Value>, f: fn(f64, f64) -> bool| {
+        let b = stack.pop()?.number();
+        let a = stack.pop()?.number();
+        stack.push(Bool(f(a, b)));
+        Some(())
+    };
+    // The boolean operators are bitwise on integers.
+    let logical = |stack: &mut Vec<Value>, f: fn(i64, i64) -> i64, g: fn(bool, bool) -> bool| {
+        let b = stack.pop()?;
+        let a = stack.pop()?;
+        stack.push(match (a, b) {
+            (Bool(a), Bool(b)) => Bool(g(a, b)),
+            _ => Number(f(a.int(), b.int()) as f64),
+        });
+        Some(())
+    };
+
+    match operator {
+        // Arithmetic operators
+        "abs" => unary(stack, f64::abs),
+        "add" => binary(stack, |a, b| a + b),
+        "atan" => binary(stack, |a, b| {
+            let angle = a.atan2(b).to_degrees();
+            if angle < 0.0 {
+                angle + 360.0
+            } else {
+                angle
+            }
+        }),
+        "ceiling" => unary(stack, f64::ceil),
+        "cos" => unary(stack, |a| a.to_radians().cos()),
+        "cvi" => unary(stack, f64::trunc),
+        "cvr" => unary(stack, |a| a),
+        "div" => binary(stack, |a, b| if b == 0.0 { 0.0 } else { a / b }),
+        "exp" => binary(stack, f64::powf),
+        "floor" => unary(stack, f64::floor),
+        "idiv" => binary(stack, |a, b| {
+            let (a, b) = (a as i64, b as i64);
+            if b == 0 {
+                0.0
+            } else {
+                (a / b) as f64
+            }
+        }),
+        "ln" => unary(stack, f64::ln),
+        "log" => unary(stack, f64::log10),
+        "mod" => binary(stack, |a, b| {
+            let (a, b) = (a as i64, b as i64);
+            if b == 0 {
+                0.0
+            } else {
+                (a % b) as f64
+            }
+        }),
+        "mul" => binary(stack, |a, b| a * b),
+        "neg" => unary(stack, |a| -a),
+        "round" => unary(stack, |a| (a + 0.5).floor()),
+        "sin" => unary(stack, |a| a.to_radians().sin()),
+        "sqrt" => unary(stack, f64::sqrt),
+        "sub" => binary(stack, |a, b| a - b),
+        "truncate" => unary(stack, f64::trunc),
+
+        // Relational, boolean and bitwise operators
+        "eq" => compare(stack, |a, b| a == b),
+        "ne" => compare(stack, |a, b| a != b),
+        "ge" => compare(stack, |a, b| a >= b),
+        "gt" => compare(stack, |a, b| a > b),
+        "le" => compare(stack, |a, b| a <= b),
+        "lt" => compare(stack, |a, b| a < b),
+        "and" => logical(stack, |a, b| a & b, |a, b| a && b),
+        "or" => logical(stack, |a, b| a | b, |a, b| a || b),
+        "xor" => logical(stack, |a, b| a ^ b, |a, b| a ^ b),
+        "not" => {
+            let value = match stack.pop()? {
+                Bool(b) => Bool(!b),
+                Number(n) => Number(!(n as i64) as f64),
+            };
+            stack.push(value);
+            Some(())
+        }
+        "bitshift" => {
+            let shift = stack.pop()?.int();
+            let value = stack.pop()?.int();
+            let result = if shift >= 0 {
+                value.checked_shl(shift as u32).unwrap_or(0)
+            } else {
+                value.checked_shr(-shift as u32).unwrap_or(0)
+            };
+            stack.push(Number(result as f64));
+            Some(())
+        }
+
+        // Stack operators
+        "pop" => stack.pop().map(|_| ()),
+        "exch" => {
+            let b = stack.pop()?;
+            let a = stack.pop()?;
+            stack.extend([b, a]);
+            Some(())
+        }
+        "dup" => {
+            let a = *stack.last()?;
+            stack.push(a);
+            Some(())
+        }
+        "copy" => {
+            let n = stack.pop()?.int();
+            let n = usize::try_from(n).ok().filter(|&n| n <= stack.len())?;
+            stack.extend_from_within(stack.len() - n..);
+            Some(())
+        }
+        "index" => {
+            let n = usize::try_from(stack.pop()?.int()).ok()?;
+            let value = *stack.get(stack.len().checked_sub(n + 1)?)?;
+            stack.push(value);
+            Some(())
+        }
+        "roll" => {
+            let j = stack.pop()?.int();
+            let n = usize::try_from(stack.pop()?.int()).ok()?;
+            if n == 0 || n > stack.len() {
+                return (n == 0).then_some(());
+            }
+            let start = stack.len() - n;
+            let shift = j.rem_euclid(n as i64) as usize;
+            stack[start..].rotate_right(shift);
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program() {
+        // A tint transform from one input to CMYK.
+        let program = Program::parse(b"{ dup 0.5 mul exch 0 exch 1 exch sub 0 }").unwrap();
+        assert_eq!(program.run(&[0.5], 4), vec![0.25, 0.0, 0.5, 0.0]);
+
+        let program = Program::parse(b"{ 2 copy lt { exch } if pop 10 20 30 3 1 roll }").unwrap();
+        assert_eq!(program.run(&[3.0, 7.0], 4), vec![7.0, 30.0, 10.0, 20.0]);
+
+        let program = Program::parse(b"{ 0.5 ge { 1 } if 90 sin 6 4 bitshift }").unwrap();
+        assert_eq!(program.run(&[0.7], 3), vec![1.0, 1.0, 96.0]);
+        assert_eq!(program.run(&[0.2], 2), vec![1.0, 96.0]);
+    }
+}
@@ -0,0 +1,347 @@
+///
+/// A transformation matrix [a b c d e f], representing the 3x3 matrix
+///     | a b 0 |
+///     | c d 0 |
+///     | e f 1 |
+///
+/// Points are row vectors, so a point (x, y) is transformed to
+/// (a*x + c*y + e, b*x + d*y + f). See sec. 8.3.4 (pg. 128)
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Matrix {
+    fn default() -> Self {
+        Matrix::identity()
+    }
+}
+
+impl Matrix {
+    pub fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Matrix {
+        Matrix { a, b, c, d, e, f }
+    }
+
+    pub fn identity() -> Matrix {
+        Matrix::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0)
+    }
+
+    pub fn translate(tx: f64, ty: f64) -> Matrix {
+        Matrix::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Matrix {
+        Matrix::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// Reads a matrix from six numbers, such as the operands of "cm" or a /Matrix
+    /// array.
+    pub fn from_slice(values: &[f64]) -> Option<Matrix> {
+        match values {
+            [a, b, c, d, e, f] => Some(Matrix::new(*a, *b, *c, *d, *e, *f)),
+            _ => None,
+        }
+    }
+
+    pub fn to_array(&self) -> [f64; 6] {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+    }
+
+    /// Returns self × other: the transformation that applies self first and then
+    /// other. "cm" replaces the CTM with M × CTM. See sec. 8.4.4, Table 56 (pg. 151)
+    pub fn multiply(&self, other: &Matrix) -> Matrix {
+        Matrix {
+            a: self.a * other.a + self.b * other.c,
+            b: self.a * other.b + self.b * other.d,
+            c: self.c * other.a + self.d * other.c,
+            d: self.c * other.b + self.d * other.d,
+            e: self.e * other.a + self.f * other.c + other.e,
+            f: self.e * other.b + self.f * other.d + other.f,
+        }
+    }
+
+    pub fn inverse(&self) -> Option<Matrix> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+        Some(Matrix {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        Point {
+            x: self.a * point.x + self.c * point.y + self.e,
+            y: self.b * point.x + self.d * point.y + self.f,
+        }
+    }
+
+    /// The factor by which the matrix scales lengths, averaged over both axes.
+    pub fn expansion(&self) -> f64 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Point {
+    pub fn new(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+}
+
+///
+/// A segment of a path, as built by the path construction operators.
+/// See sec. 8.5.2, Table 58 (pg. 153)
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo(Point),
+    LineTo(Point),
+    CurveTo(Point, Point, Point),
+    ClosePath,
+}
+
+///
+/// "Paths define shapes, trajectories, and regions of all sorts." A path is made
+/// up of one or more subpaths, each starting with a MoveTo segment.
+/// See sec. 8.5 (pg. 152)
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        self.segments.push(PathSegment::MoveTo(Point::new(x, y)));
+    }
+
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.segments.push(PathSegment::LineTo(Point::new(x, y)));
+    }
+
+    pub fn curve_to(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64) {
+        self.segments.push(PathSegment::CurveTo(
+            Point::new(x1, y1),
+            Point::new(x2, y2),
+            Point::new(x3, y3),
+        ));
+    }
+
+    pub fn close(&mut self) {
+        self.segments.push(PathSegment::ClosePath);
+    }
+
+    /// Appends a rectangle as a complete subpath, as done by the "re" operator.
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.move_to(x, y);
+        self.line_to(x + width, y);
+        self.line_to(x + width, y + height);
+        self.line_to(x, y + height);
+        self.close();
+    }
+
+    /// The current point: the end of the last segment, or the start of the
+    /// current subpath after it was closed.
+    pub fn current_point(&self) -> Option<Point> {
+        let mut start = None;
+        let mut current = None;
+        for segment in &self.segments {
+            match segment {
+                PathSegment::MoveTo(p) => {
+                    start = Some(*p);
+                    current = Some(*p);
+                }
+                PathSegment::LineTo(p) | PathSegment::CurveTo(_, _, p) => current = Some(*p),
+                PathSegment::ClosePath => current = start,
+            }
+        }
+        current
+    }
+
+    pub fn transform(&self, matrix: &Matrix) -> Path {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::MoveTo(p) => PathSegment::MoveTo(matrix.apply(*p)),
+                PathSegment::LineTo(p) => PathSegment::LineTo(matrix.apply(*p)),
+                PathSegment::CurveTo(p1, p2, p3) => {
+                    PathSegment::CurveTo(matrix.apply(*p1), matrix.apply(*p2), matrix.apply(*p3))
+                }
+                PathSegment::ClosePath => PathSegment::ClosePath,
+            })
+            .collect();
+        Path { segments }
+    }
+
+    /// The smallest rectangle containing every point of the path, including the
+    /// control points of curves, as [llx lly urx ury].
+    pub fn bounding_box(&self) -> Option<[f64; 4]> {
+        let mut points = self.segments.iter().flat_map(|segment| match segment {
+            PathSegment::MoveTo(p) | PathSegment::LineTo(p) => vec![*p],
+            PathSegment::CurveTo(p1, p2, p3) => vec![*p1, *p2, *p3],
+            PathSegment::ClosePath => vec![],
+        });
+        let first = points.next()?;
+        Some(points.fold([first.x, first.y, first.x, first.y], |b, p| {
+            [b[0].min(p.x), b[1].min(p.y), b[2].max(p.x), b[3].max(p.y)]
+        }))
+    }
+
+    /// Replaces the curves of each subpath by line segments that stay within the
+    /// given distance of the curve. Closing a subpath is recorded rather than
+    /// added as a segment, since stroking joins the ends of closed subpaths.
+    pub fn flatten(&self, tolerance: f64) -> Vec<Polyline> {
+        let mut polylines: Vec<Polyline> = vec![];
+        let mut current: Option<Polyline> = None;
+        let mut start = Point::default();
+        for segment in &self.segments {
+            match *segment {
+                PathSegment::MoveTo(p) => {
+                    polylines.extend(current.take());
+                    current = Some(Polyline::new(p));
+                    start = p;
+                }
+                PathSegment::LineTo(p) => {
+                    current
+                        .get_or_insert_with(|| Polyline::new(start))
+                        .points
+                        .push(p);
+                }
+                PathSegment::CurveTo(p1, p2, p3) => {
+                    let polyline = current.get_or_insert_with(|| Polyline::new(start));
+                    let p0 = *polyline.points.last().unwrap_or(&start);
+                    flatten_curve(p0, p1, p2, p3, tolerance, &mut polyline.points);
+                }
+                PathSegment::ClosePath => {
+                    if let Some(mut polyline) = current.take() {
+                        polyline.closed = true;
+                        start = polyline.points[0];
+                        polylines.push(polyline);
+                    }
+                }
+            }
+        }
+        polylines.extend(current);
+        polylines
+    }
+}
+
+/// Appends points along a cubic Bézier curve. The number of segments follows
+/// from how far the control points stray from a straight line.
+fn flatten_curve(p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f64, out: &mut Vec<Point>) {
+    let dd = |a: Point, b: Point, c: Point| {
+        let (x, y) = (a.x - 2.0 * b.x + c.x, a.y - 2.0 * b.y + c.y);
+        (x * x + y * y).sqrt()
+    };
+    let deviation = dd(p0, p1, p2).max(dd(p1, p2, p3));
+    let steps = (0.75 * deviation / tolerance.max(1e-6)).sqrt().ceil();
+    let steps = steps.clamp(1.0, 500.0) as usize;
+    for i in 1..=steps {
+        let t = i as f64 / steps as f64;
+        let u = 1.0 - t;
+        let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+        out.push(Point::new(
+            a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+            a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+        ));
+    }
+}
+
+///
+/// A subpath made of straight line segments only, as produced by
+/// `Path::flatten`.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Point>,
+    pub closed: bool,
+}
+
+impl Polyline {
+    pub fn new(start: Point) -> Polyline {
+        Polyline {
+            points: vec![start],
+            closed: false,
+        }
+    }
+
+    pub fn transform(&self, matrix: &Matrix) -> Polyline {
+        Polyline {
+            points: self.points.iter().map(|p| matrix.apply(*p)).collect(),
+            closed: self.closed,
+        }
+    }
+
+    /// Twice the signed area enclosed by the polyline: positive when its points
+    /// run counterclockwise (with the y axis pointing up).
+    pub fn signed_area(&self) -> f64 {
+        let n = self.points.len();
+        (0..n)
+            .map(|i| {
+                let (a, b) = (self.points[i], self.points[(i + 1) % n]);
+                a.x * b.y - b.x * a.y
+            })
+            .sum()
+    }
+}
+
+///
+/// The rule used to determine which points lie inside a path when it is filled
+/// or used for clipping. See sec. 8.5.3.3 (pg. 159)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    NonZeroWinding,
+    EvenOdd,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_multiply() {
+        let translate = Matrix::translate(10.0, 20.0);
+        let scale = Matrix::scale(2.0, 4.0);
+        // Scale first, then translate.
+        let combined = scale.multiply(&translate);
+        assert_eq!(combined.apply(Point::new(1.0, 1.0)), Point::new(12.0, 24.0));
+        let inverse = combined.inverse().unwrap();
+        assert_eq!(inverse.apply(Point::new(12.0, 24.0)), Point::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn test_path_current_point() {
+        let mut path = Path::new();
+        path.rect(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(path.current_point(), Some(Point::new(1.0, 2.0)));
+        assert_eq!(path.bounding_box(), Some([1.0, 2.0, 4.0, 6.0]));
+    }
+}
@@ -0,0 +1,656 @@
+use std::{collections::HashSet, fs, io, io::Write};
+
+use flate2::{write::ZlibEncoder, Compression, Crc};
+
+use crate::api::{
+    color::ColorSpace,
+    content::{
+        interpreter::{ContentVisitor, Interpreter, XObject},
+        state::GraphicsState,
+    },
+    document::Document,
+    filters::IMAGE_FORMAT_FILTERS,
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    page::Page,
+    read::invalid_data,
+};
+
+///
+/// The file formats images are extracted to. JPEG and JPEG 2000 data is stored
+/// in the PDF as complete files and is written out unchanged; every other image
+/// is decoded and written as PNG.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Jpeg2000,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Jpeg2000 => "jp2",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// The channels of each pixel in a `Bitmap`, 8 bits per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+}
+
+impl PixelFormat {
+    pub fn channels(&self) -> usize {
+        match self {
+            PixelFormat::Gray => 1,
+            PixelFormat::GrayAlpha => 2,
+            PixelFormat::Rgb => 3,
+            PixelFormat::Rgba => 4,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, PixelFormat::GrayAlpha | PixelFormat::Rgba)
+    }
+}
+
+/// Bitmaps decoded from images are limited to this many pixels (16384 by
+/// 16384), so that a crafted /Width and /Height cannot exhaust memory.
+pub const MAX_PIXELS: usize = 1 << 28;
+
+///
+/// Decoded pixels, stored row by row from the top of the image, with 8 bits
+/// per channel.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    pub format: PixelFormat,
+    pub pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    /// A bitmap with every channel of every pixel set to 0.
+    pub fn new(width: usize, height: usize, format: PixelFormat) -> Bitmap {
+        Bitmap {
+            width,
+            height,
+            format,
+            pixels: vec![0; width * height * format.channels()],
+        }
+    }
+
+    /// Like `new`, failing instead of allocating if the bitmap would have
+    /// more than `MAX_PIXELS` pixels.
+    pub fn try_new(width: usize, height: usize, format: PixelFormat) -> Result<Bitmap, io::Error> {
+        check_size(width, height)?;
+        Ok(Bitmap::new(width, height, format))
+    }
+
+    /// The channels of the pixel at (x, y).
+    pub fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let channels = self.format.channels();
+        let start = (y * self.width + x) * channels;
+        &self.pixels[start..start + channels]
+    }
+
+    /// Encodes the bitmap as a PNG file, without filtering the rows.
+    pub fn to_png(&self) -> Vec<u8> {
+        let color_type = match self.format {
+            PixelFormat::Gray => 0,
+            PixelFormat::Rgb => 2,
+            PixelFormat::GrayAlpha => 4,
+            PixelFormat::Rgba => 6,
+        };
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // Bit depth, color type, compression, filter and interlace method.
+        header.extend([8, color_type, 0, 0, 0]);
+
+        let stride = self.width * self.format.channels();
+        let mut raw = Vec::with_capacity((stride + 1) * self.height);
+        if stride > 0 {
+            for row in self.pixels.chunks(stride) {
+                raw.push(0);
+                raw.extend(row);
+            }
+        }
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        let compressed = encoder
+            .write_all(&raw)
+            .and_then(|_| encoder.finish())
+            .expect("writing to a Vec cannot fail");
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        write_png_chunk(&mut png, b"IHDR", &header);
+        write_png_chunk(&mut png, b"IDAT", &compressed);
+        write_png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), io::Error> {
+        fs::write(path, self.to_png())
+    }
+}
+
+/// Each PNG chunk is its length, type and data followed by a CRC of the type
+/// and data.
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend((data.len() as u32).to_be_bytes());
+    png.extend(kind);
+    png.extend(data);
+    png.extend(crc.sum().to_be_bytes());
+}
+
+///
+/// An image painted on a page: either an image XObject, painted with "Do", or
+/// an inline image. The resources are those in effect where the image is
+/// painted, which a named color space refers to.
+///
+#[derive(Debug, Clone)]
+pub struct PageImage {
+    pub name: Option<String>,
+    pub id: Option<ObjectId>,
+    pub stream: Stream,
+    pub resources: Dictionary,
+}
+
+impl PageImage {
+    pub fn is_inline(&self) -> bool {
+        self.name.is_none()
+    }
+
+    /// Converts the image to a file. DCTDecode and JPXDecode data is kept as it
+    /// is; other images are decoded and encoded as PNG.
+    pub fn extract(&self, doc: &Document) -> Result<ExtractedImage, io::Error> {
+        let dict = &self.stream.dict;
+        let (data, filter) =
+            doc.decode_stream_until(&self.stream, |f| IMAGE_FORMAT_FILTERS.contains(&f))?;
+        let (format, data) = match filter {
+            Some("JPXDecode") => (ImageFormat::Jpeg2000, data),
+            Some(_) => (ImageFormat::Jpeg, data),
+            None => {
+                let bitmap = image_from_samples(doc, dict, &data, Some(&self.resources))?;
+                (ImageFormat::Png, bitmap.to_png())
+            }
+        };
+        Ok(ExtractedImage {
+            format,
+            width: image_size(doc, dict, "Width").unwrap_or(0),
+            height: image_size(doc, dict, "Height").unwrap_or(0),
+            data,
+        })
+    }
+}
+
+///
+/// An image converted to a file format, ready to be written to disk.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedImage {
+    pub format: ImageFormat,
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl ExtractedImage {
+    pub fn save(&self, path: &str) -> Result<(), io::Error> {
+        fs::write(path, &self.data)
+    }
+}
+
+/// Collects the images painted by a content stream, including those painted
+/// inside form XObjects. An image XObject painted several times is only
+/// collected once.
+#[derive(Default)]
+struct ImageCollector {
+    images: Vec<PageImage>,
+    seen: HashSet<ObjectId>,
+}
+
+impl ContentVisitor for ImageCollector {
+    fn image(&mut self, image: &XObject, _state: &GraphicsState) {
+        if let Some(id) = image.id {
+            if !self.seen.insert(id) {
+                return;
+            }
+        }
+        self.images.push(PageImage {
+            name: image.name.map(String::from),
+            id: image.id,
+            stream: image.stream.clone(),
+            resources: image.resources.clone(),
+        });
+    }
+}
+
+/// The image XObjects and inline images painted on a page, in the order they
+/// are first painted.
+pub fn page_images(doc: &Document, page: &Page) -> Result<Vec<PageImage>, io::Error> {
+    let mut collector = ImageCollector::default();
+    Interpreter::run_page(doc, page, &mut collector)?;
+    Ok(collector.images)
+}
+
+/// Decodes the samples of an image XObject or inline image into a bitmap.
+/// Images compressed with DCTDecode or JPXDecode cannot be decoded.
+pub fn decode_image(
+    doc: &Document,
+    stream: &Stream,
+    resources: Option<&Dictionary>,
+) -> Result<Bitmap, io::Error> {
+    let data = doc.decode_stream(stream)?;
+    image_from_samples(doc, &stream.dict, &data, resources)
+}
+
+/// Fails if an image or bitmap of this size would exceed `MAX_PIXELS`.
+pub(crate) fn check_size(width: usize, height: usize) -> Result<(), io::Error> {
+    match width.checked_mul(height) {
+        Some(pixels) if pixels <= MAX_PIXELS => Ok(()),
+        _ => Err(invalid_data(format!(
+            "image of {} by {} pixels is too large",
+            width, height
+        ))),
+    }
+}
+
+fn image_size(doc: &Document, dict: &Dictionary, key: &str) -> Option<usize> {
+    doc.resolve_key(dict, key)
+        .and_then(PdfObject::as_i64)
+        .filter(|&size| size > 0)
+        .map(|size| size as usize)
+}
+
+/// Converts decoded image samples to pixels. See sec. 8.9.5 (pg. 226)
+///
+/// Each sample is mapped through the /Decode array and the color space to gray
+/// or RGB. Stencil masks (/ImageMask) become black where paint is applied and
+/// transparent elsewhere. A soft mask (/SMask), a stencil mask given as /Mask or
+/// a color key mask given as /Mask adds an alpha channel. See sec. 11.6.5.3
+/// (pg. 399) and 8.9.6 (pg. 239)
+fn image_from_samples(
+    doc: &Document,
+    dict: &Dictionary,
+    data: &[u8],
+    resources: Option<&Dictionary>,
+) -> Result<Bitmap, io::Error> {
+    let width = image_size(doc, dict, "Width").ok_or_else(|| invalid_data("image /Width"))?;
+    let height = image_size(doc, dict, "Height").ok_or_else(|| invalid_data("image /Height"))?;
+    check_size(width, height)?;
+    let image_mask = doc
+        .resolve_key(dict, "ImageMask")
+        .and_then(PdfObject::as_bool)
+        .unwrap_or(false);
+    let bpc = if image_mask {
+        1
+    } else {
+        doc.resolve_key(dict, "BitsPerComponent")
+            .and_then(PdfObject::as_i64)
+            .unwrap_or(8) as usize
+    };
+    if ![1, 2, 4, 8, 16].contains(&bpc) {
+        return Err(invalid_data(format!("{} bits per component", bpc)));
+    }
+
+    let space = if image_mask {
+        ColorSpace::DeviceGray
+    } else {
+        doc.resolve_key(dict, "ColorSpace")
+            .and_then(|cs| ColorSpace::from_object(doc, cs, resources))
+            .ok_or_else(|| invalid_data("image /ColorSpace"))?
+    };
+    let n = space.components().max(1);
+    let max = ((1u32 << bpc) - 1) as f64;
+    let decode = doc
+        .resolve_key(dict, "Decode")
+        .and_then(PdfObject::as_numbers)
+        .filter(|d| d.len() >= 2 * n)
+        .unwrap_or_else(|| default_decode(&space, max));
+
+    let samples = SampleReader {
+        data,
+        bpc,
+        stride: (width * n * bpc).div_ceil(8),
+    };
+    let gray = image_mask || (space.is_gray() && !matches!(space, ColorSpace::Indexed { .. }));
+    let format = match (gray, image_mask) {
+        (_, true) => PixelFormat::GrayAlpha,
+        (true, false) => PixelFormat::Gray,
+        (false, false) => PixelFormat::Rgb,
+    };
+
+    // Single component images have few possible samples, so their colors are
+    // looked up rather than converted for every pixel.
+    let table: Option<Vec<[f64; 3]>> = (n == 1 && bpc <= 8).then(|| {
+        (0..=max as u32)
+            .map(|sample| {
+                space.to_rgb(&[decode[0] + sample as f64 * (decode[1] - decode[0]) / max])
+            })
+            .collect()
+    });
+
+    let mut bitmap = Bitmap::try_new(width, height, format)?;
+    let mut components = vec![0.0; n];
+    let mut pixels = bitmap.pixels.chunks_mut(format.channels());
+    for y in 0..height {
+        for x in 0..width {
+            let Some(pixel) = pixels.next() else {
+                break;
+            };
+            let rgb = match &table {
+                Some(table) => table[samples.get(y, x) as usize],
+                None => {
+                    for (i, component) in components.iter_mut().enumerate() {
+                        let sample = samples.get(y, x * n + i) as f64;
+                        let (low, high) = (decode[2 * i], decode[2 * i + 1]);
+                        *component = low + sample * (high - low) / max;
+                    }
+                    space.to_rgb(&components)
+                }
+            };
+            if image_mask {
+                // Paint is applied where the sample decodes to 0.
+                pixel[1] = if rgb[0] < 0.5 { 255 } else { 0 };
+            } else if gray {
+                pixel[0] = to_byte(rgb[0]);
+            } else {
+                pixel.copy_from_slice(&rgb.map(to_byte));
+            }
+        }
+    }
+
+    if image_mask {
+        return Ok(bitmap);
+    }
+    let alpha = match doc
+        .resolve_key(dict, "SMask")
+        .or_else(|| doc.resolve_key(dict, "Mask"))
+    {
+        Some(PdfObject::Stream(mask)) => Some(mask_alpha(doc, mask)?),
+        Some(PdfObject::Array(ranges)) => {
+            let ranges: Vec<f64> = ranges
+                .iter()
+                .filter_map(|r| doc.resolve(r).as_f64())
+                .collect();
+            Some(color_key_alpha(&samples, width, height, n, &ranges)?)
+        }
+        _ => None,
+    };
+    let mut bitmap = match alpha {
+        Some(alpha) => add_alpha(&bitmap, &alpha),
+        None => bitmap,
+    };
+    // Colors premultiplied with a /Matte color are separated from it again.
+    if let Some(matte) = doc
+        .resolve_key(dict, "SMask")
+        .and_then(PdfObject::as_stream)
+        .and_then(|mask| doc.resolve_key(&mask.dict, "Matte"))
+        .and_then(PdfObject::as_numbers)
+    {
+        unmultiply_matte(&mut bitmap, space.to_rgb(&matte));
+    }
+    Ok(bitmap)
+}
+
+/// The default /Decode array maps samples onto the range of each component.
+/// See sec. 8.9.5.2, Table 88 (pg. 229)
+fn default_decode(space: &ColorSpace, max: f64) -> Vec<f64> {
+    match space {
+        ColorSpace::Indexed { .. } => vec![0.0, max],
+        ColorSpace::Lab(dict) => {
+            let range = dict
+                .get("Range")
+                .and_then(|r| r.as_numbers())
+                .filter(|r| r.len() == 4)
+                .unwrap_or(vec![-100.0, 100.0, -100.0, 100.0]);
+            vec![0.0, 100.0, range[0], range[1], range[2], range[3]]
+        }
+        _ => [0.0, 1.0].repeat(space.components().max(1)),
+    }
+}
+
+fn to_byte(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Reads packed samples; each row starts on a byte boundary. Samples missing
+/// from truncated data read as 0.
+struct SampleReader<'a> {
+    data: &'a [u8],
+    bpc: usize,
+    stride: usize,
+}
+
+impl SampleReader<'_> {
+    fn get(&self, row: usize, index: usize) -> u32 {
+        let bit = index * self.bpc;
+        let offset = row * self.stride + bit / 8;
+        let byte = |i: usize| self.data.get(i).copied().unwrap_or(0) as u32;
+        match self.bpc {
+            8 => byte(offset),
+            16 => (byte(offset) << 8) | byte(offset + 1),
+            bpc => (byte(offset) >> (8 - bpc - bit % 8)) & ((1 << bpc) - 1),
+        }
+    }
+}
+
+/// The alpha values given by a soft mask or a stencil mask, at the mask's own
+/// resolution.
+fn mask_alpha(doc: &Document, mask: &Stream) -> Result<Bitmap, io::Error> {
+    let bitmap = decode_image(doc, mask, None)?;
+    let alpha = match bitmap.format {
+        // A stencil mask is opaque where paint would be applied.
+        PixelFormat::GrayAlpha => bitmap.pixels.chunks(2).map(|p| p[1]).collect(),
+        PixelFormat::Gray => bitmap.pixels.clone(),
+        PixelFormat::Rgb | PixelFormat::Rgba => {
+            let channels = bitmap.format.channels();
+            bitmap.pixels.chunks(channels).map(|p| p[0]).collect()
+        }
+    };
+    Ok(Bitmap {
+        pixels: alpha,
+        format: PixelFormat::Gray,
+        ..bitmap
+    })
+}
+
+/// A color key mask is an array with a minimum and maximum sample value for
+/// each component; pixels whose samples all fall within these ranges are not
+/// painted. See sec. 8.9.6.4 (pg. 241)
+fn color_key_alpha(
+    samples: &SampleReader,
+    width: usize,
+    height: usize,
+    n: usize,
+    ranges: &[f64],
+) -> Result<Bitmap, io::Error> {
+    let mut alpha = Bitmap::try_new(width, height, PixelFormat::Gray)?;
+    for y in 0..height {
+        for x in 0..width {
+            let masked = ranges.len() >= 2 * n
+                && (0..n).all(|i| {
+                    let sample = samples.get(y, x * n + i) as f64;
+                    ranges[2 * i] <= sample && sample <= ranges[2 * i + 1]
+                });
+            alpha.pixels[y * width + x] = if masked { 0 } else { 255 };
+        }
+    }
+    Ok(alpha)
+}
+
+/// Removes the matte color that the colors of an image were premultiplied with:
+/// c = m + (c' - m) / alpha. See sec. 11.6.5.3 (pg. 400)
+fn unmultiply_matte(bitmap: &mut Bitmap, matte: [f64; 3]) {
+    let channels = bitmap.format.channels();
+    for pixel in bitmap.pixels.chunks_mut(channels) {
+        let (colors, alpha) = pixel.split_at_mut(channels - 1);
+        let alpha = alpha[0] as f64 / 255.0;
+        if alpha <= 0.0 {
+            continue;
+        }
+        for (color, m) in colors.iter_mut().zip(matte) {
+            let value = *color as f64 / 255.0;
+            *color = to_byte(m + (value - m) / alpha);
+        }
+    }
+}
+
+/// Adds an alpha channel to a gray or RGB bitmap. A mask of a different size is
+/// stretched over the image, picking the nearest mask pixel.
+fn add_alpha(bitmap: &Bitmap, alpha: &Bitmap) -> Bitmap {
+    let format = match bitmap.format {
+        PixelFormat::Gray | PixelFormat::GrayAlpha => PixelFormat::GrayAlpha,
+        PixelFormat::Rgb | PixelFormat::Rgba => PixelFormat::Rgba,
+    };
+    let color_channels = format.channels() - 1;
+    let mut result = Bitmap::new(bitmap.width, bitmap.height, format);
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let mask_x = x * alpha.width / bitmap.width;
+            let mask_y = y * alpha.height / bitmap.height;
+            let start = (y * bitmap.width + x) * format.channels();
+            let pixel = &mut result.pixels[start..start + format.channels()];
+            pixel[..color_channels].copy_from_slice(&bitmap.pixel(x, y)[..color_channels]);
+            pixel[color_channels] = alpha.pixel(mask_x, mask_y)[0];
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::read::objects::parse_dictionary;
+
+    fn dictionary(source: &str) -> Dictionary {
+        parse_dictionary(source.as_bytes()).unwrap().1
+    }
+
+    #[test]
+    fn test_decode_image() {
+        let doc = Document::load("HelloWorld.pdf").unwrap();
+        let indexed = Stream::new(
+            dictionary(
+                "<< /Width 3 /Height 1 /BitsPerComponent 2 \
+                 /ColorSpace [/Indexed /DeviceRGB 2 <FF0000 00FF00 0000FF>] >>",
+            ),
+            vec![0b0001_1000],
+        );
+        let bitmap = decode_image(&doc, &indexed, None).unwrap();
+        assert_eq!(bitmap.format, PixelFormat::Rgb);
+        assert_eq!(bitmap.pixels, vec![255, 0, 0, 0, 255, 0, 0, 0, 255]);
+
+        let cmyk = Stream::new(
+            dictionary(
+                "<< /Width 1 /Height 1 /BitsPerComponent 8 /ColorSpace /DeviceCMYK \
+                 /Decode [1 0 1 0 1 0 1 0] >>",
+            ),
+            vec![255, 255, 255, 0],
+        );
+        let bitmap = decode_image(&doc, &cmyk, None).unwrap();
+        assert_eq!(bitmap.pixels, vec![0, 0, 0]);
+
+        // Sizes whose pixels would overflow or not fit in memory are
+        // rejected before anything is allocated.
+        for size in [
+            "/Width 4294967296 /Height 4294967296",
+            "/Width 100000 /Height 100000",
+        ] {
+            let huge = Stream::new(
+                dictionary(&format!(
+                    "<< {} /BitsPerComponent 8 /ColorSpace /DeviceGray \
+                     /Mask [0 0] >>",
+                    size
+                )),
+                vec![0],
+            );
+            assert!(decode_image(&doc, &huge, None).is_err());
+        }
+    }
+
+    #[test]
+    fn test_page_images() {
+        let mut doc = Document::load("HelloWorld.pdf").unwrap();
+        let jpeg = b"\xFF\xD8\xFF\xE0 not really a JPEG \xFF\xD9".to_vec();
+        let smask = Stream::new(
+            dictionary("<< /Width 1 /Height 1 /BitsPerComponent 8 /ColorSpace /DeviceGray >>"),
+            vec![0x80],
+        );
+        let gray = Stream::new(
+            dictionary(
+                "<< /Subtype /Image /Width 2 /Height 1 /BitsPerComponent 8 \
+                 /ColorSpace /DeviceGray /SMask 103 0 R >>",
+            ),
+            vec![0x00, 0xFF],
+        );
+        let photo = Stream::new(
+            dictionary(
+                "<< /Subtype /Image /Width 1 /Height 1 /BitsPerComponent 8 \
+                 /ColorSpace /DeviceRGB /Filter /DCTDecode >>",
+            ),
+            jpeg.clone(),
+        );
+        doc.pdf
+            .body
+            .objects
+            .insert((101, 0), PdfObject::Stream(gray));
+        doc.pdf
+            .body
+            .objects
+            .insert((102, 0), PdfObject::Stream(photo));
+        doc.pdf
+            .body
+            .objects
+            .insert((103, 0), PdfObject::Stream(smask));
+
+        let mut page = doc.page(0).unwrap();
+        page.dict.set(
+            "Resources",
+            PdfObject::Dictionary(dictionary(
+                "<< /XObject << /Im1 101 0 R /Im2 102 0 R >> /ColorSpace << /CS0 /DeviceRGB >> >>",
+            )),
+        );
+        let content = Stream::new(
+            Dictionary::new(),
+            b"/Im1 Do /Im2 Do BI /W 1 /H 1 /CS /CS0 /BPC 8 ID \x01\x02\x03 EI /Im1 Do".to_vec(),
+        );
+        doc.pdf
+            .body
+            .objects
+            .insert((104, 0), PdfObject::Stream(content));
+        page.dict.set("Contents", PdfObject::Reference((104, 0)));
+
+        let images = page_images(&doc, &page).unwrap();
+        assert_eq!(images.len(), 3);
+        assert_eq!(images[0].name.as_deref(), Some("Im1"));
+        assert!(images[2].is_inline());
+
+        let extracted = images[0].extract(&doc).unwrap();
+        assert_eq!(extracted.format, ImageFormat::Png);
+        assert!(extracted.data.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let bitmap = image_from_samples(&doc, &images[0].stream.dict, &[0x00, 0xFF], None).unwrap();
+        assert_eq!(bitmap.format, PixelFormat::GrayAlpha);
+        assert_eq!(bitmap.pixels, vec![0x00, 0x80, 0xFF, 0x80]);
+
+        let extracted = images[1].extract(&doc).unwrap();
+        assert_eq!(extracted.format, ImageFormat::Jpeg);
+        assert_eq!(extracted.data, jpeg);
+
+        let extracted = images[2].extract(&doc).unwrap();
+        assert_eq!((extracted.width, extracted.height), (1, 1));
+        assert_eq!(extracted.format.extension(), "png");
+    }
+}
@@ -0,0 +1,276 @@
+use crate::api::{
+    document::Document,
+    objects::{Dictionary, PdfObject},
+};
+
+/// Roman numerals and letters are written with at most this many repeated
+/// numerals or letters. Larger page numbers, which only an absurd /St gives,
+/// are written as decimal numerals instead.
+const MAX_REPEAT: i64 = 100;
+
+///
+/// The numbering style of a range of page labels. See sec. 12.4.2, Table 161
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelStyle {
+    /// Decimal numerals: 1, 2, 3.
+    Decimal,
+    /// Uppercase roman numerals: I, II, III.
+    UpperRoman,
+    /// Lowercase roman numerals: i, ii, iii.
+    LowerRoman,
+    /// Uppercase letters: A to Z, then AA to ZZ, and so on.
+    UpperAlpha,
+    /// Lowercase letters: a to z, then aa to zz, and so on.
+    LowerAlpha,
+}
+
+impl LabelStyle {
+    pub fn from_name(name: &str) -> Option<LabelStyle> {
+        match name {
+            "D" => Some(LabelStyle::Decimal),
+            "R" => Some(LabelStyle::UpperRoman),
+            "r" => Some(LabelStyle::LowerRoman),
+            "A" => Some(LabelStyle::UpperAlpha),
+            "a" => Some(LabelStyle::LowerAlpha),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LabelStyle::Decimal => "D",
+            LabelStyle::UpperRoman => "R",
+            LabelStyle::LowerRoman => "r",
+            LabelStyle::UpperAlpha => "A",
+            LabelStyle::LowerAlpha => "a",
+        }
+    }
+
+    /// Formats a page number, which must be at least 1, in this style.
+    /// Numbers too large for roman numerals or letters are formatted as
+    /// decimal numerals.
+    pub fn format(self, number: i64) -> String {
+        let too_large = match self {
+            LabelStyle::Decimal => false,
+            LabelStyle::UpperRoman | LabelStyle::LowerRoman => number > MAX_REPEAT * 1000,
+            LabelStyle::UpperAlpha | LabelStyle::LowerAlpha => number > MAX_REPEAT * 26,
+        };
+        if too_large {
+            return number.to_string();
+        }
+        match self {
+            LabelStyle::Decimal => number.to_string(),
+            LabelStyle::UpperRoman => roman(number),
+            LabelStyle::LowerRoman => roman(number).to_lowercase(),
+            LabelStyle::UpperAlpha => alpha(number),
+            LabelStyle::LowerAlpha => alpha(number).to_lowercase(),
+        }
+    }
+}
+
+///
+/// The labelling of a range of pages, which starts at a page and lasts until
+/// the next range. A label is the prefix followed by the number of the page
+/// within the range, counted from `start`; a range without a style has only
+/// the prefix as label. See sec. 12.4.2
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLabel {
+    pub style: Option<LabelStyle>,
+    pub prefix: String,
+    pub start: i64,
+}
+
+impl PageLabel {
+    pub fn new(style: Option<LabelStyle>, prefix: &str) -> PageLabel {
+        PageLabel {
+            style,
+            prefix: prefix.to_string(),
+            start: 1,
+        }
+    }
+
+    pub fn from_dict(dict: &Dictionary) -> PageLabel {
+        PageLabel {
+            style: dict.get_name("S").and_then(LabelStyle::from_name),
+            prefix: dict
+                .get("P")
+                .and_then(PdfObject::as_text)
+                .unwrap_or_default(),
+            start: dict.get_i64("St").unwrap_or(1).max(1),
+        }
+    }
+
+    pub fn to_dict(&self) -> Dictionary {
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("PageLabel".to_string()));
+        if let Some(style) = self.style {
+            dict.set("S", PdfObject::Name(style.name().to_string()));
+        }
+        if !self.prefix.is_empty() {
+            dict.set("P", PdfObject::text_string(&self.prefix));
+        }
+        if self.start != 1 {
+            dict.set("St", PdfObject::Integer(self.start));
+        }
+        dict
+    }
+
+    /// The label of the page `offset` pages after the first of the range.
+    pub fn label(&self, offset: usize) -> String {
+        let Some(style) = self.style else {
+            return self.prefix.clone();
+        };
+        let number = i64::try_from(offset)
+            .ok()
+            .and_then(|offset| self.start.checked_add(offset));
+        match number {
+            Some(number) => format!("{}{}", self.prefix, style.format(number)),
+            None => format!("{}{}", self.prefix, self.start as i128 + offset as i128),
+        }
+    }
+}
+
+impl Document {
+    /// The label of each page, such as "iv" or "A-3", as given by the
+    /// catalog's /PageLabels number tree. Pages without a label, including
+    /// all pages of a document without page labels, are labelled with their
+    /// page number, counted from 1. See sec. 12.4.2
+    pub fn page_labels(&self) -> Vec<String> {
+        let count = self.pages().len();
+        let ranges: Vec<(usize, PageLabel)> = self
+            .catalog()
+            .and_then(|catalog| self.resolve_dict(catalog, "PageLabels"))
+            .map(|tree| {
+                self.number_tree(tree)
+                    .into_iter()
+                    .filter_map(|(index, value)| {
+                        let label = PageLabel::from_dict(self.resolve(value).as_dict()?);
+                        Some((usize::try_from(index).ok()?, label))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        (0..count)
+            .map(
+                |index| match ranges.iter().rev().find(|(first, _)| *first <= index) {
+                    Some((first, label)) => label.label(index - first),
+                    None => (index + 1).to_string(),
+                },
+            )
+            .collect()
+    }
+
+    /// Replaces the page labels with ranges starting at the given page
+    /// indices, or removes them if there are none.
+    pub fn set_page_labels(&mut self, ranges: &[(usize, PageLabel)]) {
+        let Some(catalog_id) = self.catalog_id() else {
+            return;
+        };
+        let tree = (!ranges.is_empty()).then(|| {
+            let entries = ranges
+                .iter()
+                .map(|(index, label)| (*index as i64, PdfObject::Dictionary(label.to_dict())))
+                .collect();
+            let root = self.add_number_tree(entries);
+            PdfObject::Reference(self.add_object(PdfObject::Dictionary(root)))
+        });
+        if let Some(PdfObject::Dictionary(catalog)) = self.get_mut(catalog_id) {
+            match tree {
+                Some(tree) => catalog.set("PageLabels", tree),
+                None => {
+                    catalog.remove("PageLabels");
+                }
+            }
+        }
+    }
+}
+
+/// Roman numerals, with numbers of 4000 and above using as many M's as
+/// needed.
+fn roman(mut number: i64) -> String {
+    const NUMERALS: [(i64, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut text = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            text.push_str(numeral);
+            number -= value;
+        }
+    }
+    text
+}
+
+/// Letters, repeating the letter once more for each time through the
+/// alphabet: 27 is AA and 53 is AAA.
+fn alpha(number: i64) -> String {
+    if number < 1 {
+        return String::new();
+    }
+    let letter = (b'A' + ((number - 1) % 26) as u8) as char;
+    letter.to_string().repeat(((number - 1) / 26 + 1) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{builder::DocumentBuilder, document::tests::reload};
+
+    #[test]
+    fn test_page_labels() {
+        assert_eq!(LabelStyle::UpperRoman.format(1994), "MCMXCIV");
+        assert_eq!(LabelStyle::LowerAlpha.format(28), "bb");
+        assert_eq!(LabelStyle::UpperRoman.format(4001), "MMMMI");
+        assert_eq!(LabelStyle::UpperAlpha.format(2600), "Z".repeat(100));
+        assert_eq!(LabelStyle::UpperAlpha.format(2601), "2601");
+        assert_eq!(LabelStyle::LowerRoman.format(1 << 40), "1099511627776");
+
+        // An absurd /St neither overflows nor takes long to format.
+        let mut dict = PageLabel::new(Some(LabelStyle::LowerAlpha), "p").to_dict();
+        dict.set("St", PdfObject::Integer(i64::MAX));
+        let label = PageLabel::from_dict(&dict);
+        assert_eq!(label.label(0), format!("p{}", i64::MAX));
+        assert_eq!(label.label(1), "p9223372036854775808");
+
+        let mut builder = DocumentBuilder::new();
+        for _ in 0..7 {
+            builder.add_page([0.0, 0.0, 612.0, 792.0], |_| {});
+        }
+        let mut doc = builder.build();
+        assert_eq!(doc.page_labels(), ["1", "2", "3", "4", "5", "6", "7"]);
+
+        let appendix = PageLabel {
+            start: 3,
+            ..PageLabel::new(Some(LabelStyle::Decimal), "A-")
+        };
+        doc.set_page_labels(&[
+            (5, appendix),
+            (0, PageLabel::new(Some(LabelStyle::LowerRoman), "")),
+            (2, PageLabel::new(Some(LabelStyle::Decimal), "")),
+            (4, PageLabel::new(None, "Index")),
+        ]);
+        let mut doc = reload(&doc);
+        assert_eq!(
+            doc.page_labels(),
+            ["i", "ii", "1", "2", "Index", "A-3", "A-4"]
+        );
+
+        doc.set_page_labels(&[]);
+        assert_eq!(doc.page_labels()[0], "1");
+        assert!(!doc.catalog().unwrap().contains_key("PageLabels"));
+    }
+}
@@ -0,0 +1,835 @@
+use std::fmt::Write as _;
+
+use crate::api::{
+    document::Document,
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+};
+
+///
+/// A date as written in the document information dictionary,
+/// "D:YYYYMMDDHHmmSSOHH'mm'", where O is the relationship of local time to
+/// UT: "+", "-" or "Z". Every field after the year is optional.
+/// See sec. 7.9.4
+///
+/// The offset from UT is in minutes, and None if the date does not give it.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdfDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    pub offset: Option<i16>,
+}
+
+impl PdfDate {
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> PdfDate {
+        PdfDate {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            offset: None,
+        }
+    }
+
+    /// Parses a date string. The "D:" prefix is optional, and the apostrophe
+    /// after the minutes of the offset, required by PDF 1.7, may be missing.
+    pub fn parse(text: &[u8]) -> Option<PdfDate> {
+        let text = text.strip_prefix(b"D:").unwrap_or(text);
+        let digits = text.iter().take_while(|b| b.is_ascii_digit()).count();
+        if digits < 4 || digits % 2 != 0 || digits > 14 {
+            return None;
+        }
+        let field = |start: usize, default: u16| -> u16 {
+            match text.get(start..start + 2).filter(|_| start + 2 <= digits) {
+                Some(two) => two.iter().fold(0, |n, b| n * 10 + (b - b'0') as u16),
+                None => default,
+            }
+        };
+        let year = text[..4].iter().fold(0, |n, b| n * 10 + (b - b'0') as u16);
+        let date = PdfDate {
+            year,
+            month: field(4, 1) as u8,
+            day: field(6, 1) as u8,
+            hour: field(8, 0) as u8,
+            minute: field(10, 0) as u8,
+            second: field(12, 0) as u8,
+            offset: parse_offset(&text[digits..]),
+        };
+        date.is_valid().then_some(date)
+    }
+
+    /// Parses a date in the ISO 8601 form XMP uses, e.g.
+    /// "2024-03-05T14:30:00+01:00". See ISO 16684-1, sec. 8.2.1.1
+    pub fn parse_xmp(text: &str) -> Option<PdfDate> {
+        let text = text.trim();
+        let (date, time) = text.split_once('T').unwrap_or((text, ""));
+        let mut parts = date.split('-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next().map_or(Some(1), |m| m.parse().ok())?;
+        let day = parts.next().map_or(Some(1), |d| d.parse().ok())?;
+
+        let zone = time.find(['Z', '+', '-']).unwrap_or(time.len());
+        let (time, zone) = time.split_at(zone);
+        let mut parts = time.split(':');
+        let mut next = || -> Option<u8> {
+            match parts.next().filter(|p| !p.is_empty()) {
+                // Fractions of a second are dropped.
+                Some(part) => part.split('.').next()?.parse().ok(),
+                None => Some(0),
+            }
+        };
+        let (hour, minute, second) = (next()?, next()?, next()?);
+        let date = PdfDate {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            offset: parse_offset(zone.replace(':', "'").as_bytes()),
+        };
+        date.is_valid().then_some(date)
+    }
+
+    fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    /// Formats the date as a date string, e.g. "D:20240305143000+01'00'".
+    pub fn format(&self) -> String {
+        let mut text = format!(
+            "D:{:04}{:02}{:02}{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        match self.offset {
+            Some(0) => text.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                let _ = write!(text, "{}{:02}'{:02}'", sign, offset / 60, offset % 60);
+            }
+            None => {}
+        }
+        text
+    }
+
+    /// Formats the date in the ISO 8601 form XMP uses.
+    pub fn format_xmp(&self) -> String {
+        let mut text = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        );
+        match self.offset {
+            Some(0) => text.push('Z'),
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                let _ = write!(text, "{}{:02}:{:02}", sign, offset / 60, offset % 60);
+            }
+            None => {}
+        }
+        text
+    }
+}
+
+/// Parses the relationship of local time to UT: "Z", or "+" or "-" followed
+/// by hours and optionally an apostrophe and minutes.
+fn parse_offset(text: &[u8]) -> Option<i16> {
+    let (&sign, rest) = text.split_first()?;
+    let sign = match sign {
+        b'Z' => return Some(0),
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let mut numbers = rest
+        .split(|&b| b == b'\'')
+        .map(|part| std::str::from_utf8(part).ok()?.parse::<i16>().ok());
+    let hours = numbers.next()??;
+    let minutes = numbers.next().flatten().unwrap_or(0);
+    (hours < 24 && minutes < 60).then_some(sign * (hours * 60 + minutes))
+}
+
+///
+/// The document information dictionary: "metadata for general information
+/// about the document, such as the document's title, author, and creation and
+/// modification dates." See sec. 14.3.3
+///
+/// PDF 2.0 deprecates it in favour of the XMP metadata stream of the catalog,
+/// sec. 14.3.2, and `Document::set_info` writes both, mapping each entry to
+/// its equivalent XMP property and keeping the other properties of the packet.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    pub creation_date: Option<PdfDate>,
+    pub mod_date: Option<PdfDate>,
+}
+
+/// The text entries of the information dictionary, and the XMP properties
+/// that hold the same values.
+const TEXT_ENTRIES: [(&str, &str); 6] = [
+    ("Title", "dc:title"),
+    ("Author", "dc:creator"),
+    ("Subject", "dc:description"),
+    ("Keywords", "pdf:Keywords"),
+    ("Creator", "xmp:CreatorTool"),
+    ("Producer", "pdf:Producer"),
+];
+
+/// The date entries of the information dictionary, and their XMP properties.
+const DATE_ENTRIES: [(&str, &str); 2] = [
+    ("CreationDate", "xmp:CreateDate"),
+    ("ModDate", "xmp:ModifyDate"),
+];
+
+impl DocumentInfo {
+    /// Reads the entries of an information dictionary. Entries that are not
+    /// strings, or dates that do not parse, are left out.
+    pub fn from_dict(dict: &Dictionary) -> DocumentInfo {
+        let mut info = DocumentInfo::default();
+        for (key, _) in TEXT_ENTRIES {
+            *info.text_mut(key) = dict.get(key).and_then(PdfObject::as_text);
+        }
+        for (key, _) in DATE_ENTRIES {
+            *info.date_mut(key) = dict
+                .get(key)
+                .and_then(PdfObject::as_bytes)
+                .and_then(PdfDate::parse);
+        }
+        info
+    }
+
+    /// Sets the entries of an information dictionary, removing those that
+    /// are None. Other entries, such as /Trapped, are kept.
+    pub fn update_dict(&self, dict: &mut Dictionary) {
+        for (key, _) in TEXT_ENTRIES {
+            match self.text(key) {
+                Some(text) => dict.set(key, PdfObject::text_string(text)),
+                None => {
+                    dict.remove(key);
+                }
+            }
+        }
+        for (key, _) in DATE_ENTRIES {
+            match self.date(key) {
+                Some(date) => dict.set(key, PdfObject::String(date.format().into_bytes())),
+                None => {
+                    dict.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Reads the properties of an XMP packet that correspond to entries of
+    /// the information dictionary. Properties are found by their usual
+    /// namespace prefixes, in element or attribute form. The authors in
+    /// dc:creator are joined with "; ". See ISO 16684-1
+    pub fn from_xmp(xmp: &[u8]) -> DocumentInfo {
+        let xmp = String::from_utf8_lossy(xmp);
+        let mut info = DocumentInfo::default();
+        for (key, property) in TEXT_ENTRIES {
+            *info.text_mut(key) = xmp_property(&xmp, property);
+        }
+        for (key, property) in DATE_ENTRIES {
+            *info.date_mut(key) =
+                xmp_property(&xmp, property).and_then(|date| PdfDate::parse_xmp(&date));
+        }
+        info
+    }
+
+    /// Writes the entries as an XMP packet. See ISO 16684-1
+    pub fn to_xmp(&self) -> Vec<u8> {
+        format!(
+            "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+             <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+             {} \
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>",
+            self.xmp_description("")
+        )
+        .into_bytes()
+    }
+
+    /// Sets the entries in an existing XMP packet, leaving its other
+    /// properties, such as the PDF/A identification, as they are. The
+    /// properties of the entries are removed wherever they are, and those
+    /// that are not None are written in a description of their own at the end
+    /// of the rdf:RDF element, about the same resource as the packet's other
+    /// descriptions. Returns None if the packet is not UTF-8 or has no
+    /// rdf:RDF element.
+    pub fn update_xmp(&self, xmp: &[u8]) -> Option<Vec<u8>> {
+        let xmp = std::str::from_utf8(xmp).ok()?;
+        let end = xmp.rfind("</rdf:RDF>")?;
+        let mut packet = xmp[..end].to_string();
+        let about = xmp_property(&packet, "rdf:about").unwrap_or_default();
+        for (_, property) in TEXT_ENTRIES.iter().chain(DATE_ENTRIES.iter()) {
+            remove_xmp_property(&mut packet, property);
+        }
+        packet.truncate(packet.trim_end_matches([' ', '\t']).len());
+        packet.push_str(&self.xmp_description(&about));
+        packet.push(' ');
+        packet.push_str(&xmp[end..]);
+        Some(packet.into_bytes())
+    }
+
+    /// An rdf:Description element holding the properties of the entries,
+    /// about the resource named by `about`.
+    fn xmp_description(&self, about: &str) -> String {
+        let mut properties = String::new();
+        for (key, property) in TEXT_ENTRIES {
+            let Some(text) = self.text(key) else {
+                continue;
+            };
+            let text = escape_xml(text);
+            let value = match property {
+                "dc:title" | "dc:description" => {
+                    format!("<rdf:Alt><rdf:li xml:lang=\"x-default\">{text}</rdf:li></rdf:Alt>")
+                }
+                "dc:creator" => format!("<rdf:Seq><rdf:li>{text}</rdf:li></rdf:Seq>"),
+                _ => text,
+            };
+            let _ = writeln!(properties, "   <{property}>{value}</{property}>");
+        }
+        for (key, property) in DATE_ENTRIES {
+            if let Some(date) = self.date(key) {
+                let date = date.format_xmp();
+                let _ = writeln!(properties, "   <{property}>{date}</{property}>");
+            }
+        }
+        let about = escape_xml(about);
+        format!(
+            "  <rdf:Description rdf:about=\"{about}\"\n    \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n    \
+             xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n    \
+             xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">\n\
+             {properties}  \
+             </rdf:Description>\n"
+        )
+    }
+
+    /// Fills the entries that are None from another set of entries.
+    pub fn or(mut self, other: DocumentInfo) -> DocumentInfo {
+        for (key, _) in TEXT_ENTRIES {
+            if self.text(key).is_none() {
+                *self.text_mut(key) = other.text(key).cloned();
+            }
+        }
+        for (key, _) in DATE_ENTRIES {
+            if self.date(key).is_none() {
+                *self.date_mut(key) = other.date(key);
+            }
+        }
+        self
+    }
+
+    fn text(&self, key: &str) -> Option<&String> {
+        match key {
+            "Title" => self.title.as_ref(),
+            "Author" => self.author.as_ref(),
+            "Subject" => self.subject.as_ref(),
+            "Keywords" => self.keywords.as_ref(),
+            "Creator" => self.creator.as_ref(),
+            _ => self.producer.as_ref(),
+        }
+    }
+
+    fn text_mut(&mut self, key: &str) -> &mut Option<String> {
+        match key {
+            "Title" => &mut self.title,
+            "Author" => &mut self.author,
+            "Subject" => &mut self.subject,
+            "Keywords" => &mut self.keywords,
+            "Creator" => &mut self.creator,
+            _ => &mut self.producer,
+        }
+    }
+
+    fn date(&self, key: &str) -> Option<PdfDate> {
+        match key {
+            "CreationDate" => self.creation_date,
+            _ => self.mod_date,
+        }
+    }
+
+    fn date_mut(&mut self, key: &str) -> &mut Option<PdfDate> {
+        match key {
+            "CreationDate" => &mut self.creation_date,
+            _ => &mut self.mod_date,
+        }
+    }
+}
+
+/// The value of a simple property, or the items of an array property joined
+/// with "; ". Only the first item of a language alternative is read.
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut rest = xmp;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        rest = after;
+        let Some(end) = after.find('>') else {
+            break;
+        };
+        // The name must end here, and an empty element holds no value.
+        if !after.starts_with(['>', ' ', '\t', '\r', '\n']) || after[..end].ends_with('/') {
+            continue;
+        }
+        let content = &after[end + 1..];
+        let content = &content[..content.find(&close)?];
+        if !content.contains("<rdf:li") {
+            return Some(unescape_xml(content.trim()));
+        }
+        let mut items = vec![];
+        let mut list = content;
+        while let Some(item) = list.find("<rdf:li") {
+            let item = &list[item..];
+            let text = &item[item.find('>')? + 1..];
+            let end = text.find("</rdf:li>")?;
+            items.push(unescape_xml(text[..end].trim()));
+            list = &text[end..];
+            if content.contains("<rdf:Alt") {
+                break;
+            }
+        }
+        return Some(items.join("; "));
+    }
+
+    // The attribute form, name="value", of a simple property.
+    for quote in ['"', '\''] {
+        let attribute = format!("{}={}", name, quote);
+        for (start, _) in xmp.match_indices(&attribute) {
+            // The name must start after white space.
+            if !xmp[..start].ends_with(char::is_whitespace) {
+                continue;
+            }
+            let value = &xmp[start + attribute.len()..];
+            return Some(unescape_xml(&value[..value.find(quote)?]));
+        }
+    }
+    None
+}
+
+/// Removes a property from an XMP packet, in both element and attribute
+/// form, wherever it occurs.
+fn remove_xmp_property(xmp: &mut String, name: &str) {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut from = 0;
+    while let Some(start) = xmp[from..].find(&open).map(|start| from + start) {
+        let after = &xmp[start + open.len()..];
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        if !after.starts_with(['>', '/', ' ', '\t', '\r', '\n']) {
+            from = start + open.len();
+            continue;
+        }
+        let end = if after[..tag_end].ends_with('/') {
+            start + open.len() + tag_end + 1
+        } else {
+            match after.find(&close) {
+                Some(end) => start + open.len() + end + close.len(),
+                None => break,
+            }
+        };
+        // Take the line with it if the element is all there is on it.
+        let line_start = xmp[..start].trim_end_matches([' ', '\t']).len();
+        let (start, end) = match xmp[end..].strip_prefix('\n') {
+            Some(_) if xmp[..line_start].ends_with('\n') => (line_start, end + 1),
+            _ => (start, end),
+        };
+        xmp.replace_range(start..end, "");
+        from = start;
+    }
+
+    for quote in ['"', '\''] {
+        let attribute = format!("{}={}", name, quote);
+        let mut from = 0;
+        while let Some(start) = xmp[from..].find(&attribute).map(|start| from + start) {
+            let value = start + attribute.len();
+            let Some(end) = xmp[value..].find(quote) else {
+                break;
+            };
+            // The name must start after white space.
+            let space = xmp[..start].trim_end().len();
+            if space == start {
+                from = value;
+                continue;
+            }
+            xmp.replace_range(space..value + end + 1, "");
+            from = space;
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => entity.strip_prefix('#').and_then(|d| d.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+impl Document {
+    /// The document information, from the trailer's /Info dictionary, with
+    /// the entries it lacks taken from the catalog's XMP metadata.
+    pub fn info(&self) -> DocumentInfo {
+        let info = self
+            .resolve_dict(self.trailer(), "Info")
+            .map(DocumentInfo::from_dict)
+            .unwrap_or_default();
+        match self.xmp_metadata() {
+            Some(xmp) => info.or(DocumentInfo::from_xmp(&xmp)),
+            None => info,
+        }
+    }
+
+    /// Sets the document information, in both the /Info dictionary and the
+    /// catalog's XMP metadata stream. The properties of an existing packet
+    /// other than those of the information dictionary are kept, and a packet
+    /// that cannot be updated is left as it is.
+    pub fn set_info(&mut self, info: &DocumentInfo) {
+        let mut dict = self.info_dict();
+        info.update_dict(&mut dict);
+        self.set_info_dict(dict);
+        let xmp = match self.catalog().and_then(|catalog| catalog.get("Metadata")) {
+            Some(_) => self.xmp_metadata().and_then(|xmp| info.update_xmp(&xmp)),
+            None => Some(info.to_xmp()),
+        };
+        if let Some(xmp) = xmp {
+            self.set_metadata_stream(xmp);
+        }
+    }
+
+    /// The decoded XMP packet of the catalog's /Metadata stream.
+    /// See sec. 14.3.2
+    pub fn xmp_metadata(&self) -> Option<Vec<u8>> {
+        let stream = self
+            .catalog()
+            .and_then(|catalog| self.resolve_key(catalog, "Metadata"))?
+            .as_stream()?;
+        self.decode_stream(stream).ok()
+    }
+
+    /// Replaces the XMP metadata of the document, and updates the /Info
+    /// dictionary with the properties the packet has for its entries.
+    pub fn set_xmp_metadata(&mut self, xmp: Vec<u8>) {
+        let mut dict = self.info_dict();
+        let info = DocumentInfo::from_xmp(&xmp).or(DocumentInfo::from_dict(&dict));
+        info.update_dict(&mut dict);
+        self.set_info_dict(dict);
+        self.set_metadata_stream(xmp);
+    }
+
+    fn info_dict(&self) -> Dictionary {
+        self.resolve_dict(self.trailer(), "Info")
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_info_dict(&mut self, dict: Dictionary) {
+        let dict = PdfObject::Dictionary(dict);
+        match self.trailer().get("Info").and_then(PdfObject::as_reference) {
+            Some(id) if self.get(id).is_some() => {
+                self.pdf.body.objects.insert(id, dict);
+            }
+            _ => {
+                let id = self.add_object(dict);
+                self.pdf
+                    .trailer
+                    .entries
+                    .set("Info", PdfObject::Reference(id));
+            }
+        }
+    }
+
+    /// Stores an XMP packet, uncompressed so that tools that do not parse PDF
+    /// can find it, in the /Metadata stream of the catalog.
+    fn set_metadata_stream(&mut self, xmp: Vec<u8>) {
+        let Some(catalog_id) = self.catalog_id() else {
+            return;
+        };
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("Metadata".to_string()));
+        dict.set("Subtype", PdfObject::Name("XML".to_string()));
+        dict.set("Length", PdfObject::Integer(xmp.len() as i64));
+        let stream = PdfObject::Stream(Stream::new(dict, xmp));
+
+        let existing: Option<ObjectId> = self
+            .catalog()
+            .and_then(|catalog| catalog.get("Metadata"))
+            .and_then(PdfObject::as_reference);
+        let id = match existing {
+            Some(id) => {
+                self.pdf.body.objects.insert(id, stream);
+                id
+            }
+            None => self.add_object(stream),
+        };
+        if let Some(PdfObject::Dictionary(catalog)) = self.get_mut(catalog_id) {
+            catalog.set("Metadata", PdfObject::Reference(id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::document::tests::reload;
+
+    #[test]
+    fn test_dates() {
+        let date = PdfDate::parse(b"D:199812231952-08'00'").unwrap();
+        assert_eq!(
+            date,
+            PdfDate {
+                offset: Some(-480),
+                ..PdfDate::new(1998, 12, 23, 19, 52, 0)
+            }
+        );
+        assert_eq!(date.format(), "D:19981223195200-08'00'");
+        assert_eq!(date.format_xmp(), "1998-12-23T19:52:00-08:00");
+        assert_eq!(PdfDate::parse_xmp(&date.format_xmp()), Some(date));
+
+        let date = PdfDate::parse(b"D:2024030514").unwrap();
+        assert_eq!(date, PdfDate::new(2024, 3, 5, 14, 0, 0));
+        assert_eq!(PdfDate::parse(b"20240305143000Z").unwrap().offset, Some(0));
+        assert_eq!(
+            PdfDate::parse(b"D:20240305143000+05'30").unwrap().offset,
+            Some(330)
+        );
+        assert_eq!(
+            PdfDate::parse_xmp("2024-03-05T14:30:00.25Z"),
+            Some(PdfDate {
+                offset: Some(0),
+                ..PdfDate::new(2024, 3, 5, 14, 30, 0)
+            })
+        );
+        assert_eq!(
+            PdfDate::parse_xmp("2024"),
+            Some(PdfDate::new(2024, 1, 1, 0, 0, 0))
+        );
+        assert!(PdfDate::parse(b"D:20241305").is_none());
+        assert!(PdfDate::parse(b"yesterday").is_none());
+    }
+
+    fn sample_info() -> DocumentInfo {
+        DocumentInfo {
+            title: Some("Statement <March> & more".to_string()),
+            author: Some("Jörg Müller".to_string()),
+            subject: Some("Monthly statement".to_string()),
+            keywords: Some("bank, statement".to_string()),
+            creator: Some("Ledger 3.2".to_string()),
+            producer: Some("pdf".to_string()),
+            creation_date: Some(PdfDate {
+                offset: Some(60),
+                ..PdfDate::new(2024, 3, 5, 14, 30, 0)
+            }),
+            mod_date: None,
+        }
+    }
+
+    #[test]
+    fn test_xmp() {
+        let info = sample_info();
+        let xmp = info.to_xmp();
+        assert_eq!(DocumentInfo::from_xmp(&xmp), info);
+
+        let xmp = br#"<x:xmpmeta><rdf:RDF><rdf:Description rdf:about=""
+            pdf:Producer="Distiller &#x263A;" xmp:ModifyDate='2020-01-02T03:04:05Z'>
+            <dc:creator><rdf:Seq><rdf:li>Ann</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq></dc:creator>
+            <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Report</rdf:li>
+            <rdf:li xml:lang="de">Bericht</rdf:li></rdf:Alt></dc:title>
+            <dc:titles/>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let info = DocumentInfo::from_xmp(xmp);
+        assert_eq!(info.title.as_deref(), Some("Report"));
+        assert_eq!(info.author.as_deref(), Some("Ann; Bob"));
+        assert_eq!(info.producer.as_deref(), Some("Distiller ☺"));
+        assert_eq!(info.mod_date.unwrap().year, 2020);
+        assert!(info.subject.is_none());
+    }
+
+    #[test]
+    fn test_update_xmp() {
+        let xmp = br#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/"
+    xmlns:pdf="http://ns.adobe.com/pdf/1.3/"
+    pdfaid:part="2" pdfaid:conformance="B" pdf:Producer="Old producer"
+    xpdf:Producer="Not the producer"/>
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/">
+   <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Old title</rdf:li></rdf:Alt></dc:title>
+   <dc:titles>Kept</dc:titles>
+   <dc:format>application/pdf</dc:format>
+   <xmpMM:DocumentID>uuid:1234</xmpMM:DocumentID>
+   <dc:description/>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+        let info = sample_info();
+        let updated = info.update_xmp(xmp).unwrap();
+        assert_eq!(DocumentInfo::from_xmp(&updated), info);
+        let updated = String::from_utf8(updated).unwrap();
+        for kept in [
+            "pdfaid:part=\"2\"",
+            "pdfaid:conformance=\"B\"",
+            "xpdf:Producer=\"Not the producer\"",
+            "<dc:titles>Kept</dc:titles>",
+            "<dc:format>application/pdf</dc:format>",
+            "<xmpMM:DocumentID>uuid:1234</xmpMM:DocumentID>",
+        ] {
+            assert!(updated.contains(kept), "{kept}");
+        }
+        assert!(!updated.contains("Old"));
+        assert!(!updated.contains("<dc:description/>"));
+        assert!(updated.ends_with(" </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>"));
+
+        // Entries that are None are removed from the packet.
+        let updated = DocumentInfo::default().update_xmp(xmp).unwrap();
+        assert_eq!(DocumentInfo::from_xmp(&updated), DocumentInfo::default());
+        assert!(String::from_utf8(updated).unwrap().contains("pdfaid:part"));
+        assert!(DocumentInfo::default()
+            .update_xmp(b"<x:xmpmeta/>")
+            .is_none());
+
+        // The new description is about the same resource as the others.
+        let xmp = br#"<x:xmpmeta><rdf:RDF><rdf:Description
+            rdf:about='uuid:5678 "a&amp;b"' pdf:Producer="Old"/></rdf:RDF></x:xmpmeta>"#;
+        let updated = String::from_utf8(info.update_xmp(xmp).unwrap()).unwrap();
+        assert_eq!(
+            updated
+                .matches(r#"rdf:about="uuid:5678 &quot;a&amp;b&quot;""#)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_document_info() {
+        let mut doc = Document::load("samples/rc4-40.pdf").unwrap();
+        let info = doc.info();
+        assert_eq!(info.title.as_deref(), Some("Secret (report)"));
+        assert!(doc.xmp_metadata().is_none());
+
+        let mut dict = Dictionary::new();
+        dict.set("Trapped", PdfObject::Name("False".to_string()));
+        let id = doc.add_object(PdfObject::Dictionary(dict));
+        doc.pdf
+            .trailer
+            .entries
+            .set("Info", PdfObject::Reference(id));
+        doc.set_info(&sample_info());
+
+        let mut doc = reload(&doc);
+        assert_eq!(doc.info(), sample_info());
+        let dict = doc.resolve_dict(doc.trailer(), "Info").unwrap();
+        assert_eq!(dict.get_name("Trapped"), Some("False"));
+        assert_eq!(
+            dict.get("CreationDate").and_then(PdfObject::as_bytes),
+            Some(&b"D:20240305143000+01'00'"[..])
+        );
+        let xmp = doc.xmp_metadata().unwrap();
+        assert_eq!(DocumentInfo::from_xmp(&xmp), sample_info());
+
+        // Properties of other schemas in the packet survive set_info.
+        let pdfa = String::from_utf8(xmp.clone()).unwrap().replace(
+            "<rdf:Description",
+            "<rdf:Description rdf:about=\"\" \
+             xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\" pdfaid:part=\"3\"/>\n  \
+             <rdf:Description",
+        );
+        doc.set_xmp_metadata(pdfa.into_bytes());
+        doc.set_info(&DocumentInfo {
+            title: Some("Renamed".to_string()),
+            ..sample_info()
+        });
+        let updated = String::from_utf8(doc.xmp_metadata().unwrap()).unwrap();
+        assert!(updated.contains("pdfaid:part=\"3\""));
+        assert_eq!(
+            DocumentInfo::from_xmp(updated.as_bytes()).title.as_deref(),
+            Some("Renamed")
+        );
+        doc.set_info(&sample_info());
+
+        // The XMP packet updates /Info, and fills in what it lacks when read.
+        let xmp = String::from_utf8(xmp)
+            .unwrap()
+            .replace("Ledger 3.2", "Ledger 4");
+        doc.set_xmp_metadata(xmp.into_bytes());
+        assert_eq!(doc.info().creator.as_deref(), Some("Ledger 4"));
+        if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(id) {
+            dict.remove("Title");
+        }
+        assert_eq!(
+            doc.info(),
+            DocumentInfo {
+                creator: Some("Ledger 4".to_string()),
+                ..sample_info()
+            }
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::api::{
+    document::Document,
+    filters::flate_encode,
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    outline::Destination,
+};
+
+///
+/// A file embedded in the document, found through the /EmbeddedFiles name
+/// tree. The name is the key of the tree; the file name is the one given by
+/// its file specification. See sec. 7.11.4
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedFile {
+    pub name: String,
+    pub file_name: String,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl EmbeddedFile {
+    pub fn new(name: &str, data: Vec<u8>) -> EmbeddedFile {
+        EmbeddedFile {
+            name: name.to_string(),
+            file_name: name.to_string(),
+            description: None,
+            mime_type: None,
+            data,
+        }
+    }
+}
+
+impl Document {
+    /// The named destinations of the document: those of the /Dests name tree
+    /// and of the catalog's /Dests dictionary, which PDF 1.1 used instead,
+    /// sorted by name. Destinations of pages that do not exist are left out.
+    /// See sec. 12.3.2.4
+    pub fn named_destinations(&self) -> Vec<(String, Destination)> {
+        let pages: Vec<Option<ObjectId>> = self.pages().iter().map(|page| page.id).collect();
+        let mut dests: Vec<(String, Destination)> = self
+            .name_tree_entries("Dests")
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, self.destination(value, &pages)?)))
+            .collect();
+        if let Some(dict) = self
+            .catalog()
+            .and_then(|catalog| self.resolve_dict(catalog, "Dests"))
+        {
+            for (name, value) in dict.iter() {
+                if let Some(dest) = self.destination(value, &pages) {
+                    dests.push((name.clone(), dest));
+                }
+            }
+        }
+        dests.sort_by(|(a, _), (b, _)| a.cmp(b));
+        dests
+    }
+
+    /// Replaces the named destinations with a /Dests name tree holding the
+    /// given ones, and removes the catalog's /Dests dictionary. Destinations
+    /// whose page index is out of range are left out.
+    pub fn set_named_destinations(&mut self, dests: &[(String, Destination)]) {
+        let pages: Vec<Option<ObjectId>> = self.pages().iter().map(|page| page.id).collect();
+        let entries = dests
+            .iter()
+            .filter_map(|(name, dest)| {
+                let page = (*pages.get(dest.page)?)?;
+                Some((name_key(name), dest.to_array(page)))
+            })
+            .collect();
+        self.set_name_tree("Dests", entries);
+        if let Some(PdfObject::Dictionary(catalog)) =
+            self.catalog_id().and_then(|id| self.get_mut(id))
+        {
+            catalog.remove("Dests");
+        }
+    }
+
+    /// The files embedded in the document, sorted by name. Files whose
+    /// stream cannot be decoded are left out. See sec. 7.11.3 and 7.11.4
+    pub fn embedded_files(&self) -> Vec<EmbeddedFile> {
+        self.name_tree_entries("EmbeddedFiles")
+            .into_iter()
+            .filter_map(|(name, value)| self.file_spec(self.resolve(value).as_dict()?, name))
+            .collect()
+    }
+
+    /// Replaces the embedded files with the given ones, compressing their
+    /// data.
+    pub fn set_embedded_files(&mut self, files: &[EmbeddedFile]) {
+        let entries = files
+            .iter()
+            .map(|file| {
+                let spec = self.add_file_spec(file);
+                (name_key(&file.name), PdfObject::Reference(spec))
+            })
+            .collect();
+        self.set_name_tree("EmbeddedFiles", entries);
+    }
+
+    /// The document-level JavaScript: the name and script of each JavaScript
+    /// action in the /JavaScript name tree, which are run when the document
+    /// is opened. See sec. 12.6.4.17
+    pub fn javascript(&self) -> Vec<(String, String)> {
+        self.name_tree_entries("JavaScript")
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let action = self.resolve(value).as_dict()?;
+                if action.get_name("S") != Some("JavaScript") {
+                    return None;
+                }
+                let script = match self.resolve_key(action, "JS")? {
+                    PdfObject::Stream(stream) => {
+                        PdfObject::String(self.decode_stream(stream).ok()?).as_text()?
+                    }
+                    script => script.as_text()?,
+                };
+                Some((name, script))
+            })
+            .collect()
+    }
+
+    /// Replaces the document-level JavaScript with the given scripts.
+    pub fn set_javascript(&mut self, scripts: &[(String, String)]) {
+        let entries = scripts
+            .iter()
+            .map(|(name, script)| {
+                let mut action = Dictionary::new();
+                action.set("S", PdfObject::Name("JavaScript".to_string()));
+                action.set("JS", PdfObject::text_string(script));
+                (name_key(name), PdfObject::Dictionary(action))
+            })
+            .collect();
+        self.set_name_tree("JavaScript", entries);
+    }
+
+    /// Reads a file specification with an embedded file stream, named
+    /// `name`. See sec. 7.11.3 and 7.11.4
+    pub(crate) fn file_spec(&self, spec: &Dictionary, name: String) -> Option<EmbeddedFile> {
+        let text = |key: &str| self.resolve_key(spec, key).and_then(PdfObject::as_text);
+        let streams = self.resolve_dict(spec, "EF")?;
+        let stream = ["UF", "F"]
+            .iter()
+            .find_map(|key| self.resolve_key(streams, key)?.as_stream())?;
+        Some(EmbeddedFile {
+            file_name: text("UF").or_else(|| text("F")).unwrap_or(name.clone()),
+            name,
+            description: text("Desc"),
+            mime_type: stream.dict.get_name("Subtype").map(str::to_string),
+            data: self.decode_stream(stream).ok()?,
+        })
+    }
+
+    /// Adds a file specification embedding the file, with its data
+    /// compressed.
+    pub(crate) fn add_file_spec(&mut self, file: &EmbeddedFile) -> ObjectId {
+        let data = flate_encode(&file.data);
+        let mut params = Dictionary::new();
+        params.set("Size", PdfObject::Integer(file.data.len() as i64));
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("EmbeddedFile".to_string()));
+        if let Some(mime_type) = &file.mime_type {
+            dict.set("Subtype", PdfObject::Name(mime_type.clone()));
+        }
+        dict.set("Params", PdfObject::Dictionary(params));
+        dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+        dict.set("Length", PdfObject::Integer(data.len() as i64));
+        let stream = self.add_object(PdfObject::Stream(Stream::new(dict, data)));
+
+        let mut streams = Dictionary::new();
+        streams.set("F", PdfObject::Reference(stream));
+        streams.set("UF", PdfObject::Reference(stream));
+        let mut spec = Dictionary::new();
+        spec.set("Type", PdfObject::Name("Filespec".to_string()));
+        spec.set("F", PdfObject::text_string(&file.file_name));
+        spec.set("UF", PdfObject::text_string(&file.file_name));
+        if let Some(description) = &file.description {
+            spec.set("Desc", PdfObject::text_string(description));
+        }
+        spec.set("EF", PdfObject::Dictionary(streams));
+        self.add_object(PdfObject::Dictionary(spec))
+    }
+
+    /// The entries of one of the name trees of the catalog's /Names
+    /// dictionary, with the names decoded as text. See sec. 7.7.4
+    fn name_tree_entries(&self, key: &str) -> Vec<(String, &PdfObject)> {
+        let Some(tree) = self
+            .catalog()
+            .and_then(|catalog| self.resolve_dict(catalog, "Names"))
+            .and_then(|names| self.resolve_dict(names, key))
+        else {
+            return vec![];
+        };
+        self.name_tree(tree)
+            .into_iter()
+            .filter_map(|(name, value)| Some((PdfObject::String(name).as_text()?, value)))
+            .collect()
+    }
+
+    /// Replaces one of the name trees of the /Names dictionary, creating the
+    /// dictionary if there is none, or removes the tree if there are no
+    /// entries.
+    pub(crate) fn set_name_tree(&mut self, key: &str, entries: Vec<(Vec<u8>, PdfObject)>) {
+        let Some(catalog_id) = self.catalog_id() else {
+            return;
+        };
+        let tree = (!entries.is_empty()).then(|| {
+            let root = self.add_name_tree(entries);
+            PdfObject::Reference(self.add_object(PdfObject::Dictionary(root)))
+        });
+        let update = |names: &mut Dictionary| match tree {
+            Some(tree) => names.set(key, tree),
+            None => {
+                names.remove(key);
+            }
+        };
+        let names = self.catalog().and_then(|catalog| catalog.get("Names"));
+        if let Some(id) = names.and_then(PdfObject::as_reference) {
+            if let Some(PdfObject::Dictionary(names)) = self.get_mut(id) {
+                update(names);
+            }
+            return;
+        }
+        let mut names = names
+            .and_then(PdfObject::as_dict)
+            .cloned()
+            .unwrap_or_default();
+        update(&mut names);
+        if let Some(PdfObject::Dictionary(catalog)) = self.get_mut(catalog_id) {
+            if names.is_empty() {
+                catalog.remove("Names");
+            } else {
+                catalog.set("Names", PdfObject::Dictionary(names));
+            }
+        }
+    }
+}
+
+/// The key of a name tree entry: the name as a text string, which is how
+/// `name_tree_entries` reads it back.
+fn name_key(name: &str) -> Vec<u8> {
+    PdfObject::text_string(name)
+        .as_bytes()
+        .map(<[u8]>::to_vec)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{builder::DocumentBuilder, document::tests::reload, outline::Fit};
+
+    #[test]
+    fn test_named_destinations() {
+        let mut builder = DocumentBuilder::new();
+        for _ in 0..3 {
+            builder.add_page([0.0, 0.0, 612.0, 792.0], |_| {});
+        }
+        let mut doc = builder.build();
+        let top = Destination {
+            page: 2,
+            fit: Fit::FitH { top: Some(700.0) },
+        };
+        doc.set_named_destinations(&[
+            ("chapter-2".to_string(), top),
+            ("chapter-1".to_string(), Destination::new(0)),
+            ("missing".to_string(), Destination::new(7)),
+            ("résumé".to_string(), Destination::new(1)),
+            ("目次".to_string(), Destination::new(1)),
+        ]);
+        let doc = reload(&doc);
+        assert_eq!(
+            doc.named_destinations(),
+            vec![
+                ("chapter-1".to_string(), Destination::new(0)),
+                ("chapter-2".to_string(), top),
+                ("résumé".to_string(), Destination::new(1)),
+                ("目次".to_string(), Destination::new(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_embedded_files_and_javascript() {
+        let mut doc = DocumentBuilder::new().build();
+        assert!(doc.embedded_files().is_empty());
+        let mut file = EmbeddedFile::new("data.csv", b"a,b\n1,2\n".to_vec());
+        file.mime_type = Some("text/csv".to_string());
+        file.description = Some("Données".to_string());
+        doc.set_embedded_files(&[file.clone()]);
+        doc.set_javascript(&[("init".to_string(), "app.alert('hi');".to_string())]);
+
+        let mut doc = reload(&doc);
+        assert_eq!(doc.embedded_files(), vec![file]);
+        assert_eq!(
+            doc.javascript(),
+            vec![("init".to_string(), "app.alert('hi');".to_string())]
+        );
+
+        doc.set_embedded_files(&[]);
+        assert!(doc.embedded_files().is_empty());
+        assert_eq!(doc.javascript().len(), 1);
+        let names = doc.resolve_dict(doc.catalog().unwrap(), "Names").unwrap();
+        assert!(!names.contains_key("EmbeddedFiles"));
+        doc.set_javascript(&[]);
+        assert!(!doc.catalog().unwrap().contains_key("Names"));
+    }
+}
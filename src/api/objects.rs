@@ -0,0 +1,366 @@
+use bstr::ByteSlice;
+
+use crate::api::{
+    characters::{pdf_doc_byte, pdf_doc_char},
+    filters,
+    read::cmap::utf16_be_to_string,
+};
+
+///
+/// Indirect objects are identified by their object number and generation number.
+/// Together these form the object identifier used by references ("12 0 R") and by
+/// the cross-reference table.
+///
+pub type ObjectId = (i32, i32);
+
+///
+/// A PDF object of one of the nine basic types defined in sec. 7.3 (pg. 24),
+/// along with the reference type used to point at indirect objects:
+///     - Boolean values
+///     - Integer and real numbers
+///     - Strings
+///     - Names
+///     - Arrays
+///     - Dictionaries
+///     - Streams
+///     - The null object
+///
+/// Names are stored with their "#xx" escapes already resolved and without the
+/// leading SOLIDUS. Strings are stored as their raw bytes, as they are not
+/// required to be valid UTF-8.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdfObject {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    String(Vec<u8>),
+    Name(String),
+    Array(Vec<PdfObject>),
+    Dictionary(Dictionary),
+    Stream(Stream),
+    Reference(ObjectId),
+}
+
+impl PdfObject {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PdfObject::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            PdfObject::Integer(i) => Some(*i),
+            PdfObject::Real(r) => Some(*r as i64),
+            _ => None,
+        }
+    }
+
+    /// Integers and reals are interchangeable wherever a number is expected.
+    /// See sec. 7.3.3 (pg. 25).
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            PdfObject::Integer(i) => Some(*i as f64),
+            PdfObject::Real(r) => Some(*r),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            PdfObject::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            PdfObject::String(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<PdfObject>> {
+        match self {
+            PdfObject::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// Streams carry a dictionary of their own, so both dictionaries and streams
+    /// are returned here.
+    pub fn as_dict(&self) -> Option<&Dictionary> {
+        match self {
+            PdfObject::Dictionary(dict) => Some(dict),
+            PdfObject::Stream(stream) => Some(&stream.dict),
+            _ => None,
+        }
+    }
+
+    pub fn as_stream(&self) -> Option<&Stream> {
+        match self {
+            PdfObject::Stream(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    pub fn as_reference(&self) -> Option<ObjectId> {
+        match self {
+            PdfObject::Reference(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, PdfObject::Null)
+    }
+
+    /// Reads an array of numbers, such as a rectangle or a matrix.
+    pub fn as_numbers(&self) -> Option<Vec<f64>> {
+        self.as_array()?.iter().map(|n| n.as_f64()).collect()
+    }
+
+    /// A text string, such as a document title. Text is written in
+    /// PDFDocEncoding if it can be, and as UTF-16BE with a byte order mark
+    /// otherwise. See sec. 7.9.2.2 (pg. 111)
+    pub fn text_string(text: &str) -> PdfObject {
+        if let Some(bytes) = text.chars().map(pdf_doc_byte).collect() {
+            return PdfObject::String(bytes);
+        }
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        PdfObject::String(bytes)
+    }
+
+    /// Decodes a text string: UTF-16BE or UTF-8 if it starts with the byte
+    /// order mark of either, and PDFDocEncoding otherwise.
+    /// See sec. 7.9.2.2 (pg. 111)
+    pub fn as_text(&self) -> Option<String> {
+        let bytes = self.as_bytes()?;
+        let text = match bytes {
+            [0xFE, 0xFF, rest @ ..] => utf16_be_to_string(rest),
+            [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+            _ => bytes.iter().map(|&b| pdf_doc_char(b)).collect(),
+        };
+        Some(text)
+    }
+}
+
+///
+/// "A dictionary object is an associative table containing pairs of objects,
+/// known as the dictionary’s entries. The first element of each entry is the key
+/// and the second element is the value. The key shall be a name."
+/// See sec. 7.3.7 (pg. 30)
+///
+/// The entries are kept in the order they were read so that a document can be
+/// written back out in the same shape. Setting an entry to the null object is
+/// equivalent to removing it.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dictionary {
+    entries: Vec<(String, PdfObject)>,
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&PdfObject> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .filter(|v| !v.is_null())
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut PdfObject> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Inserts or replaces the entry for the given key.
+    pub fn set(&mut self, key: impl Into<String>, value: PdfObject) {
+        let key = key.into();
+        match self.get_mut(&key) {
+            Some(existing) => *existing = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<PdfObject> {
+        let index = self.entries.iter().position(|(k, _)| k == key)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &PdfObject)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&String, &mut PdfObject)> {
+        self.entries.iter_mut().map(|(k, v)| (&*k, v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get_name(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(PdfObject::as_name)
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        self.get(key).and_then(PdfObject::as_i64)
+    }
+
+    pub fn get_f64(&self, key: &str) -> Option<f64> {
+        self.get(key).and_then(PdfObject::as_f64)
+    }
+
+    /// Checks the /Type entry of the dictionary.
+    pub fn has_type(&self, type_name: &str) -> bool {
+        self.get_name("Type") == Some(type_name)
+    }
+}
+
+impl FromIterator<(String, PdfObject)> for Dictionary {
+    fn from_iter<T: IntoIterator<Item = (String, PdfObject)>>(iter: T) -> Self {
+        let mut dict = Dictionary::new();
+        for (key, value) in iter {
+            dict.set(key, value);
+        }
+        dict
+    }
+}
+
+///
+/// "A stream object, like a string object, is a sequence of bytes. Furthermore,
+/// a stream may be of unlimited length, whereas a string shall be subject to an
+/// implementation limit."
+/// See sec. 7.3.8 (pg. 31)
+///
+/// A stream consists of a dictionary followed by the bytes between the "stream"
+/// and "endstream" keywords. The data is kept encoded exactly as read; use
+/// `Stream::decode` to apply the filters named in the /Filter entry.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub dict: Dictionary,
+    pub data: Vec<u8>,
+}
+
+impl Stream {
+    pub fn new(dict: Dictionary, data: Vec<u8>) -> Self {
+        Self { dict, data }
+    }
+
+    /// Applies every filter listed in the stream dictionary, in order.
+    /// See sec. 7.4 (pg. 34)
+    pub fn decode(&self) -> Result<Vec<u8>, filters::FilterError> {
+        filters::decode_stream(self)
+    }
+
+    /// The filter names applied to the stream. /Filter may be a single name or
+    /// an array of names.
+    pub fn filters(&self) -> Vec<&str> {
+        match self.dict.get("Filter") {
+            Some(PdfObject::Name(name)) => vec![name.as_str()],
+            Some(PdfObject::Array(names)) => names.iter().filter_map(|n| n.as_name()).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Writes an object using PDF syntax. This is a compact, single-line
+/// representation mostly useful for debugging.
+impl std::fmt::Display for PdfObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PdfObject::Null => write!(f, "null"),
+            PdfObject::Boolean(b) => write!(f, "{}", b),
+            PdfObject::Integer(i) => write!(f, "{}", i),
+            PdfObject::Real(r) => write!(f, "{}", r),
+            PdfObject::String(s) => write!(f, "({})", s.as_bstr()),
+            PdfObject::Name(n) => write!(f, "/{}", n),
+            PdfObject::Array(a) => {
+                write!(f, "[")?;
+                for (i, item) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            PdfObject::Dictionary(d) => write_dictionary(f, d),
+            PdfObject::Stream(s) => {
+                write_dictionary(f, &s.dict)?;
+                write!(f, " stream[{} bytes]", s.data.len())
+            }
+            PdfObject::Reference((num, gen)) => write!(f, "{} {} R", num, gen),
+        }
+    }
+}
+
+fn write_dictionary(f: &mut std::fmt::Formatter<'_>, dict: &Dictionary) -> std::fmt::Result {
+    write!(f, "<<")?;
+    for (key, value) in dict.iter() {
+        write!(f, "/{} {}", key, value)?;
+    }
+    write!(f, ">>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary_set_get() {
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("Page".to_string()));
+        dict.set("Rotate", PdfObject::Integer(90));
+        dict.set("Rotate", PdfObject::Integer(180));
+        assert_eq!(dict.len(), 2);
+        assert!(dict.has_type("Page"));
+        assert_eq!(dict.get_i64("Rotate"), Some(180));
+        assert_eq!(dict.remove("Rotate"), Some(PdfObject::Integer(180)));
+        assert!(dict.get("Rotate").is_none());
+    }
+
+    #[test]
+    fn test_display() {
+        let object = PdfObject::Array(vec![
+            PdfObject::Reference((1, 0)),
+            PdfObject::Name("XYZ".to_string()),
+            PdfObject::Null,
+        ]);
+        assert_eq!(object.to_string(), "[1 0 R /XYZ null]");
+    }
+
+    #[test]
+    fn test_text_string() {
+        for text in ["Report", "Grüße – 5 €", "日本語"] {
+            assert_eq!(PdfObject::text_string(text).as_text().unwrap(), text);
+        }
+        assert_eq!(
+            PdfObject::text_string("Grüße"),
+            PdfObject::String(b"Gr\xFC\xDFe".to_vec())
+        );
+        let utf8 = PdfObject::String(b"\xEF\xBB\xBFCaf\xC3\xA9".to_vec());
+        assert_eq!(utf8.as_text().unwrap(), "Café");
+        assert!(PdfObject::Integer(1).as_text().is_none());
+    }
+}
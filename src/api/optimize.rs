@@ -0,0 +1,303 @@
+use std::collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap};
+
+use crate::api::{
+    edit::{is_shareable, serialize},
+    objects::{Dictionary, ObjectId, PdfObject},
+    structure::PDF,
+};
+
+///
+/// What `optimize` removed from a document: the objects that could not be
+/// reached from the trailer, and the copies of objects identical to others.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptimizeStats {
+    pub unreachable: usize,
+    pub duplicates: usize,
+}
+
+/// Drops the objects that cannot be reached from the trailer (/Root, /Info
+/// and any other references it holds), merges objects that are byte-for-byte
+/// identical, such as a font embedded by each of several merged documents,
+/// and renumbers the remaining objects from 1 with generation 0. Every
+/// reference, including those of the trailer, is rewritten to match.
+///
+/// Only resources are merged, such as fonts, images and color spaces; pages,
+/// annotations, outline items and other objects with an identity of their
+/// own stay separate, see `edit::is_shareable`. See sec. 7.3.10 (pg. 33)
+pub fn optimize(pdf: &mut PDF) -> OptimizeStats {
+    let (objects, trailer, stats) = optimized(&pdf.body.objects, &pdf.trailer.entries);
+    pdf.body.objects = objects;
+    pdf.trailer.entries = trailer;
+    stats
+}
+
+/// Like `optimize`, returning the optimized objects and trailer instead of
+/// changing the document.
+pub fn optimized(
+    objects: &BTreeMap<ObjectId, PdfObject>,
+    trailer: &Dictionary,
+) -> (BTreeMap<ObjectId, PdfObject>, Dictionary, OptimizeStats) {
+    let reachable = reachable(objects, trailer);
+
+    // Merging two objects can make the objects that refer to them identical
+    // too, so duplicates are looked for until no more are found.
+    let mut merged: HashMap<ObjectId, ObjectId> = HashMap::new();
+    loop {
+        let mut seen: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+        let mut found = false;
+        for id in &reachable {
+            let object = &objects[id];
+            if merged.contains_key(id) || !is_shareable(object) {
+                continue;
+            }
+            let key = serialize(&renumber(object, &|id| Some(canonical(&merged, id))));
+            match seen.entry(key) {
+                Entry::Occupied(kept) => {
+                    merged.insert(*id, *kept.get());
+                    found = true;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(*id);
+                }
+            }
+        }
+        if !found {
+            break;
+        }
+    }
+
+    let numbers: HashMap<ObjectId, ObjectId> = reachable
+        .iter()
+        .filter(|id| !merged.contains_key(id))
+        .enumerate()
+        .map(|(index, &id)| (id, (index as i32 + 1, 0)))
+        .collect();
+    let new_id = |id: ObjectId| numbers.get(&canonical(&merged, id)).copied();
+
+    let mut optimized = BTreeMap::new();
+    for (id, number) in &numbers {
+        optimized.insert(*number, renumber(&objects[id], &new_id));
+    }
+    let mut entries: Dictionary = trailer
+        .iter()
+        .map(|(key, value)| (key.clone(), renumber(value, &new_id)))
+        .collect();
+    entries.set("Size", PdfObject::Integer(numbers.len() as i64 + 1));
+
+    let stats = OptimizeStats {
+        unreachable: objects.len() - reachable.len(),
+        duplicates: merged.len(),
+    };
+    (optimized, entries, stats)
+}
+
+/// The objects that the trailer refers to, directly or through other objects.
+fn reachable(objects: &BTreeMap<ObjectId, PdfObject>, trailer: &Dictionary) -> BTreeSet<ObjectId> {
+    let mut reachable = BTreeSet::new();
+    let mut pending: Vec<&PdfObject> = trailer.iter().map(|(_, value)| value).collect();
+    while let Some(object) = pending.pop() {
+        match object {
+            PdfObject::Reference(id) => {
+                if let Some(target) = objects.get(id) {
+                    if reachable.insert(*id) {
+                        pending.push(target);
+                    }
+                }
+            }
+            PdfObject::Array(items) => pending.extend(items),
+            PdfObject::Dictionary(dict) => pending.extend(dict.iter().map(|(_, value)| value)),
+            PdfObject::Stream(stream) => pending.extend(stream.dict.iter().map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+    reachable
+}
+
+/// The object that a merged object was merged into, following merges of the
+/// objects that were kept in later rounds.
+fn canonical(merged: &HashMap<ObjectId, ObjectId>, mut id: ObjectId) -> ObjectId {
+    while let Some(&kept) = merged.get(&id) {
+        id = kept;
+    }
+    id
+}
+
+/// Rewrites the references held by an object. References that map to None,
+/// such as those to objects that do not exist, become the null object.
+fn renumber(object: &PdfObject, map: &dyn Fn(ObjectId) -> Option<ObjectId>) -> PdfObject {
+    match object {
+        PdfObject::Reference(id) => map(*id).map_or(PdfObject::Null, PdfObject::Reference),
+        PdfObject::Array(items) => {
+            PdfObject::Array(items.iter().map(|item| renumber(item, map)).collect())
+        }
+        PdfObject::Dictionary(dict) => PdfObject::Dictionary(renumber_dict(dict, map)),
+        PdfObject::Stream(stream) => {
+            let mut stream = stream.clone();
+            stream.dict = renumber_dict(&stream.dict, map);
+            PdfObject::Stream(stream)
+        }
+        other => other.clone(),
+    }
+}
+
+fn renumber_dict(dict: &Dictionary, map: &dyn Fn(ObjectId) -> Option<ObjectId>) -> Dictionary {
+    dict.iter()
+        .map(|(key, value)| (key.clone(), renumber(value, map)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        builder::DocumentBuilder,
+        document::{tests::reload, Document},
+        write::WriteOptions,
+    };
+
+    /// A document of three pages whose resources and fonts are separate but
+    /// identical objects, as merging documents without sharing leaves them.
+    fn duplicated() -> Document {
+        let mut builder = DocumentBuilder::new();
+        let font = builder.add_standard_font("Helvetica").unwrap();
+        for label in ["A", "B", "C"] {
+            builder.add_page([0.0, 0.0, 612.0, 792.0], |page| {
+                page.text(font, 12.0, 72.0, 720.0, label);
+            });
+        }
+        let mut doc = builder.build();
+        for page in doc.pages() {
+            let resources = page.resources(&doc);
+            let fonts = doc.resolve_dict(&resources, "Font").unwrap();
+            let font = doc.resolve_key(fonts, "F0").unwrap().clone();
+            let font = doc.add_object(font);
+            let mut fonts = Dictionary::new();
+            fonts.set("F0", PdfObject::Reference(font));
+            let mut resources = Dictionary::new();
+            resources.set("Font", PdfObject::Dictionary(fonts));
+            let resources = doc.add_object(PdfObject::Dictionary(resources));
+            if let Some(PdfObject::Dictionary(dict)) = doc.get_mut(page.id.unwrap()) {
+                dict.set("Resources", PdfObject::Reference(resources));
+            }
+        }
+        doc
+    }
+
+    fn count_type(doc: &Document, type_name: &str) -> usize {
+        doc.pdf
+            .body
+            .objects
+            .values()
+            .filter(|object| object.as_dict().is_some_and(|d| d.has_type(type_name)))
+            .count()
+    }
+
+    #[test]
+    fn test_optimize() {
+        let mut doc = duplicated();
+        let contents: Vec<_> = doc
+            .pages()
+            .iter()
+            .map(|page| page.contents(&doc).unwrap())
+            .collect();
+        doc.add_object(PdfObject::Integer(42));
+        assert_eq!(count_type(&doc, "Font"), 4);
+
+        // The original font is no longer used; the three resource
+        // dictionaries merge once their fonts have.
+        let stats = doc.optimize();
+        assert_eq!(
+            stats,
+            OptimizeStats {
+                unreachable: 2,
+                duplicates: 4
+            }
+        );
+        assert_eq!(count_type(&doc, "Font"), 1);
+        let numbers: Vec<ObjectId> = doc.pdf.body.objects.keys().copied().collect();
+        let expected: Vec<ObjectId> = (1..=numbers.len() as i32).map(|n| (n, 0)).collect();
+        assert_eq!(numbers, expected);
+        assert_eq!(
+            doc.trailer().get_i64("Size"),
+            Some(numbers.len() as i64 + 1)
+        );
+
+        let doc = reload(&doc);
+        let pages = doc.pages();
+        assert_eq!(pages.len(), 3);
+        for (page, content) in pages.iter().zip(contents) {
+            assert_eq!(page.contents(&doc).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn test_identity() {
+        // Two layers with the same name, and the identical actions a page
+        // runs when it is opened and closed, are separate objects.
+        let mut builder = DocumentBuilder::new();
+        builder.add_page([0.0, 0.0, 612.0, 792.0], |_| {});
+        let mut doc = builder.build();
+        let mut group = Dictionary::new();
+        group.set("Type", PdfObject::Name("OCG".to_string()));
+        group.set("Name", PdfObject::text_string("Layer"));
+        let groups: Vec<PdfObject> = (0..2)
+            .map(|_| PdfObject::Reference(doc.add_object(PdfObject::Dictionary(group.clone()))))
+            .collect();
+        let mut properties = Dictionary::new();
+        properties.set("OCGs", PdfObject::Array(groups));
+        let mut action = Dictionary::new();
+        action.set("S", PdfObject::Name("URI".to_string()));
+        action.set("URI", PdfObject::String(b"https://example.com".to_vec()));
+        let actions: Dictionary = ["O", "C"]
+            .into_iter()
+            .map(|key| {
+                let action = doc.add_object(PdfObject::Dictionary(action.clone()));
+                (key.to_string(), PdfObject::Reference(action))
+            })
+            .collect();
+        let root = doc.catalog_id().unwrap();
+        if let Some(PdfObject::Dictionary(catalog)) = doc.get_mut(root) {
+            catalog.set("OCProperties", PdfObject::Dictionary(properties));
+        }
+        let page = doc.pages()[0].id.unwrap();
+        if let Some(PdfObject::Dictionary(page)) = doc.get_mut(page) {
+            page.set("AA", PdfObject::Dictionary(actions));
+        }
+
+        let stats = doc.optimize();
+        assert_eq!(stats.duplicates, 0);
+        assert_eq!(count_type(&doc, "OCG"), 2);
+        let uris = doc
+            .pdf
+            .body
+            .objects
+            .values()
+            .filter(|object| {
+                object
+                    .as_dict()
+                    .is_some_and(|d| d.get_name("S") == Some("URI"))
+            })
+            .count();
+        assert_eq!(uris, 2);
+    }
+
+    #[test]
+    fn test_write_optimized() {
+        let doc = duplicated();
+        let mut plain = vec![];
+        doc.save(&mut plain).unwrap();
+        let options = WriteOptions {
+            optimize: true,
+            ..WriteOptions::default()
+        };
+        let mut data = vec![];
+        doc.save_with(&mut data, &options).unwrap();
+        assert!(data.len() < plain.len());
+        assert_eq!(count_type(&doc, "Font"), 4);
+
+        let copy = Document::from_bytes(data).unwrap();
+        assert_eq!(count_type(&copy, "Font"), 1);
+        assert_eq!(copy.pages().len(), 3);
+    }
+}
@@ -0,0 +1,577 @@
+use std::collections::HashSet;
+
+use crate::api::{
+    document::Document,
+    objects::{Dictionary, ObjectId, PdfObject},
+};
+
+/// Outline items are read at most this deep. Items that were already read
+/// end the list they appear in, which stops cycles through /First or /Next.
+const MAX_DEPTH: usize = 32;
+
+/// The entries of an outline item that are read into the fields of
+/// `OutlineItem` or that link it into the outline, and are therefore not
+/// kept in its dictionary.
+const ITEM_KEYS: [&str; 10] = [
+    "Title", "Parent", "Prev", "Next", "First", "Last", "Count", "Dest", "C", "F",
+];
+
+///
+/// How a destination displays its page: the position of the window and the
+/// magnification. Coordinates are in default user space, and None leaves the
+/// current value unchanged. See sec. 12.3.2.2
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    Xyz {
+        left: Option<f64>,
+        top: Option<f64>,
+        zoom: Option<f64>,
+    },
+    Fit,
+    FitH {
+        top: Option<f64>,
+    },
+    FitV {
+        left: Option<f64>,
+    },
+    FitR {
+        left: f64,
+        bottom: f64,
+        right: f64,
+        top: f64,
+    },
+    FitB,
+    FitBH {
+        top: Option<f64>,
+    },
+    FitBV {
+        left: Option<f64>,
+    },
+}
+
+///
+/// "A destination defines a particular view of a document, consisting of the
+/// page of the document that shall be displayed, the location in the document
+/// window on which that page shall be displayed, and the magnification (zoom)
+/// factor." See sec. 12.3.2
+///
+/// The page is given by its index in the document.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Destination {
+    pub page: usize,
+    pub fit: Fit,
+}
+
+impl Destination {
+    pub fn new(page: usize) -> Destination {
+        Destination {
+            page,
+            fit: Fit::Fit,
+        }
+    }
+
+    /// Reads an explicit destination: an array of the page, given by a
+    /// reference to its page object or by its index, and the view.
+    pub fn from_array(array: &[PdfObject], pages: &[Option<ObjectId>]) -> Option<Destination> {
+        let (page, rest) = array.split_first()?;
+        let page = match page {
+            PdfObject::Reference(id) => pages.iter().position(|p| *p == Some(*id))?,
+            PdfObject::Integer(index) => usize::try_from(*index).ok()?,
+            _ => return None,
+        };
+        let (kind, values) = rest.split_first()?;
+        let value = |i: usize| values.get(i).and_then(PdfObject::as_f64);
+        let fit = match kind.as_name()? {
+            "XYZ" => Fit::Xyz {
+                left: value(0),
+                top: value(1),
+                // A zoom of 0 has the same meaning as null.
+                zoom: value(2).filter(|zoom| *zoom != 0.0),
+            },
+            "Fit" => Fit::Fit,
+            "FitH" => Fit::FitH { top: value(0) },
+            "FitV" => Fit::FitV { left: value(0) },
+            "FitR" => Fit::FitR {
+                left: value(0)?,
+                bottom: value(1)?,
+                right: value(2)?,
+                top: value(3)?,
+            },
+            "FitB" => Fit::FitB,
+            "FitBH" => Fit::FitBH { top: value(0) },
+            "FitBV" => Fit::FitBV { left: value(0) },
+            _ => return None,
+        };
+        Some(Destination { page, fit })
+    }
+
+    /// Writes the destination as an array starting with the page object.
+    pub fn to_array(&self, page: ObjectId) -> PdfObject {
+        let number = |value: Option<f64>| value.map_or(PdfObject::Null, PdfObject::Real);
+        let (kind, values) = match self.fit {
+            Fit::Xyz { left, top, zoom } => ("XYZ", vec![left, top, zoom]),
+            Fit::Fit => ("Fit", vec![]),
+            Fit::FitH { top } => ("FitH", vec![top]),
+            Fit::FitV { left } => ("FitV", vec![left]),
+            Fit::FitR {
+                left,
+                bottom,
+                right,
+                top,
+            } => (
+                "FitR",
+                vec![Some(left), Some(bottom), Some(right), Some(top)],
+            ),
+            Fit::FitB => ("FitB", vec![]),
+            Fit::FitBH { top } => ("FitBH", vec![top]),
+            Fit::FitBV { left } => ("FitBV", vec![left]),
+        };
+        let mut array = vec![
+            PdfObject::Reference(page),
+            PdfObject::Name(kind.to_string()),
+        ];
+        array.extend(values.into_iter().map(number));
+        PdfObject::Array(array)
+    }
+}
+
+///
+/// An item of the document outline, shown as a bookmark: its title, the
+/// destination it goes to, and its subitems. A closed item hides its
+/// subitems until it is opened. See sec. 12.3.3
+///
+/// The destination is that of the /Dest entry or of a GoTo action. The
+/// dictionary holds the other entries of the item as read, such as an /A
+/// action of another type, and is written back with them.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineItem {
+    pub title: String,
+    pub destination: Option<Destination>,
+    pub children: Vec<OutlineItem>,
+    pub open: bool,
+    /// The color of the title, as RGB components.
+    pub color: Option<[f64; 3]>,
+    /// The style of the title, `OutlineItem::ITALIC` and
+    /// `OutlineItem::BOLD`. See sec. 12.3.3, Table 154
+    pub flags: u32,
+    pub dict: Dictionary,
+}
+
+impl OutlineItem {
+    pub const ITALIC: u32 = 1 << 0;
+    pub const BOLD: u32 = 1 << 1;
+
+    pub fn new(title: &str, destination: Option<Destination>) -> OutlineItem {
+        OutlineItem {
+            title: title.to_string(),
+            destination,
+            children: vec![],
+            open: false,
+            color: None,
+            flags: 0,
+            dict: Dictionary::new(),
+        }
+    }
+
+    /// The number of descendants shown while this item is open: its
+    /// children, and the descendants of the children that are open.
+    fn visible_descendants(&self) -> i64 {
+        self.children
+            .iter()
+            .map(|child| {
+                1 + if child.open {
+                    child.visible_descendants()
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+}
+
+impl Document {
+    /// The items of the document outline, read from the /First and /Next
+    /// entries of the catalog's /Outlines dictionary.
+    pub fn outlines(&self) -> Vec<OutlineItem> {
+        let pages: Vec<Option<ObjectId>> = self.pages().iter().map(|page| page.id).collect();
+        let Some(root) = self
+            .catalog()
+            .and_then(|catalog| self.resolve_dict(catalog, "Outlines"))
+        else {
+            return vec![];
+        };
+        let mut visited = HashSet::new();
+        self.outline_items(root, &pages, &mut visited, 0)
+    }
+
+    fn outline_items(
+        &self,
+        parent: &Dictionary,
+        pages: &[Option<ObjectId>],
+        visited: &mut HashSet<ObjectId>,
+        depth: usize,
+    ) -> Vec<OutlineItem> {
+        let mut items = vec![];
+        if depth > MAX_DEPTH {
+            return items;
+        }
+        let mut next = parent.get("First");
+        while let Some(reference) = next {
+            if reference
+                .as_reference()
+                .is_some_and(|id| !visited.insert(id))
+            {
+                break;
+            }
+            let Some(dict) = self.resolve(reference).as_dict() else {
+                break;
+            };
+            let destination = match self.resolve_key(dict, "Dest") {
+                Some(dest) => self.destination(dest, pages),
+                None => self
+                    .resolve_dict(dict, "A")
+                    .filter(|action| action.get_name("S") == Some("GoTo"))
+                    .and_then(|action| self.resolve_key(action, "D"))
+                    .and_then(|dest| self.destination(dest, pages)),
+            };
+            let mut entries = dict.clone();
+            for key in ITEM_KEYS {
+                entries.remove(key);
+            }
+            items.push(OutlineItem {
+                title: self
+                    .resolve_key(dict, "Title")
+                    .and_then(PdfObject::as_text)
+                    .unwrap_or_default(),
+                destination,
+                children: self.outline_items(dict, pages, visited, depth + 1),
+                open: dict.get_i64("Count").is_some_and(|count| count > 0),
+                color: self
+                    .resolve_key(dict, "C")
+                    .and_then(PdfObject::as_numbers)
+                    .and_then(|color| color.try_into().ok()),
+                flags: self
+                    .resolve_key(dict, "F")
+                    .and_then(PdfObject::as_i64)
+                    .unwrap_or(0) as u32,
+                dict: entries,
+            });
+            next = dict.get("Next");
+        }
+        items
+    }
+
+    /// Resolves a destination: an explicit destination, or the name of one
+    /// in the catalog's /Dests dictionary (a name) or in the /Dests name tree
+    /// of its /Names dictionary (a string). A named destination may also be
+    /// a dictionary whose /D entry holds it. See sec. 12.3.2.4
+    pub fn destination(&self, dest: &PdfObject, pages: &[Option<ObjectId>]) -> Option<Destination> {
+        Destination::from_array(self.explicit_destination(dest)?, pages)
+    }
+
+    /// The array of the explicit destination a destination resolves to, as
+    /// in `destination`.
+    pub(crate) fn explicit_destination<'a>(
+        &'a self,
+        dest: &'a PdfObject,
+    ) -> Option<&'a [PdfObject]> {
+        let catalog = self.catalog()?;
+        let named = match self.resolve(dest) {
+            PdfObject::Array(array) => return Some(array),
+            dict @ PdfObject::Dictionary(_) => Some(dict),
+            PdfObject::Name(name) => self
+                .resolve_dict(catalog, "Dests")
+                .and_then(|dests| self.resolve_key(dests, name)),
+            PdfObject::String(name) => self
+                .resolve_dict(catalog, "Names")
+                .and_then(|names| self.resolve_dict(names, "Dests"))
+                .and_then(|tree| self.find_name(tree, name)),
+            _ => None,
+        }?;
+        let array = match named {
+            PdfObject::Dictionary(dict) => self.resolve_key(dict, "D")?,
+            array => array,
+        };
+        array.as_array().map(Vec::as_slice)
+    }
+
+    /// Replaces the document outline with the given items, removing the
+    /// objects of the old outline. Destinations become explicit destinations
+    /// of the page objects, in the GoTo action of the item if it has one;
+    /// those whose page index is out of range are left out. See sec. 12.3.3
+    pub fn set_outlines(&mut self, items: &[OutlineItem]) {
+        let Some(catalog_id) = self.catalog_id() else {
+            return;
+        };
+        let pages: Vec<Option<ObjectId>> = self.pages().iter().map(|page| page.id).collect();
+        for id in self.outline_objects() {
+            self.pdf.body.objects.remove(&id);
+        }
+        let root = self.add_object(PdfObject::Null);
+        let mut dict = Dictionary::new();
+        dict.set("Type", PdfObject::Name("Outlines".to_string()));
+        if let Some((first, last)) = self.add_outline_items(items, root, &pages) {
+            dict.set("First", PdfObject::Reference(first));
+            dict.set("Last", PdfObject::Reference(last));
+            let count = items
+                .iter()
+                .map(|item| {
+                    1 + if item.open {
+                        item.visible_descendants()
+                    } else {
+                        0
+                    }
+                })
+                .sum();
+            dict.set("Count", PdfObject::Integer(count));
+        }
+        self.pdf
+            .body
+            .objects
+            .insert(root, PdfObject::Dictionary(dict));
+        if let Some(PdfObject::Dictionary(catalog)) = self.get_mut(catalog_id) {
+            catalog.set("Outlines", PdfObject::Reference(root));
+        }
+    }
+
+    /// The objects of the outline: the /Outlines dictionary and the items
+    /// reached from it through /First and /Next.
+    fn outline_objects(&self) -> Vec<ObjectId> {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<&PdfObject> = self
+            .catalog()
+            .and_then(|catalog| catalog.get("Outlines"))
+            .into_iter()
+            .collect();
+        while let Some(next) = stack.pop() {
+            let Some(id) = next.as_reference().filter(|&id| visited.insert(id)) else {
+                continue;
+            };
+            if let Some(item) = self.get(id).and_then(PdfObject::as_dict) {
+                stack.extend(["First", "Next"].iter().filter_map(|key| item.get(key)));
+            }
+        }
+        visited.into_iter().collect()
+    }
+
+    /// Adds the items as siblings under a parent, returning the first and
+    /// last of them.
+    fn add_outline_items(
+        &mut self,
+        items: &[OutlineItem],
+        parent: ObjectId,
+        pages: &[Option<ObjectId>],
+    ) -> Option<(ObjectId, ObjectId)> {
+        let ids: Vec<ObjectId> = items
+            .iter()
+            .map(|_| self.add_object(PdfObject::Null))
+            .collect();
+        for (i, item) in items.iter().enumerate() {
+            let mut dict = item.dict.clone();
+            for key in ITEM_KEYS {
+                dict.remove(key);
+            }
+            dict.set("Title", PdfObject::text_string(&item.title));
+            dict.set("Parent", PdfObject::Reference(parent));
+            if i > 0 {
+                dict.set("Prev", PdfObject::Reference(ids[i - 1]));
+            }
+            if let Some(next) = ids.get(i + 1) {
+                dict.set("Next", PdfObject::Reference(*next));
+            }
+            if let Some((first, last)) = self.add_outline_items(&item.children, ids[i], pages) {
+                dict.set("First", PdfObject::Reference(first));
+                dict.set("Last", PdfObject::Reference(last));
+                // The count is negative while the item is closed.
+                let count = item.visible_descendants();
+                let count = if item.open { count } else { -count };
+                dict.set("Count", PdfObject::Integer(count));
+            }
+            if let Some(color) = item.color {
+                let color = color.iter().map(|&c| PdfObject::Real(c)).collect();
+                dict.set("C", PdfObject::Array(color));
+            }
+            if item.flags != 0 {
+                dict.set("F", PdfObject::Integer(item.flags as i64));
+            }
+            // A GoTo action is replaced by the destination, or removed with it.
+            let go_to = self
+                .resolve_dict(&dict, "A")
+                .filter(|action| action.get_name("S") == Some("GoTo"))
+                .cloned();
+            if go_to.is_some() {
+                dict.remove("A");
+            }
+            let page = item
+                .destination
+                .and_then(|dest| Some((dest, (*pages.get(dest.page)?)?)));
+            if let Some((dest, page)) = page {
+                match go_to {
+                    Some(mut action) => {
+                        action.set("D", dest.to_array(page));
+                        dict.set("A", PdfObject::Dictionary(action));
+                    }
+                    None => {
+                        // An item has either a destination or an action.
+                        dict.remove("A");
+                        dict.set("Dest", dest.to_array(page));
+                    }
+                }
+            }
+            self.pdf
+                .body
+                .objects
+                .insert(ids[i], PdfObject::Dictionary(dict));
+        }
+        Some((*ids.first()?, *ids.last()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{builder::DocumentBuilder, document::tests::reload};
+
+    fn three_pages() -> Document {
+        let mut builder = DocumentBuilder::new();
+        for _ in 0..3 {
+            builder.add_page([0.0, 0.0, 612.0, 792.0], |_| {});
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_outlines() {
+        let mut doc = three_pages();
+        assert!(doc.outlines().is_empty());
+
+        let xyz = Destination {
+            page: 2,
+            fit: Fit::Xyz {
+                left: Some(72.0),
+                top: Some(700.0),
+                zoom: None,
+            },
+        };
+        let mut chapter = OutlineItem::new("Chapter 1 – Überblick", Some(Destination::new(0)));
+        chapter.open = true;
+        chapter.children = vec![
+            OutlineItem::new("Section 1.1", Some(Destination::new(1))),
+            OutlineItem::new("Section 1.2", Some(xyz)),
+        ];
+        let mut appendix = OutlineItem::new("Appendix", None);
+        appendix.children = vec![OutlineItem::new("Tables", Some(Destination::new(2)))];
+        let items = vec![chapter, appendix];
+        doc.set_outlines(&items);
+
+        let doc = reload(&doc);
+        assert_eq!(doc.outlines(), items);
+        let catalog = doc.catalog().unwrap();
+        let root = doc.resolve_dict(catalog, "Outlines").unwrap();
+        assert_eq!(root.get_i64("Count"), Some(4));
+        let appendix = doc.resolve_dict(root, "Last").unwrap();
+        assert_eq!(appendix.get_i64("Count"), Some(-1));
+        let chapter = doc.resolve_dict(appendix, "Prev").unwrap();
+        assert_eq!(chapter.get_i64("Count"), Some(2));
+    }
+
+    #[test]
+    fn test_outline_actions() {
+        let mut doc = three_pages();
+        let action = |kind: &str, key: &str, value: PdfObject| {
+            let mut action = Dictionary::new();
+            action.set("S", PdfObject::Name(kind.to_string()));
+            action.set(key, value);
+            action
+        };
+        let mut uri = OutlineItem::new("Website", None);
+        uri.dict.set(
+            "A",
+            PdfObject::Dictionary(action(
+                "URI",
+                "URI",
+                PdfObject::String(b"https://example.com/".to_vec()),
+            )),
+        );
+        uri.color = Some([1.0, 0.0, 0.0]);
+        uri.flags = OutlineItem::ITALIC | OutlineItem::BOLD;
+        let mut named = OutlineItem::new("Print", None);
+        let print = action("Named", "N", PdfObject::Name("Print".to_string()));
+        let print = doc.add_object(PdfObject::Dictionary(print));
+        named.dict.set("A", PdfObject::Reference(print));
+        let mut go_to = OutlineItem::new("Last page", Some(Destination::new(2)));
+        let last = doc.pages()[2].id.unwrap();
+        let mut next = action("GoTo", "D", Destination::new(2).to_array(last));
+        next.set("Next", PdfObject::Reference(print));
+        go_to.dict.set("A", PdfObject::Dictionary(next));
+        let items = vec![uri, named, go_to];
+        doc.set_outlines(&items);
+
+        let mut doc = reload(&doc);
+        let read = doc.outlines();
+        assert_eq!(read, items);
+        let catalog = doc.catalog().unwrap();
+        let root = doc.resolve_dict(catalog, "Outlines").unwrap();
+        let last = doc.resolve_dict(root, "Last").unwrap();
+        assert!(!last.contains_key("Dest"));
+
+        // Setting the outline again replaces the objects of the old one,
+        // and a destination that is removed takes its GoTo action with it.
+        let count = doc.pdf.body.objects.len();
+        let mut items = read;
+        items[2].destination = None;
+        doc.set_outlines(&items);
+        assert_eq!(doc.pdf.body.objects.len(), count);
+        let read = doc.outlines();
+        assert!(read[2].destination.is_none() && !read[2].dict.contains_key("A"));
+        assert_eq!(read[..2], items[..2]);
+        assert!(doc.get(print).is_some());
+    }
+
+    #[test]
+    fn test_named_destinations() {
+        let mut doc = three_pages();
+        let pages: Vec<Option<ObjectId>> = doc.pages().iter().map(|page| page.id).collect();
+        let page = |index: usize| PdfObject::Reference(pages[index].unwrap());
+        let fit = || PdfObject::Name("Fit".to_string());
+        let string = |s: &str| PdfObject::String(s.as_bytes().to_vec());
+
+        let mut dests = Dictionary::new();
+        dests.set("intro", PdfObject::Array(vec![page(1), fit()]));
+        let mut leaf = Dictionary::new();
+        leaf.set("Limits", PdfObject::Array(vec![string("a"), string("m")]));
+        let mut entry = Dictionary::new();
+        entry.set("D", PdfObject::Array(vec![page(2), fit()]));
+        leaf.set(
+            "Names",
+            PdfObject::Array(vec![string("end"), PdfObject::Dictionary(entry)]),
+        );
+        let leaf = doc.add_object(PdfObject::Dictionary(leaf));
+        let mut tree = Dictionary::new();
+        tree.set("Kids", PdfObject::Array(vec![PdfObject::Reference(leaf)]));
+        let mut names = Dictionary::new();
+        names.set("Dests", PdfObject::Dictionary(tree));
+
+        let root = doc.catalog_id().unwrap();
+        if let Some(PdfObject::Dictionary(catalog)) = doc.get_mut(root) {
+            catalog.set("Dests", PdfObject::Dictionary(dests));
+            catalog.set("Names", PdfObject::Dictionary(names));
+        }
+
+        let by_name = PdfObject::Name("intro".to_string());
+        assert_eq!(doc.destination(&by_name, &pages), Some(Destination::new(1)));
+        assert_eq!(
+            doc.destination(&string("end"), &pages),
+            Some(Destination::new(2))
+        );
+        assert_eq!(doc.destination(&string("zebra"), &pages), None);
+        let explicit = PdfObject::Array(vec![PdfObject::Integer(0), fit()]);
+        assert_eq!(
+            doc.destination(&explicit, &pages),
+            Some(Destination::new(0))
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::io;
+
+use crate::api::{
+    document::Document,
+    objects::{Dictionary, ObjectId, PdfObject},
+};
+
+/// US Letter, used when a page has no usable /MediaBox.
+const DEFAULT_MEDIA_BOX: [f64; 4] = [0.0, 0.0, 612.0, 792.0];
+
+///
+/// "The leaves of the page tree are page objects, each of which is a dictionary
+/// specifying the attributes of a single page of the document."
+/// See sec. 7.7.3.3 (pg. 103)
+///
+/// The dictionary held by a Page already includes any attributes inherited from
+/// its ancestors in the page tree.
+///
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub id: Option<ObjectId>,
+    pub dict: Dictionary,
+}
+
+impl Page {
+    /// Page attributes that may be inherited from the page tree.
+    /// See sec. 7.7.3.4 (pg. 106)
+    pub const INHERITABLE: [&'static str; 4] = ["Resources", "MediaBox", "CropBox", "Rotate"];
+
+    pub fn new(id: Option<ObjectId>, dict: Dictionary) -> Page {
+        Page { id, dict }
+    }
+
+    /// The boundaries of the physical medium on which the page is displayed or
+    /// printed, as [llx lly urx ury].
+    pub fn media_box(&self) -> [f64; 4] {
+        self.rectangle("MediaBox").unwrap_or(DEFAULT_MEDIA_BOX)
+    }
+
+    /// The visible region of the page, which defaults to the media box.
+    pub fn crop_box(&self) -> [f64; 4] {
+        self.rectangle("CropBox")
+            .unwrap_or_else(|| self.media_box())
+    }
+
+    /// The number of degrees by which the page is rotated clockwise when displayed.
+    /// Always a multiple of 90.
+    pub fn rotation(&self) -> i64 {
+        self.dict.get_i64("Rotate").unwrap_or(0).rem_euclid(360) / 90 * 90
+    }
+
+    fn rectangle(&self, key: &str) -> Option<[f64; 4]> {
+        let numbers = self.dict.get(key)?.as_numbers()?;
+        let [x1, y1, x2, y2] = <[f64; 4]>::try_from(numbers).ok()?;
+        Some([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)])
+    }
+
+    /// The resource dictionary of the page, naming the fonts, images and other
+    /// resources used by its content stream. See sec. 7.8.3 (pg. 113)
+    pub fn resources(&self, doc: &Document) -> Dictionary {
+        doc.resolve_dict(&self.dict, "Resources")
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The decoded content stream of the page. /Contents may be a single stream or
+    /// an array of streams, which are concatenated as if they were one.
+    /// See sec. 7.7.3.3, Table 31 (pg. 104)
+    pub fn contents(&self, doc: &Document) -> Result<Vec<u8>, io::Error> {
+        let streams = match self.dict.get("Contents").map(|c| doc.resolve(c)) {
+            Some(PdfObject::Array(items)) => items.iter().map(|i| doc.resolve(i)).collect(),
+            Some(object) => vec![object],
+            None => vec![],
+        };
+
+        let mut content = vec![];
+        for stream in streams.into_iter().filter_map(PdfObject::as_stream) {
+            content.extend(stream.decode()?);
+            content.push(b'\n');
+        }
+        Ok(content)
+    }
+}
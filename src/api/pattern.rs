@@ -0,0 +1,681 @@
+use std::rc::Rc;
+
+use crate::api::{
+    color::ColorSpace,
+    document::Document,
+    function::{eval_functions, interpolate, load_functions, Function},
+    geometry::{Matrix, Point},
+    objects::{Dictionary, PdfObject},
+};
+
+///
+/// A pattern, used as a color with the Pattern color space. Tiling patterns
+/// repeat a small piece of content; shading patterns paint a smooth gradient.
+/// See sec. 8.7 (pg. 208)
+///
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Tiling(TilingPattern),
+    Shading(ShadingPattern),
+}
+
+///
+/// A pattern cell painted at fixed intervals. See sec. 8.7.3, Table 75
+/// (pg. 212)
+///
+/// Colored patterns (PaintType 1) specify their colors in their content;
+/// uncolored patterns (PaintType 2) are stencils painted with the color given
+/// alongside the pattern.
+///
+#[derive(Debug, Clone)]
+pub struct TilingPattern {
+    pub colored: bool,
+    pub tiling_type: i64,
+    pub bbox: [f64; 4],
+    pub x_step: f64,
+    pub y_step: f64,
+    /// Maps pattern space to the default coordinate space of the page, or of
+    /// the form XObject using the pattern.
+    pub matrix: Matrix,
+    pub resources: Dictionary,
+    /// The decoded content stream of the pattern cell.
+    pub content: Vec<u8>,
+}
+
+///
+/// A shading used as a pattern, filling the painted area with a gradient.
+/// See sec. 8.7.4.2, Table 76 (pg. 217)
+///
+#[derive(Debug, Clone)]
+pub struct ShadingPattern {
+    pub shading: Rc<Shading>,
+    pub matrix: Matrix,
+    pub ext_g_state: Option<Dictionary>,
+}
+
+impl Pattern {
+    pub fn load(doc: &Document, object: &PdfObject) -> Option<Pattern> {
+        let (dict, stream) = match doc.resolve(object) {
+            PdfObject::Dictionary(dict) => (dict, None),
+            PdfObject::Stream(stream) => (&stream.dict, Some(stream)),
+            _ => return None,
+        };
+        let matrix = doc
+            .resolve_key(dict, "Matrix")
+            .and_then(PdfObject::as_numbers)
+            .and_then(|m| Matrix::from_slice(&m))
+            .unwrap_or_default();
+        match dict.get_i64("PatternType")? {
+            1 => {
+                let bbox = doc
+                    .resolve_key(dict, "BBox")
+                    .and_then(PdfObject::as_numbers)
+                    .and_then(|b| <[f64; 4]>::try_from(b).ok())?;
+                Some(Pattern::Tiling(TilingPattern {
+                    colored: dict.get_i64("PaintType") != Some(2),
+                    tiling_type: dict.get_i64("TilingType").unwrap_or(1),
+                    bbox,
+                    x_step: doc.resolve_key(dict, "XStep")?.as_f64()?,
+                    y_step: doc.resolve_key(dict, "YStep")?.as_f64()?,
+                    matrix,
+                    resources: doc
+                        .resolve_dict(dict, "Resources")
+                        .cloned()
+                        .unwrap_or_default(),
+                    content: doc.decode_stream(stream?).ok()?,
+                }))
+            }
+            2 => Some(Pattern::Shading(ShadingPattern {
+                shading: Rc::new(Shading::load(doc, dict.get("Shading")?)?),
+                matrix,
+                ext_g_state: doc.resolve_dict(dict, "ExtGState").cloned(),
+            })),
+            _ => None,
+        }
+    }
+}
+
+///
+/// A smooth color gradient, painted with the "sh" operator or as a shading
+/// pattern. See sec. 8.7.4.3 (pg. 218)
+///
+#[derive(Debug, Clone)]
+pub struct Shading {
+    pub color_space: ColorSpace,
+    /// The color of the area outside the shading's geometry, for shading
+    /// patterns only.
+    pub background: Option<Vec<f64>>,
+    /// The extent of the shading, in shading space.
+    pub bbox: Option<[f64; 4]>,
+    pub anti_alias: bool,
+    /// Maps parametric values to colors; without it, the colors of mesh
+    /// vertices are given directly in the color space.
+    pub functions: Option<Vec<Function>>,
+    pub kind: ShadingKind,
+}
+
+///
+/// The geometry of each shading type. Free-form, lattice-form and patch mesh
+/// shadings (types 4 to 7) are read into triangles.
+/// See sec. 8.7.4.5, Table 77 (pg. 219)
+///
+#[derive(Debug, Clone)]
+pub enum ShadingKind {
+    /// Type 1: colors given by a function of x and y over a rectangle.
+    Function { domain: [f64; 4], matrix: Matrix },
+    /// Type 2: colors varying along the line between two points.
+    Axial {
+        coords: [f64; 4],
+        domain: [f64; 2],
+        extend: [bool; 2],
+    },
+    /// Type 3: colors varying between two circles, given as x, y and radius.
+    Radial {
+        coords: [f64; 6],
+        domain: [f64; 2],
+        extend: [bool; 2],
+    },
+    /// Types 4 to 7: triangles whose vertex colors are interpolated.
+    Mesh(Vec<[Vertex; 3]>),
+}
+
+///
+/// A point of a mesh with its color components, or with a single parametric
+/// value when the shading has a function.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vertex {
+    pub point: Point,
+    pub color: Vec<f64>,
+}
+
+impl Shading {
+    pub fn load(doc: &Document, object: &PdfObject) -> Option<Shading> {
+        let (dict, stream) = match doc.resolve(object) {
+            PdfObject::Dictionary(dict) => (dict, None),
+            PdfObject::Stream(stream) => (&stream.dict, Some(stream)),
+            _ => return None,
+        };
+        let numbers = |key: &str| doc.resolve_key(dict, key).and_then(PdfObject::as_numbers);
+        let color_space = ColorSpace::from_object(doc, dict.get("ColorSpace")?, None)?;
+        let functions = dict
+            .get("Function")
+            .and_then(|function| load_functions(doc, function));
+        let domain = numbers("Domain").unwrap_or(vec![0.0, 1.0]);
+        let extend = doc
+            .resolve_key(dict, "Extend")
+            .and_then(PdfObject::as_array)
+            .map(|e| [0, 1].map(|i| e.get(i).and_then(PdfObject::as_bool) == Some(true)))
+            .unwrap_or_default();
+
+        let shading_type = dict.get_i64("ShadingType")?;
+        let kind = match shading_type {
+            1 => ShadingKind::Function {
+                domain: numbers("Domain")
+                    .and_then(|d| d.try_into().ok())
+                    .unwrap_or([0.0, 1.0, 0.0, 1.0]),
+                matrix: numbers("Matrix")
+                    .and_then(|m| Matrix::from_slice(&m))
+                    .unwrap_or_default(),
+            },
+            2 => ShadingKind::Axial {
+                coords: numbers("Coords")?.try_into().ok()?,
+                domain: domain.get(..2)?.try_into().ok()?,
+                extend,
+            },
+            3 => ShadingKind::Radial {
+                coords: numbers("Coords")?.try_into().ok()?,
+                domain: domain.get(..2)?.try_into().ok()?,
+                extend,
+            },
+            4..=7 => {
+                let data = doc.decode_stream(stream?).ok()?;
+                let components = match functions {
+                    Some(_) => 1,
+                    None => color_space.components(),
+                };
+                let mesh = MeshFormat {
+                    bits_per_coordinate: dict.get_i64("BitsPerCoordinate")? as u32,
+                    bits_per_component: dict.get_i64("BitsPerComponent")? as u32,
+                    bits_per_flag: dict.get_i64("BitsPerFlag").unwrap_or(8) as u32,
+                    decode: numbers("Decode").filter(|d| d.len() >= 4 + 2 * components)?,
+                    components,
+                };
+                let triangles = match shading_type {
+                    4 => mesh.free_form_triangles(&data),
+                    5 => {
+                        let per_row = dict.get_i64("VerticesPerRow")?.max(2) as usize;
+                        mesh.lattice_triangles(&data, per_row)
+                    }
+                    _ => mesh.patch_triangles(&data, shading_type == 7),
+                };
+                ShadingKind::Mesh(triangles)
+            }
+            _ => return None,
+        };
+        // Shadings of types 1 to 3 need a function.
+        if functions.is_none() && !matches!(kind, ShadingKind::Mesh(_)) {
+            return None;
+        }
+        Some(Shading {
+            color_space,
+            background: numbers("Background"),
+            bbox: numbers("BBox").and_then(|b| b.try_into().ok()),
+            anti_alias: doc
+                .resolve_key(dict, "AntiAlias")
+                .and_then(PdfObject::as_bool)
+                == Some(true),
+            functions,
+            kind,
+        })
+    }
+
+    /// The color components at a point of shading space, for types 1 to 3, or
+    /// None where the shading paints nothing.
+    pub fn color_at(&self, point: Point) -> Option<Vec<f64>> {
+        let functions = self.functions.as_deref()?;
+        match &self.kind {
+            ShadingKind::Function { domain, matrix } => {
+                let point = matrix.inverse()?.apply(point);
+                let inside = (domain[0]..=domain[1]).contains(&point.x)
+                    && (domain[2]..=domain[3]).contains(&point.y);
+                inside.then(|| eval_functions(functions, &[point.x, point.y]))
+            }
+            _ => {
+                let t = self.parameter_at(point)?;
+                Some(eval_functions(functions, &[t]))
+            }
+        }
+    }
+
+    /// The parametric value t at a point of an axial or radial shading.
+    /// See sec. 8.7.4.5.3 (pg. 222) and 8.7.4.5.4 (pg. 223)
+    pub fn parameter_at(&self, point: Point) -> Option<f64> {
+        let (s, domain) = match &self.kind {
+            ShadingKind::Axial {
+                coords: [x0, y0, x1, y1],
+                domain,
+                extend,
+            } => {
+                let (dx, dy) = (x1 - x0, y1 - y0);
+                let length = dx * dx + dy * dy;
+                let s = if length == 0.0 {
+                    0.0
+                } else {
+                    ((point.x - x0) * dx + (point.y - y0) * dy) / length
+                };
+                let s = if s < 0.0 {
+                    extend[0].then_some(0.0)?
+                } else if s > 1.0 {
+                    extend[1].then_some(1.0)?
+                } else {
+                    s
+                };
+                (s, domain)
+            }
+            ShadingKind::Radial {
+                coords,
+                domain,
+                extend,
+            } => (radial_parameter(coords, *extend, point)?, domain),
+            _ => return None,
+        };
+        Some(domain[0] + s * (domain[1] - domain[0]))
+    }
+}
+
+/// Finds the largest s for which the point lies on the circle interpolated
+/// between the two circles of a radial shading, with a radius that is not
+/// negative. Values beyond 0 and 1 are used only where /Extend allows.
+fn radial_parameter(coords: &[f64; 6], extend: [bool; 2], point: Point) -> Option<f64> {
+    let [x0, y0, r0, x1, y1, r1] = *coords;
+    let (cx, cy, dr) = (x1 - x0, y1 - y0, r1 - r0);
+    let (px, py) = (point.x - x0, point.y - y0);
+    // |p - s·c|² = (r0 + s·dr)², written as a·s² - 2·b·s + c = 0.
+    let a = cx * cx + cy * cy - dr * dr;
+    let b = px * cx + py * cy + r0 * dr;
+    let c = px * px + py * py - r0 * r0;
+    let candidates = if a.abs() < 1e-12 {
+        if b == 0.0 {
+            return None;
+        }
+        [c / (2.0 * b), f64::NAN]
+    } else {
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (s1, s2) = ((b + root) / a, (b - root) / a);
+        [s1.max(s2), s1.min(s2)]
+    };
+    candidates.into_iter().find_map(|s| {
+        if s.is_nan() || r0 + s * dr < 0.0 {
+            None
+        } else if (0.0..=1.0).contains(&s) {
+            Some(s)
+        } else if s > 1.0 {
+            extend[1].then_some(s.min(1.0))
+        } else {
+            extend[0].then_some(0.0)
+        }
+    })
+}
+
+/// Reads values of any bit width, most significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> Option<u64> {
+        if bits == 0 || bits > 32 || self.position + bits as usize > self.data.len() * 8 {
+            return None;
+        }
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let byte = self.data[self.position / 8];
+            value = value << 1 | ((byte >> (7 - self.position % 8)) & 1) as u64;
+            self.position += 1;
+        }
+        Some(value)
+    }
+
+    fn align(&mut self) {
+        self.position = self.position.div_ceil(8) * 8;
+    }
+}
+
+/// How the vertices of a mesh shading are packed in its stream.
+/// See sec. 8.7.4.5.5, Table 83 (pg. 226)
+struct MeshFormat {
+    bits_per_coordinate: u32,
+    bits_per_component: u32,
+    bits_per_flag: u32,
+    decode: Vec<f64>,
+    components: usize,
+}
+
+/// The number of triangles each side of a patch is divided into.
+const PATCH_DIVISIONS: usize = 16;
+
+impl MeshFormat {
+    fn decode_value(&self, raw: u64, bits: u32, index: usize) -> f64 {
+        let max = ((1u64 << bits) - 1) as f64;
+        interpolate(
+            raw as f64,
+            0.0,
+            max,
+            self.decode[2 * index],
+            self.decode[2 * index + 1],
+        )
+    }
+
+    fn point(&self, reader: &mut BitReader) -> Option<Point> {
+        let bits = self.bits_per_coordinate;
+        let x = self.decode_value(reader.read(bits)?, bits, 0);
+        let y = self.decode_value(reader.read(bits)?, bits, 1);
+        Some(Point::new(x, y))
+    }
+
+    fn color(&self, reader: &mut BitReader) -> Option<Vec<f64>> {
+        let bits = self.bits_per_component;
+        (0..self.components)
+            .map(|i| Some(self.decode_value(reader.read(bits)?, bits, 2 + i)))
+            .collect()
+    }
+
+    fn vertex(&self, reader: &mut BitReader) -> Option<Vertex> {
+        Some(Vertex {
+            point: self.point(reader)?,
+            color: self.color(reader)?,
+        })
+    }
+
+    /// Type 4: each vertex has a flag saying whether it starts a new triangle
+    /// or forms one with two vertices of the previous triangle.
+    /// See sec. 8.7.4.5.5 (pg. 224)
+    fn free_form_triangles(&self, data: &[u8]) -> Vec<[Vertex; 3]> {
+        let mut reader = BitReader { data, position: 0 };
+        let mut triangles: Vec<[Vertex; 3]> = vec![];
+        while let Some(flag) = reader.read(self.bits_per_flag) {
+            let Some(vertex) = self.vertex(&mut reader) else {
+                break;
+            };
+            reader.align();
+            let previous = triangles.last().cloned();
+            let triangle = match (flag, previous) {
+                (1, Some([_, b, c])) => [b, c, vertex],
+                (2, Some([a, _, c])) => [a, c, vertex],
+                _ => {
+                    let mut next = || {
+                        reader.read(self.bits_per_flag)?;
+                        let vertex = self.vertex(&mut reader);
+                        reader.align();
+                        vertex
+                    };
+                    let (Some(b), Some(c)) = (next(), next()) else {
+                        break;
+                    };
+                    [vertex, b, c]
+                }
+            };
+            triangles.push(triangle);
+        }
+        triangles
+    }
+
+    /// Type 5: rows of vertices, each pair of adjacent rows forming a strip of
+    /// triangles. See sec. 8.7.4.5.6 (pg. 227)
+    fn lattice_triangles(&self, data: &[u8], per_row: usize) -> Vec<[Vertex; 3]> {
+        let mut reader = BitReader { data, position: 0 };
+        let mut vertices = vec![];
+        while let Some(vertex) = self.vertex(&mut reader) {
+            vertices.push(vertex);
+        }
+        let rows: Vec<&[Vertex]> = vertices.chunks_exact(per_row).collect();
+        let mut triangles = vec![];
+        for pair in rows.windows(2) {
+            let (top, bottom) = (pair[0], pair[1]);
+            for i in 0..per_row - 1 {
+                triangles.push([top[i].clone(), top[i + 1].clone(), bottom[i].clone()]);
+                triangles.push([top[i + 1].clone(), bottom[i + 1].clone(), bottom[i].clone()]);
+            }
+        }
+        triangles
+    }
+
+    /// Types 6 and 7: Coons and tensor-product patches of bicubic Bézier
+    /// surfaces, each divided into triangles. Patches after the first may share
+    /// an edge with the previous one. See sec. 8.7.4.5.7 (pg. 228)
+    fn patch_triangles(&self, data: &[u8], tensor: bool) -> Vec<[Vertex; 3]> {
+        let mut reader = BitReader { data, position: 0 };
+        let mut triangles = vec![];
+        // The 12 boundary points and 4 corner colors of the previous patch.
+        let mut previous: Option<(Vec<Point>, Vec<Vec<f64>>)> = None;
+        let count = if tensor { 16 } else { 12 };
+        while let Some(flag) = reader.read(self.bits_per_flag) {
+            let (mut points, mut colors) = match (flag, &previous) {
+                (1..=3, Some((points, colors))) => {
+                    let f = flag as usize;
+                    let shared: Vec<Point> = (0..4).map(|i| points[(3 * f + i) % 12]).collect();
+                    let shared_colors = vec![colors[f].clone(), colors[(f + 1) % 4].clone()];
+                    (shared, shared_colors)
+                }
+                _ => (vec![], vec![]),
+            };
+            let new_points = count - points.len();
+            let new_colors = 4 - colors.len();
+            let points_read: Option<Vec<Point>> =
+                (0..new_points).map(|_| self.point(&mut reader)).collect();
+            let colors_read: Option<Vec<Vec<f64>>> =
+                (0..new_colors).map(|_| self.color(&mut reader)).collect();
+            let (Some(points_read), Some(colors_read)) = (points_read, colors_read) else {
+                break;
+            };
+            reader.align();
+            points.extend(points_read);
+            colors.extend(colors_read);
+
+            let grid = patch_grid(&points, tensor);
+            triangles.extend(patch_to_triangles(&grid, &colors));
+            points.truncate(12);
+            previous = Some((points, colors));
+        }
+        triangles
+    }
+}
+
+/// Arranges the control points of a patch as p[i][j], where i runs along u and
+/// j along v. Coons patches get the inner points of the equivalent tensor
+/// patch. See sec. 8.7.4.5.8 (pg. 234)
+fn patch_grid(points: &[Point], tensor: bool) -> [[Point; 4]; 4] {
+    const ORDER: [(usize, usize); 16] = [
+        (0, 0),
+        (0, 1),
+        (0, 2),
+        (0, 3),
+        (1, 3),
+        (2, 3),
+        (3, 3),
+        (3, 2),
+        (3, 1),
+        (3, 0),
+        (2, 0),
+        (1, 0),
+        (1, 1),
+        (1, 2),
+        (2, 2),
+        (2, 1),
+    ];
+    let mut p = [[Point::new(0.0, 0.0); 4]; 4];
+    for (point, &(i, j)) in points.iter().zip(&ORDER) {
+        p[i][j] = *point;
+    }
+    if !tensor {
+        // Each inner point is a weighted sum of the boundary points.
+        let weighted = |terms: [(f64, usize, usize); 8]| {
+            let (x, y) = terms.iter().fold((0.0, 0.0), |(x, y), &(w, i, j)| {
+                (x + w * p[i][j].x, y + w * p[i][j].y)
+            });
+            Point::new(x / 9.0, y / 9.0)
+        };
+        let p11 = weighted([
+            (-4.0, 0, 0),
+            (6.0, 0, 1),
+            (6.0, 1, 0),
+            (-2.0, 0, 3),
+            (-2.0, 3, 0),
+            (3.0, 3, 1),
+            (3.0, 1, 3),
+            (-1.0, 3, 3),
+        ]);
+        let p12 = weighted([
+            (-4.0, 0, 3),
+            (6.0, 0, 2),
+            (6.0, 1, 3),
+            (-2.0, 0, 0),
+            (-2.0, 3, 3),
+            (3.0, 3, 2),
+            (3.0, 1, 0),
+            (-1.0, 3, 0),
+        ]);
+        let p21 = weighted([
+            (-4.0, 3, 0),
+            (6.0, 3, 1),
+            (6.0, 2, 0),
+            (-2.0, 3, 3),
+            (-2.0, 0, 0),
+            (3.0, 0, 1),
+            (3.0, 2, 3),
+            (-1.0, 0, 3),
+        ]);
+        let p22 = weighted([
+            (-4.0, 3, 3),
+            (6.0, 3, 2),
+            (6.0, 2, 3),
+            (-2.0, 3, 0),
+            (-2.0, 0, 3),
+            (3.0, 0, 2),
+            (3.0, 2, 0),
+            (-1.0, 0, 0),
+        ]);
+        (p[1][1], p[1][2], p[2][1], p[2][2]) = (p11, p12, p21, p22);
+    }
+    p
+}
+
+fn bernstein(t: f64) -> [f64; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+/// Evaluates the patch surface on a grid and splits each grid cell into two
+/// triangles. Colors are interpolated bilinearly between the corners.
+fn patch_to_triangles(p: &[[Point; 4]; 4], colors: &[Vec<f64>]) -> Vec<[Vertex; 3]> {
+    let n = PATCH_DIVISIONS;
+    let vertex = |u: f64, v: f64| {
+        let (bu, bv) = (bernstein(u), bernstein(v));
+        let mut point = Point::new(0.0, 0.0);
+        for i in 0..4 {
+            for j in 0..4 {
+                point.x += p[i][j].x * bu[i] * bv[j];
+                point.y += p[i][j].y * bu[i] * bv[j];
+            }
+        }
+        // The corner colors belong to p00, p03, p33 and p30 in turn.
+        let color = (0..colors[0].len())
+            .map(|k| {
+                colors[0][k] * (1.0 - u) * (1.0 - v)
+                    + colors[1][k] * (1.0 - u) * v
+                    + colors[2][k] * u * v
+                    + colors[3][k] * u * (1.0 - v)
+            })
+            .collect();
+        Vertex { point, color }
+    };
+    let grid: Vec<Vec<Vertex>> = (0..=n)
+        .map(|i| {
+            (0..=n)
+                .map(|j| vertex(i as f64 / n as f64, j as f64 / n as f64))
+                .collect()
+        })
+        .collect();
+    let mut triangles = Vec::with_capacity(2 * n * n);
+    for i in 0..n {
+        for j in 0..n {
+            let (a, b) = (&grid[i][j], &grid[i + 1][j]);
+            let (c, d) = (&grid[i][j + 1], &grid[i + 1][j + 1]);
+            triangles.push([a.clone(), b.clone(), c.clone()]);
+            triangles.push([b.clone(), d.clone(), c.clone()]);
+        }
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radial_parameter() {
+        // Concentric circles of radius 0 and 10.
+        let coords = [0.0, 0.0, 0.0, 0.0, 0.0, 10.0];
+        let s = radial_parameter(&coords, [false, false], Point::new(5.0, 0.0));
+        assert_eq!(s, Some(0.5));
+        assert_eq!(
+            radial_parameter(&coords, [false, false], Point::new(20.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            radial_parameter(&coords, [false, true], Point::new(20.0, 0.0)),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn test_free_form_mesh() {
+        // Two triangles with 8-bit flags, coordinates and gray components; the
+        // second shares the last two vertices of the first.
+        let mesh = MeshFormat {
+            bits_per_coordinate: 8,
+            bits_per_component: 8,
+            bits_per_flag: 8,
+            decode: vec![0.0, 255.0, 0.0, 255.0, 0.0, 1.0],
+            components: 1,
+        };
+        let data = [0, 0, 0, 0, 0, 10, 0, 255, 0, 0, 10, 255, 1, 10, 10, 0];
+        let triangles = mesh.free_form_triangles(&data);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(triangles[1][0].point, Point::new(10.0, 0.0));
+        assert_eq!(triangles[1][2].point, Point::new(10.0, 10.0));
+        assert_eq!(triangles[0][1].color, vec![1.0]);
+    }
+
+    #[test]
+    fn test_coons_patch_grid() {
+        // A square patch whose edges are straight lines: the inner points
+        // divide it evenly.
+        let boundary: Vec<Point> = [
+            (0.0, 0.0),
+            (0.0, 1.0),
+            (0.0, 2.0),
+            (0.0, 3.0),
+            (1.0, 3.0),
+            (2.0, 3.0),
+            (3.0, 3.0),
+            (3.0, 2.0),
+            (3.0, 1.0),
+            (3.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+        ]
+        .iter()
+        .map(|&(x, y)| Point::new(x, y))
+        .collect();
+        let grid = patch_grid(&boundary, false);
+        assert_eq!(grid[1][1], Point::new(1.0, 1.0));
+        assert_eq!(grid[2][1], Point::new(2.0, 1.0));
+    }
+}
@@ -0,0 +1,243 @@
+use std::io;
+
+use crate::api::{
+    color::{icc::IccProfile, ColorSpace},
+    content::{
+        interpreter::{
+            transparency_group, ContentVisitor, Interpreter, PathPaint, TextGlyph, XObject,
+        },
+        state::GraphicsState,
+    },
+    document::Document,
+    geometry::{Matrix, Path},
+    objects::PdfObject,
+    pattern::Pattern,
+};
+
+/// Tiling patterns may paint with other tiling patterns; deeper nesting than
+/// this is not checked.
+const MAX_PATTERN_DEPTH: usize = 4;
+
+///
+/// How content uses a color space.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorUse {
+    Fill,
+    Stroke,
+    Text,
+    Image,
+    /// A shading painted with "sh" or as a shading pattern.
+    Shading,
+    /// The blending color space of a transparency group.
+    Group,
+}
+
+///
+/// A place where a page uses an RGB color space. Each kind of use is reported
+/// once per page, and once per image or form XObject by name.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbUse {
+    /// The index of the page, starting at 0.
+    pub page: usize,
+    pub kind: ColorUse,
+    /// The resource name of the image or transparency group.
+    pub name: Option<String>,
+}
+
+/// Whether the document declares a CMYK output intent, i.e. that it is meant
+/// for printing on a CMYK device. See sec. 14.11.5 (pg. 702)
+pub fn has_cmyk_output_intent(doc: &Document) -> bool {
+    let Some(intents) = doc
+        .catalog()
+        .and_then(|catalog| doc.resolve_key(catalog, "OutputIntents"))
+        .and_then(PdfObject::as_array)
+    else {
+        return false;
+    };
+    intents.iter().any(|intent| {
+        doc.resolve(intent)
+            .as_dict()
+            .and_then(|intent| doc.resolve_key(intent, "DestOutputProfile"))
+            .and_then(PdfObject::as_stream)
+            .and_then(|profile| doc.decode_stream(profile).ok())
+            .and_then(|data| IccProfile::parse(&data))
+            .is_some_and(|profile| &profile.color_space == b"CMYK")
+    })
+}
+
+/// Finds the content of each page that is painted in an RGB color space:
+/// fills, strokes and text in RGB colors, RGB images and shadings, and
+/// transparency groups that blend in RGB. Print-ready CMYK files should not
+/// contain any. Patterns are checked along with the content that uses them.
+pub fn find_rgb(doc: &Document) -> Result<Vec<RgbUse>, io::Error> {
+    let mut finder = RgbFinder {
+        doc,
+        page: 0,
+        uses: vec![],
+        depth: 0,
+    };
+    for (index, page) in doc.pages().iter().enumerate() {
+        finder.page = index;
+        let content = page.contents(doc)?;
+        let mut interpreter = Interpreter::for_page(doc, page, Matrix::identity());
+        interpreter.run(&content, &mut finder);
+    }
+    Ok(finder.uses)
+}
+
+struct RgbFinder<'a> {
+    doc: &'a Document,
+    page: usize,
+    uses: Vec<RgbUse>,
+    /// How many tiling pattern cells are being checked.
+    depth: usize,
+}
+
+impl RgbFinder<'_> {
+    fn report(&mut self, kind: ColorUse, name: Option<&str>) {
+        let found = RgbUse {
+            page: self.page,
+            kind,
+            name: name.map(String::from),
+        };
+        if !self.uses.contains(&found) {
+            self.uses.push(found);
+        }
+    }
+
+    /// Checks a color space in use, including the pattern that a Pattern
+    /// color space paints with.
+    fn check(&mut self, kind: ColorUse, space: &ColorSpace, pattern: Option<&PdfObject>) {
+        if space.is_rgb() {
+            self.report(kind, None);
+        }
+        if !matches!(space, ColorSpace::Pattern(_)) {
+            return;
+        }
+        match pattern.and_then(|pattern| Pattern::load(self.doc, pattern)) {
+            Some(Pattern::Shading(pattern)) if pattern.shading.color_space.is_rgb() => {
+                self.report(ColorUse::Shading, None);
+            }
+            Some(Pattern::Tiling(pattern)) if self.depth < MAX_PATTERN_DEPTH => {
+                let mut interpreter =
+                    Interpreter::new(self.doc, pattern.resources, Matrix::identity());
+                self.depth += 1;
+                interpreter.run(&pattern.content, self);
+                self.depth -= 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ContentVisitor for RgbFinder<'_> {
+    fn path(&mut self, _path: &Path, paint: &PathPaint, state: &GraphicsState) {
+        if paint.fill.is_some() {
+            let pattern = state.fill_color.pattern_object.as_deref();
+            self.check(ColorUse::Fill, &state.fill_color_space, pattern);
+        }
+        if paint.stroke {
+            let pattern = state.stroke_color.pattern_object.as_deref();
+            self.check(ColorUse::Stroke, &state.stroke_color_space, pattern);
+        }
+    }
+
+    fn glyph(&mut self, _glyph: &TextGlyph, state: &GraphicsState) {
+        let mode = state.text.rendering_mode;
+        if mode.fills() {
+            let pattern = state.fill_color.pattern_object.as_deref();
+            self.check(ColorUse::Text, &state.fill_color_space, pattern);
+        }
+        if mode.strokes() {
+            let pattern = state.stroke_color.pattern_object.as_deref();
+            self.check(ColorUse::Text, &state.stroke_color_space, pattern);
+        }
+    }
+
+    fn image(&mut self, image: &XObject, _state: &GraphicsState) {
+        let doc = self.doc;
+        let rgb = doc
+            .resolve_key(&image.stream.dict, "ColorSpace")
+            .or_else(|| doc.resolve_key(&image.stream.dict, "CS"))
+            .and_then(|space| ColorSpace::from_object(doc, space, Some(image.resources)))
+            .is_some_and(|space| space.is_rgb());
+        if rgb {
+            self.report(ColorUse::Image, image.name);
+        }
+    }
+
+    fn begin_form(&mut self, form: &XObject, _state: &GraphicsState) -> bool {
+        let doc = self.doc;
+        let rgb = transparency_group(doc, &form.stream.dict)
+            .and_then(|group| group.get("CS"))
+            .and_then(|space| ColorSpace::from_object(doc, space, None))
+            .is_some_and(|space| space.is_rgb());
+        if rgb {
+            self.report(ColorUse::Group, form.name);
+        }
+        true
+    }
+
+    fn shading(&mut self, shading: &PdfObject, _state: &GraphicsState) {
+        let doc = self.doc;
+        let dict = match doc.resolve(shading) {
+            PdfObject::Dictionary(dict) => dict,
+            PdfObject::Stream(stream) => &stream.dict,
+            _ => return,
+        };
+        let rgb = dict
+            .get("ColorSpace")
+            .and_then(|space| ColorSpace::from_object(doc, space, None))
+            .is_some_and(|space| space.is_rgb());
+        if rgb {
+            self.report(ColorUse::Shading, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_rgb() {
+        let content = "0 0 0 1 k 0 0 10 10 re f 1 0 0 RG 0 0 m 10 10 l S \
+                       q 10 0 0 10 0 0 cm /Im Do Q /Sh sh";
+        let data = format!(
+            "%PDF-1.7\n\
+             1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+             2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj\n\
+             3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 10 10] /Contents 4 0 R \
+             /Resources << /XObject << /Im 5 0 R >> /Shading << /Sh << /ShadingType 2 \
+             /ColorSpace /DeviceCMYK /Coords [0 0 1 0] /Function << /FunctionType 2 \
+             /Domain [0 1] /C0 [0 0 0 0] /C1 [0 0 0 1] /N 1 >> >> >> >> >> endobj\n\
+             4 0 obj << /Length {} >> stream\n{}\nendstream endobj\n\
+             5 0 obj << /Type /XObject /Subtype /Image /Width 1 /Height 1 \
+             /ColorSpace [/Indexed /DeviceRGB 0 <FF0000>] /BitsPerComponent 8 /Length 1 >> \
+             stream\n\0\nendstream endobj\n\
+             trailer << /Root 1 0 R >>\n%%EOF\n",
+            content.len(),
+            content
+        );
+        let doc = Document::from_bytes(data.into_bytes()).unwrap();
+        assert!(!has_cmyk_output_intent(&doc));
+        let uses = find_rgb(&doc).unwrap();
+        assert_eq!(
+            uses,
+            vec![
+                RgbUse {
+                    page: 0,
+                    kind: ColorUse::Stroke,
+                    name: None,
+                },
+                RgbUse {
+                    page: 0,
+                    kind: ColorUse::Image,
+                    name: Some("Im".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -1,10 +1,22 @@
+pub mod cmap;
+pub mod content;
 pub mod cross_ref_table;
 pub mod header;
 pub mod objects;
 pub mod trailer;
 
 use aho_corasick::AhoCorasick;
-use std::{fs, io};
+use bstr::ByteSlice;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs, io,
+};
+
+use crate::api::{
+    encryption::{Credentials, EncryptionError, SecurityHandler},
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    structure::{Body, CrossRefTable, Subsection, SubsectionEntry, Trailer, PDF},
+};
 
 pub fn read_bytes(path: &str) -> Result<Vec<u8>, io::Error> {
     let f = fs::read(path)?;
@@ -12,34 +24,410 @@ pub fn read_bytes(path: &str) -> Result<Vec<u8>, io::Error> {
     Ok(f)
 }
 
-pub fn read_file(path: &str) -> Result<(), io::Error> {
+pub fn read_file(path: &str) -> Result<PDF, io::Error> {
     let data = read_bytes(path)?;
+    read_pdf(&data)
+}
+
+pub(crate) fn invalid_data<E: std::fmt::Debug>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+/// Reads the four parts of a PDF file from its bytes.
+///
+/// The file is read from the end: the "startxref" keyword gives the byte offset of
+/// the last cross-reference section, whose trailer may point at earlier sections
+/// through /Prev. Each in-use entry is then read at its byte offset. If the
+/// cross-reference table is missing or damaged, the file is instead scanned for
+/// object keywords from the start, as done by `scan_objects`.
+///
+/// An encrypted file is opened with the empty user password, as in
+/// `read_pdf_with_password`.
+pub fn read_pdf(data: &[u8]) -> Result<PDF, io::Error> {
+    read_pdf_with_password(data, "")
+}
+
+/// Like `read_pdf`, authenticating with the given user or owner password if the
+/// file is encrypted. See `read_pdf_with`.
+pub fn read_pdf_with_password(data: &[u8], password: &str) -> Result<PDF, io::Error> {
+    read_pdf_with(data, Credentials::Password(password))
+}
+
+/// Like `read_pdf`, opening an encrypted file with the given credentials. The
+/// strings and streams of every object are decrypted as the file is read, and
+/// /Encrypt is removed from the trailer, so that the document is written back
+/// out unencrypted. Fails with `io::ErrorKind::PermissionDenied` if the
+/// password or key is wrong. See sec. 7.6 (pg. 77)
+pub fn read_pdf_with(data: &[u8], credentials: Credentials) -> Result<PDF, io::Error> {
+    let header_offset = data.find(b"%PDF-").unwrap_or(0);
+    let (_, header) = header::read_header(&data[header_offset..]).map_err(invalid_data)?;
+
+    let start_xref = find_start_xref(data);
+    let from_xref = start_xref
+        .and_then(|offset| read_xref_chain(data, header_offset, offset))
+        .and_then(|(table, entries)| {
+            let body = read_body(data, header_offset, &table)?;
+            Some((table, entries, body))
+        });
+
+    let (cross_ref_table, mut entries, mut body) = match from_xref {
+        Some(parts) => parts,
+        None => scan_indirect_objects(data),
+    };
+
+    if let Some(encrypt) = entries.get("Encrypt").cloned() {
+        let encrypt_id = encrypt.as_reference();
+        let dict = match encrypt_id.and_then(|id| body.objects.get(&id)) {
+            Some(object) => object.as_dict(),
+            None => encrypt.as_dict(),
+        };
+        let dict = dict.cloned().ok_or_else(|| {
+            EncryptionError::Malformed("/Encrypt is not a dictionary".to_string())
+        })?;
+        let file_id = entries
+            .get("ID")
+            .and_then(PdfObject::as_array)
+            .and_then(|id| id.first())
+            .and_then(PdfObject::as_bytes)
+            .unwrap_or_default();
+        let handler = SecurityHandler::open(&dict, file_id, credentials)?;
+        for (&id, object) in body.objects.iter_mut() {
+            if Some(id) != encrypt_id {
+                handler.decrypt_object(id, object);
+            }
+        }
+        if let Some(id) = encrypt_id {
+            body.objects.remove(&id);
+        }
+        entries.remove("Encrypt");
+    }
+
+    unpack_object_streams(&mut body, &cross_ref_table);
+    set_missing_root(&mut entries, &body);
+
+    Ok(PDF {
+        header,
+        body,
+        cross_ref_table,
+        trailer: Trailer {
+            entries,
+            start_xref: start_xref.unwrap_or(0),
+        },
+    })
+}
+
+/// The second-to-last line of the file holds the offset of the last
+/// cross-reference section, following the "startxref" keyword.
+pub fn find_start_xref(data: &[u8]) -> Option<usize> {
+    let position = data.rfind(b"startxref")?;
+    let rest = objects::skip_whitespace(&data[position + 9..]);
+    let end = rest.iter().position(|c| !c.is_ascii_digit())?;
+    rest[..end].to_str().ok()?.parse().ok()
+}
+
+/// Reads the cross-reference section at the given offset along with every earlier
+/// section linked through the /Prev trailer entry. Entries in later sections take
+/// precedence, and the returned trailer dictionary is the most recent one.
+fn read_xref_chain(
+    data: &[u8],
+    header_offset: usize,
+    start: usize,
+) -> Option<(CrossRefTable, Dictionary)> {
+    let mut merged: BTreeMap<usize, SubsectionEntry> = BTreeMap::new();
+    let mut trailer: Option<Dictionary> = None;
+    let mut visited = HashSet::new();
+    let mut offset = Some(start);
+
+    while let Some(current) = offset.filter(|o| visited.insert(*o)) {
+        let (table, dict) = read_xref_section(data, header_offset.checked_add(current)?)?;
+        let mut section = entries_by_number(table);
+        // A hybrid-reference file lists the objects that only PDF 1.5 readers
+        // should find in a cross-reference stream named by /XRefStm.
+        // See sec. 7.5.8.4 (pg. 69)
+        if let Some(stream_offset) = dict.get_i64("XRefStm") {
+            let hidden = usize::try_from(stream_offset)
+                .ok()
+                .and_then(|offset| header_offset.checked_add(offset))
+                .and_then(|offset| read_xref_section(data, offset));
+            for (number, entry) in hidden
+                .map(|(t, _)| entries_by_number(t))
+                .unwrap_or_default()
+            {
+                if !section.get(&number).is_some_and(|e| e.in_use) {
+                    section.insert(number, entry);
+                }
+            }
+        }
+        for (number, entry) in section {
+            merged.entry(number).or_insert(entry);
+        }
+        // An offset that cannot be in the file means the chain is damaged, and
+        // the file is scanned instead.
+        offset = match dict.get_i64("Prev") {
+            Some(prev) => Some(usize::try_from(prev).ok()?),
+            None => None,
+        };
+        trailer.get_or_insert(dict);
+    }
+
+    Some((merge_subsections(merged), trailer?))
+}
+
+/// A classic cross-reference section starts with the "xref" keyword and is
+/// followed by the "trailer" keyword and the trailer dictionary. From PDF 1.5
+/// on, the section may instead be a cross-reference stream, whose dictionary
+/// holds the trailer entries.
+pub(crate) fn read_xref_section(data: &[u8], offset: usize) -> Option<(CrossRefTable, Dictionary)> {
+    let input = objects::skip_whitespace(data.get(offset..)?);
+    let Some(input) = input.strip_prefix(b"xref") else {
+        let (_, (_, object)) = objects::parse_indirect_object(input).ok()?;
+        return match object {
+            PdfObject::Stream(stream) if stream.dict.has_type("XRef") => {
+                let table = read_xref_stream(&stream)?;
+                Some((table, stream.dict))
+            }
+            _ => None,
+        };
+    };
+    let input = objects::skip_whitespace(input);
+    let (input, table) = cross_ref_table::read_cross_ref_table(input, 0).ok()?;
+    let input = objects::skip_whitespace(input).strip_prefix(b"trailer")?;
+    let (_, dict) = objects::parse_dictionary(input).ok()?;
+    Some((table, dict))
+}
+
+/// Reads the entries of a cross-reference stream. Each entry is a row of three
+/// big-endian fields whose widths are given by /W: the type of the entry (0 for
+/// free, 1 for in use, 2 for an object in an object stream) and two fields
+/// whose meaning depends on it. /Index lists the subsections as pairs of first
+/// object number and count. See sec. 7.5.8 (pg. 65)
+pub fn read_xref_stream(stream: &Stream) -> Option<CrossRefTable> {
+    let data = stream.decode().ok()?;
+    let widths: Vec<usize> = stream
+        .dict
+        .get("W")?
+        .as_numbers()?
+        .iter()
+        .map(|&w| w as usize)
+        .collect();
+    let [w1, w2, w3] = <[usize; 3]>::try_from(widths).ok()?;
+    let row_length = w1 + w2 + w3;
+    if row_length == 0 || w1.max(w2).max(w3) > 8 {
+        return None;
+    }
+    let index = match stream.dict.get("Index").and_then(PdfObject::as_numbers) {
+        Some(index) => index,
+        None => vec![0.0, stream.dict.get_f64("Size")?],
+    };
+
+    let field = |row: &[u8], start: usize, width: usize| {
+        row[start..start + width]
+            .iter()
+            .fold(0u64, |value, &byte| value << 8 | byte as u64)
+    };
+    let mut rows = data.chunks_exact(row_length);
+    let mut subsections = vec![];
+    for pair in index.chunks_exact(2) {
+        let (object_number, count) = (pair[0] as usize, pair[1] as usize);
+        // Object numbers must fit an ObjectId.
+        if object_number.saturating_add(count) > i32::MAX as usize {
+            return None;
+        }
+        let mut entries = vec![];
+        for row in rows.by_ref().take(count) {
+            // The type defaults to 1 when its field has a width of 0.
+            let kind = if w1 == 0 { 1 } else { field(row, 0, w1) };
+            let second = field(row, w1, w2);
+            let third = field(row, w1 + w2, w3);
+            // Entries whose numbers do not fit are treated as free.
+            let stream = i32::try_from(second).ok();
+            let generation = i32::try_from(third).ok();
+            entries.push(match (kind, stream, generation) {
+                (2, Some(stream), _) => SubsectionEntry {
+                    byte_offset: third,
+                    generation_number: 0,
+                    in_use: true,
+                    object_stream: Some(stream),
+                },
+                (kind, _, generation) => SubsectionEntry {
+                    byte_offset: second,
+                    generation_number: generation.unwrap_or(0),
+                    in_use: kind == 1 && generation.is_some(),
+                    object_stream: None,
+                },
+            });
+        }
+        subsections.push(Subsection {
+            object_number,
+            num_entries: entries.len(),
+            entries,
+        });
+    }
+    Some(CrossRefTable { subsections })
+}
+
+/// Reads the objects stored in an object stream. The decoded data starts with
+/// pairs of object number and offset, relative to /First, for each of the /N
+/// objects. See sec. 7.5.7 (pg. 64)
+pub fn read_object_stream(stream: &Stream) -> Option<Vec<(ObjectId, PdfObject)>> {
+    let data = stream.decode().ok()?;
+    let count = usize::try_from(stream.dict.get_i64("N")?).ok()?;
+    let first = usize::try_from(stream.dict.get_i64("First")?).ok()?;
+    let mut header = data.get(..first)?;
+    let mut objects = vec![];
+    for _ in 0..count {
+        let (rest, number) = objects::parse_number(objects::skip_whitespace(header)).ok()?;
+        let (rest, offset) = objects::parse_number(objects::skip_whitespace(rest)).ok()?;
+        header = rest;
+        let number = i32::try_from(number.as_i64()?).ok()?;
+        let offset = usize::try_from(offset.as_i64()?).ok()?;
+        if let Some(input) = data.get(first.checked_add(offset)?..) {
+            if let Ok((_, object)) = objects::parse_object(input) {
+                objects.push(((number, 0), object));
+            }
+        }
+    }
+    Some(objects)
+}
+
+fn entries_by_number(table: CrossRefTable) -> BTreeMap<usize, SubsectionEntry> {
+    let mut entries = BTreeMap::new();
+    for subsection in table.subsections {
+        for (i, entry) in subsection.entries.into_iter().enumerate() {
+            entries.insert(subsection.object_number + i, entry);
+        }
+    }
+    entries
+}
+
+/// Groups entries keyed by object number back into contiguous subsections.
+pub(crate) fn merge_subsections(entries: BTreeMap<usize, SubsectionEntry>) -> CrossRefTable {
+    let mut subsections: Vec<Subsection> = vec![];
+    for (object_number, entry) in entries {
+        match subsections.last_mut() {
+            Some(last) if last.object_number + last.num_entries == object_number => {
+                last.entries.push(entry);
+                last.num_entries += 1;
+            }
+            _ => subsections.push(Subsection {
+                object_number,
+                num_entries: 1,
+                entries: vec![entry],
+            }),
+        }
+    }
+    CrossRefTable { subsections }
+}
 
+/// Reads every in-use object listed in the cross-reference table. Returns None if
+/// any entry does not point at the object it claims to, so that the caller can
+/// fall back to scanning the file.
+///
+/// Objects stored in object streams are left for `unpack_object_streams`, as
+/// the streams may have to be decrypted first; only the presence of the
+/// streams themselves is checked here.
+fn read_body(data: &[u8], header_offset: usize, table: &CrossRefTable) -> Option<Body> {
+    let mut body = Body::default();
+    let mut streams = BTreeSet::new();
+    for subsection in &table.subsections {
+        for (i, entry) in subsection.entries.iter().enumerate() {
+            let number = subsection.object_number + i;
+            if !entry.in_use || number == 0 {
+                continue;
+            }
+            if let Some(stream) = entry.object_stream {
+                streams.insert(stream);
+                continue;
+            }
+            let offset = usize::try_from(entry.byte_offset)
+                .ok()
+                .and_then(|offset| header_offset.checked_add(offset))?;
+            let (_, (id, object)) = objects::parse_indirect_object(data.get(offset..)?).ok()?;
+            if id.0 as usize != number {
+                return None;
+            }
+            body.objects.insert(id, object);
+        }
+    }
+
+    let all_present = streams
+        .iter()
+        .all(|&number| matches!(body.objects.get(&(number, 0)), Some(PdfObject::Stream(_))));
+    all_present.then_some(body)
+}
+
+/// Adds the objects stored in object streams to the body, which keeps the
+/// streams along with them. Only the objects the cross-reference table places
+/// in a stream are taken from it; without a table (when the file was scanned)
+/// every object stream is read, without replacing objects stored directly.
+fn unpack_object_streams(body: &mut Body, table: &CrossRefTable) {
+    let mut compressed = BTreeMap::new();
+    for subsection in &table.subsections {
+        for (i, entry) in subsection.entries.iter().enumerate() {
+            if let (true, Some(stream)) = (entry.in_use, entry.object_stream) {
+                compressed.insert((subsection.object_number + i) as i32, stream);
+            }
+        }
+    }
+
+    let mut packed = vec![];
+    for (&(number, _), object) in &body.objects {
+        let Some(stream) = object.as_stream().filter(|s| s.dict.has_type("ObjStm")) else {
+            continue;
+        };
+        if !table.subsections.is_empty() && !compressed.values().any(|&s| s == number) {
+            continue;
+        }
+        for (id, object) in read_object_stream(stream).unwrap_or_default() {
+            if table.subsections.is_empty() || compressed.get(&id.0) == Some(&number) {
+                packed.push((id, object));
+            }
+        }
+    }
+    for (id, object) in packed {
+        body.objects.entry(id).or_insert(object);
+    }
+}
+
+/// Points /Root at the catalog when the trailer lacks it, as in some damaged
+/// files.
+fn set_missing_root(trailer: &mut Dictionary, body: &Body) {
+    if trailer.get("Root").is_none() {
+        if let Some((id, _)) = body
+            .objects
+            .iter()
+            .find(|(_, o)| o.as_dict().map(|d| d.has_type("Catalog")).unwrap_or(false))
+        {
+            trailer.set("Root", PdfObject::Reference(*id));
+        }
+    }
+}
+
+/// Reads a file without relying on the cross-reference table by searching for the
+/// keywords that start and end objects and the trailer.
+pub fn scan_objects(data: &[u8]) -> (CrossRefTable, Dictionary, Body) {
+    let (table, mut trailer, mut body) = scan_indirect_objects(data);
+    unpack_object_streams(&mut body, &table);
+    set_missing_root(&mut trailer, &body);
+    (table, trailer, body)
+}
+
+/// Like `scan_objects`, without reading the objects in object streams.
+fn scan_indirect_objects(data: &[u8]) -> (CrossRefTable, Dictionary, Body) {
     let patterns = &[
-        "%PDF-",     // Pattern 0 - Start of File
-        "\nxref\n",  // Pattern 1 - Start of cross-reference table
-        "trailer\n", // Pattern 2 - Start of trailer
-        " obj\n",    // Pattern 3 - Start of Object
-        "endobj\n",  // Pattern 4 - End of Object
+        "%PDF-",    // Pattern 0 - Start of File
+        "\nxref\n", // Pattern 1 - Start of cross-reference table
+        "trailer",  // Pattern 2 - Start of trailer
+        " obj",     // Pattern 3 - Start of Object
+        "endobj",   // Pattern 4 - End of Object
     ];
-    let haystack = data.as_slice();
+    let haystack = data;
 
     let ac = AhoCorasick::new(patterns).unwrap();
     let mut matches = vec![];
     for mat in ac.find_iter(haystack) {
         matches.push((mat.pattern(), mat.start(), mat.end()));
     }
-    println!("{:#?}", matches);
-    let header_offset: Vec<_> = matches
-        .iter()
-        .filter(|&x| x.0.as_usize() == 0)
-        .map(|x| x.1)
-        .collect();
-    let xref_offset: Vec<_> = matches
-        .iter()
-        .filter(|&x| x.0.as_usize() == 1)
-        .map(|x| x.2)
-        .collect();
     let trailer_offset: Vec<_> = matches
         .iter()
         .filter(|&x| x.0.as_usize() == 2)
@@ -47,16 +435,30 @@ pub fn read_file(path: &str) -> Result<(), io::Error> {
         .collect();
     let objects: Vec<(usize, usize)> = objects::get_objects(matches.as_slice());
 
-    println!("Header Offset: {:?}", header_offset);
-    println!("xref_offset: {:?}", xref_offset);
-    println!("Trailer Offset: {:?}", trailer_offset);
+    let mut body = Body::default();
+    for (id, object) in objects::read_objects(data, objects) {
+        // Later definitions of the same object replace earlier ones.
+        body.objects.insert(id, object);
+    }
 
-    header::read_header(&data);
-    cross_ref_table::read_cross_ref_table(&data, xref_offset[0]);
-    trailer::read_trailer(&data, trailer_offset[0]);
-    objects::read_objects(&data, objects);
+    let trailer = trailer_offset
+        .iter()
+        .rev()
+        .find_map(|&offset| objects::parse_dictionary(&data[offset..]).ok())
+        .map(|(_, dict)| dict)
+        .or_else(|| {
+            // Files with cross-reference streams have no "trailer" keyword; the
+            // stream dictionary with the highest object number holds the entries.
+            body.objects
+                .values()
+                .rev()
+                .filter_map(PdfObject::as_stream)
+                .find(|stream| stream.dict.has_type("XRef"))
+                .map(|stream| stream.dict.clone())
+        })
+        .unwrap_or_default();
 
-    Ok(())
+    (CrossRefTable::default(), trailer, body)
 }
 
 #[cfg(test)]
@@ -67,7 +469,14 @@ mod tests {
 
     #[test]
     fn test_file() -> Result<(), io::Error> {
-        read_file("HelloWorld.pdf");
+        let pdf = read_file("HelloWorld.pdf")?;
+        assert_eq!(pdf.header.version, "1.6");
+        assert_eq!(pdf.body.objects.len(), 13);
+        assert_eq!(pdf.trailer.start_xref, 6833);
+        assert_eq!(
+            pdf.trailer.entries.get("Root"),
+            Some(&PdfObject::Reference((12, 0)))
+        );
         Ok(())
     }
 
@@ -78,4 +487,74 @@ mod tests {
         println!("Bytes: {:#?}", &bytes[0..10].to_str());
         Ok(())
     }
+
+    #[test]
+    fn test_scan_objects() -> Result<(), io::Error> {
+        let data = read_bytes("HelloWorld.pdf")?;
+        let (_, trailer, body) = scan_objects(&data);
+        assert_eq!(body.objects.len(), 13);
+        assert_eq!(trailer.get_i64("Size"), Some(14));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_object_stream() {
+        let stream = |n: &str, first: &str, data: &[u8]| {
+            let mut dict = Dictionary::new();
+            dict.set("N", objects::parse_object(n.as_bytes()).unwrap().1);
+            dict.set("First", objects::parse_object(first.as_bytes()).unwrap().1);
+            Stream::new(dict, data.to_vec())
+        };
+        let valid = stream("2", "8", b"4 0 5 2 1 (a)");
+        assert_eq!(
+            read_object_stream(&valid),
+            Some(vec![
+                ((4, 0), PdfObject::Integer(1)),
+                ((5, 0), PdfObject::String(b"a".to_vec()))
+            ])
+        );
+
+        // Negative and oversized counts and offsets are rejected rather than
+        // wrapping around.
+        assert_eq!(
+            read_object_stream(&stream("-1", "8", b"4 0 5 2 1 (a)")),
+            None
+        );
+        assert_eq!(
+            read_object_stream(&stream("2", "-8", b"4 0 5 2 1 (a)")),
+            None
+        );
+        assert_eq!(read_object_stream(&stream("1", "4", b"4 -5 1")), None);
+        assert_eq!(
+            read_object_stream(&stream("1", "22", b"4 9223372036854775807 1")),
+            Some(vec![])
+        );
+        assert_eq!(
+            read_object_stream(&stream("1", "14", b"4294967296 0 1")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_bad_prev() -> Result<(), io::Error> {
+        // Junk before the header moves every offset in the file.
+        let data = [b"\n".as_slice(), &read_bytes("HelloWorld.pdf")?].concat();
+        for prev in ["-1", "9223372036854775807"] {
+            let damaged = data.replace(b"<</Size 14", format!("<</Prev {} /Size 14", prev));
+            let pdf = read_pdf(&damaged)?;
+            assert_eq!(pdf.body.objects.len(), 13);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_xref_stream_offset() -> Result<(), io::Error> {
+        let data = [b"\n".as_slice(), &read_bytes("HelloWorld.pdf")?].concat();
+        for offset in ["-1", "9223372036854775807"] {
+            let damaged = data.replace(b"<</Size 14", format!("<</XRefStm {} /Size 14", offset));
+            let pdf = read_pdf(&damaged)?;
+            assert_eq!(pdf.body.objects.len(), 13);
+        }
+        Ok(())
+    }
 }
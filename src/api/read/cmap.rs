@@ -0,0 +1,155 @@
+use crate::api::{
+    font::{CMap, CidRange},
+    objects::PdfObject,
+    read::content::parse_content,
+};
+
+/// Bfrange entries spanning more codes than this are truncated, so that a
+/// malformed range cannot exhaust memory.
+const MAX_RANGE_SIZE: u32 = 0x10000;
+
+/// Reads a CMap program, as embedded in /ToUnicode streams and /Encoding streams
+/// of Type0 fonts. CMaps are PostScript programs, but their syntax is close enough
+/// to content streams to read them as a sequence of operations; the entries of
+/// each block become the operands of the operator that closes the block:
+///     2 begincodespacerange
+///         <00> <80>
+///         <8140> <FFFF>
+///     endcodespacerange
+///
+/// See sec. 9.7.5 (pg. 321) and 9.10.3 (pg. 349)
+pub fn read_cmap(data: &[u8]) -> CMap {
+    let mut cmap = CMap::default();
+
+    for operation in parse_content(data) {
+        let operands = operation.operands.as_slice();
+        match operation.operator.as_str() {
+            "endcodespacerange" => {
+                for pair in operands.chunks_exact(2) {
+                    if let (Some(low), Some(high)) = (pair[0].as_bytes(), pair[1].as_bytes()) {
+                        cmap.codespace_ranges.push((low.to_vec(), high.to_vec()));
+                    }
+                }
+            }
+            "endcidrange" => {
+                for entry in operands.chunks_exact(3) {
+                    if let (Some(low), Some(high), Some(cid)) =
+                        (entry[0].as_bytes(), entry[1].as_bytes(), entry[2].as_i64())
+                    {
+                        cmap.cid_ranges.push(CidRange {
+                            low: bytes_to_code(low),
+                            high: bytes_to_code(high),
+                            cid: cid as u32,
+                        });
+                    }
+                }
+            }
+            "endcidchar" => {
+                for entry in operands.chunks_exact(2) {
+                    if let (Some(code), Some(cid)) = (entry[0].as_bytes(), entry[1].as_i64()) {
+                        let code = bytes_to_code(code);
+                        cmap.cid_ranges.push(CidRange {
+                            low: code,
+                            high: code,
+                            cid: cid as u32,
+                        });
+                    }
+                }
+            }
+            "endbfchar" => {
+                for entry in operands.chunks_exact(2) {
+                    if let (Some(code), Some(unicode)) = (entry[0].as_bytes(), entry[1].as_bytes())
+                    {
+                        cmap.unicode
+                            .insert(bytes_to_code(code), utf16_be_to_string(unicode));
+                    }
+                }
+            }
+            "endbfrange" => {
+                for entry in operands.chunks_exact(3) {
+                    read_bfrange(&mut cmap, &entry[0], &entry[1], &entry[2]);
+                }
+            }
+            "def" if operands.first() == Some(&PdfObject::Name("WMode".into())) => {
+                cmap.vertical = operands.get(1).and_then(|m| m.as_i64()) == Some(1);
+            }
+            _ => {}
+        }
+    }
+    cmap
+}
+
+/// A bfrange maps a range of codes either to consecutive Unicode values starting
+/// at the destination string, or to the strings in a destination array.
+fn read_bfrange(cmap: &mut CMap, low: &PdfObject, high: &PdfObject, destination: &PdfObject) {
+    let (Some(low), Some(high)) = (low.as_bytes(), high.as_bytes()) else {
+        return;
+    };
+    let (low, high) = (bytes_to_code(low), bytes_to_code(high));
+    let high = high.min(low.saturating_add(MAX_RANGE_SIZE));
+
+    match destination {
+        PdfObject::String(start) => {
+            let mut units: Vec<u16> = start
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect();
+            for code in low..=high {
+                cmap.unicode.insert(code, String::from_utf16_lossy(&units));
+                // Only the last byte of the destination is incremented.
+                if let Some(last) = units.last_mut() {
+                    *last = last.wrapping_add(1);
+                }
+            }
+        }
+        PdfObject::Array(strings) => {
+            for (code, unicode) in (low..=high).zip(strings) {
+                if let Some(unicode) = unicode.as_bytes() {
+                    cmap.unicode.insert(code, utf16_be_to_string(unicode));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Interprets a string of bytes as a big-endian character code.
+pub fn bytes_to_code(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0u32, |code, &b| (code << 8) | b as u32)
+}
+
+pub fn utf16_be_to_string(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_cmap() {
+        let data = b"/CIDInit/ProcSet findresource begin
+12 dict begin
+begincmap
+1 begincodespacerange
+<00> <FF>
+endcodespacerange
+2 beginbfchar
+<01> <0048>
+<02> <0065>
+endbfchar
+1 beginbfrange
+<10> <12> <0041>
+endbfrange
+endcmap";
+        let cmap = read_cmap(data);
+        assert_eq!(cmap.codespace_ranges, vec![(vec![0x00], vec![0xFF])]);
+        assert_eq!(cmap.to_unicode(1), Some("H"));
+        assert_eq!(cmap.to_unicode(0x12), Some("C"));
+        assert_eq!(cmap.to_unicode(0x13), None);
+    }
+}
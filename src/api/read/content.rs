@@ -0,0 +1,182 @@
+use bstr::ByteSlice;
+use nom::{
+    bytes::complete::take_while1,
+    error::{Error, ErrorKind},
+    Err, IResult,
+};
+
+use crate::api::{
+    content::Operation,
+    objects::{Dictionary, PdfObject, Stream},
+    read::objects::{parse_direct_object, parse_name, skip_whitespace},
+    utils::{is_pdf_regular, is_pdf_whitespace},
+};
+
+/// A content stream is a sequence of operations, each consisting of zero or more
+/// operands followed by an operator keyword. See sec. 7.8.2 (pg. 112)
+///
+/// Parsing is lenient: anything that cannot be read as an operand or operator is
+/// skipped, as viewers are expected to carry on past errors in content streams.
+pub fn parse_content(input: &[u8]) -> Vec<Operation> {
+    let mut operations = vec![];
+    let mut operands = vec![];
+    let mut input = input;
+
+    loop {
+        input = skip_whitespace(input);
+        if input.is_empty() {
+            break;
+        }
+        if let Ok((rest, operand)) = parse_direct_object(input) {
+            operands.push(operand);
+            input = rest;
+            continue;
+        }
+        match take_operator(input) {
+            Ok((rest, b"BI")) => match parse_inline_image(rest) {
+                Ok((rest, image)) => {
+                    operations.push(Operation::new("BI", vec![PdfObject::Stream(image)]));
+                    operands.clear();
+                    input = rest;
+                }
+                // A damaged inline image is dropped, and parsing carries on
+                // after its "EI" or, without one, after "BI".
+                Err(_) => {
+                    operands.clear();
+                    input = match find_end_of_image(rest) {
+                        Some(position) => &rest[position + 2..],
+                        None => rest,
+                    };
+                }
+            },
+            Ok((rest, operator)) => {
+                let operator = String::from_utf8_lossy(operator).into_owned();
+                operations.push(Operation::new(&operator, std::mem::take(&mut operands)));
+                input = rest;
+            }
+            // A stray delimiter such as ")" or "}"; drop it along with any operands.
+            Err(_) => {
+                operands.clear();
+                input = &input[1..];
+            }
+        }
+    }
+    operations
+}
+
+/// Operators are keywords made up of regular characters. The quote operators
+/// (' and ") are regular characters too.
+pub fn take_operator(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    take_while1(is_pdf_regular)(input)
+}
+
+/// An inline image is written as:
+///     BI
+///         key-value pairs
+///     ID single-white-space-character
+///         image data
+///     EI
+///
+/// The image data is returned as a stream with the key-value pairs as its
+/// dictionary. Since there is no reliable length, the data ends at the first "EI"
+/// surrounded by white-space, unless /L or /Length is given.
+/// See sec. 8.9.7 (pg. 241)
+pub fn parse_inline_image(input: &[u8]) -> IResult<&[u8], Stream> {
+    let mut dict = Dictionary::new();
+    let mut rest = input;
+    loop {
+        rest = skip_whitespace(rest);
+        if rest.starts_with(b"ID") && rest.get(2).map(|&c| is_pdf_whitespace(c)).unwrap_or(true) {
+            rest = rest.get(3..).unwrap_or(&[]);
+            break;
+        }
+        let (tail, key) = parse_name(rest)?;
+        let (tail, value) = parse_direct_object(tail)?;
+        dict.set(key, value);
+        rest = tail;
+    }
+
+    let length = dict
+        .get("L")
+        .or_else(|| dict.get("Length"))
+        .and_then(|l| l.as_i64())
+        .map(|l| l as usize)
+        .filter(|&l| l <= rest.len());
+    if let Some(length) = length {
+        let tail = skip_whitespace(&rest[length..]);
+        if let Some(tail) = tail.strip_prefix(b"EI") {
+            return Ok((tail, Stream::new(dict, rest[..length].to_vec())));
+        }
+    }
+
+    if let Some(position) = find_end_of_image(rest) {
+        let end = if position > 0 { position - 1 } else { 0 };
+        return Ok((
+            &rest[position + 2..],
+            Stream::new(dict, rest[..end].to_vec()),
+        ));
+    }
+    Err(Err::Error(Error::new(input, ErrorKind::TakeUntil)))
+}
+
+/// Finds the "EI" keyword that ends the data of an inline image: the first one
+/// surrounded by white-space.
+fn find_end_of_image(data: &[u8]) -> Option<usize> {
+    let mut search_from = 0;
+    while let Some(found) = data[search_from..].find(b"EI") {
+        let position = search_from + found;
+        let before = position == 0 || is_pdf_whitespace(data[position - 1]);
+        let after = data
+            .get(position + 2)
+            .map(|&c| is_pdf_whitespace(c))
+            .unwrap_or(true);
+        if before && after {
+            return Some(position);
+        }
+        search_from = position + 2;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_content() {
+        let input = b"0.1 w\nq 0 0.028 611.971 791.971 re\nW* n\nBT /F1 12 Tf[<01>-2<02>1]TJ ET Q";
+        let operations = parse_content(input);
+        let operators: Vec<_> = operations.iter().map(|o| o.operator.as_str()).collect();
+        assert_eq!(
+            operators,
+            vec!["w", "q", "re", "W*", "n", "BT", "Tf", "TJ", "ET", "Q"]
+        );
+        assert_eq!(operations[2].operands.len(), 4);
+        assert_eq!(operations[6].operands[0], PdfObject::Name("F1".to_string()));
+    }
+
+    #[test]
+    fn test_parse_inline_image() {
+        let input = b"q BI /W 2 /H 1 /BPC 8 /CS /G ID \x00\xFF EI Q";
+        let operations = parse_content(input);
+        assert_eq!(operations.len(), 3);
+        let image = operations[1].operands[0].as_stream().unwrap();
+        assert_eq!(image.dict.get_i64("W"), Some(2));
+        assert_eq!(image.data, vec![0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_parse_damaged_inline_image() {
+        let operators = |input: &[u8]| {
+            parse_content(input)
+                .into_iter()
+                .map(|o| o.operator)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            operators(b"q BI /W ] ID \x00 EI 0 g Q"),
+            vec!["q", "g", "Q"]
+        );
+        assert_eq!(operators(b"q BI 1 2 3 w Q"), vec!["q", "w", "Q"]);
+    }
+}
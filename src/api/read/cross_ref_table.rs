@@ -1,14 +1,17 @@
 use bstr::ByteSlice;
 use nom::{
-    bytes::complete::take,
-    character::complete::{digit1, line_ending, space0},
+    bytes::complete::{take, take_while1},
+    character::complete::{digit1, space0, space1},
     combinator::{map, map_res},
     multi::count,
     sequence::{tuple, Tuple},
     IResult,
 };
 
-use crate::api::structure::{self, CrossRefTable, Subsection, SubsectionEntry};
+use crate::api::{
+    structure::{self, CrossRefTable, Subsection, SubsectionEntry},
+    utils::is_pdf_eol,
+};
 
 /// The cross-reference table begins with the keyword "xref" and contains one or more
 /// cross-reference sections.
@@ -33,11 +36,27 @@ pub fn read_cross_ref_table(
     data: &[u8],
     start_offset: usize,
 ) -> IResult<&[u8], structure::CrossRefTable> {
+    let mut input = &data[start_offset..];
+    let mut subsections: Vec<Subsection> = vec![];
+
+    while let Ok((rest, (obj_number, num_objects))) = read_cross_ref_section_header(input) {
+        let (rest, subsection) = read_cross_ref_section(rest, obj_number, num_objects)?;
+        subsections.push(subsection);
+        input = rest;
+    }
+
+    let cross_ref_table = CrossRefTable { subsections };
+
+    Ok((input, cross_ref_table))
+}
+
+/// Reads the two integers at the start of each cross-reference section: the object
+/// number of the first object and the number of entries.
+pub fn read_cross_ref_section_header(input: &[u8]) -> IResult<&[u8], (usize, usize)> {
     let take_obj_number = map_res(digit1, |d: &[u8]| d.to_str().unwrap().parse::<usize>());
-    let take_space = take(1usize);
+    let take_space = space1;
     let take_num_objects = map_res(digit1, |d: &[u8]| d.to_str().unwrap().parse::<usize>());
-    let take_line_ending = line_ending;
-    let slice = &data[start_offset..];
+    let take_line_ending = tuple((space0, take_while1(is_pdf_eol)));
 
     let (input, (obj_number, _, num_objects, _)) = (
         take_obj_number,
@@ -45,14 +64,8 @@ pub fn read_cross_ref_table(
         take_num_objects,
         take_line_ending,
     )
-        .parse(slice)?;
-
-    let (input, subsection) = read_cross_ref_section(input, obj_number, num_objects).unwrap();
-    let subsections: Vec<Subsection> = vec![subsection];
-
-    let cross_ref_table = CrossRefTable { subsections };
-
-    Ok((input, cross_ref_table))
+        .parse(input)?;
+    Ok((input, (obj_number, num_objects)))
 }
 
 /// Each cross-reference table section begins with two integers, separated by a
@@ -60,14 +73,12 @@ pub fn read_cross_ref_table(
 /// object and the number of entries in the section.
 pub fn read_cross_ref_section(
     data: &[u8],
-    start_offset: usize,
+    object_number: usize,
     num_entries: usize,
 ) -> IResult<&[u8], Subsection> {
-    let slice = &data[start_offset..];
-
-    let (input, subsections) = read_subsections(slice, num_entries).unwrap();
+    let (input, subsections) = read_subsections(data, num_entries)?;
     let subsection = Subsection {
-        object_number: start_offset,
+        object_number,
         num_entries,
         entries: subsections,
     };
@@ -103,12 +114,54 @@ pub fn read_subsection(input: &[u8]) -> IResult<&[u8], SubsectionEntry> {
             take(1usize),
             take(1usize),
             space0,
-            line_ending,
+            take_while1(is_pdf_eol),
         )),
         |(byte_offset, _, gen_number, _, is_active, _, _)| SubsectionEntry {
-            byte_offset: byte_offset.to_str().unwrap().parse::<i32>().unwrap(),
-            generation_number: gen_number.to_str().unwrap().parse::<i32>().unwrap(),
-            in_use: true,
+            byte_offset: parse_field(byte_offset).unwrap_or(0),
+            generation_number: parse_field(gen_number)
+                .and_then(|number| i32::try_from(number).ok())
+                .unwrap_or(0),
+            in_use: is_active == b"n",
+            object_stream: None,
         },
     )(input)
 }
+
+/// Reads the digits of a fixed-width entry field, which may be padded with
+/// spaces. Returns None if the field holds anything else.
+fn parse_field(field: &[u8]) -> Option<u64> {
+    let digits = field.trim_with(|c| c == ' ');
+    if digits.is_empty() {
+        return None;
+    }
+    digits.iter().try_fold(0u64, |number, &c| {
+        let digit = (c as char).to_digit(10)?;
+        number.checked_mul(10)?.checked_add(digit as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_cross_ref_table() {
+        let input =
+            b"0 2\n0000000000 65535 f \n0000000017 00000 n \n5 1\n0000000081 00002 n\r\ntrailer";
+        let (rest, table) = read_cross_ref_table(input, 0).unwrap();
+        assert_eq!(rest, b"trailer");
+        assert_eq!(table.subsections.len(), 2);
+        assert!(!table.subsections[0].entries[0].in_use);
+        assert_eq!(table.subsections[0].entries[1].byte_offset, 17);
+        assert_eq!(table.subsections[1].object_number, 5);
+        assert_eq!(table.subsections[1].entries[0].generation_number, 2);
+    }
+
+    #[test]
+    fn test_read_damaged_subsection() {
+        let (_, entry) = read_subsection(b"\xff\xfe00000017 0000x n \n").unwrap();
+        assert_eq!(entry.byte_offset, 0);
+        assert_eq!(entry.generation_number, 0);
+        assert!(entry.in_use);
+    }
+}
@@ -1,4 +1,3 @@
-use bstr::ByteSlice;
 use nom::{
     bytes::complete::tag,
     character::complete::{multispace0, not_line_ending},
@@ -23,8 +22,8 @@ pub fn read_header(input: &[u8]) -> IResult<&[u8], structure::Header> {
     let (version, _) = tag("%PDF-")(first_row)?;
 
     let header = structure::Header {
-        version: String::from_utf8(version.to_vec()).unwrap(),
-        comment: second_row.is_ascii(),
+        version: String::from_utf8_lossy(version).into_owned(),
+        comment: second_row.starts_with(b"%") && !second_row.is_ascii(),
     };
 
     Ok((input, header))
@@ -47,6 +46,8 @@ pub fn take_second_row(input: &[u8]) -> IResult<&[u8], &[u8]> {
 
 #[cfg(test)]
 mod tests {
+    use bstr::ByteSlice;
+
     use super::*;
 
     #[test]
@@ -59,6 +60,13 @@ mod tests {
         println!("{:#?}", header);
     }
 
+    #[test]
+    fn test_read_binary_header() {
+        let (_, header) = read_header(b"%PDF-1.\xff\n%\xe2\xe3\xcf\xd3\n").unwrap();
+        assert_eq!(header.version, "1.\u{fffd}");
+        assert!(header.comment);
+    }
+
     #[test]
     fn test_first_row() {
         let input = "%PDF-1.6\nSECOND_ROW";
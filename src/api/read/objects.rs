@@ -1,9 +1,34 @@
 use aho_corasick::PatternID;
+use bstr::ByteSlice;
+use nom::{
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::digit1,
+    combinator::{map_res, opt},
+    error::{Error, ErrorKind},
+    sequence::tuple,
+    Err, IResult,
+};
 
-pub fn read_objects(data: &[u8], objects: Vec<(usize, usize)>) {
-    for kv in objects {
-        println!("Start: {:?}; End: {:?}", kv.0, kv.1);
-    }
+use crate::api::{
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    utils::{is_pdf_eol, is_pdf_regular, is_pdf_whitespace},
+};
+
+/// Parses each of the object spans found by `get_objects` into indirect objects.
+/// Spans that cannot be parsed are skipped.
+pub fn read_objects(data: &[u8], objects: Vec<(usize, usize)>) -> Vec<(ObjectId, PdfObject)> {
+    objects
+        .into_iter()
+        .filter_map(|(start, _)| {
+            let line_start = data[..start]
+                .rfind_byteset(b"\r\n")
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            parse_indirect_object(&data[line_start..])
+                .ok()
+                .map(|(_, object)| object)
+        })
+        .collect()
 }
 
 pub fn get_objects(matches: &[(PatternID, usize, usize)]) -> Vec<(usize, usize)> {
@@ -19,3 +44,406 @@ pub fn get_objects(matches: &[(PatternID, usize, usize)]) -> Vec<(usize, usize)>
         .collect();
     starts.into_iter().zip(ends).collect::<Vec<_>>()
 }
+
+fn error(input: &[u8], kind: ErrorKind) -> Err<Error<&[u8]>> {
+    Err::Error(Error::new(input, kind))
+}
+
+/// Skips white-space and comments. A comment starts with a PERCENT SIGN and runs
+/// to the end of the line. See sec. 7.2.4 (pg. 23)
+pub fn skip_whitespace(mut input: &[u8]) -> &[u8] {
+    loop {
+        let start = input
+            .iter()
+            .position(|&c| !is_pdf_whitespace(c))
+            .unwrap_or(input.len());
+        let trimmed = &input[start..];
+        match trimmed.first() {
+            Some(b'%') => {
+                let end = trimmed.find_byteset(b"\r\n").unwrap_or(trimmed.len());
+                input = &trimmed[end..];
+            }
+            _ => return trimmed,
+        }
+    }
+}
+
+/// Parses an indirect object definition:
+///     12 0 obj
+///         . . .
+///     endobj
+///
+/// If the object is a dictionary immediately followed by the "stream" keyword,
+/// the stream data is read as well. See sec. 7.3.10 (pg. 33) and 7.3.8 (pg. 31).
+pub fn parse_indirect_object(input: &[u8]) -> IResult<&[u8], (ObjectId, PdfObject)> {
+    let input = skip_whitespace(input);
+    let (input, (object_number, _, generation_number, _, _)) = tuple((
+        parse_i32,
+        take_while1(is_pdf_whitespace),
+        parse_i32,
+        take_while1(is_pdf_whitespace),
+        tag("obj"),
+    ))(input)?;
+
+    let (input, object) = parse_object(input)?;
+    let (input, object) = match object {
+        PdfObject::Dictionary(dict) => {
+            let after_dict = skip_whitespace(input);
+            if after_dict.starts_with(b"stream") {
+                let (input, data) = take_stream_data(&after_dict[6..], &dict)?;
+                (input, PdfObject::Stream(Stream::new(dict, data)))
+            } else {
+                (input, PdfObject::Dictionary(dict))
+            }
+        }
+        object => (input, object),
+    };
+
+    let input = skip_whitespace(input);
+    let (input, _) = opt(tag("endobj"))(input)?;
+    Ok((input, ((object_number, generation_number), object)))
+}
+
+/// The "stream" keyword is followed by an EOL (CRLF or LF) and then exactly
+/// /Length bytes of data. When /Length is an indirect reference or is wrong, the
+/// data runs until the "endstream" keyword instead.
+pub fn take_stream_data<'a>(input: &'a [u8], dict: &Dictionary) -> IResult<&'a [u8], Vec<u8>> {
+    let input = input
+        .strip_prefix(b"\r\n")
+        .or_else(|| input.strip_prefix(b"\n"))
+        .or_else(|| input.strip_prefix(b"\r"))
+        .unwrap_or(input);
+
+    if let Some(length) = dict.get("Length").and_then(|l| match l {
+        PdfObject::Integer(i) => Some(*i as usize),
+        _ => None,
+    }) {
+        if length <= input.len() {
+            let rest = skip_whitespace(&input[length..]);
+            if rest.starts_with(b"endstream") {
+                return Ok((&rest[9..], input[..length].to_vec()));
+            }
+        }
+    }
+
+    let end = input
+        .find(b"endstream")
+        .ok_or_else(|| error(input, ErrorKind::TakeUntil))?;
+    let mut data = &input[..end];
+    if data.ends_with(b"\r\n") {
+        data = &data[..data.len() - 2];
+    } else if data.last().copied().map(is_pdf_eol).unwrap_or(false) {
+        data = &data[..data.len() - 1];
+    }
+    Ok((&input[end + 9..], data.to_vec()))
+}
+
+/// Parses any object, including indirect references ("12 0 R").
+pub fn parse_object(input: &[u8]) -> IResult<&[u8], PdfObject> {
+    object(input, true)
+}
+
+/// Parses any direct object. Content streams cannot contain indirect references,
+/// so the operands of content stream operators are read with this.
+pub fn parse_direct_object(input: &[u8]) -> IResult<&[u8], PdfObject> {
+    object(input, false)
+}
+
+fn object(input: &[u8], allow_references: bool) -> IResult<&[u8], PdfObject> {
+    let input = skip_whitespace(input);
+    match input.first() {
+        None => Err(error(input, ErrorKind::Eof)),
+        Some(b'/') => {
+            let (input, name) = parse_name(input)?;
+            Ok((input, PdfObject::Name(name)))
+        }
+        Some(b'(') => {
+            let (input, string) = parse_literal_string(input)?;
+            Ok((input, PdfObject::String(string)))
+        }
+        Some(b'<') if input.starts_with(b"<<") => {
+            let (input, dict) = parse_dictionary_with(input, allow_references)?;
+            Ok((input, PdfObject::Dictionary(dict)))
+        }
+        Some(b'<') => {
+            let (input, string) = parse_hex_string(input)?;
+            Ok((input, PdfObject::String(string)))
+        }
+        Some(b'[') => parse_array(input, allow_references),
+        Some(c) if c.is_ascii_digit() || *c == b'+' || *c == b'-' || *c == b'.' => {
+            if allow_references {
+                if let Ok((rest, id)) = parse_reference(input) {
+                    return Ok((rest, PdfObject::Reference(id)));
+                }
+            }
+            parse_number(input)
+        }
+        Some(_) => {
+            let (rest, keyword) = take_while1(is_pdf_regular)(input)?;
+            match keyword {
+                b"true" => Ok((rest, PdfObject::Boolean(true))),
+                b"false" => Ok((rest, PdfObject::Boolean(false))),
+                b"null" => Ok((rest, PdfObject::Null)),
+                _ => Err(error(input, ErrorKind::Tag)),
+            }
+        }
+    }
+}
+
+fn parse_i32(input: &[u8]) -> IResult<&[u8], i32> {
+    map_res(digit1, |d: &[u8]| d.to_str().unwrap().parse::<i32>())(input)
+}
+
+/// An indirect reference consists of the object number, the generation number
+/// and the keyword "R". See sec. 7.3.10 (pg. 33)
+pub fn parse_reference(input: &[u8]) -> IResult<&[u8], ObjectId> {
+    let (input, (object_number, _, generation_number, _, _)) = tuple((
+        parse_i32,
+        take_while1(is_pdf_whitespace),
+        parse_i32,
+        take_while1(is_pdf_whitespace),
+        tag("R"),
+    ))(input)?;
+    match input.first() {
+        Some(&c) if is_pdf_regular(c) => Err(error(input, ErrorKind::Tag)),
+        _ => Ok((input, (object_number, generation_number))),
+    }
+}
+
+/// Integers and real numbers. Reals are written with a decimal point and may not
+/// use exponent notation. See sec. 7.3.3 (pg. 25)
+pub fn parse_number(input: &[u8]) -> IResult<&[u8], PdfObject> {
+    let (rest, token) = take_while1(|c: u8| c.is_ascii_digit() || b"+-.".contains(&c))(input)?;
+    let text = token.to_str().map_err(|_| error(input, ErrorKind::Digit))?;
+    if !text.contains('.') {
+        if let Ok(integer) = text.parse::<i64>() {
+            return Ok((rest, PdfObject::Integer(integer)));
+        }
+    }
+    // Some producers emit values such as "--1" or "1.2.3"; read what makes sense.
+    let cleaned = clean_real(text);
+    cleaned
+        .parse::<f64>()
+        .map(|real| (rest, PdfObject::Real(real)))
+        .map_err(|_| error(input, ErrorKind::Float))
+}
+
+fn clean_real(text: &str) -> String {
+    let negative = text.starts_with('-') && text.trim_start_matches(['-', '+']).len() < text.len();
+    let body = text.trim_start_matches(['-', '+']);
+    let mut seen_point = false;
+    let digits: String = body
+        .chars()
+        .filter(|&c| {
+            if c == '.' {
+                let keep = !seen_point;
+                seen_point = true;
+                keep
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+        .collect();
+    let digits = if digits.is_empty() || digits == "." {
+        "0".to_string()
+    } else {
+        digits
+    };
+    if negative {
+        format!("-{}", digits)
+    } else {
+        digits
+    }
+}
+
+/// Names start with a SOLIDUS followed by regular characters. Any character may
+/// be written as "#" followed by its two-digit hexadecimal code.
+/// See sec. 7.3.5 (pg. 28)
+pub fn parse_name(input: &[u8]) -> IResult<&[u8], String> {
+    let (input, _) = tag("/")(input)?;
+    let (input, raw) = take_while(is_pdf_regular)(input)?;
+
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'#' {
+            if let Some(byte) = raw
+                .get(i + 1..i + 3)
+                .and_then(|hex| hex.to_str().ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        bytes.push(raw[i]);
+        i += 1;
+    }
+    Ok((input, String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+/// Literal strings are enclosed in parentheses. Balanced parentheses are allowed
+/// without escaping, and the REVERSE SOLIDUS introduces escape sequences.
+/// See sec. 7.3.4.2, Table 3 (pg. 26)
+pub fn parse_literal_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let (mut rest, _) = tag("(")(input)?;
+    let mut output = vec![];
+    let mut depth = 1;
+
+    loop {
+        let Some((&c, tail)) = rest.split_first() else {
+            return Err(error(input, ErrorKind::Eof));
+        };
+        rest = tail;
+        match c {
+            b'(' => {
+                depth += 1;
+                output.push(c);
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((rest, output));
+                }
+                output.push(c);
+            }
+            b'\\' => {
+                let Some((&escaped, tail)) = rest.split_first() else {
+                    return Err(error(input, ErrorKind::Eof));
+                };
+                rest = tail;
+                match escaped {
+                    b'n' => output.push(b'\n'),
+                    b'r' => output.push(b'\r'),
+                    b't' => output.push(b'\t'),
+                    b'b' => output.push(0x08),
+                    b'f' => output.push(0x0C),
+                    b'0'..=b'7' => {
+                        let mut value = (escaped - b'0') as u32;
+                        for _ in 0..2 {
+                            match rest.first() {
+                                Some(&d @ b'0'..=b'7') => {
+                                    value = value * 8 + (d - b'0') as u32;
+                                    rest = &rest[1..];
+                                }
+                                _ => break,
+                            }
+                        }
+                        output.push(value as u8);
+                    }
+                    // A REVERSE SOLIDUS at the end of a line continues the string.
+                    b'\r' => rest = rest.strip_prefix(b"\n").unwrap_or(rest),
+                    b'\n' => {}
+                    other => output.push(other),
+                }
+            }
+            // An unescaped end-of-line is read as a single LINE FEED.
+            b'\r' => {
+                rest = rest.strip_prefix(b"\n").unwrap_or(rest);
+                output.push(b'\n');
+            }
+            _ => output.push(c),
+        }
+    }
+}
+
+/// Hexadecimal strings are enclosed in angle brackets. See sec. 7.3.4.3 (pg. 27)
+pub fn parse_hex_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let (rest, _) = tag("<")(input)?;
+    let end = rest
+        .find_byte(b'>')
+        .ok_or_else(|| error(input, ErrorKind::TakeUntil))?;
+    let bytes = crate::api::filters::ascii_hex_decode(&rest[..end])
+        .map_err(|_| error(input, ErrorKind::HexDigit))?;
+    Ok((&rest[end + 1..], bytes))
+}
+
+fn parse_array(input: &[u8], allow_references: bool) -> IResult<&[u8], PdfObject> {
+    let (mut rest, _) = tag("[")(input)?;
+    let mut items = vec![];
+    loop {
+        rest = skip_whitespace(rest);
+        match rest.first() {
+            Some(b']') => return Ok((&rest[1..], PdfObject::Array(items))),
+            None => return Err(error(input, ErrorKind::Eof)),
+            _ => {
+                let (tail, item) = object(rest, allow_references)?;
+                items.push(item);
+                rest = tail;
+            }
+        }
+    }
+}
+
+/// Parses a dictionary of key-value pairs delimited by "<< . . . >>".
+/// See sec. 7.3.7 (pg. 30)
+pub fn parse_dictionary(input: &[u8]) -> IResult<&[u8], Dictionary> {
+    parse_dictionary_with(skip_whitespace(input), true)
+}
+
+fn parse_dictionary_with(input: &[u8], allow_references: bool) -> IResult<&[u8], Dictionary> {
+    let (mut rest, _) = tag("<<")(input)?;
+    let mut dict = Dictionary::new();
+    loop {
+        rest = skip_whitespace(rest);
+        if let Some(tail) = rest.strip_prefix(b">>") {
+            return Ok((tail, dict));
+        }
+        let (tail, key) = parse_name(rest)?;
+        let (tail, value) = object(tail, allow_references)?;
+        dict.set(key, value);
+        rest = tail;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object() {
+        let input =
+            b"<</Type/Page/Parent 4 0 R/MediaBox[0 0 612 792]/Group<</S/Transparency/I true>>>>";
+        let (_, object) = parse_object(input).unwrap();
+        let dict = object.as_dict().unwrap();
+        assert!(dict.has_type("Page"));
+        assert_eq!(dict.get("Parent"), Some(&PdfObject::Reference((4, 0))));
+        assert_eq!(
+            dict.get("MediaBox").unwrap().as_numbers(),
+            Some(vec![0.0, 0.0, 612.0, 792.0])
+        );
+        let group = dict.get("Group").unwrap().as_dict().unwrap();
+        assert_eq!(group.get("I"), Some(&PdfObject::Boolean(true)));
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let (_, string) = parse_literal_string(b"(a (nested) \\(str\\)ing\\n\\101)").unwrap();
+        assert_eq!(string, b"a (nested) (str)ing\nA");
+        let (_, string) = parse_hex_string(b"<FEFF0057>").unwrap();
+        assert_eq!(string, vec![0xFE, 0xFF, 0x00, 0x57]);
+    }
+
+    #[test]
+    fn test_parse_name_and_numbers() {
+        let (_, name) = parse_name(b"/A#20B ").unwrap();
+        assert_eq!(name, "A B");
+        let (_, number) = parse_number(b"-.5").unwrap();
+        assert_eq!(number, PdfObject::Real(-0.5));
+        let (_, number) = parse_direct_object(b"12 0 R").unwrap();
+        assert_eq!(number, PdfObject::Integer(12));
+    }
+
+    #[test]
+    fn test_parse_indirect_object() {
+        let input = b"3 0 obj\n<</Length 5>>\nstream\nhello\nendstream\nendobj\n";
+        let (_, (id, object)) = parse_indirect_object(input).unwrap();
+        assert_eq!(id, (3, 0));
+        assert_eq!(object.as_stream().unwrap().data, b"hello");
+
+        let input = b"% comment\n4 0 obj\n<</Length 9 0 R>>\nstream\r\nhi\r\nendstream\nendobj";
+        let (_, (_, object)) = parse_indirect_object(input).unwrap();
+        assert_eq!(object.as_stream().unwrap().data, b"hi");
+    }
+}
@@ -4,7 +4,7 @@ use nom::character::complete::{multispace0, space0, space1};
 use nom::character::{is_alphabetic, is_digit};
 use nom::combinator::{map_res, rest};
 use nom::error::ErrorKind;
-use nom::sequence::{delimited, separated_pair, terminated, tuple};
+use nom::sequence::{delimited, separated_pair, tuple};
 use nom::{
     bytes::complete::{tag, take_till, take_until},
     character::is_space,
@@ -66,6 +66,12 @@ pub fn take_trailer_kv_pair(input: &[u8]) -> IResult<&[u8], Vec<(TrailerKey, Tra
                 parsed_pairs.push((pair.0, parse_object(pair.1).unwrap().1))
             }
             TrailerKey::ID => parsed_pairs.push((pair.0, parse_array(pair.1).unwrap().1)),
+            // The encryption dictionary is usually an indirect object, but may be
+            // given directly. See `encryption::SecurityHandler`.
+            TrailerKey::Encrypt => {
+                let value = parse_object(pair.1).map_or(TrailerValue::Unknown, |(_, v)| v);
+                parsed_pairs.push((pair.0, value))
+            }
             _ => parsed_pairs.push((pair.0, parse_unknown(pair.1).unwrap().1)),
         }
     }
@@ -0,0 +1,1029 @@
+/// Blend modes, mixing colors with the backdrop they are painted over.
+pub mod blend;
+
+/// Colors of patterns and shadings over the pixels being painted.
+pub mod paint;
+
+/// Scan conversion of polygons into anti-aliased coverage masks.
+pub mod raster;
+
+/// Conversion of stroked paths into the polygons that make up the stroke.
+pub mod stroke;
+
+use std::{io, rc::Rc};
+
+use crate::api::{
+    color::{Color, ColorSpace},
+    content::{
+        interpreter::{
+            transparency_group, ContentVisitor, Interpreter, PathPaint, TextGlyph, XObject,
+        },
+        state::{ClipPath, GraphicsState, SoftMask},
+    },
+    document::Document,
+    font::FontType,
+    function::Function,
+    geometry::{FillRule, Matrix, Path, Point, Polyline},
+    image::{check_size, decode_image, Bitmap, PixelFormat},
+    objects::{Dictionary, PdfObject},
+    page::Page,
+    pattern::{Pattern, Shading, TilingPattern},
+};
+
+use blend::{composite, lum, BlendMode};
+use paint::{Paint, Tile};
+use raster::{rasterize, Mask};
+use stroke::{stroke, StrokeStyle};
+
+/// The largest distance, in device pixels, between a curve and the line
+/// segments drawn in its place.
+const TOLERANCE: f64 = 0.2;
+
+/// Type3 glyph procedures and tiling pattern cells, which may show Type3 text
+/// or use patterns themselves, nest at most this deep.
+const MAX_NESTING: usize = 4;
+
+/// The largest width or height, in pixels, at which a tiling pattern cell is
+/// rendered.
+const MAX_TILE_SIZE: f64 = 2048.0;
+
+/// Dash patterns that repeat within this many pixels are stroked as solid
+/// lines, which is how they look.
+const MIN_DASH_PERIOD: f64 = 0.1;
+
+/// Renders a page to an RGBA bitmap at the given resolution, in dots per inch.
+///
+/// The visible region of the page (its crop box) is drawn on a white background,
+/// turned as the page's /Rotate entry asks. Paths are filled and stroked with
+/// anti-aliasing, clipped by the current clipping path; images are resampled to
+/// the device. Use `Bitmap::to_png` or `Bitmap::save_png` to write the result.
+/// Pages of more than `MAX_PIXELS` pixels at this resolution are an error.
+pub fn render_page(doc: &Document, page: &Page, dpi: f64) -> Result<Bitmap, io::Error> {
+    let (ctm, width, height) = page_transform(page, dpi / 72.0);
+    check_size(width, height)?;
+    let mut renderer = Renderer::new(doc, width, height);
+    renderer.pattern_spaces = vec![ctm];
+    let content = page.contents(doc)?;
+    let mut interpreter = Interpreter::for_page(doc, page, ctm);
+    interpreter.run(&content, &mut renderer);
+    Ok(renderer.canvas.to_bitmap())
+}
+
+/// The matrix mapping the default user space of a page to device pixels, with
+/// the y axis pointing down, and the size of the device. See sec. 8.3.2.3
+/// (pg. 126)
+pub fn page_transform(page: &Page, scale: f64) -> (Matrix, usize, usize) {
+    let [llx, lly, urx, ury] = page.crop_box();
+    let (w, h) = ((urx - llx) * scale, (ury - lly) * scale);
+    let rotation = match page.rotation() {
+        90 => Matrix::new(0.0, scale, scale, 0.0, 0.0, 0.0),
+        180 => Matrix::new(-scale, 0.0, 0.0, scale, w, 0.0),
+        270 => Matrix::new(0.0, -scale, -scale, 0.0, h, w),
+        _ => Matrix::new(scale, 0.0, 0.0, -scale, 0.0, h),
+    };
+    let ctm = Matrix::translate(-llx, -lly).multiply(&rotation);
+    let (width, height) = match page.rotation() {
+        90 | 270 => (h, w),
+        _ => (w, h),
+    };
+    (
+        ctm,
+        width.ceil().max(1.0) as usize,
+        height.ceil().max(1.0) as usize,
+    )
+}
+
+///
+/// The pixels being drawn on, as RGB colors and alpha with components between
+/// 0 and 1.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 4]>,
+    /// For the canvas of a non-isolated group, the alpha painted by the group
+    /// alone, without its backdrop.
+    pub group_alpha: Option<Vec<f32>>,
+    /// For the canvas of a knockout group, the pixels at the start of the
+    /// group, which each object is painted over instead of the objects before
+    /// it. See sec. 11.4.8 (pg. 380)
+    pub knockout: Option<Vec<[f32; 4]>>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, background: [f32; 4]) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![background; width * height],
+            group_alpha: None,
+            knockout: None,
+        }
+    }
+
+    /// Paints a color over a pixel with the given opacity and blend mode. The
+    /// shape is the fraction of the pixel that is painted.
+    pub fn composite(
+        &mut self,
+        x: usize,
+        y: usize,
+        color: [f32; 3],
+        alpha: f32,
+        shape: f32,
+        mode: BlendMode,
+    ) {
+        if alpha <= 0.0 || shape <= 0.0 {
+            return;
+        }
+        let i = y * self.width + x;
+        if let Some(group_alpha) = &mut self.group_alpha {
+            let painted = alpha * shape;
+            group_alpha[i] += painted - group_alpha[i] * painted;
+        }
+        match &self.knockout {
+            Some(initial) => {
+                let mut result = initial[i];
+                composite(&mut result, color, alpha, mode);
+                let pixel = &mut self.pixels[i];
+                for (channel, value) in pixel.iter_mut().zip(result) {
+                    *channel += (value - *channel) * shape;
+                }
+            }
+            None => composite(&mut self.pixels[i], color, alpha * shape, mode),
+        }
+    }
+
+    pub fn to_bitmap(&self) -> Bitmap {
+        let mut bitmap = Bitmap::new(self.width, self.height, PixelFormat::Rgba);
+        for (target, pixel) in bitmap.pixels.chunks_mut(4).zip(&self.pixels) {
+            for (channel, value) in target.iter_mut().zip(pixel) {
+                *channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+        bitmap
+    }
+}
+
+///
+/// A content visitor that draws on a canvas. The CTM of the interpreter must map
+/// user space to canvas pixels, as `page_transform` does.
+///
+/// Glyphs are drawn from the outlines of the embedded font programs, and Type3
+/// glyphs by running their glyph procedures; glyphs of fonts that are not
+/// embedded are not drawn. Areas painted with the Pattern color space show the
+/// pattern, and tiling patterns are rendered once per pattern and matrix. Text
+/// rendering modes that add glyphs to the clipping path paint them but do not
+/// clip.
+///
+/// Objects are composited with the constant alpha, blend mode and soft mask of
+/// their graphics state. Form XObjects that are transparency groups are drawn
+/// on a canvas of their own, then composited as a whole. See sec. 11 (pg. 355)
+///
+pub struct Renderer<'a> {
+    pub doc: &'a Document,
+    pub canvas: Canvas,
+    /// The intersection of the clipping paths of the last state drawn with.
+    clip: Option<(Vec<ClipPath>, Rc<Mask>)>,
+    /// How many Type3 glyph procedures and pattern cells are being drawn.
+    nesting: usize,
+    /// The matrices mapping the default coordinate space of the page, and of
+    /// each form XObject being painted, to the device. Patterns are placed in
+    /// the innermost one. See sec. 8.7.2 (pg. 209)
+    pattern_spaces: Vec<Matrix>,
+    /// The last pattern used, and the last tiling pattern cell rendered with
+    /// its pattern matrix.
+    pattern: Option<(Rc<PdfObject>, Rc<Pattern>)>,
+    tile: Option<(Rc<PdfObject>, Matrix, Rc<Tile>)>,
+    /// The last soft mask used, as a value for each pixel.
+    soft_mask: Option<(SoftMask, Rc<Vec<f32>>)>,
+    /// For each form XObject being painted, the group it is drawn in, if it is
+    /// a transparency group.
+    groups: Vec<Option<Group>>,
+}
+
+///
+/// A transparency group being drawn: the canvas it will be composited on, and
+/// how. See sec. 11.4 (pg. 372)
+///
+struct Group {
+    backdrop: Canvas,
+    alpha: f32,
+    mode: BlendMode,
+    soft_mask: Option<Rc<Vec<f32>>>,
+}
+
+impl<'a> Renderer<'a> {
+    /// A renderer drawing on a white, opaque canvas.
+    pub fn new(doc: &'a Document, width: usize, height: usize) -> Renderer<'a> {
+        Renderer {
+            doc,
+            canvas: Canvas::new(width, height, [1.0; 4]),
+            clip: None,
+            nesting: 0,
+            pattern_spaces: vec![Matrix::identity()],
+            pattern: None,
+            tile: None,
+            soft_mask: None,
+            groups: vec![],
+        }
+    }
+
+    /// A renderer drawing for this one on another canvas, with patterns placed
+    /// in the given pattern space.
+    fn offscreen(&self, canvas: Canvas, pattern_space: Matrix) -> Renderer<'a> {
+        Renderer {
+            doc: self.doc,
+            canvas,
+            clip: None,
+            nesting: self.nesting + 1,
+            pattern_spaces: vec![pattern_space],
+            pattern: None,
+            tile: None,
+            soft_mask: None,
+            groups: vec![],
+        }
+    }
+
+    /// Fills a path given in device space.
+    pub fn fill(
+        &mut self,
+        path: &Path,
+        rule: FillRule,
+        paint: &Paint,
+        alpha: f32,
+        state: &GraphicsState,
+    ) {
+        let polygons = path.flatten(TOLERANCE);
+        self.fill_polygons(&polygons, rule, paint, alpha, state);
+    }
+
+    /// Strokes a path given in user space, with the line width and dash pattern
+    /// of the state in user space units.
+    pub fn stroke(&mut self, path: &Path, paint: &Paint, alpha: f32, state: &GraphicsState) {
+        let expansion = state.ctm.expansion().max(1e-9);
+        let tolerance = TOLERANCE / expansion;
+        // A width of 0 asks for the thinnest line the device can draw.
+        let width = state.line_width.abs().max(1.0 / expansion);
+        let mut dash = state.dash.clone();
+        let period: f64 = dash.array.iter().map(|length| length.max(0.0)).sum();
+        if period * expansion < MIN_DASH_PERIOD {
+            dash.array.clear();
+        }
+        let style = StrokeStyle {
+            width,
+            cap: state.line_cap,
+            join: state.line_join,
+            miter_limit: state.miter_limit,
+            dash,
+        };
+        let polygons: Vec<Polyline> = stroke(&path.flatten(tolerance), &style, tolerance)
+            .iter()
+            .map(|polygon| polygon.transform(&state.ctm))
+            .collect();
+        self.fill_polygons(&polygons, FillRule::NonZeroWinding, paint, alpha, state);
+    }
+
+    fn fill_polygons(
+        &mut self,
+        polygons: &[Polyline],
+        rule: FillRule,
+        paint: &Paint,
+        alpha: f32,
+        state: &GraphicsState,
+    ) {
+        let mut mask = rasterize(polygons, rule, self.canvas.width, self.canvas.height);
+        if let Some(clip) = self.clip_mask(state) {
+            mask.intersect(&clip);
+        }
+        self.paint_mask(&mask, paint, alpha, state);
+    }
+
+    /// Paints the pixels of a mask, as much as they are covered, with the
+    /// blend mode and soft mask of the state.
+    fn paint_mask(&mut self, mask: &Mask, paint: &Paint, alpha: f32, state: &GraphicsState) {
+        let colors = paint.colors(mask);
+        let mode = blend_mode(state);
+        let soft_mask = self.soft_mask(state);
+        let width = self.canvas.width;
+        for (row, y) in mask.rows().enumerate() {
+            for (column, x) in mask.columns().enumerate() {
+                let (color, opacity) = colors[row * mask.width + column];
+                let masked = soft_mask.as_ref().map_or(1.0, |m| m[y * width + x]);
+                self.canvas
+                    .composite(x, y, color, opacity * alpha * masked, mask.get(x, y), mode);
+            }
+        }
+    }
+
+    /// The values of the soft mask of the state for each pixel of the canvas.
+    fn soft_mask(&mut self, state: &GraphicsState) -> Option<Rc<Vec<f32>>> {
+        let mask = state.soft_mask.as_ref()?;
+        if let Some((cached, values)) = &self.soft_mask {
+            if cached == mask {
+                return Some(values.clone());
+            }
+        }
+        // A mask that cannot be drawn leaves everything visible.
+        let values = Rc::new(
+            self.draw_soft_mask(mask)
+                .unwrap_or_else(|| vec![1.0; self.canvas.width * self.canvas.height]),
+        );
+        self.soft_mask = Some((mask.clone(), values.clone()));
+        Some(values)
+    }
+
+    /// Draws the group of a soft mask, and takes the mask values from its
+    /// alpha or its luminosity over the backdrop color, through the transfer
+    /// function. See sec. 11.6.5.2, Table 142 (pg. 398)
+    fn draw_soft_mask(&self, mask: &SoftMask) -> Option<Vec<f32>> {
+        if self.nesting >= MAX_NESTING {
+            return None;
+        }
+        let doc = self.doc;
+        let entry = mask.dict.get("G")?;
+        let stream = doc.resolve(entry).as_stream()?;
+        let luminosity = mask.dict.get_name("S") == Some("Luminosity");
+        let background = if luminosity {
+            let space = transparency_group(doc, &stream.dict)
+                .and_then(|group| group.get("CS"))
+                .and_then(|space| ColorSpace::from_object(doc, space, None))
+                .unwrap_or(ColorSpace::DeviceGray);
+            let backdrop = doc
+                .resolve_key(&mask.dict, "BC")
+                .and_then(PdfObject::as_numbers)
+                .unwrap_or_else(|| space.initial_color().components);
+            let [r, g, b] = space.to_rgb(&backdrop);
+            [r as f32, g as f32, b as f32, 1.0]
+        } else {
+            [0.0; 4]
+        };
+        // The transfer function is sampled, as it is applied to every pixel.
+        let transfer: Option<Vec<f32>> = doc
+            .resolve_key(&mask.dict, "TR")
+            .filter(|transfer| transfer.as_name() != Some("Identity"))
+            .and_then(|transfer| Function::load(doc, transfer))
+            .map(|function| {
+                (0..=255)
+                    .map(|i| {
+                        let value = function.eval(&[i as f64 / 255.0]);
+                        value.first().copied().unwrap_or(0.0) as f32
+                    })
+                    .collect()
+            });
+
+        let (width, height) = (self.canvas.width, self.canvas.height);
+        let mut renderer = self.offscreen(Canvas::new(width, height, background), mask.ctm);
+        let resources = Dictionary::new();
+        let form = XObject {
+            name: None,
+            id: entry.as_reference(),
+            stream,
+            resources: &resources,
+        };
+        let mut interpreter = Interpreter::new(doc, Dictionary::new(), mask.ctm);
+        interpreter.run_form(&form, &mut renderer);
+
+        let values = renderer
+            .canvas
+            .pixels
+            .iter()
+            .map(|&[r, g, b, a]| {
+                let value = if luminosity { lum([r, g, b]) } else { a };
+                match &transfer {
+                    Some(table) => table[(value.clamp(0.0, 1.0) * 255.0).round() as usize],
+                    None => value,
+                }
+            })
+            .collect();
+        Some(values)
+    }
+
+    /// What the current color paints with, if it can be painted. Patterns are
+    /// positioned by their matrix in the current pattern space.
+    fn paint(&mut self, space: &ColorSpace, color: &Color) -> Option<Paint> {
+        let ColorSpace::Pattern(base) = space else {
+            return rgb(space, &color.components).map(Paint::Color);
+        };
+        let object = color.pattern_object.clone()?;
+        let pattern = match &self.pattern {
+            Some((cached, pattern)) if same_object(cached, &object) => pattern.clone(),
+            _ => {
+                let pattern = Rc::new(Pattern::load(self.doc, &object)?);
+                self.pattern = Some((object.clone(), pattern.clone()));
+                pattern
+            }
+        };
+        let pattern_space = *self.pattern_spaces.last()?;
+        match &*pattern {
+            Pattern::Shading(shading) => Some(Paint::Shading {
+                shading: shading.shading.clone(),
+                matrix: shading.matrix.multiply(&pattern_space),
+                background: true,
+            }),
+            Pattern::Tiling(tiling) => {
+                // Uncolored patterns are painted with a color of the
+                // underlying color space.
+                let color = match tiling.colored {
+                    true => None,
+                    false => Some(rgb(base.as_deref()?, &color.components)?),
+                };
+                let matrix = tiling.matrix.multiply(&pattern_space);
+                let tile = self.tile(&object, tiling, matrix)?;
+                Some(Paint::Tiling { tile, color })
+            }
+        }
+    }
+
+    fn fill_paint(&mut self, state: &GraphicsState) -> Option<Paint> {
+        self.paint(&state.fill_color_space, &state.fill_color)
+    }
+
+    fn stroke_paint(&mut self, state: &GraphicsState) -> Option<Paint> {
+        self.paint(&state.stroke_color_space, &state.stroke_color)
+    }
+
+    /// Renders the cell of a tiling pattern over its bounding box, at the
+    /// resolution that the pattern matrix gives it on the device.
+    /// See sec. 8.7.3.1 (pg. 212)
+    fn tile(
+        &mut self,
+        object: &Rc<PdfObject>,
+        pattern: &TilingPattern,
+        matrix: Matrix,
+    ) -> Option<Rc<Tile>> {
+        if let Some((cached, cached_matrix, tile)) = &self.tile {
+            if same_object(cached, object) && *cached_matrix == matrix {
+                return Some(tile.clone());
+            }
+        }
+        if self.nesting >= MAX_NESTING {
+            return None;
+        }
+        let [x0, y0, x1, y1] = pattern.bbox;
+        let bbox = [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)];
+        let (width, height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+        let step = [pattern.x_step.abs(), pattern.y_step.abs()];
+        if width <= 0.0 || height <= 0.0 || step[0] == 0.0 || step[1] == 0.0 {
+            return None;
+        }
+        let inverse = matrix.inverse()?;
+        let scale = matrix.expansion().min(MAX_TILE_SIZE / width.max(height));
+        // Canvas rows run from the top of the bounding box.
+        let cell_matrix =
+            Matrix::translate(-bbox[0], -bbox[3]).multiply(&Matrix::scale(scale, -scale));
+        let canvas = Canvas::new(
+            (width * scale).ceil().max(1.0) as usize,
+            (height * scale).ceil().max(1.0) as usize,
+            [0.0; 4],
+        );
+        let mut renderer = self.offscreen(canvas, cell_matrix);
+        let mut interpreter = Interpreter::new(self.doc, pattern.resources.clone(), cell_matrix);
+        interpreter.run(&pattern.content, &mut renderer);
+
+        let tile = Rc::new(Tile {
+            canvas: renderer.canvas,
+            cell_matrix,
+            inverse,
+            bbox,
+            step,
+        });
+        self.tile = Some((object.clone(), matrix, tile.clone()));
+        Some(tile)
+    }
+
+    /// The coverage of the clipping region of the state, or None when nothing
+    /// is clipped.
+    fn clip_mask(&mut self, state: &GraphicsState) -> Option<Rc<Mask>> {
+        if state.clip.is_empty() {
+            return None;
+        }
+        if let Some((clip, mask)) = &self.clip {
+            if *clip == state.clip {
+                return Some(mask.clone());
+            }
+        }
+        let (width, height) = (self.canvas.width, self.canvas.height);
+        let mut paths = state.clip.iter();
+        let first = paths.next()?;
+        let mut mask = rasterize(&first.path.flatten(TOLERANCE), first.rule, width, height);
+        for clip in paths {
+            mask.intersect(&rasterize(
+                &clip.path.flatten(TOLERANCE),
+                clip.rule,
+                width,
+                height,
+            ));
+        }
+        let mask = Rc::new(mask);
+        self.clip = Some((state.clip.clone(), mask.clone()));
+        Some(mask)
+    }
+
+    /// Draws an image over the unit square of user space, sampling the nearest
+    /// image pixel for each device pixel. Stencil masks paint the fill color
+    /// where they are opaque.
+    fn draw_image(&mut self, image: &XObject, state: &GraphicsState) -> Result<(), io::Error> {
+        let Some(inverse) = state.ctm.inverse() else {
+            return Ok(());
+        };
+        let bitmap = decode_image(self.doc, image.stream, Some(image.resources))?;
+        if bitmap.width == 0 || bitmap.height == 0 {
+            return Ok(());
+        }
+        let stencil = image.stream.dict.get("ImageMask").and_then(|m| m.as_bool()) == Some(true)
+            || image.stream.dict.get("IM").and_then(|m| m.as_bool()) == Some(true);
+        let fill = if stencil {
+            match self.fill_paint(state) {
+                Some(paint) => Some(paint),
+                None => return Ok(()),
+            }
+        } else {
+            None
+        };
+
+        let mut square = Path::new();
+        square.rect(0.0, 0.0, 1.0, 1.0);
+        let mut region = rasterize(
+            &square.transform(&state.ctm).flatten(TOLERANCE),
+            FillRule::NonZeroWinding,
+            self.canvas.width,
+            self.canvas.height,
+        );
+        if let Some(clip) = self.clip_mask(state) {
+            region.intersect(&clip);
+        }
+        let alpha = state.fill_alpha as f32;
+        let mode = blend_mode(state);
+        let soft_mask = self.soft_mask(state);
+        let fill_colors = fill.map(|paint| paint.colors(&region));
+        for (i, y) in region.rows().enumerate() {
+            for (j, x) in region.columns().enumerate() {
+                let coverage = region.get(x, y);
+                if coverage <= 0.0 {
+                    continue;
+                }
+                let point = inverse.apply(Point::new(x as f64 + 0.5, y as f64 + 0.5));
+                // Image rows run from the top, where user space y is 1.
+                let column = (point.x * bitmap.width as f64).floor();
+                let row = ((1.0 - point.y) * bitmap.height as f64).floor();
+                let column = column.clamp(0.0, (bitmap.width - 1) as f64) as usize;
+                let row = row.clamp(0.0, (bitmap.height - 1) as f64) as usize;
+                let (mut color, mut opacity) = bitmap_color(&bitmap, column, row);
+                if let Some(fill_colors) = &fill_colors {
+                    let (fill, fill_opacity) = fill_colors[i * region.width + j];
+                    (color, opacity) = (fill, opacity * fill_opacity);
+                }
+                let masked = soft_mask
+                    .as_ref()
+                    .map_or(1.0, |m| m[y * self.canvas.width + x]);
+                self.canvas
+                    .composite(x, y, color, opacity * alpha * masked, coverage, mode);
+            }
+        }
+        Ok(())
+    }
+
+    /// The outline of a glyph in device space, if its font program provides one.
+    fn glyph_outline(&self, glyph: &TextGlyph) -> Option<Path> {
+        let outline = glyph.font.glyph_outline(glyph.code)?;
+        Some(outline.transform(&glyph.font.font_matrix.multiply(&glyph.matrix)))
+    }
+
+    /// Runs the glyph procedure of a Type3 font from its /CharProcs, with glyph
+    /// space mapped to the device through the font matrix. The procedure paints
+    /// with the graphics state in effect where the glyph is shown.
+    /// See sec. 9.6.4 (pg. 304)
+    fn draw_type3_glyph(&mut self, glyph: &TextGlyph, state: &GraphicsState) {
+        if self.nesting >= MAX_NESTING {
+            return;
+        }
+        let font = &glyph.font;
+        let Some(name) = font.glyph_name(glyph.code) else {
+            return;
+        };
+        let doc = self.doc;
+        let Some(PdfObject::Stream(procedure)) = doc
+            .resolve_dict(&font.dict, "CharProcs")
+            .and_then(|procs| procs.get(&name))
+            .map(|procedure| doc.resolve(procedure))
+        else {
+            return;
+        };
+        let Ok(content) = doc.decode_stream(procedure) else {
+            return;
+        };
+        let resources = doc
+            .resolve_dict(&font.dict, "Resources")
+            .cloned()
+            .unwrap_or_default();
+        let ctm = font.font_matrix.multiply(&glyph.matrix);
+        let mut interpreter = Interpreter::new(doc, resources, ctm);
+        *interpreter.state_mut() = GraphicsState {
+            ctm,
+            ..state.clone()
+        };
+        self.nesting += 1;
+        interpreter.run(&content, self);
+        self.nesting -= 1;
+    }
+}
+
+/// The RGB color and opacity of a bitmap pixel.
+fn bitmap_color(bitmap: &Bitmap, x: usize, y: usize) -> ([f32; 3], f32) {
+    let pixel = bitmap.pixel(x, y);
+    let value = |i: usize| pixel[i] as f32 / 255.0;
+    match bitmap.format {
+        PixelFormat::Gray => ([value(0); 3], 1.0),
+        PixelFormat::GrayAlpha => ([value(0); 3], value(1)),
+        PixelFormat::Rgb => ([value(0), value(1), value(2)], 1.0),
+        PixelFormat::Rgba => ([value(0), value(1), value(2)], value(3)),
+    }
+}
+
+fn rgb(space: &ColorSpace, components: &[f64]) -> Option<[f32; 3]> {
+    if matches!(space, ColorSpace::Pattern(_)) {
+        return None;
+    }
+    let [r, g, b] = space.to_rgb(components);
+    Some([r as f32, g as f32, b as f32])
+}
+
+/// The blend mode of the state; modes that are not known blend as Normal.
+fn blend_mode(state: &GraphicsState) -> BlendMode {
+    BlendMode::from_name(&state.blend_mode).unwrap_or_default()
+}
+
+/// Whether two pattern objects are the same, as set by one color operator or
+/// looked up again by another.
+fn same_object(a: &Rc<PdfObject>, b: &Rc<PdfObject>) -> bool {
+    Rc::ptr_eq(a, b) || a == b
+}
+
+impl ContentVisitor for Renderer<'_> {
+    fn path(&mut self, path: &Path, paint: &PathPaint, state: &GraphicsState) {
+        if let Some(rule) = paint.fill {
+            if let Some(fill) = self.fill_paint(state) {
+                let device = path.transform(&state.ctm);
+                self.fill(&device, rule, &fill, state.fill_alpha as f32, state);
+            }
+        }
+        if paint.stroke {
+            if let Some(stroke) = self.stroke_paint(state) {
+                self.stroke(path, &stroke, state.stroke_alpha as f32, state);
+            }
+        }
+    }
+
+    fn glyph(&mut self, glyph: &TextGlyph, state: &GraphicsState) {
+        let mode = state.text.rendering_mode;
+        if glyph.font.font_type == FontType::Type3 {
+            if mode.fills() || mode.strokes() {
+                self.draw_type3_glyph(glyph, state);
+            }
+            return;
+        }
+        let Some(outline) = self.glyph_outline(glyph) else {
+            return;
+        };
+        if let Some(fill) = mode.fills().then(|| self.fill_paint(state)).flatten() {
+            self.fill(
+                &outline,
+                FillRule::NonZeroWinding,
+                &fill,
+                state.fill_alpha as f32,
+                state,
+            );
+        }
+        if let Some(stroke) = mode.strokes().then(|| self.stroke_paint(state)).flatten() {
+            // The outline is in device space, so it is stroked with an identity
+            // CTM and the line width scaled to match.
+            let stroke_state = GraphicsState {
+                ctm: Matrix::identity(),
+                line_width: state.line_width * state.ctm.expansion(),
+                ..state.clone()
+            };
+            self.stroke(&outline, &stroke, state.stroke_alpha as f32, &stroke_state);
+        }
+    }
+
+    fn image(&mut self, image: &XObject, state: &GraphicsState) {
+        // Images that cannot be decoded are left out of the rendering.
+        let _ = self.draw_image(image, state);
+    }
+
+    fn begin_form(&mut self, form: &XObject, state: &GraphicsState) -> bool {
+        self.pattern_spaces.push(state.ctm);
+        let group = transparency_group(self.doc, &form.stream.dict).and_then(|group| {
+            let flag = |key: &str| group.get(key).and_then(PdfObject::as_bool) == Some(true);
+            let (isolated, knockout) = (flag("I"), flag("K"));
+            let alpha = state.fill_alpha as f32;
+            let mode = blend_mode(state);
+            let soft_mask = self.soft_mask(state);
+            // Painting the content in place gives the same result.
+            if !isolated
+                && !knockout
+                && alpha >= 1.0
+                && mode == BlendMode::Normal
+                && soft_mask.is_none()
+            {
+                return None;
+            }
+            let (width, height) = (self.canvas.width, self.canvas.height);
+            let mut canvas = if isolated {
+                Canvas::new(width, height, [0.0; 4])
+            } else {
+                // A non-isolated group starts from its backdrop, and keeps
+                // track of its own alpha to remove the backdrop at the end.
+                Canvas {
+                    group_alpha: Some(vec![0.0; width * height]),
+                    knockout: None,
+                    ..self.canvas.clone()
+                }
+            };
+            if knockout {
+                canvas.knockout = Some(canvas.pixels.clone());
+            }
+            Some(Group {
+                backdrop: std::mem::replace(&mut self.canvas, canvas),
+                alpha,
+                mode,
+                soft_mask,
+            })
+        });
+        self.groups.push(group);
+        true
+    }
+
+    fn end_form(&mut self, _form: &XObject, _state: &GraphicsState) {
+        self.pattern_spaces.pop();
+        let Some(Some(group)) = self.groups.pop() else {
+            return;
+        };
+        let layer = std::mem::replace(&mut self.canvas, group.backdrop);
+        for (i, &[r, g, b, a]) in layer.pixels.iter().enumerate() {
+            let (color, alpha) = match &layer.group_alpha {
+                // The color of the group alone, with the backdrop's
+                // contribution removed. See sec. 11.4.4 (pg. 375)
+                Some(group_alpha) if group_alpha[i] > 0.0 => {
+                    let [r0, g0, b0, a0] = self.canvas.pixels[i];
+                    let k = a0 / group_alpha[i] - a0;
+                    let color = [(r, r0), (g, g0), (b, b0)]
+                        .map(|(c, c0)| (c + (c - c0) * k).clamp(0.0, 1.0));
+                    (color, group_alpha[i])
+                }
+                Some(_) => continue,
+                None => ([r, g, b], a),
+            };
+            let masked = group.soft_mask.as_ref().map_or(1.0, |m| m[i]);
+            let (x, y) = (i % layer.width, i / layer.width);
+            self.canvas
+                .composite(x, y, color, alpha * group.alpha * masked, 1.0, group.mode);
+        }
+    }
+
+    fn shading(&mut self, shading: &PdfObject, state: &GraphicsState) {
+        let Some(shading) = Shading::load(self.doc, shading) else {
+            return;
+        };
+        // The shading covers the clipping region, limited by its bounding box.
+        let (width, height) = (self.canvas.width, self.canvas.height);
+        let mut region = match shading.bbox {
+            Some([x0, y0, x1, y1]) => {
+                let mut bbox = Path::new();
+                bbox.rect(x0, y0, x1 - x0, y1 - y0);
+                let polygons = bbox.transform(&state.ctm).flatten(TOLERANCE);
+                rasterize(&polygons, FillRule::NonZeroWinding, width, height)
+            }
+            None => Mask::full(width, height),
+        };
+        if let Some(clip) = self.clip_mask(state) {
+            region.intersect(&clip);
+        }
+        let paint = Paint::Shading {
+            shading: Rc::new(shading),
+            matrix: state.ctm,
+            background: false,
+        };
+        self.paint_mask(&region, &paint, state.fill_alpha as f32, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::read::objects::parse_dictionary;
+
+    /// A document with one page of the given size and content.
+    pub(crate) fn document(width: u32, height: u32, content: &str) -> Document {
+        document_with_objects(width, height, content, "")
+    }
+
+    /// A document with one page, and more objects numbered from 5.
+    fn document_with_objects(width: u32, height: u32, content: &str, objects: &str) -> Document {
+        let data = format!(
+            "%PDF-1.7\n\
+             1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+             2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 >> endobj\n\
+             3 0 obj << /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents 4 0 R >> endobj\n\
+             4 0 obj << /Length {} >> stream\n{}\nendstream endobj\n\
+             {}\n\
+             trailer << /Root 1 0 R >>\n%%EOF\n",
+            width,
+            height,
+            content.len(),
+            content,
+            objects
+        );
+        Document::from_bytes(data.into_bytes()).unwrap()
+    }
+
+    fn pixel(bitmap: &Bitmap, x: usize, y: usize) -> [u8; 4] {
+        bitmap.pixel(x, y).try_into().unwrap()
+    }
+
+    #[test]
+    fn test_render_paths() {
+        let doc = document(
+            20,
+            10,
+            "1 0 0 rg 0 0 10 10 re f \
+             q 10 0 10 5 re W n 0 0 1 rg 0 0 20 10 re f Q \
+             0 g 2 w 15 10 m 15 5 l S",
+        );
+        let page = doc.page(0).unwrap();
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (20, 10));
+        // A page too large to render is an error rather than an allocation
+        // failure.
+        let mut huge = page.clone();
+        let size = PdfObject::Real(1e9);
+        let media_box = vec![
+            PdfObject::Integer(0),
+            PdfObject::Integer(0),
+            size.clone(),
+            size,
+        ];
+        huge.dict.set("MediaBox", PdfObject::Array(media_box));
+        assert!(render_page(&doc, &huge, 72.0).is_err());
+        // The top of the page is the first row of the bitmap.
+        assert_eq!(pixel(&bitmap, 5, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 12, 8), [0, 0, 255, 255]);
+        assert_eq!(pixel(&bitmap, 12, 2), [255, 255, 255, 255]);
+        assert_eq!(pixel(&bitmap, 14, 2), [0, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 16, 2), [255, 255, 255, 255]);
+
+        // At twice the resolution, a rotated page swaps its dimensions.
+        let mut page = page;
+        page.dict.set("Rotate", PdfObject::Integer(90));
+        let bitmap = render_page(&doc, &page, 144.0).unwrap();
+        assert_eq!((bitmap.width, bitmap.height), (20, 40));
+        assert_eq!(pixel(&bitmap, 10, 10), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_render_image() {
+        // A 2x1 gray image scaled over the left half of the page, half opaque.
+        let doc = document(
+            4,
+            2,
+            "/GS gs q 2 0 0 2 0 0 cm BI /W 2 /H 1 /BPC 8 /CS /G /F /AHx ID 0080> EI Q",
+        );
+        let mut page = doc.page(0).unwrap();
+        let (_, resources) = parse_dictionary(b"<< /ExtGState << /GS << /ca 0.5 >> >> >>").unwrap();
+        page.dict.set("Resources", PdfObject::Dictionary(resources));
+
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        assert_eq!(pixel(&bitmap, 0, 0), [128, 128, 128, 255]);
+        assert!((191..=192).contains(&pixel(&bitmap, 1, 1)[0]));
+        assert_eq!(pixel(&bitmap, 3, 1), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_shading() {
+        // An axial shading from red to blue across the top half, and the same
+        // shading as a pattern filling the bottom half, shifted right by 10.
+        let doc = document(
+            20,
+            10,
+            "q 0 5 20 5 re W n /Sh sh Q /Pattern cs /P scn 0 0 20 5 re f",
+        );
+        let mut page = doc.page(0).unwrap();
+        let shading = "<< /ShadingType 2 /ColorSpace /DeviceRGB /Coords [0 0 20 0] \
+             /Function << /FunctionType 2 /Domain [0 1] /C0 [1 0 0] /C1 [0 0 1] /N 1 >> >>";
+        let resources = format!(
+            "<< /Shading << /Sh {} >> \
+             /Pattern << /P << /PatternType 2 /Matrix [1 0 0 1 10 0] /Shading {} >> >> >>",
+            shading, shading
+        );
+        let (_, resources) = parse_dictionary(resources.as_bytes()).unwrap();
+        page.dict.set("Resources", PdfObject::Dictionary(resources));
+
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        let top_left = pixel(&bitmap, 0, 0);
+        let top_middle = pixel(&bitmap, 10, 0);
+        let top_right = pixel(&bitmap, 19, 0);
+        assert!(top_left[0] > 240 && top_left[2] < 15);
+        assert!((120..136).contains(&top_middle[0]) && (120..136).contains(&top_middle[2]));
+        assert!(top_right[0] < 15 && top_right[2] > 240);
+        // The pattern does not extend the shading: left of its start nothing
+        // is painted.
+        assert_eq!(pixel(&bitmap, 5, 8), [255, 255, 255, 255]);
+        assert_eq!(pixel(&bitmap, 10, 8), top_left);
+    }
+
+    #[test]
+    fn test_render_tiling() {
+        // A colored pattern with a red square in each 4 by 4 cell, and an
+        // uncolored pattern painted green.
+        let doc = document_with_objects(
+            20,
+            10,
+            "/Pattern cs /P scn 0 0 10 10 re f \
+             /CS cs 0 1 0 /U scn 10 0 10 10 re f",
+            "5 0 obj << /PatternType 1 /PaintType 1 /TilingType 1 /BBox [0 0 4 4] \
+             /XStep 4 /YStep 4 /Resources << >> /Length 21 >> stream\n\
+             1 0 0 rg 0 0 2 2 re f\nendstream endobj\n\
+             6 0 obj << /PatternType 1 /PaintType 2 /TilingType 1 /BBox [0 0 4 4] \
+             /XStep 4 /YStep 4 /Resources << >> /Length 12 >> stream\n\
+             0 0 2 2 re f\nendstream endobj",
+        );
+        let mut page = doc.page(0).unwrap();
+        let (_, resources) = parse_dictionary(
+            b"<< /Pattern << /P 5 0 R /U 6 0 R >> /ColorSpace << /CS [/Pattern /DeviceRGB] >> >>",
+        )
+        .unwrap();
+        page.dict.set("Resources", PdfObject::Dictionary(resources));
+
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        // The bottom row of the page is the last row of the bitmap.
+        assert_eq!(pixel(&bitmap, 1, 9), [255, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 5, 9), [255, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 5, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 3, 7), [255, 255, 255, 255]);
+        assert_eq!(pixel(&bitmap, 13, 9), [0, 255, 0, 255]);
+        assert_eq!(pixel(&bitmap, 15, 7), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_transparency() {
+        let form = |id: u32, group: &str, content: &str| {
+            format!(
+                "{} 0 obj << /Type /XObject /Subtype /Form /BBox [0 0 30 10] \
+                 /Group << /S /Transparency {} >> /Length {} >> stream\n{}\nendstream endobj\n",
+                id,
+                group,
+                content.len(),
+                content
+            )
+        };
+        // Red multiplied with blue, black through a soft mask that covers the
+        // left half of its area, and an isolated group of red and blue painted
+        // at half opacity.
+        let doc = document_with_objects(
+            30,
+            10,
+            "q 1 0 0 rg 0 0 10 10 re f /M gs 0 0 1 rg 0 0 10 10 re f Q \
+             q /SM gs 0 g 10 0 10 10 re f Q \
+             q /H gs /G Do Q",
+            &[
+                form(5, "/CS /DeviceGray", "1 g 10 0 5 10 re f"),
+                form(
+                    6,
+                    "/I true",
+                    "1 0 0 rg 20 0 10 10 re f 0 0 1 rg 25 0 5 10 re f",
+                ),
+            ]
+            .concat(),
+        );
+        let mut page = doc.page(0).unwrap();
+        let (_, resources) = parse_dictionary(
+            b"<< /ExtGState << /M << /BM /Multiply >> /H << /ca 0.5 >> \
+              /SM << /SMask << /S /Luminosity /G 5 0 R >> >> >> \
+              /XObject << /G 6 0 R >> >>",
+        )
+        .unwrap();
+        page.dict.set("Resources", PdfObject::Dictionary(resources));
+
+        let bitmap = render_page(&doc, &page, 72.0).unwrap();
+        assert_eq!(pixel(&bitmap, 5, 5), [0, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 12, 5), [0, 0, 0, 255]);
+        assert_eq!(pixel(&bitmap, 17, 5), [255, 255, 255, 255]);
+        // Within the group, blue covers red; the group as a whole is then
+        // half transparent.
+        assert_eq!(pixel(&bitmap, 22, 5), [255, 128, 128, 255]);
+        assert_eq!(pixel(&bitmap, 27, 5), [128, 128, 255, 255]);
+    }
+
+    #[test]
+    fn test_render_file() -> Result<(), io::Error> {
+        let doc = Document::load("HelloWorld.pdf")?;
+        let page = doc.page(0).unwrap();
+        let bitmap = render_page(&doc, &page, 36.0)?;
+        assert_eq!((bitmap.width, bitmap.height), (306, 396));
+        assert!(bitmap.to_png().starts_with(b"\x89PNG"));
+        // The text is drawn from the embedded font.
+        assert!(bitmap.pixels.chunks(4).any(|pixel| pixel[0] < 128));
+        Ok(())
+    }
+}
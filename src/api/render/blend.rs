@@ -0,0 +1,211 @@
+///
+/// How the color of an object is mixed with the backdrop it is painted over.
+/// See sec. 11.3.5, Table 134 (pg. 362) and Table 135 (pg. 364)
+///
+/// Separable modes mix each color component on its own; the non-separable
+/// modes (Hue, Saturation, Color and Luminosity) mix hue, saturation and
+/// luminosity instead.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    /// The blend mode of a /BM name. /Compatible is an old name for Normal.
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        Some(match name {
+            "Normal" | "Compatible" => BlendMode::Normal,
+            "Multiply" => BlendMode::Multiply,
+            "Screen" => BlendMode::Screen,
+            "Overlay" => BlendMode::Overlay,
+            "Darken" => BlendMode::Darken,
+            "Lighten" => BlendMode::Lighten,
+            "ColorDodge" => BlendMode::ColorDodge,
+            "ColorBurn" => BlendMode::ColorBurn,
+            "HardLight" => BlendMode::HardLight,
+            "SoftLight" => BlendMode::SoftLight,
+            "Difference" => BlendMode::Difference,
+            "Exclusion" => BlendMode::Exclusion,
+            "Hue" => BlendMode::Hue,
+            "Saturation" => BlendMode::Saturation,
+            "Color" => BlendMode::Color,
+            "Luminosity" => BlendMode::Luminosity,
+            _ => return None,
+        })
+    }
+
+    /// B(Cb, Cs): the mixed color of a backdrop and source color.
+    pub fn blend(self, backdrop: [f32; 3], source: [f32; 3]) -> [f32; 3] {
+        let separable = |f: fn(f32, f32) -> f32| [0, 1, 2].map(|i| f(backdrop[i], source[i]));
+        match self {
+            BlendMode::Normal => source,
+            BlendMode::Multiply => separable(|b, s| b * s),
+            BlendMode::Screen => separable(screen),
+            BlendMode::Overlay => separable(|b, s| hard_light(s, b)),
+            BlendMode::Darken => separable(f32::min),
+            BlendMode::Lighten => separable(f32::max),
+            BlendMode::ColorDodge => separable(|b, s| {
+                if b == 0.0 {
+                    0.0
+                } else if s >= 1.0 {
+                    1.0
+                } else {
+                    (b / (1.0 - s)).min(1.0)
+                }
+            }),
+            BlendMode::ColorBurn => separable(|b, s| {
+                if b >= 1.0 {
+                    1.0
+                } else if s <= 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - b) / s).min(1.0)
+                }
+            }),
+            BlendMode::HardLight => separable(hard_light),
+            BlendMode::SoftLight => separable(|b, s| {
+                if s <= 0.5 {
+                    b - (1.0 - 2.0 * s) * b * (1.0 - b)
+                } else {
+                    let d = if b <= 0.25 {
+                        ((16.0 * b - 12.0) * b + 4.0) * b
+                    } else {
+                        b.sqrt()
+                    };
+                    b + (2.0 * s - 1.0) * (d - b)
+                }
+            }),
+            BlendMode::Difference => separable(|b, s| (b - s).abs()),
+            BlendMode::Exclusion => separable(|b, s| b + s - 2.0 * b * s),
+            BlendMode::Hue => set_lum(set_sat(source, sat(backdrop)), lum(backdrop)),
+            BlendMode::Saturation => set_lum(set_sat(backdrop, sat(source)), lum(backdrop)),
+            BlendMode::Color => set_lum(source, lum(backdrop)),
+            BlendMode::Luminosity => set_lum(backdrop, lum(source)),
+        }
+    }
+}
+
+/// Paints a color over a pixel, an RGB color with alpha, using the basic
+/// compositing formula. See sec. 11.3.6 (pg. 369)
+pub fn composite(pixel: &mut [f32; 4], color: [f32; 3], alpha: f32, mode: BlendMode) {
+    if alpha <= 0.0 {
+        return;
+    }
+    let backdrop_alpha = pixel[3];
+    let result = backdrop_alpha + alpha - backdrop_alpha * alpha;
+    let t = alpha / result;
+    let backdrop = [pixel[0], pixel[1], pixel[2]];
+    let blended = mode.blend(backdrop, color);
+    for i in 0..3 {
+        let source = (1.0 - backdrop_alpha) * color[i] + backdrop_alpha * blended[i];
+        pixel[i] += (source - pixel[i]) * t;
+    }
+    pixel[3] = result;
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    b + s - b * s
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        b * 2.0 * s
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+
+/// The luminosity of a color, as the non-separable blend modes define it.
+/// See sec. 11.3.5.3 (pg. 366)
+pub fn lum(c: [f32; 3]) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut c = c;
+    if n < 0.0 {
+        c = c.map(|v| l + (v - l) * l / (l - n));
+    }
+    if x > 1.0 {
+        c = c.map(|v| l + (v - l) * (1.0 - l) / (x - l));
+    }
+    c
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color(c.map(|v| v + d))
+}
+
+fn sat(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+/// Gives a color the saturation s, keeping the order of its components.
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let mut order = [0, 1, 2];
+    order.sort_by(|&i, &j| c[i].total_cmp(&c[j]));
+    let [min, mid, max] = order;
+    let mut result = [0.0; 3];
+    if c[max] > c[min] {
+        result[mid] = (c[mid] - c[min]) * s / (c[max] - c[min]);
+        result[max] = s;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close<const N: usize>(a: [f32; N], b: [f32; N]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_blend() {
+        let (b, s) = ([0.2, 0.5, 1.0], [0.5, 0.8, 0.0]);
+        assert_close(BlendMode::Multiply.blend(b, s), [0.1, 0.4, 0.0]);
+        assert_close(BlendMode::Darken.blend(b, s), [0.2, 0.5, 0.0]);
+        assert_close(BlendMode::Difference.blend(b, s), [0.3, 0.3, 1.0]);
+        // Luminosity keeps the hue of the backdrop at the source's luminosity.
+        let result = BlendMode::Luminosity.blend(b, s);
+        assert!((lum(result) - lum(s)).abs() < 1e-5);
+        let gray = BlendMode::Color.blend([0.5; 3], s);
+        assert!((lum(gray) - 0.5).abs() < 1e-5);
+
+        // Over an opaque backdrop, Multiply at half opacity.
+        let mut pixel = [0.2, 0.5, 1.0, 1.0];
+        composite(&mut pixel, s, 0.5, BlendMode::Multiply);
+        assert_close(pixel, [0.15, 0.45, 0.5, 1.0]);
+        // Over a transparent backdrop, the blend mode has no effect.
+        let mut pixel = [0.0; 4];
+        composite(&mut pixel, s, 0.5, BlendMode::Multiply);
+        assert_close(pixel, [0.5, 0.8, 0.0, 0.5]);
+    }
+}
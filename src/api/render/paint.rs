@@ -0,0 +1,275 @@
+use std::rc::Rc;
+
+use crate::api::{
+    function::eval_functions,
+    geometry::{Matrix, Point},
+    pattern::{Shading, ShadingKind, Vertex},
+};
+
+use super::{raster::Mask, Canvas};
+
+/// Axial and radial shadings are sampled at this many evenly spaced parametric
+/// values; each pixel takes the color of the nearest sample.
+const SHADING_SAMPLES: usize = 1024;
+
+/// Tiling patterns whose bounding box overlaps more copies of the cell than
+/// this, in either direction, only paint the nearest ones.
+const MAX_OVERLAP: i64 = 4;
+
+///
+/// What a filled or stroked area is painted with: a single color, or colors
+/// varying over the device as given by a pattern or shading.
+///
+#[derive(Debug, Clone)]
+pub enum Paint {
+    Color([f32; 3]),
+    /// A shading, with the matrix mapping shading space to the device. The
+    /// shading's background is painted only by shading patterns, not by "sh".
+    /// See sec. 8.7.4.3 (pg. 218)
+    Shading {
+        shading: Rc<Shading>,
+        matrix: Matrix,
+        background: bool,
+    },
+    /// A tiling pattern, with the color of uncolored patterns.
+    Tiling {
+        tile: Rc<Tile>,
+        color: Option<[f32; 3]>,
+    },
+}
+
+///
+/// The cell of a tiling pattern rendered once, at the resolution of the device,
+/// and repeated by sampling. See sec. 8.7.3.1 (pg. 212)
+///
+#[derive(Debug, Clone)]
+pub struct Tile {
+    /// The cell over the pattern's bounding box, transparent where nothing is
+    /// painted.
+    pub canvas: Canvas,
+    /// Maps pattern space to pixels of the canvas.
+    pub cell_matrix: Matrix,
+    /// Maps the device to pattern space.
+    pub inverse: Matrix,
+    /// The bounding box of the cell, with the lower left corner first.
+    pub bbox: [f64; 4],
+    /// The distances between copies of the cell, in pattern space.
+    pub step: [f64; 2],
+}
+
+impl Paint {
+    /// The color and opacity of each pixel of the mask's rectangle, row by row.
+    /// Pixels that the mask does not cover are left transparent.
+    pub fn colors(&self, mask: &Mask) -> Vec<([f32; 3], f32)> {
+        let mut colors = vec![([0.0; 3], 0.0); mask.width * mask.height];
+        match self {
+            Paint::Color(color) => {
+                for (i, (x, y)) in pixels(mask).enumerate() {
+                    if mask.get(x, y) > 0.0 {
+                        colors[i] = (*color, 1.0);
+                    }
+                }
+            }
+            Paint::Shading {
+                shading,
+                matrix,
+                background,
+            } => shade(shading, matrix, *background, mask, &mut colors),
+            Paint::Tiling { tile, color } => {
+                for (i, (x, y)) in pixels(mask).enumerate() {
+                    if mask.get(x, y) > 0.0 {
+                        let (sample, alpha) = tile.sample(center(x, y));
+                        colors[i] = (color.unwrap_or(sample), alpha);
+                    }
+                }
+            }
+        }
+        colors
+    }
+}
+
+impl Tile {
+    /// The color and opacity of the copies of the cell at a device point,
+    /// painted over each other.
+    fn sample(&self, point: Point) -> ([f32; 3], f32) {
+        let point = self.inverse.apply(point);
+        let [x0, y0, x1, y1] = self.bbox;
+        let [x_step, y_step] = self.step;
+        // The copies i, j whose bounding box, offset by i × XStep and
+        // j × YStep, contains the point.
+        let copies = |p: f64, low: f64, high: f64, step: f64| {
+            let first = ((p - high) / step).ceil() as i64;
+            let last = ((p - low) / step).floor() as i64;
+            first.max(last - MAX_OVERLAP + 1)..=last
+        };
+        let (mut color, mut alpha) = ([0.0f32; 3], 0.0f32);
+        for j in copies(point.y, y0, y1, y_step) {
+            for i in copies(point.x, x0, x1, x_step) {
+                let offset = Point::new(point.x - i as f64 * x_step, point.y - j as f64 * y_step);
+                let cell = self.cell_matrix.apply(offset);
+                let (width, height) = (self.canvas.width, self.canvas.height);
+                if width == 0 || height == 0 {
+                    continue;
+                }
+                let column = (cell.x.floor().max(0.0) as usize).min(width - 1);
+                let row = (cell.y.floor().max(0.0) as usize).min(height - 1);
+                let [r, g, b, a] = self.canvas.pixels[row * width + column];
+                if a <= 0.0 {
+                    continue;
+                }
+                // Source-over, as the canvas composites.
+                let result = alpha + a - alpha * a;
+                let t = a / result;
+                for (channel, value) in color.iter_mut().zip([r, g, b]) {
+                    *channel += (value - *channel) * t;
+                }
+                alpha = result;
+            }
+        }
+        (color, alpha)
+    }
+}
+
+/// The device pixels of a mask's rectangle, row by row.
+fn pixels(mask: &Mask) -> impl Iterator<Item = (usize, usize)> + '_ {
+    mask.rows()
+        .flat_map(move |y| mask.columns().map(move |x| (x, y)))
+}
+
+fn center(x: usize, y: usize) -> Point {
+    Point::new(x as f64 + 0.5, y as f64 + 0.5)
+}
+
+/// Fills in the colors of a shading over the pixels covered by the mask.
+fn shade(
+    shading: &Shading,
+    matrix: &Matrix,
+    background: bool,
+    mask: &Mask,
+    colors: &mut [([f32; 3], f32)],
+) {
+    let Some(inverse) = matrix.inverse() else {
+        return;
+    };
+    let to_rgb = |components: &[f64]| {
+        let [r, g, b] = shading.color_space.to_rgb(components);
+        [r as f32, g as f32, b as f32]
+    };
+    let in_bbox = |point: Point| match shading.bbox {
+        Some([x0, y0, x1, y1]) => {
+            (x0.min(x1)..=x0.max(x1)).contains(&point.x)
+                && (y0.min(y1)..=y0.max(y1)).contains(&point.y)
+        }
+        None => true,
+    };
+
+    match &shading.kind {
+        ShadingKind::Mesh(triangles) => {
+            for triangle in triangles {
+                shade_triangle(shading, triangle, matrix, mask, colors, &to_rgb);
+            }
+        }
+        ShadingKind::Function { .. } => {
+            for (i, (x, y)) in pixels(mask).enumerate() {
+                let point = inverse.apply(center(x, y));
+                if mask.get(x, y) > 0.0 && in_bbox(point) {
+                    if let Some(components) = shading.color_at(point) {
+                        colors[i] = (to_rgb(&components), 1.0);
+                    }
+                }
+            }
+        }
+        ShadingKind::Axial { domain, .. } | ShadingKind::Radial { domain, .. } => {
+            let Some(functions) = shading.functions.as_deref() else {
+                return;
+            };
+            let [t0, t1] = *domain;
+            let last = (SHADING_SAMPLES - 1) as f64;
+            let samples: Vec<[f32; 3]> = (0..SHADING_SAMPLES)
+                .map(|i| {
+                    to_rgb(&eval_functions(
+                        functions,
+                        &[t0 + (t1 - t0) * i as f64 / last],
+                    ))
+                })
+                .collect();
+            for (i, (x, y)) in pixels(mask).enumerate() {
+                let point = inverse.apply(center(x, y));
+                if mask.get(x, y) <= 0.0 || !in_bbox(point) {
+                    continue;
+                }
+                if let Some(t) = shading.parameter_at(point) {
+                    let s = if t1 == t0 { 0.0 } else { (t - t0) / (t1 - t0) };
+                    let index = (s * last).round().clamp(0.0, last) as usize;
+                    colors[i] = (samples[index], 1.0);
+                }
+            }
+        }
+    }
+
+    if let (true, Some(components)) = (background, &shading.background) {
+        let color = to_rgb(components);
+        for (i, (x, y)) in pixels(mask).enumerate() {
+            if colors[i].1 <= 0.0 && mask.get(x, y) > 0.0 && in_bbox(inverse.apply(center(x, y))) {
+                colors[i] = (color, 1.0);
+            }
+        }
+    }
+}
+
+/// Paints the pixels whose centers lie in a triangle of a mesh, interpolating
+/// the colors of its vertices (Gouraud shading). See sec. 8.7.4.5.5 (pg. 225)
+fn shade_triangle(
+    shading: &Shading,
+    triangle: &[Vertex; 3],
+    matrix: &Matrix,
+    mask: &Mask,
+    colors: &mut [([f32; 3], f32)],
+    to_rgb: &impl Fn(&[f64]) -> [f32; 3],
+) {
+    let [a, b, c] = triangle.clone().map(|v| matrix.apply(v.point));
+    let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if area == 0.0 {
+        return;
+    }
+    let bound = |f: fn(f64, f64) -> f64, p: fn(&Point) -> f64| f(f(p(&a), p(&b)), p(&c));
+    let clamp = |v: f64, low: usize, high: usize| (v.max(low as f64) as usize).min(high);
+    let right = mask.x + mask.width;
+    let bottom = mask.y + mask.height;
+    let (x0, x1) = (
+        clamp(bound(f64::min, |p| p.x).floor(), mask.x, right),
+        clamp(bound(f64::max, |p| p.x).ceil(), mask.x, right),
+    );
+    let (y0, y1) = (
+        clamp(bound(f64::min, |p| p.y).floor(), mask.y, bottom),
+        clamp(bound(f64::max, |p| p.y).ceil(), mask.y, bottom),
+    );
+
+    let components = triangle[0].color.len();
+    let mut values = vec![0.0; components];
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if mask.get(x, y) <= 0.0 {
+                continue;
+            }
+            let p = center(x, y);
+            // Barycentric coordinates of the pixel center.
+            let wa = ((b.x - p.x) * (c.y - p.y) - (c.x - p.x) * (b.y - p.y)) / area;
+            let wb = ((c.x - p.x) * (a.y - p.y) - (a.x - p.x) * (c.y - p.y)) / area;
+            let wc = 1.0 - wa - wb;
+            if wa < -1e-9 || wb < -1e-9 || wc < -1e-9 {
+                continue;
+            }
+            for (k, value) in values.iter_mut().enumerate() {
+                let color = |v: &Vertex| v.color.get(k).copied().unwrap_or(0.0);
+                *value =
+                    wa * color(&triangle[0]) + wb * color(&triangle[1]) + wc * color(&triangle[2]);
+            }
+            let color = match &shading.functions {
+                Some(functions) => to_rgb(&eval_functions(functions, &values)),
+                None => to_rgb(&values),
+            };
+            colors[(y - mask.y) * mask.width + (x - mask.x)] = (color, 1.0);
+        }
+    }
+}
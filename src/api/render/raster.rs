@@ -0,0 +1,233 @@
+use crate::api::geometry::{FillRule, Point, Polyline};
+
+/// Each row of pixels is sampled at this many evenly spaced heights. Along a
+/// row, coverage is computed exactly from where the edges cross it.
+const SUBSAMPLES: usize = 16;
+
+///
+/// The fraction of each pixel covered by a shape, for the pixels of a rectangle
+/// within the device. Pixels outside of the rectangle are not covered.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub coverage: Vec<f32>,
+}
+
+impl Mask {
+    pub fn empty() -> Mask {
+        Mask {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+            coverage: vec![],
+        }
+    }
+
+    /// A mask covering every pixel of a device.
+    pub fn full(width: usize, height: usize) -> Mask {
+        Mask {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            coverage: vec![1.0; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        if x < self.x || y < self.y || x >= self.x + self.width || y >= self.y + self.height {
+            return 0.0;
+        }
+        self.coverage[(y - self.y) * self.width + (x - self.x)]
+    }
+
+    /// Multiplies the coverage by that of another mask, as clipping paths
+    /// intersect.
+    pub fn intersect(&mut self, other: &Mask) {
+        for row in 0..self.height {
+            for column in 0..self.width {
+                let value = other.get(self.x + column, self.y + row);
+                self.coverage[row * self.width + column] *= value;
+            }
+        }
+    }
+
+    /// The rows and columns of the mask, as device pixel ranges.
+    pub fn rows(&self) -> std::ops::Range<usize> {
+        self.y..self.y + self.height
+    }
+
+    pub fn columns(&self) -> std::ops::Range<usize> {
+        self.x..self.x + self.width
+    }
+}
+
+/// A line segment of a polygon, going down (increasing y) and recording its
+/// original direction for the nonzero winding rule.
+struct Edge {
+    x0: f64,
+    y0: f64,
+    y1: f64,
+    slope: f64,
+    winding: i32,
+}
+
+/// Computes the anti-aliased coverage of the polygons within a device of the
+/// given size. Polygons are implicitly closed, and the fill rule decides which
+/// regions are inside when they overlap. See sec. 8.5.3.3 (pg. 159)
+pub fn rasterize(polygons: &[Polyline], rule: FillRule, width: usize, height: usize) -> Mask {
+    let mut edges = vec![];
+    let (mut min, mut max) = (
+        Point::new(f64::MAX, f64::MAX),
+        Point::new(f64::MIN, f64::MIN),
+    );
+    for polygon in polygons {
+        let n = polygon.points.len();
+        for i in 0..n {
+            let (a, b) = (polygon.points[i], polygon.points[(i + 1) % n]);
+            if !(a.x.is_finite() && a.y.is_finite() && b.x.is_finite() && b.y.is_finite()) {
+                continue;
+            }
+            min = Point::new(min.x.min(a.x), min.y.min(a.y));
+            max = Point::new(max.x.max(a.x), max.y.max(a.y));
+            if a.y == b.y {
+                continue;
+            }
+            let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+            edges.push(Edge {
+                x0: top.x,
+                y0: top.y,
+                y1: bottom.y,
+                slope: (bottom.x - top.x) / (bottom.y - top.y),
+                winding,
+            });
+        }
+    }
+    if edges.is_empty() {
+        return Mask::empty();
+    }
+
+    let x_start = min.x.floor().clamp(0.0, width as f64) as usize;
+    let x_end = max.x.ceil().clamp(0.0, width as f64) as usize;
+    let y_start = min.y.floor().clamp(0.0, height as f64) as usize;
+    let y_end = max.y.ceil().clamp(0.0, height as f64) as usize;
+    let mut mask = Mask {
+        x: x_start,
+        y: y_start,
+        width: x_end - x_start,
+        height: y_end - y_start,
+        coverage: vec![0.0; (x_end - x_start) * (y_end - y_start)],
+    };
+    if mask.width == 0 || mask.height == 0 {
+        return mask;
+    }
+
+    edges.sort_by(|a, b| a.y0.total_cmp(&b.y0));
+    let mut next_edge = 0;
+    let mut active: Vec<usize> = vec![];
+    let mut crossings: Vec<(f64, i32)> = vec![];
+    // Coverage of partially covered pixels, and differences accumulated for runs
+    // of fully covered pixels.
+    let mut partial = vec![0.0f32; mask.width + 1];
+    let mut runs = vec![0.0f32; mask.width + 1];
+    let weight = 1.0 / SUBSAMPLES as f32;
+
+    for row in 0..mask.height {
+        partial.fill(0.0);
+        runs.fill(0.0);
+        for sample in 0..SUBSAMPLES {
+            let y = (y_start + row) as f64 + (sample as f64 + 0.5) / SUBSAMPLES as f64;
+            while next_edge < edges.len() && edges[next_edge].y0 <= y {
+                active.push(next_edge);
+                next_edge += 1;
+            }
+            active.retain(|&i| edges[i].y1 > y);
+
+            crossings.clear();
+            for &i in &active {
+                let edge = &edges[i];
+                if edge.y0 <= y {
+                    crossings.push((edge.x0 + (y - edge.y0) * edge.slope, edge.winding));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut winding = 0;
+            for pair in crossings.windows(2) {
+                winding += pair[0].1;
+                let inside = match rule {
+                    FillRule::NonZeroWinding => winding != 0,
+                    FillRule::EvenOdd => winding % 2 != 0,
+                };
+                if inside {
+                    let from = pair[0].0 - x_start as f64;
+                    let to = pair[1].0 - x_start as f64;
+                    add_span(&mut partial, &mut runs, from, to, weight);
+                }
+            }
+        }
+
+        let coverage = &mut mask.coverage[row * mask.width..(row + 1) * mask.width];
+        let mut run = 0.0;
+        for (column, value) in coverage.iter_mut().enumerate() {
+            run += runs[column];
+            *value = (partial[column] + run).clamp(0.0, 1.0);
+        }
+    }
+    mask
+}
+
+/// Adds the coverage of a horizontal span of one sample row, with fractional
+/// coverage for the pixels at its ends.
+fn add_span(partial: &mut [f32], runs: &mut [f32], from: f64, to: f64, weight: f32) {
+    let width = (partial.len() - 1) as f64;
+    let (from, to) = (from.clamp(0.0, width), to.clamp(0.0, width));
+    if to <= from {
+        return;
+    }
+    let (first, last) = (from.floor() as usize, to.floor() as usize);
+    if first == last {
+        partial[first] += (to - from) as f32 * weight;
+        return;
+    }
+    partial[first] += (first as f64 + 1.0 - from) as f32 * weight;
+    runs[first + 1] += weight;
+    runs[last] -= weight;
+    partial[last] += (to - last as f64) as f32 * weight;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::geometry::Path;
+
+    #[test]
+    fn test_rasterize() {
+        // A 4x4 square from (1, 1), with a 2x2 hole in the middle.
+        let mut path = Path::new();
+        path.rect(1.0, 1.0, 4.0, 4.0);
+        path.rect(2.0, 2.0, 2.0, 2.0);
+        let polygons = path.flatten(0.1);
+
+        let mask = rasterize(&polygons, FillRule::EvenOdd, 8, 8);
+        assert_eq!((mask.x, mask.y, mask.width, mask.height), (1, 1, 4, 4));
+        assert_eq!(mask.get(1, 1), 1.0);
+        assert_eq!(mask.get(2, 2), 0.0);
+        assert_eq!(mask.get(0, 0), 0.0);
+
+        // Both rectangles run the same way, so the hole is filled.
+        let mask = rasterize(&polygons, FillRule::NonZeroWinding, 8, 8);
+        assert_eq!(mask.get(2, 2), 1.0);
+
+        // Half a pixel is covered at the edges of a shifted rectangle.
+        let mut path = Path::new();
+        path.rect(0.5, 0.0, 2.0, 1.0);
+        let mask = rasterize(&path.flatten(0.1), FillRule::NonZeroWinding, 8, 8);
+        assert_eq!(mask.coverage, vec![0.5, 1.0, 0.5]);
+    }
+}
@@ -0,0 +1,374 @@
+use std::f64::consts::PI;
+
+use crate::api::{
+    content::state::{DashPattern, LineCap, LineJoin},
+    geometry::{Point, Polyline},
+};
+
+///
+/// The graphics state parameters that control how a path is stroked.
+/// See sec. 8.4.3 (pg. 143)
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f64,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub miter_limit: f64,
+    pub dash: DashPattern,
+}
+
+/// A dashed path is split into at most this many dashes, and the rest of it
+/// is stroked as a solid line.
+const MAX_DASHES: usize = 100_000;
+
+/// Converts stroked polylines into polygons that, filled with the nonzero
+/// winding rule, cover the stroke: a rectangle for each segment, plus the caps
+/// and joins. Every polygon runs counterclockwise so that overlapping parts
+/// add up instead of cancelling out. The tolerance bounds the error of round
+/// caps and joins. See sec. 8.5.3.2 (pg. 158)
+pub fn stroke(polylines: &[Polyline], style: &StrokeStyle, tolerance: f64) -> Vec<Polyline> {
+    let mut stroker = Stroker {
+        style,
+        half: style.width / 2.0,
+        tolerance,
+        polygons: vec![],
+    };
+    let mut budget = MAX_DASHES;
+    for polyline in polylines {
+        let points = dedup(&polyline.points);
+        if style.dash.array.iter().sum::<f64>() > 0.0 {
+            let mut closed = points.clone();
+            if polyline.closed && closed.len() > 1 {
+                closed.push(closed[0]);
+            }
+            if let Some(dashes) = dashes(&closed, &style.dash, budget) {
+                budget -= dashes.len();
+                for dash in dashes {
+                    stroker.polyline(&dash, false);
+                }
+                continue;
+            }
+            budget = 0;
+        }
+        stroker.polyline(&points, polyline.closed);
+    }
+    stroker.polygons
+}
+
+/// Drops points equal to the one before them, which have no direction.
+fn dedup(points: &[Point]) -> Vec<Point> {
+    let mut result: Vec<Point> = Vec::with_capacity(points.len());
+    for &point in points {
+        if result.last() != Some(&point) {
+            result.push(point);
+        }
+    }
+    result
+}
+
+/// Splits a polyline into the pieces drawn by a dash pattern, or returns None
+/// if there are more than `limit` of them. See sec. 8.4.3.6 (pg. 146)
+fn dashes(points: &[Point], dash: &DashPattern, limit: usize) -> Option<Vec<Vec<Point>>> {
+    let lengths: Vec<f64> = dash.array.iter().map(|l| l.max(0.0)).collect();
+    let period: f64 = lengths.iter().sum();
+    // The pattern repeats, so the phase only matters modulo its total length
+    // (twice that for an odd number of elements, where dashes become gaps).
+    let cycle = if lengths.len() % 2 == 1 {
+        2.0 * period
+    } else {
+        period
+    };
+    let mut phase = dash.phase.rem_euclid(cycle);
+    let mut index = 0;
+    let mut on = true;
+    while phase >= lengths[index % lengths.len()] {
+        phase -= lengths[index % lengths.len()];
+        index += 1;
+        on = !on;
+    }
+    let mut remaining = lengths[index % lengths.len()] - phase;
+
+    let mut pieces = vec![];
+    let mut current = if on { vec![points[0]] } else { vec![] };
+    for pair in points.windows(2) {
+        let (mut start, end) = (pair[0], pair[1]);
+        let mut length = distance(start, end);
+        while length > remaining {
+            let t = remaining / length;
+            let split = Point::new(
+                start.x + (end.x - start.x) * t,
+                start.y + (end.y - start.y) * t,
+            );
+            if on {
+                current.push(split);
+                pieces.push(std::mem::take(&mut current));
+                if pieces.len() > limit {
+                    return None;
+                }
+            } else {
+                current = vec![split];
+            }
+            on = !on;
+            length -= remaining;
+            start = split;
+            index += 1;
+            remaining = lengths[index % lengths.len()];
+        }
+        remaining -= length;
+        if on {
+            current.push(end);
+        }
+    }
+    if on && !current.is_empty() {
+        pieces.push(current);
+    }
+    (pieces.len() <= limit).then_some(pieces)
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt()
+}
+
+/// The unit vector perpendicular to a segment, to its left.
+fn normal(a: Point, b: Point) -> Point {
+    let length = distance(a, b);
+    Point::new(-(b.y - a.y) / length, (b.x - a.x) / length)
+}
+
+fn offset(point: Point, direction: Point, distance: f64) -> Point {
+    Point::new(
+        point.x + direction.x * distance,
+        point.y + direction.y * distance,
+    )
+}
+
+struct Stroker<'a> {
+    style: &'a StrokeStyle,
+    half: f64,
+    tolerance: f64,
+    polygons: Vec<Polyline>,
+}
+
+impl Stroker<'_> {
+    fn polyline(&mut self, points: &[Point], closed: bool) {
+        if points.len() == 1 {
+            // A zero-length subpath only has its caps drawn.
+            let point = points[0];
+            match self.style.cap {
+                LineCap::Round => self.circle(point),
+                LineCap::ProjectingSquare => {
+                    let h = self.half;
+                    self.polygon(vec![
+                        Point::new(point.x - h, point.y - h),
+                        Point::new(point.x + h, point.y - h),
+                        Point::new(point.x + h, point.y + h),
+                        Point::new(point.x - h, point.y + h),
+                    ]);
+                }
+                LineCap::Butt => {}
+            }
+            return;
+        }
+        let mut points = points.to_vec();
+        if closed && points.len() > 2 {
+            if points.first() != points.last() {
+                points.push(points[0]);
+            }
+        } else if closed {
+            // A closed subpath of two points is a segment drawn there and back.
+            points.push(points[0]);
+        }
+        let closed = closed && points.len() > 3;
+        let last = points.len() - 1;
+
+        for i in 0..last {
+            let (mut a, mut b) = (points[i], points[i + 1]);
+            if !closed && self.style.cap == LineCap::ProjectingSquare {
+                let length = distance(a, b);
+                let direction = Point::new((b.x - a.x) / length, (b.y - a.y) / length);
+                if i == 0 {
+                    a = offset(a, direction, -self.half);
+                }
+                if i + 1 == last {
+                    b = offset(b, direction, self.half);
+                }
+            }
+            let n = normal(points[i], points[i + 1]);
+            self.polygon(vec![
+                offset(a, n, self.half),
+                offset(a, n, -self.half),
+                offset(b, n, -self.half),
+                offset(b, n, self.half),
+            ]);
+        }
+
+        for i in 1..last {
+            self.join(points[i - 1], points[i], points[i + 1]);
+        }
+        if closed {
+            self.join(points[last - 1], points[0], points[1]);
+        } else if self.style.cap == LineCap::Round {
+            self.circle(points[0]);
+            self.circle(points[last]);
+        }
+    }
+
+    /// Fills the gap on the outer side of the corner between two segments.
+    fn join(&mut self, a: Point, vertex: Point, b: Point) {
+        let (n1, n2) = (normal(a, vertex), normal(vertex, b));
+        let cross = (vertex.x - a.x) * (b.y - vertex.y) - (vertex.y - a.y) * (b.x - vertex.x);
+        if cross.abs() < 1e-12 && n1.x * n2.x + n1.y * n2.y > 0.0 {
+            return;
+        }
+        // Turning left leaves the gap on the right side.
+        let sign = if cross > 0.0 { -1.0 } else { 1.0 };
+        let (o1, o2) = (
+            Point::new(n1.x * sign, n1.y * sign),
+            Point::new(n2.x * sign, n2.y * sign),
+        );
+        match self.style.join {
+            LineJoin::Round => self.circle(vertex),
+            LineJoin::Miter => {
+                let sum = Point::new(o1.x + o2.x, o1.y + o2.y);
+                let length = (sum.x * sum.x + sum.y * sum.y).sqrt();
+                // The ratio of the miter length to the line width.
+                if length > 1e-12 && 2.0 / length <= self.style.miter_limit {
+                    let scale = self.half / (1.0 + o1.x * o2.x + o1.y * o2.y);
+                    self.polygon(vec![
+                        vertex,
+                        offset(vertex, o1, self.half),
+                        offset(vertex, sum, scale),
+                        offset(vertex, o2, self.half),
+                    ]);
+                } else {
+                    self.bevel(vertex, o1, o2);
+                }
+            }
+            LineJoin::Bevel => self.bevel(vertex, o1, o2),
+        }
+    }
+
+    fn bevel(&mut self, vertex: Point, o1: Point, o2: Point) {
+        self.polygon(vec![
+            vertex,
+            offset(vertex, o1, self.half),
+            offset(vertex, o2, self.half),
+        ]);
+    }
+
+    fn circle(&mut self, center: Point) {
+        let radius = self.half;
+        let steps = if radius > self.tolerance {
+            (PI / (1.0 - self.tolerance / radius).acos()).ceil()
+        } else {
+            8.0
+        };
+        let steps = steps.clamp(8.0, 256.0) as usize;
+        let points = (0..steps)
+            .map(|i| {
+                let angle = 2.0 * PI * i as f64 / steps as f64;
+                Point::new(
+                    center.x + radius * angle.cos(),
+                    center.y + radius * angle.sin(),
+                )
+            })
+            .collect();
+        self.polygon(points);
+    }
+
+    fn polygon(&mut self, points: Vec<Point>) {
+        let mut polygon = Polyline {
+            points,
+            closed: true,
+        };
+        if polygon.signed_area() < 0.0 {
+            polygon.points.reverse();
+        }
+        self.polygons.push(polygon);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        geometry::{FillRule, Path},
+        render::raster::rasterize,
+    };
+
+    fn style(cap: LineCap, join: LineJoin, dash: Vec<f64>) -> StrokeStyle {
+        StrokeStyle {
+            width: 2.0,
+            cap,
+            join,
+            miter_limit: 10.0,
+            dash: DashPattern {
+                array: dash,
+                phase: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_stroke() {
+        // An L shape: the miter join fills the outer corner, which the bevel
+        // join cuts off.
+        let mut path = Path::new();
+        path.move_to(2.0, 8.0);
+        path.line_to(8.0, 8.0);
+        path.line_to(8.0, 2.0);
+        let polylines = path.flatten(0.1);
+
+        let miter = stroke(
+            &polylines,
+            &style(LineCap::Butt, LineJoin::Miter, vec![]),
+            0.1,
+        );
+        let mask = rasterize(&miter, FillRule::NonZeroWinding, 12, 12);
+        assert_eq!(mask.get(8, 8), 1.0);
+        assert_eq!(mask.get(7, 7), 1.0);
+        assert_eq!(mask.get(1, 7), 0.0);
+
+        let bevel = stroke(
+            &polylines,
+            &style(LineCap::Butt, LineJoin::Bevel, vec![]),
+            0.1,
+        );
+        let mask = rasterize(&bevel, FillRule::NonZeroWinding, 12, 12);
+        assert_eq!(mask.get(8, 8), 0.5);
+
+        // Projecting caps extend the ends by half the line width.
+        let square = stroke(
+            &polylines,
+            &style(LineCap::ProjectingSquare, LineJoin::Miter, vec![]),
+            0.1,
+        );
+        let mask = rasterize(&square, FillRule::NonZeroWinding, 12, 12);
+        assert_eq!(mask.get(1, 7), 1.0);
+    }
+
+    #[test]
+    fn test_dashes() {
+        let points = [Point::new(0.0, 0.0), Point::new(10.0, 0.0)];
+        let dash = DashPattern {
+            array: vec![3.0, 1.0],
+            phase: 2.0,
+        };
+        let pieces = dashes(&points, &dash, 3).unwrap();
+        let starts: Vec<f64> = pieces.iter().map(|p| p[0].x).collect();
+        let ends: Vec<f64> = pieces.iter().map(|p| p.last().unwrap().x).collect();
+        assert_eq!(starts, vec![0.0, 2.0, 6.0]);
+        assert_eq!(ends, vec![1.0, 5.0, 9.0]);
+        assert!(dashes(&points, &dash, 2).is_none());
+
+        // Past the limit, a path is stroked solid: one rectangle for its
+        // segment instead of a million dashes.
+        let long = Polyline {
+            points: vec![Point::new(0.0, 0.0), Point::new(1e6, 0.0)],
+            closed: false,
+        };
+        let dash = style(LineCap::Butt, LineJoin::Miter, vec![0.5, 0.5]);
+        assert_eq!(stroke(&[long], &dash, 0.1).len(), 1);
+    }
+}
@@ -1,15 +1,20 @@
+use std::collections::BTreeMap;
+
+use crate::api::objects::{Dictionary, ObjectId, PdfObject};
+
 ///
 /// The Basic PDF struct, containing the follow core elements:
 ///
 /// "A basic conforming PDF file shall be constructed of the following four elements:
 /// - A one-line header identifying the version of the PDF specification to which the
-///     PDF file conforms
+///   PDF file conforms
 /// - A body containing the objects that make up the document contained in the PDF file
 /// - A cross-reference table containing information about the indirect objects in the
-///     PDF file
+///   PDF file
 /// - A trailer giving the location of the cross-reference table and of certain special
-///     objects within the␍body of the PDF file."
-///     See sec. 7.5.1 of the ISO 3200-2:2020 spec (pg. 53)
+///   objects within the␍body of the PDF file."
+///
+/// See sec. 7.5.1 of the ISO 3200-2:2020 spec (pg. 53)
 ///
 /// ______________
 /// | __________  |
@@ -31,10 +36,10 @@
 ///
 #[derive(Debug)]
 pub struct PDF {
-    header: Header,
-    body: Body,
-    cross_ref_table: CrossRefTable,
-    trailer: Trailer,
+    pub header: Header,
+    pub body: Body,
+    pub cross_ref_table: CrossRefTable,
+    pub trailer: Trailer,
 }
 
 ///
@@ -64,11 +69,12 @@ pub struct Header {
 /// Beginning with PDF 1.5, the body can also contain object streams, each of which contains
 /// a sequence of indirect objects; see 7.5.7, "Object streams"."
 ///
-/// Accordingly, the Body struct contains a Vec of Objects.
+/// Accordingly, the Body struct contains the indirect objects, keyed by their
+/// object number and generation number.
 ///
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Body {
-    objects: Vec<Object>,
+    pub objects: BTreeMap<ObjectId, PdfObject>,
 }
 
 ///
@@ -76,7 +82,7 @@ pub struct Body {
 /// file by specifying the byte offset of it's start. the table begins with a line
 /// containing the keyword "xref" and subsections following.
 ///
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct CrossRefTable {
     pub subsections: Vec<Subsection>,
 }
@@ -97,9 +103,13 @@ pub struct CrossRefTable {
 /// where they are required or optional, see Table 15, sec. 7.5.5 (pg. 58)
 /// of the PDF ISO 32000-2:2020 spec.
 ///
-#[derive(Debug)]
+/// The Trailer struct holds the trailer dictionary and the byte offset given
+/// after the "startxref" keyword.
+///
+#[derive(Debug, Default)]
 pub struct Trailer {
-    entries: Vec<(TrailerKey, String)>,
+    pub entries: Dictionary,
+    pub start_xref: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -188,9 +198,15 @@ pub struct Subsection {
 ///
 /// Accordingly, each subsection entry is exactly 20-bytes.
 ///
+/// Cross-reference streams (PDF 1.5) can also locate objects stored in an
+/// object stream, in which case `object_stream` holds the object number of the
+/// stream and `byte_offset` the index of the object within it.
+/// See sec. 7.5.8.3, Table 18 (pg. 67)
+///
 #[derive(Debug)]
 pub struct SubsectionEntry {
-    pub byte_offset: i32,
+    pub byte_offset: u64,
     pub generation_number: i32,
     pub in_use: bool,
+    pub object_stream: Option<i32>,
 }
@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::api::{
+    document::Document,
+    objects::{Dictionary, ObjectId, PdfObject},
+};
+
+/// Trees are followed at most this deep, which bounds the recursion. Cycles,
+/// and kids shared between nodes, are caught by keeping the visited nodes.
+const MAX_DEPTH: usize = 32;
+
+/// Trees written with `add_name_tree` and `add_number_tree` keep at most this
+/// many entries in each leaf.
+const ENTRIES_PER_LEAF: usize = 64;
+
+///
+/// The keys of a tree: strings for name trees, which hold their entries in
+/// /Names, and integers for number trees, which hold them in /Nums.
+///
+trait TreeKey: Ord + Clone {
+    const ENTRIES: &'static str;
+    fn from_object(object: &PdfObject) -> Option<Self>;
+    fn to_object(&self) -> PdfObject;
+}
+
+impl TreeKey for Vec<u8> {
+    const ENTRIES: &'static str = "Names";
+
+    fn from_object(object: &PdfObject) -> Option<Self> {
+        object.as_bytes().map(<[u8]>::to_vec)
+    }
+
+    fn to_object(&self) -> PdfObject {
+        PdfObject::String(self.clone())
+    }
+}
+
+impl TreeKey for i64 {
+    const ENTRIES: &'static str = "Nums";
+
+    fn from_object(object: &PdfObject) -> Option<Self> {
+        object.as_i64()
+    }
+
+    fn to_object(&self) -> PdfObject {
+        PdfObject::Integer(*self)
+    }
+}
+
+impl Document {
+    /// The entries of a name tree, sorted by key. The values are returned as
+    /// stored, and may be references. See sec. 7.9.6
+    pub fn name_tree<'a>(&'a self, root: &'a Dictionary) -> Vec<(Vec<u8>, &'a PdfObject)> {
+        self.tree_entries(root)
+    }
+
+    /// The entries of a number tree, sorted by key. See sec. 7.9.7
+    pub fn number_tree<'a>(&'a self, root: &'a Dictionary) -> Vec<(i64, &'a PdfObject)> {
+        self.tree_entries(root)
+    }
+
+    /// Looks up a key in a name tree, following only the /Kids whose /Limits
+    /// include it, and resolves the value.
+    pub fn find_name<'a>(&'a self, root: &'a Dictionary, key: &[u8]) -> Option<&'a PdfObject> {
+        self.find_in_tree(root, &key.to_vec(), &mut HashSet::new(), 0)
+    }
+
+    /// Looks up a key in a number tree, and resolves the value.
+    pub fn find_number<'a>(&'a self, root: &'a Dictionary, key: i64) -> Option<&'a PdfObject> {
+        self.find_in_tree(root, &key, &mut HashSet::new(), 0)
+    }
+
+    /// Adds the kids of a name tree holding the entries, and returns its root
+    /// node. Entries with the same key replace earlier ones.
+    pub fn add_name_tree(&mut self, entries: Vec<(Vec<u8>, PdfObject)>) -> Dictionary {
+        self.add_tree(entries)
+    }
+
+    /// Adds the kids of a number tree holding the entries, and returns its
+    /// root node.
+    pub fn add_number_tree(&mut self, entries: Vec<(i64, PdfObject)>) -> Dictionary {
+        self.add_tree(entries)
+    }
+
+    fn tree_entries<'a, K: TreeKey>(&'a self, root: &'a Dictionary) -> Vec<(K, &'a PdfObject)> {
+        let mut entries: Vec<(K, &'a PdfObject)> = vec![];
+        let mut visited = HashSet::new();
+        self.collect_tree_entries(root, &mut entries, &mut visited, 0);
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        entries
+    }
+
+    fn collect_tree_entries<'a, K: TreeKey>(
+        &'a self,
+        node: &'a Dictionary,
+        entries: &mut Vec<(K, &'a PdfObject)>,
+        visited: &mut HashSet<ObjectId>,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if let Some(pairs) = self
+            .resolve_key(node, K::ENTRIES)
+            .and_then(PdfObject::as_array)
+        {
+            for pair in pairs.chunks_exact(2) {
+                if let Some(key) = K::from_object(self.resolve(&pair[0])) {
+                    entries.push((key, &pair[1]));
+                }
+            }
+        }
+        let kids = self.resolve_key(node, "Kids").and_then(PdfObject::as_array);
+        for kid in kids.into_iter().flatten() {
+            if kid.as_reference().is_some_and(|id| !visited.insert(id)) {
+                continue;
+            }
+            if let Some(kid) = self.resolve(kid).as_dict() {
+                self.collect_tree_entries(kid, entries, visited, depth + 1);
+            }
+        }
+    }
+
+    fn find_in_tree<'a, K: TreeKey>(
+        &'a self,
+        node: &'a Dictionary,
+        key: &K,
+        visited: &mut HashSet<ObjectId>,
+        depth: usize,
+    ) -> Option<&'a PdfObject> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        if let Some(pairs) = self
+            .resolve_key(node, K::ENTRIES)
+            .and_then(PdfObject::as_array)
+        {
+            return pairs
+                .chunks_exact(2)
+                .find(|pair| K::from_object(self.resolve(&pair[0])).as_ref() == Some(key))
+                .map(|pair| self.resolve(&pair[1]));
+        }
+        let kids = self.resolve_key(node, "Kids")?.as_array()?;
+        for kid in kids {
+            if kid.as_reference().is_some_and(|id| !visited.insert(id)) {
+                continue;
+            }
+            let Some(kid) = self.resolve(kid).as_dict() else {
+                continue;
+            };
+            let limits = kid.get("Limits").and_then(PdfObject::as_array);
+            let within = match limits.map(|limits| limits.as_slice()) {
+                Some([low, high]) => {
+                    K::from_object(low).is_some_and(|low| low <= *key)
+                        && K::from_object(high).is_some_and(|high| *key <= high)
+                }
+                _ => true,
+            };
+            if let Some(value) = within
+                .then(|| self.find_in_tree(kid, key, visited, depth + 1))
+                .flatten()
+            {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Sorts the entries into leaves of at most `ENTRIES_PER_LEAF` entries.
+    /// A tree that fits in one leaf is held by the root itself; otherwise the
+    /// root's /Kids are the leaves, as indirect objects with /Limits.
+    fn add_tree<K: TreeKey>(&mut self, entries: Vec<(K, PdfObject)>) -> Dictionary {
+        let sorted: BTreeMap<K, PdfObject> = entries.into_iter().collect();
+        let sorted: Vec<(K, PdfObject)> = sorted.into_iter().collect();
+
+        let leaf = |entries: &[(K, PdfObject)]| {
+            let pairs = entries
+                .iter()
+                .flat_map(|(key, value)| [key.to_object(), value.clone()])
+                .collect();
+            let mut leaf = Dictionary::new();
+            leaf.set(K::ENTRIES, PdfObject::Array(pairs));
+            leaf
+        };
+        if sorted.len() <= ENTRIES_PER_LEAF {
+            return leaf(&sorted);
+        }
+        let mut kids = vec![];
+        for chunk in sorted.chunks(ENTRIES_PER_LEAF) {
+            let mut node = leaf(chunk);
+            let limits = vec![chunk[0].0.to_object(), chunk[chunk.len() - 1].0.to_object()];
+            node.set("Limits", PdfObject::Array(limits));
+            kids.push(PdfObject::Reference(
+                self.add_object(PdfObject::Dictionary(node)),
+            ));
+        }
+        let mut root = Dictionary::new();
+        root.set("Kids", PdfObject::Array(kids));
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::builder::DocumentBuilder;
+
+    #[test]
+    fn test_trees() {
+        let mut doc = DocumentBuilder::new().build();
+        let names: Vec<(Vec<u8>, PdfObject)> = (0..150)
+            .rev()
+            .map(|i| (format!("n{:03}", i).into_bytes(), PdfObject::Integer(i)))
+            .collect();
+        let root = doc.add_name_tree(names);
+        assert_eq!(
+            root.get("Kids")
+                .and_then(PdfObject::as_array)
+                .unwrap()
+                .len(),
+            3
+        );
+        let entries = doc.name_tree(&root);
+        assert_eq!(entries.len(), 150);
+        assert_eq!(entries[0], (b"n000".to_vec(), &PdfObject::Integer(0)));
+        assert_eq!(
+            doc.find_name(&root, b"n149"),
+            Some(&PdfObject::Integer(149))
+        );
+        assert_eq!(doc.find_name(&root, b"n064"), Some(&PdfObject::Integer(64)));
+        assert_eq!(doc.find_name(&root, b"x"), None);
+
+        let numbers = vec![
+            (10, PdfObject::Name("b".to_string())),
+            (0, PdfObject::Name("a".to_string())),
+            (10, PdfObject::Name("c".to_string())),
+        ];
+        let root = doc.add_number_tree(numbers);
+        assert!(root.get("Kids").is_none());
+        let entries = doc.number_tree(&root);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            doc.find_number(&root, 10),
+            Some(&PdfObject::Name("c".to_string()))
+        );
+        assert_eq!(doc.find_number(&root, 5), None);
+    }
+
+    #[test]
+    fn test_tree_cycle() {
+        let mut doc = DocumentBuilder::new().build();
+        let id = doc.add_object(PdfObject::Null);
+        let kids = PdfObject::Array(vec![PdfObject::Reference(id), PdfObject::Reference(id)]);
+        let node: Dictionary = [("Kids".to_string(), kids)].into_iter().collect();
+        *doc.get_mut(id).unwrap() = PdfObject::Dictionary(node.clone());
+        assert_eq!(doc.find_name(&node, b"x"), None);
+        assert_eq!(doc.find_number(&node, 1), None);
+        assert!(doc.name_tree(&node).is_empty());
+    }
+}
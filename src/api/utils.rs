@@ -1,5 +1,24 @@
-use nom::{branch::alt, bytes::complete::tag, sequence::tuple, IResult};
-
 pub fn is_pdf_eol(chr: u8) -> bool {
     chr == b'\n' || chr == b'\r'
 }
+
+/// White-space characters as defined in sec. 7.2.3, Table 1 (pg. 22):
+/// NUL, HT, LF, FF, CR and SP.
+pub fn is_pdf_whitespace(chr: u8) -> bool {
+    matches!(chr, 0 | 9 | 10 | 12 | 13 | 32)
+}
+
+/// Delimiter characters as defined in sec. 7.2.3, Table 2 (pg. 23):
+/// ( ) < > [ ] { } / %
+pub fn is_pdf_delimiter(chr: u8) -> bool {
+    matches!(
+        chr,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+/// Regular characters are any characters that are neither white-space nor
+/// delimiters.
+pub fn is_pdf_regular(chr: u8) -> bool {
+    !is_pdf_whitespace(chr) && !is_pdf_delimiter(chr)
+}
@@ -0,0 +1,962 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+};
+
+use bstr::ByteSlice;
+
+use crate::api::{
+    content::Operation,
+    encryption::{random_bytes, Encryption, SecurityHandler},
+    filters::{flate_encode, png_up_encode},
+    objects::{Dictionary, ObjectId, PdfObject, Stream},
+    optimize,
+    read::{self, content::parse_inline_image, merge_subsections},
+    structure::{CrossRefTable, SubsectionEntry, PDF},
+    utils::{is_pdf_eol, is_pdf_regular},
+};
+
+/// The comment written after the header line. Its four bytes above 127 tell
+/// file transfer programs that the file holds binary data. See sec. 7.5.2 (pg. 54)
+const BINARY_MARKER: &[u8] = b"%\xE2\xE3\xCF\xD3\n";
+
+/// Trailer entries that describe the cross-reference section the trailer was
+/// read with, rather than the document. A freshly written table replaces them.
+const XREF_KEYS: [&str; 8] = [
+    "Prev",
+    "XRefStm",
+    "Type",
+    "W",
+    "Index",
+    "Filter",
+    "DecodeParms",
+    "Length",
+];
+
+/// Object streams written with `WriteOptions::object_streams` hold at most this
+/// many objects each.
+const OBJECTS_PER_STREAM: usize = 100;
+
+///
+/// Options for writing a PDF file. The defaults write a classic
+/// cross-reference table and keep every object as it is.
+///
+/// Cross-reference streams and object streams need PDF 1.5; the header
+/// version is raised to 1.5 when either is used.
+///
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Writes a cross-reference stream instead of a table, compressed with
+    /// FlateDecode and the PNG Up predictor. See sec. 7.5.8 (pg. 65)
+    pub xref_stream: bool,
+    /// Packs the objects other than streams into compressed object streams.
+    /// This implies `xref_stream`. See sec. 7.5.7 (pg. 63)
+    pub object_streams: bool,
+    /// Compresses the data of streams without a filter with FlateDecode.
+    pub compress_streams: bool,
+    /// Encrypts the strings and streams with the standard security handler,
+    /// adding /Encrypt and /ID to the trailer. The header version is raised
+    /// to the one the algorithm needs. See sec. 7.6 (pg. 77)
+    pub encryption: Option<Encryption>,
+    /// Leaves out unreachable objects, merges identical ones and renumbers
+    /// the rest, as `optimize::optimize` does, without changing the document.
+    pub optimize: bool,
+}
+
+impl WriteOptions {
+    /// Every option that makes the file smaller.
+    pub fn compressed() -> Self {
+        WriteOptions {
+            xref_stream: true,
+            object_streams: true,
+            compress_streams: true,
+            encryption: None,
+            optimize: false,
+        }
+    }
+}
+
+pub fn write_file(pdf: &PDF, path: &str) -> Result<(), io::Error> {
+    let mut data = vec![];
+    write_pdf(pdf, &mut data)?;
+    fs::write(path, data)
+}
+
+/// Writes the four parts of a PDF file: the header with a binary marker, every
+/// object of the body, a cross-reference table computed for the offsets they
+/// were written at, and the trailer followed by "startxref" and "%%EOF".
+///
+/// The trailer dictionary is copied from the one read, with /Size set for the
+/// new table and the entries that pointed at earlier sections left out.
+pub fn write_pdf<W: Write>(pdf: &PDF, writer: W) -> Result<(), io::Error> {
+    write_pdf_with(pdf, writer, &WriteOptions::default())
+}
+
+/// Like `write_pdf`, with the cross-reference section and objects written as
+/// the options ask.
+pub fn write_pdf_with<W: Write>(
+    pdf: &PDF,
+    writer: W,
+    options: &WriteOptions,
+) -> Result<(), io::Error> {
+    let xref_stream = options.xref_stream || options.object_streams;
+    let mut writer = CountingWriter::new(writer);
+    let mut version = pdf.header.version.as_str();
+    let required = [
+        xref_stream.then_some("1.5"),
+        options.encryption.as_ref().map(|e| e.algorithm.version()),
+    ];
+    for required in required.into_iter().flatten() {
+        if version
+            .parse::<f64>()
+            .is_ok_and(|v| v < required.parse().unwrap())
+        {
+            version = required;
+        }
+    }
+    writeln!(writer, "%PDF-{}", version)?;
+    writer.write_all(BINARY_MARKER)?;
+
+    let optimized;
+    let (body, entries) = match options.optimize {
+        true => {
+            optimized = optimize::optimized(&pdf.body.objects, &pdf.trailer.entries);
+            (&optimized.0, &optimized.1)
+        }
+        false => (&pdf.body.objects, &pdf.trailer.entries),
+    };
+
+    // Only the latest generation of each object number is in use. The object
+    // streams and cross-reference streams the file was read with are replaced.
+    let mut objects: BTreeMap<i32, (i32, &PdfObject)> = BTreeMap::new();
+    for (&(number, generation), object) in body {
+        if number > 0 && !is_structure_stream(object) {
+            objects.insert(number, (generation, object));
+        }
+    }
+    let mut size = objects.keys().last().map_or(1, |last| *last as usize + 1);
+    let mut trailer = trailer_entries(entries);
+
+    // The encryption dictionary takes the next free object number, and the key
+    // of AES-128 depends on the file identifier, which is created if missing.
+    let security = options
+        .encryption
+        .as_ref()
+        .map(|encryption| -> Result<_, io::Error> {
+            let file_id = match trailer.get("ID").and_then(PdfObject::as_array) {
+                Some(id) => id
+                    .first()
+                    .and_then(PdfObject::as_bytes)
+                    .unwrap_or_default()
+                    .to_vec(),
+                None => {
+                    let id = random_bytes(16)?;
+                    let pair = vec![PdfObject::String(id.clone()), PdfObject::String(id.clone())];
+                    trailer.set("ID", PdfObject::Array(pair));
+                    id
+                }
+            };
+            let (handler, dict) = SecurityHandler::create(encryption, &file_id)?;
+            let id = (object_number(size)?, 0);
+            size += 1;
+            trailer.set("Encrypt", PdfObject::Reference(id));
+            Ok((handler, id, PdfObject::Dictionary(dict)))
+        })
+        .transpose()?;
+    let handler = security.as_ref().map(|(handler, _, _)| handler);
+
+    // Streams, objects of later generations and the encryption dictionary
+    // cannot be stored in object streams.
+    let encrypt = entries.get("Encrypt").and_then(PdfObject::as_reference);
+    let (packed, unpacked): (Vec<_>, Vec<_>) =
+        objects
+            .iter()
+            .partition(|(&number, &(generation, object))| {
+                options.object_streams
+                    && generation == 0
+                    && !matches!(object, PdfObject::Stream(_))
+                    && encrypt != Some((number, generation))
+            });
+
+    let mut entries = BTreeMap::new();
+    for (&number, &(generation, object)) in unpacked {
+        entries.insert(number as usize, in_use(writer.position, generation));
+        let compressed = match object {
+            PdfObject::Stream(stream) if options.compress_streams => {
+                compress_stream(stream).map(PdfObject::Stream)
+            }
+            _ => None,
+        };
+        let object = compressed.as_ref().unwrap_or(object);
+        let object = encrypt_object(handler, (number, generation), object)?;
+        write_indirect_object(&mut writer, (number, generation), &object)?;
+    }
+    if let Some((_, id, dict)) = &security {
+        entries.insert(id.0 as usize, in_use(writer.position, 0));
+        write_indirect_object(&mut writer, *id, dict)?;
+    }
+    for chunk in packed.chunks(OBJECTS_PER_STREAM) {
+        let number = size;
+        size += 1;
+        let chunk: Vec<(i32, &PdfObject)> = chunk
+            .iter()
+            .map(|(&number, &(_, object))| (number, object))
+            .collect();
+        let stream_number = object_number(number)?;
+        for (index, (packed_number, _)) in chunk.iter().enumerate() {
+            let entry = SubsectionEntry {
+                byte_offset: index as u64,
+                generation_number: 0,
+                in_use: true,
+                object_stream: Some(stream_number),
+            };
+            entries.insert(*packed_number as usize, entry);
+        }
+        entries.insert(number, in_use(writer.position, 0));
+        let stream = PdfObject::Stream(object_stream(&chunk)?);
+        let stream = encrypt_object(handler, (stream_number, 0), &stream)?;
+        write_indirect_object(&mut writer, (stream_number, 0), &stream)?;
+    }
+
+    let free: Vec<(usize, i32)> = (1..size)
+        .filter(|n| !entries.contains_key(n))
+        .map(|n| (n, 0))
+        .collect();
+    link_free_entries(&mut entries, &free);
+
+    trailer.set("Size", PdfObject::Integer(size as i64));
+    write_xref_section(&mut writer, entries, trailer, xref_stream, 0)
+}
+
+fn encrypt_object<'a>(
+    handler: Option<&SecurityHandler>,
+    id: ObjectId,
+    object: &'a PdfObject,
+) -> Result<Cow<'a, PdfObject>, io::Error> {
+    Ok(match handler {
+        Some(handler) => {
+            let mut object = object.clone();
+            handler.encrypt_object(id, &mut object)?;
+            Cow::Owned(object)
+        }
+        None => Cow::Borrowed(object),
+    })
+}
+
+fn is_structure_stream(object: &PdfObject) -> bool {
+    match object {
+        PdfObject::Stream(stream) => stream.dict.has_type("ObjStm") || stream.dict.has_type("XRef"),
+        _ => false,
+    }
+}
+
+/// Appends an incremental update to the bytes a document was read from: the
+/// objects that were added or changed since, a cross-reference section for
+/// them and for the objects that were removed, and a trailer whose /Prev entry
+/// points at the previous section. The original bytes are written unchanged.
+/// The new section is a cross-reference stream if the previous one is.
+/// See sec. 7.5.6 (pg. 60)
+pub fn write_incremental<W: Write>(pdf: &PDF, original: &[u8], writer: W) -> Result<(), io::Error> {
+    let header_offset = original.find(b"%PDF-").unwrap_or(0);
+    let previous_xref = read::find_start_xref(original).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the original file has no cross-reference section to append to",
+        )
+    })?;
+    // The update would have to be encrypted like the rest of the file.
+    let encrypted = read::read_xref_section(original, header_offset + previous_xref)
+        .is_some_and(|(_, trailer)| trailer.contains_key("Encrypt"));
+    if encrypted {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "incremental updates of encrypted files are not supported",
+        ));
+    }
+    let previous = read::read_pdf(original)?;
+    let xref_stream = original
+        .get(header_offset + previous_xref..)
+        .is_some_and(|section| !read::objects::skip_whitespace(section).starts_with(b"xref"));
+
+    let mut writer = CountingWriter::new(writer);
+    writer.write_all(original)?;
+    if !original.last().copied().is_some_and(is_pdf_eol) {
+        writer.write_all(b"\n")?;
+    }
+
+    let mut entries = BTreeMap::new();
+    for (&id, object) in &pdf.body.objects {
+        if id.0 > 0 && previous.body.objects.get(&id) != Some(object) {
+            let offset = writer.position - header_offset;
+            entries.insert(id.0 as usize, in_use(offset, id.1));
+            write_indirect_object(&mut writer, id, object)?;
+        }
+    }
+    // A removed object's number may be reused with the next generation.
+    let removed: Vec<(usize, i32)> = previous
+        .body
+        .objects
+        .keys()
+        .filter(|id| !pdf.body.objects.contains_key(id) && !entries.contains_key(&(id.0 as usize)))
+        .map(|&(number, generation)| (number as usize, generation + 1))
+        .collect();
+    if !removed.is_empty() {
+        link_free_entries(&mut entries, &removed);
+    }
+
+    let size = [
+        previous.trailer.entries.get_i64("Size").unwrap_or(0),
+        pdf.trailer.entries.get_i64("Size").unwrap_or(0),
+        entries.keys().last().map_or(0, |last| *last as i64 + 1),
+    ]
+    .into_iter()
+    .max()
+    .unwrap_or(0);
+
+    let mut trailer = trailer_entries(&pdf.trailer.entries);
+    trailer.set("Size", PdfObject::Integer(size));
+    trailer.set("Prev", PdfObject::Integer(previous_xref as i64));
+    write_xref_section(&mut writer, entries, trailer, xref_stream, header_offset)
+}
+
+/// Writes the cross-reference section for the given entries, followed by
+/// "startxref" and "%%EOF". A cross-reference stream takes the object number
+/// given by the trailer's /Size, and holds the trailer entries itself.
+fn write_xref_section<W: Write>(
+    writer: &mut CountingWriter<W>,
+    mut entries: BTreeMap<usize, SubsectionEntry>,
+    mut trailer: Dictionary,
+    as_stream: bool,
+    header_offset: usize,
+) -> Result<(), io::Error> {
+    let start_xref = writer.position - header_offset;
+    if as_stream {
+        let number = trailer.get_i64("Size").unwrap_or(0).max(1);
+        let id = (object_number(number)?, 0);
+        trailer.set("Size", PdfObject::Integer(number + 1));
+        entries.insert(id.0 as usize, in_use(start_xref, 0));
+        let stream = xref_stream(&merge_subsections(entries), trailer);
+        write_indirect_object(writer, id, &PdfObject::Stream(stream))?;
+    } else {
+        write_cross_ref_table(writer, &merge_subsections(entries))?;
+        writer.write_all(b"trailer\n")?;
+        write_dictionary(writer, &trailer)?;
+        writer.write_all(b"\n")?;
+    }
+    write!(writer, "startxref\n{}\n%%EOF\n", start_xref)?;
+    writer.flush()
+}
+
+/// Object numbers are limited to those that an `ObjectId` can hold.
+fn object_number<T: TryInto<i32> + Copy + std::fmt::Display>(number: T) -> Result<i32, io::Error> {
+    number.try_into().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("object number {} is too large", number),
+        )
+    })
+}
+
+fn in_use(offset: usize, generation: i32) -> SubsectionEntry {
+    SubsectionEntry {
+        byte_offset: offset as u64,
+        generation_number: generation,
+        in_use: true,
+        object_stream: None,
+    }
+}
+
+/// Adds free entries for the given object numbers and generations, linked into
+/// a list that starts at object 0: the byte offset field of each free entry
+/// holds the number of the next one. See sec. 7.5.4 (pg. 56)
+fn link_free_entries(entries: &mut BTreeMap<usize, SubsectionEntry>, free: &[(usize, i32)]) {
+    let mut numbers: Vec<usize> = free.iter().map(|(number, _)| *number).collect();
+    numbers.sort_unstable();
+    let next = |number: usize| numbers.iter().find(|&&n| n > number).copied().unwrap_or(0);
+    entries.insert(
+        0,
+        SubsectionEntry {
+            byte_offset: next(0) as u64,
+            generation_number: 65535,
+            in_use: false,
+            object_stream: None,
+        },
+    );
+    for &(number, generation) in free {
+        entries.insert(
+            number,
+            SubsectionEntry {
+                byte_offset: next(number) as u64,
+                generation_number: generation,
+                in_use: false,
+                object_stream: None,
+            },
+        );
+    }
+}
+
+/// The trailer entries that describe the document, leaving out the ones that
+/// describe the cross-reference section the trailer was read with.
+fn trailer_entries(entries: &Dictionary) -> Dictionary {
+    entries
+        .iter()
+        .filter(|(key, _)| !XREF_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// A cross-reference stream holding the entries of the table, with a row of
+/// three fields per entry: its type (0 free, 1 in use, 2 in an object stream)
+/// followed by the next free object number and generation, the byte offset and
+/// generation, or the object stream number and index. Each field is as wide as
+/// its largest value needs. See sec. 7.5.8.2 (pg. 66)
+fn xref_stream(table: &CrossRefTable, trailer: Dictionary) -> Stream {
+    let rows: Vec<[u64; 3]> = table
+        .subsections
+        .iter()
+        .flat_map(|subsection| &subsection.entries)
+        .map(|entry| match entry.object_stream {
+            _ if !entry.in_use => [0, entry.byte_offset, entry.generation_number as u64],
+            Some(stream) => [2, stream as u64, entry.byte_offset],
+            None => [1, entry.byte_offset, entry.generation_number as u64],
+        })
+        .collect();
+    let width = |field: usize| {
+        let max = rows.iter().map(|row| row[field]).max().unwrap_or(0);
+        (u64::BITS - max.leading_zeros()).div_ceil(8).max(1) as usize
+    };
+    let widths = [1, width(1), width(2)];
+    let columns: usize = widths.iter().sum();
+
+    let mut data = Vec::with_capacity(rows.len() * columns);
+    for row in &rows {
+        for (value, width) in row.iter().zip(widths) {
+            data.extend_from_slice(&value.to_be_bytes()[8 - width..]);
+        }
+    }
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", PdfObject::Name("XRef".to_string()));
+    for (key, value) in trailer.iter() {
+        dict.set(key.clone(), value.clone());
+    }
+    let integers = |values: Vec<usize>| {
+        PdfObject::Array(
+            values
+                .into_iter()
+                .map(|v| PdfObject::Integer(v as i64))
+                .collect(),
+        )
+    };
+    dict.set("W", integers(widths.to_vec()));
+    let index: Vec<usize> = table
+        .subsections
+        .iter()
+        .flat_map(|subsection| [subsection.object_number, subsection.num_entries])
+        .collect();
+    // The default /Index covers every object from 0 to /Size.
+    let size = usize::try_from(trailer.get_i64("Size").unwrap_or(0)).unwrap_or(0);
+    if index != [0, size] {
+        dict.set("Index", integers(index));
+    }
+    dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+    let mut params = Dictionary::new();
+    params.set("Predictor", PdfObject::Integer(12));
+    params.set("Columns", PdfObject::Integer(columns as i64));
+    dict.set("DecodeParms", PdfObject::Dictionary(params));
+    Stream::new(dict, flate_encode(&png_up_encode(&data, columns)))
+}
+
+/// An object stream holding the given objects. Its data starts with pairs of
+/// object number and offset, relative to /First, followed by the objects.
+/// See sec. 7.5.7 (pg. 63)
+fn object_stream(objects: &[(i32, &PdfObject)]) -> Result<Stream, io::Error> {
+    let mut pairs = vec![];
+    let mut body = vec![];
+    for (number, object) in objects {
+        pairs.push(format!("{} {}", number, body.len()));
+        write_object(&mut body, object)?;
+        body.push(b'\n');
+    }
+    let mut data = pairs.join(" ").into_bytes();
+    data.push(b'\n');
+    let first = data.len();
+    data.extend(body);
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", PdfObject::Name("ObjStm".to_string()));
+    dict.set("N", PdfObject::Integer(objects.len() as i64));
+    dict.set("First", PdfObject::Integer(first as i64));
+    dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+    Ok(Stream::new(dict, flate_encode(&data)))
+}
+
+/// Compresses the data of a stream without a filter with FlateDecode, when that
+/// makes it smaller.
+fn compress_stream(stream: &Stream) -> Option<Stream> {
+    if stream.dict.contains_key("Filter") || stream.dict.contains_key("DecodeParms") {
+        return None;
+    }
+    let data = flate_encode(&stream.data);
+    if data.len() >= stream.data.len() {
+        return None;
+    }
+    let mut dict = stream.dict.clone();
+    dict.set("Filter", PdfObject::Name("FlateDecode".to_string()));
+    Some(Stream::new(dict, data))
+}
+
+/// Writes a cross-reference section, starting with the "xref" keyword, with
+/// each subsection's entries following its first object number and count.
+pub fn write_cross_ref_table<W: Write>(
+    writer: &mut W,
+    table: &CrossRefTable,
+) -> Result<(), io::Error> {
+    writer.write_all(b"xref\n")?;
+    for subsection in &table.subsections {
+        writeln!(
+            writer,
+            "{} {}",
+            subsection.object_number, subsection.num_entries
+        )?;
+        for entry in &subsection.entries {
+            write_xref_entry(writer, entry)?;
+        }
+    }
+    Ok(())
+}
+
+/// Each entry is exactly 20 bytes long, including its two-byte end of line,
+/// so byte offsets are limited to 10 digits.
+fn write_xref_entry<W: Write>(writer: &mut W, entry: &SubsectionEntry) -> Result<(), io::Error> {
+    if entry.byte_offset > 9_999_999_999 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "byte offset {} does not fit a cross-reference table",
+                entry.byte_offset
+            ),
+        ));
+    }
+    let keyword = if entry.in_use { 'n' } else { 'f' };
+    write!(
+        writer,
+        "{:010} {:05} {}\r\n",
+        entry.byte_offset, entry.generation_number, keyword
+    )
+}
+
+/// Writes an indirect object definition:
+///     12 0 obj
+///         . . .
+///     endobj
+pub fn write_indirect_object<W: Write>(
+    writer: &mut W,
+    id: ObjectId,
+    object: &PdfObject,
+) -> Result<(), io::Error> {
+    writeln!(writer, "{} {} obj", id.0, id.1)?;
+    write_object(writer, object)?;
+    writer.write_all(b"\nendobj\n")
+}
+
+/// Writes an object using PDF syntax, such that reading it back gives an equal
+/// object. Dictionary entries whose value is the null object are left out.
+pub fn write_object<W: Write>(writer: &mut W, object: &PdfObject) -> Result<(), io::Error> {
+    match object {
+        PdfObject::Null => writer.write_all(b"null"),
+        PdfObject::Boolean(b) => write!(writer, "{}", b),
+        PdfObject::Integer(i) => write!(writer, "{}", i),
+        PdfObject::Real(r) => writer.write_all(format_real(*r).as_bytes()),
+        PdfObject::String(bytes) => write_string(writer, bytes),
+        PdfObject::Name(name) => write_name(writer, name),
+        PdfObject::Array(items) => {
+            writer.write_all(b"[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b" ")?;
+                }
+                write_object(writer, item)?;
+            }
+            writer.write_all(b"]")
+        }
+        PdfObject::Dictionary(dict) => write_dictionary(writer, dict),
+        PdfObject::Stream(stream) => write_stream(writer, stream),
+        PdfObject::Reference((number, generation)) => {
+            write!(writer, "{} {} R", number, generation)
+        }
+    }
+}
+
+pub fn write_dictionary<W: Write>(writer: &mut W, dict: &Dictionary) -> Result<(), io::Error> {
+    writer.write_all(b"<<")?;
+    let entries = dict.iter().filter(|(_, value)| !value.is_null());
+    for (i, (key, value)) in entries.enumerate() {
+        if i > 0 {
+            writer.write_all(b" ")?;
+        }
+        write_name(writer, key)?;
+        writer.write_all(b" ")?;
+        write_object(writer, value)?;
+    }
+    writer.write_all(b">>")
+}
+
+/// Writes content stream operations, one per line, such that parsing them back
+/// gives equal operations. See sec. 7.8.2 (pg. 112)
+pub fn write_content<W: Write>(writer: &mut W, operations: &[Operation]) -> Result<(), io::Error> {
+    for operation in operations {
+        match (operation.operator.as_str(), operation.operands.as_slice()) {
+            ("BI", [PdfObject::Stream(image)]) => write_inline_image(writer, image)?,
+            _ => {
+                for operand in &operation.operands {
+                    write_object(writer, operand)?;
+                    writer.write_all(b" ")?;
+                }
+                writer.write_all(operation.operator.as_bytes())?;
+            }
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Inline images are written as "BI", the image parameters, "ID", the data and
+/// "EI". Data that would be read back cut short, because it contains "EI"
+/// between white-space, is given its length in an /L entry. See sec. 8.9.7 (pg. 241)
+fn write_inline_image<W: Write>(writer: &mut W, image: &Stream) -> Result<(), io::Error> {
+    let write = |dict: &Dictionary| -> Result<Vec<u8>, io::Error> {
+        let mut output = vec![];
+        for (key, value) in dict.iter() {
+            write_name(&mut output, key)?;
+            output.write_all(b" ")?;
+            write_object(&mut output, value)?;
+            output.write_all(b" ")?;
+        }
+        output.write_all(b"ID ")?;
+        output.write_all(&image.data)?;
+        output.write_all(b"\nEI")?;
+        Ok(output)
+    };
+    let mut output = write(&image.dict)?;
+    let read_back = parse_inline_image(&output).map(|(_, read_back)| read_back.data);
+    if read_back.ok().as_ref() != Some(&image.data) {
+        let mut dict = image.dict.clone();
+        dict.set("L", PdfObject::Integer(image.data.len() as i64));
+        output = write(&dict)?;
+    }
+    writer.write_all(b"BI ")?;
+    writer.write_all(&output)
+}
+
+/// The /Length entry is always written as a direct integer matching the data,
+/// which otherwise would depend on another object being kept in sync.
+fn write_stream<W: Write>(writer: &mut W, stream: &Stream) -> Result<(), io::Error> {
+    let mut dict = stream.dict.clone();
+    dict.set("Length", PdfObject::Integer(stream.data.len() as i64));
+    write_dictionary(writer, &dict)?;
+    writer.write_all(b"\nstream\n")?;
+    writer.write_all(&stream.data)?;
+    writer.write_all(b"\nendstream")
+}
+
+/// Reals are written with a decimal point, so that they are read back as reals,
+/// and without exponent notation, which PDF does not allow. See sec. 7.3.3 (pg. 25)
+fn format_real(real: f64) -> String {
+    if !real.is_finite() {
+        return "0.0".to_string();
+    }
+    let text = real.to_string();
+    if text.contains('.') {
+        text
+    } else {
+        text + ".0"
+    }
+}
+
+/// Strings are written as literal strings. Parentheses and backslashes are
+/// escaped, as is CARRIAGE RETURN, which would otherwise be read as an end of
+/// line. See sec. 7.3.4.2 (pg. 26)
+fn write_string<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<(), io::Error> {
+    let mut escaped = Vec::with_capacity(bytes.len() + 2);
+    escaped.push(b'(');
+    for &byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => escaped.extend([b'\\', byte]),
+            b'\r' => escaped.extend(b"\\r"),
+            _ => escaped.push(byte),
+        }
+    }
+    escaped.push(b')');
+    writer.write_all(&escaped)
+}
+
+/// Names are written with a leading SOLIDUS. Bytes outside the printable ASCII
+/// range, delimiters, white-space and NUMBER SIGN are written as "#xx" escapes.
+/// See sec. 7.3.5 (pg. 28)
+fn write_name<W: Write>(writer: &mut W, name: &str) -> Result<(), io::Error> {
+    let mut escaped = Vec::with_capacity(name.len() + 1);
+    escaped.push(b'/');
+    for &byte in name.as_bytes() {
+        if (b'!'..=b'~').contains(&byte) && byte != b'#' && is_pdf_regular(byte) {
+            escaped.push(byte);
+        } else {
+            escaped.extend(format!("#{:02X}", byte).bytes());
+        }
+    }
+    writer.write_all(&escaped)
+}
+
+/// Keeps track of the number of bytes written, which gives the byte offsets of
+/// objects for the cross-reference table.
+struct CountingWriter<W> {
+    inner: W,
+    position: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, position: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        document::Document,
+        encryption::EncryptionAlgorithm,
+        read::{content::parse_content, cross_ref_table::read_subsection, objects::parse_object},
+    };
+
+    #[test]
+    fn test_write_object() {
+        let input =
+            b"<</Type /Font /Name /A#20B /Widths [1 2.5 -3.0] /Text (a\\(b\\)\\\\) /Next 4 0 R>>";
+        let (_, object) = parse_object(input).unwrap();
+        let mut output = vec![];
+        write_object(&mut output, &object).unwrap();
+        assert_eq!(output, input);
+
+        let (_, read_back) = parse_object(&output).unwrap();
+        assert_eq!(read_back, object);
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        // Offsets past 2 GB are written in full, and those past the 10 digits
+        // of a table entry are rejected.
+        let mut output = vec![];
+        write_xref_entry(&mut output, &in_use(3_000_000_000, 0)).unwrap();
+        assert_eq!(output, b"3000000000 00000 n\r\n");
+        let (_, entry) = read_subsection(&output).unwrap();
+        assert_eq!(entry.byte_offset, 3_000_000_000);
+        assert!(write_xref_entry(&mut vec![], &in_use(10_000_000_000, 0)).is_err());
+
+        // Object numbers that an ObjectId cannot hold are an error.
+        let mut doc = Document::load("HelloWorld.pdf").unwrap();
+        doc.pdf
+            .body
+            .objects
+            .insert((i32::MAX, 0), PdfObject::Integer(1));
+        let error = doc
+            .save_with(&mut vec![], &WriteOptions::compressed())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_write_content() {
+        let input: &[u8] = b"q 1 0 0 1 72.5 -3 cm BT /F1 12 Tf [(a\\) b) -250 <00FF>] TJ ET \
+            /P <</MCID 0>> BDC BI /W 2 /H 1 /CS /G /BPC 8 ID \x00\xFF EI EMC \
+            BI /W 4 /H 1 /CS /G /BPC 8 ID a EI  EI Q";
+        let operations = parse_content(input);
+        let mut output = vec![];
+        write_content(&mut output, &operations).unwrap();
+        assert!(output
+            .starts_with(b"q\n1 0 0 1 72.5 -3 cm\nBT\n/F1 12 Tf\n[(a\\) b) -250 (\0\xFF)] TJ\n"));
+        assert_eq!(parse_content(&output), operations);
+
+        // Image data that reads back as shorter without its length.
+        let image = Stream::new(Dictionary::new(), b"a EI b".to_vec());
+        let operations = vec![Operation::new("BI", vec![PdfObject::Stream(image)])];
+        output.clear();
+        write_content(&mut output, &operations).unwrap();
+        assert_eq!(output, b"BI /L 6 ID a EI b\nEI\n");
+        let image = parse_content(&output)[0].operands[0]
+            .as_stream()
+            .unwrap()
+            .data
+            .clone();
+        assert_eq!(image, b"a EI b");
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), io::Error> {
+        let doc = Document::load("HelloWorld.pdf")?;
+        let mut data = vec![];
+        doc.save(&mut data)?;
+        assert!(data.starts_with(b"%PDF-1.6\n%\xE2\xE3\xCF\xD3\n"));
+        assert!(data.ends_with(b"%%EOF\n"));
+
+        let copy = Document::from_bytes(data.clone())?;
+        assert!(copy.pdf.header.comment);
+        assert_eq!(copy.trailer().get_i64("Size"), Some(14));
+        assert_eq!(copy.pdf.cross_ref_table.subsections[0].num_entries, 14);
+        assert_eq!(
+            copy.pdf.body.objects.keys().collect::<Vec<_>>(),
+            doc.pdf.body.objects.keys().collect::<Vec<_>>()
+        );
+        for (id, object) in &doc.pdf.body.objects {
+            match (object, copy.get(*id).unwrap()) {
+                (PdfObject::Stream(original), PdfObject::Stream(written)) => {
+                    assert_eq!(original.data, written.data);
+                }
+                (original, written) => assert_eq!(original, written),
+            }
+        }
+        assert_eq!(copy.pages().len(), 1);
+        assert_eq!(
+            copy.pages()[0].contents(&copy)?,
+            doc.pages()[0].contents(&doc)?
+        );
+
+        // Writing the copy again gives the same bytes.
+        let mut again = vec![];
+        copy.save(&mut again)?;
+        assert_eq!(again, data);
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_update() -> Result<(), io::Error> {
+        let mut doc = Document::load("HelloWorld.pdf")?;
+        let catalog = doc.get_mut((12, 0)).unwrap();
+        let PdfObject::Dictionary(catalog) = catalog else {
+            panic!("expected the catalog dictionary");
+        };
+        catalog.set("PageMode", PdfObject::Name("UseNone".to_string()));
+        doc.pdf
+            .body
+            .objects
+            .insert((14, 0), PdfObject::String(b"new".to_vec()));
+        doc.pdf.body.objects.remove(&(3, 0));
+
+        let mut data = vec![];
+        doc.save_incremental(&mut data)?;
+        assert!(data.starts_with(&doc.data));
+
+        let update = &data[doc.data.len()..];
+        let (table, _) =
+            read::read_xref_section(&data, read::find_start_xref(&data).unwrap()).unwrap();
+        let numbers: Vec<_> = table.subsections.iter().map(|s| s.object_number).collect();
+        assert_eq!(numbers, vec![0, 3, 12, 14]);
+        assert!(update.ends_with(b"%%EOF\n"));
+
+        let copy = Document::from_bytes(data)?;
+        assert_eq!(copy.trailer().get_i64("Prev"), Some(6833));
+        assert_eq!(copy.trailer().get_i64("Size"), Some(15));
+        assert_eq!(
+            copy.catalog().unwrap().get_name("PageMode"),
+            Some("UseNone")
+        );
+        assert_eq!(copy.get((14, 0)), Some(&PdfObject::String(b"new".to_vec())));
+        assert!(copy.get((3, 0)).is_none());
+        assert_eq!(
+            copy.pages()[0].contents(&copy)?,
+            doc.pages()[0].contents(&doc)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed() -> Result<(), io::Error> {
+        let doc = Document::load("HelloWorld.pdf")?;
+        let mut plain = vec![];
+        doc.save(&mut plain)?;
+        let mut data = vec![];
+        doc.save_with(&mut data, &WriteOptions::compressed())?;
+        assert!(data.len() < plain.len());
+        assert!(data.find(b"\nxref\n").is_none());
+
+        let copy = Document::from_bytes(data)?;
+        let entries = &copy.pdf.cross_ref_table.subsections[0].entries;
+        assert!(entries.iter().any(|entry| entry.object_stream.is_some()));
+        for (id, object) in &doc.pdf.body.objects {
+            match (object, copy.get(*id).unwrap()) {
+                (PdfObject::Stream(original), PdfObject::Stream(written)) => {
+                    assert_eq!(original.decode(), written.decode());
+                }
+                (original, written) => assert_eq!(original, written),
+            }
+        }
+        assert_eq!(
+            copy.pages()[0].contents(&copy)?,
+            doc.pages()[0].contents(&doc)?
+        );
+
+        // Updates to a file with a cross-reference stream end with one too.
+        let mut copy = copy;
+        let number = i32::try_from(copy.trailer().get_i64("Size").unwrap()).unwrap();
+        copy.pdf
+            .body
+            .objects
+            .insert((number, 0), PdfObject::Integer(42));
+        let mut updated = vec![];
+        copy.save_incremental(&mut updated)?;
+        assert!(updated[copy.data.len()..].find(b"/Type /XRef").is_some());
+        let updated = Document::from_bytes(updated)?;
+        assert_eq!(updated.get((number, 0)), Some(&PdfObject::Integer(42)));
+        assert_eq!(updated.pages().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypted() -> Result<(), io::Error> {
+        let doc = Document::load("HelloWorld.pdf")?;
+        let info = doc.resolve_dict(doc.trailer(), "Info").cloned();
+        for algorithm in [EncryptionAlgorithm::Aes128, EncryptionAlgorithm::Aes256] {
+            for compressed in [false, true] {
+                let mut options = match compressed {
+                    true => WriteOptions::compressed(),
+                    false => WriteOptions::default(),
+                };
+                options.encryption = Some(Encryption {
+                    algorithm,
+                    ..Encryption::new("user", "owner")
+                });
+                let mut data = vec![];
+                doc.save_with(&mut data, &options)?;
+                assert!(data.starts_with(format!("%PDF-{}", algorithm.version()).as_bytes()));
+
+                let err = Document::from_bytes(data.clone()).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+                for password in ["user", "owner"] {
+                    let copy = Document::from_bytes_with_password(data.clone(), password)?;
+                    assert!(copy.trailer().get("Encrypt").is_none());
+                    assert_eq!(copy.trailer().get("ID"), doc.trailer().get("ID"));
+                    assert_eq!(copy.resolve_dict(copy.trailer(), "Info").cloned(), info);
+                    assert_eq!(
+                        copy.pages()[0].contents(&copy)?,
+                        doc.pages()[0].contents(&doc)?
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}